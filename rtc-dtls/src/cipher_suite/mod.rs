@@ -42,6 +42,9 @@ pub enum CipherSuiteId {
     Tls_Psk_With_Aes_128_Ccm_8 = 0xc0a8,
     Tls_Psk_With_Aes_128_Gcm_Sha256 = 0x00a8,
 
+    // DTLS 1.3 only
+    Tls_Aes_128_Gcm_Sha256 = 0x1301,
+
     Unsupported,
 }
 
@@ -71,6 +74,7 @@ impl fmt::Display for CipherSuiteId {
             CipherSuiteId::Tls_Psk_With_Aes_128_Gcm_Sha256 => {
                 write!(f, "TLS_PSK_WITH_AES_128_GCM_SHA256")
             }
+            CipherSuiteId::Tls_Aes_128_Gcm_Sha256 => write!(f, "TLS_AES_128_GCM_SHA256"),
             _ => write!(f, "Unsupported CipherSuiteID"),
         }
     }
@@ -95,11 +99,20 @@ impl From<u16> for CipherSuiteId {
             0xc0a8 => CipherSuiteId::Tls_Psk_With_Aes_128_Ccm_8,
             0x00a8 => CipherSuiteId::Tls_Psk_With_Aes_128_Gcm_Sha256,
 
+            0x1301 => CipherSuiteId::Tls_Aes_128_Gcm_Sha256,
+
             _ => CipherSuiteId::Unsupported,
         }
     }
 }
 
+impl CipherSuiteId {
+    // is_dtls13_only returns true for suites that can only be negotiated in DTLS 1.3
+    pub fn is_dtls13_only(&self) -> bool {
+        matches!(self, CipherSuiteId::Tls_Aes_128_Gcm_Sha256)
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum CipherSuiteHash {
     Sha256,
@@ -131,7 +144,7 @@ pub trait CipherSuite {
     ) -> Result<()>;
 
     fn encrypt(&self, pkt_rlh: &RecordLayerHeader, raw: &[u8]) -> Result<Vec<u8>>;
-    fn decrypt(&self, input: &[u8]) -> Result<Vec<u8>>;
+    fn decrypt(&self, pkt_rlh: &RecordLayerHeader, input: &[u8]) -> Result<Vec<u8>>;
 }
 
 // Taken from https://www.iana.org/assignments/tls-parameters/tls-parameters.xml
@@ -1,16 +1,21 @@
 use crate::cipher_suite::*;
-use crate::conn::{DEFAULT_REPLAY_PROTECTION_WINDOW, INITIAL_TICKER_INTERVAL};
+use crate::conn::{COOKIE_LENGTH, DEFAULT_REPLAY_PROTECTION_WINDOW, INITIAL_TICKER_INTERVAL};
 use crate::crypto::*;
 use crate::extension::extension_use_srtp::SrtpProtectionProfile;
+use crate::fragment_buffer::DEFAULT_MAX_FRAGMENT_BUFFER_SIZE;
+use crate::handshake::handshake_random::HandshakeRandom;
 use crate::signature_hash_algorithm::{
     parse_signature_schemes, SignatureHashAlgorithm, SignatureScheme,
 };
+use hmac::{Hmac, Mac};
 use log::warn;
+use rand::Rng;
+use sha2::Sha256;
 use shared::error::*;
 use std::collections::HashMap;
 use std::fmt;
-use std::net::SocketAddr;
-use std::sync::Arc;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 /// Config is used to configure a DTLS client or server.
@@ -21,6 +26,7 @@ pub struct ConfigBuilder {
     cipher_suites: Vec<CipherSuiteId>,
     signature_schemes: Vec<SignatureScheme>,
     srtp_protection_profiles: Vec<SrtpProtectionProfile>,
+    supported_protocols: Vec<String>,
     client_auth: ClientAuthType,
     extended_master_secret: ExtendedMasterSecretType,
     flight_interval: Duration,
@@ -35,6 +41,10 @@ pub struct ConfigBuilder {
     server_name: String,
     mtu: usize,
     replay_protection_window: usize,
+    max_fragment_buffer_size: usize,
+    disable_cookie: bool,
+    support_connection_id: bool,
+    local_connection_id: Option<ConnectionIdGenerator>,
 }
 
 impl Default for ConfigBuilder {
@@ -44,6 +54,7 @@ impl Default for ConfigBuilder {
             cipher_suites: vec![],
             signature_schemes: vec![],
             srtp_protection_profiles: vec![],
+            supported_protocols: vec![],
             client_auth: ClientAuthType::default(),
             extended_master_secret: ExtendedMasterSecretType::default(),
             flight_interval: Duration::default(),
@@ -58,6 +69,10 @@ impl Default for ConfigBuilder {
             server_name: String::default(),
             mtu: 0,
             replay_protection_window: 0,
+            max_fragment_buffer_size: 0,
+            disable_cookie: false,
+            support_connection_id: false,
+            local_connection_id: None,
         }
     }
 }
@@ -95,6 +110,16 @@ impl ConfigBuilder {
         self
     }
 
+    /// supported_protocols are the application protocols negotiated with ALPN, e.g.
+    /// "webrtc" and "c-webrtc", in order of preference
+    /// Clients will offer them and assert that the server selects one of them
+    /// Servers will select the first one offered by the client they support,
+    /// and abort the handshake with a no_application_protocol alert if there is none
+    pub fn with_supported_protocols(mut self, supported_protocols: Vec<String>) -> Self {
+        self.supported_protocols = supported_protocols;
+        self
+    }
+
     /// client_auth determines the server's policy for
     /// TLS Client Authentication. The default is NoClientCert.
     pub fn with_client_auth(mut self, client_auth: ClientAuthType) -> Self {
@@ -216,6 +241,41 @@ impl ConfigBuilder {
         self.replay_protection_window = replay_protection_window;
         self
     }
+
+    /// max_fragment_buffer_size is the maximum number of bytes of handshake fragments
+    /// buffered for reassembly. When it is exceeded, the oldest incomplete handshake
+    /// messages are dropped. (default is 64 KiB)
+    pub fn with_max_fragment_buffer_size(mut self, max_fragment_buffer_size: usize) -> Self {
+        self.max_fragment_buffer_size = max_fragment_buffer_size;
+        self
+    }
+
+    /// disable_cookie makes a server answer the first ClientHello with its ServerHello
+    /// instead of a HelloVerifyRequest, skipping the cookie exchange of RFC 6347 Section
+    /// 4.2.1. It is meant for tests and trusted environments only, since the cookie
+    /// protects servers from amplification with spoofed client addresses.
+    pub fn with_disable_cookie(mut self, disable_cookie: bool) -> Self {
+        self.disable_cookie = disable_cookie;
+        self
+    }
+
+    /// support_connection_id negotiates the connection_id extension of RFC 9146, so that
+    /// records sent to a peer asking for a connection id carry it. Unless local_connection_id
+    /// is also set, the peer is asked for an empty connection id, i.e. to send plain records.
+    pub fn with_support_connection_id(mut self, support_connection_id: bool) -> Self {
+        self.support_connection_id = support_connection_id;
+        self
+    }
+
+    /// local_connection_id generates the connection id the peer is asked to put in its
+    /// records, once per connection. It lets a server associate the records of a client with
+    /// its connection when its address changes, e.g. on a NAT rebinding. All the connection
+    /// ids of an endpoint must have the same length, which is not on the wire. It implies
+    /// support_connection_id.
+    pub fn with_local_connection_id(mut self, local_connection_id: ConnectionIdGenerator) -> Self {
+        self.local_connection_id = Some(local_connection_id);
+        self
+    }
 }
 
 pub(crate) const DEFAULT_MTU: usize = 1228; // bytes
@@ -224,6 +284,19 @@ pub(crate) const DEFAULT_MTU: usize = 1228; // bytes
 /// If the remote provided none it will be nil
 pub(crate) type PskCallback = Arc<dyn (Fn(&[u8]) -> Result<Vec<u8>>) + Send + Sync>;
 
+/// ConnectionIdGenerator returns the connection id of a new connection.
+pub type ConnectionIdGenerator = Arc<dyn (Fn() -> Vec<u8>) + Send + Sync>;
+
+/// random_connection_id_generator returns a ConnectionIdGenerator of random connection ids
+/// of len bytes.
+pub fn random_connection_id_generator(len: usize) -> ConnectionIdGenerator {
+    Arc::new(move || {
+        let mut connection_id = vec![0u8; len];
+        rand::thread_rng().fill(connection_id.as_mut_slice());
+        connection_id
+    })
+}
+
 /// ClientAuthType declares the policy the server will follow for
 /// TLS Client Authentication.
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
@@ -274,6 +347,14 @@ impl ConfigBuilder {
 
         parse_cipher_suites(&self.cipher_suites, self.psk.is_none(), self.psk.is_some())?;
 
+        if self
+            .supported_protocols
+            .iter()
+            .any(|protocol| protocol.is_empty() || protocol.len() > u8::MAX as usize)
+        {
+            return Err(Error::ErrAlpnInvalidFormat);
+        }
+
         Ok(())
     }
 
@@ -308,6 +389,12 @@ impl ConfigBuilder {
             self.replay_protection_window
         };
 
+        let max_fragment_buffer_size = if self.max_fragment_buffer_size == 0 {
+            DEFAULT_MAX_FRAGMENT_BUFFER_SIZE
+        } else {
+            self.max_fragment_buffer_size
+        };
+
         let mut server_name = self.server_name.clone();
 
         // Use host from conn address when server_name is not provided
@@ -320,6 +407,18 @@ impl ConfigBuilder {
             }
         }
 
+        // client certificates are verified against client_cas only when the policy asks for it
+        let client_cert_verifier: Option<Arc<dyn rustls::server::ClientCertVerifier>> = if matches!(
+            self.client_auth,
+            ClientAuthType::VerifyClientCertIfGiven | ClientAuthType::RequireAndVerifyClientCert
+        ) {
+            Some(Arc::new(rustls::server::AllowAnyAuthenticatedClient::new(
+                self.client_cas,
+            )))
+        } else {
+            None
+        };
+
         Ok(HandshakeConfig {
             local_psk_callback: self.psk.take(),
             local_psk_identity_hint: self.psk_identity_hint.take(),
@@ -327,6 +426,7 @@ impl ConfigBuilder {
             local_signature_schemes,
             extended_master_secret: self.extended_master_secret,
             local_srtp_protection_profiles: self.srtp_protection_profiles,
+            supported_protocols: self.supported_protocols,
             server_name,
             client_auth: self.client_auth,
             local_certificates: self.certificates,
@@ -338,11 +438,15 @@ impl ConfigBuilder {
                 rustls::RootCertStore::empty(),
                 None,
             )),
-            client_cert_verifier: None,
+            client_cert_verifier,
             retransmit_interval,
             initial_epoch: 0,
             maximum_transmission_unit,
             replay_protection_window,
+            max_fragment_buffer_size,
+            disable_cookie: self.disable_cookie,
+            support_connection_id: self.support_connection_id || self.local_connection_id.is_some(),
+            local_connection_id: self.local_connection_id.take(),
             ..Default::default()
         })
     }
@@ -351,6 +455,70 @@ impl ConfigBuilder {
 pub(crate) type VerifyPeerCertificateFn =
     Arc<dyn (Fn(&[Vec<u8>], &[rustls::Certificate]) -> Result<()>) + Send + Sync>;
 
+type HmacSha256 = Hmac<Sha256>;
+
+const COOKIE_SECRET_LENGTH: usize = 32;
+
+/// CookieSecret keys the HelloVerifyRequest cookies of a server. The previous secret is kept
+/// after a rotation, so that the cookies handed out just before remain valid.
+#[derive(Debug)]
+pub(crate) struct CookieSecret {
+    current: [u8; COOKIE_SECRET_LENGTH],
+    previous: Option<[u8; COOKIE_SECRET_LENGTH]>,
+}
+
+impl CookieSecret {
+    fn new() -> Self {
+        let mut current = [0u8; COOKIE_SECRET_LENGTH];
+        rand::thread_rng().fill(&mut current);
+        CookieSecret {
+            current,
+            previous: None,
+        }
+    }
+
+    fn rotate(&mut self) {
+        let mut current = [0u8; COOKIE_SECRET_LENGTH];
+        rand::thread_rng().fill(&mut current);
+        self.previous = Some(std::mem::replace(&mut self.current, current));
+    }
+}
+
+// cookie is an HMAC-SHA256 over the client address and the random of its ClientHello,
+// truncated to COOKIE_LENGTH
+fn cookie(
+    secret: &[u8; COOKIE_SECRET_LENGTH],
+    remote_addr: Option<SocketAddr>,
+    client_random: &HandshakeRandom,
+) -> Result<Vec<u8>> {
+    let mut cookie = cookie_mac(secret, remote_addr, client_random)?
+        .finalize()
+        .into_bytes()
+        .to_vec();
+    cookie.truncate(COOKIE_LENGTH);
+    Ok(cookie)
+}
+
+fn cookie_mac(
+    secret: &[u8; COOKIE_SECRET_LENGTH],
+    remote_addr: Option<SocketAddr>,
+    client_random: &HandshakeRandom,
+) -> Result<HmacSha256> {
+    let mut mac = HmacSha256::new_from_slice(secret).map_err(|e| Error::Other(e.to_string()))?;
+    if let Some(remote_addr) = remote_addr {
+        match remote_addr.ip() {
+            IpAddr::V4(ip) => mac.update(&ip.octets()),
+            IpAddr::V6(ip) => mac.update(&ip.octets()),
+        }
+        mac.update(&remote_addr.port().to_be_bytes());
+    }
+    let mut random = vec![];
+    client_random.marshal(&mut random)?;
+    mac.update(&random);
+
+    Ok(mac)
+}
+
 #[derive(Clone)]
 pub struct HandshakeConfig {
     pub(crate) local_psk_callback: Option<PskCallback>,
@@ -359,6 +527,7 @@ pub struct HandshakeConfig {
     pub(crate) local_signature_schemes: Vec<SignatureHashAlgorithm>, // Available signature schemes
     pub(crate) extended_master_secret: ExtendedMasterSecretType, // Policy for the Extended Master Support extension
     pub(crate) local_srtp_protection_profiles: Vec<SrtpProtectionProfile>, // Available SRTPProtectionProfiles, if empty no SRTP support
+    pub(crate) supported_protocols: Vec<String>, // Available ALPN protocols, if empty no ALPN support
     pub(crate) server_name: String,
     pub(crate) client_auth: ClientAuthType, // If we are a client should we request a client certificate
     pub(crate) local_certificates: Vec<Certificate>,
@@ -374,6 +543,11 @@ pub struct HandshakeConfig {
     pub(crate) maximum_transmission_unit: usize,
     pub(crate) maximum_retransmit_number: usize,
     pub(crate) replay_protection_window: usize,
+    pub(crate) max_fragment_buffer_size: usize,
+    pub(crate) disable_cookie: bool,
+    pub(crate) cookie_secret: Arc<Mutex<CookieSecret>>, // Shared by the clones of the config
+    pub(crate) support_connection_id: bool,             // Negotiate the connection_id extension
+    pub(crate) local_connection_id: Option<ConnectionIdGenerator>, // Connection ids to ask the peer for, if not empty
 }
 
 impl fmt::Debug for HandshakeConfig {
@@ -387,6 +561,7 @@ impl fmt::Debug for HandshakeConfig {
                 "local_srtp_protection_profiles",
                 &self.local_srtp_protection_profiles,
             )
+            .field("supported_protocols", &self.supported_protocols)
             .field("server_name", &self.server_name)
             .field("client_auth", &self.client_auth)
             .field("local_certificates", &self.local_certificates)
@@ -399,6 +574,9 @@ impl fmt::Debug for HandshakeConfig {
             .field("maximum_transmission_unit", &self.maximum_transmission_unit)
             .field("maximum_retransmit_number", &self.maximum_retransmit_number)
             .field("replay_protection_window", &self.replay_protection_window)
+            .field("max_fragment_buffer_size", &self.max_fragment_buffer_size)
+            .field("disable_cookie", &self.disable_cookie)
+            .field("support_connection_id", &self.support_connection_id)
             .finish()
     }
 }
@@ -412,6 +590,7 @@ impl Default for HandshakeConfig {
             local_signature_schemes: vec![],
             extended_master_secret: ExtendedMasterSecretType::Disable,
             local_srtp_protection_profiles: vec![],
+            supported_protocols: vec![],
             server_name: String::new(),
             client_auth: ClientAuthType::NoClientCert,
             local_certificates: vec![],
@@ -430,11 +609,67 @@ impl Default for HandshakeConfig {
             maximum_transmission_unit: DEFAULT_MTU,
             maximum_retransmit_number: 7,
             replay_protection_window: DEFAULT_REPLAY_PROTECTION_WINDOW,
+            max_fragment_buffer_size: DEFAULT_MAX_FRAGMENT_BUFFER_SIZE,
+            disable_cookie: false,
+            cookie_secret: Arc::new(Mutex::new(CookieSecret::new())),
+            support_connection_id: false,
+            local_connection_id: None,
         }
     }
 }
 
 impl HandshakeConfig {
+    /// rotate_cookie_secret replaces the secret keying the HelloVerifyRequest cookies of the
+    /// servers using this config. It is meant to be called periodically, e.g. every minute,
+    /// so that cookies expire: those of the previous secret are accepted until the next
+    /// rotation.
+    pub fn rotate_cookie_secret(&self) {
+        self.cookie_secret
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .rotate();
+    }
+
+    pub(crate) fn generate_cookie(
+        &self,
+        remote_addr: Option<SocketAddr>,
+        client_random: &HandshakeRandom,
+    ) -> Result<Vec<u8>> {
+        let secret = self
+            .cookie_secret
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .current;
+        cookie(&secret, remote_addr, client_random)
+    }
+
+    pub(crate) fn verify_cookie(
+        &self,
+        cookie_to_verify: &[u8],
+        remote_addr: Option<SocketAddr>,
+        client_random: &HandshakeRandom,
+    ) -> Result<bool> {
+        // verify_truncated_left accepts any shorter tag
+        if cookie_to_verify.len() != COOKIE_LENGTH {
+            return Ok(false);
+        }
+
+        let (current, previous) = {
+            let secret = self
+                .cookie_secret
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            (secret.current, secret.previous)
+        };
+        for secret in std::iter::once(current).chain(previous) {
+            let mac = cookie_mac(&secret, remote_addr, client_random)?;
+            if mac.verify_truncated_left(cookie_to_verify).is_ok() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
     pub(crate) fn get_certificate(&self, server_name: &str) -> Result<Certificate> {
         if self.local_certificates.is_empty() {
             return Err(Error::ErrNoCertificates);
@@ -1,4 +1,11 @@
 use super::*;
+use crate::config::{
+    random_connection_id_generator, ClientAuthType, ConfigBuilder, ExtendedMasterSecretType,
+    VerifyPeerCertificateFn,
+};
+use crate::crypto::{Certificate, CryptoPrivateKey};
+use crate::handshake::handshake_random::HandshakeRandom;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 /*use crate::cipher_suite::cipher_suite_aes_128_gcm_sha256::*;
 use crate::cipher_suite::*;
@@ -1083,7 +1090,7 @@ async fn test_extended_master_secret() -> Result<()> {
                 extended_master_secret: ExtendedMasterSecretType::Disable,
                 ..Default::default()
             },
-            Some(Error::ErrClientRequiredButNoServerEms),
+            Some(Error::ErrServerNoExtendedMasterSecret),
             Some(Error::ErrAlertFatalOrClose),
         ),
         (
@@ -1110,7 +1117,7 @@ async fn test_extended_master_secret() -> Result<()> {
                 ..Default::default()
             },
             Some(Error::ErrAlertFatalOrClose),
-            Some(Error::ErrServerRequiredButNoClientEms),
+            Some(Error::ErrClientNoExtendedMasterSecret),
         ),
         (
             "Disable_Disable_ExtendedMasterSecret",
@@ -2327,7 +2334,7 @@ async fn test_renegotation_info() -> Result<()> {
             .recv(&mut resp)
             .await
             .map_err(|err| Error::Other(err.to_string()))?;
-        let messages = unpack_datagram(&resp[..n])?;
+        let messages = unpack_datagram(&resp[..n], 0)?;
 
         let mut reader = BufReader::new(&messages[0][..]);
         let record = RecordLayer::unmarshal(&mut reader)?;
@@ -2362,3 +2369,782 @@ async fn test_renegotation_info() -> Result<()> {
     Ok(())
 }
 */
+
+#[test]
+fn test_retransmit_timer_backoff() -> Result<()> {
+    let handshake_config = Arc::new(HandshakeConfig {
+        retransmit_interval: Duration::from_secs(1),
+        maximum_retransmit_number: 7,
+        ..Default::default()
+    });
+    let mut conn = DTLSConn::new(handshake_config, true, None);
+    conn.retransmit_timer.max = Duration::from_secs(8);
+
+    let mut now = Instant::now();
+    conn.handshake(now)?;
+    assert!(conn.current_handshake_state == HandshakeState::Waiting);
+
+    let mut intervals = vec![];
+    for _ in 0..5 {
+        let timeout = conn
+            .current_retransmit_timer
+            .take()
+            .expect("retransmit timer should be armed");
+        // the timer is armed from the time passed in, not from the wall clock
+        assert_eq!(now + conn.retransmit_interval(), timeout);
+        intervals.push(conn.retransmit_interval());
+
+        conn.handshake_timeout(timeout)?;
+        now = timeout;
+        assert!(conn.outgoing_raw_packet().is_some());
+    }
+    assert_eq!(
+        intervals,
+        vec![1, 2, 4, 8, 8]
+            .into_iter()
+            .map(Duration::from_secs)
+            .collect::<Vec<_>>()
+    );
+
+    conn.retransmit_timer.reset();
+    assert_eq!(conn.retransmit_interval(), Duration::from_secs(1));
+
+    Ok(())
+}
+
+fn handshake_record(sequence_number: u64) -> Vec<u8> {
+    let mut record = vec![
+        0x16, // handshake
+        0xfe, 0xfd, // DTLS 1.2
+        0x00, 0x00, // epoch
+    ];
+    record.extend_from_slice(&sequence_number.to_be_bytes()[2..]);
+    record.extend_from_slice(&[0x00, 0x0c]); // length
+    record.extend_from_slice(&[
+        0x01, // client_hello
+        0x00, 0x00, 0x00, // length
+        0x00, 0x00, // message_seq
+        0x00, 0x00, 0x00, // fragment_offset
+        0x00, 0x00, 0x00, // fragment_length
+    ]);
+    record
+}
+
+#[test]
+fn test_replay_protection_window() -> Result<()> {
+    for (replay_protection_window, accept_older) in
+        [(1, false), (DEFAULT_REPLAY_PROTECTION_WINDOW, true)]
+    {
+        let handshake_config = Arc::new(HandshakeConfig {
+            replay_protection_window,
+            ..Default::default()
+        });
+        let mut conn = DTLSConn::new(handshake_config, false, None);
+
+        let (accepted, _, _) = conn.handle_incoming_packet(handshake_record(5), true);
+        assert!(accepted);
+        let (accepted, _, _) = conn.handle_incoming_packet(handshake_record(5), true);
+        assert!(!accepted, "duplicated record must be dropped");
+        let (accepted, _, _) = conn.handle_incoming_packet(handshake_record(4), true);
+        assert_eq!(accepted, accept_older);
+    }
+
+    Ok(())
+}
+
+fn deliver_packets(from: &mut DTLSConn, to: &mut DTLSConn) -> Result<()> {
+    // records are read one by one, so that the ChangeCipherSpec and Finished of a
+    // flight are not queued before the keys are derived from the preceding records
+    while let Some(packet) = from.outgoing_raw_packet() {
+        for record in unpack_datagram(&packet, to.state.local_connection_id.len())? {
+            to.read(&record)?;
+            if !to.is_handshake_completed() {
+                to.handshake(Instant::now())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_epoch_changed_events() -> Result<()> {
+    let config = |is_client: bool| -> Result<Arc<HandshakeConfig>> {
+        Ok(Arc::new(
+            ConfigBuilder::default()
+                .with_certificates(vec![Certificate::generate_self_signed(vec![
+                    "localhost".to_owned()
+                ])?])
+                .with_insecure_skip_verify(true)
+                .build(is_client, None)?,
+        ))
+    };
+    let mut client = DTLSConn::new(config(true)?, true, None);
+    let mut server = DTLSConn::new(config(false)?, false, None);
+
+    client.handshake(Instant::now())?;
+    for _ in 0..4 {
+        deliver_packets(&mut client, &mut server)?;
+        deliver_packets(&mut server, &mut client)?;
+    }
+    assert!(client.is_handshake_completed() && server.is_handshake_completed());
+
+    for conn in [&mut client, &mut server] {
+        let mut events = vec![];
+        while let Some(event) = conn.poll_event() {
+            events.push(event);
+        }
+        assert!(events.contains(&DTLSEvent::EpochChanged { old: 0, new: 1 }));
+        assert!(events.contains(&DTLSEvent::LocalEpochChanged { epoch: 1 }));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_resume_from_serialized_state() -> Result<()> {
+    let config = |is_client: bool| -> Result<Arc<HandshakeConfig>> {
+        Ok(Arc::new(
+            ConfigBuilder::default()
+                .with_certificates(vec![Certificate::generate_self_signed(vec![
+                    "localhost".to_owned()
+                ])?])
+                .with_insecure_skip_verify(true)
+                .build(is_client, None)?,
+        ))
+    };
+    let mut client = DTLSConn::new(config(true)?, true, None);
+    let mut server = DTLSConn::new(config(false)?, false, None);
+
+    client.handshake(Instant::now())?;
+    for _ in 0..4 {
+        deliver_packets(&mut client, &mut server)?;
+        deliver_packets(&mut server, &mut client)?;
+    }
+    assert!(client.is_handshake_completed() && server.is_handshake_completed());
+
+    client.write(b"before")?;
+    let replayed = client.outgoing_raw_packet().expect("application data");
+    server.read(&replayed)?;
+    assert_eq!(
+        server.incoming_application_data().as_deref(),
+        Some(&b"before"[..])
+    );
+
+    let data = server.connection_state().serialize()?;
+    assert_eq!(data[0], 1);
+    drop(server);
+    let mut server = DTLSConn::from_serialized_state(config(false)?, &data)?;
+    assert!(!server.connection_state().is_client);
+
+    server.read(&replayed)?;
+    assert!(
+        server.incoming_application_data().is_none(),
+        "record received before serialization must be dropped"
+    );
+
+    client.write(b"ping")?;
+    deliver_packets(&mut client, &mut server)?;
+    assert_eq!(
+        server.incoming_application_data().as_deref(),
+        Some(&b"ping"[..])
+    );
+
+    server.write(b"pong")?;
+    deliver_packets(&mut server, &mut client)?;
+    assert_eq!(
+        client.incoming_application_data().as_deref(),
+        Some(&b"pong"[..])
+    );
+
+    let mut data = data;
+    data[0] = 0;
+    assert!(matches!(
+        DTLSConn::from_serialized_state(config(false)?, &data),
+        Err(Error::ErrUnsupportedStateVersion)
+    ));
+
+    Ok(())
+}
+
+// delivers the packets of from like deliver_packets, recording the types of the unencrypted
+// handshake messages
+fn deliver_and_record_handshakes(
+    from: &mut DTLSConn,
+    to: &mut DTLSConn,
+    transcript: &mut Vec<HandshakeType>,
+) -> Result<()> {
+    while let Some(packet) = from.outgoing_raw_packet() {
+        for record in unpack_datagram(&packet, to.state.local_connection_id.len())? {
+            let header = RecordLayerHeader::unmarshal(&mut BufReader::new(&record[..]))?;
+            if header.content_type == ContentType::Handshake && header.epoch == 0 {
+                transcript.push(HandshakeType::from(record[RECORD_LAYER_HEADER_SIZE]));
+            }
+            to.read(&record)?;
+            if !to.is_handshake_completed() {
+                to.handshake(Instant::now())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_hello_verify_request_cookie() -> Result<()> {
+    let config = |is_client: bool, disable_cookie: bool| -> Result<Arc<HandshakeConfig>> {
+        Ok(Arc::new(
+            ConfigBuilder::default()
+                .with_certificates(vec![Certificate::generate_self_signed(vec![
+                    "localhost".to_owned()
+                ])?])
+                .with_insecure_skip_verify(true)
+                .with_disable_cookie(disable_cookie)
+                .build(is_client, None)?,
+        ))
+    };
+
+    for disable_cookie in [false, true] {
+        let mut client = DTLSConn::new(config(true, false)?, true, None);
+        let mut server = DTLSConn::new(config(false, disable_cookie)?, false, None);
+        server.set_remote_addr("127.0.0.1:5000".parse().unwrap());
+
+        let mut transcript = vec![];
+        client.handshake(Instant::now())?;
+        for _ in 0..4 {
+            deliver_and_record_handshakes(&mut client, &mut server, &mut transcript)?;
+            deliver_and_record_handshakes(&mut server, &mut client, &mut transcript)?;
+        }
+        assert!(client.is_handshake_completed() && server.is_handshake_completed());
+
+        let client_hellos = transcript
+            .iter()
+            .filter(|typ| **typ == HandshakeType::ClientHello)
+            .count();
+        if disable_cookie {
+            assert!(!transcript.contains(&HandshakeType::HelloVerifyRequest));
+            assert_eq!(client_hellos, 1);
+        } else {
+            assert_eq!(
+                &transcript[..3],
+                &[
+                    HandshakeType::ClientHello,
+                    HandshakeType::HelloVerifyRequest,
+                    HandshakeType::ClientHello
+                ]
+            );
+            assert_eq!(client_hellos, 2);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_cookie_secret_rotation() -> Result<()> {
+    let config = HandshakeConfig::default();
+    let remote_addr = Some("127.0.0.1:5000".parse().unwrap());
+    let mut client_random = HandshakeRandom::default();
+    client_random.populate();
+
+    let cookie = config.generate_cookie(remote_addr, &client_random)?;
+    assert_eq!(cookie.len(), COOKIE_LENGTH);
+    assert!(config.verify_cookie(&cookie, remote_addr, &client_random)?);
+    assert!(
+        !config.verify_cookie(
+            &cookie,
+            Some("127.0.0.1:5001".parse().unwrap()),
+            &client_random
+        )?,
+        "cookie must be bound to the client address"
+    );
+    assert!(
+        !config.verify_cookie(&cookie[..1], remote_addr, &client_random)?,
+        "a truncated cookie must be rejected"
+    );
+
+    config.rotate_cookie_secret();
+    assert!(
+        config.verify_cookie(&cookie, remote_addr, &client_random)?,
+        "cookie of the previous secret must remain valid"
+    );
+    config.rotate_cookie_secret();
+    assert!(!config.verify_cookie(&cookie, remote_addr, &client_random)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_connection_id() -> Result<()> {
+    let config = |is_client: bool, builder: ConfigBuilder| -> Result<Arc<HandshakeConfig>> {
+        Ok(Arc::new(
+            builder
+                .with_certificates(vec![Certificate::generate_self_signed(vec![
+                    "localhost".to_owned()
+                ])?])
+                .with_insecure_skip_verify(true)
+                .build(is_client, None)?,
+        ))
+    };
+
+    let tests = vec![
+        (
+            "both sides use a connection id",
+            ConfigBuilder::default().with_local_connection_id(random_connection_id_generator(8)),
+            ConfigBuilder::default().with_local_connection_id(random_connection_id_generator(4)),
+            8,
+            4,
+        ),
+        (
+            "client only receives a connection id",
+            ConfigBuilder::default().with_local_connection_id(random_connection_id_generator(8)),
+            ConfigBuilder::default().with_support_connection_id(true),
+            8,
+            0,
+        ),
+        (
+            "server does not support connection ids",
+            ConfigBuilder::default().with_local_connection_id(random_connection_id_generator(8)),
+            ConfigBuilder::default(),
+            0,
+            0,
+        ),
+    ];
+
+    for (name, client_config, server_config, client_cid_len, server_cid_len) in tests {
+        let mut client = DTLSConn::new(config(true, client_config)?, true, None);
+        let mut server = DTLSConn::new(config(false, server_config)?, false, None);
+
+        client.handshake(Instant::now())?;
+        for _ in 0..4 {
+            deliver_packets(&mut client, &mut server)?;
+            deliver_packets(&mut server, &mut client)?;
+        }
+        assert!(
+            client.is_handshake_completed() && server.is_handshake_completed(),
+            "{name}: handshake must complete"
+        );
+        assert_eq!(
+            client.connection_state().local_connection_id.len(),
+            client_cid_len,
+            "{name}"
+        );
+        assert_eq!(
+            server.connection_state().local_connection_id.len(),
+            server_cid_len,
+            "{name}"
+        );
+
+        for client_sends in [true, false] {
+            let (from, to) = if client_sends {
+                (&mut client, &mut server)
+            } else {
+                (&mut server, &mut client)
+            };
+            from.write(b"ping")?;
+            let packet = from.outgoing_raw_packet().expect("application data");
+            let connection_id = to.connection_state().local_connection_id.clone();
+            if connection_id.is_empty() {
+                assert_eq!(packet[0], ContentType::ApplicationData as u8, "{name}");
+            } else {
+                assert_eq!(packet[0], ContentType::Tls12Cid as u8, "{name}");
+                assert_eq!(
+                    &packet[RECORD_LAYER_HEADER_SIZE - 2..][..connection_id.len()],
+                    &connection_id[..],
+                    "{name}: record must carry the connection id of the receiver"
+                );
+            }
+            to.read(&packet)?;
+            assert_eq!(
+                to.incoming_application_data().as_deref(),
+                Some(&b"ping"[..]),
+                "{name}"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_connection_id_mismatch() -> Result<()> {
+    let config = |is_client: bool| -> Result<Arc<HandshakeConfig>> {
+        Ok(Arc::new(
+            ConfigBuilder::default()
+                .with_certificates(vec![Certificate::generate_self_signed(vec![
+                    "localhost".to_owned()
+                ])?])
+                .with_insecure_skip_verify(true)
+                .with_local_connection_id(random_connection_id_generator(4))
+                .build(is_client, None)?,
+        ))
+    };
+    let mut client = DTLSConn::new(config(true)?, true, None);
+    let mut server = DTLSConn::new(config(false)?, false, None);
+
+    client.handshake(Instant::now())?;
+    for _ in 0..4 {
+        deliver_packets(&mut client, &mut server)?;
+        deliver_packets(&mut server, &mut client)?;
+    }
+    assert!(client.is_handshake_completed() && server.is_handshake_completed());
+
+    client.write(b"ping")?;
+    let mut packet = client.outgoing_raw_packet().expect("application data");
+    packet[RECORD_LAYER_HEADER_SIZE - 2] ^= 0xff;
+    server.read(&packet)?;
+    assert!(
+        server.incoming_application_data().is_none(),
+        "record with an unknown connection id must be dropped"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_extended_master_secret_policy() -> Result<()> {
+    use ExtendedMasterSecretType::*;
+
+    let config = |is_client: bool, ems: ExtendedMasterSecretType| -> Result<Arc<HandshakeConfig>> {
+        Ok(Arc::new(
+            ConfigBuilder::default()
+                .with_certificates(vec![Certificate::generate_self_signed(vec![
+                    "localhost".to_owned()
+                ])?])
+                .with_insecure_skip_verify(true)
+                .with_extended_master_secret(ems)
+                .build(is_client, None)?,
+        ))
+    };
+
+    // client policy, server policy, client error, server error, negotiated
+    let tests = vec![
+        (Request, Request, None, None, true),
+        (Request, Require, None, None, true),
+        (Request, Disable, None, None, false),
+        (Require, Request, None, None, true),
+        (Require, Require, None, None, true),
+        (
+            Require,
+            Disable,
+            Some(Error::ErrServerNoExtendedMasterSecret),
+            Some(Error::ErrAlertFatalOrClose),
+            false,
+        ),
+        (Disable, Request, None, None, false),
+        (
+            Disable,
+            Require,
+            Some(Error::ErrAlertFatalOrClose),
+            Some(Error::ErrClientNoExtendedMasterSecret),
+            false,
+        ),
+        (Disable, Disable, None, None, false),
+    ];
+
+    for (client_ems, server_ems, want_client_err, want_server_err, want_negotiated) in tests {
+        let name = format!("{client_ems:?}_{server_ems:?}");
+        let mut client = DTLSConn::new(config(true, client_ems)?, true, None);
+        let mut server = DTLSConn::new(config(false, server_ems)?, false, None);
+
+        // the first error of each side ends its handshake, its alert is still delivered
+        let (mut client_err, mut server_err) = (None, None);
+        client.handshake(Instant::now())?;
+        for _ in 0..4 {
+            if server_err.is_none() {
+                server_err = deliver_packets(&mut client, &mut server).err();
+            }
+            if client_err.is_none() {
+                client_err = deliver_packets(&mut server, &mut client).err();
+            }
+        }
+        assert_eq!(client_err, want_client_err, "{name}: client error");
+        assert_eq!(server_err, want_server_err, "{name}: server error");
+        if want_client_err.is_some() {
+            continue;
+        }
+
+        assert!(
+            client.is_handshake_completed() && server.is_handshake_completed(),
+            "{name}: handshake must complete"
+        );
+        assert_eq!(
+            client.connection_state().extended_master_secret,
+            want_negotiated,
+            "{name}: client extended master secret"
+        );
+        assert_eq!(
+            server.connection_state().extended_master_secret,
+            want_negotiated,
+            "{name}: server extended master secret"
+        );
+        // both sides derive the master secret from the same session hash or randoms
+        assert_eq!(
+            client.connection_state().master_secret,
+            server.connection_state().master_secret,
+            "{name}: master secret"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_alpn() -> Result<()> {
+    let config = |is_client: bool, protocols: &[&str]| -> Result<Arc<HandshakeConfig>> {
+        Ok(Arc::new(
+            ConfigBuilder::default()
+                .with_certificates(vec![Certificate::generate_self_signed(vec![
+                    "localhost".to_owned()
+                ])?])
+                .with_insecure_skip_verify(true)
+                .with_supported_protocols(protocols.iter().map(|p| p.to_string()).collect())
+                .build(is_client, None)?,
+        ))
+    };
+
+    // client protocols, server protocols, client error, server error, negotiated
+    let tests: Vec<(&str, &[&str], &[&str], _, _, _)> = vec![
+        (
+            "first client protocol supported by the server",
+            &["webrtc", "c-webrtc"],
+            &["c-webrtc", "webrtc"],
+            None,
+            None,
+            Some("webrtc"),
+        ),
+        (
+            "no overlap",
+            &["webrtc"],
+            &["c-webrtc"],
+            Some(Error::ErrAlertFatalOrClose),
+            Some(Error::ErrServerNoMatchingApplicationProtocol),
+            None,
+        ),
+        (
+            "client does not offer alpn",
+            &[],
+            &["webrtc"],
+            None,
+            None,
+            None,
+        ),
+        (
+            "server does not support alpn",
+            &["webrtc"],
+            &[],
+            None,
+            None,
+            None,
+        ),
+    ];
+
+    for (name, client_protocols, server_protocols, want_client_err, want_server_err, want) in tests
+    {
+        let mut client = DTLSConn::new(config(true, client_protocols)?, true, None);
+        let mut server = DTLSConn::new(config(false, server_protocols)?, false, None);
+
+        // the first error of each side ends its handshake, its alert is still delivered
+        let (mut client_err, mut server_err) = (None, None);
+        client.handshake(Instant::now())?;
+        for _ in 0..4 {
+            if server_err.is_none() {
+                server_err = deliver_packets(&mut client, &mut server).err();
+            }
+            if client_err.is_none() {
+                client_err = deliver_packets(&mut server, &mut client).err();
+            }
+        }
+        assert_eq!(client_err, want_client_err, "{name}: client error");
+        assert_eq!(server_err, want_server_err, "{name}: server error");
+        if want_client_err.is_some() {
+            continue;
+        }
+
+        assert!(
+            client.is_handshake_completed() && server.is_handshake_completed(),
+            "{name}: handshake must complete"
+        );
+        assert_eq!(
+            client.connection_state().negotiated_protocol(),
+            want,
+            "{name}: client negotiated protocol"
+        );
+        assert_eq!(
+            server.connection_state().negotiated_protocol(),
+            want,
+            "{name}: server negotiated protocol"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_client_certificate_authentication() -> Result<()> {
+    let client_certificate = Certificate::generate_self_signed(vec!["client".to_owned()])?;
+    // a distinct subject, so that the client certificate is not mistaken for its issuer
+    let other_certificate = {
+        let mut params = rcgen::CertificateParams::new(vec!["other".to_owned()]);
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, "other");
+        let cert = rcgen::Certificate::from_params(params)?;
+        Certificate {
+            certificate: vec![rustls::Certificate(cert.serialize_der()?)],
+            private_key: CryptoPrivateKey::try_from(cert.get_key_pair())?,
+        }
+    };
+
+    let verified = Arc::new(AtomicBool::new(false));
+    let mut client_cas = rustls::RootCertStore::empty();
+    client_cas
+        .add(&client_certificate.certificate[0])
+        .map_err(|err| Error::Other(err.to_string()))?;
+    let server_config = |verify_peer_certificate: VerifyPeerCertificateFn| -> Result<_> {
+        Ok(Arc::new(
+            ConfigBuilder::default()
+                .with_certificates(vec![Certificate::generate_self_signed(vec![
+                    "localhost".to_owned()
+                ])?])
+                .with_client_auth(ClientAuthType::RequireAndVerifyClientCert)
+                .with_client_cas(client_cas.clone())
+                .with_verify_peer_certificate(Some(verify_peer_certificate))
+                .build(false, None)?,
+        ))
+    };
+    let client_config = |certificates: Vec<Certificate>| -> Result<_> {
+        Ok(Arc::new(
+            ConfigBuilder::default()
+                .with_certificates(certificates)
+                .with_insecure_skip_verify(true)
+                .build(true, None)?,
+        ))
+    };
+
+    let record_verified: VerifyPeerCertificateFn = {
+        let verified = Arc::clone(&verified);
+        Arc::new(move |certificates, chains| {
+            assert!(!certificates.is_empty() && !chains.is_empty());
+            verified.store(true, Ordering::SeqCst);
+            Ok(())
+        })
+    };
+    let reject: VerifyPeerCertificateFn =
+        Arc::new(|_, _| Err(Error::Other("unknown device".to_owned())));
+
+    // name, client certificates, verify callback, client error, server error
+    let tests = vec![
+        (
+            "verified certificate",
+            vec![client_certificate.clone()],
+            record_verified.clone(),
+            None,
+            None,
+        ),
+        (
+            "no certificate",
+            vec![],
+            record_verified.clone(),
+            Some(Error::ErrAlertFatalOrClose),
+            Some(Error::ErrClientCertificateRequired),
+        ),
+        (
+            "certificate not issued by client_cas",
+            vec![other_certificate],
+            record_verified,
+            Some(Error::ErrAlertFatalOrClose),
+            Some(Error::Other(
+                "invalid peer certificate: UnknownIssuer".to_owned(),
+            )),
+        ),
+        (
+            "certificate rejected by the callback",
+            vec![client_certificate],
+            reject,
+            Some(Error::ErrAlertFatalOrClose),
+            Some(Error::Other("unknown device".to_owned())),
+        ),
+    ];
+
+    for (name, certificates, verify_peer_certificate, want_client_err, want_server_err) in tests {
+        let mut client = DTLSConn::new(client_config(certificates)?, true, None);
+        let mut server = DTLSConn::new(server_config(verify_peer_certificate)?, false, None);
+
+        // the first error of each side ends its handshake, its alert is still delivered
+        let (mut client_err, mut server_err) = (None, None);
+        client.handshake(Instant::now())?;
+        for _ in 0..4 {
+            if server_err.is_none() {
+                server_err = deliver_packets(&mut client, &mut server).err();
+            }
+            if client_err.is_none() {
+                client_err = deliver_packets(&mut server, &mut client).err();
+            }
+        }
+        assert_eq!(client_err, want_client_err, "{name}: client error");
+        assert_eq!(server_err, want_server_err, "{name}: server error");
+        if want_server_err.is_some() {
+            continue;
+        }
+
+        assert!(
+            client.is_handshake_completed() && server.is_handshake_completed(),
+            "{name}: handshake must complete"
+        );
+        assert!(
+            server.connection_state().peer_certificates_verified,
+            "{name}"
+        );
+        assert_eq!(
+            server.connection_state().peer_certificates.len(),
+            1,
+            "{name}"
+        );
+    }
+    assert!(
+        verified.load(Ordering::SeqCst),
+        "verify_peer_certificate must be called with the verified chains"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_unmarshal_binary_keeps_unversioned_layout() -> Result<()> {
+    let config = |is_client: bool| -> Result<Arc<HandshakeConfig>> {
+        Ok(Arc::new(
+            ConfigBuilder::default()
+                .with_certificates(vec![Certificate::generate_self_signed(vec![
+                    "localhost".to_owned()
+                ])?])
+                .with_insecure_skip_verify(true)
+                .build(is_client, None)?,
+        ))
+    };
+    let mut client = DTLSConn::new(config(true)?, true, None);
+    let mut server = DTLSConn::new(config(false)?, false, None);
+
+    client.handshake(Instant::now())?;
+    for _ in 0..4 {
+        deliver_packets(&mut client, &mut server)?;
+        deliver_packets(&mut server, &mut client)?;
+    }
+    assert!(client.is_handshake_completed() && server.is_handshake_completed());
+
+    let expected = server.connection_state();
+    let data = expected.marshal_binary()?;
+    // no version prefix, the layout starts with the local epoch
+    assert_eq!(&data[..2], &expected.local_epoch.to_le_bytes());
+
+    let mut state = State::default();
+    state.unmarshal_binary(&data)?;
+    assert_eq!(state.local_epoch, expected.local_epoch);
+    assert_eq!(state.remote_epoch, expected.remote_epoch);
+    assert_eq!(state.master_secret, expected.master_secret);
+    assert_eq!(state.is_client, expected.is_client);
+    assert_eq!(
+        state.cipher_suite.as_ref().map(|c| c.id()),
+        expected.cipher_suite.as_ref().map(|c| c.id())
+    );
+
+    Ok(())
+}
@@ -27,10 +27,13 @@ use crate::config::HandshakeConfig;
 use bytes::BytesMut;
 use log::*;
 use std::io::{BufReader, BufWriter};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 pub(crate) const INITIAL_TICKER_INTERVAL: Duration = Duration::from_secs(1);
+// Upper bound of the flight retransmission timeout, RFC 6347 Section 4.2.4.1
+pub(crate) const MAX_RETRANSMIT_INTERVAL: Duration = Duration::from_secs(60);
 pub(crate) const COOKIE_LENGTH: usize = 20;
 pub(crate) const DEFAULT_NAMED_CURVE: NamedCurve = NamedCurve::X25519;
 pub(crate) const INBOUND_BUFFER_SIZE: usize = 8192;
@@ -44,6 +47,49 @@ pub(crate) static INVALID_KEYING_LABELS: &[&str] = &[
     "key expansion",
 ];
 
+/// DTLSEvent reports changes in the state of a DTLSConn, pulled by calling `poll_event`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DTLSEvent {
+    /// The remote epoch increased after a ChangeCipherSpec was received.
+    EpochChanged { old: u16, new: u16 },
+    /// The local epoch was changed, e.g. when a ChangeCipherSpec is sent.
+    LocalEpochChanged { epoch: u16 },
+}
+
+// RetransmitTimer tracks the flight retransmission timeout, which is doubled
+// on every retransmission up to max, as specified by RFC 6347 Section 4.2.4
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct RetransmitTimer {
+    pub(crate) initial: Duration,
+    pub(crate) current: Duration,
+    pub(crate) max: Duration,
+}
+
+impl RetransmitTimer {
+    pub(crate) fn new(initial: Duration) -> Self {
+        RetransmitTimer {
+            initial,
+            current: initial,
+            max: std::cmp::max(initial, MAX_RETRANSMIT_INTERVAL),
+        }
+    }
+
+    // on_sent returns when the timer of a flight transmitted at now expires
+    pub(crate) fn on_sent(&self, now: Instant) -> Instant {
+        now + self.current
+    }
+
+    // backoff doubles the timeout for the next retransmission
+    pub(crate) fn backoff(&mut self) {
+        self.current = std::cmp::min(self.current * 2, self.max);
+    }
+
+    // reset restores the initial timeout once the peer has made progress
+    pub(crate) fn reset(&mut self) {
+        self.current = self.initial;
+    }
+}
+
 // Conn represents a DTLS connection
 pub struct DTLSConn {
     is_client: bool,
@@ -58,6 +104,7 @@ pub struct DTLSConn {
     pub(crate) outgoing_packets: VecDeque<Packet>,
     outgoing_queued_packets: VecDeque<Packet>,
     outgoing_compacted_raw_packets: VecDeque<BytesMut>,
+    events: VecDeque<DTLSEvent>, // Epoch changes, pull by calling `poll_event`
 
     pub(crate) state: State, // Internal state
 
@@ -80,6 +127,7 @@ pub struct DTLSConn {
     pub(crate) current_handshake_state: HandshakeState,
     pub(crate) current_retransmit_timer: Option<Instant>,
     pub(crate) current_retransmit_count: usize,
+    pub(crate) retransmit_timer: RetransmitTimer,
 
     pub(crate) current_flight: Box<dyn Flight>,
     pub(crate) flights: Option<Vec<Packet>>,
@@ -94,6 +142,7 @@ impl DTLSConn {
         is_client: bool,
         initial_state: Option<State>,
     ) -> Self {
+        let resumed = initial_state.is_some();
         let (state, flight, initial_fsm_state) = if let Some(state) = initial_state {
             let flight = if is_client {
                 Box::new(Flight5 {}) as Box<dyn Flight>
@@ -119,7 +168,7 @@ impl DTLSConn {
             )
         };
 
-        Self {
+        let mut conn = Self {
             is_client,
             maximum_transmission_unit: handshake_config.maximum_transmission_unit,
             maximum_retransmit_number: handshake_config.maximum_retransmit_number,
@@ -127,10 +176,13 @@ impl DTLSConn {
             replay_detector: vec![],
             incoming_decrypted_packets: VecDeque::new(),
             incoming_encrypted_packets: VecDeque::new(),
-            fragment_buffer: FragmentBuffer::new(),
+            fragment_buffer: FragmentBuffer::with_max_size(
+                handshake_config.max_fragment_buffer_size,
+            ),
             outgoing_packets: VecDeque::new(),
             outgoing_queued_packets: VecDeque::new(),
             outgoing_compacted_raw_packets: VecDeque::new(),
+            events: VecDeque::new(),
 
             cache: HandshakeCache::new(),
             state,
@@ -141,13 +193,60 @@ impl DTLSConn {
             current_handshake_state: initial_fsm_state,
             current_retransmit_timer: None,
             current_retransmit_count: 0,
+            retransmit_timer: RetransmitTimer::new(handshake_config.retransmit_interval),
 
             current_flight: flight,
             flights: None,
             handshake_config,
             retransmit: false,
             handshake_rx: None,
+        };
+        if resumed {
+            conn.prime_replay_detector();
         }
+        conn
+    }
+
+    /// from_serialized_state creates a connection resuming the session of a state encoded by
+    /// `State::serialize`, e.g. before a restart, without a new handshake.
+    pub fn from_serialized_state(
+        handshake_config: Arc<HandshakeConfig>,
+        data: &[u8],
+    ) -> Result<Self> {
+        let state = State::deserialize(data)?;
+        let is_client = state.is_client;
+        Ok(Self::new(handshake_config, is_client, Some(state)))
+    }
+
+    // Marks the records of the remote epoch preceding the last one received before the state
+    // was serialized as seen, so that the resumed connection does not accept them again.
+    fn prime_replay_detector(&mut self) {
+        let epoch = self.state.remote_epoch as usize;
+        let next = self
+            .state
+            .remote_sequence_number
+            .get(epoch)
+            .copied()
+            .unwrap_or(0);
+        while self.replay_detector.len() <= epoch {
+            self.replay_detector
+                .push(Box::new(SlidingWindowDetector::new(
+                    self.replay_protection_window,
+                    MAX_SEQUENCE_NUMBER,
+                )));
+        }
+
+        let replay_detector = &mut self.replay_detector[epoch];
+        for sequence_number in next.saturating_sub(self.replay_protection_window as u64)..next {
+            if replay_detector.check(sequence_number) {
+                replay_detector.accept();
+            }
+        }
+    }
+
+    // Returns the next event of the connection, if any.
+    pub fn poll_event(&mut self) -> Option<DTLSEvent> {
+        self.events.pop_front()
     }
 
     // Read reads data from the connection.
@@ -208,12 +307,23 @@ impl DTLSConn {
         }
     }
 
+    /// retransmit_interval returns the timeout used for the next flight retransmission
+    pub fn retransmit_interval(&self) -> Duration {
+        self.retransmit_timer.current
+    }
+
     /// connection_state returns basic DTLS details about the connection.
     /// Note that this replaced the `Export` function of v1.
     pub fn connection_state(&self) -> &State {
         &self.state
     }
 
+    /// set_remote_addr sets the address of the peer, to which a server binds the cookie of
+    /// its HelloVerifyRequest.
+    pub fn set_remote_addr(&mut self, remote_addr: SocketAddr) {
+        self.state.remote_addr = Some(remote_addr);
+    }
+
     // selected_srtp_protection_profile returns the selected SRTPProtectionProfile
     pub(crate) fn selected_srtp_protection_profile(&self) -> SrtpProtectionProfile {
         self.state.srtp_protection_profile
@@ -326,6 +436,7 @@ impl DTLSConn {
 
         if p.should_encrypt {
             if let Some(cipher_suite) = &self.state.cipher_suite {
+                p.record.record_layer_header.connection_id = self.remote_connection_id()?;
                 raw_packet = cipher_suite.encrypt(&p.record.record_layer_header, &raw_packet)?;
             }
         }
@@ -360,6 +471,11 @@ impl DTLSConn {
                 content_len: handshake_fragment.len() as u16,
                 epoch: p.record.record_layer_header.epoch,
                 sequence_number: seq,
+                connection_id: if p.should_encrypt {
+                    self.remote_connection_id()?
+                } else {
+                    ConnectionId::default()
+                },
             };
 
             let mut record_layer_header_bytes = vec![];
@@ -385,6 +501,29 @@ impl DTLSConn {
         Ok(raw_packets)
     }
 
+    // Returns the epoch and the sequence number of the newest record authenticated from the
+    // peer, or the ones following it.
+    pub(crate) fn newest_remote_record(&self) -> (u16, u64) {
+        let epoch = self.state.remote_epoch;
+        let sequence_number = self
+            .state
+            .remote_sequence_number
+            .get(epoch as usize)
+            .copied()
+            .unwrap_or(0);
+        (epoch, sequence_number)
+    }
+
+    // Returns the connection id to protect records with, empty unless the peer asked for one.
+    fn remote_connection_id(&self) -> Result<ConnectionId> {
+        ConnectionId::new(
+            self.state
+                .remote_connection_id
+                .as_deref()
+                .unwrap_or_default(),
+        )
+    }
+
     fn fragment_handshake(maximum_transmission_unit: usize, h: &Handshake) -> Result<Vec<Vec<u8>>> {
         let mut content = vec![];
         {
@@ -439,7 +578,7 @@ impl DTLSConn {
     }
 
     pub fn read(&mut self, buf: &[u8]) -> Result<()> {
-        for pkt in unpack_datagram(buf)? {
+        for pkt in unpack_datagram(buf, self.state.local_connection_id.len())? {
             let (hs, alert, err) = self.handle_incoming_packet(pkt, true);
             if let Some(alert) = alert {
                 self.outgoing_packets.push_back(Packet {
@@ -513,8 +652,29 @@ impl DTLSConn {
         mut pkt: Vec<u8>,
         enqueue: bool,
     ) -> (bool, Option<Alert>, Option<Error>) {
+        if let Ok(DtlsVersion::V1_3) = DtlsVersion::detect(&pkt) {
+            // DTLS 1.3 is never negotiated yet, so there are no keys to decrypt the record
+            match RecordLayerHeader::unmarshal_v13(&pkt, 0) {
+                Ok((h, _)) => debug!(
+                    "{}: discarded DTLS 1.3 packet (epoch: {}, seq: {})",
+                    srv_cli_str(self.is_client),
+                    h.epoch,
+                    h.sequence_number,
+                ),
+                Err(err) => debug!(
+                    "{}: discarded broken DTLS 1.3 packet: {}",
+                    srv_cli_str(self.is_client),
+                    err
+                ),
+            }
+            return (false, None, None);
+        }
+
         let mut reader = BufReader::new(pkt.as_slice());
-        let h = match RecordLayerHeader::unmarshal(&mut reader) {
+        let h = match RecordLayerHeader::unmarshal_with_connection_id(
+            &mut reader,
+            self.state.local_connection_id.len(),
+        ) {
             Ok(h) => h,
             Err(err) => {
                 // Decode error must be silently discarded
@@ -528,6 +688,19 @@ impl DTLSConn {
             }
         };
 
+        // tls12_cid records are always protected, and carry the connection id we asked for
+        if h.content_type == ContentType::Tls12Cid
+            && (h.epoch == 0 || *h.connection_id != self.state.local_connection_id[..])
+        {
+            debug!(
+                "{}: discarded packet of unknown connection id (epoch: {}, seq: {})",
+                srv_cli_str(self.is_client),
+                h.epoch,
+                h.sequence_number,
+            );
+            return (false, None, None);
+        }
+
         // Validate epoch
         let epoch = self.state.remote_epoch;
         if h.epoch > epoch {
@@ -591,7 +764,7 @@ impl DTLSConn {
             }
 
             if let Some(cipher_suite) = &self.state.cipher_suite {
-                pkt = match cipher_suite.decrypt(&pkt) {
+                pkt = match cipher_suite.decrypt(&h, &pkt) {
                     Ok(pkt) => pkt,
                     Err(err) => {
                         debug!("{}: decrypt failed: {}", srv_cli_str(self.is_client), err);
@@ -614,6 +787,13 @@ impl DTLSConn {
             }
         }
 
+        // Remembered to prime the replay detector of a connection resumed from the state
+        while self.state.remote_sequence_number.len() <= h.epoch as usize {
+            self.state.remote_sequence_number.push(0);
+        }
+        let remote_sequence_number = &mut self.state.remote_sequence_number[h.epoch as usize];
+        *remote_sequence_number = (*remote_sequence_number).max(h.sequence_number + 1);
+
         let is_handshake = match self.fragment_buffer.push(&pkt) {
             Ok(is_handshake) => is_handshake,
             Err(err) => {
@@ -726,6 +906,10 @@ impl DTLSConn {
 
                 if epoch + 1 == new_remote_epoch {
                     self.state.remote_epoch = new_remote_epoch;
+                    self.events.push_back(DTLSEvent::EpochChanged {
+                        old: epoch,
+                        new: new_remote_epoch,
+                    });
                     self.replay_detector[h.epoch as usize].accept();
                 }
             }
@@ -773,6 +957,10 @@ impl DTLSConn {
     }
 
     pub(crate) fn set_local_epoch(&mut self, epoch: u16) {
+        if self.state.local_epoch != epoch {
+            self.events
+                .push_back(DTLSEvent::LocalEpochChanged { epoch });
+        }
         self.state.local_epoch = epoch;
     }
 
@@ -13,6 +13,7 @@ pub enum ContentType {
     Alert = 21,
     Handshake = 22,
     ApplicationData = 23,
+    Tls12Cid = 25, // https://www.rfc-editor.org/rfc/rfc9146#section-4
     #[default]
     Invalid,
 }
@@ -24,6 +25,7 @@ impl From<u8> for ContentType {
             21 => ContentType::Alert,
             22 => ContentType::Handshake,
             23 => ContentType::ApplicationData,
+            25 => ContentType::Tls12Cid,
             _ => ContentType::Invalid,
         }
     }
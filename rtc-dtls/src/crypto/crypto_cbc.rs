@@ -11,10 +11,10 @@
 use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
 use p256::elliptic_curve::subtle::ConstantTimeEq;
 use rand::Rng;
-use std::io::Cursor;
 use std::ops::Not;
 
 use super::padding::DtlsPadding;
+use super::{generate_aead_additional_data, plaintext_record, protected_record};
 use crate::content::*;
 use crate::prf::*;
 use crate::record_layer::record_layer_header::*;
@@ -51,17 +51,11 @@ impl CryptoCbc {
     }
 
     pub fn encrypt(&self, pkt_rlh: &RecordLayerHeader, raw: &[u8]) -> Result<Vec<u8>> {
-        let mut payload = raw[RECORD_LAYER_HEADER_SIZE..].to_vec();
-        let raw = &raw[..RECORD_LAYER_HEADER_SIZE];
+        let (mut h, mut payload) = protected_record(pkt_rlh, raw);
 
         // Generate + Append MAC
-        let h = pkt_rlh;
-
         let mac = prf_mac(
-            h.epoch,
-            h.sequence_number,
-            h.content_type,
-            h.protocol_version,
+            &generate_aead_additional_data(&h, payload.len()),
             &payload,
             &self.write_mac,
         )?;
@@ -74,27 +68,23 @@ impl CryptoCbc {
         let encrypted = write_cbc.encrypt_padded_vec_mut::<DtlsPadding>(&payload);
 
         // Prepend unencrypte header with encrypted payload
+        h.content_len = (iv.len() + encrypted.len()) as u16;
+
         let mut r = vec![];
-        r.extend_from_slice(raw);
+        h.marshal(&mut r)?;
         r.extend_from_slice(&iv);
         r.extend_from_slice(&encrypted);
 
-        let r_len = (r.len() - RECORD_LAYER_HEADER_SIZE) as u16;
-        r[RECORD_LAYER_HEADER_SIZE - 2..RECORD_LAYER_HEADER_SIZE]
-            .copy_from_slice(&r_len.to_be_bytes());
-
         Ok(r)
     }
 
-    pub fn decrypt(&self, r: &[u8]) -> Result<Vec<u8>> {
-        let mut reader = Cursor::new(r);
-        let h = RecordLayerHeader::unmarshal(&mut reader)?;
+    pub fn decrypt(&self, h: &RecordLayerHeader, r: &[u8]) -> Result<Vec<u8>> {
         if h.content_type == ContentType::ChangeCipherSpec {
             // Nothing to encrypt with ChangeCipherSpec
             return Ok(r.to_vec());
         }
 
-        let body = &r[RECORD_LAYER_HEADER_SIZE..];
+        let body = &r[h.size()..];
         let iv = &body[0..Self::BLOCK_SIZE];
         let body = &body[Self::BLOCK_SIZE..];
         //TODO: add body.len() check
@@ -108,10 +98,7 @@ impl CryptoCbc {
         let recv_mac = &decrypted[decrypted.len() - Self::MAC_SIZE..];
         let decrypted = &decrypted[0..decrypted.len() - Self::MAC_SIZE];
         let mac = prf_mac(
-            h.epoch,
-            h.sequence_number,
-            h.content_type,
-            h.protocol_version,
+            &generate_aead_additional_data(h, decrypted.len()),
             decrypted,
             &self.read_mac,
         )?;
@@ -120,10 +107,6 @@ impl CryptoCbc {
             return Err(Error::ErrInvalidMac);
         }
 
-        let mut d = Vec::with_capacity(RECORD_LAYER_HEADER_SIZE + decrypted.len());
-        d.extend_from_slice(&r[..RECORD_LAYER_HEADER_SIZE]);
-        d.extend_from_slice(decrypted);
-
-        Ok(d)
+        plaintext_record(h, r, decrypted)
     }
 }
@@ -8,8 +8,6 @@
 // https://github.com/RustCrypto/AEADs
 // https://docs.rs/ccm/0.3.0/ccm/ Or https://crates.io/crates/aes-ccm?
 
-use std::io::Cursor;
-
 use aes::Aes128;
 use ccm::aead::generic_array::GenericArray;
 use ccm::aead::AeadInPlace;
@@ -104,18 +102,16 @@ impl CryptoCcm {
     }
 
     pub fn encrypt(&self, pkt_rlh: &RecordLayerHeader, raw: &[u8]) -> Result<Vec<u8>> {
-        let payload = &raw[RECORD_LAYER_HEADER_SIZE..];
-        let raw = &raw[..RECORD_LAYER_HEADER_SIZE];
+        let (mut h, payload) = protected_record(pkt_rlh, raw);
 
         let mut nonce = vec![0u8; CRYPTO_CCM_NONCE_LENGTH];
         nonce[..4].copy_from_slice(&self.local_write_iv[..4]);
         rand::thread_rng().fill(&mut nonce[4..]);
         let nonce = GenericArray::from_slice(&nonce);
 
-        let additional_data = generate_aead_additional_data(pkt_rlh, payload.len());
+        let additional_data = generate_aead_additional_data(&h, payload.len());
 
-        let mut buffer: Vec<u8> = Vec::new();
-        buffer.extend_from_slice(payload);
+        let mut buffer = payload;
 
         match &self.local_ccm {
             CryptoCcmType::CryptoCcm(ccm) => {
@@ -128,61 +124,54 @@ impl CryptoCcm {
             }
         }
 
-        let mut r = Vec::with_capacity(raw.len() + nonce.len() + buffer.len());
+        // Update recordLayer size to include explicit nonce
+        h.content_len = (nonce.len() - 4 + buffer.len()) as u16;
 
-        r.extend_from_slice(raw);
+        let mut r = Vec::with_capacity(h.size() + h.content_len as usize);
+        h.marshal(&mut r)?;
         r.extend_from_slice(&nonce[4..]);
         r.extend_from_slice(&buffer);
 
-        // Update recordLayer size to include explicit nonce
-        let r_len = (r.len() - RECORD_LAYER_HEADER_SIZE) as u16;
-        r[RECORD_LAYER_HEADER_SIZE - 2..RECORD_LAYER_HEADER_SIZE]
-            .copy_from_slice(&r_len.to_be_bytes());
-
         Ok(r)
     }
 
-    pub fn decrypt(&self, r: &[u8]) -> Result<Vec<u8>> {
-        let mut reader = Cursor::new(r);
-        let h = RecordLayerHeader::unmarshal(&mut reader)?;
+    pub fn decrypt(&self, h: &RecordLayerHeader, r: &[u8]) -> Result<Vec<u8>> {
         if h.content_type == ContentType::ChangeCipherSpec {
             // Nothing to encrypt with ChangeCipherSpec
             return Ok(r.to_vec());
         }
 
-        if r.len() <= (RECORD_LAYER_HEADER_SIZE + 8) {
+        let header_size = h.size();
+        let tag_length = match &self.remote_ccm {
+            CryptoCcmType::CryptoCcm(_) => CRYPTO_CCM_TAG_LENGTH,
+            CryptoCcmType::CryptoCcm8(_) => CRYPTO_CCM_8_TAG_LENGTH,
+        };
+        if r.len() <= (header_size + 8) || r.len() < header_size + 8 + tag_length {
             return Err(Error::ErrNotEnoughRoomForNonce);
         }
 
         let mut nonce = vec![];
         nonce.extend_from_slice(&self.remote_write_iv[..4]);
-        nonce.extend_from_slice(&r[RECORD_LAYER_HEADER_SIZE..RECORD_LAYER_HEADER_SIZE + 8]);
+        nonce.extend_from_slice(&r[header_size..header_size + 8]);
         let nonce = GenericArray::from_slice(&nonce);
 
-        let out = &r[RECORD_LAYER_HEADER_SIZE + 8..];
+        let out = &r[header_size + 8..];
+        let additional_data = generate_aead_additional_data(h, out.len() - tag_length);
 
         let mut buffer: Vec<u8> = Vec::new();
         buffer.extend_from_slice(out);
 
         match &self.remote_ccm {
             CryptoCcmType::CryptoCcm(ccm) => {
-                let additional_data =
-                    generate_aead_additional_data(&h, out.len() - CRYPTO_CCM_TAG_LENGTH);
                 ccm.decrypt_in_place(nonce, &additional_data, &mut buffer)
                     .map_err(|e| Error::Other(e.to_string()))?;
             }
             CryptoCcmType::CryptoCcm8(ccm8) => {
-                let additional_data =
-                    generate_aead_additional_data(&h, out.len() - CRYPTO_CCM_8_TAG_LENGTH);
                 ccm8.decrypt_in_place(nonce, &additional_data, &mut buffer)
                     .map_err(|e| Error::Other(e.to_string()))?;
             }
         }
 
-        let mut d = Vec::with_capacity(RECORD_LAYER_HEADER_SIZE + buffer.len());
-        d.extend_from_slice(&r[..RECORD_LAYER_HEADER_SIZE]);
-        d.extend_from_slice(&buffer);
-
-        Ok(d)
+        plaintext_record(h, r, &buffer)
     }
 }
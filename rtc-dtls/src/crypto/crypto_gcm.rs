@@ -6,8 +6,6 @@
 // https://github.com/RustCrypto/AEADs
 // https://docs.rs/aes-gcm/0.8.0/aes_gcm/
 
-use std::io::Cursor;
-
 use aes_gcm::aead::generic_array::GenericArray;
 use aes_gcm::aead::AeadInPlace;
 use aes_gcm::{Aes128Gcm, KeyInit};
@@ -52,56 +50,51 @@ impl CryptoGcm {
     }
 
     pub fn encrypt(&self, pkt_rlh: &RecordLayerHeader, raw: &[u8]) -> Result<Vec<u8>> {
-        let payload = &raw[RECORD_LAYER_HEADER_SIZE..];
-        let raw = &raw[..RECORD_LAYER_HEADER_SIZE];
+        let (mut h, payload) = protected_record(pkt_rlh, raw);
 
         let mut nonce = vec![0u8; CRYPTO_GCM_NONCE_LENGTH];
         nonce[..4].copy_from_slice(&self.local_write_iv[..4]);
         rand::thread_rng().fill(&mut nonce[4..]);
         let nonce = GenericArray::from_slice(&nonce);
 
-        let additional_data = generate_aead_additional_data(pkt_rlh, payload.len());
+        let additional_data = generate_aead_additional_data(&h, payload.len());
 
-        let mut buffer: Vec<u8> = Vec::new();
-        buffer.extend_from_slice(payload);
+        let mut buffer = payload;
 
         self.local_gcm
             .encrypt_in_place(nonce, &additional_data, &mut buffer)
             .map_err(|e| Error::Other(e.to_string()))?;
 
-        let mut r = Vec::with_capacity(raw.len() + nonce.len() + buffer.len());
-        r.extend_from_slice(raw);
+        // Update recordLayer size to include explicit nonce
+        h.content_len = (nonce.len() - 4 + buffer.len()) as u16;
+
+        let mut r = Vec::with_capacity(h.size() + h.content_len as usize);
+        h.marshal(&mut r)?;
         r.extend_from_slice(&nonce[4..]);
         r.extend_from_slice(&buffer);
 
-        // Update recordLayer size to include explicit nonce
-        let r_len = (r.len() - RECORD_LAYER_HEADER_SIZE) as u16;
-        r[RECORD_LAYER_HEADER_SIZE - 2..RECORD_LAYER_HEADER_SIZE]
-            .copy_from_slice(&r_len.to_be_bytes());
-
         Ok(r)
     }
 
-    pub fn decrypt(&self, r: &[u8]) -> Result<Vec<u8>> {
-        let mut reader = Cursor::new(r);
-        let h = RecordLayerHeader::unmarshal(&mut reader)?;
+    pub fn decrypt(&self, h: &RecordLayerHeader, r: &[u8]) -> Result<Vec<u8>> {
         if h.content_type == ContentType::ChangeCipherSpec {
             // Nothing to encrypt with ChangeCipherSpec
             return Ok(r.to_vec());
         }
 
-        if r.len() <= (RECORD_LAYER_HEADER_SIZE + 8) {
+        let header_size = h.size();
+        if r.len() <= (header_size + 8) || r.len() < header_size + 8 + CRYPTO_GCM_TAG_LENGTH {
             return Err(Error::ErrNotEnoughRoomForNonce);
         }
 
         let mut nonce = vec![];
         nonce.extend_from_slice(&self.remote_write_iv[..4]);
-        nonce.extend_from_slice(&r[RECORD_LAYER_HEADER_SIZE..RECORD_LAYER_HEADER_SIZE + 8]);
+        nonce.extend_from_slice(&r[header_size..header_size + 8]);
         let nonce = GenericArray::from_slice(&nonce);
 
-        let out = &r[RECORD_LAYER_HEADER_SIZE + 8..];
+        let out = &r[header_size + 8..];
 
-        let additional_data = generate_aead_additional_data(&h, out.len() - CRYPTO_GCM_TAG_LENGTH);
+        let additional_data = generate_aead_additional_data(h, out.len() - CRYPTO_GCM_TAG_LENGTH);
 
         let mut buffer: Vec<u8> = Vec::new();
         buffer.extend_from_slice(out);
@@ -110,10 +103,6 @@ impl CryptoGcm {
             .decrypt_in_place(nonce, &additional_data, &mut buffer)
             .map_err(|e| Error::Other(e.to_string()))?;
 
-        let mut d = Vec::with_capacity(RECORD_LAYER_HEADER_SIZE + buffer.len());
-        d.extend_from_slice(&r[..RECORD_LAYER_HEADER_SIZE]);
-        d.extend_from_slice(&buffer);
-
-        Ok(d)
+        plaintext_record(h, r, &buffer)
     }
 }
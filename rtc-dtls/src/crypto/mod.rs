@@ -16,6 +16,7 @@ use rcgen::KeyPair;
 use ring::rand::SystemRandom;
 use ring::signature::{EcdsaKeyPair, Ed25519KeyPair};
 
+use crate::content::ContentType;
 use crate::curve::named_curve::*;
 use crate::record_layer::record_layer_header::*;
 use crate::signature_hash_algorithm::{HashAlgorithm, SignatureAlgorithm, SignatureHashAlgorithm};
@@ -491,6 +492,22 @@ pub(crate) fn verify_server_cert(
 }
 
 pub(crate) fn generate_aead_additional_data(h: &RecordLayerHeader, payload_len: usize) -> Vec<u8> {
+    if h.content_type == ContentType::Tls12Cid {
+        // https://www.rfc-editor.org/rfc/rfc9146#section-5
+        let mut additional_data = Vec::with_capacity(23 + h.connection_id.len());
+        additional_data.extend_from_slice(&[0xff; 8]); // seq_num_placeholder
+        additional_data.push(ContentType::Tls12Cid as u8);
+        additional_data.push(h.connection_id.len() as u8);
+        additional_data.push(ContentType::Tls12Cid as u8);
+        additional_data.push(h.protocol_version.major);
+        additional_data.push(h.protocol_version.minor);
+        additional_data.extend_from_slice(&h.epoch.to_be_bytes());
+        additional_data.extend_from_slice(&h.sequence_number.to_be_bytes()[2..]);
+        additional_data.extend_from_slice(&h.connection_id);
+        additional_data.extend_from_slice(&(payload_len as u16).to_be_bytes());
+        return additional_data;
+    }
+
     let mut additional_data = vec![0u8; 13];
     // SequenceNumber MUST be set first
     // we only want uint48, clobbering an extra 2 (using uint64, rust doesn't have uint48)
@@ -504,6 +521,57 @@ pub(crate) fn generate_aead_additional_data(h: &RecordLayerHeader, payload_len:
     additional_data
 }
 
+// protected_record returns the header to send a plaintext record with and the payload to
+// protect. A record to protect with a connection id is sent as a tls12_cid record, whose
+// payload is the DTLSInnerPlaintext: the content followed by its real type, unpadded.
+// https://www.rfc-editor.org/rfc/rfc9146#section-4
+pub(crate) fn protected_record(
+    pkt_rlh: &RecordLayerHeader,
+    raw: &[u8],
+) -> (RecordLayerHeader, Vec<u8>) {
+    let mut payload = raw[RECORD_LAYER_HEADER_SIZE..].to_vec();
+    if pkt_rlh.connection_id.is_empty() {
+        return (*pkt_rlh, payload);
+    }
+
+    payload.push(pkt_rlh.content_type as u8);
+    (
+        RecordLayerHeader {
+            content_type: ContentType::Tls12Cid,
+            ..*pkt_rlh
+        },
+        payload,
+    )
+}
+
+// plaintext_record rebuilds the plaintext record of the payload decrypted from the record r
+// with header h. The real type of a tls12_cid record is the last non-zero byte of its
+// DTLSInnerPlaintext, and the record is rebuilt with the header of other records.
+pub(crate) fn plaintext_record(h: &RecordLayerHeader, r: &[u8], payload: &[u8]) -> Result<Vec<u8>> {
+    if h.content_type != ContentType::Tls12Cid {
+        let mut d = Vec::with_capacity(RECORD_LAYER_HEADER_SIZE + payload.len());
+        d.extend_from_slice(&r[..RECORD_LAYER_HEADER_SIZE]);
+        d.extend_from_slice(payload);
+        return Ok(d);
+    }
+
+    let content_len = payload
+        .iter()
+        .rposition(|b| *b != 0)
+        .ok_or(Error::ErrInvalidContentType)?;
+    let header = RecordLayerHeader {
+        content_type: payload[content_len].into(),
+        connection_id: ConnectionId::default(),
+        content_len: content_len as u16,
+        ..*h
+    };
+
+    let mut d = Vec::with_capacity(RECORD_LAYER_HEADER_SIZE + content_len);
+    header.marshal(&mut d)?;
+    d.extend_from_slice(&payload[..content_len]);
+    Ok(d)
+}
+
 #[cfg(test)]
 mod test {
     #[cfg(feature = "pem")]
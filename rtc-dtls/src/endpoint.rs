@@ -1,11 +1,17 @@
+#[cfg(test)]
+mod endpoint_test;
+
 use crate::conn::DTLSConn;
 use shared::error::{Error, Result};
 use shared::{EcnCodepoint, TransportContext};
 use shared::{Protocol, Transmit};
 
 use crate::config::HandshakeConfig;
+use crate::content::ContentType;
+use crate::record_layer::record_layer_header::RECORD_LAYER_HEADER_SIZE;
 use crate::state::State;
 use bytes::BytesMut;
+use log::{debug, warn};
 use std::collections::hash_map::Keys;
 use std::collections::{hash_map::Entry::Vacant, HashMap, VecDeque};
 use std::net::SocketAddr;
@@ -27,6 +33,10 @@ pub struct Endpoint {
     protocol: Protocol,
     transmits: VecDeque<Transmit<BytesMut>>,
     connections: HashMap<SocketAddr, DTLSConn>,
+    // remote address of the connections by the connection id their peer sends records with
+    connection_ids: HashMap<Vec<u8>, SocketAddr>,
+    // length of the connection ids, the same for all the connections of the endpoint
+    connection_id_length: Option<usize>,
     server_config: Option<Arc<HandshakeConfig>>,
 }
 
@@ -44,6 +54,8 @@ impl Endpoint {
             protocol,
             transmits: VecDeque::new(),
             connections: HashMap::new(),
+            connection_ids: HashMap::new(),
+            connection_id_length: None,
             server_config,
         }
     }
@@ -79,6 +91,18 @@ impl Endpoint {
         remote: SocketAddr,
         client_config: Arc<HandshakeConfig>,
         initial_state: Option<State>,
+    ) -> Result<()> {
+        self.connect_at(Instant::now(), remote, client_config, initial_state)
+    }
+
+    /// Initiate an Association at now, which the retransmit timer of the first flight
+    /// is armed from
+    pub fn connect_at(
+        &mut self,
+        now: Instant,
+        remote: SocketAddr,
+        client_config: Arc<HandshakeConfig>,
+        initial_state: Option<State>,
     ) -> Result<()> {
         if remote.port() == 0 {
             return Err(Error::InvalidRemoteAddress(remote));
@@ -86,11 +110,11 @@ impl Endpoint {
 
         if let Vacant(e) = self.connections.entry(remote) {
             let mut conn = DTLSConn::new(client_config, true, initial_state);
-            conn.handshake()?;
+            conn.handshake(now)?;
 
             while let Some(payload) = conn.outgoing_raw_packet() {
                 self.transmits.push_back(Transmit {
-                    now: Instant::now(),
+                    now,
                     transport: TransportContext {
                         local_addr: self.local_addr,
                         peer_addr: remote,
@@ -124,6 +148,7 @@ impl Endpoint {
                 });
             }
         }
+        self.connection_ids.retain(|_, addr| *addr != remote);
         self.connections.remove(&remote)
     }
 
@@ -145,6 +170,8 @@ impl Endpoint {
             }
         }
         self.connections.clear();
+        self.connection_ids.clear();
+        self.connection_id_length = None;
 
         Ok(())
     }
@@ -157,26 +184,110 @@ impl Endpoint {
         ecn: Option<EcnCodepoint>,
         data: BytesMut,
     ) -> Result<Vec<EndpointEvent>> {
+        if !self.connections.contains_key(&remote) {
+            if let Some(previous_remote) = self.find_connection_by_id(&data) {
+                return self.read_migrated(now, previous_remote, remote, ecn, data);
+            }
+        }
+
         if let Vacant(e) = self.connections.entry(remote) {
             if let Some(server_config) = &self.server_config {
                 let handshake_config = server_config.clone();
-                let conn = DTLSConn::new(handshake_config, false, None);
+                let mut conn = DTLSConn::new(handshake_config, false, None);
+                conn.set_remote_addr(remote);
                 e.insert(conn);
             } else {
                 return Err(Error::NoServerConfig);
             }
         }
 
+        self.read_connection(now, remote, ecn, data)
+    }
+
+    // Returns the remote address of the connection a tls12_cid record is sent to.
+    fn find_connection_by_id(&self, data: &[u8]) -> Option<SocketAddr> {
+        if data.first() != Some(&(ContentType::Tls12Cid as u8)) {
+            return None;
+        }
+
+        // the connection id follows the sequence number, its length is not on the wire
+        let offset = RECORD_LAYER_HEADER_SIZE - 2;
+        let connection_id = data.get(offset..offset + self.connection_id_length?)?;
+        self.connection_ids.get(connection_id).copied()
+    }
+
+    // Reads a datagram of a connection from a new remote address. The connection only moves
+    // to the new address when the datagram authenticates a record newer than all the ones
+    // received before, so that neither replayed nor forged records can redirect it. The
+    // datagram is read at the previous address, so nothing is sent to the new one before then.
+    // https://www.rfc-editor.org/rfc/rfc9146#section-6
+    fn read_migrated(
+        &mut self,
+        now: Instant,
+        previous_remote: SocketAddr,
+        remote: SocketAddr,
+        ecn: Option<EcnCodepoint>,
+        data: BytesMut,
+    ) -> Result<Vec<EndpointEvent>> {
+        let Some(conn) = self.connections.get(&previous_remote) else {
+            return Err(Error::InvalidRemoteAddress(previous_remote));
+        };
+        let newest_remote_record = conn.newest_remote_record();
+
+        let messages = self.read_connection(now, previous_remote, ecn, data)?;
+
+        let is_authenticated = self
+            .connections
+            .get(&previous_remote)
+            .is_some_and(|conn| conn.newest_remote_record() > newest_remote_record);
+        if is_authenticated {
+            if let Some(mut conn) = self.connections.remove(&previous_remote) {
+                debug!("connection of {} moved to {}", previous_remote, remote);
+                conn.set_remote_addr(remote);
+                for addr in self.connection_ids.values_mut() {
+                    if *addr == previous_remote {
+                        *addr = remote;
+                    }
+                }
+                self.connections.insert(remote, conn);
+            }
+        }
+
+        Ok(messages)
+    }
+
+    fn read_connection(
+        &mut self,
+        now: Instant,
+        remote: SocketAddr,
+        ecn: Option<EcnCodepoint>,
+        data: BytesMut,
+    ) -> Result<Vec<EndpointEvent>> {
         // Handle packet on existing association, if any
         let mut messages = vec![];
         if let Some(conn) = self.connections.get_mut(&remote) {
             let is_handshake_completed_before = conn.is_handshake_completed();
             conn.read(&data)?;
             if !conn.is_handshake_completed() {
-                conn.handshake()?;
+                conn.handshake(now)?;
                 conn.handle_incoming_queued_packets()?;
             }
             if !is_handshake_completed_before && conn.is_handshake_completed() {
+                let connection_id = &conn.connection_state().local_connection_id;
+                if !connection_id.is_empty() {
+                    let connection_id_length =
+                        *self.connection_id_length.get_or_insert(connection_id.len());
+                    if connection_id.len() == connection_id_length {
+                        self.connection_ids.insert(connection_id.clone(), remote);
+                    } else {
+                        warn!(
+                            "connection id of {} is {} bytes instead of {}, ignoring it",
+                            remote,
+                            connection_id.len(),
+                            connection_id_length
+                        );
+                    }
+                }
                 messages.push(EndpointEvent::HandshakeComplete)
             }
             while let Some(message) = conn.incoming_application_data() {
@@ -0,0 +1,109 @@
+use super::*;
+use crate::config::{random_connection_id_generator, ConfigBuilder};
+use crate::crypto::Certificate;
+use crate::record_layer::unpack_datagram;
+use std::time::Duration;
+
+const CONNECTION_ID_LEN: usize = 8;
+
+fn config(is_client: bool) -> Result<Arc<HandshakeConfig>> {
+    Ok(Arc::new(
+        ConfigBuilder::default()
+            .with_certificates(vec![Certificate::generate_self_signed(vec![
+                "localhost".to_owned()
+            ])?])
+            .with_insecure_skip_verify(true)
+            .with_local_connection_id(random_connection_id_generator(CONNECTION_ID_LEN))
+            .build(is_client, None)?,
+    ))
+}
+
+// delivers the transmits of from to the endpoint they are sent to, as if they were sent from
+// source. Records are read one by one, so that the ChangeCipherSpec and Finished of a flight
+// are not queued before the keys are derived from the preceding records.
+fn deliver(from: &mut Endpoint, to: &mut Endpoint, source: SocketAddr) -> Result<()> {
+    while let Some(transmit) = from.poll_transmit() {
+        for record in unpack_datagram(&transmit.message, CONNECTION_ID_LEN)? {
+            to.read(Instant::now(), source, None, BytesMut::from(&record[..]))?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_endpoint_connection_id_address_update() -> Result<()> {
+    let client_addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+    let new_client_addr: SocketAddr = "127.0.0.1:5001".parse().unwrap();
+    let spoofed_addr: SocketAddr = "127.0.0.1:5002".parse().unwrap();
+    let server_addr: SocketAddr = "127.0.0.1:6000".parse().unwrap();
+
+    let mut client = Endpoint::new(client_addr, Protocol::UDP, None);
+    let mut server = Endpoint::new(server_addr, Protocol::UDP, Some(config(false)?));
+
+    client.connect(server_addr, config(true)?, None)?;
+    for _ in 0..4 {
+        deliver(&mut client, &mut server, client_addr)?;
+        deliver(&mut server, &mut client, server_addr)?;
+    }
+    let state = server
+        .get_connection_state(client_addr)
+        .expect("server connection");
+    assert!(!state.local_connection_id.is_empty());
+
+    // the client moved, e.g. after a NAT rebinding
+    client.write(server_addr, b"ping")?;
+    let transmit = client.poll_transmit().expect("application data");
+    let replayed = transmit.message.clone();
+    let events = server.read(Instant::now(), new_client_addr, None, transmit.message)?;
+    assert!(matches!(
+        &events[..],
+        [EndpointEvent::ApplicationData(data)] if &data[..] == b"ping"
+    ));
+    assert!(server.get_connection_state(client_addr).is_none());
+    assert!(server.get_connection_state(new_client_addr).is_some());
+
+    // replies are sent to the new address
+    server.write(new_client_addr, b"pong")?;
+    let transmit = server.poll_transmit().expect("application data");
+    assert_eq!(transmit.transport.peer_addr, new_client_addr);
+
+    // a replayed record must not move the connection
+    let events = server.read(Instant::now(), spoofed_addr, None, replayed)?;
+    assert!(events.is_empty());
+    assert!(server.get_connection_state(spoofed_addr).is_none());
+    assert!(server.get_connection_state(new_client_addr).is_some());
+
+    // neither must a forged one, nor may anything be sent to its address
+    client.write(server_addr, b"forged")?;
+    let mut forged = client.poll_transmit().expect("application data").message;
+    let last = forged.len() - 1;
+    forged[last] ^= 0xff;
+    let _ = server.read(Instant::now(), spoofed_addr, None, forged);
+    assert!(server.get_connection_state(spoofed_addr).is_none());
+    assert!(server.get_connection_state(new_client_addr).is_some());
+    while let Some(transmit) = server.poll_transmit() {
+        assert_ne!(transmit.transport.peer_addr, spoofed_addr);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_endpoint_connect_at_arms_retransmit_from_now() -> Result<()> {
+    let client_addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+    let server_addr: SocketAddr = "127.0.0.1:6000".parse().unwrap();
+
+    let mut client = Endpoint::new(client_addr, Protocol::UDP, None);
+    let now = Instant::now() + Duration::from_secs(60);
+    client.connect_at(now, server_addr, config(true)?, None)?;
+
+    let transmit = client.poll_transmit().expect("first flight");
+    assert_eq!(transmit.now, now);
+
+    let mut eto = now + Duration::from_secs(3600);
+    client.poll_timeout(server_addr, &mut eto)?;
+    assert!(eto > now);
+    assert!(eto <= now + Duration::from_secs(60));
+
+    Ok(())
+}
@@ -0,0 +1,86 @@
+#[cfg(test)]
+mod extension_alpn_test;
+
+use super::*;
+
+// ExtensionAlpn lists the application protocols a client offers, or the single one a
+// server selected among them.
+// https://www.rfc-editor.org/rfc/rfc7301#section-3.1
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExtensionAlpn {
+    pub(crate) protocol_name_list: Vec<String>,
+}
+
+impl ExtensionAlpn {
+    pub fn extension_value(&self) -> ExtensionValue {
+        ExtensionValue::Alpn
+    }
+
+    pub fn size(&self) -> usize {
+        2 + 2 + self.protocol_name_list_size()
+    }
+
+    fn protocol_name_list_size(&self) -> usize {
+        self.protocol_name_list
+            .iter()
+            .map(|protocol_name| 1 + protocol_name.len())
+            .sum()
+    }
+
+    pub fn marshal<W: Write>(&self, writer: &mut W) -> Result<()> {
+        if self
+            .protocol_name_list
+            .iter()
+            .any(|protocol_name| protocol_name.is_empty() || protocol_name.len() > u8::MAX as usize)
+        {
+            return Err(Error::ErrAlpnInvalidFormat);
+        }
+
+        let protocol_name_list_size = self.protocol_name_list_size();
+        writer.write_u16::<BigEndian>(2 + protocol_name_list_size as u16)?;
+        writer.write_u16::<BigEndian>(protocol_name_list_size as u16)?;
+        for protocol_name in &self.protocol_name_list {
+            writer.write_u8(protocol_name.len() as u8)?;
+            writer.write_all(protocol_name.as_bytes())?;
+        }
+
+        Ok(writer.flush()?)
+    }
+
+    pub fn unmarshal<R: Read>(reader: &mut R) -> Result<Self> {
+        let _ = reader.read_u16::<BigEndian>()?;
+
+        let mut protocol_name_list_size = reader.read_u16::<BigEndian>()? as usize;
+        let mut protocol_name_list = vec![];
+        while protocol_name_list_size > 0 {
+            let protocol_name_len = reader.read_u8()? as usize;
+            if protocol_name_len == 0 || 1 + protocol_name_len > protocol_name_list_size {
+                return Err(Error::ErrAlpnInvalidFormat);
+            }
+
+            let mut buf = vec![0u8; protocol_name_len];
+            reader.read_exact(&mut buf)?;
+            protocol_name_list.push(String::from_utf8(buf)?);
+            protocol_name_list_size -= 1 + protocol_name_len;
+        }
+
+        if protocol_name_list.is_empty() {
+            return Err(Error::ErrAlpnInvalidFormat);
+        }
+
+        Ok(ExtensionAlpn { protocol_name_list })
+    }
+}
+
+// alpn_protocol_selection returns the first protocol offered by the peer that is
+// also supported locally
+pub(crate) fn alpn_protocol_selection(
+    supported_protocols: &[String],
+    peer_supported_protocols: &[String],
+) -> Result<String> {
+    peer_supported_protocols
+        .iter()
+        .find(|protocol| supported_protocols.contains(protocol))
+        .cloned()
+        .ok_or(Error::ErrServerNoMatchingApplicationProtocol)
+}
@@ -0,0 +1,76 @@
+use super::*;
+
+use std::io::{BufReader, BufWriter};
+
+#[test]
+fn test_extension_alpn() -> Result<()> {
+    let raw_extension_alpn = vec![
+        0x00, 0x12, 0x00, 0x10, 0x06, 0x77, 0x65, 0x62, 0x72, 0x74, 0x63, 0x08, 0x63, 0x2d, 0x77,
+        0x65, 0x62, 0x72, 0x74, 0x63,
+    ];
+    let parsed_extension_alpn = ExtensionAlpn {
+        protocol_name_list: vec!["webrtc".to_owned(), "c-webrtc".to_owned()],
+    };
+
+    let mut raw = vec![];
+    {
+        let mut writer = BufWriter::<&mut Vec<u8>>::new(raw.as_mut());
+        parsed_extension_alpn.marshal(&mut writer)?;
+    }
+
+    assert_eq!(
+        raw, raw_extension_alpn,
+        "extension_alpn marshal: got {raw:?}, want {raw_extension_alpn:?}"
+    );
+    assert_eq!(parsed_extension_alpn.size(), raw_extension_alpn.len());
+
+    let mut reader = BufReader::new(raw.as_slice());
+    let new_extension_alpn = ExtensionAlpn::unmarshal(&mut reader)?;
+
+    assert_eq!(
+        new_extension_alpn, parsed_extension_alpn,
+        "extension_alpn unmarshal: got {new_extension_alpn:?}, want {parsed_extension_alpn:?}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_extension_alpn_invalid_format() -> Result<()> {
+    let tests = vec![
+        ("empty protocol name list", vec![0x00, 0x02, 0x00, 0x00]),
+        ("empty protocol name", vec![0x00, 0x03, 0x00, 0x01, 0x00]),
+        (
+            "protocol name overflows the list",
+            vec![0x00, 0x04, 0x00, 0x02, 0x02, 0x68, 0x32],
+        ),
+    ];
+
+    for (name, raw) in tests {
+        let mut reader = BufReader::new(raw.as_slice());
+        assert_eq!(
+            ExtensionAlpn::unmarshal(&mut reader),
+            Err(Error::ErrAlpnInvalidFormat),
+            "{name}"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_alpn_protocol_selection() -> Result<()> {
+    let supported = vec!["c-webrtc".to_owned(), "webrtc".to_owned()];
+
+    assert_eq!(
+        alpn_protocol_selection(&supported, &["webrtc".to_owned(), "c-webrtc".to_owned()])?,
+        "webrtc",
+        "the first protocol offered by the peer must be selected"
+    );
+    assert_eq!(
+        alpn_protocol_selection(&supported, &["h2".to_owned()]),
+        Err(Error::ErrServerNoMatchingApplicationProtocol)
+    );
+
+    Ok(())
+}
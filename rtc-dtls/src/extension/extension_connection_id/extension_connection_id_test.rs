@@ -0,0 +1,32 @@
+use super::*;
+
+use std::io::{BufReader, BufWriter};
+
+#[test]
+fn test_extension_connection_id() -> Result<()> {
+    let raw_extension_connection_id = vec![0x00, 0x05, 0x04, 0x01, 0x02, 0x03, 0x04];
+    let parsed_extension_connection_id = ExtensionConnectionId {
+        connection_id: vec![0x01, 0x02, 0x03, 0x04],
+    };
+
+    let mut raw = vec![];
+    {
+        let mut writer = BufWriter::<&mut Vec<u8>>::new(raw.as_mut());
+        parsed_extension_connection_id.marshal(&mut writer)?;
+    }
+
+    assert_eq!(
+        raw, raw_extension_connection_id,
+        "extension_connection_id marshal: got {raw:?}, want {raw_extension_connection_id:?}"
+    );
+
+    let mut reader = BufReader::new(raw.as_slice());
+    let new_extension_connection_id = ExtensionConnectionId::unmarshal(&mut reader)?;
+
+    assert_eq!(
+        new_extension_connection_id, parsed_extension_connection_id,
+        "extension_connection_id unmarshal: got {new_extension_connection_id:?}, want {parsed_extension_connection_id:?}"
+    );
+
+    Ok(())
+}
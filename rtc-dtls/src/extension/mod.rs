@@ -1,3 +1,5 @@
+pub mod extension_alpn;
+pub mod extension_connection_id;
 pub mod extension_server_name;
 pub mod extension_supported_elliptic_curves;
 pub mod extension_supported_point_formats;
@@ -6,6 +8,8 @@ pub mod extension_use_extended_master_secret;
 pub mod extension_use_srtp;
 pub mod renegotiation_info;
 
+use extension_alpn::*;
+use extension_connection_id::*;
 use extension_server_name::*;
 use extension_supported_elliptic_curves::*;
 use extension_supported_point_formats::*;
@@ -27,7 +31,9 @@ pub enum ExtensionValue {
     SupportedPointFormats = 11,
     SupportedSignatureAlgorithms = 13,
     UseSrtp = 14,
+    Alpn = 16,
     UseExtendedMasterSecret = 23,
+    ConnectionId = 54,
     RenegotiationInfo = 65281,
     Unsupported,
 }
@@ -40,7 +46,9 @@ impl From<u16> for ExtensionValue {
             11 => ExtensionValue::SupportedPointFormats,
             13 => ExtensionValue::SupportedSignatureAlgorithms,
             14 => ExtensionValue::UseSrtp,
+            16 => ExtensionValue::Alpn,
             23 => ExtensionValue::UseExtendedMasterSecret,
+            54 => ExtensionValue::ConnectionId,
             65281 => ExtensionValue::RenegotiationInfo,
             _ => ExtensionValue::Unsupported,
         }
@@ -54,7 +62,9 @@ pub enum Extension {
     SupportedPointFormats(ExtensionSupportedPointFormats),
     SupportedSignatureAlgorithms(ExtensionSupportedSignatureAlgorithms),
     UseSrtp(ExtensionUseSrtp),
+    Alpn(ExtensionAlpn),
     UseExtendedMasterSecret(ExtensionUseExtendedMasterSecret),
+    ConnectionId(ExtensionConnectionId),
     RenegotiationInfo(ExtensionRenegotiationInfo),
 }
 
@@ -66,7 +76,9 @@ impl Extension {
             Extension::SupportedPointFormats(ext) => ext.extension_value(),
             Extension::SupportedSignatureAlgorithms(ext) => ext.extension_value(),
             Extension::UseSrtp(ext) => ext.extension_value(),
+            Extension::Alpn(ext) => ext.extension_value(),
             Extension::UseExtendedMasterSecret(ext) => ext.extension_value(),
+            Extension::ConnectionId(ext) => ext.extension_value(),
             Extension::RenegotiationInfo(ext) => ext.extension_value(),
         }
     }
@@ -80,7 +92,9 @@ impl Extension {
             Extension::SupportedPointFormats(ext) => ext.size(),
             Extension::SupportedSignatureAlgorithms(ext) => ext.size(),
             Extension::UseSrtp(ext) => ext.size(),
+            Extension::Alpn(ext) => ext.size(),
             Extension::UseExtendedMasterSecret(ext) => ext.size(),
+            Extension::ConnectionId(ext) => ext.size(),
             Extension::RenegotiationInfo(ext) => ext.size(),
         };
 
@@ -95,7 +109,9 @@ impl Extension {
             Extension::SupportedPointFormats(ext) => ext.marshal(writer),
             Extension::SupportedSignatureAlgorithms(ext) => ext.marshal(writer),
             Extension::UseSrtp(ext) => ext.marshal(writer),
+            Extension::Alpn(ext) => ext.marshal(writer),
             Extension::UseExtendedMasterSecret(ext) => ext.marshal(writer),
+            Extension::ConnectionId(ext) => ext.marshal(writer),
             Extension::RenegotiationInfo(ext) => ext.marshal(writer),
         }
     }
@@ -118,9 +134,13 @@ impl Extension {
                 ))
             }
             ExtensionValue::UseSrtp => Ok(Extension::UseSrtp(ExtensionUseSrtp::unmarshal(reader)?)),
+            ExtensionValue::Alpn => Ok(Extension::Alpn(ExtensionAlpn::unmarshal(reader)?)),
             ExtensionValue::UseExtendedMasterSecret => Ok(Extension::UseExtendedMasterSecret(
                 ExtensionUseExtendedMasterSecret::unmarshal(reader)?,
             )),
+            ExtensionValue::ConnectionId => Ok(Extension::ConnectionId(
+                ExtensionConnectionId::unmarshal(reader)?,
+            )),
             ExtensionValue::RenegotiationInfo => Ok(Extension::RenegotiationInfo(
                 ExtensionRenegotiationInfo::unmarshal(reader)?,
             )),
@@ -1,7 +1,9 @@
 use super::flight2::*;
+use super::flight4::*;
 use super::*;
 use crate::config::*;
 use crate::conn::*;
+use crate::extension::extension_alpn::*;
 use crate::extension::*;
 use crate::handshake::*;
 use crate::record_layer::record_layer_header::*;
@@ -9,7 +11,6 @@ use crate::*;
 use shared::error::Error;
 
 use log::debug;
-use rand::Rng;
 use std::fmt;
 
 #[derive(Debug, PartialEq)]
@@ -71,6 +72,21 @@ impl Flight for Flight0 {
 
             state.remote_random = client_hello.random.clone();
 
+            if !cfg.disable_cookie {
+                state.cookie = match cfg.generate_cookie(state.remote_addr, &client_hello.random) {
+                    Ok(cookie) => cookie,
+                    Err(err) => {
+                        return Err((
+                            Some(Alert {
+                                alert_level: AlertLevel::Fatal,
+                                alert_description: AlertDescription::InternalError,
+                            }),
+                            Some(err),
+                        ))
+                    }
+                };
+            }
+
             if let Ok(id) =
                 find_matching_cipher_suite(&client_hello.cipher_suites, &cfg.local_cipher_suites)
             {
@@ -92,6 +108,9 @@ impl Flight for Flight0 {
                 ));
             }
 
+            // only the ClientHello carrying the cookie negotiates the Extended Master Secret
+            state.extended_master_secret = false;
+            state.negotiated_protocol = None;
             for extension in &client_hello.extensions {
                 match extension {
                     Extension::SupportedEllipticCurves(e) => {
@@ -130,10 +149,38 @@ impl Flight for Flight0 {
                     Extension::ServerName(e) => {
                         state.server_name.clone_from(&e.server_name); // remote server name
                     }
+                    Extension::ConnectionId(e) if cfg.support_connection_id => {
+                        state.remote_connection_id = Some(e.connection_id.clone());
+                    }
+                    Extension::Alpn(e) if !cfg.supported_protocols.is_empty() => {
+                        match alpn_protocol_selection(
+                            &cfg.supported_protocols,
+                            &e.protocol_name_list,
+                        ) {
+                            Ok(protocol) => state.negotiated_protocol = Some(protocol),
+                            Err(err) => {
+                                return Err((
+                                    Some(Alert {
+                                        alert_level: AlertLevel::Fatal,
+                                        alert_description: AlertDescription::NoApplicationProtocol,
+                                    }),
+                                    Some(err),
+                                ))
+                            }
+                        }
+                    }
                     _ => {}
                 }
             }
 
+            if state.remote_connection_id.is_some() {
+                state.local_connection_id = cfg
+                    .local_connection_id
+                    .as_ref()
+                    .map(|generator| generator())
+                    .unwrap_or_default();
+            }
+
             if cfg.extended_master_secret == ExtendedMasterSecretType::Require
                 && !state.extended_master_secret
             {
@@ -142,7 +189,7 @@ impl Flight for Flight0 {
                         alert_level: AlertLevel::Fatal,
                         alert_description: AlertDescription::InsufficientSecurity,
                     }),
-                    Some(Error::ErrServerRequiredButNoClientEms),
+                    Some(Error::ErrClientNoExtendedMasterSecret),
                 ));
             }
 
@@ -161,7 +208,11 @@ impl Flight for Flight0 {
                 };
             }
 
-            Ok(Box::new(Flight2 {}))
+            if cfg.disable_cookie {
+                Ok(Box::new(Flight4 {}))
+            } else {
+                Ok(Box::new(Flight2 {}))
+            }
         } else {
             Err((
                 Some(Alert {
@@ -180,8 +231,9 @@ impl Flight for Flight0 {
         _cfg: &HandshakeConfig,
     ) -> Result<Vec<Packet>, (Option<Alert>, Option<Error>)> {
         // Initialize
-        state.cookie = vec![0; COOKIE_LENGTH];
-        rand::thread_rng().fill(state.cookie.as_mut_slice());
+        state.cookie = vec![];
+        state.local_connection_id = vec![];
+        state.remote_connection_id = None;
 
         state.local_epoch = 0;
         state.remote_epoch = 0;
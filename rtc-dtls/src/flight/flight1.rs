@@ -5,6 +5,8 @@ use crate::config::*;
 use crate::conn::*;
 use crate::content::*;
 use crate::curve::named_curve::*;
+use crate::extension::extension_alpn::*;
+use crate::extension::extension_connection_id::*;
 use crate::extension::extension_server_name::*;
 use crate::extension::extension_supported_elliptic_curves::*;
 use crate::extension::extension_supported_point_formats::*;
@@ -121,6 +123,12 @@ impl Flight for Flight1 {
         state.named_curve = DEFAULT_NAMED_CURVE;
         state.cookie = vec![];
         state.local_random.populate();
+        state.local_connection_id = cfg
+            .local_connection_id
+            .as_ref()
+            .map(|generator| generator())
+            .unwrap_or_default();
+        state.remote_connection_id = None;
 
         let mut extensions = vec![
             Extension::SupportedSignatureAlgorithms(ExtensionSupportedSignatureAlgorithms {
@@ -148,6 +156,12 @@ impl Flight for Flight1 {
             }));
         }
 
+        if !cfg.supported_protocols.is_empty() {
+            extensions.push(Extension::Alpn(ExtensionAlpn {
+                protocol_name_list: cfg.supported_protocols.clone(),
+            }));
+        }
+
         if cfg.extended_master_secret == ExtendedMasterSecretType::Request
             || cfg.extended_master_secret == ExtendedMasterSecretType::Require
         {
@@ -162,6 +176,12 @@ impl Flight for Flight1 {
             }));
         }
 
+        if cfg.support_connection_id {
+            extensions.push(Extension::ConnectionId(ExtensionConnectionId {
+                connection_id: state.local_connection_id.clone(),
+            }));
+        }
+
         Ok(vec![Packet {
             record: RecordLayer::new(
                 PROTOCOL_VERSION1_2,
@@ -79,7 +79,25 @@ impl Flight for Flight2 {
                 return Err((None, None));
             }
 
-            if state.cookie != client_hello.cookie {
+            // The cookie is verified against the secret rather than state.cookie, since it may
+            // have been handed out before the secret rotated
+            let valid_cookie = match cfg.verify_cookie(
+                &client_hello.cookie,
+                state.remote_addr,
+                &client_hello.random,
+            ) {
+                Ok(valid_cookie) => valid_cookie,
+                Err(err) => {
+                    return Err((
+                        Some(Alert {
+                            alert_level: AlertLevel::Fatal,
+                            alert_description: AlertDescription::InternalError,
+                        }),
+                        Some(err),
+                    ))
+                }
+            };
+            if !valid_cookie {
                 return Err((
                     Some(Alert {
                         alert_level: AlertLevel::Fatal,
@@ -4,6 +4,8 @@ use crate::compression_methods::*;
 use crate::config::*;
 use crate::content::*;
 use crate::curve::named_curve::*;
+use crate::extension::extension_alpn::*;
+use crate::extension::extension_connection_id::*;
 use crate::extension::extension_server_name::*;
 use crate::extension::extension_supported_elliptic_curves::*;
 use crate::extension::extension_supported_point_formats::*;
@@ -183,6 +185,8 @@ impl Flight for Flight3 {
                 ));
             }
 
+            state.extended_master_secret = false;
+            state.negotiated_protocol = None;
             for extension in &h.extensions {
                 match extension {
                     Extension::UseSrtp(e) => {
@@ -208,10 +212,36 @@ impl Flight for Flight3 {
                             state.extended_master_secret = true;
                         }
                     }
+                    Extension::ConnectionId(e) if cfg.support_connection_id => {
+                        state.remote_connection_id = Some(e.connection_id.clone());
+                    }
+                    Extension::Alpn(e) => {
+                        // the server must select exactly one of the offered protocols
+                        // https://www.rfc-editor.org/rfc/rfc7301#section-3.1
+                        match &e.protocol_name_list[..] {
+                            [protocol] if cfg.supported_protocols.contains(protocol) => {
+                                state.negotiated_protocol = Some(protocol.clone());
+                            }
+                            _ => {
+                                return Err((
+                                    Some(Alert {
+                                        alert_level: AlertLevel::Fatal,
+                                        alert_description: AlertDescription::IllegalParameter,
+                                    }),
+                                    Some(Error::ErrClientNoMatchingApplicationProtocol),
+                                ))
+                            }
+                        }
+                    }
                     _ => {}
                 };
             }
 
+            // The server sends plain records unless it accepted the connection id
+            if state.remote_connection_id.is_none() {
+                state.local_connection_id.clear();
+            }
+
             if cfg.extended_master_secret == ExtendedMasterSecretType::Require
                 && !state.extended_master_secret
             {
@@ -220,7 +250,7 @@ impl Flight for Flight3 {
                         alert_level: AlertLevel::Fatal,
                         alert_description: AlertDescription::InsufficientSecurity,
                     }),
-                    Some(Error::ErrClientRequiredButNoServerEms),
+                    Some(Error::ErrServerNoExtendedMasterSecret),
                 ));
             }
             if !cfg.local_srtp_protection_profiles.is_empty()
@@ -365,6 +395,12 @@ impl Flight for Flight3 {
             }));
         }
 
+        if !cfg.supported_protocols.is_empty() {
+            extensions.push(Extension::Alpn(ExtensionAlpn {
+                protocol_name_list: cfg.supported_protocols.clone(),
+            }));
+        }
+
         if cfg.extended_master_secret == ExtendedMasterSecretType::Request
             || cfg.extended_master_secret == ExtendedMasterSecretType::Require
         {
@@ -379,6 +415,12 @@ impl Flight for Flight3 {
             }));
         }
 
+        if cfg.support_connection_id {
+            extensions.push(Extension::ConnectionId(ExtensionConnectionId {
+                connection_id: state.local_connection_id.clone(),
+            }));
+        }
+
         Ok(vec![Packet {
             record: RecordLayer::new(
                 PROTOCOL_VERSION1_2,
@@ -8,6 +8,8 @@ use crate::content::*;
 use crate::crypto::*;
 use crate::curve::named_curve::*;
 use crate::curve::*;
+use crate::extension::extension_alpn::*;
+use crate::extension::extension_connection_id::*;
 use crate::extension::extension_supported_elliptic_curves::*;
 use crate::extension::extension_supported_point_formats::*;
 use crate::extension::extension_use_extended_master_secret::*;
@@ -448,7 +450,7 @@ impl Flight for Flight4 {
                     return Err((
                         Some(Alert {
                             alert_level: AlertLevel::Fatal,
-                            alert_description: AlertDescription::NoCertificate,
+                            alert_description: AlertDescription::CertificateRequired,
                         }),
                         Some(Error::ErrClientCertificateRequired),
                     ));
@@ -470,7 +472,7 @@ impl Flight for Flight4 {
                     return Err((
                         Some(Alert {
                             alert_level: AlertLevel::Fatal,
-                            alert_description: AlertDescription::NoCertificate,
+                            alert_description: AlertDescription::CertificateRequired,
                         }),
                         Some(Error::ErrClientCertificateRequired),
                     ));
@@ -517,6 +519,12 @@ impl Flight for Flight4 {
             }));
         }
 
+        if let Some(protocol) = &state.negotiated_protocol {
+            extensions.push(Extension::Alpn(ExtensionAlpn {
+                protocol_name_list: vec![protocol.clone()],
+            }));
+        }
+
         if cfg.local_psk_callback.is_none() {
             extensions.extend_from_slice(&[
                 Extension::SupportedEllipticCurves(ExtensionSupportedEllipticCurves {
@@ -528,6 +536,12 @@ impl Flight for Flight4 {
             ]);
         }
 
+        if state.remote_connection_id.is_some() {
+            extensions.push(Extension::ConnectionId(ExtensionConnectionId {
+                connection_id: state.local_connection_id.clone(),
+            }));
+        }
+
         let mut pkts = vec![Packet {
             record: RecordLayer::new(
                 PROTOCOL_VERSION1_2,
@@ -762,7 +776,7 @@ mod tests {
         fn encrypt(&self, _pkt_rlh: &RecordLayerHeader, _raw: &[u8]) -> Result<Vec<u8>> {
             unimplemented!();
         }
-        fn decrypt(&self, _input: &[u8]) -> Result<Vec<u8>> {
+        fn decrypt(&self, _pkt_rlh: &RecordLayerHeader, _input: &[u8]) -> Result<Vec<u8>> {
             unimplemented!();
         }
     }
@@ -172,3 +172,114 @@ fn test_fragment_buffer_overflow() -> Result<()> {
 
     Ok(())
 }
+
+fn handshake_fragment(message_sequence: u16, fragment_offset: usize, length: usize) -> Vec<u8> {
+    let mut packet = vec![
+        0x16, 0xfe, 0xfd, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+    packet[11..13].copy_from_slice(&((HANDSHAKE_HEADER_LENGTH + length) as u16).to_be_bytes());
+    packet.push(0x0b);
+    packet.extend_from_slice(&1_000_000u32.to_be_bytes()[1..]);
+    packet.extend_from_slice(&message_sequence.to_be_bytes());
+    packet.extend_from_slice(&(fragment_offset as u32).to_be_bytes()[1..]);
+    packet.extend_from_slice(&(length as u32).to_be_bytes()[1..]);
+    packet.extend_from_slice(&vec![0xAA; length]);
+    packet
+}
+
+#[test]
+fn test_fragment_buffer_max_size() -> Result<()> {
+    const MAX_SIZE: usize = 100_000;
+    const FRAGMENT_LENGTH: usize = 1000;
+
+    let mut fragment_buffer = FragmentBuffer::with_max_size(MAX_SIZE);
+
+    // 200 KB of fragments of ten incomplete 1 MB handshake messages
+    for i in 0..200usize {
+        let message_sequence = (i / 20) as u16;
+        let fragment_offset = (i % 20) * FRAGMENT_LENGTH;
+        let packet = handshake_fragment(message_sequence, fragment_offset, FRAGMENT_LENGTH);
+
+        assert!(fragment_buffer.push(&packet)?);
+        assert!(
+            fragment_buffer.size() <= MAX_SIZE,
+            "fragment buffer grew to {} bytes",
+            fragment_buffer.size()
+        );
+    }
+
+    // the message being assembled is kept, the oldest others were dropped in
+    // favor of the newest
+    assert!(fragment_buffer.cache.contains_key(&0));
+    assert!(!fragment_buffer.cache.contains_key(&1));
+    assert!(fragment_buffer.cache.contains_key(&9));
+
+    Ok(())
+}
+
+#[test]
+fn test_fragment_buffer_max_size_overflow() -> Result<()> {
+    const FRAGMENT_LENGTH: usize = 1000;
+
+    let mut fragment_buffer = FragmentBuffer::with_max_size(2 * FRAGMENT_LENGTH);
+
+    // record headers do not count against the limit
+    assert!(fragment_buffer.push(&handshake_fragment(0, 0, FRAGMENT_LENGTH))?);
+    assert!(fragment_buffer.push(&handshake_fragment(0, FRAGMENT_LENGTH, FRAGMENT_LENGTH))?);
+    assert_eq!(fragment_buffer.size(), 2 * FRAGMENT_LENGTH);
+
+    // the message being assembled is not evicted to make room for itself
+    let result = fragment_buffer.push(&handshake_fragment(0, 2 * FRAGMENT_LENGTH, FRAGMENT_LENGTH));
+    assert_eq!(
+        result,
+        Err(Error::ErrFragmentBufferOverflow {
+            new_size: 3 * FRAGMENT_LENGTH,
+            max_size: 2 * FRAGMENT_LENGTH,
+        })
+    );
+    assert_eq!(fragment_buffer.size(), 2 * FRAGMENT_LENGTH);
+
+    Ok(())
+}
+
+#[test]
+fn test_fragment_buffer_default_size_reassembles_certificate_chain() -> Result<()> {
+    use crate::handshake::handshake_message_certificate::HandshakeMessageCertificate;
+    use crate::handshake::{Handshake, HandshakeMessage};
+
+    // a leaf, intermediate and root certificate of a 4096-bit RSA chain
+    let handshake = Handshake::new(HandshakeMessage::Certificate(HandshakeMessageCertificate {
+        certificate: vec![vec![0x30; 2048], vec![0x31; 2048], vec![0x32; 2048]],
+    }));
+    let mut raw = vec![];
+    handshake.marshal(&mut raw)?;
+    let body = &raw[HANDSHAKE_HEADER_LENGTH..];
+
+    // fragmented to fit a 1200 byte MTU
+    const FRAGMENT_LENGTH: usize = 1024;
+
+    let mut fragment_buffer = FragmentBuffer::new();
+    for (i, fragment) in body.chunks(FRAGMENT_LENGTH).enumerate() {
+        let handshake_header = HandshakeHeader {
+            fragment_offset: (i * FRAGMENT_LENGTH) as u32,
+            fragment_length: fragment.len() as u32,
+            ..handshake.handshake_header
+        };
+        let mut packet = vec![
+            0x16, 0xfe, 0xfd, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        packet[11..13]
+            .copy_from_slice(&((HANDSHAKE_HEADER_LENGTH + fragment.len()) as u16).to_be_bytes());
+        handshake_header.marshal(&mut packet)?;
+        packet.extend_from_slice(fragment);
+
+        assert!(fragment_buffer.push(&packet)?);
+    }
+
+    let (message, epoch) = fragment_buffer.pop()?;
+    assert_eq!(message, raw);
+    assert_eq!(epoch, 0);
+    assert_eq!(fragment_buffer.size(), 0);
+
+    Ok(())
+}
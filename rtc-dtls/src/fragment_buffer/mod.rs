@@ -4,13 +4,18 @@ mod fragment_buffer_test;
 use crate::content::*;
 use crate::handshake::handshake_header::*;
 use crate::record_layer::record_layer_header::*;
+use log::warn;
 use shared::error::*;
 
 use std::collections::HashMap;
 use std::io::{BufWriter, Cursor};
 
-// 2 mb max buffer size
-const FRAGMENT_BUFFER_MAX_SIZE: usize = 2_000_000;
+// 64 KiB default max buffer size. The largest flight is the server's ServerHello,
+// Certificate, ServerKeyExchange, CertificateRequest and ServerHelloDone, which is
+// dominated by the certificate chain: a leaf, intermediate and root with 4096-bit RSA
+// keys are about 2 KB each, so even a deep chain stays well below 16 KB, leaving room
+// for a retransmitted flight while still bounding what a peer can make us hold.
+pub(crate) const DEFAULT_MAX_FRAGMENT_BUFFER_SIZE: usize = 64 * 1024;
 
 pub(crate) struct Fragment {
     record_layer_header: RecordLayerHeader,
@@ -23,13 +28,23 @@ pub(crate) struct FragmentBuffer {
     cache: HashMap<u16, Vec<Fragment>>,
 
     current_message_sequence_number: u16,
+
+    max_size: usize,
 }
 
 impl FragmentBuffer {
     pub fn new() -> Self {
+        Self::with_max_size(DEFAULT_MAX_FRAGMENT_BUFFER_SIZE)
+    }
+
+    /// with_max_size creates a FragmentBuffer holding at most max_bytes of handshake
+    /// fragments. When a push would exceed it, the oldest incomplete messages are dropped,
+    /// except the one being assembled.
+    pub fn with_max_size(max_bytes: usize) -> Self {
         FragmentBuffer {
             cache: HashMap::new(),
             current_message_sequence_number: 0,
+            max_size: max_bytes,
         }
     }
 
@@ -37,11 +52,11 @@ impl FragmentBuffer {
     // when it returns true it means the FragmentBuffer has inserted and the buffer shouldn't be handled
     // when an error returns it is fatal, and the DTLS connection should be stopped
     pub fn push(&mut self, mut buf: &[u8]) -> Result<bool> {
-        let current_size = self.size();
-        if current_size + buf.len() >= FRAGMENT_BUFFER_MAX_SIZE {
+        // a packet larger than the whole buffer can never be reassembled
+        if buf.len() > self.max_size {
             return Err(Error::ErrFragmentBufferOverflow {
-                new_size: current_size + buf.len(),
-                max_size: FRAGMENT_BUFFER_MAX_SIZE,
+                new_size: self.size() + buf.len(),
+                max_size: self.max_size,
             });
         }
 
@@ -53,15 +68,12 @@ impl FragmentBuffer {
             return Ok(false);
         }
 
+        let mut fragments = vec![];
         buf = &buf[RECORD_LAYER_HEADER_SIZE..];
         while !buf.is_empty() {
             let mut reader = Cursor::new(buf);
             let handshake_header = HandshakeHeader::unmarshal(&mut reader)?;
 
-            self.cache
-                .entry(handshake_header.message_sequence)
-                .or_default();
-
             // end index should be the length of handshake header but if the handshake
             // was fragmented, we should keep them all
             let mut end = HANDSHAKE_HEADER_LENGTH + handshake_header.length as usize;
@@ -72,16 +84,30 @@ impl FragmentBuffer {
             // Discard all headers, when rebuilding the packet we will re-build
             let data = buf[HANDSHAKE_HEADER_LENGTH..end].to_vec();
 
-            if let Some(x) = self.cache.get_mut(&handshake_header.message_sequence) {
-                x.push(Fragment {
-                    record_layer_header,
-                    handshake_header,
-                    data,
-                });
-            }
+            fragments.push(Fragment {
+                record_layer_header,
+                handshake_header,
+                data,
+            });
             buf = &buf[end..];
         }
 
+        let incoming_size: usize = fragments.iter().map(|f| f.data.len()).sum();
+        let new_size = self.evict(&fragments, incoming_size);
+        if new_size > self.max_size {
+            return Err(Error::ErrFragmentBufferOverflow {
+                new_size,
+                max_size: self.max_size,
+            });
+        }
+
+        for fragment in fragments {
+            self.cache
+                .entry(fragment.handshake_header.message_sequence)
+                .or_default()
+                .push(fragment);
+        }
+
         Ok(true)
     }
 
@@ -125,7 +151,45 @@ impl FragmentBuffer {
         Ok((content, epoch))
     }
 
-    fn size(&self) -> usize {
+    // Drops the oldest incomplete messages, by epoch and then message sequence,
+    // until incoming_size more bytes fit into the buffer. The next message to pop
+    // and the messages of the incoming fragments are kept, so the size including
+    // incoming_size is returned.
+    fn evict(&mut self, incoming: &[Fragment], incoming_size: usize) -> usize {
+        let mut current_size = self.size();
+        while current_size + incoming_size > self.max_size {
+            let oldest = self
+                .cache
+                .iter()
+                .filter(|(message_sequence, _)| {
+                    **message_sequence != self.current_message_sequence_number
+                        && !incoming
+                            .iter()
+                            .any(|f| f.handshake_header.message_sequence == **message_sequence)
+                })
+                .filter_map(|(message_sequence, frags)| {
+                    frags
+                        .first()
+                        .map(|f| (f.record_layer_header.epoch, *message_sequence))
+                })
+                .min();
+            let Some((epoch, message_sequence)) = oldest else {
+                break;
+            };
+
+            if let Some(frags) = self.cache.remove(&message_sequence) {
+                let dropped: usize = frags.iter().map(|f| f.data.len()).sum();
+                warn!(
+                    "fragment buffer exceeds {} bytes, dropping incomplete handshake message (epoch {}, message_sequence {}, {} bytes)",
+                    self.max_size, epoch, message_sequence, dropped
+                );
+                current_size -= dropped;
+            }
+        }
+        current_size + incoming_size
+    }
+
+    pub(crate) fn size(&self) -> usize {
         self.cache
             .values()
             .map(|fragment| fragment.iter().map(|f| f.data.len()).sum::<usize>())
@@ -13,9 +13,7 @@ type HmacSha256 = Hmac<Sha256>;
 type HmacSha1 = Hmac<Sha1>;
 
 use crate::cipher_suite::CipherSuiteHash;
-use crate::content::ContentType;
 use crate::curve::named_curve::*;
-use crate::record_layer::record_layer_header::ProtocolVersion;
 use shared::error::*;
 
 pub(crate) const PRF_MASTER_SECRET_LABEL: &str = "master secret";
@@ -290,25 +288,12 @@ pub(crate) fn prf_verify_data_server(
 }
 
 // compute the MAC using HMAC-SHA1
-pub(crate) fn prf_mac(
-    epoch: u16,
-    sequence_number: u64,
-    content_type: ContentType,
-    protocol_version: ProtocolVersion,
-    payload: &[u8],
-    key: &[u8],
-) -> Result<Vec<u8>> {
+// header holds the record fields covered by the MAC, laid out like the additional data of
+// AEAD ciphers
+pub(crate) fn prf_mac(header: &[u8], payload: &[u8], key: &[u8]) -> Result<Vec<u8>> {
     let mut hmac = HmacSha1::new_from_slice(key).map_err(|e| Error::Other(e.to_string()))?;
 
-    let mut msg = vec![0u8; 13];
-    msg[..2].copy_from_slice(&epoch.to_be_bytes());
-    msg[2..8].copy_from_slice(&sequence_number.to_be_bytes()[2..]);
-    msg[8] = content_type as u8;
-    msg[9] = protocol_version.major;
-    msg[10] = protocol_version.minor;
-    msg[11..].copy_from_slice(&(payload.len() as u16).to_be_bytes());
-
-    hmac.update(&msg);
+    hmac.update(header);
     hmac.update(payload);
     let result = hmac.finalize();
 
@@ -43,6 +43,7 @@ impl RecordLayer {
                 protocol_version,
                 epoch,
                 sequence_number: 0,
+                connection_id: ConnectionId::default(),
                 content_len: content.size() as u16,
             },
             content,
@@ -82,18 +83,32 @@ impl RecordLayer {
 // two DTLS messages into the same datagram: in the same record or in
 // separate records.
 // https://tools.ietf.org/html/rfc6347#section-4.2.3
-pub(crate) fn unpack_datagram(buf: &[u8]) -> Result<Vec<Vec<u8>>> {
+// The connection id of tls12_cid records is connection_id_len bytes long.
+pub(crate) fn unpack_datagram(buf: &[u8], connection_id_len: usize) -> Result<Vec<Vec<u8>>> {
     let mut out = vec![];
 
     let mut offset = 0;
     while buf.len() != offset {
-        if buf.len() - offset <= RECORD_LAYER_HEADER_SIZE {
+        if buf[offset] & DTLS1_3_UNIFIED_HEADER_MASK == DTLS1_3_UNIFIED_HEADER_BITS {
+            let (h, header_len) = RecordLayerHeader::unmarshal_v13(&buf[offset..], 0)?;
+            let pkt_len = header_len + h.content_len as usize;
+            out.push(buf[offset..offset + pkt_len].to_vec());
+            offset += pkt_len;
+            continue;
+        }
+
+        let header_size = if buf[offset] == ContentType::Tls12Cid as u8 {
+            RECORD_LAYER_HEADER_SIZE + connection_id_len
+        } else {
+            RECORD_LAYER_HEADER_SIZE
+        };
+        if buf.len() - offset <= header_size {
             return Err(Error::ErrInvalidPacketLength);
         }
 
-        let pkt_len = RECORD_LAYER_HEADER_SIZE
-            + (((buf[offset + RECORD_LAYER_HEADER_SIZE - 2] as usize) << 8)
-                | buf[offset + RECORD_LAYER_HEADER_SIZE - 1] as usize);
+        let pkt_len = header_size
+            + (((buf[offset + header_size - 2] as usize) << 8)
+                | buf[offset + header_size - 1] as usize);
         if offset + pkt_len > buf.len() {
             return Err(Error::ErrInvalidPacketLength);
         }
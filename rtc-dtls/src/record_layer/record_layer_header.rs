@@ -3,7 +3,9 @@ use crate::content::*;
 use shared::error::*;
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::fmt;
 use std::io::{Read, Write};
+use std::ops::Deref;
 
 pub const RECORD_LAYER_HEADER_SIZE: usize = 13;
 pub const MAX_SEQUENCE_NUMBER: u64 = 0x0000FFFFFFFFFFFF;
@@ -14,6 +16,15 @@ pub const DTLS1_2MINOR: u8 = 0xfd;
 pub const DTLS1_0MAJOR: u8 = 0xfe;
 pub const DTLS1_0MINOR: u8 = 0xff;
 
+// DTLS 1.3 ciphertext records use the unified header, whose first byte is 0b001CSLEE
+// https://www.rfc-editor.org/rfc/rfc9147#section-4
+pub const DTLS1_3_UNIFIED_HEADER_MASK: u8 = 0b1110_0000;
+pub const DTLS1_3_UNIFIED_HEADER_BITS: u8 = 0b0010_0000;
+pub const DTLS1_3_CID_BIT: u8 = 0b0001_0000;
+pub const DTLS1_3_SEQUENCE_NUMBER_LEN_BIT: u8 = 0b0000_1000;
+pub const DTLS1_3_LENGTH_BIT: u8 = 0b0000_0100;
+pub const DTLS1_3_EPOCH_MASK: u8 = 0b0000_0011;
+
 // VERSION_DTLS12 is the DTLS version in the same style as
 // VersionTLSXX from crypto/tls
 pub const VERSION_DTLS12: u16 = 0xfefd;
@@ -34,16 +45,123 @@ pub struct ProtocolVersion {
     pub minor: u8,
 }
 
+// DtlsVersion is the record layer format a datagram record is encoded with
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DtlsVersion {
+    V1_0,
+    V1_2,
+    V1_3,
+}
+
+impl DtlsVersion {
+    // detect returns the DTLS version of the record starting at buf. DTLS 1.3 ciphertext
+    // records are recognized from their first byte alone, while DTLS 1.3 plaintext records
+    // carry the legacy 1.2 version and are reported as V1_2.
+    pub fn detect(buf: &[u8]) -> Result<Self> {
+        if buf.is_empty() {
+            return Err(Error::ErrInvalidPacketLength);
+        }
+        if buf[0] & DTLS1_3_UNIFIED_HEADER_MASK == DTLS1_3_UNIFIED_HEADER_BITS {
+            return Ok(DtlsVersion::V1_3);
+        }
+        if buf.len() < 3 {
+            return Err(Error::ErrInvalidPacketLength);
+        }
+
+        let protocol_version = ProtocolVersion {
+            major: buf[1],
+            minor: buf[2],
+        };
+        if protocol_version == PROTOCOL_VERSION1_0 {
+            Ok(DtlsVersion::V1_0)
+        } else if protocol_version == PROTOCOL_VERSION1_2 {
+            Ok(DtlsVersion::V1_2)
+        } else {
+            Err(Error::ErrUnsupportedProtocolVersion)
+        }
+    }
+}
+
+// MAX_CONNECTION_ID_LENGTH is the longest connection id, cid<0..2^8-1>
+// https://www.rfc-editor.org/rfc/rfc9146#section-3
+pub const MAX_CONNECTION_ID_LENGTH: usize = 255;
+
+// ConnectionId holds a connection id inline, so that RecordLayerHeader stays Copy.
+// It dereferences to the bytes of the connection id.
+#[derive(Copy, Clone)]
+pub struct ConnectionId {
+    len: u8,
+    buf: [u8; MAX_CONNECTION_ID_LENGTH],
+}
+
+impl ConnectionId {
+    pub fn new(connection_id: &[u8]) -> Result<Self> {
+        if connection_id.len() > MAX_CONNECTION_ID_LENGTH {
+            return Err(Error::ErrConnectionIdTooLong);
+        }
+        let mut buf = [0u8; MAX_CONNECTION_ID_LENGTH];
+        buf[..connection_id.len()].copy_from_slice(connection_id);
+        Ok(ConnectionId {
+            len: connection_id.len() as u8,
+            buf,
+        })
+    }
+}
+
+impl Default for ConnectionId {
+    fn default() -> Self {
+        ConnectionId {
+            len: 0,
+            buf: [0u8; MAX_CONNECTION_ID_LENGTH],
+        }
+    }
+}
+
+impl Deref for ConnectionId {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf[..self.len as usize]
+    }
+}
+
+impl PartialEq for ConnectionId {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for ConnectionId {}
+
+impl fmt::Debug for ConnectionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
 pub struct RecordLayerHeader {
     pub content_type: ContentType,
     pub protocol_version: ProtocolVersion,
     pub epoch: u16,
     pub sequence_number: u64, // uint48 in spec
+    // connection id of a tls12_cid record, or the one to protect a record with
+    // https://www.rfc-editor.org/rfc/rfc9146#section-4
+    pub connection_id: ConnectionId,
     pub content_len: u16,
 }
 
 impl RecordLayerHeader {
+    // size returns the length of the header on the wire, which includes the connection id
+    // of tls12_cid records
+    pub fn size(&self) -> usize {
+        if self.content_type == ContentType::Tls12Cid {
+            RECORD_LAYER_HEADER_SIZE + self.connection_id.len()
+        } else {
+            RECORD_LAYER_HEADER_SIZE
+        }
+    }
+
     pub fn marshal<W: Write>(&self, writer: &mut W) -> Result<()> {
         if self.sequence_number > MAX_SEQUENCE_NUMBER {
             return Err(Error::ErrSequenceNumberOverflow);
@@ -57,12 +175,26 @@ impl RecordLayerHeader {
         let be: [u8; 8] = self.sequence_number.to_be_bytes();
         writer.write_all(&be[2..])?; // uint48 in spec
 
+        if self.content_type == ContentType::Tls12Cid {
+            writer.write_all(&self.connection_id)?;
+        }
+
         writer.write_u16::<BigEndian>(self.content_len)?;
 
         Ok(writer.flush()?)
     }
 
     pub fn unmarshal<R: Read>(reader: &mut R) -> Result<Self> {
+        Self::unmarshal_with_connection_id(reader, 0)
+    }
+
+    // unmarshal_with_connection_id decodes a header whose connection id, if it is a tls12_cid
+    // record, is connection_id_len bytes long. The length is not on the wire, the receiver
+    // knows it from the connection id it asked for.
+    pub fn unmarshal_with_connection_id<R: Read>(
+        reader: &mut R,
+        connection_id_len: usize,
+    ) -> Result<Self> {
         let content_type = reader.read_u8()?.into();
         let major = reader.read_u8()?;
         let minor = reader.read_u8()?;
@@ -77,6 +209,17 @@ impl RecordLayerHeader {
         if protocol_version != PROTOCOL_VERSION1_0 && protocol_version != PROTOCOL_VERSION1_2 {
             return Err(Error::ErrUnsupportedProtocolVersion);
         }
+
+        let mut connection_id = ConnectionId::default();
+        if content_type == ContentType::Tls12Cid {
+            let mut buf = [0u8; MAX_CONNECTION_ID_LENGTH];
+            let buf = buf
+                .get_mut(..connection_id_len)
+                .ok_or(Error::ErrConnectionIdTooLong)?;
+            reader.read_exact(buf)?;
+            connection_id = ConnectionId::new(buf)?;
+        }
+
         let content_len = reader.read_u16::<BigEndian>()?;
 
         Ok(RecordLayerHeader {
@@ -84,7 +227,72 @@ impl RecordLayerHeader {
             protocol_version,
             epoch,
             sequence_number,
+            connection_id,
             content_len,
         })
     }
+
+    // unmarshal_v13 decodes a DTLS 1.3 unified header and returns it together with the
+    // number of header bytes consumed. Only the low 2 bits of the epoch and the low 8 or
+    // 16 bits of the sequence number are on the wire, so the caller has to reconstruct
+    // the full values. The connection id, when present, is connection_id_len bytes long
+    // and is skipped. When the length field is omitted the record extends to the end of
+    // the datagram.
+    // https://www.rfc-editor.org/rfc/rfc9147#section-4
+    pub fn unmarshal_v13(buf: &[u8], connection_id_len: usize) -> Result<(Self, usize)> {
+        if buf.is_empty() {
+            return Err(Error::ErrInvalidPacketLength);
+        }
+        let flags = buf[0];
+        if flags & DTLS1_3_UNIFIED_HEADER_MASK != DTLS1_3_UNIFIED_HEADER_BITS {
+            return Err(Error::ErrUnsupportedProtocolVersion);
+        }
+
+        let mut offset = 1;
+        if flags & DTLS1_3_CID_BIT != 0 {
+            offset += connection_id_len;
+        }
+
+        let sequence_number_len = if flags & DTLS1_3_SEQUENCE_NUMBER_LEN_BIT != 0 {
+            2
+        } else {
+            1
+        };
+        if buf.len() < offset + sequence_number_len {
+            return Err(Error::ErrInvalidPacketLength);
+        }
+        let sequence_number = if sequence_number_len == 2 {
+            u16::from_be_bytes([buf[offset], buf[offset + 1]]) as u64
+        } else {
+            buf[offset] as u64
+        };
+        offset += sequence_number_len;
+
+        let content_len = if flags & DTLS1_3_LENGTH_BIT != 0 {
+            if buf.len() < offset + 2 {
+                return Err(Error::ErrInvalidPacketLength);
+            }
+            let content_len = u16::from_be_bytes([buf[offset], buf[offset + 1]]);
+            offset += 2;
+            if buf.len() < offset + content_len as usize {
+                return Err(Error::ErrInvalidPacketLength);
+            }
+            content_len
+        } else {
+            (buf.len() - offset) as u16
+        };
+
+        Ok((
+            RecordLayerHeader {
+                // the real content type is inside the encrypted DTLSInnerPlaintext
+                content_type: ContentType::ApplicationData,
+                protocol_version: PROTOCOL_VERSION1_2,
+                epoch: (flags & DTLS1_3_EPOCH_MASK) as u16,
+                sequence_number,
+                connection_id: ConnectionId::default(),
+                content_len,
+            },
+            offset,
+        ))
+    }
 }
@@ -52,7 +52,7 @@ fn test_udp_decode() -> Result<()> {
     ];
 
     for (name, data, wanted, wanted_err) in tests {
-        let dtls_pkts = unpack_datagram(&data);
+        let dtls_pkts = unpack_datagram(&data, 0);
         if let Some(err) = wanted_err {
             if let Err(dtls) = dtls_pkts {
                 assert_eq!(err.to_string(), dtls.to_string());
@@ -89,6 +89,7 @@ fn test_record_layer_round_trip() -> Result<()> {
                 epoch: 0,
                 sequence_number: 18,
                 content_len: 1,
+                connection_id: ConnectionId::default(),
             },
             content: Content::ChangeCipherSpec(ChangeCipherSpec {}),
         },
@@ -116,3 +117,145 @@ fn test_record_layer_round_trip() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_dtls_version_detect() -> Result<()> {
+    let tests = vec![
+        ("DTLS 1.0", vec![0x14, 0xfe, 0xff], DtlsVersion::V1_0),
+        ("DTLS 1.2", vec![0x16, 0xfe, 0xfd], DtlsVersion::V1_2),
+        (
+            "DTLS 1.3 unified header",
+            vec![0x2c, 0x00],
+            DtlsVersion::V1_3,
+        ),
+    ];
+
+    for (name, data, want) in tests {
+        let got = DtlsVersion::detect(&data)?;
+        assert_eq!(want, got, "{name} detect: got {got:?}, want {want:?}");
+    }
+
+    assert_eq!(
+        DtlsVersion::detect(&[0x16, 0x03, 0x03]),
+        Err(Error::ErrUnsupportedProtocolVersion)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_record_layer_header_unmarshal_v13() -> Result<()> {
+    let tests = vec![
+        (
+            "16-bit sequence number with length",
+            // 0b001_0_1_1_10: no cid, 16-bit seq, length present, epoch 2
+            vec![0x2e, 0x01, 0x02, 0x00, 0x02, 0xaa, 0xbb],
+            0,
+            RecordLayerHeader {
+                content_type: ContentType::ApplicationData,
+                protocol_version: PROTOCOL_VERSION1_2,
+                epoch: 2,
+                sequence_number: 0x0102,
+                content_len: 2,
+                connection_id: ConnectionId::default(),
+            },
+            5,
+        ),
+        (
+            "8-bit sequence number without length",
+            // 0b001_0_0_0_11: no cid, 8-bit seq, no length, epoch 3
+            vec![0x23, 0x07, 0xaa, 0xbb, 0xcc],
+            0,
+            RecordLayerHeader {
+                content_type: ContentType::ApplicationData,
+                protocol_version: PROTOCOL_VERSION1_2,
+                epoch: 3,
+                sequence_number: 0x07,
+                content_len: 3,
+                connection_id: ConnectionId::default(),
+            },
+            2,
+        ),
+        (
+            "connection id",
+            // 0b001_1_0_1_01: cid, 8-bit seq, length present, epoch 1
+            vec![0x35, 0xc1, 0xc2, 0x09, 0x00, 0x01, 0xaa],
+            2,
+            RecordLayerHeader {
+                content_type: ContentType::ApplicationData,
+                protocol_version: PROTOCOL_VERSION1_2,
+                epoch: 1,
+                sequence_number: 0x09,
+                content_len: 1,
+                connection_id: ConnectionId::default(),
+            },
+            6,
+        ),
+    ];
+
+    for (name, data, connection_id_len, want, want_len) in tests {
+        let (h, header_len) = RecordLayerHeader::unmarshal_v13(&data, connection_id_len)?;
+        assert_eq!(want, h, "{name} unmarshal_v13: got {h:?}, want {want:?}");
+        assert_eq!(want_len, header_len, "{name} header length");
+    }
+
+    // declared length exceeds the datagram
+    assert_eq!(
+        RecordLayerHeader::unmarshal_v13(&[0x2c, 0x00, 0x01, 0x00, 0x05, 0xaa], 0),
+        Err(Error::ErrInvalidPacketLength)
+    );
+
+    // DTLS 1.3 records are split by unpack_datagram like DTLS 1.2 ones
+    let pkts = unpack_datagram(&[0x2c, 0x00, 0x01, 0x00, 0x01, 0xaa, 0x23, 0x01, 0xbb], 0)?;
+    assert_eq!(
+        pkts,
+        vec![
+            vec![0x2c, 0x00, 0x01, 0x00, 0x01, 0xaa],
+            vec![0x23, 0x01, 0xbb]
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_record_layer_header_connection_id() -> Result<()> {
+    let raw = vec![
+        0x19, 0xfe, 0xfd, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0xc1, 0xc2, 0xc3, 0x00,
+        0x02, 0xaa, 0xbb,
+    ];
+    let want = RecordLayerHeader {
+        content_type: ContentType::Tls12Cid,
+        protocol_version: PROTOCOL_VERSION1_2,
+        epoch: 1,
+        sequence_number: 5,
+        content_len: 2,
+        connection_id: ConnectionId::new(&[0xc1, 0xc2, 0xc3])?,
+    };
+
+    let mut reader = BufReader::new(raw.as_slice());
+    let h = RecordLayerHeader::unmarshal_with_connection_id(&mut reader, 3)?;
+    assert_eq!(want, h, "unmarshal: got {h:?}, want {want:?}");
+    assert_eq!(h.size(), RECORD_LAYER_HEADER_SIZE + 3);
+
+    let mut data = vec![];
+    {
+        let mut writer = BufWriter::<&mut Vec<u8>>::new(data.as_mut());
+        h.marshal(&mut writer)?;
+    }
+    assert_eq!(&data[..], &raw[..h.size()], "marshal");
+
+    // the header with its connection id is Copy
+    let copy = h;
+    assert_eq!(copy, h);
+    assert_eq!(
+        ConnectionId::new(&[0u8; MAX_CONNECTION_ID_LENGTH + 1]).map(|_| ()),
+        Err(Error::ErrConnectionIdTooLong)
+    );
+
+    // the connection id length is not on the wire, records are split with the local one
+    let pkts = unpack_datagram(&[raw.clone(), raw.clone()].concat(), 3)?;
+    assert_eq!(pkts, vec![raw.clone(), raw]);
+
+    Ok(())
+}
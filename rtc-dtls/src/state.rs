@@ -9,18 +9,24 @@ use shared::error::*;
 use serde::{Deserialize, Serialize};
 use shared::crypto::KeyingMaterialExporter;
 use std::io::{BufWriter, Cursor};
+use std::net::SocketAddr;
+
+// Version of the format written by State::serialize, its first byte
+const STATE_SERIALIZATION_VERSION: u8 = 1;
 
 // State holds the dtls connection state and implements both encoding.BinaryMarshaler and encoding.BinaryUnmarshaler
 pub struct State {
     pub(crate) local_epoch: u16,
     pub(crate) remote_epoch: u16,
-    pub(crate) local_sequence_number: Vec<u64>, // uint48
+    pub(crate) local_sequence_number: Vec<u64>,  // uint48
+    pub(crate) remote_sequence_number: Vec<u64>, // uint48, next expected in each remote epoch
     pub(crate) local_random: HandshakeRandom,
     pub(crate) remote_random: HandshakeRandom,
     pub(crate) master_secret: Vec<u8>,
     pub(crate) cipher_suite: Option<Box<dyn CipherSuite>>, // nil if a cipher_suite hasn't been chosen
 
     pub(crate) srtp_protection_profile: SrtpProtectionProfile, // Negotiated srtp_protection_profile
+    pub(crate) negotiated_protocol: Option<String>,            // Negotiated ALPN protocol
     pub peer_certificates: Vec<Vec<u8>>,
     pub identity_hint: Vec<u8>,
 
@@ -32,6 +38,9 @@ pub struct State {
     pub(crate) named_curve: NamedCurve,
     pub(crate) local_keypair: Option<NamedCurveKeypair>,
     pub(crate) cookie: Vec<u8>,
+    pub(crate) remote_addr: Option<SocketAddr>, // Address the server binds its cookie to
+    pub(crate) local_connection_id: Vec<u8>,    // Connection id the peer sends its records with
+    pub(crate) remote_connection_id: Option<Vec<u8>>, // Connection id to send records with, once negotiated
     pub(crate) handshake_send_sequence: isize,
     pub(crate) handshake_recv_sequence: isize,
     pub(crate) server_name: String,
@@ -45,6 +54,26 @@ pub struct State {
 
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 struct SerializedState {
+    local_epoch: u16,
+    remote_epoch: u16,
+    local_random: [u8; HANDSHAKE_RANDOM_LENGTH],
+    remote_random: [u8; HANDSHAKE_RANDOM_LENGTH],
+    cipher_suite_id: u16,
+    master_secret: Vec<u8>,
+    sequence_number: u64,
+    remote_sequence_number: u64,
+    srtp_protection_profile: u16,
+    negotiated_protocol: Option<String>,
+    peer_certificates: Vec<Vec<u8>>,
+    identity_hint: Vec<u8>,
+    is_client: bool,
+    local_connection_id: Vec<u8>,
+    remote_connection_id: Option<Vec<u8>>,
+}
+
+// Unversioned layout of marshal_binary, kept so that previously stored states still load
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct LegacySerializedState {
     local_epoch: u16,
     remote_epoch: u16,
     local_random: [u8; HANDSHAKE_RANDOM_LENGTH],
@@ -58,18 +87,60 @@ struct SerializedState {
     is_client: bool,
 }
 
+impl From<SerializedState> for LegacySerializedState {
+    fn from(s: SerializedState) -> Self {
+        LegacySerializedState {
+            local_epoch: s.local_epoch,
+            remote_epoch: s.remote_epoch,
+            local_random: s.local_random,
+            remote_random: s.remote_random,
+            cipher_suite_id: s.cipher_suite_id,
+            master_secret: s.master_secret,
+            sequence_number: s.sequence_number,
+            srtp_protection_profile: s.srtp_protection_profile,
+            peer_certificates: s.peer_certificates,
+            identity_hint: s.identity_hint,
+            is_client: s.is_client,
+        }
+    }
+}
+
+impl From<LegacySerializedState> for SerializedState {
+    fn from(s: LegacySerializedState) -> Self {
+        SerializedState {
+            local_epoch: s.local_epoch,
+            remote_epoch: s.remote_epoch,
+            local_random: s.local_random,
+            remote_random: s.remote_random,
+            cipher_suite_id: s.cipher_suite_id,
+            master_secret: s.master_secret,
+            sequence_number: s.sequence_number,
+            remote_sequence_number: 0,
+            srtp_protection_profile: s.srtp_protection_profile,
+            negotiated_protocol: None,
+            peer_certificates: s.peer_certificates,
+            identity_hint: s.identity_hint,
+            is_client: s.is_client,
+            local_connection_id: vec![],
+            remote_connection_id: None,
+        }
+    }
+}
+
 impl Default for State {
     fn default() -> Self {
         State {
             local_epoch: 0,
             remote_epoch: 0,
             local_sequence_number: vec![],
+            remote_sequence_number: vec![],
             local_random: HandshakeRandom::default(),
             remote_random: HandshakeRandom::default(),
             master_secret: vec![],
             cipher_suite: None, // nil if a cipher_suite hasn't been chosen
 
             srtp_protection_profile: SrtpProtectionProfile::Unsupported, // Negotiated srtp_protection_profile
+            negotiated_protocol: None,
             peer_certificates: vec![],
             identity_hint: vec![],
 
@@ -81,6 +152,9 @@ impl Default for State {
             named_curve: NamedCurve::Unsupported,
             local_keypair: None,
             cookie: vec![],
+            remote_addr: None,
+            local_connection_id: vec![],
+            remote_connection_id: None,
             handshake_send_sequence: 0,
             handshake_recv_sequence: 0,
             server_name: "".to_string(),
@@ -95,7 +169,7 @@ impl Default for State {
 }
 
 impl State {
-    fn serialize(&self) -> Result<SerializedState> {
+    fn to_serialized(&self) -> Result<SerializedState> {
         let mut local_rand = vec![];
         {
             let mut writer = BufWriter::<&mut Vec<u8>>::new(local_rand.as_mut());
@@ -116,6 +190,11 @@ impl State {
         let local_epoch = self.local_epoch;
         let remote_epoch = self.remote_epoch;
         let sequence_number = self.local_sequence_number[local_epoch as usize];
+        let remote_sequence_number = self
+            .remote_sequence_number
+            .get(remote_epoch as usize)
+            .copied()
+            .unwrap_or(0);
         let cipher_suite_id = {
             match &self.cipher_suite {
                 Some(cipher_suite) => cipher_suite.id() as u16,
@@ -131,14 +210,18 @@ impl State {
             cipher_suite_id,
             master_secret: self.master_secret.clone(),
             sequence_number,
+            remote_sequence_number,
             srtp_protection_profile: self.srtp_protection_profile as u16,
+            negotiated_protocol: self.negotiated_protocol.clone(),
             peer_certificates: self.peer_certificates.clone(),
             identity_hint: self.identity_hint.clone(),
             is_client: self.is_client,
+            local_connection_id: self.local_connection_id.clone(),
+            remote_connection_id: self.remote_connection_id.clone(),
         })
     }
 
-    fn deserialize(&mut self, serialized: &SerializedState) -> Result<()> {
+    fn apply_serialized(&mut self, serialized: &SerializedState) -> Result<()> {
         // Set epoch values
         self.local_epoch = serialized.local_epoch;
         self.remote_epoch = serialized.remote_epoch;
@@ -148,6 +231,12 @@ impl State {
             }
             self.local_sequence_number[serialized.local_epoch as usize] =
                 serialized.sequence_number;
+
+            while self.remote_sequence_number.len() <= serialized.remote_epoch as usize {
+                self.remote_sequence_number.push(0);
+            }
+            self.remote_sequence_number[serialized.remote_epoch as usize] =
+                serialized.remote_sequence_number;
         }
 
         // Set random values
@@ -159,6 +248,11 @@ impl State {
 
         self.is_client = serialized.is_client;
 
+        self.local_connection_id
+            .clone_from(&serialized.local_connection_id);
+        self.remote_connection_id
+            .clone_from(&serialized.remote_connection_id);
+
         // Set master secret
         self.master_secret.clone_from(&serialized.master_secret);
 
@@ -166,6 +260,8 @@ impl State {
         self.cipher_suite = Some(cipher_suite_for_id(serialized.cipher_suite_id.into())?);
 
         self.srtp_protection_profile = serialized.srtp_protection_profile.into();
+        self.negotiated_protocol
+            .clone_from(&serialized.negotiated_protocol);
 
         // Set remote certificate
         self.peer_certificates
@@ -202,9 +298,44 @@ impl State {
         }
     }
 
-    // marshal_binary is a binary.BinaryMarshaler.marshal_binary implementation
+    /// serialize encodes the state of an established connection, i.e. its cipher suite,
+    /// master secret, epochs, sequence numbers, SRTP protection profile and role, so that
+    /// it can be resumed with `DTLSConn::from_serialized_state` without a new handshake.
+    /// The first byte is the version of the format.
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        let serialized = self.to_serialized()?;
+
+        let mut data = vec![STATE_SERIALIZATION_VERSION];
+        match bincode::serialize_into(&mut data, &serialized) {
+            Ok(()) => Ok(data),
+            Err(err) => Err(Error::Other(err.to_string())),
+        }
+    }
+
+    /// deserialize decodes a state encoded by serialize, and initializes its cipher suite
+    /// from the master secret.
+    pub fn deserialize(data: &[u8]) -> Result<Self> {
+        let (version, data) = data.split_first().ok_or(Error::ErrBufferTooSmall)?;
+        if *version != STATE_SERIALIZATION_VERSION {
+            return Err(Error::ErrUnsupportedStateVersion);
+        }
+        let serialized: SerializedState = match bincode::deserialize(data) {
+            Ok(dec) => dec,
+            Err(err) => return Err(Error::Other(err.to_string())),
+        };
+
+        let mut state = State::default();
+        state.apply_serialized(&serialized)?;
+        state.init_cipher_suite()?;
+
+        Ok(state)
+    }
+
+    // marshal_binary is a binary.BinaryMarshaler.marshal_binary implementation.
+    // It keeps the unversioned layout, use serialize to also save the remote sequence
+    // number, the negotiated protocol and the connection ids.
     pub fn marshal_binary(&self) -> Result<Vec<u8>> {
-        let serialized = self.serialize()?;
+        let serialized = LegacySerializedState::from(self.to_serialized()?);
 
         match bincode::serialize(&serialized) {
             Ok(enc) => Ok(enc),
@@ -214,11 +345,11 @@ impl State {
 
     // unmarshal_binary is a binary.BinaryUnmarshaler.unmarshal_binary implementation
     pub fn unmarshal_binary(&mut self, data: &[u8]) -> Result<()> {
-        let serialized: SerializedState = match bincode::deserialize(data) {
+        let serialized: LegacySerializedState = match bincode::deserialize(data) {
             Ok(dec) => dec,
             Err(err) => return Err(Error::Other(err.to_string())),
         };
-        self.deserialize(&serialized)?;
+        self.apply_serialized(&serialized.into())?;
         self.init_cipher_suite()?;
 
         Ok(())
@@ -227,6 +358,12 @@ impl State {
     pub fn srtp_protection_profile(&self) -> SrtpProtectionProfile {
         self.srtp_protection_profile
     }
+
+    /// negotiated_protocol returns the application protocol selected with ALPN,
+    /// if both sides support it
+    pub fn negotiated_protocol(&self) -> Option<&str> {
+        self.negotiated_protocol.as_deref()
+    }
 }
 
 impl KeyingMaterialExporter for State {
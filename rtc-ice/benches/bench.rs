@@ -0,0 +1,76 @@
+use bytes::BytesMut;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rtc_ice::candidate::candidate_host::CandidateHostConfig;
+use rtc_ice::candidate::CandidateConfig;
+use rtc_ice::{Agent, AgentConfig};
+use shared::{Transmit, TransportContext};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
+
+const CANDIDATE_COUNT: u16 = 64;
+
+fn new_agent() -> Agent {
+    let mut a = Agent::new(Arc::new(AgentConfig::default())).unwrap();
+    for port in 0..CANDIDATE_COUNT {
+        let c = CandidateHostConfig {
+            base_config: CandidateConfig {
+                network: "udp".to_owned(),
+                address: "192.168.0.1".to_owned(),
+                port: 5000 + port,
+                component: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+        .new_candidate_host()
+        .unwrap();
+        a.add_local_candidate(c).unwrap();
+    }
+    a
+}
+
+fn bench_handle_read(c: &mut Criterion, name: &str, a: &mut Agent, local_addr: SocketAddr) {
+    let now = Instant::now();
+    let transport = TransportContext {
+        local_addr,
+        peer_addr: "192.168.0.2:6000".parse().unwrap(),
+        ..Default::default()
+    };
+    // non-STUN traffic, so handle_read does little beyond finding the local candidate
+    let message = BytesMut::from(&b"not a stun message"[..]);
+
+    c.bench_function(name, |b| {
+        b.iter(|| {
+            let _ = black_box(a.handle_read(Transmit {
+                now,
+                transport,
+                message: message.clone(),
+            }));
+        })
+    });
+}
+
+fn benchmark_find_local_candidate(c: &mut Criterion) {
+    let mut a = new_agent();
+    let last = format!("192.168.0.1:{}", 5000 + CANDIDATE_COUNT - 1)
+        .parse()
+        .unwrap();
+    let unknown = "192.168.0.1:4999".parse().unwrap();
+
+    bench_handle_read(
+        c,
+        "BenchmarkAgent_HandleRead_LastOf64Candidates",
+        &mut a,
+        last,
+    );
+    bench_handle_read(
+        c,
+        "BenchmarkAgent_HandleRead_UnknownOf64Candidates",
+        &mut a,
+        unknown,
+    );
+}
+
+criterion_group!(benches, benchmark_find_local_candidate);
+criterion_main!(benches);
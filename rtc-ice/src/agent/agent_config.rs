@@ -1,6 +1,7 @@
 use std::time::Duration;
 
 use super::*;
+use crate::mdns::MulticastDnsMode;
 use crate::url::*;
 
 /// The interval at which the agent performs candidate checks in the connecting phase.
@@ -9,6 +10,12 @@ pub(crate) const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_millis(200);
 /// The interval used to keep candidates alive.
 pub(crate) const DEFAULT_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(2);
 
+/// The interval between consent freshness checks on the selected pair.
+pub(crate) const DEFAULT_CONSENT_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The time after which consent expires if no consent freshness check was answered.
+pub(crate) const CONSENT_TIMEOUT: Duration = Duration::from_secs(30);
+
 /// The default time till an Agent transitions disconnected.
 pub(crate) const DEFAULT_DISCONNECTED_TIMEOUT: Duration = Duration::from_secs(5);
 
@@ -27,8 +34,16 @@ pub(crate) const DEFAULT_PRFLX_ACCEPTANCE_MIN_WAIT: Duration = Duration::from_mi
 /// Wait time before nominating a relay candidate.
 pub(crate) const DEFAULT_RELAY_ACCEPTANCE_MIN_WAIT: Duration = Duration::from_millis(2000);
 
-/// Max binding request before considering a pair failed.
-pub(crate) const DEFAULT_MAX_BINDING_REQUESTS: u16 = 7;
+/// The initial retransmission timeout of connectivity checks, doubled after each
+/// retransmission (RFC 8489 section 6.2.1).
+pub(crate) const DEFAULT_INITIAL_RTO: Duration = Duration::from_millis(500);
+
+/// Max retransmissions of a connectivity check before considering a pair failed, so that a check
+/// is sent 7 times (RFC 8489 section 6.2.1).
+pub(crate) const DEFAULT_MAX_RETRANSMISSIONS: u16 = 6;
+
+/// Max number of candidate pairs in the checklist (RFC 8445 section 6.1.2.5).
+pub(crate) const DEFAULT_MAX_CANDIDATE_PAIRS: usize = 100;
 
 /// The number of bytes that can be buffered before we start to error.
 pub(crate) const MAX_BUFFER_SIZE: usize = 1000 * 1000; // 1MB
@@ -36,6 +51,19 @@ pub(crate) const MAX_BUFFER_SIZE: usize = 1000 * 1000; // 1MB
 /// Wait time before binding requests can be deleted.
 pub(crate) const MAX_BINDING_REQUEST_TIMEOUT: Duration = Duration::from_millis(4000);
 
+/// The default number of outbound Binding Requests awaiting a response that are remembered.
+pub(crate) const DEFAULT_MAX_PENDING_BINDING_REQUESTS: usize = 100;
+
+/// The number of inbound Binding Request transaction IDs remembered for replay protection.
+pub(crate) const STUN_REPLAY_PROTECTION_WINDOW: usize = 1024;
+
+/// The initial retransmission timeout of Binding requests to STUN servers, doubled after each
+/// retransmission (RFC 8489 section 6.2.1).
+pub(crate) const STUN_GATHER_RTO: Duration = Duration::from_millis(500);
+
+/// Max Binding requests sent to a STUN server before gathering from it fails.
+pub(crate) const MAX_STUN_GATHER_REQUESTS: u16 = 7;
+
 pub(crate) fn default_candidate_types() -> Vec<CandidateType> {
     vec![
         CandidateType::Host,
@@ -44,6 +72,21 @@ pub(crate) fn default_candidate_types() -> Vec<CandidateType> {
     ]
 }
 
+/// Returns the priority of a local candidate, replacing the one computed from its type and
+/// local preference, e.g. to prefer the candidates of a VPN interface.
+pub type CandidatePriorityFn = Arc<dyn Fn(&Candidate) -> u32 + Send + Sync>;
+
+/// Determines how the controlling agent nominates a candidate pair.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum NominationMode {
+    /// Checks are sent without USE-CANDIDATE, and a valid pair is nominated afterwards with a
+    /// check that carries it (RFC 8445 section 8.1.1).
+    #[default]
+    Regular,
+    /// Every check carries USE-CANDIDATE, so the first pair that succeeds is selected.
+    Aggressive,
+}
+
 /// Collects the arguments to `ice::Agent` construction into a single structure, for
 /// future-proofness of the interface.
 #[derive(Default)]
@@ -72,9 +115,24 @@ pub struct AgentConfig {
     /// A keepalive interval of 0 means we never send keepalive packets
     pub keepalive_interval: Option<Duration>,
 
+    /// Determines how often consent freshness Binding Requests are sent on the selected pair
+    /// (RFC 7675), defaults to 5 seconds when this is nil. Each check is randomized between 0.8
+    /// and 1.2 times this interval, and the selected pair fails once no check has been
+    /// answered for 30 seconds. A consent check interval of 0 disables consent checks.
+    pub consent_check_interval: Option<Duration>,
+
+    /// Sends keepalives between consent checks as STUN Binding Indications, which are not
+    /// answered, instead of Binding Requests.
+    pub use_binding_indication_for_keepalive: bool,
+
     /// An optional configuration for disabling or enabling support for specific candidate types.
     pub candidate_types: Vec<CandidateType>,
 
+    /// When set, decides the priority of every local candidate. The candidate passed to it
+    /// still reports the priority computed from its type and local preference. Can be
+    /// replaced with `Agent::set_candidate_priority_fn()`.
+    pub candidate_priority_fn: Option<CandidatePriorityFn>,
+
     /// Controls how often our internal task loop runs when in the connecting state.
     /// Only useful for testing.
     pub check_interval: Duration,
@@ -82,10 +140,42 @@ pub struct AgentConfig {
     /// The max amount of binding requests the agent will send over a candidate pair for validation
     /// or nomination, if after max_binding_requests the candidate is yet to answer a binding
     /// request or a nomination we set the pair as failed.
+    ///
+    /// Superseded by max_retransmissions, and only used when it is nil.
     pub max_binding_requests: Option<u16>,
 
+    /// The initial retransmission timeout of a connectivity check, defaults to 500ms when this is
+    /// nil. The timeout doubles after each retransmission.
+    pub initial_rto: Option<Duration>,
+
+    /// The max amount of times a connectivity check is retransmitted before its candidate pair is
+    /// set as failed, defaults to 6 when this is nil.
+    pub max_retransmissions: Option<u16>,
+
+    /// The max amount of candidate pairs kept in the checklist, defaults to 100 when this is nil.
+    /// The lowest priority pairs are pruned beyond this limit.
+    pub max_candidate_pairs: Option<usize>,
+
+    /// The max amount of outbound binding requests awaiting a response that are remembered,
+    /// defaults to 100 when this is nil. Once reached, the oldest request is forgotten and a
+    /// late response to it is discarded.
+    pub max_pending_binding_requests: Option<usize>,
+
     pub is_controlling: bool,
 
+    /// Determines how the controlling agent nominates a candidate pair, Regular by default.
+    pub nomination_mode: NominationMode,
+
+    /// In Regular nomination, how long after the checks started a valid pair may be nominated
+    /// while the highest priority pair has not succeeded yet. Defaults to 0, which nominates the
+    /// best valid pair right away.
+    pub nomination_min_wait: Duration,
+
+    /// In Regular nomination, how long after the checks started the controlling agent keeps
+    /// checking the remaining pairs and nominates a valid pair with a higher priority than the
+    /// selected one. Defaults to 0, which never renominates.
+    pub renomination_window: Duration,
+
     /// lite agents do not perform connectivity check and only provide host candidates.
     pub lite: bool,
 
@@ -104,4 +194,40 @@ pub struct AgentConfig {
     /// Controls if self-signed certificates are accepted when connecting to TURN servers via TLS or
     /// DTLS.
     pub insecure_skip_verify: bool,
+
+    /// Keeps the ICE tie-breaker across restart() calls instead of drawing a new one.
+    pub preserve_tie_breaker_on_restart: bool,
+
+    /// Replaces the IP address of local host candidates with a random `.local` mDNS name,
+    /// so the address isn't leaked through signaling. The names are available from
+    /// `Agent::mdns_host_names()` to answer mDNS queries for them.
+    pub enable_mdns_obfuscation: bool,
+
+    /// Controls how remote `.local` host candidates are handled. With QueryOnly, the default,
+    /// they are held unpaired and announced through `Event::RemoteCandidateNeedsResolution`
+    /// until `Agent::resolve_remote_candidate()` supplies their address.
+    pub multicast_dns_mode: MulticastDnsMode,
+
+    /// A static `.local` name announced for every local host candidate when multicast_dns_mode
+    /// is QueryAndGather, instead of a random name per IP address.
+    pub multicast_dns_host_name: String,
+
+    /// When set, included as the SOFTWARE attribute of outbound STUN Binding Requests
+    /// (RFC 5389 section 15.10), e.g. for debugging on the remote side.
+    pub software: Option<String>,
+
+    /// Answers inbound STUN Binding Requests that reuse the transaction ID of one of the
+    /// last STUN_REPLAY_PROTECTION_WINDOW authenticated requests with the cached response
+    /// instead of processing them again, and rejects them if no response was sent.
+    pub stun_replay_protection: bool,
+}
+
+impl AgentConfig {
+    /// Checks every URL in urls with Url::validate.
+    pub fn validate(&self) -> Result<()> {
+        for url in &self.urls {
+            url.validate()?;
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,244 @@
+use super::*;
+use crate::candidate::candidate_server_reflexive::CandidateServerReflexiveConfig;
+
+/// A Binding request sent to a STUN server to learn the server reflexive address of a local
+/// host candidate.
+pub(crate) struct GatherRequest {
+    pub(crate) url: Url,
+    pub(crate) local_index: usize,
+    pub(crate) server_addr: SocketAddr,
+    pub(crate) message: BytesMut,
+    pub(crate) requests_sent: u16,
+    pub(crate) rto: Duration,
+    pub(crate) next_retransmit: Instant,
+}
+
+impl Agent {
+    /// Sends a Binding request to every UDP STUN server in urls from every local UDP host
+    /// candidate. Each server reflexive address learned is added as a local candidate and
+    /// announced through `Event::CandidateGathered`. A STUN server named by a host name is
+    /// announced through `Event::UrlNeedsResolution` and contacted once
+    /// `Agent::resolve_url()` resolves it.
+    pub fn gather_srflx_candidates(&mut self, now: Instant) -> Result<()> {
+        if self.is_closed() {
+            return Err(Error::ErrConnectionClosed);
+        }
+        if !contains_candidate_type(CandidateType::ServerReflexive, &self.candidate_types) {
+            return Ok(());
+        }
+
+        let urls: Vec<Url> = self
+            .urls
+            .iter()
+            .filter(|url| url.scheme == SchemeType::Stun && url.proto == ProtoType::Udp)
+            .cloned()
+            .collect();
+        for url in urls {
+            if let Ok(ip) = url.host.parse::<IpAddr>() {
+                let server_addr = SocketAddr::new(ip, url.port);
+                self.send_gather_requests(&url, server_addr, now)?;
+            } else {
+                self.events.push_back(Event::UrlNeedsResolution(url));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resolves the STUN and TURN servers of urls named by host, and sends them the Binding
+    /// requests of `Agent::gather_srflx_candidates()` or the Allocate requests of
+    /// `Agent::gather_relay_candidates()`.
+    pub fn resolve_url(&mut self, host: &str, ip: IpAddr, now: Instant) -> Result<()> {
+        if self.is_closed() {
+            return Err(Error::ErrConnectionClosed);
+        }
+
+        let urls: Vec<Url> = self
+            .urls
+            .iter()
+            .filter(|url| {
+                (url.scheme == SchemeType::Stun || url.scheme == SchemeType::Turn)
+                    && url.proto == ProtoType::Udp
+                    && url.host == host
+            })
+            .cloned()
+            .collect();
+        if urls.is_empty() {
+            return Err(Error::ErrHost);
+        }
+        for url in urls {
+            let server_addr = SocketAddr::new(ip, url.port);
+            if url.scheme == SchemeType::Turn {
+                self.allocate_relays(&url, server_addr)?;
+            } else {
+                self.send_gather_requests(&url, server_addr, now)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn send_gather_requests(
+        &mut self,
+        url: &Url,
+        server_addr: SocketAddr,
+        now: Instant,
+    ) -> Result<()> {
+        for local_index in 0..self.local_candidates.len() {
+            let local = &self.local_candidates[local_index];
+            if local.candidate_type() != CandidateType::Host
+                || !local.network_type().is_udp()
+                || local.addr().is_ipv4() != server_addr.is_ipv4()
+            {
+                continue;
+            }
+
+            let mut msg = Message::new();
+            let mut setters: Vec<Box<dyn Setter>> =
+                vec![Box::new(BINDING_REQUEST), Box::new(TransactionId::new())];
+            if let Some(software) = &self.software {
+                setters.push(Box::new(software.clone()));
+            }
+            setters.push(Box::new(FINGERPRINT));
+            msg.build(&setters)?;
+
+            let request = GatherRequest {
+                url: url.clone(),
+                local_index,
+                server_addr,
+                message: BytesMut::from(&msg.raw[..]),
+                requests_sent: 1,
+                rto: STUN_GATHER_RTO,
+                next_retransmit: now + STUN_GATHER_RTO,
+            };
+            trace!(
+                "[{}]: gather STUN from {} to {}",
+                self.get_name(),
+                local.addr(),
+                server_addr
+            );
+            self.send_gather_request(&request, now);
+            self.gather_requests.insert(msg.transaction_id, request);
+        }
+
+        Ok(())
+    }
+
+    fn send_gather_request(&mut self, request: &GatherRequest, now: Instant) {
+        self.transmits.push_back(Transmit {
+            now,
+            transport: TransportContext {
+                local_addr: self.local_candidates[request.local_index].addr(),
+                peer_addr: request.server_addr,
+                ecn: None,
+                protocol: Protocol::UDP,
+            },
+            message: request.message.clone(),
+        });
+    }
+
+    /// Handles the response of a STUN server to a Binding request of
+    /// `Agent::gather_srflx_candidates()`.
+    pub(crate) fn handle_gather_response(
+        &mut self,
+        m: &Message,
+        remote_addr: SocketAddr,
+    ) -> Result<()> {
+        if self
+            .gather_requests
+            .get(&m.transaction_id)
+            .is_none_or(|request| request.server_addr != remote_addr)
+        {
+            return Err(Error::ErrUnhandledStunpacket);
+        }
+        let Some(request) = self.gather_requests.remove(&m.transaction_id) else {
+            return Err(Error::ErrUnhandledStunpacket);
+        };
+
+        let mut xor_addr = XorMappedAddress::default();
+        if m.typ != BINDING_SUCCESS {
+            warn!(
+                "[{}]: STUN server {} answered with {}",
+                self.get_name(),
+                request.url,
+                m.typ
+            );
+            self.events.push_back(Event::GatheringFailed(request.url));
+            return Ok(());
+        } else if let Err(err) = xor_addr.get_from(m) {
+            warn!(
+                "[{}]: STUN server {} answered without XOR-MAPPED-ADDRESS: {}",
+                self.get_name(),
+                request.url,
+                err
+            );
+            self.events.push_back(Event::GatheringFailed(request.url));
+            return Ok(());
+        }
+
+        let base = &self.local_candidates[request.local_index];
+        let base_addr = base.addr();
+        let c = CandidateServerReflexiveConfig {
+            base_config: CandidateConfig {
+                network: base.network_type().network_short(),
+                address: xor_addr.ip.to_string(),
+                port: xor_addr.port,
+                component: base.component(),
+                ..CandidateConfig::default()
+            },
+            rel_addr: base_addr.ip().to_string(),
+            rel_port: base_addr.port(),
+        }
+        .new_candidate_server_reflexive()?;
+
+        let local_candidates = self.local_candidates.len();
+        self.add_local_candidate(c.clone())?;
+        if self.local_candidates.len() > local_candidates {
+            self.events.push_back(Event::CandidateGathered(Box::new(c)));
+        }
+
+        Ok(())
+    }
+
+    /// Retransmits the Binding requests to STUN servers whose retransmission timeout expired,
+    /// doubling the timeout each time, and gives up on a STUN server after
+    /// `MAX_STUN_GATHER_REQUESTS` requests.
+    pub(crate) fn handle_gather_timeout(&mut self, now: Instant) {
+        let expired: Vec<TransactionId> = self
+            .gather_requests
+            .iter()
+            .filter(|(_, request)| request.next_retransmit <= now)
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in expired {
+            let Some(mut request) = self.gather_requests.remove(&id) else {
+                continue;
+            };
+            if request.requests_sent >= MAX_STUN_GATHER_REQUESTS {
+                warn!(
+                    "[{}]: STUN server {} did not answer {} Binding requests",
+                    self.get_name(),
+                    request.url,
+                    request.requests_sent
+                );
+                self.events.push_back(Event::GatheringFailed(request.url));
+                continue;
+            }
+
+            request.requests_sent += 1;
+            request.rto *= 2;
+            request.next_retransmit = now + request.rto;
+            self.send_gather_request(&request, now);
+            self.gather_requests.insert(id, request);
+        }
+    }
+
+    /// Returns when the next Binding request to a STUN server times out.
+    pub(crate) fn gather_timeout(&self) -> Option<Instant> {
+        self.gather_requests
+            .values()
+            .map(|request| request.next_retransmit)
+            .min()
+    }
+}
@@ -0,0 +1,388 @@
+use super::*;
+use crate::candidate::candidate_relay::CandidateRelayConfig;
+use turn::client::{Client, ClientConfig, Event as TurnEvent, TurnCredentials};
+
+/// A TURN client allocating a relayed address on a TURN server from a local host candidate.
+pub(crate) struct RelayClient {
+    pub(crate) url: Url,
+    pub(crate) client: Client,
+    pub(crate) local_index: usize,
+    pub(crate) server_addr: SocketAddr,
+    // relayed address of the allocation, once the TURN server granted it
+    pub(crate) relayed_addr: Option<SocketAddr>,
+    // when the TURN client next needs handle_timeout()
+    pub(crate) timeout: Option<Instant>,
+}
+
+impl Agent {
+    /// Allocates a relayed address on every UDP TURN server in urls from every local UDP host
+    /// candidate. Each relayed address allocated is added as a local relay candidate and
+    /// announced through `Event::CandidateGathered`. A TURN server named by a host name is
+    /// announced through `Event::UrlNeedsResolution` and contacted once
+    /// `Agent::resolve_url()` resolves it.
+    pub fn gather_relay_candidates(&mut self) -> Result<()> {
+        if self.is_closed() {
+            return Err(Error::ErrConnectionClosed);
+        }
+        if !contains_candidate_type(CandidateType::Relay, &self.candidate_types) {
+            return Ok(());
+        }
+
+        let urls: Vec<Url> = self
+            .urls
+            .iter()
+            .filter(|url| url.scheme == SchemeType::Turn && url.proto == ProtoType::Udp)
+            .cloned()
+            .collect();
+        for url in urls {
+            if let Ok(ip) = url.host.parse::<IpAddr>() {
+                let server_addr = SocketAddr::new(ip, url.port);
+                self.allocate_relays(&url, server_addr)?;
+            } else {
+                self.events.push_back(Event::UrlNeedsResolution(url));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn allocate_relays(&mut self, url: &Url, server_addr: SocketAddr) -> Result<()> {
+        for local_index in 0..self.local_candidates.len() {
+            let local = &self.local_candidates[local_index];
+            if local.candidate_type() != CandidateType::Host
+                || !local.network_type().is_udp()
+                || local.addr().is_ipv4() != server_addr.is_ipv4()
+            {
+                continue;
+            }
+
+            let local_addr = local.addr();
+            let mut client = Client::new(ClientConfig {
+                stun_serv_addr: String::new(),
+                turn_serv_addr: server_addr.to_string(),
+                local_addr,
+                protocol: Protocol::UDP,
+                username: url.username.clone(),
+                password: url.password.clone(),
+                realm: String::new(),
+                credentials: TurnCredentials::LongTerm,
+                software: self
+                    .software
+                    .as_ref()
+                    .map(|software| software.text.clone())
+                    .unwrap_or_default(),
+                rto_in_ms: 0,
+                prefer_ipv6: false,
+                ipv6_timeout: Duration::ZERO,
+                force_local_addr: None,
+            })?;
+            client.allocate()?;
+
+            trace!(
+                "[{}]: allocate TURN relay from {} on {}",
+                self.get_name(),
+                local_addr,
+                server_addr
+            );
+            self.relay_clients.push(RelayClient {
+                url: url.clone(),
+                client,
+                local_index,
+                server_addr,
+                relayed_addr: None,
+                timeout: None,
+            });
+            self.poll_relay_transmits(self.relay_clients.len() - 1);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the TURN client talking to the peer of transport from its local address, unless
+    /// the message in buf answers a Binding request of `Agent::gather_srflx_candidates()` sent
+    /// to the same server.
+    pub(crate) fn find_relay_client(
+        &self,
+        transport: &TransportContext,
+        buf: &[u8],
+    ) -> Option<usize> {
+        if transport.protocol != Protocol::UDP {
+            return None;
+        }
+        if stun::message::is_message(buf) {
+            let mut id = TransactionId::default();
+            id.0.copy_from_slice(&buf[8..MESSAGE_HEADER_SIZE]);
+            if self.gather_requests.contains_key(&id) {
+                return None;
+            }
+        }
+
+        self.relay_clients.iter().position(|relay_client| {
+            relay_client.server_addr == transport.peer_addr
+                && self.local_candidates[relay_client.local_index].addr() == transport.local_addr
+        })
+    }
+
+    /// Passes a message from a TURN server to its TURN client, and handles the outcome.
+    pub(crate) fn handle_relay_read(
+        &mut self,
+        client_index: usize,
+        msg: Transmit<BytesMut>,
+    ) -> Result<()> {
+        let now = msg.now;
+        let result = self.relay_clients[client_index].client.handle_transmit(msg);
+        if let Err(err) = &result {
+            warn!(
+                "[{}]: Failed to handle TURN message from {}: {}",
+                self.get_name(),
+                self.relay_clients[client_index].server_addr,
+                err
+            );
+        }
+        let relayed_result = self.handle_relay_events(client_index, now);
+        result.and(relayed_result)
+    }
+
+    /// Handles the events of a TURN client and queues its transmits. Returns the first error
+    /// of the messages relayed to a local relay candidate.
+    fn handle_relay_events(&mut self, client_index: usize, now: Instant) -> Result<()> {
+        let mut result = Ok(());
+        while let Some(event) = self.relay_clients[client_index].client.poll_event() {
+            if let Err(err) = self.handle_relay_event(client_index, event, now) {
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+        self.poll_relay_transmits(client_index);
+        result
+    }
+
+    fn handle_relay_event(
+        &mut self,
+        client_index: usize,
+        event: TurnEvent,
+        now: Instant,
+    ) -> Result<()> {
+        match event {
+            TurnEvent::AllocateResponse(_, relayed_addr) => {
+                self.relay_clients[client_index].relayed_addr = Some(relayed_addr);
+
+                let base = &self.local_candidates[self.relay_clients[client_index].local_index];
+                let base_addr = base.addr();
+                let c = CandidateRelayConfig {
+                    base_config: CandidateConfig {
+                        network: base.network_type().network_short(),
+                        address: relayed_addr.ip().to_string(),
+                        port: relayed_addr.port(),
+                        component: base.component(),
+                        ..CandidateConfig::default()
+                    },
+                    rel_addr: base_addr.ip().to_string(),
+                    rel_port: base_addr.port(),
+                }
+                .new_candidate_relay()?;
+
+                let local_candidates = self.local_candidates.len();
+                self.add_local_candidate(c.clone())?;
+                if self.local_candidates.len() > local_candidates {
+                    self.events.push_back(Event::CandidateGathered(Box::new(c)));
+                }
+            }
+            TurnEvent::AllocateError(_, err) => {
+                let url = self.relay_clients[client_index].url.clone();
+                warn!(
+                    "[{}]: TURN server {} refused the allocation: {}",
+                    self.get_name(),
+                    url,
+                    err
+                );
+                self.events.push_back(Event::GatheringFailed(url));
+            }
+            TurnEvent::TransactionTimeout(_) => {
+                let relay_client = &self.relay_clients[client_index];
+                if relay_client.relayed_addr.is_none() {
+                    let url = relay_client.url.clone();
+                    warn!(
+                        "[{}]: TURN server {} did not answer the allocation",
+                        self.get_name(),
+                        url
+                    );
+                    self.events.push_back(Event::GatheringFailed(url));
+                }
+            }
+            TurnEvent::CreatePermissionResponse(_, peer_addr) => {
+                trace!(
+                    "[{}]: TURN server {} permitted {}",
+                    self.get_name(),
+                    self.relay_clients[client_index].server_addr,
+                    peer_addr
+                );
+            }
+            TurnEvent::CreatePermissionError(_, err) => {
+                warn!(
+                    "[{}]: TURN server {} refused a permission: {}",
+                    self.get_name(),
+                    self.relay_clients[client_index].server_addr,
+                    err
+                );
+            }
+            TurnEvent::DataIndicationOrChannelData(_, peer_addr, data) => {
+                let Some(relayed_addr) = self.relay_clients[client_index].relayed_addr else {
+                    return Err(Error::ErrUnhandledStunpacket);
+                };
+                let Some(local_index) = self.find_local_candidate(relayed_addr, Protocol::UDP)
+                else {
+                    return Err(Error::ErrUnhandledStunpacket);
+                };
+                if !stun::message::is_message(&data) {
+                    return self.handle_relayed_data(relayed_addr, peer_addr, data, now);
+                }
+                return self.handle_inbound_candidate_msg(
+                    local_index,
+                    &data,
+                    peer_addr,
+                    relayed_addr,
+                    now,
+                );
+            }
+            TurnEvent::BindingResponse(_, _) | TurnEvent::BindingError(_, _) => {}
+        }
+
+        Ok(())
+    }
+
+    /// Queues application data relayed by the TURN server for `Agent::poll_read()`, if it
+    /// comes from a remote candidate.
+    fn handle_relayed_data(
+        &mut self,
+        relayed_addr: SocketAddr,
+        peer_addr: SocketAddr,
+        data: BytesMut,
+        now: Instant,
+    ) -> Result<()> {
+        if self.find_remote_candidate(peer_addr).is_none() {
+            warn!(
+                "[{}]: Discarded relayed message, not a valid remote candidate from {}",
+                self.get_name(),
+                peer_addr,
+            );
+            return Err(Error::ErrNonStunmessage);
+        }
+
+        let transport = TransportContext {
+            local_addr: relayed_addr,
+            peer_addr,
+            ecn: None,
+            protocol: Protocol::UDP,
+        };
+        self.record_data_packet(&transport, false, data.len(), now);
+        self.relayed_reads.push_back(Transmit {
+            now,
+            transport,
+            message: data,
+        });
+        Ok(())
+    }
+
+    fn poll_relay_transmits(&mut self, client_index: usize) {
+        let relay_client = &mut self.relay_clients[client_index];
+        while let Some(transmit) = relay_client.client.poll_transmit() {
+            self.transmits.push_back(transmit);
+        }
+        relay_client.timeout = relay_client.client.poll_timout();
+    }
+
+    fn find_relay_client_by_relayed_addr(&self, relayed_addr: SocketAddr) -> Option<usize> {
+        self.relay_clients
+            .iter()
+            .position(|relay_client| relay_client.relayed_addr == Some(relayed_addr))
+    }
+
+    /// Asks the TURN server of a local relay candidate to permit traffic from a remote
+    /// candidate it is paired with.
+    pub(crate) fn create_relay_permission(&mut self, local_index: usize, remote_index: usize) {
+        let relayed_addr = self.local_candidates[local_index].addr();
+        let Some(client_index) = self.find_relay_client_by_relayed_addr(relayed_addr) else {
+            return;
+        };
+        let peer_addr = self.remote_candidates[remote_index].addr();
+
+        let result = self.relay_clients[client_index]
+            .client
+            .relay(relayed_addr)
+            .and_then(|mut relay| relay.create_permission(peer_addr));
+        if let Err(err) = result {
+            warn!(
+                "[{}]: Failed to create a TURN permission for {} on {}: {}",
+                self.get_name(),
+                peer_addr,
+                relayed_addr,
+                err
+            );
+        }
+        self.poll_relay_transmits(client_index);
+    }
+
+    /// Returns whether local_addr is the relayed address of a local relay candidate, whose
+    /// traffic has to go through `Agent::send_relayed()`.
+    pub fn is_relayed_addr(&self, local_addr: SocketAddr) -> bool {
+        self.find_relay_client_by_relayed_addr(local_addr).is_some()
+    }
+
+    /// Sends buf from a local relay candidate to peer_addr through its TURN server, in a Send
+    /// indication until a channel is bound to peer_addr and in ChannelData afterwards. The
+    /// messages to the TURN server are queued for `Agent::poll_transmit()`.
+    pub fn send_relayed(
+        &mut self,
+        relayed_addr: SocketAddr,
+        buf: &[u8],
+        peer_addr: SocketAddr,
+    ) -> Result<()> {
+        let client_index = self
+            .find_relay_client_by_relayed_addr(relayed_addr)
+            .ok_or(Error::ErrConnClosed)?;
+        let result = self.relay_clients[client_index]
+            .client
+            .relay(relayed_addr)
+            .and_then(|mut relay| relay.send_to(buf, peer_addr));
+        self.poll_relay_transmits(client_index);
+        result
+    }
+
+    /// Refreshes the allocations and permissions of the TURN clients whose timeout expired.
+    pub(crate) fn handle_relay_timeout(&mut self, now: Instant) {
+        for client_index in 0..self.relay_clients.len() {
+            if self.relay_clients[client_index]
+                .timeout
+                .is_some_and(|timeout| timeout <= now)
+            {
+                self.relay_clients[client_index].client.handle_timeout(now);
+                let _ = self.handle_relay_events(client_index, now);
+            }
+        }
+    }
+
+    /// Returns when a TURN client next needs `Agent::handle_relay_timeout()`.
+    pub(crate) fn relay_timeout(&self) -> Option<Instant> {
+        self.relay_clients
+            .iter()
+            .filter_map(|relay_client| relay_client.timeout)
+            .min()
+    }
+
+    /// Releases the allocations of all TURN clients.
+    pub(crate) fn close_relay_clients(&mut self) {
+        for client_index in 0..self.relay_clients.len() {
+            let relay_client = &mut self.relay_clients[client_index];
+            if let Some(relayed_addr) = relay_client.relayed_addr {
+                if let Ok(mut relay) = relay_client.client.relay(relayed_addr) {
+                    let _ = relay.close();
+                }
+            }
+            self.poll_relay_transmits(client_index);
+            self.relay_clients[client_index].client.close();
+        }
+        self.relay_clients.clear();
+    }
+}
@@ -1,3 +1,4 @@
+use crate::agent::agent_config::NominationMode;
 use crate::agent::Agent;
 use log::{debug, error, trace, warn};
 use std::net::SocketAddr;
@@ -13,34 +14,48 @@ use crate::candidate::{candidate_pair::*, *};
 
 trait ControllingSelector {
     fn start(&mut self);
-    fn contact_candidates(&mut self);
-    fn ping_candidate(&mut self, local_index: usize, remote_index: usize);
+    fn contact_candidates(&mut self, now: Instant);
+    fn ping_candidate(&mut self, local_index: usize, remote_index: usize, now: Instant);
     fn handle_success_response(
         &mut self,
         m: &Message,
         local_index: usize,
         remote_index: usize,
         remote_addr: SocketAddr,
+        now: Instant,
+    );
+    fn handle_binding_request(
+        &mut self,
+        m: &Message,
+        local_index: usize,
+        remote_index: usize,
+        now: Instant,
     );
-    fn handle_binding_request(&mut self, m: &Message, local_index: usize, remote_index: usize);
 }
 
 trait ControlledSelector {
     fn start(&mut self);
-    fn contact_candidates(&mut self);
-    fn ping_candidate(&mut self, local_index: usize, remote_index: usize);
+    fn contact_candidates(&mut self, now: Instant);
+    fn ping_candidate(&mut self, local_index: usize, remote_index: usize, now: Instant);
     fn handle_success_response(
         &mut self,
         m: &Message,
         local_index: usize,
         remote_index: usize,
         remote_addr: SocketAddr,
+        now: Instant,
+    );
+    fn handle_binding_request(
+        &mut self,
+        m: &Message,
+        local_index: usize,
+        remote_index: usize,
+        now: Instant,
     );
-    fn handle_binding_request(&mut self, m: &Message, local_index: usize, remote_index: usize);
 }
 
 impl Agent {
-    fn is_nominatable(&self, index: usize, is_local: bool) -> bool {
+    fn is_nominatable(&self, index: usize, is_local: bool, now: Instant) -> bool {
         let start_time = self.start_time;
         let c = if is_local {
             &self.local_candidates[index]
@@ -49,29 +64,25 @@ impl Agent {
         };
         match c.candidate_type() {
             CandidateType::Host => {
-                Instant::now()
-                    .checked_duration_since(start_time)
+                now.checked_duration_since(start_time)
                     .unwrap_or_else(|| Duration::from_secs(0))
                     .as_nanos()
                     > self.host_acceptance_min_wait.as_nanos()
             }
             CandidateType::ServerReflexive => {
-                Instant::now()
-                    .checked_duration_since(start_time)
+                now.checked_duration_since(start_time)
                     .unwrap_or_else(|| Duration::from_secs(0))
                     .as_nanos()
                     > self.srflx_acceptance_min_wait.as_nanos()
             }
             CandidateType::PeerReflexive => {
-                Instant::now()
-                    .checked_duration_since(start_time)
+                now.checked_duration_since(start_time)
                     .unwrap_or_else(|| Duration::from_secs(0))
                     .as_nanos()
                     > self.prflx_acceptance_min_wait.as_nanos()
             }
             CandidateType::Relay => {
-                Instant::now()
-                    .checked_duration_since(start_time)
+                now.checked_duration_since(start_time)
                     .unwrap_or_else(|| Duration::from_secs(0))
                     .as_nanos()
                     > self.relay_acceptance_min_wait.as_nanos()
@@ -86,7 +97,50 @@ impl Agent {
         }
     }
 
-    fn nominate_pair(&mut self) {
+    /// Returns whether the valid pair may be nominated in Regular nomination: either it is the
+    /// highest priority pair that can still succeed, or nomination_min_wait has elapsed.
+    fn is_nomination_wait_over(&self, pair_index: usize, now: Instant) -> bool {
+        self.get_best_available_candidate_pair() == Some(pair_index)
+            || now
+                .checked_duration_since(self.start_time)
+                .unwrap_or_else(|| Duration::from_secs(0))
+                >= self.nomination_min_wait
+    }
+
+    /// Returns whether the selected pair may still be replaced by a better one: only in Regular
+    /// nomination and within renomination_window of the start of the checks.
+    pub(crate) fn is_renomination_allowed(&self, now: Instant) -> bool {
+        self.nomination_mode == NominationMode::Regular
+            && now
+                .checked_duration_since(self.start_time)
+                .is_some_and(|elapsed| elapsed < self.renomination_window)
+    }
+
+    /// Keeps checking the remaining pairs, and nominates a valid pair with a higher priority
+    /// than the selected one.
+    pub(crate) fn renominate(&mut self, selected_pair: usize, now: Instant) {
+        self.ping_all_candidates(now);
+
+        if let Some(pair_index) = self.get_best_valid_candidate_pair() {
+            if self.nominated_pair != Some(pair_index)
+                && self.candidate_pairs[pair_index].priority()
+                    > self.candidate_pairs[selected_pair].priority()
+            {
+                trace!(
+                    "renominating ({}, {})",
+                    self.local_candidates[self.candidate_pairs[pair_index].local_index],
+                    self.remote_candidates[self.candidate_pairs[pair_index].remote_index],
+                );
+                self.set_nominated_pair(pair_index);
+            }
+        }
+
+        if self.nominated_pair.is_some() && self.nominated_pair != Some(selected_pair) {
+            self.nominate_pair(now);
+        }
+    }
+
+    fn nominate_pair(&mut self, now: Instant) {
         let result = {
             let Some(remote_credentials) = &self.ufrag_pwd.remote_credentials else {
                 error!("ufrag_pwd.remote_credentials is none");
@@ -104,18 +158,22 @@ impl Agent {
                         + ":"
                         + self.ufrag_pwd.local_credentials.ufrag.as_str();
                     let mut msg = Message::new();
-                    let result = msg.build(&[
+                    let mut setters: Vec<Box<dyn Setter>> = vec![
                         Box::new(BINDING_REQUEST),
                         Box::new(TransactionId::new()),
                         Box::new(Username::new(ATTR_USERNAME, username)),
                         Box::<UseCandidateAttr>::default(),
                         Box::new(AttrControlling(self.tie_breaker)),
                         Box::new(PriorityAttr(pair.local_priority)),
-                        Box::new(MessageIntegrity::new_short_term_integrity(
-                            remote_credentials.pwd.clone(),
-                        )),
-                        Box::new(FINGERPRINT),
-                    ]);
+                    ];
+                    if let Some(software) = &self.software {
+                        setters.push(Box::new(software.clone()));
+                    }
+                    setters.push(Box::new(MessageIntegrity::new_short_term_integrity(
+                        remote_credentials.pwd.clone(),
+                    )));
+                    setters.push(Box::new(FINGERPRINT));
+                    let result = msg.build(&setters);
                     (msg, result)
                 };
 
@@ -138,7 +196,7 @@ impl Agent {
         };
 
         if let Some((msg, local, remote)) = result {
-            self.send_binding_request(&msg, local, remote);
+            self.send_binding_request(&msg, local, remote, now);
         }
     }
 
@@ -150,21 +208,21 @@ impl Agent {
         }
     }
 
-    pub(crate) fn contact_candidates(&mut self) {
+    pub(crate) fn contact_candidates(&mut self, now: Instant) {
         if self.is_controlling {
-            ControllingSelector::contact_candidates(self);
+            ControllingSelector::contact_candidates(self, now);
         } else {
-            ControlledSelector::contact_candidates(self);
+            ControlledSelector::contact_candidates(self, now);
         }
     }
 
-    pub(crate) fn ping_candidate(&mut self, local_index: usize, remote_index: usize) {
+    pub(crate) fn ping_candidate(&mut self, local_index: usize, remote_index: usize, now: Instant) {
         trace!("[{}]: ping_candidate", self.get_name());
 
         if self.is_controlling {
-            ControllingSelector::ping_candidate(self, local_index, remote_index);
+            ControllingSelector::ping_candidate(self, local_index, remote_index, now);
         } else {
-            ControlledSelector::ping_candidate(self, local_index, remote_index);
+            ControlledSelector::ping_candidate(self, local_index, remote_index, now);
         }
     }
 
@@ -174,6 +232,7 @@ impl Agent {
         local_index: usize,
         remote_index: usize,
         remote_addr: SocketAddr,
+        now: Instant,
     ) {
         if self.is_controlling {
             ControllingSelector::handle_success_response(
@@ -182,6 +241,7 @@ impl Agent {
                 local_index,
                 remote_index,
                 remote_addr,
+                now,
             );
         } else {
             ControlledSelector::handle_success_response(
@@ -190,6 +250,7 @@ impl Agent {
                 local_index,
                 remote_index,
                 remote_addr,
+                now,
             );
         }
     }
@@ -199,11 +260,12 @@ impl Agent {
         m: &Message,
         local_index: usize,
         remote_index: usize,
+        now: Instant,
     ) {
         if self.is_controlling {
-            ControllingSelector::handle_binding_request(self, m, local_index, remote_index);
+            ControllingSelector::handle_binding_request(self, m, local_index, remote_index, now);
         } else {
-            ControlledSelector::handle_binding_request(self, m, local_index, remote_index);
+            ControlledSelector::handle_binding_request(self, m, local_index, remote_index, now);
         }
     }
 }
@@ -214,7 +276,7 @@ impl ControllingSelector for Agent {
         self.start_time = Instant::now();
     }
 
-    fn contact_candidates(&mut self) {
+    fn contact_candidates(&mut self, now: Instant) {
         // A lite selector should not contact candidates
         if self.lite {
             // This only happens if both peers are lite. See RFC 8445 S6.1.1 and S6.2
@@ -223,42 +285,48 @@ impl ControllingSelector for Agent {
 
         let nominated_pair_is_some = self.nominated_pair.is_some();
 
-        if self.get_selected_pair().is_some() {
-            if self.validate_selected_pair() {
-                self.check_keepalive();
+        if let Some(selected_pair) = self.get_selected_pair() {
+            if self.validate_selected_pair(now) {
+                self.check_keepalive(now);
+                if self.is_renomination_allowed(now) {
+                    self.renominate(selected_pair, now);
+                }
             }
+        } else if self.nomination_mode == NominationMode::Aggressive {
+            // every check carries USE-CANDIDATE, the first pair to succeed is selected
+            self.ping_all_candidates(now);
         } else if nominated_pair_is_some {
-            self.nominate_pair();
+            self.nominate_pair(now);
         } else {
             let has_nominated_pair = if let Some(pair_index) = self.get_best_valid_candidate_pair()
             {
                 let p = self.candidate_pairs[pair_index];
-                self.is_nominatable(p.local_index, true)
-                    && self.is_nominatable(p.remote_index, false)
+                self.is_nominatable(p.local_index, true, now)
+                    && self.is_nominatable(p.remote_index, false, now)
+                    && self.is_nomination_wait_over(pair_index, now)
             } else {
                 false
             };
 
             if has_nominated_pair {
                 if let Some(pair_index) = self.get_best_valid_candidate_pair() {
-                    let p = &mut self.candidate_pairs[pair_index];
+                    let p = &self.candidate_pairs[pair_index];
                     trace!(
                         "Nominatable pair found, nominating ({}, {})",
                         self.local_candidates[p.local_index],
                         self.remote_candidates[p.remote_index],
                     );
-                    p.nominated = true;
-                    self.nominated_pair = Some(pair_index);
+                    self.set_nominated_pair(pair_index);
                 }
 
-                self.nominate_pair();
+                self.nominate_pair(now);
             } else {
-                self.ping_all_candidates();
+                self.ping_all_candidates(now);
             }
         }
     }
 
-    fn ping_candidate(&mut self, local_index: usize, remote_index: usize) {
+    fn ping_candidate(&mut self, local_index: usize, remote_index: usize, now: Instant) {
         let (msg, result) = {
             let Some(remote_credentials) = &self.ufrag_pwd.remote_credentials else {
                 error!("ufrag_pwd.remote_credentials is none");
@@ -269,24 +337,33 @@ impl ControllingSelector for Agent {
                 + ":"
                 + self.ufrag_pwd.local_credentials.ufrag.as_str();
             let mut msg = Message::new();
-            let result = msg.build(&[
+            let mut setters: Vec<Box<dyn Setter>> = vec![
                 Box::new(BINDING_REQUEST),
                 Box::new(TransactionId::new()),
                 Box::new(Username::new(ATTR_USERNAME, username)),
-                Box::new(AttrControlling(self.tie_breaker)),
-                Box::new(PriorityAttr(self.local_candidates[local_index].priority())),
-                Box::new(MessageIntegrity::new_short_term_integrity(
-                    remote_credentials.pwd.clone(),
-                )),
-                Box::new(FINGERPRINT),
-            ]);
+            ];
+            if self.nomination_mode == NominationMode::Aggressive {
+                setters.push(Box::<UseCandidateAttr>::default());
+            }
+            setters.push(Box::new(AttrControlling(self.tie_breaker)));
+            setters.push(Box::new(PriorityAttr(
+                self.local_candidates[local_index].priority(),
+            )));
+            if let Some(software) = &self.software {
+                setters.push(Box::new(software.clone()));
+            }
+            setters.push(Box::new(MessageIntegrity::new_short_term_integrity(
+                remote_credentials.pwd.clone(),
+            )));
+            setters.push(Box::new(FINGERPRINT));
+            let result = msg.build(&setters);
             (msg, result)
         };
 
         if let Err(err) = result {
             error!("{}", err);
         } else {
-            self.send_binding_request(&msg, local_index, remote_index);
+            self.send_binding_request(&msg, local_index, remote_index, now);
         }
     }
 
@@ -296,8 +373,9 @@ impl ControllingSelector for Agent {
         local_index: usize,
         remote_index: usize,
         remote_addr: SocketAddr,
+        now: Instant,
     ) {
-        if let Some(pending_request) = self.handle_inbound_binding_success(m.transaction_id) {
+        if let Some(pending_request) = self.handle_inbound_binding_success(m.transaction_id, now) {
             let transaction_addr = pending_request.destination;
 
             // Assert that NAT is not symmetric
@@ -315,8 +393,9 @@ impl ControllingSelector for Agent {
             let selected_pair_is_none = self.get_selected_pair().is_none();
 
             if let Some(pair_index) = self.find_pair(local_index, remote_index) {
+                self.set_pair_state(pair_index, CandidatePairState::Succeeded);
                 let p = &mut self.candidate_pairs[pair_index];
-                p.state = CandidatePairState::Succeeded;
+                p.record_response_received(pending_request.timestamp, now);
                 trace!(
                     "Found valid candidate pair: {}, p.state: {}, isUseCandidate: {}, {}",
                     *p,
@@ -324,8 +403,13 @@ impl ControllingSelector for Agent {
                     pending_request.is_use_candidate,
                     selected_pair_is_none
                 );
-                if pending_request.is_use_candidate && selected_pair_is_none {
-                    self.set_selected_pair(Some(pair_index));
+                // a renominated pair replaces the selected one
+                if pending_request.is_use_candidate
+                    && (selected_pair_is_none
+                        || (self.nominated_pair == Some(pair_index)
+                            && self.selected_pair != Some(pair_index)))
+                {
+                    self.set_selected_pair(pair_index, now);
                 }
             } else {
                 // This shouldn't happen
@@ -339,8 +423,14 @@ impl ControllingSelector for Agent {
         }
     }
 
-    fn handle_binding_request(&mut self, m: &Message, local_index: usize, remote_index: usize) {
-        self.send_binding_success(m, local_index, remote_index);
+    fn handle_binding_request(
+        &mut self,
+        m: &Message,
+        local_index: usize,
+        remote_index: usize,
+        now: Instant,
+    ) {
+        self.send_binding_success(m, local_index, remote_index, now);
         trace!("controllingSelector: sendBindingSuccess");
 
         if let Some(pair_index) = self.find_pair(local_index, remote_index) {
@@ -364,13 +454,13 @@ impl ControllingSelector for Agent {
                         best_pair_index
                     );
                     if best_pair_index == pair_index
-                        && self.is_nominatable(p.local_index, true)
-                        && self.is_nominatable(p.remote_index, false)
+                        && self.is_nominatable(p.local_index, true, now)
+                        && self.is_nominatable(p.remote_index, false, now)
                     {
                         trace!("The candidate ({}, {}) is the best candidate available, marking it as nominated",
                             p.local_index, p.remote_index);
-                        self.nominated_pair = Some(pair_index);
-                        self.nominate_pair();
+                        self.set_nominated_pair(pair_index);
+                        self.nominate_pair(now);
                     }
                 } else {
                     trace!("No best pair available");
@@ -386,20 +476,20 @@ impl ControllingSelector for Agent {
 impl ControlledSelector for Agent {
     fn start(&mut self) {}
 
-    fn contact_candidates(&mut self) {
+    fn contact_candidates(&mut self, now: Instant) {
         // A lite selector should not contact candidates
         if self.lite {
-            self.validate_selected_pair();
+            self.validate_selected_pair(now);
         } else if self.get_selected_pair().is_some() {
-            if self.validate_selected_pair() {
-                self.check_keepalive();
+            if self.validate_selected_pair(now) {
+                self.check_keepalive(now);
             }
         } else {
-            self.ping_all_candidates();
+            self.ping_all_candidates(now);
         }
     }
 
-    fn ping_candidate(&mut self, local_index: usize, remote_index: usize) {
+    fn ping_candidate(&mut self, local_index: usize, remote_index: usize, now: Instant) {
         let (msg, result) = {
             let Some(remote_credentials) = &self.ufrag_pwd.remote_credentials else {
                 error!("ufrag_pwd.remote_credentials is none");
@@ -410,24 +500,28 @@ impl ControlledSelector for Agent {
                 + ":"
                 + self.ufrag_pwd.local_credentials.ufrag.as_str();
             let mut msg = Message::new();
-            let result = msg.build(&[
+            let mut setters: Vec<Box<dyn Setter>> = vec![
                 Box::new(BINDING_REQUEST),
                 Box::new(TransactionId::new()),
                 Box::new(Username::new(ATTR_USERNAME, username)),
                 Box::new(AttrControlled(self.tie_breaker)),
                 Box::new(PriorityAttr(self.local_candidates[local_index].priority())),
-                Box::new(MessageIntegrity::new_short_term_integrity(
-                    remote_credentials.pwd.clone(),
-                )),
-                Box::new(FINGERPRINT),
-            ]);
+            ];
+            if let Some(software) = &self.software {
+                setters.push(Box::new(software.clone()));
+            }
+            setters.push(Box::new(MessageIntegrity::new_short_term_integrity(
+                remote_credentials.pwd.clone(),
+            )));
+            setters.push(Box::new(FINGERPRINT));
+            let result = msg.build(&setters);
             (msg, result)
         };
 
         if let Err(err) = result {
             error!("{}", err);
         } else {
-            self.send_binding_request(&msg, local_index, remote_index);
+            self.send_binding_request(&msg, local_index, remote_index, now);
         }
     }
 
@@ -437,6 +531,7 @@ impl ControlledSelector for Agent {
         local_index: usize,
         remote_index: usize,
         remote_addr: SocketAddr,
+        now: Instant,
     ) {
         // https://tools.ietf.org/html/rfc8445#section-7.3.1.5
         // If the controlled agent does not accept the request from the
@@ -444,7 +539,7 @@ impl ControlledSelector for Agent {
         // request with an appropriate error code response (e.g., 400)
         // [RFC5389].
 
-        if let Some(pending_request) = self.handle_inbound_binding_success(m.transaction_id) {
+        if let Some(pending_request) = self.handle_inbound_binding_success(m.transaction_id, now) {
             let transaction_addr = pending_request.destination;
 
             // Assert that NAT is not symmetric
@@ -461,8 +556,9 @@ impl ControlledSelector for Agent {
             );
 
             if let Some(pair_index) = self.find_pair(local_index, remote_index) {
+                self.set_pair_state(pair_index, CandidatePairState::Succeeded);
                 let p = &mut self.candidate_pairs[pair_index];
-                p.state = CandidatePairState::Succeeded;
+                p.record_response_received(pending_request.timestamp, now);
                 trace!("Found valid candidate pair: {}", *p);
             } else {
                 // This shouldn't happen
@@ -476,7 +572,13 @@ impl ControlledSelector for Agent {
         }
     }
 
-    fn handle_binding_request(&mut self, m: &Message, local_index: usize, remote_index: usize) {
+    fn handle_binding_request(
+        &mut self,
+        m: &Message,
+        local_index: usize,
+        remote_index: usize,
+        now: Instant,
+    ) {
         if self.find_pair(local_index, remote_index).is_none() {
             self.add_pair(local_index, remote_index);
         }
@@ -492,10 +594,16 @@ impl ControlledSelector for Agent {
                     // previously sent by this pair produced a successful response and
                     // generated a valid pair (Section 7.2.5.3.2).  The agent sets the
                     // nominated flag value of the valid pair to true.
-                    if self.get_selected_pair().is_none() {
-                        self.set_selected_pair(Some(pair_index));
+                    // with several nominated pairs, the highest priority one is used
+                    // (RFC 8445 section 8.1.1)
+                    if self.get_selected_pair().is_none_or(|selected_pair| {
+                        selected_pair != pair_index
+                            && self.candidate_pairs[pair_index].priority()
+                                > self.candidate_pairs[selected_pair].priority()
+                    }) {
+                        self.set_selected_pair(pair_index, now);
                     }
-                    self.send_binding_success(m, local_index, remote_index);
+                    self.send_binding_success(m, local_index, remote_index, now);
                 } else {
                     // If the received Binding request triggered a new check to be
                     // enqueued in the triggered-check queue (Section 7.3.1.4), once the
@@ -505,11 +613,11 @@ impl ControlledSelector for Agent {
                     // MUST remove the candidate pair from the valid list, set the
                     // candidate pair state to Failed, and set the checklist state to
                     // Failed.
-                    self.ping_candidate(local_index, remote_index);
+                    self.ping_candidate(local_index, remote_index, now);
                 }
             } else {
-                self.send_binding_success(m, local_index, remote_index);
-                self.ping_candidate(local_index, remote_index);
+                self.send_binding_success(m, local_index, remote_index, now);
+                self.ping_candidate(local_index, remote_index, now);
             }
         }
     }
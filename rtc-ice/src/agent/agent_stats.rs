@@ -1,7 +1,7 @@
 use crate::agent::Agent;
 use std::time::Instant;
 
-use crate::candidate::{candidate_pair::CandidatePairState, CandidateType};
+use crate::candidate::{candidate_pair::CandidatePairState, Candidate, CandidateType};
 use crate::network_type::NetworkType;
 
 /// Contains ICE candidate pair statistics.
@@ -22,6 +22,10 @@ pub struct CandidatePairStats {
     /// if it is the highest-priority one amongst those whose nominated flag is set.
     pub nominated: bool,
 
+    /// It is true when this pair is the one currently selected by the agent for sending and
+    /// receiving data.
+    pub selected: bool,
+
     /// The total number of packets sent on this candidate pair.
     pub packets_sent: u32,
 
@@ -119,6 +123,7 @@ impl Default for CandidatePairStats {
             remote_candidate_id: String::new(),
             state: CandidatePairState::default(),
             nominated: false,
+            selected: false,
             packets_sent: 0,
             packets_received: 0,
             bytes_sent: 0,
@@ -191,6 +196,27 @@ pub struct CandidateStats {
     ///
     /// Only defined for local candidates. For remote candidates, this property is not applicable.
     pub deleted: bool,
+
+    /// The total number of packets, STUN and application data, sent on (local) or to (remote)
+    /// this candidate.
+    pub packets_sent: u64,
+
+    /// The total number of packets, STUN and application data, received on (local) or from
+    /// (remote) this candidate.
+    pub packets_received: u64,
+
+    /// The total number of bytes sent on (local) or to (remote) this candidate.
+    pub bytes_sent: u64,
+
+    /// The total number of bytes received on (local) or from (remote) this candidate.
+    pub bytes_received: u64,
+
+    /// The timestamp at which the last packet was sent on (local) or to (remote) this candidate.
+    pub last_packet_sent_timestamp: Instant,
+
+    /// The timestamp at which the last packet was received on (local) or from (remote) this
+    /// candidate.
+    pub last_packet_received_timestamp: Instant,
 }
 
 impl Default for CandidateStats {
@@ -206,6 +232,36 @@ impl Default for CandidateStats {
             url: String::new(),
             relay_protocol: String::new(),
             deleted: false,
+            packets_sent: 0,
+            packets_received: 0,
+            bytes_sent: 0,
+            bytes_received: 0,
+            last_packet_sent_timestamp: Instant::now(),
+            last_packet_received_timestamp: Instant::now(),
+        }
+    }
+}
+
+impl CandidateStats {
+    fn new(c: &Candidate, now: Instant) -> Self {
+        Self {
+            timestamp: now,
+            id: c.id(),
+            network_type: c.network_type(),
+            ip: c.address().to_owned(),
+            port: c.port(),
+            candidate_type: c.candidate_type(),
+            priority: c.priority(),
+            packets_sent: c.packets_sent,
+            packets_received: c.packets_received,
+            bytes_sent: c.bytes_sent,
+            bytes_received: c.bytes_received,
+            last_packet_sent_timestamp: c.last_packet_sent_time.unwrap_or(now),
+            last_packet_received_timestamp: c.last_packet_received_time.unwrap_or(now),
+            // URL string
+            relay_protocol: "udp".to_owned(),
+            // Deleted bool
+            ..CandidateStats::default()
         }
     }
 }
@@ -213,14 +269,33 @@ impl Default for CandidateStats {
 impl Agent {
     /// Returns a list of candidate pair stats.
     pub fn get_candidate_pairs_stats(&self) -> Vec<CandidatePairStats> {
+        let now = Instant::now();
         let mut res = Vec::with_capacity(self.candidate_pairs.len());
-        for cp in &self.candidate_pairs {
+        for (pair_index, cp) in self.candidate_pairs.iter().enumerate() {
             let stat = CandidatePairStats {
-                timestamp: Instant::now(),
+                timestamp: now,
                 local_candidate_id: self.local_candidates[cp.local_index].id(),
                 remote_candidate_id: self.remote_candidates[cp.remote_index].id(),
                 state: cp.state,
                 nominated: cp.nominated,
+                selected: self.selected_pair == Some(pair_index),
+                packets_sent: cp.packets_sent,
+                packets_received: cp.packets_received,
+                bytes_sent: cp.bytes_sent,
+                bytes_received: cp.bytes_received,
+                last_packet_sent_timestamp: cp.last_packet_sent_time.unwrap_or(now),
+                last_packet_received_timestamp: cp.last_packet_received_time.unwrap_or(now),
+                first_request_timestamp: cp.first_request_time.unwrap_or(now),
+                last_request_timestamp: cp.last_request_time.unwrap_or(now),
+                last_response_timestamp: cp.last_response_time.unwrap_or(now),
+                total_round_trip_time: cp.total_round_trip_time,
+                current_round_trip_time: cp.current_round_trip_time,
+                requests_received: cp.requests_received,
+                requests_sent: cp.requests_sent,
+                responses_received: cp.responses_received,
+                responses_sent: cp.responses_sent,
+                consent_requests_sent: cp.consent_requests_sent,
+                consent_expired_timestamp: cp.consent_expiry.unwrap_or(now),
                 ..CandidatePairStats::default()
             };
             res.push(stat);
@@ -230,45 +305,26 @@ impl Agent {
 
     /// Returns a list of local candidates stats.
     pub fn get_local_candidates_stats(&self) -> Vec<CandidateStats> {
-        let mut res = Vec::with_capacity(self.local_candidates.len());
-        for c in &self.local_candidates {
-            let stat = CandidateStats {
-                timestamp: Instant::now(),
-                id: c.id(),
-                network_type: c.network_type(),
-                ip: c.address().to_owned(),
-                port: c.port(),
-                candidate_type: c.candidate_type(),
-                priority: c.priority(),
-                // URL string
-                relay_protocol: "udp".to_owned(),
-                // Deleted bool
-                ..CandidateStats::default()
-            };
-            res.push(stat);
-        }
-        res
+        let now = Instant::now();
+        self.local_candidates
+            .iter()
+            .map(|c| CandidateStats::new(c, now))
+            .collect()
     }
 
     /// Returns a list of remote candidates stats.
     pub fn get_remote_candidates_stats(&self) -> Vec<CandidateStats> {
-        let mut res = Vec::with_capacity(self.remote_candidates.len());
-        for c in &self.remote_candidates {
-            let stat = CandidateStats {
-                timestamp: Instant::now(),
-                id: c.id(),
-                network_type: c.network_type(),
-                ip: c.address().to_owned(),
-                port: c.port(),
-                candidate_type: c.candidate_type(),
-                priority: c.priority(),
-                // URL string
-                relay_protocol: "udp".to_owned(),
-                // Deleted bool
-                ..CandidateStats::default()
-            };
-            res.push(stat);
-        }
+        let now = Instant::now();
+        self.remote_candidates
+            .iter()
+            .map(|c| CandidateStats::new(c, now))
+            .collect()
+    }
+
+    /// Returns the stats of the local candidates followed by those of the remote candidates.
+    pub fn get_candidates_stats(&self) -> Vec<CandidateStats> {
+        let mut res = self.get_local_candidates_stats();
+        res.extend(self.get_remote_candidates_stats());
         res
     }
 }
@@ -11,6 +11,11 @@ use crate::candidate::candidate_peer_reflexive::*;
 use crate::candidate::candidate_relay::CandidateRelayConfig;
 use crate::candidate::candidate_server_reflexive::*;
 use crate::candidate::*;
+use crate::tcp_type::TcpType;
+use turn::proto::data::Data;
+use turn::proto::lifetime::Lifetime;
+use turn::proto::peeraddr::PeerAddress;
+use turn::proto::relayaddr::RelayedAddress;
 
 #[test]
 fn test_pair_search() -> Result<()> {
@@ -166,6 +171,20 @@ fn pipe(
     Ok((a_agent, b_agent))
 }
 
+fn new_host_candidate(network: &str, address: &str, port: u16) -> Result<Candidate> {
+    CandidateHostConfig {
+        base_config: CandidateConfig {
+            network: network.to_owned(),
+            address: address.to_owned(),
+            port,
+            component: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+    .new_candidate_host()
+}
+
 #[test]
 fn test_on_selected_candidate_pair_change() -> Result<()> {
     let mut a = Agent::new(Arc::new(AgentConfig::default()))?;
@@ -201,7 +220,7 @@ fn test_on_selected_candidate_pair_change() -> Result<()> {
     // select the pair
     let (local, remote) = (0, 0);
     a.add_pair(local, remote);
-    a.set_selected_pair(Some(0));
+    a.set_selected_pair(0, Instant::now());
 
     // ensure that the callback fired on setting the pair
     let mut is_selected_candidate_pair_change_event_fired = false;
@@ -248,7 +267,7 @@ fn test_handle_peer_reflexive_udp_pflx_candidate() -> Result<()> {
         (
             a.ufrag_pwd.local_credentials.ufrag.to_owned() + ":",
             a.ufrag_pwd.local_credentials.pwd.clone(),
-            a.tie_breaker,
+            a.tie_breaker.wrapping_add(1),
         )
     };
 
@@ -265,7 +284,7 @@ fn test_handle_peer_reflexive_udp_pflx_candidate() -> Result<()> {
     ])?;
 
     {
-        a.handle_inbound(&mut msg, local, remote_addr)?;
+        a.handle_inbound(&mut msg, local, remote_addr, Instant::now())?;
 
         // length of remote candidate list must be one now
         assert_eq!(
@@ -300,6 +319,100 @@ fn test_handle_peer_reflexive_udp_pflx_candidate() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_stun_replay_protection() -> Result<()> {
+    let mut a = Agent::new(Arc::new(AgentConfig {
+        stun_replay_protection: true,
+        ..Default::default()
+    }))?;
+
+    let local_candidate = new_host_candidate("udp", "192.168.0.2", 777)?;
+    let local_priority = local_candidate.priority();
+    a.add_local_candidate(local_candidate)?;
+    a.ufrag_pwd.remote_credentials = Some(Credentials {
+        ufrag: "".to_string(),
+        pwd: "".to_string(),
+    });
+
+    let remote_addr = SocketAddr::from_str("172.17.0.3:999")?;
+    let username = a.ufrag_pwd.local_credentials.ufrag.to_owned() + ":";
+    let local_pwd = a.ufrag_pwd.local_credentials.pwd.clone();
+    let tie_breaker = a.tie_breaker.wrapping_add(1);
+    let binding_request = |transaction_id: TransactionId| -> Result<Message> {
+        let mut msg = Message::new();
+        msg.build(&[
+            Box::new(BINDING_REQUEST),
+            Box::new(transaction_id),
+            Box::new(Username::new(ATTR_USERNAME, username.clone())),
+            Box::new(AttrControlling(tie_breaker)),
+            Box::new(PriorityAttr(local_priority)),
+            Box::new(MessageIntegrity::new_short_term_integrity(
+                local_pwd.clone(),
+            )),
+            Box::new(FINGERPRINT),
+        ])?;
+        Ok(msg)
+    };
+
+    let tid = TransactionId::new();
+    a.handle_inbound(&mut binding_request(tid)?, 0, remote_addr, Instant::now())?;
+    let mut responses = vec![];
+    while let Some(transmit) = a.poll_transmit() {
+        let mut msg = Message::new();
+        msg.raw = transmit.message.to_vec();
+        msg.decode()?;
+        if msg.typ == BINDING_SUCCESS {
+            responses.push(transmit);
+        }
+    }
+    assert_eq!(responses.len(), 1);
+    let response = responses.remove(0);
+    let requests_received = a.candidate_pairs[0].requests_received;
+
+    // a retransmission is answered with the cached response without being processed
+    a.handle_inbound(&mut binding_request(tid)?, 0, remote_addr, Instant::now())?;
+    let retransmitted = a
+        .poll_transmit()
+        .expect("retransmission should be answered");
+    assert_eq!(retransmitted.transport, response.transport);
+    assert_eq!(retransmitted.message, response.message);
+    assert!(a.poll_transmit().is_none());
+    assert_eq!(a.candidate_pairs[0].requests_received, requests_received);
+
+    // a replayed request that was never answered is rejected
+    let unanswered = TransactionId::new();
+    a.record_transaction_id(unanswered);
+    assert_eq!(
+        a.handle_inbound(
+            &mut binding_request(unanswered)?,
+            0,
+            remote_addr,
+            Instant::now()
+        ),
+        Err(Error::ErrStunReplayedTransaction)
+    );
+    a.handle_inbound(
+        &mut binding_request(TransactionId::new())?,
+        0,
+        remote_addr,
+        Instant::now(),
+    )?;
+
+    // old transaction IDs fall out of the window
+    for _ in 0..STUN_REPLAY_PROTECTION_WINDOW {
+        a.handle_inbound(
+            &mut binding_request(TransactionId::new())?,
+            0,
+            remote_addr,
+            Instant::now(),
+        )?;
+    }
+    a.handle_inbound(&mut binding_request(tid)?, 0, remote_addr, Instant::now())?;
+
+    a.close()?;
+    Ok(())
+}
+
 #[test]
 fn test_handle_peer_reflexive_unknown_remote() -> Result<()> {
     let mut a = Agent::new(Arc::new(AgentConfig::default()))?;
@@ -308,12 +421,13 @@ fn test_handle_peer_reflexive_unknown_remote() -> Result<()> {
     tid.0[..3].copy_from_slice("ABC".as_bytes());
 
     let remote_pwd = {
-        a.pending_binding_requests = vec![BindingRequest {
+        a.pending_binding_requests.push(BindingRequest {
             timestamp: Instant::now(),
             transaction_id: tid,
             destination: SocketAddr::from_str("0.0.0.0:0")?,
             is_use_candidate: false,
-        }];
+            is_controlling: false,
+        });
         a.ufrag_pwd.remote_credentials = Some(Credentials {
             ufrag: "".to_string(),
             pwd: "".to_string(),
@@ -345,7 +459,7 @@ fn test_handle_peer_reflexive_unknown_remote() -> Result<()> {
         Box::new(FINGERPRINT),
     ])?;
 
-    let result = a.handle_inbound(&mut msg, local_index, remote_addr);
+    let result = a.handle_inbound(&mut msg, local_index, remote_addr, Instant::now());
     assert!(result.is_err());
 
     assert_eq!(
@@ -2006,3 +2120,1933 @@ fn test_lite_lifecycle() -> Result<()> {
     Ok(())
 }
 */
+
+#[test]
+fn test_pending_binding_requests_count() -> Result<()> {
+    let mut a = Agent::new(Arc::new(AgentConfig::default()))?;
+
+    a.local_candidates
+        .push(new_host_candidate("udp", "192.168.1.1", 19216)?);
+    a.remote_candidates
+        .push(new_host_candidate("udp", "192.168.1.2", 19217)?);
+
+    assert_eq!(a.pending_binding_requests_count(), 0);
+    assert!(a.oldest_pending_request_age(Instant::now()).is_none());
+
+    let mut m = Message::new();
+    m.build(&[Box::new(BINDING_REQUEST), Box::new(TransactionId::new())])?;
+    a.send_binding_request(&m, 0, 0, Instant::now());
+
+    let now = Instant::now();
+    assert_eq!(a.pending_binding_requests_count(), 1);
+    assert!(a.oldest_pending_request_age(now).is_some());
+
+    let later = now + MAX_BINDING_REQUEST_TIMEOUT + Duration::from_millis(100);
+    assert!(a.oldest_pending_request_age(later).unwrap() > MAX_BINDING_REQUEST_TIMEOUT);
+    a.contact(later);
+    assert_eq!(
+        a.pending_binding_requests_count(),
+        0,
+        "expired binding request should be removed by contact()"
+    );
+
+    a.close()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_inbound_same_tie_breaker() -> Result<()> {
+    let mut a = Agent::new(Arc::new(AgentConfig::default()))?;
+
+    a.add_local_candidate(new_host_candidate("udp", "192.168.0.2", 777)?)?;
+
+    a.ufrag_pwd.remote_credentials = Some(Credentials {
+        ufrag: "".to_string(),
+        pwd: "".to_string(),
+    });
+    let username = a.ufrag_pwd.local_credentials.ufrag.to_owned() + ":";
+    let local_pwd = a.ufrag_pwd.local_credentials.pwd.clone();
+
+    let mut msg = Message::new();
+    msg.build(&[
+        Box::new(BINDING_REQUEST),
+        Box::new(TransactionId::new()),
+        Box::new(Username::new(ATTR_USERNAME, username)),
+        Box::new(AttrControlling(a.tie_breaker)),
+        Box::new(PriorityAttr(1)),
+        Box::new(MessageIntegrity::new_short_term_integrity(local_pwd)),
+        Box::new(FINGERPRINT),
+    ])?;
+
+    let remote_addr = SocketAddr::from_str("172.17.0.3:999")?;
+    assert_eq!(
+        Err(Error::ErrSameICEControllingStatus),
+        a.handle_inbound(&mut msg, 0, remote_addr, Instant::now())
+    );
+    assert!(a.remote_candidates.is_empty());
+
+    a.close()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_preserve_tie_breaker_on_restart() -> Result<()> {
+    let mut a = Agent::new(Arc::new(AgentConfig {
+        preserve_tie_breaker_on_restart: true,
+        ..Default::default()
+    }))?;
+    let tie_breaker = a.tie_breaker;
+    a.restart("".to_owned(), "".to_owned(), false, false)?;
+    assert_eq!(tie_breaker, a.tie_breaker);
+    a.close()?;
+
+    let mut b = Agent::new(Arc::new(AgentConfig::default()))?;
+    let tie_breaker = b.tie_breaker;
+    b.restart("".to_owned(), "".to_owned(), false, false)?;
+    assert_ne!(tie_breaker, b.tie_breaker);
+    b.close()?;
+
+    Ok(())
+}
+
+fn forward_transmits(from: &mut Agent, to: &mut Agent) {
+    while let Some(transmit) = from.poll_transmit() {
+        let _ = to.handle_read(Transmit {
+            now: Instant::now(),
+            transport: TransportContext {
+                local_addr: transmit.transport.peer_addr,
+                peer_addr: transmit.transport.local_addr,
+                ..transmit.transport
+            },
+            message: transmit.message,
+        });
+    }
+}
+
+fn connect_pair(a: &mut Agent, b: &mut Agent) -> Result<()> {
+    a.add_local_candidate(new_host_candidate("udp", "192.168.0.1", 5000)?)?;
+    b.add_remote_candidate(new_host_candidate("udp", "192.168.0.1", 5000)?)?;
+    b.add_local_candidate(new_host_candidate("udp", "192.168.0.2", 5001)?)?;
+    a.add_remote_candidate(new_host_candidate("udp", "192.168.0.2", 5001)?)?;
+
+    let a_credentials = a.get_local_credentials().clone();
+    let b_credentials = b.get_local_credentials().clone();
+    a.start_connectivity_checks(true, b_credentials.ufrag, b_credentials.pwd)?;
+    b.start_connectivity_checks(false, a_credentials.ufrag, a_credentials.pwd)?;
+
+    for _ in 0..10 {
+        if a.is_connected() && b.is_connected() {
+            break;
+        }
+        forward_transmits(a, b);
+        forward_transmits(b, a);
+        a.handle_timeout(Instant::now());
+        b.handle_timeout(Instant::now());
+    }
+
+    Ok(())
+}
+
+impl Agent {
+    /// simulate_connected_pair creates a controlling and a controlled agent with the
+    /// given local ufrags and drives them to Connected by exchanging their STUN messages
+    /// in memory. It returns both agents with the local and remote address of the pair
+    /// selected by the controlling agent.
+    pub(crate) fn simulate_connected_pair(
+        local_ufrag: &str,
+        remote_ufrag: &str,
+    ) -> Result<(Agent, Agent, SocketAddr, SocketAddr)> {
+        let (mut a, mut b) = pipe(
+            Some(AgentConfig {
+                local_ufrag: local_ufrag.to_owned(),
+                ..Default::default()
+            }),
+            Some(AgentConfig {
+                local_ufrag: remote_ufrag.to_owned(),
+                ..Default::default()
+            }),
+        )?;
+        connect_pair(&mut a, &mut b)?;
+
+        let (local, remote) = a
+            .get_selected_candidate_pair()
+            .ok_or(Error::ErrNoCandidatePairs)?;
+        Ok((a, b, local.addr(), remote.addr()))
+    }
+}
+
+#[test]
+fn test_simulate_connected_pair() -> Result<()> {
+    let (mut a, mut b, local_addr, remote_addr) =
+        Agent::simulate_connected_pair("localufrag", "remoteufrag")?;
+
+    assert_eq!(a.state(), ConnectionState::Connected);
+    assert_eq!(b.state(), ConnectionState::Connected);
+    assert_eq!(a.get_local_credentials().ufrag, "localufrag");
+    assert_eq!(b.get_local_credentials().ufrag, "remoteufrag");
+
+    let (a_local, a_remote) = a
+        .get_selected_candidate_pair()
+        .expect("controlling agent should select a pair");
+    assert_eq!((a_local.addr(), a_remote.addr()), (local_addr, remote_addr));
+    let (b_local, b_remote) = b
+        .get_selected_candidate_pair()
+        .expect("controlled agent should select a pair");
+    assert_eq!((b_local.addr(), b_remote.addr()), (remote_addr, local_addr));
+
+    a.close()?;
+    b.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_get_nominated_pair() -> Result<()> {
+    let (mut a, mut b, _, _) = Agent::simulate_connected_pair("localufrag", "remoteufrag")?;
+
+    for agent in [&a, &b] {
+        let pair = agent
+            .get_nominated_pair()
+            .expect("connected agent should have a nominated pair");
+        assert!(pair.nominated());
+        assert!(agent.is_pair_nominated(pair.local_index, pair.remote_index));
+
+        let (local, remote) = agent.get_selected_candidate_pair().unwrap();
+        assert_eq!(
+            agent.local_candidates[pair.local_index].addr(),
+            local.addr()
+        );
+        assert_eq!(
+            agent.remote_candidates[pair.remote_index].addr(),
+            remote.addr()
+        );
+    }
+    assert!(!a.is_pair_nominated(usize::MAX, usize::MAX));
+
+    a.close()?;
+    b.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_agent_restart_in_band() -> Result<()> {
+    let (mut a, mut b, _, remote_addr) =
+        Agent::simulate_connected_pair("localufrag", "remoteufrag")?;
+    while a.poll_transmit().is_some() {}
+
+    a.restart_in_band("newlocalufrag".to_owned(), String::new())?;
+    assert_eq!(a.get_local_credentials().ufrag, "newlocalufrag");
+    assert_eq!(a.state(), ConnectionState::Checking);
+    assert_eq!(
+        a.get_remote_credentials().map(|c| c.ufrag.as_str()),
+        Some("remoteufrag")
+    );
+
+    // checks resume right away without new remote candidates
+    let transmit = a.poll_transmit().expect("binding request should be sent");
+    assert_eq!(transmit.transport.peer_addr, remote_addr);
+    let mut m = Message::new();
+    m.raw = transmit.message.to_vec();
+    m.decode()?;
+    let username = Username::get_from_as(&m, ATTR_USERNAME)?;
+    assert_eq!(username.to_string(), "remoteufrag:newlocalufrag");
+
+    a.close()?;
+    b.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_binding_request_software() -> Result<()> {
+    let (mut a, mut b) = pipe(
+        Some(AgentConfig {
+            software: Some("rtc-rs/1.0".to_owned()),
+            ..Default::default()
+        }),
+        None,
+    )?;
+    connect_pair(&mut a, &mut b)?;
+    assert!(a.is_connected() && b.is_connected());
+    while a.poll_transmit().is_some() {}
+
+    a.restart_in_band("newlocalufrag".to_owned(), String::new())?;
+    let transmit = a.poll_transmit().expect("binding request should be sent");
+    let mut m = Message::new();
+    m.raw = transmit.message.to_vec();
+    m.decode()?;
+    assert_eq!(m.typ, BINDING_REQUEST);
+    let software = TextAttribute::get_from_as(&m, ATTR_SOFTWARE)?;
+    assert_eq!(software.to_string(), "rtc-rs/1.0");
+
+    // the controlled agent was not configured with a SOFTWARE attribute
+    b.restart_in_band("newremoteufrag".to_owned(), String::new())?;
+    while let Some(transmit) = b.poll_transmit() {
+        let mut m = Message::new();
+        m.raw = transmit.message.to_vec();
+        m.decode()?;
+        if m.typ == BINDING_REQUEST {
+            assert!(!m.contains(ATTR_SOFTWARE));
+        }
+    }
+
+    a.close()?;
+    b.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_agent_state_predicates() -> Result<()> {
+    let (mut a, mut b) = pipe(None, None)?;
+    assert_eq!(a.state(), ConnectionState::New);
+    assert!(!a.is_connected());
+    assert!(!a.is_checking());
+
+    connect_pair(&mut a, &mut b)?;
+
+    assert!(a.is_connected(), "controlling agent should be connected");
+    assert!(b.is_connected(), "controlled agent should be connected");
+    assert!(a.wait_for_state(ConnectionState::Connected, 1));
+    assert!(!a.is_failed());
+
+    a.close()?;
+    assert!(a.is_closed());
+    assert!(!a.is_connected());
+
+    b.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_candidate_packet_stats() -> Result<()> {
+    let mut a = Agent::new(Arc::new(AgentConfig::default()))?;
+
+    a.local_candidates
+        .push(new_host_candidate("udp", "192.168.1.1", 19216)?);
+    a.remote_candidates
+        .push(new_host_candidate("udp", "192.168.1.2", 19217)?);
+
+    let mut m = Message::new();
+    m.build(&[Box::new(BINDING_REQUEST), Box::new(TransactionId::new())])?;
+    a.send_binding_request(&m, 0, 0, Instant::now());
+
+    let local_stats = a.get_local_candidates_stats();
+    assert_eq!(local_stats.len(), 1);
+    assert_eq!(local_stats[0].packets_sent, 1);
+    assert_eq!(local_stats[0].bytes_sent, m.raw.len() as u64);
+    assert_eq!(local_stats[0].packets_received, 0);
+    assert_eq!(local_stats[0].bytes_received, 0);
+
+    let remote_stats = a.get_remote_candidates_stats();
+    assert_eq!(remote_stats.len(), 1);
+    assert_eq!(remote_stats[0].packets_sent, 1);
+    assert_eq!(remote_stats[0].packets_received, 0);
+
+    a.close()?;
+
+    let (mut a, mut b) = pipe(None, None)?;
+    connect_pair(&mut a, &mut b)?;
+    for agent in [&a, &b] {
+        let local_stats = agent.get_local_candidates_stats();
+        assert!(local_stats[0].packets_sent > 0);
+        assert!(local_stats[0].packets_received > 0);
+        assert!(local_stats[0].bytes_received > 0);
+    }
+
+    a.close()?;
+    b.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_mdns_obfuscation() -> Result<()> {
+    let mut a = Agent::new(Arc::new(AgentConfig {
+        enable_mdns_obfuscation: true,
+        ..Default::default()
+    }))?;
+
+    a.add_local_candidate(new_host_candidate("udp", "192.168.1.1", 19216)?)?;
+    a.add_local_candidate(new_host_candidate("udp", "192.168.1.1", 19217)?)?;
+    a.add_local_candidate(new_host_candidate("udp", "10.0.0.1", 19216)?)?;
+    // the same candidate is only added once
+    a.add_local_candidate(new_host_candidate("udp", "192.168.1.1", 19216)?)?;
+
+    let candidates = a.get_local_candidates();
+    assert_eq!(candidates.len(), 3);
+    for c in candidates {
+        assert!(c.address().ends_with(".local"), "{}", c.address());
+        let marshaled = c.marshal();
+        assert!(!marshaled.contains("192.168.1.1") && !marshaled.contains("10.0.0.1"));
+    }
+
+    // the real address is still used for connectivity
+    assert_eq!(candidates[0].addr(), "192.168.1.1:19216".parse().unwrap());
+    assert_eq!(candidates[0].address(), candidates[1].address());
+    assert_ne!(candidates[0].address(), candidates[2].address());
+    assert_eq!(
+        a.mdns_host_names().get(&"192.168.1.1".parse().unwrap()),
+        Some(&candidates[0].address().to_owned())
+    );
+
+    a.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_agent_new_invalid_url() -> Result<()> {
+    let result = Agent::new(Arc::new(AgentConfig {
+        urls: vec![Url {
+            scheme: SchemeType::from("ftp"),
+            host: "example.com".to_owned(),
+            port: 3478,
+            ..Default::default()
+        }],
+        ..Default::default()
+    }));
+    assert_eq!(result.err(), Some(Error::ErrInvalidUrl));
+
+    Ok(())
+}
+
+#[test]
+fn test_set_remote_candidate_address() -> Result<()> {
+    let mut a = Agent::new(Arc::new(AgentConfig::default()))?;
+    a.add_local_candidate(new_host_candidate("udp", "192.168.0.1", 5000)?)?;
+
+    let remote = unmarshal_candidate(
+        "4207374052 1 udp 2130706431 1f7ac32e-9c3c-4f6c-a4f6-5bdbf5b3e8a2.local 5001 typ host",
+    )?;
+    a.add_remote_candidate(remote)?;
+    assert!(a.candidate_pairs.is_empty(), "mDNS candidate must wait");
+
+    let index = a
+        .find_remote_candidate_by_foundation("4207374052")
+        .expect("candidate should be found by its foundation");
+    assert_eq!(a.find_remote_candidate_by_foundation("0"), None);
+
+    let resolved_addr: SocketAddr = "192.168.0.2:5001".parse().unwrap();
+    a.set_remote_candidate_address(index, resolved_addr)?;
+    assert_eq!(a.remote_candidates.len(), 1);
+    assert_eq!(a.candidate_pairs.len(), 1);
+    let pair = a.candidate_pairs[0];
+    assert_eq!(pair.remote_index, index);
+    assert_eq!(a.remote_candidates[pair.remote_index].addr(), resolved_addr);
+
+    // resolving again updates the existing pair
+    let resolved_addr: SocketAddr = "[fe80::1]:5002".parse().unwrap();
+    a.set_remote_candidate_address(index, resolved_addr)?;
+    assert_eq!(a.candidate_pairs.len(), 1);
+    assert_eq!(a.remote_candidates[index].addr(), resolved_addr);
+    assert_eq!(a.remote_candidates[index].network_type(), NetworkType::Udp6);
+
+    assert_eq!(
+        a.set_remote_candidate_address(1, resolved_addr),
+        Err(Error::ErrRemoteCandidateNotFound)
+    );
+
+    a.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_agent_close_rejects_read() -> Result<()> {
+    let (mut a, mut b) = pipe(None, None)?;
+    connect_pair(&mut a, &mut b)?;
+    while a.poll_event().is_some() {}
+
+    a.close()?;
+    assert!(a.is_closed());
+    assert!(matches!(
+        a.poll_event(),
+        Some(Event::ConnectionStateChange(ConnectionState::Closed))
+    ));
+
+    // inbound traffic on the former local candidate is refused
+    let result = a.handle_read(Transmit {
+        now: Instant::now(),
+        transport: TransportContext {
+            local_addr: SocketAddr::from_str("192.168.0.1:5000")?,
+            peer_addr: SocketAddr::from_str("192.168.0.2:5001")?,
+            ..Default::default()
+        },
+        message: BytesMut::from(&b"binding request"[..]),
+    });
+    assert_eq!(Err(Error::ErrConnectionClosed), result);
+
+    b.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_find_local_candidate() -> Result<()> {
+    let mut a = Agent::new(Arc::new(AgentConfig::default()))?;
+
+    for port in 5000..5064 {
+        a.add_local_candidate(new_host_candidate("udp", "192.168.0.1", port)?)?;
+    }
+    a.add_local_candidate(new_host_candidate("tcp", "192.168.0.1", 5000)?)?;
+
+    let addr = |port: u16| SocketAddr::from_str(&format!("192.168.0.1:{port}"));
+    assert_eq!(Some(0), a.find_local_candidate(addr(5000)?, Protocol::UDP));
+    assert_eq!(Some(63), a.find_local_candidate(addr(5063)?, Protocol::UDP));
+    assert_eq!(Some(64), a.find_local_candidate(addr(5000)?, Protocol::TCP));
+    assert_eq!(None, a.find_local_candidate(addr(5064)?, Protocol::UDP));
+    assert_eq!(None, a.find_local_candidate(addr(5001)?, Protocol::TCP));
+
+    // candidates survive a restart that keeps them, and are forgotten on close
+    a.restart("".to_owned(), "".to_owned(), true, false)?;
+    assert_eq!(Some(63), a.find_local_candidate(addr(5063)?, Protocol::UDP));
+    a.close()?;
+    assert_eq!(None, a.find_local_candidate(addr(5063)?, Protocol::UDP));
+
+    Ok(())
+}
+
+#[test]
+fn test_resolve_remote_mdns_candidate() -> Result<()> {
+    const NAME: &str = "1f7ac32e-9c3c-4f6c-a4f6-5bdbf5b3e8a2.local";
+    let remote =
+        || unmarshal_candidate(&format!("4207374052 1 udp 2130706431 {NAME} 5001 typ host"));
+    let local = || new_host_candidate("udp", "192.168.0.1", 5000);
+
+    let mut a = Agent::new(Arc::new(AgentConfig {
+        multicast_dns_mode: MulticastDnsMode::Disabled,
+        ..Default::default()
+    }))?;
+    assert_eq!(
+        a.add_remote_candidate(remote()?),
+        Err(Error::ErrMulticastDnsNotSupported)
+    );
+    a.close()?;
+
+    let mut a = Agent::new(Arc::new(AgentConfig::default()))?;
+    a.add_local_candidate(local()?)?;
+    a.add_remote_candidate(remote()?)?;
+    assert!(a.candidate_pairs.is_empty(), "mDNS candidate must wait");
+
+    let mut needs_resolution = None;
+    while let Some(event) = a.poll_event() {
+        if let Event::RemoteCandidateNeedsResolution(name, candidate) = event {
+            needs_resolution = Some((name, candidate.port()));
+        }
+    }
+    assert_eq!(needs_resolution, Some((NAME.to_owned(), 5001)));
+
+    assert_eq!(
+        a.resolve_remote_candidate("unknown.local", "192.168.0.2".parse().unwrap()),
+        Err(Error::ErrRemoteCandidateNotFound)
+    );
+    a.resolve_remote_candidate(&NAME.to_uppercase(), "192.168.0.2".parse().unwrap())?;
+    assert_eq!(a.candidate_pairs.len(), 1);
+    assert_eq!(
+        a.remote_candidates[a.candidate_pairs[0].remote_index].addr(),
+        "192.168.0.2:5001".parse().unwrap()
+    );
+
+    // candidates that are never resolved are dropped on close
+    a.add_remote_candidate(unmarshal_candidate(
+        "1 1 udp 2130706431 e2b0cbea-2b44-4a2c-8a51-1b3f7ac4a0d6.local 5002 typ host",
+    )?)?;
+    a.close()?;
+    assert!(a.remote_candidates.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_multicast_dns_query_and_gather() -> Result<()> {
+    for host_name in ["noDot", "two.dots.local", "example.com"] {
+        let result = Agent::new(Arc::new(AgentConfig {
+            multicast_dns_mode: MulticastDnsMode::QueryAndGather,
+            multicast_dns_host_name: host_name.to_owned(),
+            ..Default::default()
+        }));
+        assert_eq!(
+            result.err(),
+            Some(Error::ErrInvalidMulticastDnshostName),
+            "{host_name}"
+        );
+    }
+
+    let mut a = Agent::new(Arc::new(AgentConfig {
+        multicast_dns_mode: MulticastDnsMode::QueryAndGather,
+        multicast_dns_host_name: "webrtc-rs.local".to_owned(),
+        ..Default::default()
+    }))?;
+    a.add_local_candidate(new_host_candidate("udp", "192.168.0.1", 5000)?)?;
+
+    let marshaled = a.get_local_candidates()[0].marshal();
+    assert!(marshaled.contains("webrtc-rs.local 5000"), "{marshaled}");
+    assert!(!marshaled.contains("192.168.0.1"), "{marshaled}");
+
+    // inbound traffic to the real address still finds the candidate
+    let real_addr = SocketAddr::from_str("192.168.0.1:5000")?;
+    assert_eq!(Some(0), a.find_local_candidate(real_addr, Protocol::UDP));
+    assert_eq!(
+        Err(Error::ErrNonStunmessage),
+        a.handle_read(Transmit {
+            now: Instant::now(),
+            transport: TransportContext {
+                local_addr: real_addr,
+                peer_addr: SocketAddr::from_str("192.168.0.2:5001")?,
+                ..Default::default()
+            },
+            message: BytesMut::from(&b"not a stun message"[..]),
+        })
+    );
+
+    a.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_candidate_pair_stats_after_exchange() -> Result<()> {
+    let (mut a, mut b, local_addr, remote_addr) =
+        Agent::simulate_connected_pair("localufrag", "remoteufrag")?;
+
+    let stats = a.get_candidate_pairs_stats();
+    assert_eq!(stats.len(), 1);
+    let stat = &stats[0];
+    assert_eq!(stat.state, CandidatePairState::Succeeded);
+    assert!(stat.nominated);
+    assert!(stat.selected);
+    assert!(stat.requests_sent > 0);
+    assert!(stat.requests_received > 0);
+    assert!(stat.responses_sent > 0);
+    assert!(stat.responses_received > 0);
+    assert!(stat.responses_received <= stat.requests_sent);
+    assert!(stat.current_round_trip_time >= 0.0);
+    assert!(stat.current_round_trip_time < 1.0);
+    assert!(stat.total_round_trip_time >= stat.current_round_trip_time);
+    assert!(stat.first_request_timestamp <= stat.last_request_timestamp);
+    assert!(stat.last_request_timestamp <= stat.last_response_timestamp);
+
+    let stats = b.get_candidate_pairs_stats();
+    assert_eq!(stats.len(), 1);
+    assert!(stats[0].selected);
+    assert!(stats[0].requests_received > 0);
+    assert!(stats[0].responses_sent > 0);
+
+    let transport = TransportContext {
+        local_addr,
+        peer_addr: remote_addr,
+        ..Default::default()
+    };
+    let now = Instant::now() + Duration::from_secs(1);
+    a.record_data_packet(&transport, true, 100, now);
+    a.record_data_packet(&transport, true, 50, now);
+    a.record_data_packet(&transport, false, 20, now);
+    // traffic from an unknown peer is not accounted to any pair
+    a.record_data_packet(
+        &TransportContext {
+            peer_addr: SocketAddr::from_str("10.0.0.1:1234")?,
+            ..transport
+        },
+        false,
+        1000,
+        Instant::now(),
+    );
+
+    let stat = &a.get_candidate_pairs_stats()[0];
+    assert_eq!(stat.packets_sent, 2);
+    assert_eq!(stat.bytes_sent, 150);
+    assert_eq!(stat.packets_received, 1);
+    assert_eq!(stat.bytes_received, 20);
+    assert_eq!(stat.last_packet_sent_timestamp, now);
+    assert_eq!(stat.last_packet_received_timestamp, now);
+
+    a.close()?;
+    b.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_consent_expiry_fails_selected_pair() -> Result<()> {
+    // without disconnected and failed timeouts only the consent can fail the pair
+    let config = || AgentConfig {
+        disconnected_timeout: Some(Duration::from_secs(0)),
+        failed_timeout: Some(Duration::from_secs(0)),
+        ..Default::default()
+    };
+    let (mut a, mut b) = pipe(Some(config()), Some(config()))?;
+    connect_pair(&mut a, &mut b)?;
+    let pair_index = a
+        .selected_pair
+        .expect("controlling agent should select a pair");
+    let now = Instant::now();
+
+    // consent checks are sent, but go unanswered for CONSENT_TIMEOUT
+    let mut elapsed = Duration::from_secs(0);
+    while elapsed < CONSENT_TIMEOUT {
+        assert_eq!(a.state(), ConnectionState::Connected);
+        elapsed += Duration::from_secs(1);
+        a.handle_timeout(now + elapsed);
+        while a.poll_transmit().is_some() {}
+    }
+    assert!(a.candidate_pairs[pair_index].consent_requests_sent > 0);
+    assert_eq!(
+        a.candidate_pairs[pair_index].state,
+        CandidatePairState::Failed
+    );
+    assert_eq!(a.state(), ConnectionState::Failed);
+
+    // the consent of a lite agent never expires
+    let (mut c, mut d) = pipe(Some(config()), Some(config()))?;
+    connect_pair(&mut c, &mut d)?;
+    let pair_index = c
+        .selected_pair
+        .expect("controlling agent should select a pair");
+    c.lite = true;
+    assert!(!c.consent_expired(pair_index, now + CONSENT_TIMEOUT));
+
+    a.close()?;
+    b.close()?;
+    c.close()?;
+    d.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_keepalive_binding_indication() -> Result<()> {
+    let config = || AgentConfig {
+        keepalive_interval: Some(Duration::from_secs(1)),
+        use_binding_indication_for_keepalive: true,
+        ..Default::default()
+    };
+    let (mut a, mut b) = pipe(Some(config()), Some(config()))?;
+    connect_pair(&mut a, &mut b)?;
+    assert!(a.is_connected());
+    while a.poll_transmit().is_some() {}
+    let now = Instant::now();
+
+    // nothing was sent or received for longer than the keepalive interval, and the first
+    // consent check is not due before 0.8 times the consent check interval
+    let pending = a.pending_binding_requests_count();
+    a.check_keepalive(now + Duration::from_secs(2));
+
+    let transmit = a.poll_transmit().expect("keepalive should be sent");
+    let mut m = Message {
+        raw: transmit.message.to_vec(),
+        ..Message::default()
+    };
+    m.decode()?;
+    assert_eq!(m.typ, MessageType::new(METHOD_BINDING, CLASS_INDICATION));
+    assert_eq!(a.pending_binding_requests_count(), pending);
+
+    // the keepalive is accepted by the remote agent
+    b.handle_read(Transmit {
+        now: transmit.now,
+        transport: TransportContext {
+            local_addr: transmit.transport.peer_addr,
+            peer_addr: transmit.transport.local_addr,
+            ..transmit.transport
+        },
+        message: transmit.message,
+    })?;
+
+    // a due consent check is a tracked Binding Request
+    let pair_index = a
+        .selected_pair
+        .expect("controlling agent should select a pair");
+    let consent_check = a
+        .next_consent_check
+        .expect("selected pair should schedule a consent check");
+    a.check_keepalive(consent_check);
+
+    let transmit = a.poll_transmit().expect("consent check should be sent");
+    let mut m = Message {
+        raw: transmit.message.to_vec(),
+        ..Message::default()
+    };
+    m.decode()?;
+    assert_eq!(m.typ, BINDING_REQUEST);
+    assert_eq!(a.pending_binding_requests_count(), pending + 1);
+    assert_eq!(a.candidate_pairs[pair_index].consent_requests_sent, 1);
+    assert!(a.next_consent_check.is_some_and(|t| t > consent_check));
+
+    a.close()?;
+    b.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_pending_binding_requests_cap() -> Result<()> {
+    let mut a = Agent::new(Arc::new(AgentConfig::default()))?;
+
+    a.local_candidates
+        .push(new_host_candidate("udp", "192.168.1.1", 19216)?);
+    a.remote_candidates
+        .push(new_host_candidate("udp", "192.168.1.2", 19217)?);
+
+    let now = Instant::now();
+    let mut transaction_ids = vec![];
+    for _ in 0..500 {
+        let mut m = Message::new();
+        m.build(&[Box::new(BINDING_REQUEST), Box::new(TransactionId::new())])?;
+        a.send_binding_request(&m, 0, 0, now);
+        transaction_ids.push(m.transaction_id);
+
+        assert!(a.pending_binding_requests_count() <= DEFAULT_MAX_PENDING_BINDING_REQUESTS);
+    }
+    assert_eq!(
+        a.pending_binding_requests_count(),
+        DEFAULT_MAX_PENDING_BINDING_REQUESTS
+    );
+
+    // the oldest requests were evicted, the most recent ones can still be matched
+    assert!(a
+        .handle_inbound_binding_success(transaction_ids[0], now)
+        .is_none());
+    let recent = transaction_ids[transaction_ids.len() - 1];
+    let binding_request = a
+        .handle_inbound_binding_success(recent, now)
+        .expect("recent binding request should be pending");
+    assert_eq!(binding_request.transaction_id, recent);
+    assert!(a.handle_inbound_binding_success(recent, now).is_none());
+    let oldest_kept = transaction_ids[transaction_ids.len() - DEFAULT_MAX_PENDING_BINDING_REQUESTS];
+    assert!(a.handle_inbound_binding_success(oldest_kept, now).is_some());
+    assert_eq!(
+        a.pending_binding_requests_count(),
+        DEFAULT_MAX_PENDING_BINDING_REQUESTS - 2
+    );
+
+    a.close()?;
+
+    let mut a = Agent::new(Arc::new(AgentConfig {
+        max_pending_binding_requests: Some(3),
+        ..Default::default()
+    }))?;
+    a.local_candidates
+        .push(new_host_candidate("udp", "192.168.1.1", 19216)?);
+    a.remote_candidates
+        .push(new_host_candidate("udp", "192.168.1.2", 19217)?);
+    for _ in 0..10 {
+        let mut m = Message::new();
+        m.build(&[Box::new(BINDING_REQUEST), Box::new(TransactionId::new())])?;
+        a.send_binding_request(&m, 0, 0, now);
+    }
+    assert_eq!(a.pending_binding_requests_count(), 3);
+
+    a.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_pending_binding_requests_evict_after_remove() {
+    let now = Instant::now();
+    let request = |transaction_id: TransactionId| BindingRequest {
+        timestamp: now,
+        transaction_id,
+        ..Default::default()
+    };
+    let ids: Vec<TransactionId> = (0..6).map(|_| TransactionId::new()).collect();
+    let mut pending = PendingBindingRequests::new(3);
+
+    for id in &ids[..3] {
+        assert!(pending.push(request(*id)).is_none());
+    }
+    // the removed request is skipped, the oldest remaining one is evicted first
+    assert!(pending.remove(&ids[1]).is_some());
+    assert!(pending.push(request(ids[3])).is_none());
+    let evicted = pending
+        .push(request(ids[4]))
+        .expect("pending requests are full");
+    assert_eq!(evicted.transaction_id, ids[0]);
+    let evicted = pending
+        .push(request(ids[5]))
+        .expect("pending requests are full");
+    assert_eq!(evicted.transaction_id, ids[2]);
+
+    // a reused transaction ID moves its request to the back
+    assert!(pending.push(request(ids[3])).is_none());
+    assert_eq!(pending.len(), 3);
+    assert_eq!(pending.oldest().map(|r| r.transaction_id), Some(ids[4]));
+
+    // stale entries don't pile up when requests are answered out of order
+    assert!(pending.remove(&ids[5]).is_some());
+    for _ in 0..1000 {
+        let id = TransactionId::new();
+        pending.push(request(id));
+        pending.remove(&id);
+    }
+    assert_eq!(pending.len(), 2);
+    assert!(pending.order.len() <= 2 * pending.len() + 16);
+    assert_eq!(pending.oldest().map(|r| r.transaction_id), Some(ids[4]));
+}
+
+#[test]
+fn test_prune_candidate_pairs() -> Result<()> {
+    let srflx = |address: &str, port: u16, rel_addr: &str, rel_port: u16| {
+        CandidateServerReflexiveConfig {
+            base_config: CandidateConfig {
+                network: "udp".to_owned(),
+                address: address.to_owned(),
+                port,
+                component: 1,
+                ..Default::default()
+            },
+            rel_addr: rel_addr.to_owned(),
+            rel_port,
+        }
+        .new_candidate_server_reflexive()
+    };
+
+    let mut a = Agent::new(Arc::new(AgentConfig::default()))?;
+    a.add_remote_candidate(new_host_candidate("udp", "10.0.0.1", 7000)?)?;
+    a.add_remote_candidate(new_host_candidate("udp", "10.0.0.2", 7001)?)?;
+    a.add_local_candidate(new_host_candidate("udp", "192.168.0.1", 5000)?)?;
+    a.add_local_candidate(srflx("1.2.3.4", 6000, "192.168.0.1", 5000)?)?;
+    a.add_local_candidate(new_host_candidate("udp", "192.168.0.2", 5000)?)?;
+    // a srflx candidate without a local base is kept as is
+    a.add_local_candidate(srflx("1.2.3.5", 6001, "192.168.0.3", 5000)?)?;
+
+    // the pairs of the first srflx candidate are redundant with the pairs of its base
+    assert_eq!(a.candidate_pairs.len(), 6);
+    for p in &a.candidate_pairs {
+        assert_ne!(p.local_index, 1);
+    }
+    for w in a.candidate_pairs.windows(2) {
+        assert!(w[0].priority() >= w[1].priority());
+    }
+    let srflx_pairs = a
+        .candidate_pairs
+        .iter()
+        .filter(|p| p.local_index == 3)
+        .count();
+    assert_eq!(srflx_pairs, 2);
+    // srflx pairs have a lower priority than host pairs
+    assert_eq!(a.candidate_pairs[4].local_index, 3);
+    assert_eq!(a.candidate_pairs[5].local_index, 3);
+
+    a.close()?;
+
+    // the checklist is limited to max_candidate_pairs, keeping the selected pair
+    let mut a = Agent::new(Arc::new(AgentConfig {
+        max_candidate_pairs: Some(2),
+        ..Default::default()
+    }))?;
+    a.add_local_candidate(srflx("1.2.3.5", 6001, "192.168.0.3", 5000)?)?;
+    a.add_remote_candidate(new_host_candidate("udp", "10.0.0.1", 7000)?)?;
+    a.set_selected_pair(0, Instant::now());
+    a.add_local_candidate(new_host_candidate("udp", "192.168.0.1", 5000)?)?;
+    a.add_local_candidate(new_host_candidate("udp", "192.168.0.2", 5000)?)?;
+    a.add_remote_candidate(new_host_candidate("udp", "10.0.0.2", 7001)?)?;
+
+    assert_eq!(a.candidate_pairs.len(), 3);
+    let selected = a.selected_pair.expect("selected pair should be kept");
+    let p = &a.candidate_pairs[selected];
+    assert_eq!((p.local_index, p.remote_index), (0, 0));
+    assert_eq!(selected, 2, "selected pair has the lowest priority");
+    assert!(a.candidate_pairs[0].priority() >= a.candidate_pairs[1].priority());
+
+    a.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_agent_graceful_restart() -> Result<()> {
+    let (mut a, mut b, local_addr, remote_addr) =
+        Agent::simulate_connected_pair("localufrag", "remoteufrag")?;
+    while a.poll_event().is_some() {}
+
+    // the old pair could not be received on without its local candidate
+    assert_eq!(
+        a.restart("".to_owned(), "".to_owned(), false, true),
+        Err(Error::ErrGracefulRestartWithoutLocalCandidates)
+    );
+    assert!(a.is_connected());
+
+    a.restart("".to_owned(), "".to_owned(), true, true)?;
+    b.restart("".to_owned(), "".to_owned(), true, true)?;
+
+    // checking again, without going through disconnected
+    assert_eq!(a.state(), ConnectionState::Checking);
+    let mut events = vec![];
+    while let Some(event) = a.poll_event() {
+        events.push(event);
+    }
+    assert!(!events.iter().any(|event| matches!(
+        event,
+        Event::ConnectionStateChange(ConnectionState::Disconnected)
+    )));
+
+    // the old pair is still usable while the new checks run
+    let (local, remote) = a
+        .get_selected_candidate_pair()
+        .expect("old pair should be kept during a graceful restart");
+    assert_eq!((local.addr(), remote.addr()), (local_addr, remote_addr));
+    assert!(a.validate_non_stun_traffic(0, remote_addr, 0, Instant::now()));
+    assert!(!a.validate_non_stun_traffic(
+        0,
+        SocketAddr::from_str("10.0.0.1:1234")?,
+        0,
+        Instant::now()
+    ));
+
+    // selecting a new pair retires the old one
+    connect_pair(&mut a, &mut b)?;
+    assert!(a.is_connected());
+    assert!(a.old_selected_pair.is_none());
+    let mut events = vec![];
+    while let Some(event) = a.poll_event() {
+        events.push(event);
+    }
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, Event::IceRestartCompleted)));
+    let (local, _) = a
+        .get_selected_candidate_pair()
+        .expect("new pair should be selected");
+    assert_eq!(local.addr(), local_addr);
+
+    // a regular restart drops the selected pair immediately
+    a.restart("".to_owned(), "".to_owned(), true, false)?;
+    assert!(a.get_selected_candidate_pair().is_none());
+    assert!(!a.validate_non_stun_traffic(0, remote_addr, 0, Instant::now()));
+
+    a.close()?;
+    b.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_candidate_pair_state_change_events() -> Result<()> {
+    let (mut a, mut b) = pipe(None, None)?;
+    connect_pair(&mut a, &mut b)?;
+    assert!(a.is_connected());
+
+    let mut states = vec![];
+    let mut nominations = 0;
+    while let Some(event) = a.poll_event() {
+        match event {
+            Event::CandidatePairStateChange(local, remote, state) => {
+                assert_eq!(local.addr(), SocketAddr::from_str("192.168.0.1:5000")?);
+                assert_eq!(remote.addr(), SocketAddr::from_str("192.168.0.2:5001")?);
+                states.push(state);
+            }
+            Event::NominationChange(local, remote) => {
+                assert_eq!(local.addr(), SocketAddr::from_str("192.168.0.1:5000")?);
+                assert_eq!(remote.addr(), SocketAddr::from_str("192.168.0.2:5001")?);
+                // nominated once the pair succeeded
+                assert_eq!(states.last(), Some(&CandidatePairState::Succeeded));
+                nominations += 1;
+            }
+            _ => {}
+        }
+    }
+    assert_eq!(
+        states,
+        vec![
+            CandidatePairState::Frozen,
+            CandidatePairState::Waiting,
+            CandidatePairState::InProgress,
+            CandidatePairState::Succeeded,
+        ]
+    );
+    assert_eq!(nominations, 1);
+
+    // the controlled agent doesn't nominate
+    let mut nominated = false;
+    while let Some(event) = b.poll_event() {
+        nominated |= matches!(event, Event::NominationChange(_, _));
+    }
+    assert!(!nominated);
+
+    a.close()?;
+    b.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_tcp_framing() -> Result<()> {
+    let mut a = Agent::new(Arc::new(AgentConfig::default()))?;
+
+    let tcp_host = |address: &str, port: u16, tcp_type: TcpType| {
+        CandidateHostConfig {
+            base_config: CandidateConfig {
+                network: "tcp".to_owned(),
+                address: address.to_owned(),
+                port,
+                component: 1,
+                ..Default::default()
+            },
+            tcp_type,
+        }
+        .new_candidate_host()
+    };
+    let local_candidate = tcp_host("192.168.0.2", 777, TcpType::Passive)?;
+    let local_priority = local_candidate.priority();
+    a.add_local_candidate(local_candidate)?;
+    a.add_remote_candidate(tcp_host("172.17.0.3", 999, TcpType::Active)?)?;
+    a.ufrag_pwd.remote_credentials = Some(Credentials {
+        ufrag: "".to_string(),
+        pwd: "".to_string(),
+    });
+    while a.poll_transmit().is_some() {}
+
+    let local_addr = SocketAddr::from_str("192.168.0.2:777")?;
+    let remote_addr = SocketAddr::from_str("172.17.0.3:999")?;
+    let username = a.ufrag_pwd.local_credentials.ufrag.to_owned() + ":";
+    let local_pwd = a.ufrag_pwd.local_credentials.pwd.clone();
+    let tie_breaker = a.tie_breaker.wrapping_add(1);
+    let framed_binding_request = || -> Result<Vec<u8>> {
+        let mut msg = Message::new();
+        msg.build(&[
+            Box::new(BINDING_REQUEST),
+            Box::new(TransactionId::new()),
+            Box::new(Username::new(ATTR_USERNAME, username.clone())),
+            Box::new(AttrControlling(tie_breaker)),
+            Box::new(PriorityAttr(local_priority)),
+            Box::new(MessageIntegrity::new_short_term_integrity(
+                local_pwd.clone(),
+            )),
+            Box::new(FINGERPRINT),
+        ])?;
+        let mut framed = (msg.raw.len() as u16).to_be_bytes().to_vec();
+        framed.extend_from_slice(&msg.raw);
+        Ok(framed)
+    };
+    let read = |a: &mut Agent, buf: &[u8]| {
+        a.handle_read(Transmit {
+            now: Instant::now(),
+            transport: TransportContext {
+                local_addr,
+                peer_addr: remote_addr,
+                protocol: Protocol::TCP,
+                ecn: None,
+            },
+            message: BytesMut::from(buf),
+        })
+    };
+
+    // a frame split across three reads is handled once, when complete
+    let framed = framed_binding_request()?;
+    read(&mut a, &framed[..1])?;
+    read(&mut a, &framed[1..20])?;
+    assert_eq!(a.candidate_pairs[0].requests_received, 0);
+    read(&mut a, &framed[20..])?;
+    assert_eq!(a.candidate_pairs[0].requests_received, 1);
+    assert!(a.tcp_read_buffers.is_empty());
+
+    // the response is framed too
+    let transmit = a.poll_transmit().expect("binding success should be sent");
+    assert_eq!(transmit.transport.protocol, Protocol::TCP);
+    let len = u16::from_be_bytes([transmit.message[0], transmit.message[1]]) as usize;
+    assert_eq!(len + 2, transmit.message.len());
+    let mut m = Message {
+        raw: transmit.message[2..].to_vec(),
+        ..Message::default()
+    };
+    m.decode()?;
+    assert_eq!(m.typ, BINDING_SUCCESS);
+
+    // two frames and the start of a third in one read
+    let mut buf = framed_binding_request()?;
+    buf.extend(framed_binding_request()?);
+    let third = framed_binding_request()?;
+    buf.extend_from_slice(&third[..10]);
+    read(&mut a, &buf)?;
+    assert_eq!(a.candidate_pairs[0].requests_received, 3);
+    read(&mut a, &third[10..])?;
+    assert_eq!(a.candidate_pairs[0].requests_received, 4);
+
+    a.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_nomination_mode() -> Result<()> {
+    let new_agent = |nomination_mode: NominationMode| -> Result<(Agent, usize, usize)> {
+        let mut a = Agent::new(Arc::new(AgentConfig {
+            is_controlling: true,
+            nomination_mode,
+            nomination_min_wait: Duration::from_secs(10),
+            renomination_window: Duration::from_secs(10),
+            srflx_acceptance_min_wait: Some(Duration::from_secs(0)),
+            ..Default::default()
+        }))?;
+        a.add_local_candidate(new_host_candidate("udp", "192.168.0.1", 5000)?)?;
+        a.add_remote_candidate(new_host_candidate("udp", "192.168.0.2", 5001)?)?;
+        a.add_remote_candidate(
+            CandidateServerReflexiveConfig {
+                base_config: CandidateConfig {
+                    network: "udp".to_owned(),
+                    address: "1.2.3.4".to_owned(),
+                    port: 6001,
+                    component: 1,
+                    ..Default::default()
+                },
+                rel_addr: "192.168.0.3".to_owned(),
+                rel_port: 5001,
+            }
+            .new_candidate_server_reflexive()?,
+        )?;
+        a.ufrag_pwd.remote_credentials = Some(Credentials {
+            ufrag: "remoteufrag".to_owned(),
+            pwd: "remotepwdremotepwdremotepwd".to_owned(),
+        });
+
+        let high = a.find_pair(0, 0).expect("host pair");
+        let low = a.find_pair(0, 1).expect("srflx pair");
+        assert!(a.candidate_pairs[high].priority() > a.candidate_pairs[low].priority());
+        Ok((a, high, low))
+    };
+    let sent_use_candidate = |a: &mut Agent| -> Result<bool> {
+        let mut use_candidate = false;
+        while let Some(transmit) = a.poll_transmit() {
+            let mut m = Message {
+                raw: transmit.message.to_vec(),
+                ..Message::default()
+            };
+            m.decode()?;
+            use_candidate |= m.contains(ATTR_USE_CANDIDATE);
+        }
+        Ok(use_candidate)
+    };
+
+    // Regular: a lower priority pair succeeding first isn't nominated while the higher
+    // priority one may still succeed within nomination_min_wait
+    let (mut a, high, low) = new_agent(NominationMode::Regular)?;
+    a.candidate_pairs[low].state = CandidatePairState::Succeeded;
+    a.candidate_pairs[high].state = CandidatePairState::InProgress;
+    a.contact_candidates(Instant::now());
+    assert_eq!(a.nominated_pair, None);
+    assert!(!sent_use_candidate(&mut a)?);
+
+    a.candidate_pairs[high].state = CandidatePairState::Succeeded;
+    a.contact_candidates(Instant::now());
+    assert_eq!(a.nominated_pair, Some(high));
+    assert!(sent_use_candidate(&mut a)?);
+    a.close()?;
+
+    // once nomination_min_wait elapsed, the best valid pair is nominated
+    let (mut a, high, low) = new_agent(NominationMode::Regular)?;
+    a.candidate_pairs[low].state = CandidatePairState::Succeeded;
+    a.candidate_pairs[high].state = CandidatePairState::InProgress;
+    a.start_time = Instant::now() - Duration::from_secs(11);
+    a.contact_candidates(Instant::now());
+    assert_eq!(a.nominated_pair, Some(low));
+    a.close()?;
+
+    // a better pair succeeding within renomination_window is nominated
+    let (mut a, high, low) = new_agent(NominationMode::Regular)?;
+    a.candidate_pairs[low].state = CandidatePairState::Succeeded;
+    a.set_nominated_pair(low);
+    a.set_selected_pair(low, Instant::now());
+    a.candidate_pairs[high].state = CandidatePairState::Succeeded;
+    while a.poll_transmit().is_some() {}
+    a.contact_candidates(Instant::now());
+    assert_eq!(a.nominated_pair, Some(high));
+    assert_eq!(a.selected_pair, Some(low));
+    assert!(sent_use_candidate(&mut a)?);
+    a.close()?;
+
+    // Aggressive: every check carries USE-CANDIDATE
+    let (mut a, _, _) = new_agent(NominationMode::Aggressive)?;
+    while a.poll_transmit().is_some() {}
+    let now = Instant::now();
+    a.contact_candidates(now);
+    a.contact_candidates(now + a.check_interval);
+    let mut checks = 0;
+    while let Some(transmit) = a.poll_transmit() {
+        let mut m = Message {
+            raw: transmit.message.to_vec(),
+            ..Message::default()
+        };
+        m.decode()?;
+        assert!(m.contains(ATTR_USE_CANDIDATE));
+        checks += 1;
+    }
+    assert_eq!(checks, 2);
+    assert_eq!(a.nominated_pair, None);
+    a.close()?;
+
+    // recomputing the priorities renominates within renomination_window only
+    for (elapsed, renominated) in [
+        (Duration::from_secs(1), true),
+        (Duration::from_secs(11), false),
+    ] {
+        let (mut a, high, low) = new_agent(NominationMode::Regular)?;
+        a.candidate_pairs[low].state = CandidatePairState::Succeeded;
+        a.set_nominated_pair(low);
+        a.set_selected_pair(low, Instant::now());
+        a.candidate_pairs[high].state = CandidatePairState::Succeeded;
+        while a.poll_transmit().is_some() {}
+        a.recompute_priorities(a.start_time + elapsed);
+        assert_eq!(a.nominated_pair == Some(high), renominated);
+        assert_eq!(sent_use_candidate(&mut a)?, renominated);
+        a.close()?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_gather_srflx_candidates() -> Result<()> {
+    let mut a = Agent::new(Arc::new(AgentConfig {
+        urls: vec![
+            Url::parse_url("stun:1.2.3.4:3478")?,
+            Url::parse_url("stun:stun.example.com:3478")?,
+        ],
+        ..Default::default()
+    }))?;
+    a.add_local_candidate(new_host_candidate("udp", "192.168.0.1", 5000)?)?;
+    let local_addr = SocketAddr::from_str("192.168.0.1:5000")?;
+    let server_addr = SocketAddr::from_str("1.2.3.4:3478")?;
+
+    let now = Instant::now();
+    a.gather_srflx_candidates(now)?;
+    assert!(matches!(
+        a.poll_event(),
+        Some(Event::UrlNeedsResolution(url)) if url.host == "stun.example.com"
+    ));
+
+    let transmit = a
+        .poll_transmit()
+        .expect("Binding request to the STUN server");
+    assert_eq!(transmit.transport.local_addr, local_addr);
+    assert_eq!(transmit.transport.peer_addr, server_addr);
+    let mut request = Message {
+        raw: transmit.message.to_vec(),
+        ..Message::default()
+    };
+    request.decode()?;
+    assert_eq!(request.typ, BINDING_REQUEST);
+    assert!(a.poll_transmit().is_none());
+    assert_eq!(a.poll_timeout(), Some(now + STUN_GATHER_RTO));
+
+    let mut response = Message::new();
+    response.build(&[
+        Box::new(BINDING_SUCCESS),
+        Box::new(request.transaction_id),
+        Box::new(XorMappedAddress {
+            ip: IpAddr::from_str("5.6.7.8")?,
+            port: 9000,
+        }),
+        Box::new(FINGERPRINT),
+    ])?;
+    a.handle_read(Transmit {
+        now,
+        transport: TransportContext {
+            local_addr,
+            peer_addr: server_addr,
+            ecn: None,
+            protocol: Protocol::UDP,
+        },
+        message: BytesMut::from(&response.raw[..]),
+    })?;
+
+    let srflx = a
+        .get_local_candidates()
+        .iter()
+        .find(|c| c.candidate_type() == CandidateType::ServerReflexive)
+        .expect("server reflexive candidate");
+    assert_eq!(srflx.addr(), SocketAddr::from_str("5.6.7.8:9000")?);
+    let related_address = srflx.related_address().expect("related address");
+    assert_eq!(related_address.address, "192.168.0.1");
+    assert_eq!(related_address.port, 5000);
+    assert!(matches!(
+        a.poll_event(),
+        Some(Event::CandidateGathered(c)) if c.addr() == SocketAddr::from_str("5.6.7.8:9000").unwrap()
+    ));
+    assert!(a.gather_requests.is_empty());
+
+    // an unanswered STUN server is retransmitted to with a doubling timeout, then fails
+    a.resolve_url("stun.example.com", IpAddr::from_str("4.3.2.1")?, now)?;
+    let mut requests = 0;
+    let mut now = now;
+    let mut rto = STUN_GATHER_RTO;
+    while let Some(transmit) = a.poll_transmit() {
+        assert_eq!(
+            transmit.transport.peer_addr,
+            SocketAddr::from_str("4.3.2.1:3478")?
+        );
+        requests += 1;
+        assert_eq!(a.poll_timeout(), Some(now + rto));
+        now += rto;
+        rto *= 2;
+        a.handle_timeout(now);
+    }
+    assert_eq!(requests, MAX_STUN_GATHER_REQUESTS);
+    assert!(matches!(
+        a.poll_event(),
+        Some(Event::GatheringFailed(url)) if url.host == "stun.example.com"
+    ));
+    assert!(a.poll_timeout().is_none());
+
+    a.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_gather_relay_candidates() -> Result<()> {
+    let mut url = Url::parse_url("turn:1.2.3.4:3478?transport=udp")?;
+    url.username = "user".to_owned();
+    url.password = "pass".to_owned();
+    let mut a = Agent::new(Arc::new(AgentConfig {
+        urls: vec![url],
+        candidate_types: vec![CandidateType::Host, CandidateType::Relay],
+        ..Default::default()
+    }))?;
+    a.add_local_candidate(new_host_candidate("udp", "192.168.0.1", 5000)?)?;
+    a.add_remote_candidate(new_host_candidate("udp", "10.0.0.2", 6000)?)?;
+    let local_addr = SocketAddr::from_str("192.168.0.1:5000")?;
+    let server_addr = SocketAddr::from_str("1.2.3.4:3478")?;
+    let relayed_addr = SocketAddr::from_str("5.6.7.8:9000")?;
+    let peer_addr = SocketAddr::from_str("10.0.0.2:6000")?;
+
+    let next_request = |a: &mut Agent| -> Result<Message> {
+        let transmit = a.poll_transmit().expect("request to the TURN server");
+        assert_eq!(transmit.transport.local_addr, local_addr);
+        assert_eq!(transmit.transport.peer_addr, server_addr);
+        let mut m = Message {
+            raw: transmit.message.to_vec(),
+            ..Message::default()
+        };
+        m.decode()?;
+        Ok(m)
+    };
+    let respond = |a: &mut Agent, setters: &[Box<dyn Setter>]| -> Result<()> {
+        let mut res = Message::new();
+        res.build(setters)?;
+        a.handle_read(Transmit {
+            now: Instant::now(),
+            transport: TransportContext {
+                local_addr,
+                peer_addr: server_addr,
+                ecn: None,
+                protocol: Protocol::UDP,
+            },
+            message: BytesMut::from(&res.raw[..]),
+        })
+    };
+
+    // the first Allocate learns NONCE and REALM from a 401 response
+    a.gather_relay_candidates()?;
+    let req = next_request(&mut a)?;
+    assert_eq!(req.typ, MessageType::new(METHOD_ALLOCATE, CLASS_REQUEST));
+    respond(
+        &mut a,
+        &[
+            Box::new(req.transaction_id),
+            Box::new(MessageType::new(METHOD_ALLOCATE, CLASS_ERROR_RESPONSE)),
+            Box::new(CODE_UNAUTHORIZED),
+            Box::new(Nonce::new(ATTR_NONCE, "nonce".to_owned())),
+            Box::new(Realm::new(ATTR_REALM, "example.org".to_owned())),
+        ],
+    )?;
+    let req = next_request(&mut a)?;
+    assert!(req.contains(ATTR_MESSAGE_INTEGRITY));
+    respond(
+        &mut a,
+        &[
+            Box::new(req.transaction_id),
+            Box::new(MessageType::new(METHOD_ALLOCATE, CLASS_SUCCESS_RESPONSE)),
+            Box::new(RelayedAddress {
+                ip: relayed_addr.ip(),
+                port: relayed_addr.port(),
+            }),
+            Box::new(Lifetime(Duration::from_secs(600))),
+        ],
+    )?;
+
+    let relay = a
+        .get_local_candidates()
+        .iter()
+        .find(|c| c.candidate_type() == CandidateType::Relay)
+        .expect("relay candidate");
+    assert_eq!(relay.addr(), relayed_addr);
+    let related_address = relay.related_address().expect("related address");
+    assert_eq!(related_address.address, "192.168.0.1");
+    assert_eq!(related_address.port, 5000);
+    let mut gathered = false;
+    while let Some(event) = a.poll_event() {
+        if let Event::CandidateGathered(c) = event {
+            assert_eq!(c.addr(), relayed_addr);
+            gathered = true;
+        }
+    }
+    assert!(gathered, "expected CandidateGathered");
+    assert!(a.poll_timeout().is_some());
+
+    // pairing the relay candidate with the remote candidate asks for a permission
+    let req = next_request(&mut a)?;
+    assert_eq!(
+        req.typ,
+        MessageType::new(METHOD_CREATE_PERMISSION, CLASS_REQUEST)
+    );
+    let mut addr = PeerAddress::default();
+    addr.get_from(&req)?;
+    assert_eq!(SocketAddr::new(addr.ip, addr.port), peer_addr);
+    assert!(a.poll_transmit().is_none());
+    respond(
+        &mut a,
+        &[
+            Box::new(req.transaction_id),
+            Box::new(MessageType::new(
+                METHOD_CREATE_PERMISSION,
+                CLASS_SUCCESS_RESPONSE,
+            )),
+        ],
+    )?;
+
+    // the check from the relay candidate, started a Ta after the one of the host candidate,
+    // goes to the TURN server in a Send indication
+    a.start_connectivity_checks(true, "ufrag".to_owned(), "pwd".to_owned())?;
+    a.handle_timeout(Instant::now() + a.check_interval);
+    let mut check = None;
+    while let Some(transmit) = a.poll_transmit() {
+        if transmit.transport.peer_addr != server_addr {
+            assert_eq!(transmit.transport.peer_addr, peer_addr);
+            continue;
+        }
+        let mut m = Message {
+            raw: transmit.message.to_vec(),
+            ..Message::default()
+        };
+        m.decode()?;
+        if m.typ != MessageType::new(METHOD_SEND, CLASS_INDICATION) {
+            continue;
+        }
+        let mut addr = PeerAddress::default();
+        addr.get_from(&m)?;
+        assert_eq!(SocketAddr::new(addr.ip, addr.port), peer_addr);
+        let mut data = Data::default();
+        data.get_from(&m)?;
+        let mut request = Message {
+            raw: data.0,
+            ..Message::default()
+        };
+        request.decode()?;
+        assert_eq!(request.typ, BINDING_REQUEST);
+        check = Some(request);
+    }
+    let check = check.expect("connectivity check through the TURN server");
+
+    // the response of the peer is relayed back in a Data indication
+    let mut response = Message::new();
+    response.build(&[
+        Box::new(BINDING_SUCCESS),
+        Box::new(check.transaction_id),
+        Box::new(XorMappedAddress {
+            ip: relayed_addr.ip(),
+            port: relayed_addr.port(),
+        }),
+        Box::new(MessageIntegrity::new_short_term_integrity("pwd".to_owned())),
+        Box::new(FINGERPRINT),
+    ])?;
+    respond(
+        &mut a,
+        &[
+            Box::new(TransactionId::new()),
+            Box::new(MessageType::new(METHOD_DATA, CLASS_INDICATION)),
+            Box::new(Data(response.raw)),
+            Box::new(PeerAddress {
+                ip: peer_addr.ip(),
+                port: peer_addr.port(),
+            }),
+        ],
+    )?;
+    let relay_index = a
+        .find_local_candidate(relayed_addr, Protocol::UDP)
+        .expect("relay candidate");
+    let pair_index = a.find_pair(relay_index, 0).expect("relay pair");
+    assert_eq!(
+        a.candidate_pairs[pair_index].state,
+        CandidatePairState::Succeeded
+    );
+
+    // application data from the relay candidate is relayed too
+    assert!(a.is_relayed_addr(relayed_addr));
+    assert!(!a.is_relayed_addr(local_addr));
+    while a.poll_transmit().is_some() {}
+    let payload = [0x17, 0xfe, 0xfd, 0x00, 0x01];
+    a.send_relayed(relayed_addr, &payload, peer_addr)?;
+    let mut relayed = false;
+    while let Some(transmit) = a.poll_transmit() {
+        assert_eq!(transmit.transport.peer_addr, server_addr);
+        let mut m = Message {
+            raw: transmit.message.to_vec(),
+            ..Message::default()
+        };
+        m.decode()?;
+        if m.typ == MessageType::new(METHOD_SEND, CLASS_INDICATION) {
+            let mut data = Data::default();
+            data.get_from(&m)?;
+            assert_eq!(data.0, payload);
+            relayed = true;
+        }
+    }
+    assert!(
+        relayed,
+        "expected a Send indication with the application data"
+    );
+
+    // and application data of the peer is read from the relayed address
+    respond(
+        &mut a,
+        &[
+            Box::new(TransactionId::new()),
+            Box::new(MessageType::new(METHOD_DATA, CLASS_INDICATION)),
+            Box::new(Data(payload.to_vec())),
+            Box::new(PeerAddress {
+                ip: peer_addr.ip(),
+                port: peer_addr.port(),
+            }),
+        ],
+    )?;
+    let read = a.poll_read().expect("relayed application data");
+    assert_eq!(read.transport.local_addr, relayed_addr);
+    assert_eq!(read.transport.peer_addr, peer_addr);
+    assert_eq!(&read.message[..], &payload);
+    assert!(a.poll_read().is_none());
+
+    a.close()?;
+    assert!(a.relay_clients.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_role_conflict() -> Result<()> {
+    // forwards the transmits of from to to, and returns the number of 487 responses
+    let forward_counting_role_conflicts = |from: &mut Agent, to: &mut Agent| -> Result<usize> {
+        let mut role_conflicts = 0;
+        while let Some(transmit) = from.poll_transmit() {
+            let mut m = Message {
+                raw: transmit.message.to_vec(),
+                ..Message::default()
+            };
+            m.decode()?;
+            if m.typ == BINDING_ERROR {
+                let mut error_code = ErrorCodeAttribute::default();
+                error_code.get_from(&m)?;
+                assert!(error_code.code == CODE_ROLE_CONFLICT);
+                assert!(m.contains(ATTR_MESSAGE_INTEGRITY));
+                role_conflicts += 1;
+            }
+            let _ = to.handle_read(Transmit {
+                now: Instant::now(),
+                transport: TransportContext {
+                    local_addr: transmit.transport.peer_addr,
+                    peer_addr: transmit.transport.local_addr,
+                    ..transmit.transport
+                },
+                message: transmit.message,
+            });
+        }
+        Ok(role_conflicts)
+    };
+
+    for is_controlling in [true, false] {
+        let (mut a, mut b) = pipe(None, None)?;
+        a.add_local_candidate(new_host_candidate("udp", "192.168.0.1", 5000)?)?;
+        b.add_remote_candidate(new_host_candidate("udp", "192.168.0.1", 5000)?)?;
+        b.add_local_candidate(new_host_candidate("udp", "192.168.0.2", 5001)?)?;
+        a.add_remote_candidate(new_host_candidate("udp", "192.168.0.2", 5001)?)?;
+
+        // both agents start with the same role, and b has the larger tie-breaker so it ends
+        // up controlling
+        a.tie_breaker = 1;
+        b.tie_breaker = 2;
+        let a_credentials = a.get_local_credentials().clone();
+        let b_credentials = b.get_local_credentials().clone();
+        a.start_connectivity_checks(is_controlling, b_credentials.ufrag, b_credentials.pwd)?;
+        b.start_connectivity_checks(is_controlling, a_credentials.ufrag, a_credentials.pwd)?;
+
+        let mut role_conflicts = forward_counting_role_conflicts(&mut a, &mut b)?;
+        role_conflicts += forward_counting_role_conflicts(&mut b, &mut a)?;
+        role_conflicts += forward_counting_role_conflicts(&mut a, &mut b)?;
+        assert_eq!(
+            role_conflicts, 1,
+            "the agent keeping its role answers the first check with 487"
+        );
+        assert!(!a.is_controlling);
+        assert!(b.is_controlling);
+        assert!(a.candidate_pairs.iter().all(|p| !p.ice_role_controlling));
+        assert!(b.candidate_pairs.iter().all(|p| p.ice_role_controlling));
+
+        for _ in 0..10 {
+            if a.is_connected() && b.is_connected() {
+                break;
+            }
+            forward_counting_role_conflicts(&mut a, &mut b)?;
+            forward_counting_role_conflicts(&mut b, &mut a)?;
+            a.handle_timeout(Instant::now());
+            b.handle_timeout(Instant::now());
+        }
+        assert!(a.is_connected());
+        assert!(b.is_connected());
+        assert!(!a.is_controlling);
+        assert!(b.is_controlling);
+
+        a.close()?;
+        b.close()?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_candidate_byte_counters() -> Result<()> {
+    let mut a = Agent::new(Arc::new(AgentConfig::default()))?;
+
+    a.add_local_candidate(new_host_candidate("udp", "192.168.1.1", 19216)?)?;
+    a.add_remote_candidate(new_host_candidate("udp", "192.168.1.2", 19217)?)?;
+    let transport = TransportContext {
+        local_addr: SocketAddr::from_str("192.168.1.1:19216")?,
+        peer_addr: SocketAddr::from_str("192.168.1.2:19217")?,
+        ecn: None,
+        protocol: Protocol::UDP,
+    };
+    let start = Instant::now();
+
+    // outbound STUN
+    let mut indication = Message::new();
+    indication.build(&[
+        Box::new(MessageType::new(METHOD_BINDING, CLASS_INDICATION)),
+        Box::new(TransactionId::new()),
+    ])?;
+    a.send_stun(&indication, 0, 0, Instant::now());
+    a.send_stun(&indication, 0, 0, Instant::now());
+
+    // inbound STUN and non-STUN, counted even though they are rejected
+    let mut request = Message::new();
+    request.build(&[Box::new(BINDING_REQUEST), Box::new(TransactionId::new())])?;
+    assert!(a
+        .handle_read(Transmit {
+            now: Instant::now(),
+            transport,
+            message: BytesMut::from(&request.raw[..]),
+        })
+        .is_err());
+    let data = BytesMut::from(&[0x80u8; 100][..]);
+    assert!(a
+        .handle_read(Transmit {
+            now: Instant::now(),
+            transport,
+            message: data.clone(),
+        })
+        .is_err());
+
+    // outbound application data
+    a.record_data_packet(&transport, true, 50, Instant::now());
+
+    let stats = a.get_candidates_stats();
+    assert_eq!(stats.len(), 2);
+    for stat in &stats {
+        assert_eq!(stat.packets_sent, 3);
+        assert_eq!(stat.bytes_sent, 2 * indication.raw.len() as u64 + 50);
+        assert_eq!(stat.packets_received, 2);
+        assert_eq!(
+            stat.bytes_received,
+            request.raw.len() as u64 + data.len() as u64
+        );
+        assert!(stat.last_packet_sent_timestamp >= start);
+        assert!(stat.last_packet_received_timestamp >= start);
+    }
+    assert_eq!(stats[0].id, a.local_candidates[0].id());
+    assert_eq!(stats[1].id, a.remote_candidates[0].id());
+
+    a.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_check_retransmission() -> Result<()> {
+    let initial_rto = Duration::from_millis(100);
+    let max_retransmissions = 3;
+    let mut a = Agent::new(Arc::new(AgentConfig {
+        initial_rto: Some(initial_rto),
+        max_retransmissions: Some(max_retransmissions),
+        ..Default::default()
+    }))?;
+
+    a.add_local_candidate(new_host_candidate("udp", "192.168.0.1", 5000)?)?;
+    a.add_remote_candidate(new_host_candidate("udp", "192.168.0.2", 5001)?)?;
+    a.start_connectivity_checks(true, "remoteufrag".to_owned(), "remotepwd".to_owned())?;
+
+    // the first check is sent right away
+    assert_eq!(a.candidate_pairs[0].state, CandidatePairState::InProgress);
+    let first_check = a.candidate_pairs[0]
+        .next_retransmit
+        .expect("check in flight")
+        - initial_rto;
+    let mut sent = vec![];
+    while a.poll_transmit().is_some() {
+        sent.push(first_check);
+    }
+
+    // the checks nobody answers are retransmitted on their own timer, which poll_timeout
+    // reflects, until the pair fails
+    for _ in 0..100 {
+        if a.candidate_pairs[0].state == CandidatePairState::Failed {
+            break;
+        }
+        let now = a.poll_timeout().expect("checks are scheduled");
+        a.handle_timeout(now);
+        while a.poll_transmit().is_some() {
+            sent.push(now);
+        }
+    }
+
+    assert_eq!(a.candidate_pairs[0].state, CandidatePairState::Failed);
+    assert_eq!(sent.len(), 1 + max_retransmissions as usize);
+    let mut rto = initial_rto;
+    for checks in sent.windows(2) {
+        assert_eq!(checks[1] - checks[0], rto);
+        rto *= 2;
+    }
+    assert!(a.next_retransmit().is_none());
+
+    a.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_candidate_priority_fn() -> Result<()> {
+    let mut a = Agent::new(Arc::new(AgentConfig::default()))?;
+    for (address, interface_index) in [("192.168.0.1", 0), ("10.8.0.1", 1)] {
+        a.add_local_candidate(
+            CandidateHostConfig {
+                base_config: CandidateConfig {
+                    network: "udp".to_owned(),
+                    address: address.to_owned(),
+                    port: 5000,
+                    component: 1,
+                    interface_index: Some(interface_index),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+            .new_candidate_host()?,
+        )?;
+    }
+    a.add_remote_candidate(new_host_candidate("udp", "192.168.0.2", 5001)?)?;
+    let checklist = |a: &Agent| -> Vec<String> {
+        a.candidate_pairs
+            .iter()
+            .map(|p| a.local_candidates[p.local_index].address().to_owned())
+            .collect()
+    };
+
+    // the interface with the lower index is preferred by default
+    assert_eq!(checklist(&a), vec!["192.168.0.1", "10.8.0.1"]);
+    let best = a.get_best_available_candidate_pair().expect("best pair");
+    assert_eq!(a.candidate_pairs[best].local_index, 0);
+
+    // a policy preferring the VPN interface swaps the checklist order
+    let vpn_priority = 0x7EFF_0000;
+    a.set_candidate_priority_fn(Some(Arc::new(move |c: &Candidate| {
+        if c.address().starts_with("10.8.") {
+            vpn_priority
+        } else {
+            c.priority()
+        }
+    })));
+    assert_eq!(checklist(&a), vec!["192.168.0.1", "10.8.0.1"]);
+    a.recompute_priorities(Instant::now());
+    assert_eq!(a.local_candidates[1].priority(), vpn_priority);
+    assert_eq!(checklist(&a), vec!["10.8.0.1", "192.168.0.1"]);
+    let best = a.get_best_available_candidate_pair().expect("best pair");
+    assert_eq!(a.candidate_pairs[best].local_index, 1);
+    assert_eq!(a.candidate_pairs[best].local_priority, vpn_priority);
+
+    // removing the policy restores the computed priorities
+    a.set_candidate_priority_fn(None);
+    a.recompute_priorities(Instant::now());
+    assert_eq!(checklist(&a), vec!["192.168.0.1", "10.8.0.1"]);
+
+    a.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_frozen_pairs_of_a_foundation() -> Result<()> {
+    let mut a = Agent::new(Arc::new(AgentConfig::default()))?;
+    a.add_local_candidate(new_host_candidate("udp", "192.168.0.1", 5000)?)?;
+    // two remote candidates on the same IP address share a foundation
+    for port in [5001, 5002] {
+        a.add_remote_candidate(new_host_candidate("udp", "192.168.0.2", port)?)?;
+    }
+    let (first, second) = (
+        a.find_pair(0, 0).expect("first pair"),
+        a.find_pair(0, 1).expect("second pair"),
+    );
+    assert_eq!(
+        a.candidate_pairs[first].foundation(&a.local_candidates, &a.remote_candidates),
+        a.candidate_pairs[second].foundation(&a.local_candidates, &a.remote_candidates)
+    );
+    assert_eq!(a.candidate_pairs[first].state, CandidatePairState::Waiting);
+    assert_eq!(a.candidate_pairs[second].state, CandidatePairState::Frozen);
+
+    let local_addr = SocketAddr::from_str("192.168.0.1:5000")?;
+    let checks_sent = |a: &mut Agent| -> Result<Vec<(SocketAddr, Message)>> {
+        let mut checks = vec![];
+        while let Some(transmit) = a.poll_transmit() {
+            let mut m = Message {
+                raw: transmit.message.to_vec(),
+                ..Message::default()
+            };
+            m.decode()?;
+            assert_eq!(m.typ, BINDING_REQUEST);
+            checks.push((transmit.transport.peer_addr, m));
+        }
+        Ok(checks)
+    };
+
+    let mut now = Instant::now();
+    a.start_connectivity_checks(false, "ufrag".to_owned(), "pwd".to_owned())?;
+    let checks = checks_sent(&mut a)?;
+    assert_eq!(checks.len(), 1);
+    let (peer_addr, check) = &checks[0];
+    assert_eq!(*peer_addr, SocketAddr::from_str("192.168.0.2:5001")?);
+
+    // the second pair stays Frozen while the check of the first one is in progress
+    for _ in 0..2 {
+        now += a.check_interval;
+        a.handle_timeout(now);
+        assert!(checks_sent(&mut a)?.is_empty());
+        assert_eq!(a.candidate_pairs[second].state, CandidatePairState::Frozen);
+    }
+
+    let mut response = Message::new();
+    response.build(&[
+        Box::new(BINDING_SUCCESS),
+        Box::new(check.transaction_id),
+        Box::new(XorMappedAddress {
+            ip: local_addr.ip(),
+            port: local_addr.port(),
+        }),
+        Box::new(MessageIntegrity::new_short_term_integrity("pwd".to_owned())),
+        Box::new(FINGERPRINT),
+    ])?;
+    a.handle_read(Transmit {
+        now,
+        transport: TransportContext {
+            local_addr,
+            peer_addr: *peer_addr,
+            ecn: None,
+            protocol: Protocol::UDP,
+        },
+        message: BytesMut::from(&response.raw[..]),
+    })?;
+    assert_eq!(
+        a.candidate_pairs[first].state,
+        CandidatePairState::Succeeded
+    );
+    assert_eq!(a.candidate_pairs[second].state, CandidatePairState::Waiting);
+
+    // the second pair is checked on the next Ta tick
+    now += a.check_interval;
+    a.handle_timeout(now);
+    let checks = checks_sent(&mut a)?;
+    assert_eq!(checks.len(), 1);
+    assert_eq!(checks[0].0, SocketAddr::from_str("192.168.0.2:5002")?);
+    assert_eq!(
+        a.candidate_pairs[second].state,
+        CandidatePairState::InProgress
+    );
+
+    a.close()?;
+    Ok(())
+}
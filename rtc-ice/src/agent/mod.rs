@@ -2,26 +2,34 @@
 mod agent_test;
 
 pub mod agent_config;
+pub mod agent_gather;
+pub mod agent_relay;
 pub mod agent_selector;
 pub mod agent_stats;
 
 use agent_config::*;
-use bytes::BytesMut;
+use agent_gather::*;
+use agent_relay::*;
+use bytes::{Buf, BufMut, BytesMut};
 use log::{debug, error, info, trace, warn};
-use std::collections::VecDeque;
-use std::net::{Ipv4Addr, SocketAddr};
+use rand::Rng;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use stun::attributes::*;
+use stun::error_code::*;
 use stun::fingerprint::*;
 use stun::integrity::*;
 use stun::message::*;
 use stun::textattrs::*;
 use stun::xoraddr::*;
 
+use crate::attributes::control::TieBreaker;
 use crate::candidate::candidate_peer_reflexive::CandidatePeerReflexiveConfig;
 use crate::candidate::{candidate_pair::*, *};
-use crate::network_type::NetworkType;
+use crate::mdns::MulticastDnsMode;
+use crate::network_type::{determine_network_type, NetworkType};
 use crate::rand::*;
 use crate::state::*;
 use crate::url::*;
@@ -36,6 +44,8 @@ pub(crate) struct BindingRequest {
     pub(crate) transaction_id: TransactionId,
     pub(crate) destination: SocketAddr,
     pub(crate) is_use_candidate: bool,
+    // the role the request was sent with, to resolve a role conflict answered to it
+    pub(crate) is_controlling: bool,
 }
 
 impl Default for BindingRequest {
@@ -45,10 +55,117 @@ impl Default for BindingRequest {
             transaction_id: TransactionId::default(),
             destination: SocketAddr::new(Ipv4Addr::new(0, 0, 0, 0).into(), 0),
             is_use_candidate: false,
+            is_controlling: false,
         }
     }
 }
 
+/// Outbound Binding Requests awaiting a response, looked up by transaction ID. Holds at most
+/// `max` requests, evicting the oldest one when full.
+pub(crate) struct PendingBindingRequests {
+    // requests with the sequence number of their entry in order
+    requests: HashMap<TransactionId, (u64, BindingRequest)>,
+    // sequence numbers and transaction IDs in the order the requests were sent, oldest first.
+    // Entries of removed or replaced requests are skipped when they reach the front.
+    order: VecDeque<(u64, TransactionId)>,
+    next_seq: u64,
+    max: usize,
+}
+
+impl PendingBindingRequests {
+    pub(crate) fn new(max: usize) -> Self {
+        Self {
+            requests: HashMap::new(),
+            order: VecDeque::new(),
+            next_seq: 0,
+            max,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.requests.clear();
+        self.order.clear();
+    }
+
+    /// Adds a request, returning the oldest request if it had to be evicted to make room.
+    pub(crate) fn push(&mut self, binding_request: BindingRequest) -> Option<BindingRequest> {
+        // a reused transaction ID replaces the request, whose entry in order becomes stale
+        let replaced = self.requests.contains_key(&binding_request.transaction_id);
+        let evicted = if !replaced && self.requests.len() >= self.max {
+            self.pop_oldest()
+        } else {
+            None
+        };
+
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.order.push_back((seq, binding_request.transaction_id));
+        self.requests
+            .insert(binding_request.transaction_id, (seq, binding_request));
+        self.skip_stale();
+
+        evicted
+    }
+
+    /// Removes and returns the request with the given transaction ID.
+    pub(crate) fn remove(&mut self, id: &TransactionId) -> Option<BindingRequest> {
+        let (_, binding_request) = self.requests.remove(id)?;
+        self.skip_stale();
+        Some(binding_request)
+    }
+
+    /// Returns the oldest request, if any.
+    pub(crate) fn oldest(&self) -> Option<&BindingRequest> {
+        self.order
+            .front()
+            .and_then(|(_, id)| self.requests.get(id))
+            .map(|(_, binding_request)| binding_request)
+    }
+
+    fn pop_oldest(&mut self) -> Option<BindingRequest> {
+        let (_, id) = self.order.pop_front()?;
+        let (_, binding_request) = self.requests.remove(&id)?;
+        self.skip_stale();
+        Some(binding_request)
+    }
+
+    fn is_stale(&self, (seq, id): &(u64, TransactionId)) -> bool {
+        self.requests.get(id).is_none_or(|(s, _)| s != seq)
+    }
+
+    // drops the stale entries at the front of order, so that it starts with the oldest
+    // request, and compacts order once most of its entries are stale
+    fn skip_stale(&mut self) {
+        while self.order.front().is_some_and(|entry| self.is_stale(entry)) {
+            self.order.pop_front();
+        }
+        if self.order.len() > 2 * self.requests.len() + 16 {
+            let requests = &self.requests;
+            self.order
+                .retain(|(seq, id)| requests.get(id).is_some_and(|(s, _)| s == seq));
+        }
+    }
+
+    /// Removes the requests sent `timeout` or longer before `filter_time`, returning how many
+    /// were removed.
+    pub(crate) fn remove_expired(&mut self, filter_time: Instant, timeout: Duration) -> usize {
+        let mut removed = 0;
+        while self.oldest().is_some_and(|binding_request| {
+            filter_time
+                .checked_duration_since(binding_request.timestamp)
+                .is_some_and(|duration| duration >= timeout)
+        }) {
+            self.pop_oldest();
+            removed += 1;
+        }
+        removed
+    }
+}
+
 #[derive(Default, Clone)]
 pub struct Credentials {
     pub ufrag: String,
@@ -59,6 +176,9 @@ pub struct Credentials {
 pub(crate) struct UfragPwd {
     pub(crate) local_credentials: Credentials,
     pub(crate) remote_credentials: Option<Credentials>,
+    // HMAC contexts of the local and remote passwords for checking MESSAGE-INTEGRITY
+    pub(crate) local_integrity_cache: MessageIntegrityCache,
+    pub(crate) remote_integrity_cache: MessageIntegrityCache,
 }
 
 fn assert_inbound_username(m: &Message, expected_username: &str) -> Result<()> {
@@ -77,21 +197,48 @@ fn assert_inbound_username(m: &Message, expected_username: &str) -> Result<()> {
     Ok(())
 }
 
-fn assert_inbound_message_integrity(m: &mut Message, key: &[u8]) -> Result<()> {
-    let message_integrity_attr = MessageIntegrity(key.to_vec());
-    message_integrity_attr.check(m)
+fn assert_inbound_message_integrity(
+    m: &mut Message,
+    key: &[u8],
+    cache: &mut MessageIntegrityCache,
+) -> Result<()> {
+    MessageIntegrity::check_cached(m, key, cache)
 }
 
 pub enum Event {
     ConnectionStateChange(ConnectionState),
     SelectedCandidatePairChange(Box<Candidate>, Box<Candidate>),
+    /// A remote host candidate with the given `.local` name is waiting for
+    /// `Agent::resolve_remote_candidate()`.
+    RemoteCandidateNeedsResolution(String, Box<Candidate>),
+    /// The state of the candidate pair of the given (local, remote) candidates changed.
+    CandidatePairStateChange(Box<Candidate>, Box<Candidate>, CandidatePairState),
+    /// The controlling agent nominated the candidate pair of the given (local, remote)
+    /// candidates, and sends USE-CANDIDATE on it.
+    NominationChange(Box<Candidate>, Box<Candidate>),
+    /// A pair was selected after a graceful restart, and the pair selected before the restart
+    /// was retired.
+    IceRestartCompleted,
+    /// A server reflexive candidate was learned from a STUN server and added as a local
+    /// candidate.
+    CandidateGathered(Box<Candidate>),
+    /// Gathering from the STUN server of the given URL failed, as it didn't answer the
+    /// Binding requests or answered with an error.
+    GatheringFailed(Url),
+    /// The STUN server of the given URL is named by a host name and is waiting for
+    /// `Agent::resolve_url()`.
+    UrlNeedsResolution(Url),
 }
 
 /// Represents the ICE agent.
 pub struct Agent {
     pub(crate) tie_breaker: u64,
+    pub(crate) preserve_tie_breaker_on_restart: bool,
     pub(crate) is_controlling: bool,
     pub(crate) lite: bool,
+    pub(crate) nomination_mode: NominationMode,
+    pub(crate) nomination_min_wait: Duration,
+    pub(crate) renomination_window: Duration,
 
     pub(crate) start_time: Instant,
 
@@ -102,17 +249,31 @@ pub struct Agent {
     pub(crate) ufrag_pwd: UfragPwd,
 
     pub(crate) local_candidates: Vec<Candidate>,
+    // index of local_candidates by (address, protocol) for inbound traffic lookups
+    pub(crate) local_candidate_index: HashMap<(SocketAddr, Protocol), usize>,
     pub(crate) remote_candidates: Vec<Candidate>,
     pub(crate) candidate_pairs: Vec<CandidatePair>,
+    // index of candidate_pairs by (local address, remote address, protocol) for data
+    // packet accounting
+    pub(crate) candidate_pair_index: HashMap<(SocketAddr, SocketAddr, Protocol), usize>,
     pub(crate) nominated_pair: Option<usize>,
     pub(crate) selected_pair: Option<usize>,
+    // (local, remote) candidates selected before a graceful restart, still used for traffic
+    // until a new pair is selected
+    pub(crate) old_selected_pair: Option<(Candidate, Candidate)>,
 
-    // LRU of outbound Binding request Transaction IDs
-    pub(crate) pending_binding_requests: Vec<BindingRequest>,
+    // outbound Binding requests by Transaction ID, oldest evicted first
+    pub(crate) pending_binding_requests: PendingBindingRequests,
+    // outbound Binding requests to STUN servers by Transaction ID
+    pub(crate) gather_requests: HashMap<TransactionId, GatherRequest>,
+    // TURN clients allocating relay candidates
+    pub(crate) relay_clients: Vec<RelayClient>,
 
     // the following variables won't be changed after init_with_defaults()
     pub(crate) insecure_skip_verify: bool,
-    pub(crate) max_binding_requests: u16,
+    pub(crate) initial_rto: Duration,
+    pub(crate) max_retransmissions: u16,
+    pub(crate) max_candidate_pairs: usize,
     pub(crate) host_acceptance_min_wait: Duration,
     pub(crate) srflx_acceptance_min_wait: Duration,
     pub(crate) prflx_acceptance_min_wait: Duration,
@@ -126,14 +287,44 @@ pub struct Agent {
     // How often should we send keepalive packets?
     // 0 means never
     pub(crate) keepalive_interval: Duration,
+    // How often should we send consent freshness checks?
+    // 0 means never
+    pub(crate) consent_check_interval: Duration,
+    pub(crate) next_consent_check: Option<Instant>,
+    pub(crate) use_binding_indication_for_keepalive: bool,
     // How often should we run our internal taskLoop to check for state changes when connecting
     pub(crate) check_interval: Duration,
+    // when the last connectivity check of a Waiting pair was started, one is started every
+    // check_interval (Ta)
+    pub(crate) last_check_started: Option<Instant>,
     pub(crate) checking_duration: Instant,
     pub(crate) last_checking_time: Instant,
 
     pub(crate) candidate_types: Vec<CandidateType>,
+    pub(crate) candidate_priority_fn: Option<CandidatePriorityFn>,
     pub(crate) urls: Vec<Url>,
 
+    pub(crate) enable_mdns_obfuscation: bool,
+    // mDNS names announced instead of the IP address of local host candidates
+    pub(crate) mdns_host_names: HashMap<IpAddr, String>,
+    pub(crate) multicast_dns_mode: MulticastDnsMode,
+    pub(crate) multicast_dns_host_name: String,
+
+    // SOFTWARE attribute added to outbound Binding Requests
+    pub(crate) software: Option<Software>,
+
+    pub(crate) stun_replay_protection: bool,
+    // transaction IDs of recent inbound Binding Requests, oldest first
+    pub(crate) recent_transaction_ids: VecDeque<TransactionId>,
+    // Binding Success sent for each recent inbound Binding Request, with its local and
+    // remote candidate index, to answer retransmissions of the request
+    pub(crate) recent_responses: HashMap<TransactionId, Option<(Message, usize, usize)>>,
+
+    // partial RFC 4571 frames received on ICE-TCP connections, by (local, peer) address
+    pub(crate) tcp_read_buffers: HashMap<(SocketAddr, SocketAddr), BytesMut>,
+
+    // application data received through the TURN server of a local relay candidate
+    pub(crate) relayed_reads: VecDeque<Transmit<BytesMut>>,
     pub(crate) transmits: VecDeque<Transmit<BytesMut>>,
     pub(crate) events: VecDeque<Event>,
 }
@@ -141,6 +332,17 @@ pub struct Agent {
 impl Agent {
     /// Creates a new Agent.
     pub fn new(config: Arc<AgentConfig>) -> Result<Self> {
+        if config.validate().is_err() {
+            return Err(Error::ErrInvalidUrl);
+        }
+
+        let host_name = &config.multicast_dns_host_name;
+        if !host_name.is_empty()
+            && (!host_name.ends_with(".local") || host_name.split('.').count() != 2)
+        {
+            return Err(Error::ErrInvalidMulticastDnshostName);
+        }
+
         let candidate_types = if config.candidate_types.is_empty() {
             default_candidate_types()
         } else {
@@ -161,14 +363,20 @@ impl Agent {
 
         let mut agent = Self {
             tie_breaker: rand::random::<u64>(),
+            preserve_tie_breaker_on_restart: config.preserve_tie_breaker_on_restart,
             is_controlling: config.is_controlling,
             lite: config.lite,
+            nomination_mode: config.nomination_mode,
+            nomination_min_wait: config.nomination_min_wait,
+            renomination_window: config.renomination_window,
 
             start_time: Instant::now(),
 
             nominated_pair: None,
             selected_pair: None,
+            old_selected_pair: None,
             candidate_pairs: vec![],
+            candidate_pair_index: HashMap::new(),
 
             connection_state: ConnectionState::New,
 
@@ -177,10 +385,22 @@ impl Agent {
             //started_ch_tx: MuteSome(started_ch_tx)),
 
             //won't change after init_with_defaults()
-            max_binding_requests: if let Some(max_binding_requests) = config.max_binding_requests {
-                max_binding_requests
+            initial_rto: if let Some(initial_rto) = config.initial_rto {
+                initial_rto
+            } else {
+                DEFAULT_INITIAL_RTO
+            },
+            max_retransmissions: if let Some(max_retransmissions) =
+                config.max_retransmissions.or(config.max_binding_requests)
+            {
+                max_retransmissions
+            } else {
+                DEFAULT_MAX_RETRANSMISSIONS
+            },
+            max_candidate_pairs: if let Some(max_candidate_pairs) = config.max_candidate_pairs {
+                max_candidate_pairs
             } else {
-                DEFAULT_MAX_BINDING_REQUESTS
+                DEFAULT_MAX_CANDIDATE_PAIRS
             },
             host_acceptance_min_wait: if let Some(host_acceptance_min_wait) =
                 config.host_acceptance_min_wait
@@ -235,12 +455,25 @@ impl Agent {
                 DEFAULT_KEEPALIVE_INTERVAL
             },
 
+            // How often should we send consent freshness checks?
+            // 0 means never
+            consent_check_interval: if let Some(consent_check_interval) =
+                config.consent_check_interval
+            {
+                consent_check_interval
+            } else {
+                DEFAULT_CONSENT_CHECK_INTERVAL
+            },
+            next_consent_check: None,
+            use_binding_indication_for_keepalive: config.use_binding_indication_for_keepalive,
+
             // How often should we run our internal taskLoop to check for state changes when connecting
             check_interval: if config.check_interval == Duration::from_secs(0) {
                 DEFAULT_CHECK_INTERVAL
             } else {
                 config.check_interval
             },
+            last_check_started: None,
             checking_duration: Instant::now(),
             last_checking_time: Instant::now(),
             last_connection_state: ConnectionState::Unspecified,
@@ -248,21 +481,53 @@ impl Agent {
             ufrag_pwd: UfragPwd::default(),
 
             local_candidates: vec![],
+            local_candidate_index: HashMap::new(),
             remote_candidates: vec![],
 
-            // LRU of outbound Binding request Transaction IDs
-            pending_binding_requests: vec![],
+            // outbound Binding requests by Transaction ID, oldest evicted first
+            pending_binding_requests: PendingBindingRequests::new(
+                if let Some(max_pending_binding_requests) = config.max_pending_binding_requests {
+                    max_pending_binding_requests
+                } else {
+                    DEFAULT_MAX_PENDING_BINDING_REQUESTS
+                },
+            ),
+            gather_requests: HashMap::new(),
+            relay_clients: vec![],
 
             candidate_types,
+            candidate_priority_fn: config.candidate_priority_fn.clone(),
             urls: config.urls.clone(),
 
+            enable_mdns_obfuscation: config.enable_mdns_obfuscation
+                || config.multicast_dns_mode == MulticastDnsMode::QueryAndGather,
+            mdns_host_names: HashMap::new(),
+            multicast_dns_mode: config.multicast_dns_mode,
+            multicast_dns_host_name: config.multicast_dns_host_name.clone(),
+
+            software: config
+                .software
+                .clone()
+                .map(|software| Software::new(ATTR_SOFTWARE, software)),
+
+            stun_replay_protection: config.stun_replay_protection,
+            recent_transaction_ids: VecDeque::new(),
+            recent_responses: HashMap::new(),
+
+            tcp_read_buffers: HashMap::new(),
+
+            relayed_reads: VecDeque::new(),
             transmits: VecDeque::new(),
             events: VecDeque::new(),
         };
 
         // Restart is also used to initialize the agent for the first time
-        if let Err(err) = agent.restart(config.local_ufrag.clone(), config.local_pwd.clone(), false)
-        {
+        if let Err(err) = agent.restart(
+            config.local_ufrag.clone(),
+            config.local_pwd.clone(),
+            false,
+            false,
+        ) {
             let _ = agent.close();
             return Err(err);
         }
@@ -271,46 +536,152 @@ impl Agent {
     }
 
     /// Adds a new local candidate.
-    pub fn add_local_candidate(&mut self, c: Candidate) -> Result<()> {
+    pub fn add_local_candidate(&mut self, mut c: Candidate) -> Result<()> {
+        if self.enable_mdns_obfuscation
+            && c.candidate_type() == CandidateType::Host
+            && !c.address().ends_with(".local")
+        {
+            c.address = self
+                .mdns_host_names
+                .entry(c.addr().ip())
+                .or_insert_with(|| {
+                    if self.multicast_dns_host_name.is_empty() {
+                        generate_mdns_host_name()
+                    } else {
+                        self.multicast_dns_host_name.clone()
+                    }
+                })
+                .clone();
+        }
+
+        self.apply_priority_policy(&mut c);
+
         for cand in &self.local_candidates {
             if cand.equal(&c) {
                 return Ok(());
             }
         }
 
+        // keep the first candidate bound to an address, as a linear scan would find it
+        self.local_candidate_index
+            .entry((c.addr(), c.network_type().to_protocol()))
+            .or_insert(self.local_candidates.len());
         self.local_candidates.push(c);
 
         for remote_index in 0..self.remote_candidates.len() {
             self.add_pair(self.local_candidates.len() - 1, remote_index);
         }
+        self.prune_pairs();
 
         self.request_connectivity_check();
 
         Ok(())
     }
 
-    /// Adds a new remote candidate.
-    pub fn add_remote_candidate(&mut self, c: Candidate) -> Result<()> {
-        // If we have a mDNS Candidate lets fully resolve it before adding it locally
-        if c.candidate_type() == CandidateType::Host && c.address().ends_with(".local") {
-            warn!(
-                "remote mDNS candidate added, but mDNS is disabled: ({})",
-                c.address()
-            );
-            return Err(Error::ErrMulticastDnsNotSupported);
+    /// Replaces the address of the remote candidate at index, e.g. once its mDNS name has
+    /// been resolved. The pairs of an unresolved mDNS candidate are created, while existing
+    /// pairs keep their state and only have their priority updated.
+    pub fn set_remote_candidate_address(
+        &mut self,
+        index: usize,
+        resolved_addr: SocketAddr,
+    ) -> Result<()> {
+        let c = self
+            .remote_candidates
+            .get_mut(index)
+            .ok_or(Error::ErrRemoteCandidateNotFound)?;
+        let unresolved = c.address().ends_with(".local");
+        c.address = resolved_addr.ip().to_string();
+        c.port = resolved_addr.port();
+        c.resolved_addr = resolved_addr;
+        c.network_type = determine_network_type(&c.network, &resolved_addr.ip())?;
+        let priority = c.priority();
+
+        if unresolved {
+            for local_index in 0..self.local_candidates.len() {
+                self.add_pair(local_index, index);
+            }
+            self.prune_pairs();
+        } else {
+            for p in &mut self.candidate_pairs {
+                if p.remote_index == index {
+                    p.remote_priority = priority;
+                }
+            }
+            self.index_candidate_pairs();
+        }
+
+        self.request_connectivity_check();
+
+        Ok(())
+    }
+
+    /// Resolves every remote host candidate announced with the mDNS name to ip, keeping
+    /// its port, which creates its pairs and triggers connectivity checks.
+    pub fn resolve_remote_candidate(&mut self, name: &str, ip: IpAddr) -> Result<()> {
+        let indexes: Vec<usize> = self
+            .remote_candidates
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.address().eq_ignore_ascii_case(name))
+            .map(|(index, _)| index)
+            .collect();
+        if indexes.is_empty() {
+            return Err(Error::ErrRemoteCandidateNotFound);
         }
 
+        for index in indexes {
+            let port = self.remote_candidates[index].port();
+            self.set_remote_candidate_address(index, SocketAddr::new(ip, port))?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the index of the remote candidate with the given foundation.
+    pub fn find_remote_candidate_by_foundation(&self, foundation: &str) -> Option<usize> {
+        self.remote_candidates
+            .iter()
+            .position(|c| c.foundation() == foundation)
+    }
+
+    /// Returns the mDNS names announced instead of the IP addresses of local host candidates.
+    pub fn mdns_host_names(&self) -> &HashMap<IpAddr, String> {
+        &self.mdns_host_names
+    }
+
+    /// Adds a new remote candidate.
+    pub fn add_remote_candidate(&mut self, c: Candidate) -> Result<()> {
         for cand in &self.remote_candidates {
             if cand.equal(&c) {
                 return Ok(());
             }
         }
 
+        // A mDNS candidate is only paired once resolve_remote_candidate resolves it
+        if c.candidate_type() == CandidateType::Host && c.address().ends_with(".local") {
+            if self.multicast_dns_mode == MulticastDnsMode::Disabled {
+                return Err(Error::ErrMulticastDnsNotSupported);
+            }
+
+            debug!(
+                "remote mDNS candidate added, waiting for its resolution: ({})",
+                c.address()
+            );
+            self.events.push_back(Event::RemoteCandidateNeedsResolution(
+                c.address().to_owned(),
+                Box::new(c.clone()),
+            ));
+            self.remote_candidates.push(c);
+            return Ok(());
+        }
+
         self.remote_candidates.push(c);
 
         for local_index in 0..self.local_candidates.len() {
             self.add_pair(local_index, self.remote_candidates.len() - 1);
         }
+        self.prune_pairs();
 
         self.request_connectivity_check();
 
@@ -348,14 +719,27 @@ impl Agent {
     }
 
     pub fn handle_read(&mut self, msg: Transmit<BytesMut>) -> Result<()> {
+        if self.is_closed() {
+            return Err(Error::ErrConnectionClosed);
+        }
+
+        if let Some(client_index) = self.find_relay_client(&msg.transport, &msg.message) {
+            return self.handle_relay_read(client_index, msg);
+        }
+
         if let Some(local_index) =
             self.find_local_candidate(msg.transport.local_addr, msg.transport.protocol)
         {
+            if msg.transport.protocol == Protocol::TCP {
+                return self.handle_read_tcp(local_index, msg);
+            }
+
             self.handle_inbound_candidate_msg(
                 local_index,
                 &msg.message,
                 msg.transport.peer_addr,
                 msg.transport.local_addr,
+                msg.now,
             )
         } else {
             warn!(
@@ -368,24 +752,79 @@ impl Agent {
         }
     }
 
+    /// Appends a TCP segment to the reassembly buffer of its connection, and handles every
+    /// complete RFC 4571 frame in it. Returns the first error of the handled frames.
+    fn handle_read_tcp(&mut self, local_index: usize, msg: Transmit<BytesMut>) -> Result<()> {
+        let (local_addr, peer_addr, now) =
+            (msg.transport.local_addr, msg.transport.peer_addr, msg.now);
+        let buf = self
+            .tcp_read_buffers
+            .entry((local_addr, peer_addr))
+            .or_default();
+        buf.extend_from_slice(&msg.message);
+
+        let mut frames = vec![];
+        while buf.len() >= 2 {
+            let len = u16::from_be_bytes([buf[0], buf[1]]) as usize;
+            if buf.len() < 2 + len {
+                break;
+            }
+            buf.advance(2);
+            frames.push(buf.split_to(len));
+        }
+        if buf.is_empty() {
+            self.tcp_read_buffers.remove(&(local_addr, peer_addr));
+        }
+
+        let mut result = Ok(());
+        for frame in frames {
+            if let Err(err) =
+                self.handle_inbound_candidate_msg(local_index, &frame, peer_addr, local_addr, now)
+            {
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+        result
+    }
+
     pub fn poll_transmit(&mut self) -> Option<Transmit<BytesMut>> {
         self.transmits.pop_front()
     }
 
+    /// Returns the next application data received through the TURN server of a local relay
+    /// candidate. Its transport goes from the remote candidate to the relayed address.
+    pub fn poll_read(&mut self) -> Option<Transmit<BytesMut>> {
+        self.relayed_reads.pop_front()
+    }
+
     pub fn handle_timeout(&mut self, now: Instant) {
+        self.handle_gather_timeout(now);
+        self.handle_relay_timeout(now);
+
         if self.ufrag_pwd.remote_credentials.is_some()
-            && self.last_checking_time + self.get_timeout_interval() <= now
+            && (self.last_checking_time + self.get_timeout_interval() <= now
+                || self.next_retransmit().is_some_and(|t| t <= now))
         {
             self.contact(now);
         }
     }
 
     pub fn poll_timeout(&self) -> Option<Instant> {
-        if self.ufrag_pwd.remote_credentials.is_some() {
-            Some(self.last_checking_time + self.get_timeout_interval())
+        let check_timeout = if self.ufrag_pwd.remote_credentials.is_some() {
+            let next_check = self.last_checking_time + self.get_timeout_interval();
+            Some(match self.next_retransmit() {
+                Some(next_retransmit) => next_check.min(next_retransmit),
+                None => next_check,
+            })
         } else {
             None
-        }
+        };
+        [check_timeout, self.gather_timeout(), self.relay_timeout()]
+            .into_iter()
+            .flatten()
+            .min()
     }
 
     pub fn poll_event(&mut self) -> Option<Event> {
@@ -414,6 +853,7 @@ impl Agent {
             }
             ConnectionState::Connected | ConnectionState::Disconnected => {
                 update_interval(keepalive_interval);
+                update_interval(self.consent_check_interval);
             }
             _ => {}
         };
@@ -423,9 +863,52 @@ impl Agent {
         interval
     }
 
+    /// Returns the current connection state of the Agent.
+    pub fn state(&self) -> ConnectionState {
+        self.connection_state
+    }
+
+    /// Returns true if the Agent has a usable candidate pair.
+    pub fn is_connected(&self) -> bool {
+        matches!(
+            self.connection_state,
+            ConnectionState::Connected | ConnectionState::Completed
+        )
+    }
+
+    /// Returns true if the Agent has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.connection_state == ConnectionState::Closed
+    }
+
+    /// Returns true if the Agent failed to connect.
+    pub fn is_failed(&self) -> bool {
+        self.connection_state == ConnectionState::Failed
+    }
+
+    /// Returns true if the Agent is running connectivity checks.
+    pub fn is_checking(&self) -> bool {
+        self.connection_state == ConnectionState::Checking
+    }
+
+    /// Drives handle_timeout up to max_polls times and returns whether the
+    /// Agent reached the target state.
+    #[cfg(test)]
+    pub(crate) fn wait_for_state(&mut self, target: ConnectionState, max_polls: usize) -> bool {
+        for _ in 0..max_polls {
+            if self.connection_state == target {
+                return true;
+            }
+            self.handle_timeout(Instant::now());
+        }
+        self.connection_state == target
+    }
+
     /// Cleans up the Agent.
     pub fn close(&mut self) -> Result<()> {
-        self.set_selected_pair(None);
+        self.clear_selected_pair();
+        self.old_selected_pair = None;
+        self.tcp_read_buffers.clear();
         self.delete_all_candidates(false);
         self.update_connection_state(ConnectionState::Closed);
 
@@ -441,10 +924,28 @@ impl Agent {
                 self.remote_candidates[candidate_pair.remote_index].clone(),
             ))
         } else {
-            None
+            self.old_selected_pair.clone()
         }
     }
 
+    /// Returns a copy of the nominated candidate pair or none. On the controlled side, where
+    /// nomination is signalled by the remote agent, this is the selected pair.
+    pub fn get_nominated_pair(&self) -> Option<CandidatePair> {
+        self.nominated_pair
+            .or(self.selected_pair)
+            .and_then(|pair_index| self.candidate_pairs.get(pair_index))
+            .filter(|p| p.nominated)
+            .copied()
+    }
+
+    /// Returns whether the candidate pair with the given local and remote candidate indices
+    /// has been nominated
+    pub fn is_pair_nominated(&self, local_index: usize, remote_index: usize) -> bool {
+        self.candidate_pairs
+            .iter()
+            .any(|p| p.local_index == local_index && p.remote_index == remote_index && p.nominated)
+    }
+
     /// start connectivity checks
     pub fn start_connectivity_checks(
         &mut self,
@@ -457,8 +958,7 @@ impl Agent {
             is_controlling, remote_ufrag, remote_pwd
         );
         self.set_remote_credentials(remote_ufrag, remote_pwd)?;
-        self.is_controlling = is_controlling;
-        self.start();
+        self.set_role(is_controlling);
 
         self.update_connection_state(ConnectionState::Checking);
         self.request_connectivity_check();
@@ -468,11 +968,17 @@ impl Agent {
 
     /// Restarts the ICE Agent with the provided ufrag/pwd
     /// If no ufrag/pwd is provided the Agent will generate one itself.
+    ///
+    /// A graceful restart is make-before-break: the currently selected pair is still returned by
+    /// get_selected_candidate_pair() and accepted for inbound traffic until a new pair is
+    /// selected, at which point Event::IceRestartCompleted is emitted. It requires
+    /// keep_local_candidates, as the old pair is received on its local candidate.
     pub fn restart(
         &mut self,
         mut ufrag: String,
         mut pwd: String,
         keep_local_candidates: bool,
+        graceful: bool,
     ) -> Result<()> {
         if ufrag.is_empty() {
             ufrag = generate_ufrag();
@@ -487,17 +993,33 @@ impl Agent {
         if pwd.len() * 8 < 128 {
             return Err(Error::ErrLocalPwdInsufficientBits);
         }
+        if graceful && !keep_local_candidates {
+            return Err(Error::ErrGracefulRestartWithoutLocalCandidates);
+        }
 
         // Clear all agent needed to take back to fresh state
         self.ufrag_pwd.local_credentials.ufrag = ufrag;
         self.ufrag_pwd.local_credentials.pwd = pwd;
         self.ufrag_pwd.remote_credentials = None;
 
-        self.pending_binding_requests = vec![];
+        if !self.preserve_tie_breaker_on_restart {
+            self.tie_breaker = rand::random::<u64>();
+        }
+
+        self.pending_binding_requests.clear();
+        self.recent_transaction_ids.clear();
+        self.recent_responses.clear();
 
+        self.old_selected_pair = if graceful {
+            self.get_selected_candidate_pair()
+        } else {
+            None
+        };
         self.candidate_pairs = vec![];
+        self.candidate_pair_index.clear();
+        self.last_check_started = None;
 
-        self.set_selected_pair(None);
+        self.clear_selected_pair();
         self.delete_all_candidates(keep_local_candidates);
         self.start();
 
@@ -510,11 +1032,121 @@ impl Agent {
         Ok(())
     }
 
+    /// Restarts the ICE Agent with the provided ufrag/pwd like restart, but keeps the
+    /// remote credentials and candidates and resumes connectivity checks right away.
+    /// This allows an ICE restart that is not signaled through an offer/answer exchange.
+    pub fn restart_in_band(&mut self, ufrag: String, pwd: String) -> Result<()> {
+        let remote_credentials = self
+            .ufrag_pwd
+            .remote_credentials
+            .clone()
+            .ok_or(Error::ErrRemoteUfragEmpty)?;
+        let remote_candidates = self.remote_candidates.clone();
+
+        self.restart(ufrag, pwd, true, false)?;
+        for c in remote_candidates {
+            self.add_remote_candidate(c)?;
+        }
+
+        self.start_connectivity_checks(
+            self.is_controlling,
+            remote_credentials.ufrag,
+            remote_credentials.pwd,
+        )
+    }
+
     /// Returns the local candidates.
     pub fn get_local_candidates(&self) -> &[Candidate] {
         &self.local_candidates
     }
 
+    /// Replaces the candidate priority policy of `AgentConfig::candidate_priority_fn`. The new
+    /// policy applies to the candidates added afterwards, and to the existing ones once
+    /// `Agent::recompute_priorities()` is called.
+    pub fn set_candidate_priority_fn(
+        &mut self,
+        candidate_priority_fn: Option<CandidatePriorityFn>,
+    ) {
+        self.candidate_priority_fn = candidate_priority_fn;
+    }
+
+    /// Applies the candidate priority policy to the local candidates again, e.g. after it
+    /// changed or after the network interfaces it looks at changed. The pair priorities are
+    /// updated and the checklist re-sorted, and, in Regular nomination within
+    /// renomination_window, the controlling agent renominates a valid pair whose priority is
+    /// now higher than the selected one.
+    pub fn recompute_priorities(&mut self, now: Instant) {
+        let mut local_candidates = std::mem::take(&mut self.local_candidates);
+        for c in &mut local_candidates {
+            self.apply_priority_policy(c);
+        }
+        self.local_candidates = local_candidates;
+
+        for p in &mut self.candidate_pairs {
+            p.local_priority = self.local_candidates[p.local_index].priority();
+            p.remote_priority = self.remote_candidates[p.remote_index].priority();
+        }
+        self.prune_pairs();
+
+        if self.is_controlling && self.is_renomination_allowed(now) {
+            if let Some(selected_pair) = self.selected_pair {
+                self.renominate(selected_pair, now);
+            }
+        }
+    }
+
+    fn apply_priority_policy(&self, c: &mut Candidate) {
+        c.policy_priority = None;
+        if let Some(candidate_priority_fn) = &self.candidate_priority_fn {
+            c.policy_priority = Some(candidate_priority_fn(c));
+        }
+    }
+
+    /// Returns the number of outbound binding requests still awaiting a response.
+    pub fn pending_binding_requests_count(&self) -> usize {
+        self.pending_binding_requests.len()
+    }
+
+    /// Returns how long the oldest pending binding request has been outstanding at `now`,
+    /// or none if there is no pending binding request.
+    pub fn oldest_pending_request_age(&self, now: Instant) -> Option<Duration> {
+        self.pending_binding_requests
+            .oldest()
+            .map(|binding_request| {
+                now.checked_duration_since(binding_request.timestamp)
+                    .unwrap_or_else(|| Duration::from_secs(0))
+            })
+    }
+
+    /// Records an application data packet of `len` bytes sent (outbound) or received over
+    /// `transport` at `now`, so that it is accounted on the matching candidate pair stats.
+    /// Packets that don't belong to a known candidate pair are ignored.
+    pub fn record_data_packet(
+        &mut self,
+        transport: &TransportContext,
+        outbound: bool,
+        len: usize,
+        now: Instant,
+    ) {
+        let Some(&pair_index) = self.candidate_pair_index.get(&(
+            transport.local_addr,
+            transport.peer_addr,
+            transport.protocol,
+        )) else {
+            return;
+        };
+        let Some(p) = self.candidate_pairs.get_mut(pair_index) else {
+            return;
+        };
+        p.record_packet(outbound, len, now);
+        if let Some(c) = self.local_candidates.get_mut(p.local_index) {
+            c.record_packet(outbound, len, now);
+        }
+        if let Some(c) = self.remote_candidates.get_mut(p.remote_index) {
+            c.record_packet(outbound, len, now);
+        }
+    }
+
     fn contact(&mut self, now: Instant) {
         if self.connection_state == ConnectionState::Failed {
             // The connection is currently failed so don't send any checks
@@ -540,7 +1172,8 @@ impl Agent {
             }
         }
 
-        self.contact_candidates();
+        self.invalidate_pending_binding_requests(now);
+        self.contact_candidates(now);
 
         self.last_connection_state = self.connection_state;
         self.last_checking_time = now;
@@ -550,7 +1183,8 @@ impl Agent {
         if self.connection_state != new_state {
             // Connection has gone to failed, release all gathered candidates
             if new_state == ConnectionState::Failed {
-                self.set_selected_pair(None);
+                self.clear_selected_pair();
+                self.old_selected_pair = None;
                 self.delete_all_candidates(false);
             }
 
@@ -565,80 +1199,313 @@ impl Agent {
         }
     }
 
-    pub(crate) fn set_selected_pair(&mut self, selected_pair: Option<usize>) {
-        if let Some(pair_index) = selected_pair {
-            trace!(
-                "[{}]: Set selected candidate pair: {:?}",
-                self.get_name(),
-                self.candidate_pairs[pair_index]
-            );
+    pub(crate) fn set_selected_pair(&mut self, pair_index: usize, now: Instant) {
+        trace!(
+            "[{}]: Set selected candidate pair: {:?}",
+            self.get_name(),
+            self.candidate_pairs[pair_index]
+        );
 
-            self.candidate_pairs[pair_index].nominated = true;
-            self.selected_pair = Some(pair_index);
+        let p = &mut self.candidate_pairs[pair_index];
+        p.nominated = true;
+        p.consent_expiry.get_or_insert(now + CONSENT_TIMEOUT);
+        self.selected_pair = Some(pair_index);
+        self.next_consent_check = Some(now + self.next_consent_check_interval());
 
-            self.update_connection_state(ConnectionState::Connected);
+        self.update_connection_state(ConnectionState::Connected);
 
-            // Notify when the selected pair changes
-            let candidate_pair = &self.candidate_pairs[pair_index];
-            self.events.push_back(Event::SelectedCandidatePairChange(
-                Box::new(self.local_candidates[candidate_pair.local_index].clone()),
-                Box::new(self.remote_candidates[candidate_pair.remote_index].clone()),
-            ));
-        } else {
-            self.selected_pair = None;
+        // Notify when the selected pair changes
+        let candidate_pair = &self.candidate_pairs[pair_index];
+        self.events.push_back(Event::SelectedCandidatePairChange(
+            Box::new(self.local_candidates[candidate_pair.local_index].clone()),
+            Box::new(self.remote_candidates[candidate_pair.remote_index].clone()),
+        ));
+
+        if self.old_selected_pair.take().is_some() {
+            debug!("[{}]: ICE restart completed", self.get_name());
+            self.events.push_back(Event::IceRestartCompleted);
         }
     }
 
-    pub(crate) fn ping_all_candidates(&mut self) {
+    pub(crate) fn clear_selected_pair(&mut self) {
+        self.selected_pair = None;
+        self.next_consent_check = None;
+    }
+
+    /// Returns the consent check interval randomized between 0.8 and 1.2 times its configured
+    /// value, as recommended by RFC 7675 section 5.1.
+    fn next_consent_check_interval(&self) -> Duration {
+        self.consent_check_interval
+            .mul_f64(rand::thread_rng().gen_range(0.8..=1.2))
+    }
+
+    /// Returns when the earliest connectivity check in flight is due for retransmission.
+    fn next_retransmit(&self) -> Option<Instant> {
+        self.candidate_pairs
+            .iter()
+            .filter(|p| p.state == CandidatePairState::InProgress)
+            .filter_map(|p| p.next_retransmit)
+            .min()
+    }
+
+    /// Sends a connectivity check on every waiting pair, and retransmits the checks in flight
+    /// whose retransmission timeout expired. The timeout starts at initial_rto and doubles after
+    /// each retransmission; a pair whose check was retransmitted max_retransmissions times fails
+    /// once the last timeout expires.
+    pub(crate) fn ping_all_candidates(&mut self, now: Instant) {
         trace!("[{}]: pinging all candidates", self.get_name(),);
 
         let mut pairs: Vec<(usize, usize)> = vec![];
 
-        {
-            let name = self.get_name().to_string();
-            if self.candidate_pairs.is_empty() {
-                warn!(
+        if self.candidate_pairs.is_empty() {
+            warn!(
                 "[{}]: pingAllCandidates called with no candidate pairs. Connection is not possible yet.",
-                name,
+                self.get_name(),
             );
+        }
+
+        // a single Waiting pair, the highest priority one, is checked every Ta
+        // (RFC 8445 section 6.1.4.2), Frozen pairs wait for their foundation
+        let next_waiting_pair = if self
+            .last_check_started
+            .is_none_or(|started| started + self.check_interval <= now)
+        {
+            self.candidate_pairs
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| p.state == CandidatePairState::Waiting)
+                .max_by_key(|(index, p)| (p.priority(), std::cmp::Reverse(*index)))
+                .map(|(index, _)| index)
+        } else {
+            None
+        };
+
+        for pair_index in 0..self.candidate_pairs.len() {
+            let state = self.candidate_pairs[pair_index].state;
+            if Some(pair_index) == next_waiting_pair {
+                self.set_pair_state(pair_index, CandidatePairState::InProgress);
+                self.last_check_started = Some(now);
+            } else if state != CandidatePairState::InProgress
+                || self.candidate_pairs[pair_index]
+                    .next_retransmit
+                    .is_some_and(|t| t > now)
+            {
+                continue;
             }
-            for p in &mut self.candidate_pairs {
-                if p.state == CandidatePairState::Waiting {
-                    p.state = CandidatePairState::InProgress;
-                } else if p.state != CandidatePairState::InProgress {
-                    continue;
-                }
 
-                if p.binding_request_count > self.max_binding_requests {
-                    trace!(
-                        "[{}]: max requests reached for pair {}, marking it as failed",
-                        name,
-                        *p
-                    );
-                    p.state = CandidatePairState::Failed;
+            let p = &mut self.candidate_pairs[pair_index];
+            if p.binding_request_count > self.max_retransmissions {
+                trace!(
+                    "[{}]: max requests reached for pair {}, marking it as failed",
+                    self.get_name(),
+                    self.candidate_pairs[pair_index]
+                );
+                self.set_pair_state(pair_index, CandidatePairState::Failed);
+            } else {
+                p.rto = if p.next_retransmit.is_none() {
+                    self.initial_rto
                 } else {
-                    p.binding_request_count += 1;
-                    let local = p.local_index;
-                    let remote = p.remote_index;
-                    pairs.push((local, remote));
-                }
+                    p.rto * 2
+                };
+                p.next_retransmit = Some(now + p.rto);
+                p.binding_request_count += 1;
+                pairs.push((p.local_index, p.remote_index));
             }
         }
 
         for (local, remote) in pairs {
-            self.ping_candidate(local, remote);
+            self.ping_candidate(local, remote, now);
         }
     }
 
     pub(crate) fn add_pair(&mut self, local_index: usize, remote_index: usize) {
-        let p = CandidatePair::new(
+        let mut p = CandidatePair::new(
             local_index,
             remote_index,
             self.local_candidates[local_index].priority(),
             self.remote_candidates[remote_index].priority(),
             self.is_controlling,
         );
+        p.state = CandidatePairState::Frozen;
+        self.candidate_pair_index
+            .entry(self.candidate_pair_key(&p))
+            .or_insert(self.candidate_pairs.len());
         self.candidate_pairs.push(p);
+        if self.local_candidates[local_index].candidate_type() == CandidateType::Relay {
+            self.create_relay_permission(local_index, remote_index);
+        }
+        self.events.push_back(Event::CandidatePairStateChange(
+            Box::new(self.local_candidates[local_index].clone()),
+            Box::new(self.remote_candidates[remote_index].clone()),
+            p.state,
+        ));
+        self.update_frozen_pairs();
+    }
+
+    /// Sets the state of a candidate pair, emitting Event::CandidatePairStateChange if it
+    /// changed.
+    pub(crate) fn set_pair_state(&mut self, pair_index: usize, state: CandidatePairState) {
+        let p = &mut self.candidate_pairs[pair_index];
+        if p.state == state {
+            return;
+        }
+        p.state = state;
+        if state != CandidatePairState::InProgress {
+            p.next_retransmit = None;
+        }
+
+        let (local_index, remote_index) = (p.local_index, p.remote_index);
+        self.events.push_back(Event::CandidatePairStateChange(
+            Box::new(self.local_candidates[local_index].clone()),
+            Box::new(self.remote_candidates[remote_index].clone()),
+            state,
+        ));
+
+        // the completed check unfreezes the next pair of its foundation
+        if state == CandidatePairState::Succeeded || state == CandidatePairState::Failed {
+            self.update_frozen_pairs();
+        }
+    }
+
+    /// Checks the pairs of a foundation one at a time (RFC 8445 section 6.1.2.6): for every
+    /// foundation without a check in progress, its highest priority pair not checked yet is
+    /// Waiting, and the other ones are Frozen.
+    pub(crate) fn update_frozen_pairs(&mut self) {
+        let mut order: Vec<usize> = (0..self.candidate_pairs.len()).collect();
+        // stable, so the earliest pair is checked first among pairs of equal priority
+        order.sort_by_key(|index| std::cmp::Reverse(self.candidate_pairs[*index].priority()));
+
+        let foundations: Vec<(String, String)> = self
+            .candidate_pairs
+            .iter()
+            .map(|p| p.foundation(&self.local_candidates, &self.remote_candidates))
+            .collect();
+        let mut active: HashSet<&(String, String)> = self
+            .candidate_pairs
+            .iter()
+            .zip(&foundations)
+            .filter(|(p, _)| p.state == CandidatePairState::InProgress)
+            .map(|(_, foundation)| foundation)
+            .collect();
+
+        let mut states = vec![];
+        for index in order {
+            let state = self.candidate_pairs[index].state;
+            if state != CandidatePairState::Waiting && state != CandidatePairState::Frozen {
+                continue;
+            }
+            if active.insert(&foundations[index]) {
+                states.push((index, CandidatePairState::Waiting));
+            } else {
+                states.push((index, CandidatePairState::Frozen));
+            }
+        }
+        for (index, state) in states {
+            self.set_pair_state(index, state);
+        }
+    }
+
+    /// Nominates a candidate pair on the controlling agent, emitting Event::NominationChange.
+    pub(crate) fn set_nominated_pair(&mut self, pair_index: usize) {
+        let p = &mut self.candidate_pairs[pair_index];
+        p.nominated = true;
+        self.nominated_pair = Some(pair_index);
+
+        let (local_index, remote_index) = (p.local_index, p.remote_index);
+        self.events.push_back(Event::NominationChange(
+            Box::new(self.local_candidates[local_index].clone()),
+            Box::new(self.remote_candidates[remote_index].clone()),
+        ));
+    }
+
+    /// Prunes the checklist as described in RFC 8445 section 6.1.2.4: server reflexive local
+    /// candidates are replaced by their base, redundant pairs are removed keeping the highest
+    /// priority one, and the pairs are ordered by priority and limited to max_candidate_pairs.
+    /// The selected and nominated pairs are always kept.
+    pub(crate) fn prune_pairs(&mut self) {
+        let (selected, nominated) = (self.selected_pair, self.nominated_pair);
+
+        let mut pairs: Vec<(CandidatePair, bool, bool)> = self
+            .candidate_pairs
+            .iter()
+            .enumerate()
+            .map(|(index, p)| (*p, selected == Some(index), nominated == Some(index)))
+            .collect();
+
+        for (p, _, _) in &mut pairs {
+            if self.local_candidates[p.local_index].candidate_type()
+                != CandidateType::ServerReflexive
+            {
+                continue;
+            }
+            if let Some(base_index) = self.find_local_candidate_base(p.local_index) {
+                p.local_index = base_index;
+                p.local_priority = self.local_candidates[base_index].priority();
+            }
+        }
+
+        // stable, so the earliest pair wins among redundant pairs of equal priority
+        pairs.sort_by_key(|(p, _, _)| std::cmp::Reverse(p.priority()));
+
+        let mut seen = HashSet::new();
+        let mut kept = 0;
+        pairs.retain(|(p, is_selected, is_nominated)| {
+            if !seen.insert((p.local_index, p.remote_index)) {
+                return *is_selected || *is_nominated;
+            }
+            kept += 1;
+            kept <= self.max_candidate_pairs || *is_selected || *is_nominated
+        });
+
+        if pairs.len() < self.candidate_pairs.len() {
+            trace!(
+                "[{}]: pruned {} candidate pairs, {} remaining",
+                self.get_name(),
+                self.candidate_pairs.len() - pairs.len(),
+                pairs.len()
+            );
+        }
+
+        self.selected_pair = pairs.iter().position(|(_, is_selected, _)| *is_selected);
+        self.nominated_pair = pairs.iter().position(|(_, _, is_nominated)| *is_nominated);
+        self.candidate_pairs = pairs.into_iter().map(|(p, _, _)| p).collect();
+        self.index_candidate_pairs();
+        self.update_frozen_pairs();
+    }
+
+    fn candidate_pair_key(&self, p: &CandidatePair) -> (SocketAddr, SocketAddr, Protocol) {
+        let local = &self.local_candidates[p.local_index];
+        (
+            local.addr(),
+            self.remote_candidates[p.remote_index].addr(),
+            local.network_type().to_protocol(),
+        )
+    }
+
+    // keep the first pair of a 5-tuple, as a linear scan would find it
+    fn index_candidate_pairs(&mut self) {
+        let mut index = HashMap::new();
+        for (pair_index, p) in self.candidate_pairs.iter().enumerate() {
+            index
+                .entry(self.candidate_pair_key(p))
+                .or_insert(pair_index);
+        }
+        self.candidate_pair_index = index;
+    }
+
+    /// Returns the index of the local host candidate that is the base of the given server
+    /// reflexive candidate, i.e. whose address is its related address.
+    fn find_local_candidate_base(&self, local_index: usize) -> Option<usize> {
+        let c = &self.local_candidates[local_index];
+        let related_address = c.related_address()?;
+        let ip = related_address.address.parse::<IpAddr>().ok()?;
+        let base_addr = SocketAddr::new(ip, related_address.port);
+
+        self.local_candidates.iter().position(|base| {
+            base.candidate_type() == CandidateType::Host
+                && base.network_type() == c.network_type()
+                && base.addr() == base_addr
+        })
     }
 
     pub(crate) fn find_pair(&self, local_index: usize, remote_index: usize) -> Option<usize> {
@@ -652,20 +1519,33 @@ impl Agent {
 
     /// Checks if the selected pair is (still) valid.
     /// Note: the caller should hold the agent lock.
-    pub(crate) fn validate_selected_pair(&mut self) -> bool {
+    pub(crate) fn validate_selected_pair(&mut self, now: Instant) -> bool {
         let (valid, disconnected_time) = {
             self.selected_pair.as_ref().map_or_else(
                 || (false, Duration::from_secs(0)),
                 |&pair_index| {
                     let remote_index = self.candidate_pairs[pair_index].remote_index;
 
-                    let disconnected_time = Instant::now()
-                        .duration_since(self.remote_candidates[remote_index].last_received());
+                    let disconnected_time =
+                        now.duration_since(self.remote_candidates[remote_index].last_received());
                     (true, disconnected_time)
                 },
             )
         };
 
+        if let Some(pair_index) = self.selected_pair {
+            if self.consent_expired(pair_index, now) {
+                warn!(
+                    "[{}]: consent expired for selected pair {}",
+                    self.get_name(),
+                    self.candidate_pairs[pair_index]
+                );
+                self.set_pair_state(pair_index, CandidatePairState::Failed);
+                self.update_connection_state(ConnectionState::Failed);
+                return false;
+            }
+        }
+
         if valid {
             // Only allow transitions to fail if a.failedTimeout is non-zero
             let mut total_time_to_failure = self.failed_timeout;
@@ -689,33 +1569,55 @@ impl Agent {
         valid
     }
 
-    /// Sends STUN Binding Indications to the selected pair.
-    /// if no packet has been sent on that pair in the last keepaliveInterval.
+    /// Returns whether no consent freshness check sent on the pair has been answered within
+    /// CONSENT_TIMEOUT. Lite agents don't send checks, so their consent never expires.
+    pub(crate) fn consent_expired(&self, pair_index: usize, now: Instant) -> bool {
+        !self.lite
+            && self.consent_check_interval != Duration::from_secs(0)
+            && self.candidate_pairs[pair_index]
+                .consent_expiry
+                .is_some_and(|consent_expiry| now >= consent_expiry)
+    }
+
+    /// Sends a consent freshness Binding Request to the selected pair every consent check
+    /// interval, and a keepalive if no packet has been sent or received on that pair in the last
+    /// keepaliveInterval.
     /// Note: the caller should hold the agent lock.
-    pub(crate) fn check_keepalive(&mut self) {
+    pub(crate) fn check_keepalive(&mut self, now: Instant) {
+        let Some(pair_index) = self.selected_pair else {
+            return;
+        };
         let (local_index, remote_index) = {
-            self.selected_pair
-                .as_ref()
-                .map_or((None, None), |&pair_index| {
-                    let p = &self.candidate_pairs[pair_index];
-                    (Some(p.local_index), Some(p.remote_index))
-                })
+            let p = &self.candidate_pairs[pair_index];
+            (p.local_index, p.remote_index)
         };
 
-        if let (Some(local_index), Some(remote_index)) = (local_index, remote_index) {
-            let last_sent =
-                Instant::now().duration_since(self.local_candidates[local_index].last_sent());
+        if self.consent_check_interval != Duration::from_secs(0)
+            && self
+                .next_consent_check
+                .is_some_and(|next_consent_check| now >= next_consent_check)
+        {
+            // a consent check also serves as keepalive
+            self.candidate_pairs[pair_index].consent_requests_sent += 1;
+            self.next_consent_check = Some(now + self.next_consent_check_interval());
+            self.ping_candidate(local_index, remote_index, now);
+            return;
+        }
+
+        let last_sent = now.duration_since(self.local_candidates[local_index].last_sent());
 
-            let last_received =
-                Instant::now().duration_since(self.remote_candidates[remote_index].last_received());
+        let last_received =
+            now.duration_since(self.remote_candidates[remote_index].last_received());
 
-            if (self.keepalive_interval != Duration::from_secs(0))
-                && ((last_sent > self.keepalive_interval)
-                    || (last_received > self.keepalive_interval))
-            {
+        if (self.keepalive_interval != Duration::from_secs(0))
+            && ((last_sent > self.keepalive_interval) || (last_received > self.keepalive_interval))
+        {
+            if self.use_binding_indication_for_keepalive {
+                self.send_binding_indication(local_index, remote_index, now);
+            } else {
                 // we use binding request instead of indication to support refresh consent schemas
                 // see https://tools.ietf.org/html/rfc7675
-                self.ping_candidate(local_index, remote_index);
+                self.ping_candidate(local_index, remote_index, now);
             }
         }
     }
@@ -732,9 +1634,13 @@ impl Agent {
     /// This is used for restarts, failures and on close.
     pub(crate) fn delete_all_candidates(&mut self, keep_local_candidates: bool) {
         if !keep_local_candidates {
+            self.gather_requests.clear();
+            self.close_relay_clients();
             self.local_candidates.clear();
+            self.local_candidate_index.clear();
         }
         self.remote_candidates.clear();
+        self.candidate_pair_index.clear();
     }
 
     pub(crate) fn find_remote_candidate(&self, addr: SocketAddr) -> Option<usize> {
@@ -752,12 +1658,7 @@ impl Agent {
         addr: SocketAddr,
         protocol: Protocol,
     ) -> Option<usize> {
-        for (index, c) in self.local_candidates.iter().enumerate() {
-            if c.addr() == addr && c.network_type().to_protocol() == protocol {
-                return Some(index);
-            }
-        }
-        None
+        self.local_candidate_index.get(&(addr, protocol)).copied()
     }
 
     pub(crate) fn send_binding_request(
@@ -765,6 +1666,7 @@ impl Agent {
         m: &Message,
         local_index: usize,
         remote_index: usize,
+        now: Instant,
     ) {
         trace!(
             "[{}]: ping STUN from {} to {}",
@@ -773,16 +1675,26 @@ impl Agent {
             remote_index
         );
 
-        self.invalidate_pending_binding_requests(Instant::now());
+        self.invalidate_pending_binding_requests(now);
 
-        self.pending_binding_requests.push(BindingRequest {
-            timestamp: Instant::now(),
+        if let Some(evicted) = self.pending_binding_requests.push(BindingRequest {
+            timestamp: now,
             transaction_id: m.transaction_id,
             destination: self.remote_candidates[remote_index].addr(),
             is_use_candidate: m.contains(ATTR_USE_CANDIDATE),
-        });
+            is_controlling: self.is_controlling,
+        }) {
+            trace!(
+                "[{}]: Discarded binding request to {} because too many requests are pending",
+                self.get_name(),
+                evicted.destination
+            );
+        }
+        if let Some(pair_index) = self.find_pair(local_index, remote_index) {
+            self.candidate_pairs[pair_index].record_request_sent(now);
+        }
 
-        self.send_stun(m, local_index, remote_index);
+        self.send_stun(m, local_index, remote_index, now);
     }
 
     pub(crate) fn send_binding_success(
@@ -790,6 +1702,7 @@ impl Agent {
         m: &Message,
         local_index: usize,
         remote_index: usize,
+        now: Instant,
     ) {
         let addr = self.remote_candidates[remote_index].addr();
         let (ip, port) = (addr.ip(), addr.port());
@@ -816,7 +1729,39 @@ impl Agent {
                 err
             );
         } else {
-            self.send_stun(&out, local_index, remote_index);
+            if let Some(pair_index) = self.find_pair(local_index, remote_index) {
+                self.candidate_pairs[pair_index].responses_sent += 1;
+            }
+            if let Some(response) = self.recent_responses.get_mut(&out.transaction_id) {
+                *response = Some((out.clone(), local_index, remote_index));
+            }
+            self.send_stun(&out, local_index, remote_index, now);
+        }
+    }
+
+    /// Sends a STUN Binding Indication, which is not answered and so not tracked as a pending
+    /// binding request.
+    pub(crate) fn send_binding_indication(
+        &mut self,
+        local_index: usize,
+        remote_index: usize,
+        now: Instant,
+    ) {
+        let mut msg = Message::new();
+        if let Err(err) = msg.build(&[
+            Box::new(MessageType::new(METHOD_BINDING, CLASS_INDICATION)),
+            Box::new(TransactionId::new()),
+            Box::new(FINGERPRINT),
+        ]) {
+            warn!(
+                "[{}]: Failed to build binding indication from: {} to: {} error: {}",
+                self.get_name(),
+                local_index,
+                remote_index,
+                err
+            );
+        } else {
+            self.send_stun(&msg, local_index, remote_index, now);
         }
     }
 
@@ -825,29 +1770,15 @@ impl Agent {
     ///
     /// reference: (IETF ref-8445)[https://tools.ietf.org/html/rfc8445#appendix-B.1].
     pub(crate) fn invalidate_pending_binding_requests(&mut self, filter_time: Instant) {
-        let pending_binding_requests = &mut self.pending_binding_requests;
-        let initial_size = pending_binding_requests.len();
-
-        let mut temp = vec![];
-        for binding_request in pending_binding_requests.drain(..) {
-            if filter_time
-                .checked_duration_since(binding_request.timestamp)
-                .map(|duration| duration < MAX_BINDING_REQUEST_TIMEOUT)
-                .unwrap_or(true)
-            {
-                temp.push(binding_request);
-            }
-        }
-
-        *pending_binding_requests = temp;
-        let bind_requests_remaining = pending_binding_requests.len();
-        let bind_requests_removed = initial_size - bind_requests_remaining;
+        let bind_requests_removed = self
+            .pending_binding_requests
+            .remove_expired(filter_time, MAX_BINDING_REQUEST_TIMEOUT);
         if bind_requests_removed > 0 {
             trace!(
                 "[{}]: Discarded {} binding requests because they expired, still {} remaining",
                 self.get_name(),
                 bind_requests_removed,
-                bind_requests_remaining,
+                self.pending_binding_requests.len(),
             );
         }
     }
@@ -857,17 +1788,23 @@ impl Agent {
     pub(crate) fn handle_inbound_binding_success(
         &mut self,
         id: TransactionId,
+        now: Instant,
     ) -> Option<BindingRequest> {
-        self.invalidate_pending_binding_requests(Instant::now());
+        self.invalidate_pending_binding_requests(now);
 
-        let pending_binding_requests = &mut self.pending_binding_requests;
-        for i in 0..pending_binding_requests.len() {
-            if pending_binding_requests[i].transaction_id == id {
-                let valid_binding_request = pending_binding_requests.remove(i);
-                return Some(valid_binding_request);
+        self.pending_binding_requests.remove(&id)
+    }
+
+    /// Records the transaction ID of an inbound Binding Request within the replay
+    /// protection window, so that the response to it can be cached.
+    fn record_transaction_id(&mut self, transaction_id: TransactionId) {
+        self.recent_responses.insert(transaction_id, None);
+        self.recent_transaction_ids.push_back(transaction_id);
+        if self.recent_transaction_ids.len() > STUN_REPLAY_PROTECTION_WINDOW {
+            if let Some(oldest) = self.recent_transaction_ids.pop_front() {
+                self.recent_responses.remove(&oldest);
             }
         }
-        None
     }
 
     /// Processes STUN traffic from a remote candidate.
@@ -876,9 +1813,16 @@ impl Agent {
         m: &mut Message,
         local_index: usize,
         remote_addr: SocketAddr,
+        now: Instant,
     ) -> Result<()> {
+        self.local_candidates[local_index].record_packet(false, m.raw.len(), now);
+        if let Some(remote_index) = self.find_remote_candidate(remote_addr) {
+            self.remote_candidates[remote_index].record_packet(false, m.raw.len(), now);
+        }
+
         if m.typ.method != METHOD_BINDING
             || !(m.typ.class == CLASS_SUCCESS_RESPONSE
+                || m.typ.class == CLASS_ERROR_RESPONSE
                 || m.typ.class == CLASS_REQUEST
                 || m.typ.class == CLASS_INDICATION)
         {
@@ -893,26 +1837,18 @@ impl Agent {
             return Err(Error::ErrUnhandledStunpacket);
         }
 
-        if self.is_controlling {
-            if m.contains(ATTR_ICE_CONTROLLING) {
+        // A tie-breaker equal to ours cannot resolve a role conflict, which usually
+        // means our own request was looped back to us.
+        for attr in [ATTR_ICE_CONTROLLING, ATTR_ICE_CONTROLLED] {
+            let mut tie_breaker = TieBreaker::default();
+            if tie_breaker.get_from_as(m, attr).is_ok() && tie_breaker.0 == self.tie_breaker {
                 debug!(
-                    "[{}]: inbound isControlling && a.isControlling == true",
+                    "[{}]: inbound {} carries our own tie-breaker",
                     self.get_name(),
+                    attr
                 );
-                return Err(Error::ErrUnexpectedStunrequestMessage);
-            } else if m.contains(ATTR_USE_CANDIDATE) {
-                debug!(
-                    "[{}]: useCandidate && a.isControlling == true",
-                    self.get_name(),
-                );
-                return Err(Error::ErrUnexpectedStunrequestMessage);
+                return Err(Error::ErrSameICEControllingStatus);
             }
-        } else if m.contains(ATTR_ICE_CONTROLLED) {
-            debug!(
-                "[{}]: inbound isControlled && a.isControlling == false",
-                self.get_name(),
-            );
-            return Err(Error::ErrUnexpectedStunrequestMessage);
         }
 
         let Some(remote_credentials) = &self.ufrag_pwd.remote_credentials else {
@@ -924,9 +1860,12 @@ impl Agent {
         };
 
         let mut remote_candidate_index = self.find_remote_candidate(remote_addr);
-        if m.typ.class == CLASS_SUCCESS_RESPONSE {
-            if let Err(err) = assert_inbound_message_integrity(m, remote_credentials.pwd.as_bytes())
-            {
+        if m.typ.class == CLASS_SUCCESS_RESPONSE || m.typ.class == CLASS_ERROR_RESPONSE {
+            if let Err(err) = assert_inbound_message_integrity(
+                m,
+                remote_credentials.pwd.as_bytes(),
+                &mut self.ufrag_pwd.remote_integrity_cache,
+            ) {
                 warn!(
                     "[{}]: discard message from ({}), {}",
                     self.get_name(),
@@ -936,8 +1875,10 @@ impl Agent {
                 return Err(err);
             }
 
-            if let Some(remote_index) = &remote_candidate_index {
-                self.handle_success_response(m, local_index, *remote_index, remote_addr);
+            if m.typ.class == CLASS_ERROR_RESPONSE {
+                return self.handle_error_response(m, local_index, remote_candidate_index, now);
+            } else if let Some(remote_index) = &remote_candidate_index {
+                self.handle_success_response(m, local_index, *remote_index, remote_addr, now);
             } else {
                 warn!(
                     "[{}]: discard success message from ({}), no such remote",
@@ -962,6 +1903,7 @@ impl Agent {
                 } else if let Err(err) = assert_inbound_message_integrity(
                     m,
                     self.ufrag_pwd.local_credentials.pwd.as_bytes(),
+                    &mut self.ufrag_pwd.local_integrity_cache,
                 ) {
                     warn!(
                         "[{}]: discard message from ({}), {}",
@@ -973,6 +1915,29 @@ impl Agent {
                 }
             }
 
+            if self.stun_replay_protection {
+                if let Some(response) = self.recent_responses.get(&m.transaction_id) {
+                    // a retransmission is answered with the same response (RFC 5389
+                    // section 7.3.1), without processing the request again
+                    let Some((out, local_index, remote_index)) = response.clone() else {
+                        warn!(
+                            "[{}]: discard replayed message from ({})",
+                            self.get_name(),
+                            remote_addr
+                        );
+                        return Err(Error::ErrStunReplayedTransaction);
+                    };
+                    debug!(
+                        "[{}]: answer retransmitted request from ({}) with the cached response",
+                        self.get_name(),
+                        remote_addr
+                    );
+                    self.send_stun(&out, local_index, remote_index, now);
+                    return Ok(());
+                }
+                self.record_transaction_id(m.transaction_id);
+            }
+
             if remote_candidate_index.is_none() {
                 let (ip, port, network_type) =
                     (remote_addr.ip(), remote_addr.port(), NetworkType::Udp4);
@@ -1019,48 +1984,247 @@ impl Agent {
             );
 
             if let Some(remote_index) = &remote_candidate_index {
-                self.handle_binding_request(m, local_index, *remote_index);
+                if !self.resolve_role_conflict(m, local_index, *remote_index, now) {
+                    return Ok(());
+                }
+                if self.is_controlling && m.contains(ATTR_USE_CANDIDATE) {
+                    debug!(
+                        "[{}]: useCandidate && a.isControlling == true",
+                        self.get_name(),
+                    );
+                    return Err(Error::ErrUnexpectedStunrequestMessage);
+                }
+
+                self.handle_binding_request(m, local_index, *remote_index, now);
+                if let Some(pair_index) = self.find_pair(local_index, *remote_index) {
+                    self.candidate_pairs[pair_index].requests_received += 1;
+                }
             }
         }
 
         if let Some(remote_index) = remote_candidate_index {
-            self.remote_candidates[remote_index].seen(false);
+            self.remote_candidates[remote_index].seen(false, now);
+        }
+
+        Ok(())
+    }
+
+    /// Resolves a role conflict signalled by an inbound Binding Request carrying our own role
+    /// (RFC 8445 section 7.3.1.1). Either switches our role, or answers the request with a 487
+    /// (Role Conflict) error response so that the remote agent switches. Returns false if the
+    /// request was answered with the error response and must not be processed further.
+    fn resolve_role_conflict(
+        &mut self,
+        m: &Message,
+        local_index: usize,
+        remote_index: usize,
+        now: Instant,
+    ) -> bool {
+        let attr = if self.is_controlling {
+            ATTR_ICE_CONTROLLING
+        } else {
+            ATTR_ICE_CONTROLLED
+        };
+        let mut tie_breaker = TieBreaker::default();
+        if tie_breaker.get_from_as(m, attr).is_err() {
+            return true;
+        }
+
+        // the agent with the larger tie-breaker is controlling
+        let keep_role = if self.is_controlling {
+            self.tie_breaker >= tie_breaker.0
+        } else {
+            self.tie_breaker < tie_breaker.0
+        };
+        if keep_role {
+            debug!(
+                "[{}]: role conflict with {}, answering 487",
+                self.get_name(),
+                self.remote_candidates[remote_index]
+            );
+            self.send_role_conflict(m, local_index, remote_index, now);
+            false
+        } else {
+            self.switch_role();
+            true
+        }
+    }
+
+    fn send_role_conflict(
+        &mut self,
+        m: &Message,
+        local_index: usize,
+        remote_index: usize,
+        now: Instant,
+    ) {
+        let local_pwd = self.ufrag_pwd.local_credentials.pwd.clone();
+
+        let mut out = Message::new();
+        if let Err(err) = out.build(&[
+            Box::new(m.clone()),
+            Box::new(BINDING_ERROR),
+            Box::new(CODE_ROLE_CONFLICT),
+            Box::new(MessageIntegrity::new_short_term_integrity(local_pwd)),
+            Box::new(FINGERPRINT),
+        ]) {
+            warn!(
+                "[{}]: Failed to build role conflict response from: {} to: {} error: {}",
+                self.get_name(),
+                local_index,
+                remote_index,
+                err
+            );
+        } else {
+            self.send_stun(&out, local_index, remote_index, now);
+        }
+    }
+
+    /// Handles an error response to one of our Binding Requests. A 487 (Role Conflict) error
+    /// switches our role, unless it was already switched since the request was sent, and
+    /// retries the check.
+    fn handle_error_response(
+        &mut self,
+        m: &Message,
+        local_index: usize,
+        remote_index: Option<usize>,
+        now: Instant,
+    ) -> Result<()> {
+        let mut error_code = ErrorCodeAttribute::default();
+        error_code.get_from(m)?;
+        if error_code.code != CODE_ROLE_CONFLICT {
+            debug!(
+                "[{}]: inbound Binding error response {}",
+                self.get_name(),
+                error_code.code.0
+            );
+            return Err(Error::ErrUnhandledStunpacket);
+        }
+
+        let Some(request) = self.handle_inbound_binding_success(m.transaction_id, now) else {
+            warn!(
+                "[{}]: discard role conflict response, unknown TransactionID 0x{:?}",
+                self.get_name(),
+                m.transaction_id
+            );
+            return Err(Error::ErrUnhandledStunpacket);
+        };
+        if request.is_controlling == self.is_controlling {
+            self.switch_role();
+        }
+        if let Some(remote_index) = remote_index {
+            self.ping_candidate(local_index, remote_index, now);
         }
 
         Ok(())
     }
 
+    /// Switches between the controlling and controlled roles to resolve a role conflict.
+    pub(crate) fn switch_role(&mut self) {
+        self.set_role(!self.is_controlling);
+
+        info!(
+            "[{}]: switched role to resolve a role conflict",
+            self.get_name()
+        );
+    }
+
+    /// Sets the role of the agent, which also changes the priority of every candidate pair.
+    pub(crate) fn set_role(&mut self, is_controlling: bool) {
+        self.is_controlling = is_controlling;
+        for p in &mut self.candidate_pairs {
+            p.ice_role_controlling = is_controlling;
+        }
+        self.start();
+    }
+
     // Processes non STUN traffic from a remote candidate, and returns true if it is an actual
-    // remote candidate.
-    pub(crate) fn validate_non_stun_traffic(&mut self, remote_addr: SocketAddr) -> bool {
-        self.find_remote_candidate(remote_addr)
-            .map_or(false, |remote_index| {
-                self.remote_candidates[remote_index].seen(false);
-                true
-            })
+    // remote candidate. The `len` bytes received on the local candidate at local_index are
+    // accounted on both candidates.
+    pub(crate) fn validate_non_stun_traffic(
+        &mut self,
+        local_index: usize,
+        remote_addr: SocketAddr,
+        len: usize,
+        now: Instant,
+    ) -> bool {
+        if let Some(remote_index) = self.find_remote_candidate(remote_addr) {
+            self.remote_candidates[remote_index].seen(false, now);
+            self.remote_candidates[remote_index].record_packet(false, len, now);
+            self.local_candidates[local_index].record_packet(false, len, now);
+            true
+        } else {
+            // traffic on the pair selected before a graceful restart is still valid
+            self.old_selected_pair
+                .as_ref()
+                .is_some_and(|(_, remote)| remote.addr() == remote_addr)
+        }
     }
 
-    pub(crate) fn send_stun(&mut self, msg: &Message, local_index: usize, remote_index: usize) {
+    pub(crate) fn send_stun(
+        &mut self,
+        msg: &Message,
+        local_index: usize,
+        remote_index: usize,
+        now: Instant,
+    ) {
         let peer_addr = self.remote_candidates[remote_index].addr();
         let local_addr = self.local_candidates[local_index].addr();
+
+        if self.local_candidates[local_index].candidate_type() == CandidateType::Relay {
+            // relayed through the TURN server of the local relay candidate
+            if let Err(err) = self.send_relayed(local_addr, &msg.raw, peer_addr) {
+                debug!(
+                    "[{}]: Failed to relay STUN from {} to {}: {}",
+                    self.get_name(),
+                    local_addr,
+                    peer_addr,
+                    err
+                );
+                return;
+            }
+        } else {
+            self.send_stun_direct(msg, local_index, peer_addr, now);
+        }
+
+        self.local_candidates[local_index].seen(true, now);
+        self.local_candidates[local_index].record_packet(true, msg.raw.len(), now);
+        self.remote_candidates[remote_index].record_packet(true, msg.raw.len(), now);
+    }
+
+    fn send_stun_direct(
+        &mut self,
+        msg: &Message,
+        local_index: usize,
+        peer_addr: SocketAddr,
+        now: Instant,
+    ) {
+        let local_addr = self.local_candidates[local_index].addr();
         let protocol = if self.local_candidates[local_index].network_type().is_tcp() {
             Protocol::TCP
         } else {
             Protocol::UDP
         };
 
+        let message = if protocol == Protocol::TCP {
+            // RFC 4571 framing
+            let mut message = BytesMut::with_capacity(2 + msg.raw.len());
+            message.put_u16(msg.raw.len() as u16);
+            message.extend_from_slice(&msg.raw);
+            message
+        } else {
+            BytesMut::from(&msg.raw[..])
+        };
+
         self.transmits.push_back(Transmit {
-            now: Instant::now(),
+            now,
             transport: TransportContext {
                 local_addr,
                 peer_addr,
                 ecn: None,
                 protocol,
             },
-            message: BytesMut::from(&msg.raw[..]),
+            message,
         });
-
-        self.local_candidates[local_index].seen(true);
     }
 
     fn handle_inbound_candidate_msg(
@@ -1069,6 +2233,7 @@ impl Agent {
         buf: &[u8],
         remote_addr: SocketAddr,
         local_addr: SocketAddr,
+        now: Instant,
     ) -> Result<()> {
         if stun::message::is_message(buf) {
             let mut m = Message {
@@ -1087,11 +2252,13 @@ impl Agent {
                     err
                 );
                 Err(err)
+            } else if self.gather_requests.contains_key(&m.transaction_id) {
+                self.handle_gather_response(&m, remote_addr)
             } else {
-                self.handle_inbound(&mut m, local_index, remote_addr)
+                self.handle_inbound(&mut m, local_index, remote_addr, now)
             }
         } else {
-            if !self.validate_non_stun_traffic(remote_addr) {
+            if !self.validate_non_stun_traffic(local_index, remote_addr, buf.len(), now) {
                 warn!(
                     "[{}]: Discarded message, not a valid remote candidate from {}",
                     self.get_name(),
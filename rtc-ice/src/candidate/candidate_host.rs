@@ -1,5 +1,6 @@
 use super::*;
 use crate::rand::generate_cand_id;
+use std::net::Ipv4Addr;
 
 /// The config required to create a new `CandidateHost`.
 #[derive(Default)]
@@ -17,11 +18,18 @@ impl CandidateHostConfig {
             candidate_id = generate_cand_id();
         }
 
-        let ip: IpAddr = match self.base_config.address.parse() {
-            Ok(ip) => ip,
-            Err(_) => return Err(Error::ErrAddressParseFailed),
+        // an mDNS name stays unresolved until set_remote_candidate_address is called
+        let ip: IpAddr = if self.base_config.address.ends_with(".local") {
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+        } else {
+            match self.base_config.address.parse() {
+                Ok(ip) => ip,
+                Err(_) => return Err(Error::ErrAddressParseFailed),
+            }
         };
         let network_type = determine_network_type(&self.base_config.network, &ip)?;
+        let local_preference =
+            local_preference_for(&ip, network_type, self.base_config.interface_index);
 
         Ok(Candidate {
             id: candidate_id,
@@ -35,6 +43,7 @@ impl CandidateHostConfig {
             priority_override: self.base_config.priority,
             network: self.base_config.network,
             tcp_type: self.tcp_type,
+            local_preference,
             ..Candidate::default()
         })
     }
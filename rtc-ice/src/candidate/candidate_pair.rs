@@ -1,5 +1,8 @@
+use super::Candidate;
+use crate::agent::agent_config::CONSENT_TIMEOUT;
 use serde::Serialize;
 use std::fmt;
+use std::time::{Duration, Instant};
 
 /// Represent the ICE candidate pair state.
 #[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Serialize)]
@@ -24,6 +27,11 @@ pub enum CandidatePairState {
     /// Means a check for this pair was already done and produced a successful result.
     #[serde(rename = "succeeded")]
     Succeeded = 4,
+
+    /// Means a check for this pair waits for the check of another pair with the same foundation
+    /// to complete.
+    #[serde(rename = "frozen")]
+    Frozen = 5,
 }
 
 impl From<u8> for CandidatePairState {
@@ -33,6 +41,7 @@ impl From<u8> for CandidatePairState {
             2 => Self::InProgress,
             3 => Self::Failed,
             4 => Self::Succeeded,
+            5 => Self::Frozen,
             _ => Self::Unspecified,
         }
     }
@@ -45,6 +54,7 @@ impl fmt::Display for CandidatePairState {
             Self::InProgress => "in-progress",
             Self::Failed => "failed",
             Self::Succeeded => "succeeded",
+            Self::Frozen => "frozen",
             Self::Unspecified => "unspecified",
         };
 
@@ -61,8 +71,30 @@ pub struct CandidatePair {
     pub remote_priority: u32,
     pub(crate) ice_role_controlling: bool,
     pub(crate) binding_request_count: u16,
+    // retransmission timeout of the connectivity check in flight, and when it expires
+    pub(crate) rto: Duration,
+    pub(crate) next_retransmit: Option<Instant>,
     pub(crate) state: CandidatePairState,
     pub(crate) nominated: bool,
+
+    pub(crate) requests_sent: u64,
+    pub(crate) requests_received: u64,
+    pub(crate) responses_sent: u64,
+    pub(crate) responses_received: u64,
+    pub(crate) first_request_time: Option<Instant>,
+    pub(crate) last_request_time: Option<Instant>,
+    pub(crate) last_response_time: Option<Instant>,
+    pub(crate) current_round_trip_time: f64,
+    pub(crate) total_round_trip_time: f64,
+    pub(crate) consent_requests_sent: u64,
+    pub(crate) consent_expiry: Option<Instant>,
+
+    pub(crate) packets_sent: u32,
+    pub(crate) packets_received: u32,
+    pub(crate) bytes_sent: u64,
+    pub(crate) bytes_received: u64,
+    pub(crate) last_packet_sent_time: Option<Instant>,
+    pub(crate) last_packet_received_time: Option<Instant>,
 }
 
 impl fmt::Debug for CandidatePair {
@@ -116,7 +148,75 @@ impl CandidatePair {
             ice_role_controlling,
             state: CandidatePairState::Waiting,
             binding_request_count: 0,
+            rto: Duration::ZERO,
+            next_retransmit: None,
             nominated: false,
+            requests_sent: 0,
+            requests_received: 0,
+            responses_sent: 0,
+            responses_received: 0,
+            first_request_time: None,
+            last_request_time: None,
+            last_response_time: None,
+            current_round_trip_time: 0.0,
+            total_round_trip_time: 0.0,
+            consent_requests_sent: 0,
+            consent_expiry: None,
+            packets_sent: 0,
+            packets_received: 0,
+            bytes_sent: 0,
+            bytes_received: 0,
+            last_packet_sent_time: None,
+            last_packet_received_time: None,
+        }
+    }
+
+    /// Returns the foundation of this pair (RFC 8445 section 6.1.2.6), the foundations of its
+    /// local and remote candidates.
+    pub fn foundation(
+        &self,
+        local_candidates: &[Candidate],
+        remote_candidates: &[Candidate],
+    ) -> (String, String) {
+        (
+            local_candidates[self.local_index].foundation(),
+            remote_candidates[self.remote_index].foundation(),
+        )
+    }
+
+    /// Returns whether the nominated flag is set on this pair.
+    pub fn nominated(&self) -> bool {
+        self.nominated
+    }
+
+    /// Records a connectivity check request sent on this pair at `now`.
+    pub(crate) fn record_request_sent(&mut self, now: Instant) {
+        self.requests_sent += 1;
+        self.first_request_time.get_or_insert(now);
+        self.last_request_time = Some(now);
+    }
+
+    /// Records a connectivity check response received at `now` for a request sent at `sent`,
+    /// updating the round trip time and refreshing consent.
+    pub(crate) fn record_response_received(&mut self, sent: Instant, now: Instant) {
+        let rtt = now.saturating_duration_since(sent).as_secs_f64();
+        self.responses_received += 1;
+        self.last_response_time = Some(now);
+        self.current_round_trip_time = rtt;
+        self.total_round_trip_time += rtt;
+        self.consent_expiry = Some(now + CONSENT_TIMEOUT);
+    }
+
+    /// Records a non-STUN packet of `len` bytes sent (outbound) or received on this pair.
+    pub(crate) fn record_packet(&mut self, outbound: bool, len: usize, now: Instant) {
+        if outbound {
+            self.packets_sent += 1;
+            self.bytes_sent += len as u64;
+            self.last_packet_sent_time = Some(now);
+        } else {
+            self.packets_received += 1;
+            self.bytes_received += len as u64;
+            self.last_packet_received_time = Some(now);
         }
     }
 
@@ -199,3 +199,29 @@ fn test_candidate_pair_equality() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_candidate_pair_foundation() -> Result<()> {
+    let with_foundation = |foundation: &str| -> Result<Candidate> {
+        let mut c = host_candidate()?;
+        c.foundation_override = foundation.to_owned();
+        Ok(c)
+    };
+    let local_candidates = vec![with_foundation("1")?, with_foundation("12")?];
+    let remote_candidates = vec![with_foundation("23")?, with_foundation("3")?];
+
+    let pair_a = CandidatePair::new(0, 0, 0, 0, true);
+    let pair_b = CandidatePair::new(1, 1, 0, 0, true);
+
+    // "1" + "23" and "12" + "3" are different foundations
+    assert_eq!(
+        pair_a.foundation(&local_candidates, &remote_candidates),
+        ("1".to_owned(), "23".to_owned())
+    );
+    assert_ne!(
+        pair_a.foundation(&local_candidates, &remote_candidates),
+        pair_b.foundation(&local_candidates, &remote_candidates)
+    );
+
+    Ok(())
+}
@@ -25,6 +25,8 @@ impl CandidatePeerReflexiveConfig {
             Err(_) => return Err(Error::ErrAddressParseFailed),
         };
         let network_type = determine_network_type(&self.base_config.network, &ip)?;
+        let local_preference =
+            local_preference_for(&ip, network_type, self.base_config.interface_index);
 
         Ok(Candidate {
             id: candidate_id,
@@ -36,6 +38,7 @@ impl CandidatePeerReflexiveConfig {
             component: self.base_config.component,
             foundation_override: self.base_config.foundation,
             priority_override: self.base_config.priority,
+            local_preference,
             related_address: Some(CandidateRelatedAddress {
                 address: self.rel_addr,
                 port: self.rel_port,
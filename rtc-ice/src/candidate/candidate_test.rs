@@ -1,4 +1,5 @@
 use super::*;
+use crate::candidate::candidate_host::CandidateHostConfig;
 use crate::candidate::candidate_pair::CandidatePairState;
 use crate::candidate::{unmarshal_candidate, Candidate};
 use std::time::Instant;
@@ -406,3 +407,37 @@ fn test_candidate_marshal() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_candidate_local_preference_multi_homed() -> Result<()> {
+    let host = |address: &str, interface_index: u32| {
+        CandidateHostConfig {
+            base_config: CandidateConfig {
+                network: "udp".to_owned(),
+                address: address.to_owned(),
+                component: COMPONENT_RTP,
+                interface_index: Some(interface_index),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+        .new_candidate_host()
+    };
+
+    let loopback = host("127.0.0.1", 0)?;
+    let ipv4 = host("192.168.1.10", 1)?;
+    let ipv4_second = host("10.0.0.10", 2)?;
+    let ipv6 = host("fe80::1", 3)?;
+
+    assert!(ipv4.priority() > loopback.priority());
+    assert!(ipv4.priority() > ipv4_second.priority());
+    assert!(ipv6.priority() > ipv4.priority());
+
+    assert!(
+        compute_local_preference(NetworkType::Udp6, u32::MAX)
+            > compute_local_preference(NetworkType::Udp4, 0)
+    );
+    assert!(compute_local_preference(NetworkType::Udp6, 0) <= u32::from(u16::MAX));
+
+    Ok(())
+}
@@ -91,6 +91,37 @@ impl CandidateType {
     }
 }
 
+/// Computes the local preference of a candidate gathered on the given interface.
+///
+/// RFC 8445 5.1.2.2.  Guidelines for Choosing Local Preference
+/// If a host is multihomed, the local preference of each candidate of the
+/// same type MUST be unique. IPv6 interfaces are ranked above IPv4 ones and,
+/// within an address family, interfaces with a lower index are preferred.
+/// Loopback candidates are ranked below all of them, see `LOOPBACK_LOCAL_PREFERENCE`.
+pub fn compute_local_preference(network_type: NetworkType, interface_index: u32) -> u32 {
+    let ip_precedence: u32 = if network_type.is_ipv6() { 1 } else { 0 };
+    let interface_preference = 0x7FFF - interface_index.min(0x7FFE);
+
+    (ip_precedence << 15) | interface_preference
+}
+
+/// Local preference of candidates on a loopback address, lower than any
+/// value returned by `compute_local_preference`.
+pub(crate) const LOOPBACK_LOCAL_PREFERENCE: u16 = 0;
+
+pub(crate) fn local_preference_for(
+    ip: &IpAddr,
+    network_type: NetworkType,
+    interface_index: Option<u32>,
+) -> u16 {
+    match interface_index {
+        // When there is only a single IP address, this value SHOULD be set to 65535.
+        None => DEFAULT_LOCAL_PREFERENCE,
+        Some(_) if ip.is_loopback() => LOOPBACK_LOCAL_PREFERENCE,
+        Some(interface_index) => compute_local_preference(network_type, interface_index) as u16,
+    }
+}
+
 pub(crate) fn contains_candidate_type(
     candidate_type: CandidateType,
     candidate_type_list: &[CandidateType],
@@ -129,6 +160,10 @@ pub struct CandidateConfig {
     pub component: u16,
     pub priority: u32,
     pub foundation: String,
+    /// Index of the network interface the candidate was gathered on, used to
+    /// rank the candidates of multi-homed hosts. None if the host has a single
+    /// IP address.
+    pub interface_index: Option<u32>,
 }
 
 #[derive(Clone)]
@@ -148,8 +183,18 @@ pub struct Candidate {
     pub(crate) last_sent: Instant,
     pub(crate) last_received: Instant,
 
+    pub(crate) packets_sent: u64,
+    pub(crate) packets_received: u64,
+    pub(crate) bytes_sent: u64,
+    pub(crate) bytes_received: u64,
+    pub(crate) last_packet_sent_time: Option<Instant>,
+    pub(crate) last_packet_received_time: Option<Instant>,
+
     pub(crate) foundation_override: String,
     pub(crate) priority_override: u32,
+    pub(crate) local_preference: u16,
+    // priority decided by the candidate priority policy of the agent
+    pub(crate) policy_priority: Option<u32>,
 
     pub(crate) network: String,
 }
@@ -172,8 +217,17 @@ impl Default for Candidate {
             last_sent: Instant::now(),
             last_received: Instant::now(),
 
+            packets_sent: 0,
+            packets_received: 0,
+            bytes_sent: 0,
+            bytes_received: 0,
+            last_packet_sent_time: None,
+            last_packet_received_time: None,
+
             foundation_override: String::new(),
             priority_override: 0,
+            local_preference: DEFAULT_LOCAL_PREFERENCE,
+            policy_priority: None,
             network: String::new(),
         }
     }
@@ -263,6 +317,9 @@ impl Candidate {
 
     /// Computes the priority for this ICE Candidate.
     pub fn priority(&self) -> u32 {
+        if let Some(priority) = self.policy_priority {
+            return priority;
+        }
         if self.priority_override != 0 {
             return self.priority_override;
         }
@@ -324,9 +381,7 @@ impl Candidate {
         self.resolved_addr
     }
 
-    pub fn seen(&mut self, outbound: bool) {
-        let now = Instant::now();
-
+    pub fn seen(&mut self, outbound: bool, now: Instant) {
         if outbound {
             self.set_last_sent(now);
         } else {
@@ -334,6 +389,19 @@ impl Candidate {
         }
     }
 
+    /// Records a packet of `len` bytes sent (outbound) or received on this candidate.
+    pub(crate) fn record_packet(&mut self, outbound: bool, len: usize, now: Instant) {
+        if outbound {
+            self.packets_sent += 1;
+            self.bytes_sent += len as u64;
+            self.last_packet_sent_time = Some(now);
+        } else {
+            self.packets_received += 1;
+            self.bytes_received += len as u64;
+            self.last_packet_received_time = Some(now);
+        }
+    }
+
     /// Used to compare two candidateBases.
     pub fn equal(&self, other: &Candidate) -> bool {
         self.network_type() == other.network_type()
@@ -414,7 +482,7 @@ impl Candidate {
 
             (1 << 13) * direction_pref + other_pref
         } else {
-            DEFAULT_LOCAL_PREFERENCE
+            self.local_preference
         }
     }
 }
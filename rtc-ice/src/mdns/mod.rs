@@ -0,0 +1,14 @@
+/// Represents the different Multicast modes that ICE can run.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub enum MulticastDnsMode {
+    /// Means remote mDNS candidates will be rejected, and local host candidates will use IPs.
+    Disabled,
+
+    /// Means remote mDNS candidates will be accepted and held until the owning layer resolves
+    /// them, and local host candidates will use IPs.
+    #[default]
+    QueryOnly,
+
+    /// Means remote mDNS candidates will be accepted, and local host candidates will use mDNS.
+    QueryAndGather,
+}
@@ -35,6 +35,21 @@ pub fn generate_cand_id() -> String {
     )
 }
 
+/// Generates a random mDNS host name to announce instead of a host candidate's IP address.
+/// <https://datatracker.ietf.org/doc/html/draft-ietf-mmusic-mdns-ice-candidates#section-3.1.1>
+pub fn generate_mdns_host_name() -> String {
+    let mut rng = thread_rng();
+    // UUID version 4
+    format!(
+        "{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}.local",
+        rng.gen::<u32>(),
+        rng.gen::<u16>(),
+        rng.gen::<u16>() & 0x0fff,
+        (rng.gen::<u16>() & 0x3fff) | 0x8000,
+        rng.gen::<u64>() & 0xffff_ffff_ffff,
+    )
+}
+
 /// Generates ICE pwd.
 /// This internally uses `generate_crypto_random_string`.
 pub fn generate_pwd() -> String {
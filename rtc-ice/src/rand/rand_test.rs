@@ -57,3 +57,18 @@ fn test_random_generator_collision() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_generate_mdns_host_name() {
+    let name = generate_mdns_host_name();
+    let uuid = name
+        .strip_suffix(".local")
+        .expect("name should end with .local");
+    let groups: Vec<&str> = uuid.split('-').collect();
+    assert_eq!(
+        groups.iter().map(|g| g.len()).collect::<Vec<_>>(),
+        vec![8, 4, 4, 4, 12]
+    );
+    assert!(groups[2].starts_with('4'), "should be a version 4 UUID");
+    assert_ne!(name, generate_mdns_host_name());
+}
@@ -258,6 +258,31 @@ impl Url {
         return proto, nil
     }*/
 
+    /// Checks that the URL is a well-formed STUN or TURN URL: the scheme is known,
+    /// the host is non-empty, the port is non-zero and TURN URLs name a UDP or TCP
+    /// transport.
+    pub fn validate(&self) -> Result<()> {
+        if self.scheme == SchemeType::Unknown {
+            return Err(Error::ErrSchemeType);
+        }
+
+        if self.host.is_empty() {
+            return Err(Error::ErrHost);
+        }
+
+        if self.port == 0 {
+            return Err(Error::ErrPort);
+        }
+
+        if (self.scheme == SchemeType::Turn || self.scheme == SchemeType::Turns)
+            && self.proto == ProtoType::Unknown
+        {
+            return Err(Error::ErrProtoType);
+        }
+
+        Ok(())
+    }
+
     /// Returns whether the this URL's scheme describes secure scheme or not.
     #[must_use]
     pub fn is_secure(&self) -> bool {
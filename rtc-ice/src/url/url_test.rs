@@ -140,3 +140,48 @@ fn test_parse_url_failure() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_url_validate() -> Result<()> {
+    for raw_url in ["stun:google.de", "turns:google.de?transport=tcp"] {
+        Url::parse_url(raw_url)?.validate()?;
+    }
+
+    let valid = Url::parse_url("turn:google.de?transport=udp")?;
+    let tests = vec![
+        (
+            Url {
+                scheme: SchemeType::from("ftp"),
+                ..valid.clone()
+            },
+            Error::ErrSchemeType,
+        ),
+        (
+            Url {
+                host: String::new(),
+                ..valid.clone()
+            },
+            Error::ErrHost,
+        ),
+        (
+            Url {
+                port: 0,
+                ..valid.clone()
+            },
+            Error::ErrPort,
+        ),
+        (
+            Url {
+                proto: ProtoType::Unknown,
+                ..valid.clone()
+            },
+            Error::ErrProtoType,
+        ),
+    ];
+
+    for (url, expected_err) in tests {
+        assert_eq!(url.validate(), Err(expected_err), "testCase: '{url:?}'");
+    }
+
+    Ok(())
+}
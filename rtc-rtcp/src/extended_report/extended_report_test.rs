@@ -182,3 +182,43 @@ fn test_decode() -> Result<()> {
     assert_eq!(actual.to_string(), expected.to_string());
     Ok(())
 }
+
+#[test]
+fn test_rle_report_block_builder() -> Result<()> {
+    let mut builder = RLEReportBlockBuilder::new(0x12345689);
+    // 20 packets with alternating loss followed by 40 received packets
+    for i in 0..60u16 {
+        builder.add_packet(65530u16.wrapping_add(i), i >= 20 || i % 2 == 0);
+    }
+
+    let block = builder.build();
+    assert_eq!(block.begin_seq, 65530);
+    assert_eq!(block.end_seq, 54);
+    assert_eq!(
+        block.chunks,
+        vec![
+            Chunk(0b1101_0101_0101_0101),
+            Chunk(0b1010_1011_1111_1111),
+            Chunk(0x4000 | 30),
+            Chunk(0x0000),
+        ]
+    );
+    assert_eq!(block.chunks[0].chunk_type(), ChunkType::BitVector);
+    assert_eq!(block.chunks[2].chunk_type(), ChunkType::RunLength);
+    assert_eq!(block.chunks[2].run_type()?, 1);
+    assert_eq!(block.chunks[2].value(), 30);
+
+    let xr = ExtendedReport {
+        sender_ssrc: 0x01020304,
+        reports: vec![Box::new(block.clone())],
+    };
+    let mut encoded = xr.marshal()?;
+    let decoded = ExtendedReport::unmarshal(&mut encoded)?;
+    let actual = decoded.reports[0]
+        .as_any()
+        .downcast_ref::<LossRLEReportBlock>()
+        .expect("expected a loss RLE report block");
+    assert_eq!(actual, &block);
+
+    Ok(())
+}
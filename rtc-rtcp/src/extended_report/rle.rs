@@ -1,6 +1,8 @@
 use super::*;
 
 const RLE_REPORT_BLOCK_MIN_LENGTH: u16 = 8;
+const RUN_LENGTH_MAX: usize = 0x3FFF;
+const BIT_VECTOR_LENGTH: usize = 15;
 
 /// ChunkType enumerates the three kinds of chunks described in RFC 3611 section 4.1.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -244,3 +246,88 @@ impl Unmarshal for RLEReportBlock {
         })
     }
 }
+
+/// RLEReportBlockBuilder builds a LossRLEReportBlock from individual packet
+/// receptions. Each run of packets is encoded either as a Run Length Chunk or
+/// as a Bit Vector Chunk, whichever covers more packets.
+#[derive(Debug, Default, Clone)]
+pub struct RLEReportBlockBuilder {
+    ssrc: u32,
+    begin_seq: u16,
+    received: Vec<bool>,
+}
+
+impl RLEReportBlockBuilder {
+    pub fn new(ssrc: u32) -> Self {
+        RLEReportBlockBuilder {
+            ssrc,
+            ..Default::default()
+        }
+    }
+
+    /// add_packet records whether the packet with the given sequence number was
+    /// received. Sequence numbers skipped between two calls are reported as lost,
+    /// and packets older than the first recorded one are ignored.
+    pub fn add_packet(&mut self, seq: u16, received: bool) {
+        if self.received.is_empty() {
+            self.begin_seq = seq;
+        }
+
+        let offset = seq.wrapping_sub(self.begin_seq);
+        if offset >= 0x8000 {
+            return;
+        }
+
+        let offset = offset as usize;
+        if offset >= self.received.len() {
+            self.received.resize(offset + 1, false);
+        }
+        self.received[offset] |= received;
+    }
+
+    /// build encodes the recorded packets into a LossRLEReportBlock.
+    pub fn build(&self) -> LossRLEReportBlock {
+        let mut chunks = vec![];
+        let mut i = 0;
+        while i < self.received.len() {
+            let run_type = self.received[i];
+            let run_length = self.received[i..]
+                .iter()
+                .take(RUN_LENGTH_MAX)
+                .take_while(|&&r| r == run_type)
+                .count();
+
+            if run_length >= BIT_VECTOR_LENGTH {
+                chunks.push(Chunk(((run_type as u16) << 14) | run_length as u16));
+                i += run_length;
+            } else {
+                let mut bits = 0x8000u16;
+                for (j, &r) in self.received[i..]
+                    .iter()
+                    .take(BIT_VECTOR_LENGTH)
+                    .enumerate()
+                {
+                    if r {
+                        bits |= 1 << (BIT_VECTOR_LENGTH - 1 - j);
+                    }
+                }
+                chunks.push(Chunk(bits));
+                i += BIT_VECTOR_LENGTH;
+            }
+        }
+
+        // pad the block to a multiple of 32 bits
+        if chunks.len() % 2 != 0 {
+            chunks.push(Chunk(0));
+        }
+
+        LossRLEReportBlock {
+            is_loss_rle: true,
+            t: 0,
+            ssrc: self.ssrc,
+            begin_seq: self.begin_seq,
+            end_seq: self.begin_seq.wrapping_add(self.received.len() as u16),
+            chunks,
+        }
+    }
+}
@@ -33,6 +33,10 @@ pub const FORMAT_TLN: u8 = 1;
 /// Transport and Payload specific feedback messages overload the count field to act as a message type. those are listed here
 pub const FORMAT_RRR: u8 = 5;
 /// Transport and Payload specific feedback messages overload the count field to act as a message type. those are listed here
+pub const FORMAT_TMMBR: u8 = 3;
+/// Transport and Payload specific feedback messages overload the count field to act as a message type. those are listed here
+pub const FORMAT_TMMBN: u8 = 4;
+/// Transport and Payload specific feedback messages overload the count field to act as a message type. those are listed here
 pub const FORMAT_REMB: u8 = 15;
 /// Transport and Payload specific feedback messages overload the count field to act as a message type. those are listed here.
 /// https://tools.ietf.org/html/draft-holmer-rmcat-transport-wide-cc-extensions-01#page-5
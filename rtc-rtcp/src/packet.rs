@@ -5,6 +5,7 @@ use crate::{
     payload_feedbacks::slice_loss_indication::*, raw_packet::*, receiver_report::*,
     sender_report::*, source_description::*,
     transport_feedbacks::rapid_resynchronization_request::*,
+    transport_feedbacks::temporary_maximum_media_bitrate::*,
     transport_feedbacks::transport_layer_cc::*, transport_feedbacks::transport_layer_nack::*,
 };
 use shared::{
@@ -100,6 +101,7 @@ where
         PacketType::TransportSpecificFeedback => match h.count {
             FORMAT_TLN => Box::new(TransportLayerNack::unmarshal(&mut in_packet)?),
             FORMAT_RRR => Box::new(RapidResynchronizationRequest::unmarshal(&mut in_packet)?),
+            FORMAT_TMMBR | FORMAT_TMMBN => Box::new(TmmbPacket::unmarshal(&mut in_packet)?),
             FORMAT_TCC => Box::new(TransportLayerCc::unmarshal(&mut in_packet)?),
             _ => Box::new(RawPacket::unmarshal(&mut in_packet)?),
         },
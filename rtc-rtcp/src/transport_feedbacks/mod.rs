@@ -1,3 +1,4 @@
 pub mod rapid_resynchronization_request;
+pub mod temporary_maximum_media_bitrate;
 pub mod transport_layer_cc;
 pub mod transport_layer_nack;
@@ -0,0 +1,236 @@
+#[cfg(test)]
+mod temporary_maximum_media_bitrate_test;
+
+use crate::{header::*, packet::*, util::*};
+use shared::{
+    error::{Error, Result},
+    marshal::{Marshal, MarshalSize, Unmarshal},
+};
+
+use bytes::{Buf, BufMut};
+use std::any::Any;
+use std::fmt;
+
+const TMMB_HEADER_LENGTH: usize = SSRC_LENGTH * 2;
+const TMMB_ENTRY_LENGTH: usize = 8;
+const MANTISSA_BITS: u32 = 17;
+const MANTISSA_MAX: u64 = (1 << MANTISSA_BITS) - 1;
+const EXP_MAX: u32 = 63;
+const OVERHEAD_MAX: u16 = 0x1FF;
+
+/// TmmbEntry is a single FCI entry of a TMMBR or TMMBN message, as defined in
+/// RFC 5104, section 4.2.1.1 and 4.2.2.1
+///
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                              SSRC                             |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// | MxTBR Exp |  MxTBR Mantissa                 |Measured Overhead|
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+#[derive(Debug, PartialEq, Eq, Default, Clone, Copy)]
+pub struct TmmbEntry {
+    /// SSRC of the media sender the entry applies to
+    pub ssrc: u32,
+    /// Maximum total media bit rate in bits per second. It is encoded as a
+    /// 17-bit mantissa and 6-bit exponent, so large values lose precision.
+    pub max_total_media_bitrate: u64,
+    /// Per-packet overhead in bytes, up to 511
+    pub measured_overhead: u16,
+}
+
+impl TmmbEntry {
+    /// mantissa_exp returns the MxTBR mantissa and exponent closest to, but not
+    /// above, max_total_media_bitrate.
+    pub fn mantissa_exp(&self) -> (u32, u8) {
+        let mut mantissa = self.max_total_media_bitrate;
+        let mut exp = 0;
+        while mantissa > MANTISSA_MAX && exp < EXP_MAX {
+            mantissa >>= 1;
+            exp += 1;
+        }
+        (mantissa.min(MANTISSA_MAX) as u32, exp as u8)
+    }
+}
+
+/// TmmbPacket is a Temporary Maximum Media Stream Bit Rate Request (TMMBR)
+/// or Notification (TMMBN) message, as defined in RFC 5104, section 4.2
+#[derive(Debug, PartialEq, Eq, Default, Clone)]
+pub struct TmmbPacket {
+    //not included in marshal/unmarshal
+    pub is_notification: bool,
+
+    //marshal/unmarshal
+    /// SSRC of sender
+    pub sender_ssrc: u32,
+    /// SSRC of the media source, always zero
+    pub media_ssrc: u32,
+    pub entries: Vec<TmmbEntry>,
+}
+
+/// TmmbrPacket requests a media sender to limit its bitrate
+/// make sure to set is_notification = false
+pub type TmmbrPacket = TmmbPacket;
+
+/// TmmbnPacket notifies which TMMBR entries the media sender applies
+/// make sure to set is_notification = true
+pub type TmmbnPacket = TmmbPacket;
+
+impl fmt::Display for TmmbPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = if self.is_notification {
+            "TMMBN"
+        } else {
+            "TMMBR"
+        };
+        let mut out = format!("{} from {:x}\n", name, self.sender_ssrc);
+        for e in &self.entries {
+            out += format!(
+                "\tssrc {:x} bitrate {} overhead {}\n",
+                e.ssrc, e.max_total_media_bitrate, e.measured_overhead
+            )
+            .as_str();
+        }
+        write!(f, "{out}")
+    }
+}
+
+impl Packet for TmmbPacket {
+    /// Header returns the Header associated with this packet.
+    fn header(&self) -> Header {
+        Header {
+            padding: get_padding_size(self.raw_size()) != 0,
+            count: if self.is_notification {
+                FORMAT_TMMBN
+            } else {
+                FORMAT_TMMBR
+            },
+            packet_type: PacketType::TransportSpecificFeedback,
+            length: ((self.marshal_size() / 4) - 1) as u16,
+        }
+    }
+
+    /// destination_ssrc returns an array of SSRC values that this packet refers to.
+    fn destination_ssrc(&self) -> Vec<u32> {
+        self.entries.iter().map(|e| e.ssrc).collect()
+    }
+
+    fn raw_size(&self) -> usize {
+        HEADER_LENGTH + TMMB_HEADER_LENGTH + self.entries.len() * TMMB_ENTRY_LENGTH
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn equal(&self, other: &dyn Packet) -> bool {
+        other
+            .as_any()
+            .downcast_ref::<TmmbPacket>()
+            .is_some_and(|a| self == a)
+    }
+
+    fn cloned(&self) -> Box<dyn Packet> {
+        Box::new(self.clone())
+    }
+}
+
+impl MarshalSize for TmmbPacket {
+    fn marshal_size(&self) -> usize {
+        let l = self.raw_size();
+        // align to 32-bit boundary
+        l + get_padding_size(l)
+    }
+}
+
+impl Marshal for TmmbPacket {
+    /// Marshal encodes the TmmbPacket in binary
+    fn marshal_to(&self, mut buf: &mut [u8]) -> Result<usize> {
+        if buf.remaining_mut() < self.marshal_size() {
+            return Err(Error::BufferTooShort);
+        }
+
+        let h = self.header();
+        let n = h.marshal_to(buf)?;
+        buf = &mut buf[n..];
+
+        buf.put_u32(self.sender_ssrc);
+        buf.put_u32(self.media_ssrc);
+
+        for e in &self.entries {
+            let (mantissa, exp) = e.mantissa_exp();
+            let overhead = e.measured_overhead.min(OVERHEAD_MAX) as u32;
+            buf.put_u32(e.ssrc);
+            buf.put_u32((exp as u32) << 26 | mantissa << 9 | overhead);
+        }
+
+        if h.padding {
+            put_padding(buf, self.raw_size());
+        }
+
+        Ok(self.marshal_size())
+    }
+}
+
+impl Unmarshal for TmmbPacket {
+    /// Unmarshal decodes the TmmbPacket from binary
+    fn unmarshal<B>(raw_packet: &mut B) -> Result<Self>
+    where
+        Self: Sized,
+        B: Buf,
+    {
+        let raw_packet_len = raw_packet.remaining();
+        if raw_packet_len < (HEADER_LENGTH + TMMB_HEADER_LENGTH) {
+            return Err(Error::PacketTooShort);
+        }
+
+        let h = Header::unmarshal(raw_packet)?;
+
+        if h.packet_type != PacketType::TransportSpecificFeedback
+            || (h.count != FORMAT_TMMBR && h.count != FORMAT_TMMBN)
+        {
+            return Err(Error::WrongType);
+        }
+
+        let length = (h.length as usize + 1) * 4;
+        if raw_packet_len < length
+            || length < HEADER_LENGTH + TMMB_HEADER_LENGTH
+            || !(length - HEADER_LENGTH - TMMB_HEADER_LENGTH).is_multiple_of(TMMB_ENTRY_LENGTH)
+        {
+            return Err(Error::PacketTooShort);
+        }
+
+        let sender_ssrc = raw_packet.get_u32();
+        let media_ssrc = raw_packet.get_u32();
+
+        let mut entries = vec![];
+        for _ in 0..(length - HEADER_LENGTH - TMMB_HEADER_LENGTH) / TMMB_ENTRY_LENGTH {
+            let ssrc = raw_packet.get_u32();
+            let v = raw_packet.get_u32();
+            let exp = v >> 26;
+            let mantissa = (v >> 9) as u64 & MANTISSA_MAX;
+            // mantissa << exp must not shift bits out of the u64
+            if mantissa.leading_zeros() < exp {
+                return Err(Error::InvalidBitrate);
+            }
+            entries.push(TmmbEntry {
+                ssrc,
+                max_total_media_bitrate: mantissa << exp,
+                measured_overhead: (v & OVERHEAD_MAX as u32) as u16,
+            });
+        }
+
+        if
+        /*h.padding &&*/
+        raw_packet.has_remaining() {
+            raw_packet.advance(raw_packet.remaining());
+        }
+
+        Ok(TmmbPacket {
+            is_notification: h.count == FORMAT_TMMBN,
+            sender_ssrc,
+            media_ssrc,
+            entries,
+        })
+    }
+}
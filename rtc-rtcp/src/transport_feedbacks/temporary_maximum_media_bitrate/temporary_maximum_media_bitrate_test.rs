@@ -0,0 +1,123 @@
+use super::*;
+use bytes::Bytes;
+
+#[test]
+fn test_tmmbr_round_trip() -> Result<()> {
+    let tmmbr = TmmbrPacket {
+        is_notification: false,
+        sender_ssrc: 0x902f9e2e,
+        media_ssrc: 0,
+        entries: vec![TmmbEntry {
+            ssrc: 0x12345678,
+            max_total_media_bitrate: 500_000,
+            measured_overhead: 40,
+        }],
+    };
+
+    let (mantissa, exp) = tmmbr.entries[0].mantissa_exp();
+    assert_eq!((mantissa, exp), (125_000, 2));
+
+    let data = tmmbr.marshal()?;
+    assert_eq!(
+        data,
+        Bytes::from_static(&[
+            0x83, 0xcd, 0x00, 0x04, // v=2, fmt=3, RTPFB, len=4
+            0x90, 0x2f, 0x9e, 0x2e, // sender=0x902f9e2e
+            0x00, 0x00, 0x00, 0x00, // media=0
+            0x12, 0x34, 0x56, 0x78, // ssrc=0x12345678
+            0x0b, 0xd0, 0x90, 0x28, // exp=2, mantissa=125000, overhead=40
+        ])
+    );
+
+    let mut buf = data.clone();
+    let decoded = TmmbPacket::unmarshal(&mut buf)?;
+    assert_eq!(decoded, tmmbr);
+
+    let bitrate = decoded.entries[0].max_total_media_bitrate as f64;
+    assert!((bitrate - 500_000.0).abs() / 500_000.0 < 0.02);
+
+    let mut buf = data;
+    let packets = crate::packet::unmarshal(&mut buf)?;
+    assert!(packets[0].as_any().downcast_ref::<TmmbPacket>().is_some());
+
+    Ok(())
+}
+
+#[test]
+fn test_tmmbn_round_trip() -> Result<()> {
+    let tmmbn = TmmbnPacket {
+        is_notification: true,
+        sender_ssrc: 0x12345678,
+        media_ssrc: 0,
+        entries: vec![
+            TmmbEntry {
+                ssrc: 0x902f9e2e,
+                max_total_media_bitrate: 64_000,
+                measured_overhead: 0,
+            },
+            TmmbEntry {
+                ssrc: 0x4bc4fcb4,
+                // needs more than 17 bits of mantissa, so precision is lost
+                max_total_media_bitrate: 2_500_001,
+                measured_overhead: 511,
+            },
+        ],
+    };
+
+    let mut buf = tmmbn.marshal()?;
+    assert_eq!(buf[0] & 0x1f, FORMAT_TMMBN);
+    let decoded = TmmbPacket::unmarshal(&mut buf)?;
+    assert!(decoded.is_notification);
+    assert_eq!(decoded.entries[0], tmmbn.entries[0]);
+    assert_eq!(decoded.entries[1].measured_overhead, 511);
+    let bitrate = decoded.entries[1].max_total_media_bitrate;
+    assert!(bitrate <= 2_500_001 && bitrate > 2_500_001 - (1 << 5));
+
+    Ok(())
+}
+
+#[test]
+fn test_tmmb_unmarshal_errors() {
+    let tests = vec![
+        (
+            "short packet",
+            Bytes::from_static(&[0x83, 0xcd, 0x00, 0x02, 0x90, 0x2f, 0x9e, 0x2e]),
+            Error::PacketTooShort,
+        ),
+        (
+            "truncated entry",
+            Bytes::from_static(&[
+                0x83, 0xcd, 0x00, 0x04, //
+                0x90, 0x2f, 0x9e, 0x2e, //
+                0x00, 0x00, 0x00, 0x00, //
+                0x12, 0x34, 0x56, 0x78, //
+            ]),
+            Error::PacketTooShort,
+        ),
+        (
+            "wrong type",
+            Bytes::from_static(&[
+                0x85, 0xcd, 0x00, 0x02, //
+                0x90, 0x2f, 0x9e, 0x2e, //
+                0x00, 0x00, 0x00, 0x00, //
+            ]),
+            Error::WrongType,
+        ),
+        (
+            "bitrate overflow",
+            Bytes::from_static(&[
+                0x83, 0xcd, 0x00, 0x04, //
+                0x90, 0x2f, 0x9e, 0x2e, //
+                0x00, 0x00, 0x00, 0x00, //
+                0x12, 0x34, 0x56, 0x78, //
+                0xff, 0xff, 0xfe, 0x00, //
+            ]),
+            Error::InvalidBitrate,
+        ),
+    ];
+
+    for (name, mut data, want) in tests {
+        let got = TmmbPacket::unmarshal(&mut data);
+        assert_eq!(got, Err(want), "Unmarshal {name}");
+    }
+}
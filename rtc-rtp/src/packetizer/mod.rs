@@ -3,7 +3,7 @@ mod packetizer_test;
 
 use crate::{extension::abs_send_time_extension::*, header::*, packet::*, sequence::*};
 use shared::{
-    error::Result,
+    error::{Error, Result},
     marshal::{Marshal, MarshalSize},
 };
 
@@ -29,6 +29,11 @@ pub trait Packetizer: fmt::Debug {
     fn enable_abs_send_time(&mut self, value: u8);
     fn packetize(&mut self, payload: &Bytes, samples: u32) -> Result<Vec<Packet>>;
     fn skip_samples(&mut self, skipped_samples: u32);
+    /// clock_rate returns the RTP clock rate used to advance packet timestamps,
+    /// or 0 if the packetizer doesn't know it
+    fn clock_rate(&self) -> u32 {
+        0
+    }
     fn clone_to(&self) -> Box<dyn Packetizer>;
 }
 
@@ -157,7 +162,82 @@ impl Packetizer for PacketizerImpl {
         self.timestamp = self.timestamp.wrapping_add(skipped_samples);
     }
 
+    fn clock_rate(&self) -> u32 {
+        self.clock_rate
+    }
+
     fn clone_to(&self) -> Box<dyn Packetizer> {
         Box::new(self.clone())
     }
 }
+
+/// static_payload_type_clock_rate returns the clock rate of a statically assigned
+/// payload type, see <https://www.rfc-editor.org/rfc/rfc3551#section-6>
+pub fn static_payload_type_clock_rate(payload_type: u8) -> Option<u32> {
+    match payload_type {
+        // PCMU, GSM, G723, DVI4, LPC, PCMA, G722, QCELP, CN, G728, G729
+        0 | 3 | 4 | 5 | 7 | 8 | 9 | 12 | 13 | 15 | 18 => Some(8000),
+        // DVI4
+        6 => Some(16000),
+        16 => Some(11025),
+        17 => Some(22050),
+        // L16
+        10 | 11 => Some(44100),
+        // MPA
+        14 => Some(90000),
+        // CelB, JPEG, nv, H261, MPV, MP2T, H263
+        25 | 26 | 28 | 31 | 32 | 33 | 34 => Some(90000),
+        _ => None,
+    }
+}
+
+/// RtpPacketBuilder builds single RTP packets with a known clock rate
+#[derive(Debug, Default, Clone)]
+pub struct RtpPacketBuilder {
+    pub clock_rate: u32,
+    pub payload_type: u8,
+    pub ssrc: u32,
+    pub sequence: u16,
+    pub timestamp: u32,
+    /// clock rates accepted for dynamic payload types (96-127), any if empty
+    pub allowed_clock_rates: Vec<u32>,
+}
+
+impl RtpPacketBuilder {
+    /// validate_clock_rate checks the clock rate against the payload type.
+    /// Statically assigned payload types must use their RFC 3551 clock rate,
+    /// other payload types must use one of allowed_clock_rates if it is set.
+    pub fn validate_clock_rate(&self) -> Result<()> {
+        let valid = if let Some(clock_rate) = static_payload_type_clock_rate(self.payload_type) {
+            clock_rate == self.clock_rate
+        } else {
+            self.clock_rate != 0
+                && (self.allowed_clock_rates.is_empty()
+                    || self.allowed_clock_rates.contains(&self.clock_rate))
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::ClockRateMismatch(self.payload_type, self.clock_rate))
+        }
+    }
+
+    /// build creates a RTP packet carrying payload, failing if the clock rate
+    /// doesn't match the payload type
+    pub fn build(&self, payload: Bytes) -> Result<Packet> {
+        self.validate_clock_rate()?;
+
+        Ok(Packet {
+            header: Header {
+                version: 2,
+                payload_type: self.payload_type,
+                sequence_number: self.sequence,
+                timestamp: self.timestamp,
+                ssrc: self.ssrc,
+                ..Default::default()
+            },
+            payload,
+        })
+    }
+}
@@ -106,3 +106,56 @@ fn test_packetizer_timestamp_rollover_does_not_panic() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_rtp_packet_builder_clock_rate() -> Result<()> {
+    let mut builder = RtpPacketBuilder {
+        clock_rate: 8000,
+        payload_type: 0,
+        ssrc: 0x1234ABCD,
+        sequence: 1,
+        timestamp: 3000,
+        ..Default::default()
+    };
+    let packet = builder.build(Bytes::from_static(&[0x01, 0x02]))?;
+    assert_eq!(packet.header.version, 2);
+    assert_eq!(packet.header.payload_type, 0);
+    assert_eq!(packet.header.ssrc, 0x1234ABCD);
+    assert_eq!(packet.header.sequence_number, 1);
+    assert_eq!(packet.header.timestamp, 3000);
+    assert_eq!(packet.payload, Bytes::from_static(&[0x01, 0x02]));
+
+    // PCMU must use 8000 Hz
+    builder.clock_rate = 90000;
+    assert!(builder.validate_clock_rate().is_err());
+    assert_eq!(
+        builder.build(Bytes::from_static(&[0x01])),
+        Err(Error::ClockRateMismatch(0, 90000))
+    );
+
+    // VP8 on a dynamic payload type with an audio clock rate
+    let mut vp8 = RtpPacketBuilder {
+        clock_rate: 8000,
+        payload_type: 96,
+        allowed_clock_rates: vec![90000],
+        ..Default::default()
+    };
+    assert_eq!(
+        vp8.validate_clock_rate(),
+        Err(Error::ClockRateMismatch(96, 8000))
+    );
+    vp8.clock_rate = 90000;
+    vp8.validate_clock_rate()?;
+
+    // dynamic payload types accept any clock rate if none are configured
+    vp8.allowed_clock_rates.clear();
+    vp8.clock_rate = 48000;
+    vp8.validate_clock_rate()?;
+
+    let g722 = Box::new(g7xx::G722Payloader {});
+    let seq = Box::new(new_random_sequencer());
+    let packetizer = new_packetizer(100, 9, 0x1234ABCD, g722, seq, 8000);
+    assert_eq!(packetizer.clock_rate(), 8000);
+
+    Ok(())
+}
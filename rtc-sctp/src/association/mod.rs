@@ -1198,7 +1198,10 @@ impl Association {
         if stream_handle_data {
             if let Some(s) = self.streams.get_mut(&d.stream_identifier) {
                 self.events.push_back(Event::DatagramReceived);
-                if s.handle_data(d) && s.reassembly_queue.is_readable() {
+                let complete = s.handle_data(d);
+                if (complete || s.reassembly_queue.partial_delivery)
+                    && s.reassembly_queue.is_readable()
+                {
                     self.events.push_back(Event::Stream(StreamEvent::Readable {
                         id: s.stream_identifier,
                     }));
@@ -1552,7 +1555,23 @@ impl Association {
             self.reset_streams_if_any(p, true, reply)?;
             Ok(())
         } else if let Some(p) = raw.as_any().downcast_ref::<ParamReconfigResponse>() {
-            self.reconfigs.remove(&p.reconfig_response_sequence_number);
+            if let Some(c) = self.reconfigs.remove(&p.reconfig_response_sequence_number) {
+                // The peer has performed our outgoing reset request
+                if p.result == ReconfigResult::SuccessPerformed
+                    || p.result == ReconfigResult::SuccessNop
+                {
+                    if let Some(req) = c
+                        .param_a
+                        .as_ref()
+                        .and_then(|a| a.as_any().downcast_ref::<ParamOutgoingResetRequest>())
+                    {
+                        for id in &req.stream_identifiers {
+                            self.events
+                                .push_back(Event::Stream(StreamEvent::Finished { id: *id }));
+                        }
+                    }
+                }
+            }
             if self.reconfigs.is_empty() {
                 self.timers.stop(Timer::Reconfig);
             }
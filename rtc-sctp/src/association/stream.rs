@@ -112,6 +112,30 @@ impl<'a> Stream<'a> {
         Err(Error::ErrStreamClosed)
     }
 
+    /// read_partial reads the fragments of the next message received so far, without
+    /// waiting for the whole message, once set_partial_delivery is enabled. The message
+    /// ends with the Chunks whose is_message_end returns true.
+    pub fn read_partial(&mut self) -> Result<Option<Chunks>> {
+        if let Some(s) = self.association.streams.get_mut(&self.stream_identifier) {
+            if s.state == RecvSendState::ReadWritable || s.state == RecvSendState::Readable {
+                return Ok(s.reassembly_queue.read_partial());
+            }
+        }
+
+        Err(Error::ErrStreamClosed)
+    }
+
+    /// set_partial_delivery makes the stream readable with read_partial as soon as
+    /// fragments of the next ordered message arrive, instead of once it is complete.
+    pub fn set_partial_delivery(&mut self, enabled: bool) -> Result<()> {
+        if let Some(s) = self.association.streams.get_mut(&self.stream_identifier) {
+            s.reassembly_queue.partial_delivery = enabled;
+            Ok(())
+        } else {
+            Err(Error::ErrStreamClosed)
+        }
+    }
+
     /// write_sctp writes len(p) bytes from p to the DTLS connection
     pub fn write_sctp(&mut self, p: &Bytes, ppi: PayloadProtocolIdentifier) -> Result<usize> {
         self.write_source(&mut ByteSlice::from_slice(p), ppi)
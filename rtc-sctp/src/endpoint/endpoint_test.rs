@@ -3,7 +3,7 @@ use crate::association::Event;
 use shared::error::{Error, Result};
 
 use crate::association::state::{AckMode, AssociationState};
-use crate::association::stream::{ReliabilityType, Stream};
+use crate::association::stream::{ReliabilityType, Stream, StreamEvent};
 use crate::chunk::chunk_abort::ChunkAbort;
 use crate::chunk::chunk_cookie_echo::ChunkCookieEcho;
 use crate::chunk::chunk_error::ChunkError;
@@ -1884,6 +1884,31 @@ fn test_assoc_reset_close_one_way() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_assoc_reset_confirmed() -> Result<()> {
+    let si: u16 = 1;
+
+    let (mut pair, client_ch, server_ch) = create_association_pair(AckMode::NoDelay, 0)?;
+
+    establish_session_pair(&mut pair, client_ch, server_ch, si)?;
+
+    pair.client_stream(client_ch, si)?.stop()?; // send reset
+    pair.drive();
+
+    let mut finished = false;
+    while let Some(event) = pair.client_conn_mut(client_ch).poll() {
+        if let Event::Stream(StreamEvent::Finished { id }) = event {
+            assert_eq!(id, si, "unexpected stream reset");
+            finished = true;
+        }
+    }
+    assert!(finished, "stream reset should be confirmed by the peer");
+
+    close_association_pair(&mut pair, client_ch, server_ch, si);
+
+    Ok(())
+}
+
 #[test]
 fn test_assoc_reset_close_both_ways() -> Result<()> {
     //let _guard = subscribe();
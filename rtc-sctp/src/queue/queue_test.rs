@@ -697,6 +697,60 @@ fn test_reassembly_queue_ignores_chunk_with_wrong_si() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_reassembly_queue_read_partial() -> Result<()> {
+    let mut rq = ReassemblyQueue::new(0);
+    rq.partial_delivery = true;
+
+    let org_ppi = PayloadProtocolIdentifier::String;
+    let fragment =
+        |tsn: u32, beginning: bool, ending: bool, data: &'static [u8]| ChunkPayloadData {
+            payload_type: org_ppi,
+            beginning_fragment: beginning,
+            ending_fragment: ending,
+            tsn,
+            stream_sequence_number: 0,
+            user_data: Bytes::from_static(data),
+            ..Default::default()
+        };
+
+    // a fragment that doesn't start the message isn't readable
+    assert!(!rq.push(fragment(2, false, false, b"DEF")));
+    assert!(!rq.is_readable());
+    assert!(rq.read_partial().is_none());
+
+    assert!(!rq.push(fragment(1, true, false, b"ABC")));
+    assert!(rq.is_readable());
+    assert!(rq.read().is_none(), "read waits for the whole message");
+    let chunks = rq
+        .read_partial()
+        .expect("leading fragments should be readable");
+    assert_eq!(chunks.ppi, org_ppi);
+    assert_eq!(chunks.fragments(), vec![&b"ABC"[..], &b"DEF"[..]]);
+    assert!(!chunks.is_message_end());
+    assert_eq!(0, rq.get_num_bytes(), "num bytes mismatch");
+    assert!(!rq.is_readable());
+    assert!(rq.read_partial().is_none());
+
+    // fragments already read are ignored when retransmitted
+    assert!(!rq.push(fragment(2, false, false, b"DEF")));
+    assert_eq!(0, rq.get_num_bytes(), "num bytes mismatch");
+
+    // the rest of the message waits for the missing fragment
+    assert!(!rq.push(fragment(4, false, true, b"J")));
+    assert!(rq.read_partial().is_none());
+    assert!(!rq.push(fragment(3, false, false, b"GHI")));
+    let chunks = rq
+        .read_partial()
+        .expect("last fragments should be readable");
+    assert_eq!(chunks.fragments(), vec![&b"GHI"[..], &b"J"[..]]);
+    assert!(chunks.is_message_end());
+    assert_eq!(1, rq.next_ssn, "next SSN should advance");
+    assert!(rq.ordered.is_empty());
+
+    Ok(())
+}
+
 #[test]
 fn test_reassembly_queue_ignores_chunk_with_stale_ssn() -> Result<()> {
     let mut rq = ReassemblyQueue::new(0);
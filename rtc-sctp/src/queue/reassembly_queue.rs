@@ -44,6 +44,8 @@ pub struct Chunks {
     offset: usize,
     index: usize,
     timestamp: Instant,
+    /// TSN of the next fragment once the first fragments were read with read_partial
+    partial_tsn: Option<u32>,
 }
 
 impl Chunks {
@@ -59,6 +61,17 @@ impl Chunks {
         l
     }
 
+    // Return the user data of every fragment, in order
+    pub fn fragments(&self) -> Vec<Bytes> {
+        self.chunks.iter().map(|c| c.user_data.clone()).collect()
+    }
+
+    // Return true if the last fragment ends the message, which is false only for
+    // the leading fragments of a message returned by read_partial
+    pub fn is_message_end(&self) -> bool {
+        self.chunks.last().is_some_and(|c| c.ending_fragment)
+    }
+
     // Concat all fragments into the buffer
     pub fn read(&self, buf: &mut [u8]) -> Result<usize> {
         let mut n_written = 0;
@@ -114,6 +127,7 @@ impl Chunks {
             offset: 0,
             index: 0,
             timestamp: Instant::now(),
+            partial_tsn: None,
         }
     }
 
@@ -177,6 +191,29 @@ impl Chunks {
 
         true
     }
+
+    /// Number of leading fragments that can be read with read_partial: the fragments
+    /// contiguous in TSN from the beginning of the message or from the last read one.
+    fn partial_len(&self) -> usize {
+        let mut next_tsn = match (self.partial_tsn, self.chunks.first()) {
+            (Some(partial_tsn), _) => partial_tsn,
+            (None, Some(c)) if c.beginning_fragment => c.tsn,
+            _ => return 0,
+        };
+
+        let mut n = 0;
+        for c in &self.chunks {
+            if c.tsn != next_tsn {
+                break;
+            }
+            n += 1;
+            if c.ending_fragment {
+                break;
+            }
+            next_tsn = next_tsn.wrapping_add(1);
+        }
+        n
+    }
 }
 
 #[derive(Default, Debug)]
@@ -188,6 +225,8 @@ pub(crate) struct ReassemblyQueue {
     pub(crate) unordered: Vec<Chunks>,
     pub(crate) unordered_chunks: Vec<ChunkPayloadData>,
     pub(crate) n_bytes: usize,
+    /// whether the next ordered message is readable with read_partial before it is complete
+    pub(crate) partial_delivery: bool,
 }
 
 impl ReassemblyQueue {
@@ -204,6 +243,7 @@ impl ReassemblyQueue {
             unordered: vec![],
             unordered_chunks: vec![],
             n_bytes: 0,
+            partial_delivery: false,
         }
     }
 
@@ -233,6 +273,15 @@ impl ReassemblyQueue {
                 return false;
             }
 
+            // Ignore fragments already read with read_partial
+            if self.ordered.iter().any(|s| {
+                s.ssn == chunk.stream_sequence_number
+                    && s.partial_tsn
+                        .is_some_and(|partial_tsn| sna32lt(chunk.tsn, partial_tsn))
+            }) {
+                return false;
+            }
+
             self.n_bytes += chunk.user_data.len();
 
             // Check if a chunkSet with the SSN already exists
@@ -319,6 +368,9 @@ impl ReassemblyQueue {
             if cset.is_complete() && sna16lte(cset.ssn, self.next_ssn) {
                 return true;
             }
+            if self.partial_delivery && cset.ssn == self.next_ssn && cset.partial_len() > 0 {
+                return true;
+            }
         }
         false
     }
@@ -382,6 +434,38 @@ impl ReassemblyQueue {
         Some(chunks)
     }
 
+    /// read_partial returns the fragments of the next ordered message received so far
+    /// in TSN order, without waiting for the rest of the message. The message is done
+    /// once the returned Chunks is_message_end. Unordered messages are only returned
+    /// once complete.
+    pub(crate) fn read_partial(&mut self) -> Option<Chunks> {
+        if !self.unordered.is_empty() {
+            let chunks = self.unordered.remove(0);
+            self.subtract_num_bytes(chunks.len());
+            return Some(chunks);
+        }
+
+        let cset = self.ordered.first_mut()?;
+        if cset.ssn != self.next_ssn {
+            return None;
+        }
+        let n = cset.partial_len();
+        if n == 0 {
+            return None;
+        }
+
+        let chunks = Chunks::new(cset.ssn, cset.ppi, cset.chunks.drain(..n).collect());
+        if chunks.is_message_end() {
+            self.ordered.remove(0);
+            self.next_ssn = self.next_ssn.wrapping_add(1);
+        } else {
+            cset.partial_tsn = chunks.chunks.last().map(|c| c.tsn.wrapping_add(1));
+        }
+        self.subtract_num_bytes(chunks.len());
+
+        Some(chunks)
+    }
+
     /// Use last_ssn to locate a chunkSet then remove it if the set has
     /// not been complete
     pub(crate) fn forward_tsn_for_ordered(&mut self, last_ssn: u16) {
@@ -590,3 +590,105 @@ fn test_unmarshal_non_nil_address() -> Result<()> {
     }
     Ok(())
 }
+
+#[test]
+fn test_unmarshal_tias_bandwidth() -> Result<()> {
+    let input = "v=0\r\n\
+        o=- 0 0 IN IP4 127.0.0.1\r\n\
+        s=-\r\n\
+        t=0 0\r\n\
+        m=video 9 UDP/TLS/RTP/SAVPF 96\r\n\
+        b=TIAS:500000\r\n";
+
+    let mut reader = Cursor::new(input.as_bytes());
+    let sdp = SessionDescription::unmarshal(&mut reader)?;
+    let bandwidth = &sdp.media_descriptions[0].bandwidth;
+    assert_eq!(1, bandwidth.len());
+    assert!(!bandwidth[0].experimental);
+    assert_eq!("TIAS", bandwidth[0].bandwidth_type);
+    assert_eq!(500_000, bandwidth[0].bandwidth);
+    assert_eq!(input, sdp.marshal());
+
+    Ok(())
+}
+
+#[test]
+fn test_unmarshal_lenient() -> Result<()> {
+    // x= is not a known type and b= lacks its bandwidth value
+    let input = "v=0\r\n\
+        o=- 0 0 IN IP4 127.0.0.1\r\n\
+        s=-\r\n\
+        x=unknown\r\n\
+        t=0 0\r\n\
+        a=group:BUNDLE 0\r\n\
+        m=audio 9 UDP/TLS/RTP/SAVPF 111\r\n\
+        b=AS\r\n\
+        a=mid:0\r\n\
+        a=rtpmap:111 opus/48000/2\r\n";
+
+    let mut reader = Cursor::new(input.as_bytes());
+    assert!(SessionDescription::unmarshal_with_mode(&mut reader, SdpParseMode::Strict).is_err());
+
+    let mut reader = Cursor::new(input.as_bytes());
+    let sdp = SessionDescription::unmarshal_with_mode(&mut reader, SdpParseMode::Lenient)?;
+    assert_eq!(Some("BUNDLE 0"), sdp.attribute("group"));
+    assert_eq!(1, sdp.media_descriptions.len());
+    let media = &sdp.media_descriptions[0];
+    assert!(media.bandwidth.is_empty());
+    assert_eq!(Some(Some("0")), media.attribute("mid"));
+    assert_eq!(Some(Some("111 opus/48000/2")), media.attribute("rtpmap"));
+
+    // well-formed descriptions parse the same in both modes
+    let mut reader = Cursor::new(CANONICAL_MARSHAL_SDP.as_bytes());
+    let sdp = SessionDescription::unmarshal_with_mode(&mut reader, SdpParseMode::Lenient)?;
+    assert_eq!(CANONICAL_MARSHAL_SDP, sdp.marshal());
+
+    Ok(())
+}
+
+#[test]
+fn test_unmarshal_lenient_rejects_malformed_media() {
+    let input = "v=0\r\n\
+        o=- 0 0 IN IP4 127.0.0.1\r\n\
+        s=-\r\n\
+        t=0 0\r\n\
+        m=audio\r\n";
+
+    let mut reader = Cursor::new(input.as_bytes());
+    assert!(SessionDescription::unmarshal_with_mode(&mut reader, SdpParseMode::Lenient).is_err());
+}
+
+#[test]
+fn test_media_description_rtcp_feedback() -> Result<()> {
+    let input = "v=0\r\n\
+        o=- 0 0 IN IP4 127.0.0.1\r\n\
+        s=-\r\n\
+        t=0 0\r\n\
+        m=video 9 UDP/TLS/RTP/SAVPF 96 97\r\n\
+        a=rtpmap:96 VP8/90000\r\n\
+        a=rtcp-fb:96 nack pli\r\n\
+        a=rtcp-fb:96 ccm tmmbr smaxpr=120\r\n\
+        a=rtcp-fb:* transport-cc\r\n\
+        a=rtcp-fb:x nack\r\n\
+        a=rtcp-fb:97\r\n";
+
+    let mut reader = Cursor::new(input.as_bytes());
+    let sdp = SessionDescription::unmarshal(&mut reader)?;
+    let feedback = |pt: u8, mechanism: &str, param: Option<&str>| RtcpFeedback {
+        pt,
+        mechanism: mechanism.to_owned(),
+        param: param.map(|p| p.to_owned()),
+    };
+
+    assert_eq!(
+        vec![
+            feedback(96, "nack", Some("pli")),
+            feedback(96, "ccm", Some("tmmbr smaxpr=120")),
+            feedback(96, "transport-cc", None),
+            feedback(97, "transport-cc", None),
+        ],
+        sdp.media_descriptions[0].rtcp_feedback()
+    );
+
+    Ok(())
+}
@@ -3,7 +3,9 @@ use std::fmt;
 use url::Url;
 
 use crate::description::common::*;
+use crate::description::session::{ATTR_KEY_MSID, ATTR_KEY_RTCP_FB};
 use crate::extmap::*;
+use crate::msid::*;
 
 /// Constants for extmap key
 pub const EXT_MAP_VALUE_TRANSPORT_CC_KEY: isize = 3;
@@ -19,6 +21,16 @@ fn ext_map_uri() -> HashMap<isize, &'static str> {
     m
 }
 
+/// RtcpFeedback is a feedback mechanism enabled for a payload type by an
+/// `a=rtcp-fb:<payload type> <mechanism> [<parameter>]` attribute.
+/// <https://tools.ietf.org/html/rfc4585#section-4.2>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtcpFeedback {
+    pub pt: u8,
+    pub mechanism: String,
+    pub param: Option<String>,
+}
+
 /// MediaDescription represents a media type.
 /// <https://tools.ietf.org/html/rfc4566#section-5.14>
 #[derive(Debug, Default, Clone)]
@@ -69,6 +81,56 @@ impl MediaDescription {
         None
     }
 
+    /// msids returns all well-formed `a=msid` attributes of the media description
+    pub fn msids(&self) -> Vec<MsidAttribute> {
+        self.attributes
+            .iter()
+            .filter(|a| a.key == ATTR_KEY_MSID)
+            .filter_map(|a| a.value.as_deref())
+            .filter_map(|value| MsidAttribute::parse(value).ok())
+            .collect()
+    }
+
+    /// rtcp_feedback returns all well-formed `a=rtcp-fb` attributes of the media description.
+    /// The `*` wildcard is expanded to every payload type of the media.
+    pub fn rtcp_feedback(&self) -> Vec<RtcpFeedback> {
+        let mut feedback = vec![];
+        for value in self
+            .attributes
+            .iter()
+            .filter(|a| a.key == ATTR_KEY_RTCP_FB)
+            .filter_map(|a| a.value.as_deref())
+        {
+            let mut fields = value.split_whitespace();
+            let (Some(pt), Some(mechanism)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            let param = fields.collect::<Vec<&str>>().join(" ");
+            let param = if param.is_empty() { None } else { Some(param) };
+
+            let pts: Vec<u8> = if pt == "*" {
+                self.media_name
+                    .formats
+                    .iter()
+                    .filter_map(|format| format.parse::<u8>().ok())
+                    .collect()
+            } else if let Ok(pt) = pt.parse::<u8>() {
+                vec![pt]
+            } else {
+                continue;
+            };
+
+            for pt in pts {
+                feedback.push(RtcpFeedback {
+                    pt,
+                    mechanism: mechanism.to_owned(),
+                    param: param.clone(),
+                });
+            }
+        }
+        feedback
+    }
+
     /// new_jsep_media_description creates a new MediaName with
     /// some settings that are required by the JSEP spec.
     pub fn new_jsep_media_description(codec_type: String, _codec_prefs: Vec<&str>) -> Self {
@@ -175,6 +237,12 @@ impl MediaDescription {
         self.with_property_attribute(e.marshal())
     }
 
+    /// with_msid adds an `a=msid` attribute to the media description
+    pub fn with_msid(mut self, msid: MsidAttribute) -> Self {
+        self.attributes.push(msid.convert());
+        self
+    }
+
     /// with_transport_cc_extmap adds an extmap to the media description
     pub fn with_transport_cc_extmap(self) -> Self {
         let uri = {
@@ -30,6 +30,7 @@ pub const ATTR_KEY_RECV_ONLY: &str = "recvonly";
 pub const ATTR_KEY_SEND_ONLY: &str = "sendonly";
 pub const ATTR_KEY_SEND_RECV: &str = "sendrecv";
 pub const ATTR_KEY_EXT_MAP: &str = "extmap";
+pub const ATTR_KEY_RTCP_FB: &str = "rtcp-fb";
 
 /// Constants for semantic tokens used in JSEP
 pub const SEMANTIC_TOKEN_LIP_SYNCHRONIZATION: &str = "LS";
@@ -158,6 +159,19 @@ impl fmt::Display for RepeatTime {
     }
 }
 
+/// SdpParseMode selects how malformed lines are handled when unmarshaling
+/// a session description.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum SdpParseMode {
+    /// Any unrecognized or malformed line is an error.
+    #[default]
+    Strict,
+    /// Unrecognized or malformed lines are skipped, which leaves the fields they
+    /// describe at their defaults. Malformed v=, o=, s=, t= and m= lines, which
+    /// define the structure of the description, are still errors.
+    Lenient,
+}
+
 /// SessionDescription is a a well-defined format for conveying sufficient
 /// information to discover and participate in a multimedia session.
 #[derive(Debug, Default, Clone)]
@@ -564,6 +578,57 @@ impl SessionDescription {
     }
 }
 
+impl SessionDescription {
+    /// unmarshal_with_mode is like unmarshal, but skips unrecognized or malformed
+    /// optional lines in SdpParseMode::Lenient.
+    pub fn unmarshal_with_mode<R: io::BufRead + io::Seek>(
+        reader: &mut R,
+        mode: SdpParseMode,
+    ) -> Result<Self> {
+        if mode == SdpParseMode::Strict {
+            return Self::unmarshal(reader);
+        }
+
+        let mut sdp = String::new();
+        reader.read_to_string(&mut sdp)?;
+        let mut lines: Vec<&str> = sdp
+            .lines()
+            .filter(|line| {
+                let b = line.as_bytes();
+                b.len() >= 2 && b[0].is_ascii_lowercase() && b[1] == b'='
+            })
+            .collect();
+
+        loop {
+            let text = lines.join(END_LINE) + END_LINE;
+            let mut cursor = io::Cursor::new(text.as_bytes());
+            let err = match Self::unmarshal(&mut cursor) {
+                Ok(desc) => return Ok(desc),
+                Err(err) => err,
+            };
+
+            // the parser stops within or right after the offending line
+            let pos = (cursor.position() as usize).saturating_sub(1);
+            let index = text.as_bytes()[..pos]
+                .iter()
+                .filter(|&&b| b == b'\n')
+                .count();
+            match lines.get(index) {
+                Some(line) if !is_structural_line(line) => {
+                    lines.remove(index);
+                }
+                _ => return Err(err),
+            }
+        }
+    }
+}
+
+fn is_structural_line(line: &str) -> bool {
+    ["v=", "o=", "s=", "t=", "m="]
+        .iter()
+        .any(|key| line.starts_with(key))
+}
+
 impl From<SessionDescription> for String {
     fn from(sdp: SessionDescription) -> String {
         sdp.marshal()
@@ -1067,7 +1132,8 @@ fn unmarshal_bandwidth(value: &str) -> Result<Bandwidth> {
     } else {
         // Set according to currently registered with IANA
         // https://tools.ietf.org/html/rfc4566#section-5.8
-        let i = index_of(parts[0], &["CT", "AS"]);
+        // https://tools.ietf.org/html/rfc3890#section-6.2
+        let i = index_of(parts[0], &["CT", "AS", "TIAS"]);
         if i == -1 {
             return Err(Error::SdpInvalidValue(parts[0].to_owned()));
         }
@@ -0,0 +1,63 @@
+#[cfg(test)]
+mod msid_test;
+
+use crate::description::common::*;
+use crate::description::session::ATTR_KEY_MSID;
+use shared::error::{Error, Result};
+
+use std::fmt;
+
+/// MsidAttribute represents an `a=msid:<stream_id> [<track_id>]` line which
+/// associates a media description with a MediaStream and, optionally, a track.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc8830#section-2>
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MsidAttribute {
+    pub stream_id: String,
+    pub track_id: Option<String>,
+}
+
+impl fmt::Display for MsidAttribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(track_id) = &self.track_id {
+            write!(f, "{} {}", self.stream_id, track_id)
+        } else {
+            write!(f, "{}", self.stream_id)
+        }
+    }
+}
+
+impl MsidAttribute {
+    /// converts this object to an Attribute
+    pub fn convert(&self) -> Attribute {
+        Attribute {
+            key: ATTR_KEY_MSID.to_string(),
+            value: Some(self.to_string()),
+        }
+    }
+
+    /// parse creates an MsidAttribute from the value of an `a=msid` attribute
+    pub fn parse(value: &str) -> Result<Self> {
+        let mut fields = value.split_whitespace();
+
+        let stream_id = match fields.next() {
+            Some(stream_id) => stream_id.to_owned(),
+            None => return Err(Error::ParseMsid(value.to_owned())),
+        };
+        let track_id = fields.next().map(|t| t.to_owned());
+
+        if fields.next().is_some() {
+            return Err(Error::ParseMsid(value.to_owned()));
+        }
+
+        Ok(MsidAttribute {
+            stream_id,
+            track_id,
+        })
+    }
+
+    /// marshal creates a string from an MsidAttribute
+    pub fn marshal(&self) -> String {
+        format!("{ATTR_KEY_MSID}:{self}")
+    }
+}
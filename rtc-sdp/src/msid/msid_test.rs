@@ -0,0 +1,102 @@
+use super::*;
+use crate::description::media::MediaDescription;
+use crate::description::session::SessionDescription;
+
+use std::io::Cursor;
+
+#[test]
+fn test_msid_parse() -> Result<()> {
+    let tests = vec![
+        (
+            "stream0 track0",
+            MsidAttribute {
+                stream_id: "stream0".to_owned(),
+                track_id: Some("track0".to_owned()),
+            },
+        ),
+        (
+            "stream1",
+            MsidAttribute {
+                stream_id: "stream1".to_owned(),
+                track_id: None,
+            },
+        ),
+    ];
+
+    for (value, expected) in tests {
+        let actual = MsidAttribute::parse(value)?;
+        assert_eq!(actual, expected);
+        assert_eq!(actual.to_string(), value);
+        assert_eq!(actual.marshal(), format!("msid:{value}"));
+    }
+
+    assert!(MsidAttribute::parse("").is_err());
+    assert!(MsidAttribute::parse("stream0 track0 extra").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_media_description_msids() {
+    let media = MediaDescription::default()
+        .with_msid(MsidAttribute {
+            stream_id: "stream0".to_owned(),
+            track_id: Some("track0".to_owned()),
+        })
+        .with_value_attribute("msid".to_owned(), "stream1 track1".to_owned())
+        .with_value_attribute("msid".to_owned(), "a b c".to_owned());
+
+    assert_eq!(
+        media.msids(),
+        vec![
+            MsidAttribute {
+                stream_id: "stream0".to_owned(),
+                track_id: Some("track0".to_owned()),
+            },
+            MsidAttribute {
+                stream_id: "stream1".to_owned(),
+                track_id: Some("track1".to_owned()),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_session_description_msid_roundtrip() -> Result<()> {
+    let tracks = [
+        ("stream0", "audio0", "audio", 111, "opus"),
+        ("stream0", "video0", "video", 96, "VP8"),
+    ];
+
+    let mut d = SessionDescription::new_jsep_session_description(false);
+    for (stream_id, track_id, kind, payload_type, name) in tracks {
+        d = d.with_media(
+            MediaDescription::new_jsep_media_description(kind.to_owned(), vec![])
+                .with_codec(payload_type, name.to_owned(), 90000, 0, String::new())
+                .with_msid(MsidAttribute {
+                    stream_id: stream_id.to_owned(),
+                    track_id: Some(track_id.to_owned()),
+                }),
+        );
+    }
+
+    let marshaled = d.marshal();
+    assert!(marshaled.contains("a=msid:stream0 audio0\r\n"));
+    assert!(marshaled.contains("a=msid:stream0 video0\r\n"));
+
+    let mut reader = Cursor::new(marshaled.as_bytes());
+    let parsed = SessionDescription::unmarshal(&mut reader)?;
+    let msids: Vec<MsidAttribute> = parsed
+        .media_descriptions
+        .iter()
+        .flat_map(|m| m.msids())
+        .collect();
+    assert_eq!(msids.len(), 2);
+    assert_ne!(msids[0], msids[1]);
+    for (msid, (stream_id, track_id, _, _, _)) in msids.iter().zip(tracks) {
+        assert_eq!(msid.stream_id, stream_id);
+        assert_eq!(msid.track_id.as_deref(), Some(track_id));
+    }
+
+    Ok(())
+}
@@ -272,6 +272,8 @@ pub enum Error {
     StapASizeLargerThanBuffer(usize, usize),
     #[error("nalu type {0} is currently not handled")]
     NaluTypeIsNotHandled(u8),
+    #[error("clock rate {1} is not valid for payload type {0}")]
+    ClockRateMismatch(u8, u32),
 
     //SRTP
     #[error("duplicated packet")]
@@ -435,6 +437,8 @@ pub enum Error {
     ErrStunserverAddressNotSet,
     #[error("only one Allocate() caller is allowed")]
     ErrOneAllocateOnly,
+    #[error("OAuth credentials are not set for the client")]
+    ErrOAuthCredentialsNotSet,
     #[error("already allocated")]
     ErrAlreadyAllocated,
     #[error("non-STUN message from STUN server")]
@@ -527,6 +531,8 @@ pub enum Error {
     ErrNoAllocationFound,
     #[error("unable to handle send-indication, no permission added")]
     ErrNoPermission,
+    #[error("permission expired before it could be refreshed")]
+    ErrPermissionExpired,
     #[error("packet write smaller than packet")]
     ErrShortWrite,
     #[error("no such channel bind")]
@@ -561,6 +567,11 @@ pub enum Error {
     #[error("local password is less than 128 bits long")]
     ErrLocalPwdInsufficientBits,
 
+    /// Indicates a graceful restart would drop the local candidate of the
+    /// pair that carries the traffic until a new pair is selected.
+    #[error("graceful restart requires keeping the local candidates")]
+    ErrGracefulRestartWithoutLocalCandidates,
+
     /// Indicates an unsupported transport type was provided.
     #[error("invalid transport protocol type")]
     ErrProtoType,
@@ -569,6 +580,10 @@ pub enum Error {
     #[error("no candidate pairs available")]
     ErrNoCandidatePairs,
 
+    /// Indicates a remote candidate index does not refer to a remote candidate.
+    #[error("remote candidate not found")]
+    ErrRemoteCandidateNotFound,
+
     /// Indicates agent connection was canceled by the caller.
     #[error("connecting canceled by caller")]
     ErrCanceledByCaller,
@@ -652,6 +667,14 @@ pub enum Error {
     #[error("mdns is not supported")]
     ErrMulticastDnsNotSupported,
 
+    /// Indicates that a remote agent sent the same ICE tie-breaker as ours.
+    #[error("remote ICE tie-breaker equals the local one")]
+    ErrSameICEControllingStatus,
+
+    /// Indicates that a STUN Binding Request reused a recently seen transaction ID.
+    #[error("replayed STUN transaction")]
+    ErrStunReplayedTransaction,
+
     /// Indicates Restart was called when Agent is in GatheringStateGathering.
     #[error("ICE Agent can not be restarted when gathering")]
     ErrRestartWhenGathering,
@@ -732,6 +755,8 @@ pub enum Error {
     ErrInvalidPacketLength,
     #[error("export_keying_material can not be used with a reserved label")]
     ErrReservedExportKeyingMaterial,
+    #[error("invalid alpn format")]
+    ErrAlpnInvalidFormat,
     #[error("client sent certificate verify but we have no certificate to verify")]
     ErrCertificateVerifyNoCertificate,
     #[error("client+server do not support any shared cipher suites")]
@@ -742,16 +767,22 @@ pub enum Error {
     ErrClientCertificateNotVerified,
     #[error("server required client verification, but got none")]
     ErrClientCertificateRequired,
+    #[error(
+        "server requires the Extended Master Secret extension, but the client does not support it"
+    )]
+    ErrClientNoExtendedMasterSecret,
+    #[error("server selected an application protocol we did not offer")]
+    ErrClientNoMatchingApplicationProtocol,
     #[error("server responded with SRTP Profile we do not support")]
     ErrClientNoMatchingSrtpProfile,
-    #[error("client required Extended Master Secret extension, but server does not support it")]
-    ErrClientRequiredButNoServerEms,
     #[error("server hello can not be created without a compression method")]
     ErrCompressionMethodUnset,
     #[error("client+server cookie does not match")]
     ErrCookieMismatch,
     #[error("cookie must not be longer then 255 bytes")]
     ErrCookieTooLong,
+    #[error("connection id must not be longer than 255 bytes")]
+    ErrConnectionIdTooLong,
     #[error("PSK Identity Hint provided but PSK is nil")]
     ErrIdentityNoPsk,
     #[error("no certificate provided")]
@@ -798,6 +829,8 @@ pub enum Error {
     ErrNoSupportedEllipticCurves,
     #[error("unsupported protocol version")]
     ErrUnsupportedProtocolVersion,
+    #[error("unsupported serialized state version")]
+    ErrUnsupportedStateVersion,
     #[error("Certificate and PSK provided")]
     ErrPskAndCertificate,
     #[error("PSK and PSK Identity Hint must both be set for client")]
@@ -806,12 +839,12 @@ pub enum Error {
     ErrRequestedButNoSrtpExtension,
     #[error("Certificate is mandatory for server")]
     ErrServerMustHaveCertificate,
+    #[error("client required Extended Master Secret extension, but server does not support it")]
+    ErrServerNoExtendedMasterSecret,
+    #[error("client requested ALPN but we have no matching protocols")]
+    ErrServerNoMatchingApplicationProtocol,
     #[error("client requested SRTP but we have no matching profiles")]
     ErrServerNoMatchingSrtpProfile,
-    #[error(
-        "server requires the Extended Master Secret extension, but the client does not support it"
-    )]
-    ErrServerRequiredButNoClientEms,
     #[error("expected and actual verify data does not match")]
     ErrVerifyDataMismatch,
     #[error("handshake message unset, unable to marshal")]
@@ -1123,10 +1156,20 @@ pub enum Error {
     #[error("data channel exist")]
     ErrDataChannelExist,
 
+    /// ErrDataChannelNotExisted indicates an operation executed on a data
+    /// channel that doesn't exist.
+    #[error("data channel not existed")]
+    ErrDataChannelNotExisted,
+
     /// ErrCertificateExpired indicates that an x509 certificate has expired.
     #[error("x509Cert expired")]
     ErrCertificateExpired,
 
+    /// ErrCertificateExpiryTooShort indicates that a certificate was requested with
+    /// a validity period too short to complete a DTLS handshake.
+    #[error("certificate validity period must be at least one minute")]
+    ErrCertificateExpiryTooShort,
+
     /// ErrNoTurnCredentials indicates that a TURN server URL was provided
     /// without required credentials.
     #[error("turn server credentials required")]
@@ -1141,6 +1184,10 @@ pub enum Error {
     #[error("track already exists")]
     ErrExistingTrack,
 
+    /// ErrUnknownTrack indicates that no sender is attached to a track with the given id.
+    #[error("unknown track")]
+    ErrUnknownTrack,
+
     /// ErrPrivateKeyType indicates that a particular private key encryption
     /// chosen to generate a certificate is not supported.
     #[error("private key type not supported")]
@@ -1212,10 +1259,15 @@ pub enum Error {
     ErrIncorrectSignalingState,
 
     /// ErrProtocolTooLarge indicates that value given for a DataChannelInit protocol is
-    /// longer then 65535 bytes
-    #[error("protocol is larger then 65535 bytes")]
+    /// longer then 32767 bytes
+    #[error("protocol is larger then 32767 bytes")]
     ErrProtocolTooLarge,
 
+    /// ErrProtocolInvalid indicates that value given for a DataChannelInit protocol
+    /// contains a NUL character
+    #[error("protocol contains a NUL character")]
+    ErrProtocolInvalid,
+
     /// ErrSenderNotCreatedByConnection indicates remove_track was called with a RtpSender not created
     /// by this PeerConnection
     #[error("RtpSender not created by this PeerConnection")]
@@ -1282,6 +1334,10 @@ pub enum Error {
     #[error("unable to populate media section, RTPSender created with no codecs")]
     ErrSenderWithNoCodecs,
 
+    /// ErrPayloadTypeConflict indicates that two codecs were assigned the same payload type.
+    #[error("payload type is assigned to more than one codec")]
+    ErrPayloadTypeConflict,
+
     /// ErrRTPSenderNewTrackHasIncorrectKind indicates that the new track is of a different kind than the previous/original
     #[error("new track must be of the same kind as previous")]
     ErrRTPSenderNewTrackHasIncorrectKind,
@@ -1294,6 +1350,22 @@ pub enum Error {
     #[error("Sequence number transformer has been already enabled")]
     ErrRTPSenderSeqTransEnabled,
 
+    /// ErrSsrcNotFound indicates that encoding parameters referenced an SSRC the RTPSender doesn't use
+    #[error("encoding parameters reference an unknown SSRC")]
+    ErrSsrcNotFound,
+
+    /// ErrRTPSenderNoEncodings indicates that encoding parameters were set without any encoding
+    #[error("encoding parameters must contain at least one encoding")]
+    ErrRTPSenderNoEncodings,
+
+    /// ErrRTPSenderNotActive indicates that RTP was written for an inactive encoding
+    #[error("RTPSender's encoding is not active")]
+    ErrRTPSenderNotActive,
+
+    /// ErrRTPSenderMaxBitrateExceeded indicates that RTP was dropped to keep the RTPSender under its max bitrate
+    #[error("RTPSender's max bitrate has been exceeded")]
+    ErrRTPSenderMaxBitrateExceeded,
+
     /// ErrUnbindFailed indicates that a TrackLocal was not able to be unbind
     #[error("failed to unbind TrackLocal from PeerConnection")]
     ErrUnbindFailed,
@@ -1479,6 +1551,8 @@ pub enum Error {
     SdpEmptyTimeDescription,
     #[error("parse extmap: {0}")]
     ParseExtMap(String),
+    #[error("parse msid: {0}")]
+    ParseMsid(String),
     #[error("{} --> {} <-- {}", .s.substring(0,*.p), .s.substring(*.p, *.p+1), .s.substring(*.p+1, .s.len())
     )]
     SyntaxError { s: String, p: usize },
@@ -128,10 +128,14 @@ impl Cipher for CipherAeadAesGcm {
         srtcp_index: usize,
         ssrc: u32,
     ) -> Result<BytesMut> {
-        if encrypted.len() < self.aead_auth_tag_len() + SRTCP_INDEX_SIZE {
+        if encrypted.len() < 8 + self.aead_auth_tag_len() + SRTCP_INDEX_SIZE {
             return Err(Error::ErrFailedToVerifyAuthTag);
         }
 
+        if encrypted[encrypted.len() - SRTCP_INDEX_SIZE] & RTCP_ENCRYPTION_FLAG == 0 {
+            return self.verify_unencrypted_rtcp(encrypted, srtcp_index, ssrc);
+        }
+
         let nonce = self.rtcp_initialization_vector(srtcp_index, ssrc);
         let aad = self.rtcp_additional_authenticated_data(encrypted, srtcp_index);
 
@@ -257,6 +261,37 @@ impl CipherAeadAesGcm {
         iv
     }
 
+    /// When the Encryption flag of an SRTCP packet is not set, the whole RTCP
+    /// packet followed by the ESRTCP word is used as AAD and the plaintext is
+    /// empty, so the AEAD tag only authenticates the packet.
+    ///
+    /// > | RTCP Header | Payload | AEAD auth tag |0| SRTCP Index |
+    ///
+    /// https://tools.ietf.org/html/rfc7714#section-9.3
+    fn verify_unencrypted_rtcp(
+        &mut self,
+        encrypted: &[u8],
+        srtcp_index: usize,
+        ssrc: u32,
+    ) -> Result<BytesMut> {
+        let tag_offset = encrypted.len() - SRTCP_INDEX_SIZE - self.aead_auth_tag_len();
+
+        let mut aad = Vec::with_capacity(tag_offset + SRTCP_INDEX_SIZE);
+        aad.extend_from_slice(&encrypted[..tag_offset]);
+        aad.extend_from_slice(&encrypted[encrypted.len() - SRTCP_INDEX_SIZE..]);
+
+        let nonce = self.rtcp_initialization_vector(srtcp_index, ssrc);
+        self.srtcp_cipher.decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &encrypted[tag_offset..encrypted.len() - SRTCP_INDEX_SIZE],
+                aad: &aad,
+            },
+        )?;
+
+        Ok(BytesMut::from(&encrypted[..tag_offset]))
+    }
+
     /// In an SRTCP packet, a 1-bit Encryption flag is prepended to the
     /// 31-bit SRTCP index to form a 32-bit value we shall call the
     /// "ESRTCP word"
@@ -303,3 +303,53 @@ fn test_decrypt_rtcp() {
 
     assert_eq!(gotten_decrypted_rtcp_packet, *DECRYPTED_RTCP_PACKET)
 }
+
+#[test]
+fn test_decrypt_unencrypted_rtcp() -> Result<()> {
+    use aes_gcm::aead::{generic_array::GenericArray, Aead, Payload};
+    use aes_gcm::{Aes128Gcm, KeyInit, Nonce};
+
+    let ssrc = 0xcafebabe;
+    let srtcp_index = 1;
+
+    // Authenticate the whole packet followed by an ESRTCP word with E=0
+    let srtcp_key = aes_cm_key_derivation(
+        LABEL_SRTCP_ENCRYPTION,
+        &MASTER_KEY,
+        &MASTER_SALT,
+        0,
+        MASTER_KEY.len(),
+    )?;
+    let iv = CipherAeadAesGcm::new(ProtectionProfile::AeadAes128Gcm, &MASTER_KEY, &MASTER_SALT)?
+        .rtcp_initialization_vector(srtcp_index, ssrc);
+    let esrtcp_word = (srtcp_index as u32).to_be_bytes();
+    let mut aad = DECRYPTED_RTCP_PACKET.to_vec();
+    aad.extend_from_slice(&esrtcp_word);
+    let tag = Aes128Gcm::new(GenericArray::from_slice(&srtcp_key)).encrypt(
+        Nonce::from_slice(&iv),
+        Payload {
+            msg: &[],
+            aad: &aad,
+        },
+    )?;
+
+    let mut unencrypted_rtcp_packet = DECRYPTED_RTCP_PACKET.to_vec();
+    unencrypted_rtcp_packet.extend_from_slice(&tag);
+    unencrypted_rtcp_packet.extend_from_slice(&esrtcp_word);
+
+    let mut ctx = Context::new(
+        &MASTER_KEY,
+        &MASTER_SALT,
+        ProtectionProfile::AeadAes128Gcm,
+        None,
+        None,
+    )?;
+    let decrypted = ctx.decrypt_rtcp(&unencrypted_rtcp_packet)?;
+    assert_eq!(decrypted, *DECRYPTED_RTCP_PACKET);
+
+    // Tampering with the authenticated payload must be detected
+    unencrypted_rtcp_packet[10] ^= 0xff;
+    assert!(ctx.decrypt_rtcp(&unencrypted_rtcp_packet).is_err());
+
+    Ok(())
+}
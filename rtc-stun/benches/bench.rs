@@ -10,7 +10,7 @@ use stun::attributes::{
 };
 use stun::error_code::{ErrorCode, ErrorCodeAttribute, CODE_STALE_NONCE};
 use stun::fingerprint::{FINGERPRINT, FINGERPRINT_SIZE};
-use stun::integrity::MessageIntegrity;
+use stun::integrity::{MessageIntegrity, MessageIntegrityCache};
 use stun::message::TransactionId;
 use stun::message::{
     is_message, Getter, Message, MessageType, Setter, ATTRIBUTE_HEADER_SIZE, BINDING_REQUEST,
@@ -258,6 +258,23 @@ fn benchmark_message_integrity(c: &mut Criterion) {
             })
         });
     }
+
+    {
+        let mut m = Message::new();
+        m.raw = Vec::with_capacity(1024);
+        let software = Software::new(ATTR_SOFTWARE, "software".to_owned());
+        let _ = software.add_to(&mut m);
+        let integrity = MessageIntegrity::new_short_term_integrity("password".to_owned());
+        m.write_header();
+        integrity.add_to(&mut m).unwrap();
+        m.write_header();
+        let mut cache = MessageIntegrityCache::default();
+        c.bench_function("BenchmarkMessageIntegrity_CheckCached", |b| {
+            b.iter(|| {
+                MessageIntegrity::check_cached(&mut m, b"password", &mut cache).unwrap();
+            })
+        });
+    }
 }
 
 fn benchmark_message(c: &mut Criterion) {
@@ -66,6 +66,8 @@ impl fmt::Display for AttrType {
             ATTR_USER_HASH => "USERHASH",
             ATTR_PASSWORD_ALGORITHMS => "PASSWORD-ALGORITHMS",
             ATTR_ALTERNATE_DOMAIN => "ALTERNATE-DOMAIN",
+            ATTR_ACCESS_TOKEN => "ACCESS-TOKEN",
+            ATTR_THIRD_PARTY_AUTHORIZATION => "THIRD-PARTY-AUTHORIZATION",
             _ => other.as_str(),
         };
 
@@ -152,6 +154,10 @@ pub const ATTR_USER_HASH: AttrType = AttrType(0x001E); // USER-HASH
 pub const ATTR_PASSWORD_ALGORITHMS: AttrType = AttrType(0x8002); // PASSWORD-ALGORITHMS
 pub const ATTR_ALTERNATE_DOMAIN: AttrType = AttrType(0x8003); // ALTERNATE-DOMAIN
 
+/// Attributes from RFC 7635 STUN Extension for Third-Party Authorization.
+pub const ATTR_ACCESS_TOKEN: AttrType = AttrType(0x001B); // ACCESS-TOKEN
+pub const ATTR_THIRD_PARTY_AUTHORIZATION: AttrType = AttrType(0x802E); // THIRD-PARTY-AUTHORIZATION
+
 /// RawAttribute is a Type-Length-Value (TLV) object that
 /// can be added to a STUN message. Attributes are divided into two
 /// types: comprehension-required and comprehension-optional.  STUN
@@ -7,7 +7,9 @@ use crate::message::*;
 use shared::error::*;
 
 use ring::hmac;
+use std::collections::hash_map::DefaultHasher;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 
 // separator for credentials.
 pub(crate) const CREDENTIALS_SEP: &str = ":";
@@ -26,6 +28,48 @@ fn new_hmac(key: &[u8], message: &[u8]) -> Vec<u8> {
     hmac::sign(&mac, message).as_ref().to_vec()
 }
 
+fn hash_key(key: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+// MessageIntegrityCache holds the HMAC-SHA1 context of a key, so that checking
+// many messages with the same key, like the ICE password, skips the key setup.
+//
+// See MessageIntegrity::check_cached.
+#[derive(Clone)]
+pub struct MessageIntegrityCache {
+    key_hash: u64,
+    key: Vec<u8>,
+    hmac_context: hmac::Key,
+}
+
+impl Default for MessageIntegrityCache {
+    fn default() -> Self {
+        MessageIntegrityCache::new(&[])
+    }
+}
+
+impl MessageIntegrityCache {
+    pub fn new(key: &[u8]) -> Self {
+        MessageIntegrityCache {
+            key_hash: hash_key(key),
+            key: key.to_vec(),
+            hmac_context: hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, key),
+        }
+    }
+
+    // context returns the HMAC context of key, recomputing it only if key changed.
+    fn context(&mut self, key: &[u8]) -> &hmac::Key {
+        let key_hash = hash_key(key);
+        if key_hash != self.key_hash || key != self.key.as_slice() {
+            *self = MessageIntegrityCache::new(key);
+        }
+        &self.hmac_context
+    }
+}
+
 impl fmt::Display for MessageIntegrity {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "KEY: 0x{:x?}", self.0)
@@ -81,33 +125,50 @@ impl MessageIntegrity {
     //
     // CPU costly, see BenchmarkMessageIntegrity_Check.
     pub fn check(&self, m: &mut Message) -> Result<()> {
-        let v = m.get(ATTR_MESSAGE_INTEGRITY)?;
+        let mac = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, &self.0);
+        check_with(m, &mac)
+    }
+
+    // check_cached checks MESSAGE-INTEGRITY attribute with key, reusing the HMAC
+    // context kept in cache while the key is unchanged.
+    //
+    // See BenchmarkMessageIntegrity_CheckCached.
+    pub fn check_cached(
+        m: &mut Message,
+        key: &[u8],
+        cache: &mut MessageIntegrityCache,
+    ) -> Result<()> {
+        check_with(m, cache.context(key))
+    }
+}
 
-        // Adjusting length in header to match m.Raw that was
-        // used when computing HMAC.
+fn check_with(m: &mut Message, mac: &hmac::Key) -> Result<()> {
+    let v = m.get(ATTR_MESSAGE_INTEGRITY)?;
 
-        let length = m.length as usize;
-        let mut after_integrity = false;
-        let mut size_reduced = 0;
+    // Adjusting length in header to match m.Raw that was
+    // used when computing HMAC.
 
-        for a in &m.attributes.0 {
-            if after_integrity {
-                size_reduced += nearest_padded_value_length(a.length as usize);
-                size_reduced += ATTRIBUTE_HEADER_SIZE;
-            }
-            if a.typ == ATTR_MESSAGE_INTEGRITY {
-                after_integrity = true;
-            }
+    let length = m.length as usize;
+    let mut after_integrity = false;
+    let mut size_reduced = 0;
+
+    for a in &m.attributes.0 {
+        if after_integrity {
+            size_reduced += nearest_padded_value_length(a.length as usize);
+            size_reduced += ATTRIBUTE_HEADER_SIZE;
+        }
+        if a.typ == ATTR_MESSAGE_INTEGRITY {
+            after_integrity = true;
         }
-        m.length -= size_reduced as u32;
-        m.write_length();
-        // start_of_hmac should be first byte of integrity attribute.
-        let start_of_hmac = MESSAGE_HEADER_SIZE + m.length as usize
-            - (ATTRIBUTE_HEADER_SIZE + MESSAGE_INTEGRITY_SIZE);
-        let b = &m.raw[..start_of_hmac]; // data before integrity attribute
-        let expected = new_hmac(&self.0, b);
-        m.length = length as u32;
-        m.write_length(); // writing length back
-        check_hmac(&v, &expected)
     }
+    m.length -= size_reduced as u32;
+    m.write_length();
+    // start_of_hmac should be first byte of integrity attribute.
+    let start_of_hmac =
+        MESSAGE_HEADER_SIZE + m.length as usize - (ATTRIBUTE_HEADER_SIZE + MESSAGE_INTEGRITY_SIZE);
+    let b = &m.raw[..start_of_hmac]; // data before integrity attribute
+    let expected = hmac::sign(mac, b);
+    m.length = length as u32;
+    m.write_length(); // writing length back
+    check_hmac(&v, expected.as_ref())
 }
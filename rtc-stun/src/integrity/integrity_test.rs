@@ -103,3 +103,35 @@ fn test_message_integrity_before_fingerprint() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_message_integrity_check_cached() -> Result<()> {
+    let mut m = Message::new();
+    m.write_header();
+    let a = TextAttribute {
+        attr: ATTR_SOFTWARE,
+        text: "software".to_owned(),
+    };
+    a.add_to(&mut m)?;
+    MessageIntegrity::new_short_term_integrity("pwd".to_owned()).add_to(&mut m)?;
+    FINGERPRINT.add_to(&mut m)?;
+
+    let mut cache = MessageIntegrityCache::default();
+    MessageIntegrity::check_cached(&mut m, b"pwd", &mut cache)?;
+    assert_eq!(cache.key, b"pwd");
+    // the cached context is reused for the same key
+    MessageIntegrity::check_cached(&mut m, b"pwd", &mut cache)?;
+
+    // and replaced when the key changes
+    let result = MessageIntegrity::check_cached(&mut m, b"other", &mut cache);
+    assert!(result.is_err(), "should be invalid with another key");
+    assert_eq!(cache.key, b"other");
+    MessageIntegrity::check_cached(&mut m, b"pwd", &mut cache)?;
+
+    m.raw[24] = 33;
+    m.decode()?;
+    let result = MessageIntegrity::check_cached(&mut m, b"pwd", &mut cache);
+    assert!(result.is_err(), "mismatch expected");
+
+    Ok(())
+}
@@ -80,8 +80,12 @@ fn main() -> Result<()> {
         username: cred[0].to_string(),
         password: cred[1].to_string(),
         realm: realm.to_string(),
+        credentials: TurnCredentials::default(),
         software: String::new(),
         rto_in_ms: 0,
+        prefer_ipv6: false,
+        ipv6_timeout: Duration::ZERO,
+        force_local_addr: None,
     };
 
     let mut client = Client::new(cfg)?;
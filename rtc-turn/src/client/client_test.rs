@@ -1,20 +1,18 @@
 use super::*;
 use std::collections::HashSet;
 use std::net::UdpSocket;
+use std::time::Duration;
+use stun::error_code::CODE_UNAUTHORIZED;
 
 fn create_listening_test_client(rto_in_ms: u64) -> Result<(UdpSocket, Client)> {
     let udp_socket = UdpSocket::bind("0.0.0.0:0")?;
 
     let client = Client::new(ClientConfig {
-        stun_serv_addr: String::new(),
-        turn_serv_addr: String::new(),
         local_addr: udp_socket.local_addr()?,
         protocol: Protocol::UDP,
-        username: String::new(),
-        password: String::new(),
-        realm: String::new(),
         software: "TEST SOFTWARE".to_owned(),
         rto_in_ms,
+        ..Default::default()
     })?;
 
     Ok((udp_socket, client))
@@ -25,19 +23,76 @@ fn create_listening_test_client_with_stun_serv() -> Result<(UdpSocket, Client)>
 
     let client = Client::new(ClientConfig {
         stun_serv_addr: "stun1.l.google.com:19302".to_owned(),
-        turn_serv_addr: String::new(),
         local_addr: udp_socket.local_addr()?,
         protocol: Protocol::UDP,
-        username: String::new(),
-        password: String::new(),
-        realm: String::new(),
         software: "TEST SOFTWARE".to_owned(),
-        rto_in_ms: 0,
+        ..Default::default()
     })?;
 
     Ok((udp_socket, client))
 }
 
+/// create_turn_test_client creates a client with long-term credentials for the TURN
+/// server at server_addr, whose requests are read with next_request and answered with
+/// respond instead of going through a socket.
+fn create_turn_test_client(server_addr: SocketAddr) -> Result<Client> {
+    Client::new(ClientConfig {
+        turn_serv_addr: server_addr.to_string(),
+        local_addr: "127.0.0.1:5000".parse()?,
+        protocol: Protocol::UDP,
+        username: "user".to_owned(),
+        password: "pass".to_owned(),
+        software: "TEST SOFTWARE".to_owned(),
+        ..Default::default()
+    })
+}
+
+/// next_request_to returns the destination and the message of the next request sent
+/// by client.
+fn next_request_to(client: &mut Client) -> Result<(SocketAddr, Message)> {
+    let transmit = client
+        .poll_transmit()
+        .expect("client should send a request");
+    let mut req = Message::new();
+    req.write(&transmit.message)?;
+    Ok((transmit.transport.peer_addr, req))
+}
+
+/// next_request returns the next request sent by client.
+fn next_request(client: &mut Client) -> Result<Message> {
+    Ok(next_request_to(client)?.1)
+}
+
+/// respond passes res to client as received from the server at from at time now.
+fn respond(client: &mut Client, now: Instant, from: SocketAddr, res: &Message) -> Result<()> {
+    let local_addr = client.local_addr;
+    client.handle_transmit(Transmit {
+        now,
+        transport: TransportContext {
+            local_addr,
+            peer_addr: from,
+            protocol: Protocol::UDP,
+            ecn: None,
+        },
+        message: BytesMut::from(&res.raw[..]),
+    })
+}
+
+/// allocate_success returns the success response to the Allocate request req.
+fn allocate_success(req: &Message) -> Result<Message> {
+    let mut res = Message::new();
+    res.build(&[
+        Box::new(req.transaction_id),
+        Box::new(MessageType::new(METHOD_ALLOCATE, CLASS_SUCCESS_RESPONSE)),
+        Box::new(RelayedAddress {
+            ip: "1.2.3.4".parse()?,
+            port: 5678,
+        }),
+        Box::new(Lifetime(Duration::from_secs(600))),
+    ])?;
+    Ok(res)
+}
+
 #[test]
 fn test_client_with_stun_send_binding_request() -> Result<()> {
     //env_logger::init();
@@ -165,3 +220,425 @@ fn test_client_with_stun_send_binding_request_to_timeout() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_client_allocate_with_oauth_token() -> Result<()> {
+    let server = UdpSocket::bind("127.0.0.1:0")?;
+    let conn = UdpSocket::bind("127.0.0.1:0")?;
+    let local_addr = conn.local_addr()?;
+    let server_addr = server.local_addr()?;
+
+    let mut client = Client::new(ClientConfig {
+        turn_serv_addr: server_addr.to_string(),
+        local_addr,
+        protocol: Protocol::UDP,
+        credentials: TurnCredentials::OAuthToken {
+            access_token: "first-token".to_owned(),
+            token_type: "pop".to_owned(),
+            expires_in: 3600,
+        },
+        software: "TEST SOFTWARE".to_owned(),
+        ..Default::default()
+    })?;
+
+    let tid = client.allocate()?;
+    while let Some(transmit) = client.poll_transmit() {
+        conn.send_to(&transmit.message, transmit.transport.peer_addr)?;
+    }
+
+    // The mocked TURN server expects ACCESS-TOKEN instead of MESSAGE-INTEGRITY.
+    let mut buffer = vec![0u8; 2048];
+    let (n, from) = server.recv_from(&mut buffer)?;
+    let mut req = Message::new();
+    req.write(&buffer[..n])?;
+    assert_eq!(tid, req.transaction_id);
+    assert_eq!(MessageType::new(METHOD_ALLOCATE, CLASS_REQUEST), req.typ);
+    let mut token = AccessToken::default();
+    token.get_from(&req)?;
+    assert_eq!(b"first-token".to_vec(), token.0);
+    assert!(!req.contains(ATTR_MESSAGE_INTEGRITY));
+    assert!(!req.contains(ATTR_USERNAME));
+
+    // Reject the token and advertise the authorization server.
+    let mut res = Message::new();
+    res.build(&[
+        Box::new(req.transaction_id),
+        Box::new(MessageType::new(METHOD_ALLOCATE, CLASS_ERROR_RESPONSE)),
+        Box::new(CODE_UNAUTHORIZED),
+    ])?;
+    res.add(ATTR_THIRD_PARTY_AUTHORIZATION, b"auth.example.com");
+    res.write_header();
+    respond(&mut client, Instant::now(), from, &res)?;
+
+    match client.poll_event() {
+        Some(Event::AllocateError(id, _)) => assert_eq!(tid, id),
+        _ => panic!("expected AllocateError"),
+    }
+    assert_eq!(Some("auth.example.com"), client.third_party_authorization());
+
+    // A refreshed token is carried by the next Allocate request.
+    client.refresh_oauth_token("second-token".to_owned())?;
+    client.allocate()?;
+    while let Some(transmit) = client.poll_transmit() {
+        conn.send_to(&transmit.message, transmit.transport.peer_addr)?;
+    }
+    let (n, _) = server.recv_from(&mut buffer)?;
+    let mut req = Message::new();
+    req.write(&buffer[..n])?;
+    token.get_from(&req)?;
+    assert_eq!(b"second-token".to_vec(), token.0);
+
+    client.close();
+
+    Ok(())
+}
+
+#[test]
+fn test_client_refresh_oauth_token_without_oauth_credentials() -> Result<()> {
+    let (_conn, mut client) = create_listening_test_client(0)?;
+
+    assert_eq!(
+        Err(Error::ErrOAuthCredentialsNotSet),
+        client.refresh_oauth_token("token".to_owned())
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_client_caches_auth_nonce() -> Result<()> {
+    let server_addr: SocketAddr = "127.0.0.1:3478".parse()?;
+    let peer_addr: SocketAddr = "127.0.0.1:6000".parse()?;
+    let mut client = create_turn_test_client(server_addr)?;
+    let now = Instant::now();
+
+    // The first Allocate learns NONCE and REALM from a 401 response.
+    client.allocate()?;
+    let req = next_request(&mut client)?;
+    assert!(!req.contains(ATTR_NONCE));
+    let mut res = Message::new();
+    res.build(&[
+        Box::new(req.transaction_id),
+        Box::new(MessageType::new(METHOD_ALLOCATE, CLASS_ERROR_RESPONSE)),
+        Box::new(CODE_UNAUTHORIZED),
+        Box::new(Nonce::new(ATTR_NONCE, "nonce-1".to_owned())),
+        Box::new(Realm::new(ATTR_REALM, "example.org".to_owned())),
+    ])?;
+    respond(&mut client, now, server_addr, &res)?;
+
+    let req = next_request(&mut client)?;
+    assert_eq!(
+        "nonce-1",
+        Nonce::get_from_as(&req, ATTR_NONCE)?.text.as_str()
+    );
+    respond(&mut client, now, server_addr, &allocate_success(&req)?)?;
+    let relayed_addr = match client.poll_event() {
+        Some(Event::AllocateResponse(_, relayed_addr)) => relayed_addr,
+        _ => panic!("expected AllocateResponse"),
+    };
+    assert_eq!(
+        Some("nonce-1"),
+        client.auth_nonce.as_ref().map(|n| n.text.as_str())
+    );
+
+    // CreatePermission is authenticated with the cached nonce right away.
+    client.relay(relayed_addr)?.create_permission(peer_addr)?;
+    let req = next_request(&mut client)?;
+    assert_eq!(
+        MessageType::new(METHOD_CREATE_PERMISSION, CLASS_REQUEST),
+        req.typ
+    );
+    assert!(req.contains(ATTR_MESSAGE_INTEGRITY));
+    assert_eq!(
+        "nonce-1",
+        Nonce::get_from_as(&req, ATTR_NONCE)?.text.as_str()
+    );
+
+    // A new Allocate skips the anonymous attempt and retries once on 438.
+    let tid = client.allocate()?;
+    let req = next_request(&mut client)?;
+    assert_eq!(tid, req.transaction_id);
+    assert!(req.contains(ATTR_MESSAGE_INTEGRITY));
+    let mut res = Message::new();
+    res.build(&[
+        Box::new(req.transaction_id),
+        Box::new(MessageType::new(METHOD_ALLOCATE, CLASS_ERROR_RESPONSE)),
+        Box::new(ErrorCodeAttribute {
+            code: CODE_STALE_NONCE,
+            reason: b"Stale Nonce".to_vec(),
+        }),
+        Box::new(Nonce::new(ATTR_NONCE, "nonce-2".to_owned())),
+    ])?;
+    respond(&mut client, now, server_addr, &res)?;
+
+    let req = next_request(&mut client)?;
+    assert_ne!(tid, req.transaction_id);
+    assert_eq!(
+        "nonce-2",
+        Nonce::get_from_as(&req, ATTR_NONCE)?.text.as_str()
+    );
+    respond(&mut client, now, server_addr, &allocate_success(&req)?)?;
+    match client.poll_event() {
+        Some(Event::AllocateResponse(id, _)) => assert_eq!(tid, id),
+        _ => panic!("expected AllocateResponse"),
+    }
+
+    // Clearing the cache goes back to the anonymous attempt.
+    client.clear_auth_cache();
+    client.allocate()?;
+    let req = next_request(&mut client)?;
+    assert!(!req.contains(ATTR_NONCE));
+
+    client.close();
+
+    Ok(())
+}
+
+#[test]
+fn test_client_refreshes_permission_before_expiry() -> Result<()> {
+    let server_addr: SocketAddr = "127.0.0.1:3478".parse()?;
+    let peer_addr: SocketAddr = "127.0.0.1:6000".parse()?;
+    let mut client = create_turn_test_client(server_addr)?;
+
+    let start = Instant::now();
+    client.allocate()?;
+    let req = next_request(&mut client)?;
+    let mut res = Message::new();
+    res.build(&[
+        Box::new(req.transaction_id),
+        Box::new(MessageType::new(METHOD_ALLOCATE, CLASS_ERROR_RESPONSE)),
+        Box::new(CODE_UNAUTHORIZED),
+        Box::new(Nonce::new(ATTR_NONCE, "nonce".to_owned())),
+        Box::new(Realm::new(ATTR_REALM, "example.org".to_owned())),
+    ])?;
+    respond(&mut client, start, server_addr, &res)?;
+
+    let req = next_request(&mut client)?;
+    respond(&mut client, start, server_addr, &allocate_success(&req)?)?;
+    let relayed_addr = match client.poll_event() {
+        Some(Event::AllocateResponse(_, relayed_addr)) => relayed_addr,
+        _ => panic!("expected AllocateResponse"),
+    };
+
+    client.relay(relayed_addr)?.create_permission(peer_addr)?;
+    let req = next_request(&mut client)?;
+    assert_eq!(
+        None,
+        client.relay(relayed_addr)?.permission_expires_at(peer_addr)
+    );
+    let mut res = Message::new();
+    res.build(&[
+        Box::new(req.transaction_id),
+        Box::new(MessageType::new(
+            METHOD_CREATE_PERMISSION,
+            CLASS_SUCCESS_RESPONSE,
+        )),
+    ])?;
+    respond(&mut client, start, server_addr, &res)?;
+    match client.poll_event() {
+        Some(Event::CreatePermissionResponse(tid, addr)) => {
+            assert_eq!(req.transaction_id, tid);
+            assert_eq!(peer_addr, addr);
+        }
+        _ => panic!("expected CreatePermissionResponse"),
+    }
+    let expires_at = client
+        .relay(relayed_addr)?
+        .permission_expires_at(peer_addr)
+        .expect("permission should be granted");
+    assert_eq!(start + Duration::from_secs(300), expires_at);
+    assert!(client
+        .poll_timout()
+        .is_some_and(|to| to <= expires_at - Duration::from_secs(60)));
+
+    // Nothing is refreshed while the permission is far from expiring.
+    client.handle_timeout(start + Duration::from_secs(120));
+    assert!(client.poll_transmit().is_none());
+
+    // At 4:30 the permission expires within a minute and is refreshed.
+    client.handle_timeout(start + Duration::from_secs(270));
+    let req = next_request(&mut client)?;
+    assert_eq!(
+        MessageType::new(METHOD_CREATE_PERMISSION, CLASS_REQUEST),
+        req.typ
+    );
+    let mut addr = PeerAddress::default();
+    addr.get_from(&req)?;
+    assert_eq!(peer_addr, SocketAddr::new(addr.ip, addr.port));
+
+    // The refresh is started only once, later requests are its retransmissions.
+    client.handle_timeout(start + Duration::from_secs(271));
+    while let Some(transmit) = client.poll_transmit() {
+        let mut rtx = Message::new();
+        rtx.write(&transmit.message)?;
+        assert_eq!(req.transaction_id, rtx.transaction_id);
+    }
+
+    // Without a response the permission expires at 5:00.
+    client.handle_timeout(expires_at);
+    let mut expired = false;
+    while let Some(event) = client.poll_event() {
+        if let Event::CreatePermissionError(tid, err) = event {
+            assert_eq!(req.transaction_id, tid);
+            assert_eq!(Error::ErrPermissionExpired, err);
+            expired = true;
+        }
+    }
+    assert!(expired, "expected CreatePermissionError");
+    assert_eq!(
+        None,
+        client.relay(relayed_addr)?.permission_expires_at(peer_addr)
+    );
+    assert_eq!(
+        Err(Error::ErrNoPermission),
+        client.relay(relayed_addr)?.send_to(b"data", peer_addr)
+    );
+
+    client.close();
+
+    Ok(())
+}
+
+#[test]
+fn test_client_happy_eyeballs_binding_request() -> Result<()> {
+    let local_addr: SocketAddr = "[::]:5000".parse()?;
+    let ipv6_addr: SocketAddr = "[::1]:3478".parse()?;
+    let ipv4_addr: SocketAddr = "127.0.0.1:3478".parse()?;
+
+    let new_client = || -> Result<Client> {
+        let mut client = Client::new(ClientConfig {
+            stun_serv_addr: ipv6_addr.to_string(),
+            local_addr,
+            protocol: Protocol::UDP,
+            rto_in_ms: 10_000,
+            prefer_ipv6: true,
+            ..Default::default()
+        })?;
+        assert_eq!(Some(ipv6_addr), client.stun_serv_addr);
+        // both families are resolved from a host name in practice
+        client.stun_serv_ipv4_addr = Some(ipv4_addr);
+        Ok(client)
+    };
+    let binding_success = |req: &Message| -> Result<Message> {
+        let mut res = Message::new();
+        res.build(&[
+            Box::new(req.transaction_id),
+            Box::new(BINDING_SUCCESS),
+            Box::new(XorMappedAddress {
+                ip: "1.2.3.4".parse()?,
+                port: 5678,
+            }),
+        ])?;
+        Ok(res)
+    };
+
+    // The IPv6 address is tried first, IPv4 only after ipv6_timeout.
+    let mut client = new_client()?;
+    let start = Instant::now();
+    let tid = client.send_binding_request_at(start)?;
+    let (to, ipv6_req) = next_request_to(&mut client)?;
+    assert_eq!(ipv6_addr, to);
+    assert_eq!(tid, ipv6_req.transaction_id);
+    assert!(client.poll_transmit().is_none());
+    let fallback_at = client.poll_timout().expect("fallback should be scheduled");
+    assert_eq!(start + Duration::from_millis(300), fallback_at);
+
+    client.handle_timeout(fallback_at - Duration::from_millis(1));
+    assert!(client.poll_transmit().is_none());
+    client.handle_timeout(fallback_at);
+    let (to, ipv4_req) = next_request_to(&mut client)?;
+    assert_eq!(ipv4_addr, to);
+    assert_ne!(tid, ipv4_req.transaction_id);
+
+    // The first success wins and is reported with the IPv6 transaction id.
+    respond(
+        &mut client,
+        fallback_at,
+        ipv4_addr,
+        &binding_success(&ipv4_req)?,
+    )?;
+    match client.poll_event() {
+        Some(Event::BindingResponse(id, refl_addr)) => {
+            assert_eq!(tid, id);
+            assert_eq!("1.2.3.4:5678".parse::<SocketAddr>()?, refl_addr);
+        }
+        _ => panic!("expected BindingResponse"),
+    }
+    assert!(
+        client.tr_map.find(&tid).is_none(),
+        "IPv6 request is cancelled"
+    );
+    respond(
+        &mut client,
+        fallback_at,
+        ipv6_addr,
+        &binding_success(&ipv6_req)?,
+    )?;
+    assert!(client.poll_event().is_none());
+
+    // An IPv6 error response falls back to IPv4 right away.
+    let mut client = new_client()?;
+    let start = Instant::now();
+    let tid = client.send_binding_request_at(start)?;
+    let (_, ipv6_req) = next_request_to(&mut client)?;
+    let mut res = Message::new();
+    res.build(&[
+        Box::new(ipv6_req.transaction_id),
+        Box::new(MessageType::new(METHOD_BINDING, CLASS_ERROR_RESPONSE)),
+        Box::new(CODE_UNAUTHORIZED),
+    ])?;
+    respond(&mut client, start, ipv6_addr, &res)?;
+    assert!(client.poll_event().is_none());
+    let (to, ipv4_req) = next_request_to(&mut client)?;
+    assert_eq!(ipv4_addr, to);
+    respond(&mut client, start, ipv4_addr, &binding_success(&ipv4_req)?)?;
+    match client.poll_event() {
+        Some(Event::BindingResponse(id, _)) => assert_eq!(tid, id),
+        _ => panic!("expected BindingResponse"),
+    }
+
+    // An IPv6 response within ipv6_timeout makes the IPv4 request unnecessary.
+    let mut client = new_client()?;
+    let start = Instant::now();
+    let tid = client.send_binding_request_at(start)?;
+    let (_, ipv6_req) = next_request_to(&mut client)?;
+    respond(&mut client, start, ipv6_addr, &binding_success(&ipv6_req)?)?;
+    match client.poll_event() {
+        Some(Event::BindingResponse(id, _)) => assert_eq!(tid, id),
+        _ => panic!("expected BindingResponse"),
+    }
+    client.handle_timeout(start + Duration::from_secs(1));
+    assert!(client.poll_transmit().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_client_force_local_addr() -> Result<()> {
+    let server_addr: SocketAddr = "127.0.0.1:3478".parse()?;
+    let force_local_addr: SocketAddr = "127.0.0.2:0".parse()?;
+
+    let mut client = Client::new(ClientConfig {
+        stun_serv_addr: server_addr.to_string(),
+        turn_serv_addr: server_addr.to_string(),
+        local_addr: "0.0.0.0:5000".parse()?,
+        protocol: Protocol::UDP,
+        username: "user".to_owned(),
+        password: "pass".to_owned(),
+        force_local_addr: Some(force_local_addr),
+        ..Default::default()
+    })?;
+
+    client.send_binding_request()?;
+    client.send_binding_request_to("127.0.0.1:3479".parse()?)?;
+    client.allocate()?;
+
+    let mut transmits = 0;
+    while let Some(transmit) = client.poll_transmit() {
+        assert_eq!(force_local_addr, transmit.transport.local_addr);
+        transmits += 1;
+    }
+    assert_eq!(transmits, 3);
+
+    Ok(())
+}
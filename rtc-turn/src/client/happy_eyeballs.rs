@@ -0,0 +1,49 @@
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use stun::message::TransactionId;
+
+// Resolution Delay recommended by RFC 8305 Section 3
+pub(crate) const DEFAULT_IPV6_TIMEOUT: Duration = Duration::from_millis(300);
+
+// HappyEyeballsBindingRequest races a binding request sent to the IPv6 address of
+// the STUN server against one sent to its IPv4 address after a delay, and reports
+// the first successful response under the transaction id of the IPv6 request.
+pub(crate) struct HappyEyeballsBindingRequest {
+    pub(crate) transaction_id: TransactionId,
+    pub(crate) ipv4_addr: SocketAddr,
+    pub(crate) ipv4_transaction_id: Option<TransactionId>,
+    pub(crate) fallback_at: Instant,
+    pub(crate) ipv6_failed: bool,
+    pub(crate) ipv4_failed: bool,
+}
+
+impl HappyEyeballsBindingRequest {
+    pub(crate) fn new(
+        transaction_id: TransactionId,
+        ipv4_addr: SocketAddr,
+        fallback_at: Instant,
+    ) -> Self {
+        HappyEyeballsBindingRequest {
+            transaction_id,
+            ipv4_addr,
+            ipv4_transaction_id: None,
+            fallback_at,
+            ipv6_failed: false,
+            ipv4_failed: false,
+        }
+    }
+
+    pub(crate) fn contains(&self, tid: &TransactionId) -> bool {
+        self.transaction_id == *tid || self.ipv4_transaction_id.as_ref() == Some(tid)
+    }
+
+    // is_fallback_due returns whether the IPv4 request should be sent at now
+    pub(crate) fn is_fallback_due(&self, now: Instant) -> bool {
+        self.ipv4_transaction_id.is_none() && (self.ipv6_failed || self.fallback_at <= now)
+    }
+
+    pub(crate) fn is_failed(&self) -> bool {
+        self.ipv6_failed && self.ipv4_failed
+    }
+}
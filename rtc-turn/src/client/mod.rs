@@ -2,15 +2,16 @@
 mod client_test;
 
 pub mod binding;
+mod happy_eyeballs;
 pub mod permission;
 pub mod relay;
 pub mod transaction;
 
 use bytes::BytesMut;
-use log::{debug, trace};
+use log::{debug, trace, warn};
 use std::collections::{HashMap, VecDeque};
-use std::net::SocketAddr;
-use std::time::Instant;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
 
 use stun::attributes::*;
 use stun::integrity::*;
@@ -19,9 +20,11 @@ use stun::textattrs::*;
 use stun::xoraddr::*;
 
 use binding::*;
+use happy_eyeballs::*;
 use transaction::*;
 
 use crate::client::relay::{Relay, RelayState};
+use crate::proto::accesstoken::AccessToken;
 use crate::proto::chandata::*;
 use crate::proto::channum::ChannelNumber;
 use crate::proto::data::*;
@@ -33,7 +36,7 @@ use crate::proto::{PROTO_TCP, PROTO_UDP};
 use shared::error::{Error, Result};
 use shared::util::lookup_host;
 use shared::{Protocol, Transmit, TransportContext};
-use stun::error_code::ErrorCodeAttribute;
+use stun::error_code::{ErrorCodeAttribute, CODE_STALE_NONCE};
 use stun::fingerprint::FINGERPRINT;
 
 const DEFAULT_RTO_IN_MS: u64 = 200;
@@ -75,6 +78,20 @@ enum AllocateState {
 // 6: 31500 ms  +32000
 // -: 63500 ms  failed
 
+/// TurnCredentials selects how the client authenticates its Allocate requests.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub enum TurnCredentials {
+    /// Long-term credentials built from ClientConfig username, password and realm.
+    #[default]
+    LongTerm,
+    /// OAuth bearer token obtained from an authorization server (RFC 7635).
+    OAuthToken {
+        access_token: String,
+        token_type: String,
+        expires_in: u64,
+    },
+}
+
 /// ClientConfig is a bag of config parameters for Client.
 pub struct ClientConfig {
     pub stun_serv_addr: String, // STUN server address (e.g. "stun.abc.com:3478")
@@ -84,19 +101,60 @@ pub struct ClientConfig {
     pub username: String,
     pub password: String,
     pub realm: String,
+    pub credentials: TurnCredentials,
     pub software: String,
     pub rto_in_ms: u64,
+    /// prefer_ipv6 resolves both the IPv6 and the IPv4 address of the STUN server,
+    /// which requires a dual-stack local socket. Binding requests then go to the
+    /// IPv6 address first and to the IPv4 address after ipv6_timeout, and the
+    /// first successful response wins (RFC 8305).
+    pub prefer_ipv6: bool,
+    /// ipv6_timeout defaults to 300ms if zero
+    pub ipv6_timeout: Duration,
+    /// force_local_addr, when set, replaces local_addr as the source address of
+    /// every transmitted message, e.g. to pin it to one interface in tests.
+    pub force_local_addr: Option<SocketAddr>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            stun_serv_addr: String::new(),
+            turn_serv_addr: String::new(),
+            local_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+            protocol: Protocol::default(),
+            username: String::new(),
+            password: String::new(),
+            realm: String::new(),
+            credentials: TurnCredentials::default(),
+            software: String::new(),
+            rto_in_ms: 0,
+            prefer_ipv6: false,
+            ipv6_timeout: Duration::ZERO,
+            force_local_addr: None,
+        }
+    }
 }
 
 /// Client is a STUN client
 pub struct Client {
     stun_serv_addr: Option<SocketAddr>,
+    // IPv4 address of the STUN server raced against the IPv6 stun_serv_addr
+    stun_serv_ipv4_addr: Option<SocketAddr>,
+    ipv6_timeout: Duration,
+    happy_eyeballs: Vec<HappyEyeballsBindingRequest>,
     turn_serv_addr: Option<SocketAddr>,
     local_addr: SocketAddr,
     protocol: Protocol,
     username: Username,
     password: String,
     realm: Realm,
+    credentials: TurnCredentials,
+    third_party_authorization: Option<String>,
+    /// NONCE and REALM of the last successful authentication, re-used by
+    /// subsequent Allocate requests instead of an anonymous attempt.
+    auth_nonce: Option<Nonce>,
+    auth_realm: Option<Realm>,
     integrity: MessageIntegrity,
     software: Software,
     tr_map: TransactionMap,
@@ -111,32 +169,55 @@ pub struct Client {
 impl Client {
     /// new returns a new Client instance. listeningAddress is the address and port to listen on, default "0.0.0.0:0"
     pub fn new(config: ClientConfig) -> Result<Self> {
-        let stun_serv_addr = if config.stun_serv_addr.is_empty() {
-            None
+        let local_addr = config.force_local_addr.unwrap_or(config.local_addr);
+        let (stun_serv_addr, stun_serv_ipv4_addr) = if config.stun_serv_addr.is_empty() {
+            (None, None)
+        } else if config.prefer_ipv6 {
+            let ipv6_addr = lookup_host(false, config.stun_serv_addr.as_str()).ok();
+            let ipv4_addr = lookup_host(true, config.stun_serv_addr.as_str()).ok();
+            match (ipv6_addr, ipv4_addr) {
+                (Some(ipv6_addr), ipv4_addr) => (Some(ipv6_addr), ipv4_addr),
+                (None, Some(ipv4_addr)) => (Some(ipv4_addr), None),
+                (None, None) => return Err(Error::ErrAddressParseFailed),
+            }
         } else {
-            Some(lookup_host(
-                config.local_addr.is_ipv4(),
-                config.stun_serv_addr.as_str(),
-            )?)
+            (
+                Some(lookup_host(
+                    local_addr.is_ipv4(),
+                    config.stun_serv_addr.as_str(),
+                )?),
+                None,
+            )
         };
 
         let turn_serv_addr = if config.turn_serv_addr.is_empty() {
             None
         } else {
             Some(lookup_host(
-                config.local_addr.is_ipv4(),
+                local_addr.is_ipv4(),
                 config.turn_serv_addr.as_str(),
             )?)
         };
 
         Ok(Client {
             stun_serv_addr,
+            stun_serv_ipv4_addr,
+            ipv6_timeout: if config.ipv6_timeout != Duration::ZERO {
+                config.ipv6_timeout
+            } else {
+                DEFAULT_IPV6_TIMEOUT
+            },
+            happy_eyeballs: vec![],
             turn_serv_addr,
-            local_addr: config.local_addr,
+            local_addr,
             protocol: config.protocol,
             username: Username::new(ATTR_USERNAME, config.username),
             password: config.password,
             realm: Realm::new(ATTR_REALM, config.realm),
+            credentials: config.credentials,
+            third_party_authorization: None,
+            auth_nonce: None,
+            auth_realm: None,
             software: Software::new(ATTR_SOFTWARE, config.software),
             tr_map: TransactionMap::new(),
             binding_mgr: BindingManager::new(),
@@ -160,6 +241,13 @@ impl Client {
                 eto = Some(to);
             }
         }
+        for race in &self.happy_eyeballs {
+            if race.ipv4_transaction_id.is_none()
+                && (eto.is_none() || race.fallback_at < *eto.as_ref().unwrap())
+            {
+                eto = Some(race.fallback_at);
+            }
+        }
 
         #[allow(clippy::map_clone)]
         let relayed_addrs: Vec<SocketAddr> = self.relays.keys().map(|key| *key).collect();
@@ -180,6 +268,12 @@ impl Client {
 
     pub fn handle_timeout(&mut self, now: Instant) {
         self.tr_map.handle_timeout(now);
+        while let Some(event) = self.tr_map.poll_event() {
+            if let Some(event) = self.race_binding_event(event, now) {
+                self.events.push_back(event);
+            }
+        }
+        self.send_happy_eyeballs_fallbacks(now);
 
         #[allow(clippy::map_clone)]
         let relayed_addrs: Vec<SocketAddr> = self.relays.keys().map(|key| *key).collect();
@@ -200,13 +294,10 @@ impl Client {
     }
 
     pub fn handle_transmit(&mut self, msg: Transmit<BytesMut>) -> Result<()> {
-        self.handle_inbound(&msg.message[..], msg.transport.peer_addr)
+        self.handle_inbound(&msg.message[..], msg.transport.peer_addr, msg.now)
     }
 
     pub fn poll_event(&mut self) -> Option<Event> {
-        while let Some(event) = self.tr_map.poll_event() {
-            self.events.push_back(event);
-        }
         self.events.pop_front()
     }
 
@@ -217,7 +308,7 @@ impl Client {
     // Caller should check if the packet was handled by this client or not.
     // If not handled, it is assumed that the packet is application data.
     // If an error is returned, the caller should discard the packet regardless.
-    fn handle_inbound(&mut self, data: &[u8], from: SocketAddr) -> Result<()> {
+    fn handle_inbound(&mut self, data: &[u8], from: SocketAddr, now: Instant) -> Result<()> {
         // +-------------------+-------------------------------+
         // |   Return Values   |                               |
         // +-------------------+       Meaning / Action        |
@@ -237,7 +328,7 @@ impl Client {
         //  - Non-STUN message from the STUN server
 
         if is_message(data) {
-            self.handle_stun_message(data)
+            self.handle_stun_message(data, now)
         } else if ChannelData::is_channel_data(data) {
             self.handle_channel_data(data)
         } else if self.stun_serv_addr.is_some() && &from == self.stun_serv_addr.as_ref().unwrap() {
@@ -250,7 +341,7 @@ impl Client {
         }
     }
 
-    fn handle_stun_message(&mut self, data: &[u8]) -> Result<()> {
+    fn handle_stun_message(&mut self, data: &[u8], now: Instant) -> Result<()> {
         let mut msg = Message::new();
         msg.raw = data.to_vec();
         msg.decode()?;
@@ -298,29 +389,26 @@ impl Client {
         if let Some(tr) = self.tr_map.delete(&msg.transaction_id) {
             match msg.typ.method {
                 METHOD_BINDING => {
-                    if msg.typ.class == CLASS_ERROR_RESPONSE {
+                    let event = if msg.typ.class == CLASS_ERROR_RESPONSE {
                         let mut code = ErrorCodeAttribute::default();
                         let err = if code.get_from(&msg).is_err() {
                             Error::Other(format!("{}", msg.typ))
                         } else {
                             Error::Other(format!("{} (error {})", msg.typ, code))
                         };
-                        self.events
-                            .push_back(Event::BindingError(tr.transaction_id, err));
+                        Event::BindingError(tr.transaction_id, err)
                     } else {
                         let mut refl_addr = XorMappedAddress::default();
                         match refl_addr.get_from(&msg) {
-                            Ok(_) => {
-                                self.events.push_back(Event::BindingResponse(
-                                    tr.transaction_id,
-                                    ReflexiveAddr::new(refl_addr.ip, refl_addr.port),
-                                ));
-                            }
-                            Err(err) => {
-                                self.events
-                                    .push_back(Event::BindingError(tr.transaction_id, err));
-                            }
+                            Ok(_) => Event::BindingResponse(
+                                tr.transaction_id,
+                                ReflexiveAddr::new(refl_addr.ip, refl_addr.port),
+                            ),
+                            Err(err) => Event::BindingError(tr.transaction_id, err),
                         }
+                    };
+                    if let Some(event) = self.race_binding_event(event, now) {
+                        self.events.push_back(event);
                     }
                 }
                 METHOD_ALLOCATE => {
@@ -334,7 +422,7 @@ impl Client {
                             relayed_addr,
                             client: self,
                         };
-                        relay.handle_create_permission_response(msg, peer_addr)?;
+                        relay.handle_create_permission_response(msg, peer_addr, now)?;
                     }
                 }
                 METHOD_REFRESH => {
@@ -432,13 +520,99 @@ impl Client {
     /// send_binding_request sends a new STUN request to the STUN server
     /// return key to find out corresponding Event either BindingResponse or BindingRequestTimeout
     pub fn send_binding_request(&mut self) -> Result<TransactionId> {
-        if let Some(stun_serv_addr) = &self.stun_serv_addr {
-            self.send_binding_request_to(*stun_serv_addr)
+        self.send_binding_request_at(Instant::now())
+    }
+
+    /// send_binding_request_at is send_binding_request sent at now, which also schedules
+    /// the IPv4 fallback of a Happy Eyeballs race ipv6_timeout after now
+    pub fn send_binding_request_at(&mut self, now: Instant) -> Result<TransactionId> {
+        if let Some(stun_serv_addr) = self.stun_serv_addr {
+            let tid = self.send_binding_request_to(stun_serv_addr)?;
+            if let Some(ipv4_addr) = self.stun_serv_ipv4_addr {
+                self.happy_eyeballs.push(HappyEyeballsBindingRequest::new(
+                    tid,
+                    ipv4_addr,
+                    now + self.ipv6_timeout,
+                ));
+            }
+            Ok(tid)
         } else {
             Err(Error::ErrStunserverAddressNotSet)
         }
     }
 
+    // send_happy_eyeballs_fallbacks sends the IPv4 binding request of every race
+    // whose IPv6 request failed or has not been answered within ipv6_timeout
+    fn send_happy_eyeballs_fallbacks(&mut self, now: Instant) {
+        for i in 0..self.happy_eyeballs.len() {
+            if self.happy_eyeballs[i].is_fallback_due(now) {
+                let ipv4_addr = self.happy_eyeballs[i].ipv4_addr;
+                match self.send_binding_request_to(ipv4_addr) {
+                    Ok(tid) => self.happy_eyeballs[i].ipv4_transaction_id = Some(tid),
+                    Err(err) => {
+                        warn!("failed to send binding request to {}: {}", ipv4_addr, err);
+                        self.happy_eyeballs[i].ipv4_failed = true;
+                    }
+                }
+            }
+        }
+    }
+
+    // race_binding_event resolves the outcome of a binding request that is part of
+    // a Happy Eyeballs race: the first success is reported and the other request is
+    // cancelled, while a failure is only reported once both requests have failed.
+    fn race_binding_event(&mut self, event: Event, now: Instant) -> Option<Event> {
+        let tid = match &event {
+            Event::BindingResponse(tid, _)
+            | Event::BindingError(tid, _)
+            | Event::TransactionTimeout(tid) => *tid,
+            _ => return Some(event),
+        };
+        let index = match self
+            .happy_eyeballs
+            .iter()
+            .position(|race| race.contains(&tid))
+        {
+            Some(index) => index,
+            None => return Some(event),
+        };
+        let is_ipv6 = self.happy_eyeballs[index].transaction_id == tid;
+
+        if let Event::BindingResponse(_, refl_addr) = event {
+            let race = self.happy_eyeballs.remove(index);
+            let other = if is_ipv6 {
+                race.ipv4_transaction_id
+            } else {
+                Some(race.transaction_id)
+            };
+            if let Some(other) = other {
+                self.tr_map.delete(&other);
+            }
+            return Some(Event::BindingResponse(race.transaction_id, refl_addr));
+        }
+
+        let race = &mut self.happy_eyeballs[index];
+        if is_ipv6 {
+            race.ipv6_failed = true;
+        } else {
+            race.ipv4_failed = true;
+        }
+        if race.ipv4_transaction_id.is_none() {
+            // fall back to IPv4 right away
+            self.send_happy_eyeballs_fallbacks(now);
+        }
+
+        if self.happy_eyeballs[index].is_failed() {
+            let race = self.happy_eyeballs.remove(index);
+            Some(match event {
+                Event::BindingError(_, err) => Event::BindingError(race.transaction_id, err),
+                _ => Event::TransactionTimeout(race.transaction_id),
+            })
+        } else {
+            None
+        }
+    }
+
     // find_addr_by_channel_number returns a peer address associated with the
     // channel number on this UDPConn
     fn find_addr_by_channel_number(&self, ch_num: u16) -> Option<SocketAddr> {
@@ -493,6 +667,43 @@ impl Client {
     */
     /// Allocate sends a TURN allocation request to the given transport address
     pub fn allocate(&mut self) -> Result<TransactionId> {
+        if let TurnCredentials::OAuthToken { access_token, .. } = &self.credentials {
+            // With third-party authorization the token replaces the long-term
+            // credentials, so there is no anonymous attempt to learn the nonce.
+            let mut msg = Message::new();
+            msg.build(&[
+                Box::new(TransactionId::new()),
+                Box::new(MessageType::new(METHOD_ALLOCATE, CLASS_REQUEST)),
+                Box::new(RequestedTransport {
+                    protocol: if self.protocol == Protocol::UDP {
+                        PROTO_UDP
+                    } else {
+                        PROTO_TCP
+                    },
+                }),
+                Box::new(AccessToken(access_token.as_bytes().to_vec())),
+                Box::new(FINGERPRINT),
+            ])?;
+
+            debug!("client.Allocate call PerformTransaction with access token");
+            return Ok(self.perform_transaction(
+                &msg,
+                self.turn_server_addr()?,
+                TransactionType::AllocateRequest(Nonce::new(ATTR_NONCE, String::new())),
+            ));
+        }
+
+        if let (Some(nonce), Some(realm)) = (self.auth_nonce.clone(), self.auth_realm.clone()) {
+            // Already authenticated in this session, skip the anonymous attempt.
+            self.realm = realm;
+            debug!("client.Allocate call PerformTransaction with cached nonce");
+            return self.send_authenticated_allocate(
+                TransactionId::new(),
+                nonce.clone(),
+                TransactionType::AllocateRequest(nonce),
+            );
+        }
+
         let mut msg = Message::new();
         msg.build(&[
             Box::new(TransactionId::new()),
@@ -517,11 +728,45 @@ impl Client {
         Ok(tid)
     }
 
+    fn send_authenticated_allocate(
+        &mut self,
+        tid: TransactionId,
+        nonce: Nonce,
+        transaction_type: TransactionType,
+    ) -> Result<TransactionId> {
+        let mut msg = Message::new();
+        msg.build(&[
+            Box::new(tid),
+            Box::new(MessageType::new(METHOD_ALLOCATE, CLASS_REQUEST)),
+            Box::new(RequestedTransport {
+                protocol: if self.protocol == Protocol::UDP {
+                    PROTO_UDP
+                } else {
+                    PROTO_TCP
+                },
+            }),
+            Box::new(self.username.clone()),
+            Box::new(self.realm.clone()),
+            Box::new(nonce),
+            Box::new(self.integrity.clone()),
+            Box::new(FINGERPRINT),
+        ])?;
+
+        Ok(self.perform_transaction(&msg, self.turn_server_addr()?, transaction_type))
+    }
+
     fn handle_allocate_response(
         &mut self,
         response: Message,
         allocate_state: TransactionType,
     ) -> Result<()> {
+        let is_first_allocate_request =
+            matches!(allocate_state, TransactionType::AllocateRequest(_));
+        // the caller matches events against the id of its original request
+        let tid = match allocate_state {
+            TransactionType::AllocateRetry(_, original_tid) => original_tid,
+            _ => response.transaction_id,
+        };
         match allocate_state {
             TransactionType::AllocateAttempt => {
                 // Anonymous allocate failed, trying to authenticate.
@@ -548,48 +793,49 @@ impl Client {
                     self.password.clone(),
                 );
 
-                let mut msg = Message::new();
-
                 // make it same as allocate() return value so that client can retrieve it
                 // from Event::AllocateResponse
                 let mut tid = response.transaction_id;
                 tid.0[TRANSACTION_ID_SIZE - 1] = tid.0[TRANSACTION_ID_SIZE - 1].wrapping_add(1);
 
                 // Trying to authorize.
-                msg.build(&[
-                    Box::new(tid),
-                    Box::new(MessageType::new(METHOD_ALLOCATE, CLASS_REQUEST)),
-                    Box::new(RequestedTransport {
-                        protocol: if self.protocol == Protocol::UDP {
-                            PROTO_UDP
-                        } else {
-                            PROTO_TCP
-                        },
-                    }),
-                    Box::new(self.username.clone()),
-                    Box::new(self.realm.clone()),
-                    Box::new(nonce.clone()),
-                    Box::new(self.integrity.clone()),
-                    Box::new(FINGERPRINT),
-                ])?;
-
                 debug!("client.Allocate call PerformTransaction 2");
-                self.perform_transaction(
-                    &msg,
-                    self.turn_server_addr()?,
+                self.send_authenticated_allocate(
+                    tid,
+                    nonce.clone(),
                     TransactionType::AllocateRequest(nonce),
-                );
+                )?;
             }
-            TransactionType::AllocateRequest(nonce) => {
+            TransactionType::AllocateRequest(nonce) | TransactionType::AllocateRetry(nonce, _) => {
                 if response.typ.class == CLASS_ERROR_RESPONSE {
+                    // The server names the authorization server the client should
+                    // obtain a new access token from (RFC 7635 Section 6.1).
+                    if let Ok(server_name) = response.get(ATTR_THIRD_PARTY_AUTHORIZATION) {
+                        self.third_party_authorization =
+                            Some(String::from_utf8_lossy(&server_name).into_owned());
+                    }
+
                     let mut code = ErrorCodeAttribute::default();
                     let err = if code.get_from(&response).is_err() {
                         Error::Other(format!("{}", response.typ))
                     } else {
+                        if code.code == CODE_STALE_NONCE && is_first_allocate_request {
+                            if let Ok(nonce) = Nonce::get_from_as(&response, ATTR_NONCE) {
+                                // Retry once with the fresh nonce. A retransmission must not reuse
+                                // the transaction id, so the retry gets a new one (RFC 5389 S6).
+                                debug!("client.Allocate: 438, retrying with new nonce");
+                                self.auth_nonce = Some(nonce.clone());
+                                self.send_authenticated_allocate(
+                                    TransactionId::new(),
+                                    nonce.clone(),
+                                    TransactionType::AllocateRetry(nonce, tid),
+                                )?;
+                                return Ok(());
+                            }
+                        }
                         Error::Other(format!("{} (error {})", response.typ, code))
                     };
-                    self.events
-                        .push_back(Event::AllocateError(response.transaction_id, err));
+                    self.events.push_back(Event::AllocateError(tid, err));
                     return Ok(());
                 }
 
@@ -602,20 +848,44 @@ impl Client {
                 let mut lifetime = Lifetime::default();
                 lifetime.get_from(&response)?;
 
+                self.auth_nonce = Some(nonce.clone());
+                self.auth_realm = Some(self.realm.clone());
                 self.relays.insert(
                     relayed_addr,
                     RelayState::new(relayed_addr, self.integrity.clone(), nonce, lifetime.0),
                 );
-                self.events.push_back(Event::AllocateResponse(
-                    response.transaction_id,
-                    relayed_addr,
-                ));
+                self.events
+                    .push_back(Event::AllocateResponse(tid, relayed_addr));
             }
             _ => {}
         }
         Ok(())
     }
 
+    /// refresh_oauth_token replaces the access token used by subsequent requests,
+    /// since OAuth tokens expire during long-lived sessions.
+    pub fn refresh_oauth_token(&mut self, new_token: String) -> Result<()> {
+        if let TurnCredentials::OAuthToken { access_token, .. } = &mut self.credentials {
+            *access_token = new_token;
+            Ok(())
+        } else {
+            Err(Error::ErrOAuthCredentialsNotSet)
+        }
+    }
+
+    /// clear_auth_cache forgets the cached NONCE and REALM, so that the next
+    /// Allocate starts over with an anonymous request.
+    pub fn clear_auth_cache(&mut self) {
+        self.auth_nonce = None;
+        self.auth_realm = None;
+    }
+
+    /// third_party_authorization returns the authorization server name advertised
+    /// by the TURN server in its last Allocate error response, if any.
+    pub fn third_party_authorization(&self) -> Option<&str> {
+        self.third_party_authorization.as_deref()
+    }
+
     /// turn_server_addr return the TURN server address
     fn turn_server_addr(&self) -> Result<SocketAddr> {
         self.turn_serv_addr.ok_or(Error::ErrNilTurnSocket)
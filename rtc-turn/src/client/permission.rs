@@ -1,3 +1,7 @@
+use std::time::Instant;
+
+use stun::message::TransactionId;
+
 #[derive(Default, Copy, Clone, PartialEq, Debug)]
 pub(crate) enum PermState {
     #[default]
@@ -17,6 +21,8 @@ impl From<u8> for PermState {
 #[derive(Default)]
 pub(crate) struct Permission {
     st: PermState,
+    expires_at: Option<Instant>,
+    refresh_transaction_id: Option<TransactionId>,
 }
 
 impl Permission {
@@ -27,4 +33,20 @@ impl Permission {
     pub(crate) fn state(&self) -> PermState {
         self.st
     }
+
+    pub(crate) fn set_expires_at(&mut self, expires_at: Instant) {
+        self.expires_at = Some(expires_at);
+    }
+
+    pub(crate) fn expires_at(&self) -> Option<Instant> {
+        self.expires_at
+    }
+
+    pub(crate) fn set_refresh_transaction_id(&mut self, tid: Option<TransactionId>) {
+        self.refresh_transaction_id = tid;
+    }
+
+    pub(crate) fn refresh_transaction_id(&self) -> Option<TransactionId> {
+        self.refresh_transaction_id
+    }
 }
@@ -19,10 +19,11 @@ use crate::client::binding::BindingState;
 use crate::client::{Client, Event, RelayedAddr};
 use shared::error::{Error, Result};
 
-const PERM_REFRESH_INTERVAL: Duration = Duration::from_secs(120);
 // https://datatracker.ietf.org/doc/html/rfc8656#name-permissions-2
 // The Permission Lifetime MUST be 300 seconds (= 5 minutes).
 const PERM_LIFETIME: Duration = Duration::from_secs(300);
+// Permissions are refreshed when they expire within this margin
+const PERM_REFRESH_MARGIN: Duration = Duration::from_secs(60);
 const MAX_RETRY_ATTEMPTS: u16 = 3;
 
 // RelayState is a set of params use by Relay
@@ -33,7 +34,6 @@ pub(crate) struct RelayState {
     pub(crate) lifetime: Duration,
     perm_map: HashMap<SocketAddr, Permission>,
     refresh_alloc_timer: Instant,
-    refresh_perms_timer: Instant,
 }
 
 impl RelayState {
@@ -52,7 +52,6 @@ impl RelayState {
             lifetime,
             perm_map: HashMap::new(),
             refresh_alloc_timer: Instant::now().add(lifetime / 2),
-            refresh_perms_timer: Instant::now().add(PERM_REFRESH_INTERVAL),
         }
     }
 
@@ -94,47 +93,55 @@ impl<'a> Relay<'a> {
         }
     }
 
+    /// permission_expires_at returns when the permission for peer_addr expires,
+    /// or None if it has not been granted yet.
+    pub fn permission_expires_at(&self, peer_addr: SocketAddr) -> Option<Instant> {
+        self.client
+            .relays
+            .get(&self.relayed_addr)
+            .and_then(|relay| relay.perm_map.get(&peer_addr))
+            .and_then(|perm| perm.expires_at())
+    }
+
     pub(crate) fn poll_timeout(&self) -> Option<Instant> {
         if let Some(relay) = self.client.relays.get(&self.relayed_addr) {
-            if relay.refresh_alloc_timer < relay.refresh_perms_timer {
-                Some(relay.refresh_alloc_timer)
-            } else {
-                Some(relay.refresh_perms_timer)
+            let mut eto = relay.refresh_alloc_timer;
+            for perm in relay.perm_map.values() {
+                if let Some(expires_at) = perm.expires_at() {
+                    // wait for the expiration once the refresh has been sent
+                    let to = if perm.refresh_transaction_id().is_some() {
+                        expires_at
+                    } else {
+                        expires_at - PERM_REFRESH_MARGIN
+                    };
+                    if to < eto {
+                        eto = to;
+                    }
+                }
             }
+            Some(eto)
         } else {
             None
         }
     }
 
     pub(crate) fn handle_timeout(&mut self, now: Instant) {
-        let (refresh_alloc_timer, refresh_perms_timer) = if let Some(relay) =
-            self.client.relays.get_mut(&self.relayed_addr)
-        {
-            let refresh_alloc_timer = if relay.refresh_alloc_timer <= now {
-                relay.refresh_alloc_timer = relay.refresh_alloc_timer.add(relay.lifetime / 2);
-                Some(relay.lifetime)
+        let refresh_alloc_timer =
+            if let Some(relay) = self.client.relays.get_mut(&self.relayed_addr) {
+                if relay.refresh_alloc_timer <= now {
+                    relay.refresh_alloc_timer = relay.refresh_alloc_timer.add(relay.lifetime / 2);
+                    Some(relay.lifetime)
+                } else {
+                    None
+                }
             } else {
                 None
             };
 
-            let refresh_perms_timer = if relay.refresh_perms_timer <= now {
-                relay.refresh_perms_timer = relay.refresh_perms_timer.add(PERM_REFRESH_INTERVAL);
-                true
-            } else {
-                false
-            };
-
-            (refresh_alloc_timer, refresh_perms_timer)
-        } else {
-            (None, false)
-        };
-
         if let Some(lifetime) = refresh_alloc_timer {
             let _ = self.refresh_allocation(lifetime);
         }
-        if refresh_perms_timer {
-            let _ = self.refresh_permissions();
-        }
+        let _ = self.refresh_permissions(now);
     }
 
     pub fn send_to(&mut self, p: &[u8], peer_addr: SocketAddr) -> Result<()> {
@@ -283,6 +290,7 @@ impl<'a> Relay<'a> {
         &mut self,
         res: Message,
         peer_addr_opt: Option<SocketAddr>,
+        now: Instant,
     ) -> Result<()> {
         if let Some(relay) = self.client.relays.get_mut(&self.relayed_addr) {
             if res.typ.class == CLASS_ERROR_RESPONSE {
@@ -292,11 +300,21 @@ impl<'a> Relay<'a> {
                     Error::Other(format!("{}", res.typ))
                 } else if code.code == CODE_STALE_NONCE {
                     relay.set_nonce_from_msg(&res);
+                    self.client.auth_nonce = Some(relay.nonce.clone());
                     Error::ErrTryAgain
                 } else {
                     Error::Other(format!("{} (error {})", res.typ, code))
                 };
                 if let Some(peer_addr) = peer_addr_opt {
+                    if let Some(perm) = relay.perm_map.get_mut(&peer_addr) {
+                        // a refresh rejected for a stale nonce is retried with the new one
+                        if err == Error::ErrTryAgain
+                            && perm.refresh_transaction_id() == Some(res.transaction_id)
+                        {
+                            perm.set_refresh_transaction_id(None);
+                            return Ok(());
+                        }
+                    }
                     self.client
                         .events
                         .push_back(Event::CreatePermissionError(res.transaction_id, err));
@@ -305,6 +323,8 @@ impl<'a> Relay<'a> {
             } else if let Some(peer_addr) = peer_addr_opt {
                 if let Some(perm) = relay.perm_map.get_mut(&peer_addr) {
                     perm.set_state(PermState::Permitted);
+                    perm.set_expires_at(now + PERM_LIFETIME);
+                    perm.set_refresh_transaction_id(None);
                     self.client
                         .events
                         .push_back(Event::CreatePermissionResponse(
@@ -356,6 +376,7 @@ impl<'a> Relay<'a> {
                     Err(Error::Other(format!("{}", res.typ)))
                 } else if code.code == CODE_STALE_NONCE {
                     relay.set_nonce_from_msg(&res);
+                    self.client.auth_nonce = Some(relay.nonce.clone());
                     //Error::ErrTryAgain
                     Ok(())
                 } else {
@@ -376,19 +397,52 @@ impl<'a> Relay<'a> {
         }
     }
 
-    fn refresh_permissions(&mut self) -> Result<()> {
-        if let Some(relay) = self.client.relays.get_mut(&self.relayed_addr) {
-            #[allow(clippy::map_clone)]
-            let addrs: Vec<SocketAddr> = relay.perm_map.keys().map(|addr| *addr).collect();
-            if addrs.is_empty() {
-                debug!("no permission to refresh");
-                return Ok(());
+    // refresh_permissions re-sends CreatePermission for every permission expiring
+    // within PERM_REFRESH_MARGIN, and drops the ones that expired without a response.
+    fn refresh_permissions(&mut self, now: Instant) -> Result<()> {
+        let (addrs, expired) = if let Some(relay) = self.client.relays.get_mut(&self.relayed_addr) {
+            let mut addrs = vec![];
+            let mut expired = vec![];
+            for (addr, perm) in &relay.perm_map {
+                if let Some(expires_at) = perm.expires_at() {
+                    if expires_at <= now {
+                        expired.push((*addr, perm.refresh_transaction_id().unwrap_or_default()));
+                    } else if perm.refresh_transaction_id().is_none()
+                        && expires_at <= now + PERM_REFRESH_MARGIN
+                    {
+                        addrs.push(*addr);
+                    }
+                }
             }
-            let _ = self.create_permissions(&addrs, None)?;
-            Ok(())
+            for (addr, _) in &expired {
+                relay.perm_map.remove(addr);
+            }
+            (addrs, expired)
         } else {
-            Err(Error::ErrConnClosed)
+            return Err(Error::ErrConnClosed);
+        };
+
+        for (addr, tid) in expired {
+            debug!("permission for {} expired", addr);
+            self.client.events.push_back(Event::CreatePermissionError(
+                tid,
+                Error::ErrPermissionExpired,
+            ));
         }
+
+        for addr in addrs {
+            let tid = self.create_permissions(&[addr], Some(addr))?;
+            if let Some(perm) = self
+                .client
+                .relays
+                .get_mut(&self.relayed_addr)
+                .and_then(|relay| relay.perm_map.get_mut(&addr))
+            {
+                perm.set_refresh_transaction_id(Some(tid));
+            }
+        }
+
+        Ok(())
     }
 
     fn channel_bind(
@@ -444,6 +498,7 @@ impl<'a> Relay<'a> {
                     Err(Error::Other(format!("{}", res.typ)))
                 } else if code.code == CODE_STALE_NONCE {
                     relay.set_nonce_from_msg(&res);
+                    self.client.auth_nonce = Some(relay.nonce.clone());
                     Err(Error::ErrTryAgain)
                 } else {
                     Err(Error::Other(format!("{} (error {})", res.typ, code)))
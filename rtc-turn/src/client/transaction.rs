@@ -18,6 +18,9 @@ pub(crate) enum TransactionType {
     BindingRequest,
     AllocateAttempt,
     AllocateRequest(TextAttribute),
+    /// AllocateRetry is sent with a fresh transaction id after a 438 Stale Nonce and
+    /// remembers the id of the original request, which is reported in events.
+    AllocateRetry(TextAttribute, TransactionId),
     CreatePermissionRequest(RelayedAddr, Option<SocketAddr>),
     RefreshRequest(RelayedAddr),
     ChannelBindRequest(RelayedAddr, SocketAddr),
@@ -161,8 +164,13 @@ impl TransactionMap {
         }
 
         for key in keys {
-            self.tr_map.remove(&key);
-            self.events.push_back(Event::TransactionTimeout(key));
+            if let Some(tr) = self.tr_map.remove(&key) {
+                let tid = match tr.transaction_type {
+                    TransactionType::AllocateRetry(_, original_tid) => original_tid,
+                    _ => key,
+                };
+                self.events.push_back(Event::TransactionTimeout(tid));
+            }
         }
     }
 
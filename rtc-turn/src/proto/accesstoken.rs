@@ -0,0 +1,41 @@
+#[cfg(test)]
+mod accesstoken_test;
+
+use shared::error::{Error, Result};
+use stun::attributes::*;
+use stun::message::*;
+
+// AccessToken represents ACCESS-TOKEN attribute.
+//
+// The ACCESS-TOKEN attribute contains the self-contained token that the
+// client obtained from the authorization server. The token is opaque to
+// the client: it is made of a nonce length, a nonce and an encrypted block
+// that only the STUN server is able to decrypt. The client includes this
+// attribute in its requests in place of long-term credentials.
+//
+// RFC 7635 Section 6.2
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct AccessToken(pub Vec<u8>);
+
+impl Setter for AccessToken {
+    // AddTo adds ACCESS-TOKEN to message.
+    fn add_to(&self, m: &mut Message) -> Result<()> {
+        if self.0.is_empty() {
+            return Err(Error::ErrAttributeSizeInvalid);
+        }
+        m.add(ATTR_ACCESS_TOKEN, &self.0);
+        Ok(())
+    }
+}
+
+impl Getter for AccessToken {
+    // GetFrom decodes ACCESS-TOKEN from message.
+    fn get_from(&mut self, m: &Message) -> Result<()> {
+        let v = m.get(ATTR_ACCESS_TOKEN)?;
+        if v.is_empty() {
+            return Err(Error::ErrAttributeSizeInvalid);
+        }
+        self.0 = v;
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+use super::*;
+use stun::checks::*;
+
+#[test]
+fn test_access_token() -> Result<()> {
+    let mut m = Message::new();
+    let tk = AccessToken(b"\x00\x04noncencrypted-block".to_vec());
+    tk.add_to(&mut m)?;
+    m.write_header();
+
+    //"HandleErr"
+    {
+        let bad_tk = AccessToken(vec![]);
+        if let Err(err) = bad_tk.add_to(&mut m) {
+            assert!(
+                is_attr_size_invalid(&err),
+                "IsAttrSizeInvalid should be true"
+            );
+        } else {
+            panic!("expected error, but got ok");
+        }
+    }
+
+    //"GetFrom"
+    {
+        let mut decoded = Message::new();
+        decoded.write(&m.raw)?;
+        let mut tok = AccessToken::default();
+        tok.get_from(&decoded)?;
+        assert_eq!(tok, tk, "Decoded {tok:?}, expected {tk:?}");
+
+        //"HandleErr"
+        {
+            let mut m = Message::new();
+            let mut handle = AccessToken::default();
+            if let Err(err) = handle.get_from(&m) {
+                assert_eq!(
+                    Error::ErrAttributeNotFound,
+                    err,
+                    "{err} should be not found"
+                );
+            } else {
+                panic!("expected error, but got ok");
+            }
+            m.add(ATTR_ACCESS_TOKEN, &[]);
+            if let Err(err) = handle.get_from(&m) {
+                assert!(
+                    is_attr_size_invalid(&err),
+                    "IsAttrSizeInvalid should be true"
+                );
+            } else {
+                panic!("expected error, got ok");
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod proto_test;
 
+pub mod accesstoken;
 pub mod addr;
 pub mod chandata;
 pub mod channum;
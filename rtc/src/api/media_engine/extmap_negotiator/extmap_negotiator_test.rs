@@ -0,0 +1,115 @@
+use super::*;
+use crate::api::media_engine::MediaEngine;
+use crate::rtp_transceiver::rtp_codec::{RTCRtpHeaderExtensionCapability, RTPCodecType};
+use crate::rtp_transceiver::rtp_transceiver_direction::RTCRtpTransceiverDirection;
+use sdp::description::session::SessionDescription;
+use shared::error::Result;
+use std::io::Cursor;
+
+#[test]
+fn test_extmap_negotiator_assign_ids() {
+    let extensions: Vec<String> = (0..16).map(|i| format!("urn:test:{i}")).collect();
+
+    let mut negotiator = ExtmapNegotiator::new();
+    let ids = negotiator.assign_ids(&extensions);
+    assert_eq!(ids.len(), 14);
+    assert_eq!(ids["urn:test:0"], 1);
+    assert_eq!(ids["urn:test:13"], 14);
+    assert!(!ids.contains_key("urn:test:14"));
+
+    // already registered extensions keep their IDs
+    assert_eq!(negotiator.assign_ids(&extensions[3..5]), ids);
+}
+
+#[test]
+fn test_extmap_negotiator_reconcile() {
+    let mut negotiator = ExtmapNegotiator::new();
+    negotiator.assign_ids(&[
+        sdp::extmap::SDES_MID_URI.to_owned(),
+        sdp::extmap::AUDIO_LEVEL_URI.to_owned(),
+    ]);
+
+    let ids = negotiator.reconcile(&[
+        (7, sdp::extmap::SDES_MID_URI),
+        (3, sdp::extmap::ABS_SEND_TIME_URI),
+        (1, sdp::extmap::AUDIO_LEVEL_URI),
+    ]);
+    assert_eq!(
+        ids,
+        HashMap::from([
+            (sdp::extmap::SDES_MID_URI.to_owned(), 7),
+            (sdp::extmap::AUDIO_LEVEL_URI.to_owned(), 1),
+        ])
+    );
+}
+
+#[test]
+fn test_extmap_negotiator_reconcile_follows_sdp_order() {
+    let mut negotiator = ExtmapNegotiator::new();
+    negotiator.assign_ids(&[
+        sdp::extmap::SDES_MID_URI.to_owned(),
+        sdp::extmap::AUDIO_LEVEL_URI.to_owned(),
+    ]);
+
+    // the first mapping of an extension or an ID wins
+    let ids = negotiator.reconcile(&[
+        (4, sdp::extmap::SDES_MID_URI),
+        (5, sdp::extmap::SDES_MID_URI),
+        (4, sdp::extmap::AUDIO_LEVEL_URI),
+        (6, sdp::extmap::AUDIO_LEVEL_URI),
+    ]);
+    assert_eq!(
+        ids,
+        HashMap::from([
+            (sdp::extmap::SDES_MID_URI.to_owned(), 4),
+            (sdp::extmap::AUDIO_LEVEL_URI.to_owned(), 6),
+        ])
+    );
+}
+
+#[test]
+fn test_answer_uses_offer_extension_ids() -> Result<()> {
+    const OFFER: &str = "v=0
+o=- 4596489990601351948 2 IN IP4 127.0.0.1
+s=-
+t=0 0
+m=audio 9 UDP/TLS/RTP/SAVPF 111
+a=extmap:7 urn:ietf:params:rtp-hdrext:sdes:mid
+a=extmap:3 http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time
+a=extmap:12 urn:ietf:params:rtp-hdrext:ssrc-audio-level
+a=rtpmap:111 opus/48000/2
+";
+
+    let mut m = MediaEngine::default();
+    m.register_default_codecs()?;
+    for extension in [sdp::extmap::AUDIO_LEVEL_URI, sdp::extmap::SDES_MID_URI] {
+        m.register_header_extension(
+            RTCRtpHeaderExtensionCapability {
+                uri: extension.to_owned(),
+            },
+            RTPCodecType::Audio,
+            None,
+        )?;
+    }
+
+    let mut reader = Cursor::new(OFFER.as_bytes());
+    m.update_from_remote_description(&SessionDescription::unmarshal(&mut reader)?)?;
+
+    let params =
+        m.get_rtp_parameters_by_kind(RTPCodecType::Audio, RTCRtpTransceiverDirection::Sendrecv);
+    let mut ids: Vec<(isize, &str)> = params
+        .header_extensions
+        .iter()
+        .map(|ext| (ext.id, ext.uri.as_str()))
+        .collect();
+    ids.sort();
+    assert_eq!(
+        ids,
+        vec![
+            (7, sdp::extmap::SDES_MID_URI),
+            (12, sdp::extmap::AUDIO_LEVEL_URI),
+        ]
+    );
+
+    Ok(())
+}
@@ -0,0 +1,52 @@
+#[cfg(test)]
+mod extmap_negotiator_test;
+
+use std::collections::HashMap;
+
+/// Highest ID of the one-byte RTP header extension format, RFC 8285 Section 4.2
+const MAX_ONE_BYTE_EXT_ID: u8 = 14;
+
+/// ExtmapNegotiator assigns the IDs of the a=extmap lines of an offer and adopts
+/// the IDs of the remote offer when answering, as the answerer must not remap them.
+#[derive(Default, Debug, Clone)]
+pub struct ExtmapNegotiator {
+    extensions: Vec<String>,
+}
+
+impl ExtmapNegotiator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// assign_ids registers extensions and gives them the one-byte IDs 1-14 in the
+    /// order they are listed. Extensions past the 14th get no ID.
+    pub fn assign_ids(&mut self, extensions: &[String]) -> HashMap<String, u8> {
+        for extension in extensions {
+            if !self.extensions.contains(extension) {
+                self.extensions.push(extension.clone());
+            }
+        }
+
+        self.extensions
+            .iter()
+            .zip(1..=MAX_ONE_BYTE_EXT_ID)
+            .map(|(extension, id)| (extension.clone(), id))
+            .collect()
+    }
+
+    /// reconcile returns the extensions of the remote extension map that were
+    /// registered with assign_ids, keeping the remote IDs. remote is in SDP order:
+    /// an extension or ID that appears again later is ignored.
+    pub fn reconcile(&self, remote: &[(u8, &str)]) -> HashMap<String, u8> {
+        let mut ids = HashMap::new();
+        for &(id, uri) in remote {
+            if self.extensions.iter().any(|extension| extension == uri)
+                && !ids.contains_key(uri)
+                && !ids.values().any(|&v| v == id)
+            {
+                ids.insert(uri.to_owned(), id);
+            }
+        }
+        ids
+    }
+}
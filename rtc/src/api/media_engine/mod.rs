@@ -1,6 +1,8 @@
 //TODO:#[cfg(test)]
 //TODO:mod media_engine_test;
 
+pub mod extmap_negotiator;
+
 use std::collections::HashMap;
 use std::ops::Range;
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -583,6 +585,15 @@ impl MediaEngine {
         Ok(())
     }
 
+    /// header_extension_uris returns the URIs of the registered header extensions,
+    /// in registration order
+    pub(crate) fn header_extension_uris(&self) -> Vec<String> {
+        self.header_extensions
+            .iter()
+            .map(|ext| ext.uri.clone())
+            .collect()
+    }
+
     pub(crate) fn push_codecs(&mut self, codecs: Vec<RTCRtpCodecParameters>, typ: RTPCodecType) {
         for codec in codecs {
             if typ == RTPCodecType::Audio {
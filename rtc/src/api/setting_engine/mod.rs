@@ -7,13 +7,14 @@ use std::time::Duration;
 
 use dtls::extension::extension_use_srtp::SrtpProtectionProfile;
 /*TODO:use ice::agent::agent_config::{InterfaceFilterFn, IpFilterFn};
-use ice::mdns::MulticastDnsMode;
 use ice::udp_network::UDPNetwork;*/
+use ice::mdns::MulticastDnsMode;
 use ice::network_type::NetworkType;
 
 use crate::constants::RECEIVE_MTU;
 use crate::transport::dtls_transport::dtls_role::DTLSRole;
 use crate::transport::ice_transport::ice_candidate_type::RTCIceCandidateType;
+use sdp::description::session::SdpParseMode;
 use shared::error::{Error, Result};
 
 #[derive(Default, Debug, Clone)]
@@ -40,8 +41,9 @@ pub struct Candidates {
     pub ip_filter: Arc<Option<IpFilterFn>>,*/
     pub nat_1to1_ips: Vec<String>,
     pub nat_1to1_ip_candidate_type: RTCIceCandidateType,
-    /*TODO:pub multicast_dns_mode: MulticastDnsMode,
-    pub multicast_dns_host_name: String,*/
+    pub multicast_dns_mode: MulticastDnsMode,
+    pub multicast_dns_host_name: String,
+    pub mdns_obfuscation: bool,
     pub username_fragment: String,
     pub password: String,
 }
@@ -73,6 +75,7 @@ pub struct SettingEngine {
     pub(crate) srtp_protection_profiles: Vec<SrtpProtectionProfile>,
     pub(crate) receive_mtu: usize,
     pub(crate) mid_generator: Option<Arc<dyn Fn(isize) -> String + Send + Sync>>,
+    pub(crate) sdp_parse_mode: SdpParseMode,
 }
 
 impl fmt::Debug for SettingEngine {
@@ -106,6 +109,7 @@ impl fmt::Debug for SettingEngine {
             .field("disable_media_engine_copy", &self.disable_media_engine_copy)
             .field("srtp_protection_profiles", &self.srtp_protection_profiles)
             .field("receive_mtu", &self.receive_mtu)
+            .field("sdp_parse_mode", &self.sdp_parse_mode)
             .finish()
     }
 }
@@ -246,8 +250,8 @@ impl SettingEngine {
         Ok(())
     }
 
-    /*TODO:/// set_ice_multicast_dns_mode controls if ice queries and generates mDNS ICE Candidates
-    pub fn set_ice_multicast_dns_mode(&mut self, multicast_dns_mode: ice::mdns::MulticastDnsMode) {
+    /// set_ice_multicast_dns_mode controls if ice queries and generates mDNS ICE Candidates
+    pub fn set_ice_multicast_dns_mode(&mut self, multicast_dns_mode: MulticastDnsMode) {
         self.candidates.multicast_dns_mode = multicast_dns_mode
     }
 
@@ -256,7 +260,13 @@ impl SettingEngine {
     /// undefined behavior
     pub fn set_multicast_dns_host_name(&mut self, host_name: String) {
         self.candidates.multicast_dns_host_name = host_name;
-    }*/
+    }
+
+    /// set_ice_mdns_obfuscation controls if the IP addresses of host candidates are
+    /// replaced with random `.local` mDNS names in the SDP to prevent IP leakage
+    pub fn set_ice_mdns_obfuscation(&mut self, mdns_obfuscation: bool) {
+        self.candidates.mdns_obfuscation = mdns_obfuscation;
+    }
 
     /// set_ice_credentials sets a staic uFrag/uPwd to be used by ice
     /// This is useful if you want to do signalless WebRTC session, or having a reproducible environment with static credentials
@@ -347,4 +357,11 @@ impl SettingEngine {
     pub fn set_mid_generator(&mut self, f: impl Fn(isize) -> String + Send + Sync + 'static) {
         self.mid_generator = Some(Arc::new(f));
     }
+
+    /// set_sdp_parse_mode sets how set_remote_description parses the remote SDP.
+    /// SdpParseMode::Lenient skips unrecognized or malformed optional lines instead
+    /// of rejecting the description. Strict parsing is the default.
+    pub fn set_sdp_parse_mode(&mut self, sdp_parse_mode: SdpParseMode) {
+        self.sdp_parse_mode = sdp_parse_mode;
+    }
 }
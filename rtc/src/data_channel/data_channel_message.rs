@@ -1,4 +1,4 @@
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 
 /// DataChannelMessage represents a message received from the
 /// data channel. IsString will be set to true if the incoming
@@ -9,3 +9,18 @@ pub struct DataChannelMessage {
     pub is_string: bool,
     pub data: Bytes,
 }
+
+/// RTCDataChannelMessage is a message delivered to the application. Complete
+/// carries a whole message, while Partial carries one piece of a message that
+/// exceeded the threshold set with RTCPeerConnection::enable_partial_delivery,
+/// delivered as its DATA chunks arrive. is_last is set on the final piece of
+/// each message.
+#[derive(Debug, Clone)]
+pub enum RTCDataChannelMessage {
+    Complete(DataChannelMessage),
+    Partial {
+        is_string: bool,
+        data: BytesMut,
+        is_last: bool,
+    },
+}
@@ -9,6 +9,8 @@ pub mod data_channel_state;
 use crate::api::setting_engine::SettingEngine;
 use crate::stats::stats_collector::StatsCollector;
 use crate::stats::{DataChannelStats, StatsReportType};
+use crate::transport::sctp_transport::RTCSctpTransport;
+use shared::error::Result;
 use std::sync::Arc;
 //use bytes::Bytes;
 //use data_channel_message::*;
@@ -22,6 +24,9 @@ use std::time::SystemTime;
 //todo:use sctp::stream::OnBufferedAmountLowFn;
 //TODO:use crate::transports::sctp_transport::RTCSctpTransport;
 
+/// RTCDataChannelId is the SCTP stream identifier of a DataChannel
+pub type RTCDataChannelId = u16;
+
 /// message size limit for Chromium
 const DATA_CHANNEL_BUFFER_SIZE: u16 = u16::MAX;
 
@@ -45,9 +50,10 @@ pub struct RTCDataChannel {
     pub(crate) max_retransmits: u16,
     pub(crate) protocol: String,
     pub(crate) negotiated: bool,
-    pub(crate) id: u16,
+    pub(crate) id: RTCDataChannelId,
     pub(crate) ready_state: RTCDataChannelState,
     pub(crate) buffered_amount_low_threshold: usize,
+    pub(crate) partial_delivery_threshold: Option<usize>,
     pub(crate) detach_called: bool,
     //TODO: pub(crate) sctp_transport: Mutex<Option<Weak<RTCSctpTransport>>>,
     pub(crate) data_channel: Option<DataChannel>,
@@ -427,25 +433,25 @@ impl RTCDataChannel {
         self.max_retransmits
     }
 
-    /// protocol represents the name of the sub-protocol used with this
-    /// DataChannel.
-    pub fn protocol(&self) -> &str {
-        self.protocol.as_str()
-    }
-
     /// negotiated represents whether this DataChannel was negotiated by the
     /// application (true), or not (false).
     pub fn negotiated(&self) -> bool {
         self.negotiated
     }*/
 
+    /// protocol represents the name of the sub-protocol used with this
+    /// DataChannel.
+    pub fn protocol(&self) -> &str {
+        self.protocol.as_str()
+    }
+
     /// ID represents the ID for this DataChannel. The value is initially
     /// null, which is what will be returned if the ID was not provided at
     /// channel creation time, and the DTLS role of the SCTP transport has not
     /// yet been negotiated. Otherwise, it will return the ID that was either
     /// selected by the script or generated. After the ID is set to a non-null
     /// value, it will not change.
-    pub fn id(&self) -> u16 {
+    pub fn id(&self) -> RTCDataChannelId {
         self.id
     }
 
@@ -510,4 +516,21 @@ impl RTCDataChannel {
     pub(crate) fn set_ready_state(&mut self, r: RTCDataChannelState) {
         self.ready_state = r;
     }
+
+    /// close resets the outgoing SCTP stream of this DataChannel. The channel stays
+    /// Closing until the peer confirms the reset, or becomes Closed right away if it
+    /// was never opened.
+    pub(crate) fn close(&mut self, sctp_transport: &mut RTCSctpTransport) -> Result<()> {
+        if self.ready_state == RTCDataChannelState::Closed {
+            return Ok(());
+        }
+
+        if let Some(stream_id) = self.data_channel.as_ref().map(|dc| dc.stream_identifier()) {
+            self.set_ready_state(RTCDataChannelState::Closing);
+            sctp_transport.reset_stream(stream_id)
+        } else {
+            self.set_ready_state(RTCDataChannelState::Closed);
+            Ok(())
+        }
+    }
 }
@@ -29,13 +29,13 @@ fn match_range(lower: u8, upper: u8, buf: &[u8]) -> bool {
 ///              +----------------+
 /// match_dtls is a MatchFunc that accepts packets with the first byte in [20..63]
 /// as defied in RFC7983
-fn match_dtls(b: &[u8]) -> bool {
+pub(crate) fn match_dtls(b: &[u8]) -> bool {
     match_range(20, 63, b)
 }
 
 /// match_srtp is a MatchFunc that accepts packets with the first byte in [128..191]
 /// as defied in RFC7983
-fn match_srtp(b: &[u8]) -> bool {
+pub(crate) fn match_srtp(b: &[u8]) -> bool {
     match_range(128, 191, b)
 }
 
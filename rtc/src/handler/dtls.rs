@@ -4,7 +4,7 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use crate::api::setting_engine::SettingEngine;
-use crate::messages::{DTLSMessage, RTCEvent, RTCMessage};
+use crate::messages::{DTLSMessage, RTCEvent, RTCMessage, RTPMessage};
 use crate::transport::dtls_transport::RTCDtlsTransport;
 use dtls::endpoint::EndpointEvent;
 use dtls::extension::extension_use_srtp::SrtpProtectionProfile;
@@ -12,6 +12,8 @@ use dtls::state::State;
 use log::{debug, error, warn};
 use shared::error::{Error, Result};
 use shared::handler::RTCHandler;
+use shared::marshal::{Marshal, Unmarshal};
+use shared::util::is_rtcp;
 use shared::Transmit;
 use srtp::option::{srtcp_replay_protection, srtp_no_replay_protection, srtp_replay_protection};
 use srtp::protection_profile::ProtectionProfile;
@@ -105,6 +107,24 @@ impl RTCHandler for RTCDtlsTransport {
                     }
                 }
             };
+        } else if let RTCMessage::Rtp(RTPMessage::Raw(srtp_message)) = msg.message {
+            debug!("recv srtp RAW {:?}", msg.transport.peer_addr);
+            let context = self
+                .remote_srtp_context
+                .as_mut()
+                .ok_or(Error::ErrDtlsTransportNotStarted)?;
+            let message = if is_rtcp(&srtp_message) {
+                let mut decrypted = context.decrypt_rtcp(&srtp_message)?;
+                RTPMessage::Rtcp(rtcp::packet::unmarshal(&mut decrypted)?)
+            } else {
+                let mut decrypted = context.decrypt_rtp(&srtp_message)?;
+                RTPMessage::Rtp(rtp::packet::Packet::unmarshal(&mut decrypted)?)
+            };
+            self.routs.push_back(Transmit {
+                now: msg.now,
+                transport: msg.transport,
+                message: RTCMessage::Rtp(message),
+            });
         } else {
             // Bypass
             debug!("bypass dtls read {:?}", msg.transport.peer_addr);
@@ -145,6 +165,17 @@ impl RTCHandler for RTCDtlsTransport {
                     Err(err)
                 }
             }
+        } else if let RTCMessage::Rtp(message @ (RTPMessage::Rtp(_) | RTPMessage::Rtcp(_))) =
+            msg.message
+        {
+            debug!("send srtp {:?}", msg.transport.peer_addr);
+            let message = self.protect_srtp(message)?;
+            self.wouts.push_back(Transmit {
+                now: msg.now,
+                transport: msg.transport,
+                message: RTCMessage::Rtp(RTPMessage::Raw(message)),
+            });
+            Ok(())
         } else {
             // Bypass
             debug!("Bypass dtls write {:?}", msg.transport.peer_addr);
@@ -204,6 +235,23 @@ impl RTCHandler for RTCDtlsTransport {
     }
 }
 
+impl RTCDtlsTransport {
+    /// protect_srtp marshals an RTP packet or RTCP compound packet and protects it
+    /// with the local SRTP context
+    fn protect_srtp(&mut self, message: RTPMessage) -> Result<BytesMut> {
+        let context = self
+            .local_srtp_context
+            .as_mut()
+            .ok_or(Error::ErrDtlsTransportNotStarted)?;
+        match message {
+            // already protected
+            RTPMessage::Raw(message) => Ok(message),
+            RTPMessage::Rtp(packet) => context.encrypt_rtp(&packet.marshal()?),
+            RTPMessage::Rtcp(packets) => context.encrypt_rtcp(&rtcp::packet::marshal(&packets)?),
+        }
+    }
+}
+
 pub(crate) fn update_srtp_contexts(
     state: &State,
     setting_engine: &Arc<SettingEngine>,
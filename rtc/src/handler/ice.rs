@@ -1,4 +1,5 @@
-use crate::messages::{RTCEvent, RTCMessage, STUNMessage};
+use crate::handler::demuxer::{match_dtls, match_srtp};
+use crate::messages::{DTLSMessage, RTCEvent, RTCMessage, RTPMessage, STUNMessage};
 use crate::transport::ice_transport::ice_candidate_pair::RTCIceCandidatePair;
 use crate::transport::ice_transport::{IceTransportEvent, RTCIceTransport};
 use bytes::BytesMut;
@@ -31,8 +32,16 @@ impl RTCHandler for RTCIceTransport {
                 warn!("try_read got error {}", err);
                 return Err(err);
             }
+            // answers to binding requests are sent without waiting for a timeout
+            self.queue_agent_transmits();
+            self.queue_agent_reads();
         } else {
             debug!("bypass StunHandler read for {}", msg.transport.peer_addr);
+            if let Some(data) = data_message(&msg.message) {
+                self.gatherer
+                    .agent
+                    .record_data_packet(&msg.transport, false, data.len(), msg.now);
+            }
             self.routs.push_back(msg)
         }
 
@@ -58,6 +67,25 @@ impl RTCHandler for RTCIceTransport {
             });
         } else {
             debug!("bypass StunHandler write for {}", msg.transport.peer_addr);
+            if let Some(data) = data_message(&msg.message) {
+                self.gatherer
+                    .agent
+                    .record_data_packet(&msg.transport, true, data.len(), msg.now);
+                if self
+                    .gatherer
+                    .agent
+                    .is_relayed_addr(msg.transport.local_addr)
+                {
+                    // data of a relay candidate goes through its TURN server
+                    self.gatherer.agent.send_relayed(
+                        msg.transport.local_addr,
+                        data,
+                        msg.transport.peer_addr,
+                    )?;
+                    self.queue_agent_transmits();
+                    return Ok(());
+                }
+            }
             self.wouts.push_back(msg);
         }
 
@@ -81,6 +109,41 @@ impl RTCHandler for RTCIceTransport {
                         )),
                     ))
                 }
+                Event::RemoteCandidateNeedsResolution(name, candidate) => {
+                    Some(RTCEvent::IceTransportEvent(
+                        IceTransportEvent::OnRemoteCandidateNeedsResolution(
+                            name,
+                            Box::new((&*candidate).into()),
+                        ),
+                    ))
+                }
+                Event::CandidatePairStateChange(local, remote, state) => Some(
+                    RTCEvent::IceTransportEvent(IceTransportEvent::OnCandidatePairStateChange(
+                        Box::new(RTCIceCandidatePair::new(
+                            (&*local).into(),
+                            (&*remote).into(),
+                        )),
+                        state,
+                    )),
+                ),
+                Event::NominationChange(local, remote) => Some(RTCEvent::IceTransportEvent(
+                    IceTransportEvent::OnNominationChange(Box::new(RTCIceCandidatePair::new(
+                        (&*local).into(),
+                        (&*remote).into(),
+                    ))),
+                )),
+                Event::IceRestartCompleted => Some(RTCEvent::IceTransportEvent(
+                    IceTransportEvent::OnIceRestartCompleted,
+                )),
+                Event::CandidateGathered(candidate) => Some(RTCEvent::IceTransportEvent(
+                    IceTransportEvent::OnCandidateGathered(Box::new((&*candidate).into())),
+                )),
+                Event::GatheringFailed(url) => Some(RTCEvent::IceTransportEvent(
+                    IceTransportEvent::OnGatheringFailed(url),
+                )),
+                Event::UrlNeedsResolution(url) => Some(RTCEvent::IceTransportEvent(
+                    IceTransportEvent::OnUrlNeedsResolution(url),
+                )),
             }
         } else {
             None
@@ -91,13 +154,7 @@ impl RTCHandler for RTCIceTransport {
     fn handle_timeout(&mut self, now: Instant) -> Result<()> {
         let mut try_timeout = || -> Result<()> {
             self.gatherer.agent.handle_timeout(now);
-            while let Some(transmit) = self.gatherer.agent.poll_transmit() {
-                self.wouts.push_back(Transmit {
-                    now: transmit.now,
-                    transport: transmit.transport,
-                    message: RTCMessage::Stun(STUNMessage::Raw(transmit.message)),
-                });
-            }
+            self.queue_agent_transmits();
 
             Ok(())
         };
@@ -115,3 +172,48 @@ impl RTCHandler for RTCIceTransport {
         self.gatherer.agent.poll_timeout()
     }
 }
+
+impl RTCIceTransport {
+    /// Queues the STUN messages of the ICE agent to be written.
+    fn queue_agent_transmits(&mut self) {
+        while let Some(transmit) = self.gatherer.agent.poll_transmit() {
+            self.wouts.push_back(Transmit {
+                now: transmit.now,
+                transport: transmit.transport,
+                message: RTCMessage::Stun(STUNMessage::Raw(transmit.message)),
+            });
+        }
+    }
+
+    /// Queues the DTLS and SRTP datagrams the ICE agent received through a TURN server to be read.
+    fn queue_agent_reads(&mut self) {
+        while let Some(transmit) = self.gatherer.agent.poll_read() {
+            let message = if match_dtls(&transmit.message) {
+                RTCMessage::Dtls(DTLSMessage::Raw(transmit.message))
+            } else if match_srtp(&transmit.message) {
+                RTCMessage::Rtp(RTPMessage::Raw(transmit.message))
+            } else {
+                debug!(
+                    "drop relayed packet of unknown type from {}",
+                    transmit.transport.peer_addr
+                );
+                continue;
+            };
+            self.routs.push_back(Transmit {
+                now: transmit.now,
+                transport: transmit.transport,
+                message,
+            });
+        }
+    }
+}
+
+/// Returns a raw DTLS or SRTP datagram passing through the ICE transport.
+fn data_message(message: &RTCMessage) -> Option<&BytesMut> {
+    match message {
+        RTCMessage::Dtls(DTLSMessage::Raw(message)) | RTCMessage::Rtp(RTPMessage::Raw(message)) => {
+            Some(message)
+        }
+        _ => None,
+    }
+}
@@ -1,4 +1,42 @@
 pub mod demuxer;
 pub mod dtls;
 pub mod ice;
+pub mod pacer;
+pub mod rtcp;
+pub mod rtcp_bye;
 pub mod sctp;
+
+use crate::messages::RTCMessage;
+use shared::error::Result;
+use shared::handler::RTCHandler;
+use shared::Transmit;
+
+/// read_through passes inbound messages to handler and returns what it forwards
+/// to the next inbound handler
+pub(crate) fn read_through<H>(
+    handler: &mut H,
+    msgs: Vec<Transmit<RTCMessage>>,
+) -> Result<Vec<Transmit<RTCMessage>>>
+where
+    H: RTCHandler<Rin = RTCMessage, Rout = RTCMessage>,
+{
+    for msg in msgs {
+        handler.handle_read(msg)?;
+    }
+    Ok(std::iter::from_fn(|| handler.poll_read()).collect())
+}
+
+/// write_through passes outbound messages to handler and returns what it forwards
+/// to the next outbound handler, including messages it queued on its own
+pub(crate) fn write_through<H>(
+    handler: &mut H,
+    msgs: Vec<Transmit<RTCMessage>>,
+) -> Result<Vec<Transmit<RTCMessage>>>
+where
+    H: RTCHandler<Win = RTCMessage, Wout = RTCMessage>,
+{
+    for msg in msgs {
+        handler.handle_write(msg)?;
+    }
+    Ok(std::iter::from_fn(|| handler.poll_write()).collect())
+}
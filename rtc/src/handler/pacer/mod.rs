@@ -0,0 +1,160 @@
+#[cfg(test)]
+mod pacer_test;
+
+use crate::messages::{RTCMessage, RTPMessage};
+use shared::error::Result;
+use shared::handler::RTCHandler;
+use shared::marshal::MarshalSize;
+use shared::Transmit;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Default interval between two releases of paced packets
+pub const DEFAULT_PACING_INTERVAL: Duration = Duration::from_millis(5);
+
+/// RtpPacer smooths outgoing RTP, e.g. the burst of packets of a video keyframe,
+/// by releasing it at target_bitrate_bps instead of all at once, which avoids
+/// building up queues at network bottlenecks. Other messages, including RTCP,
+/// are passed through immediately.
+///
+/// Paced packets are released from handle_timeout, which should be driven at
+/// the instants returned by poll_timeout.
+pub struct RtpPacer {
+    target_bitrate_bps: u64,
+    interval: Duration,
+    // earliest instant the next enqueued packet may be sent at
+    next_send: Option<Instant>,
+    last_release: Option<Instant>,
+    scheduled: VecDeque<(Instant, Transmit<RTCMessage>)>,
+    routs: VecDeque<Transmit<RTCMessage>>,
+    wouts: VecDeque<Transmit<RTCMessage>>,
+}
+
+impl RtpPacer {
+    pub fn new(target_bitrate_bps: u64, interval: Duration) -> Self {
+        Self {
+            target_bitrate_bps,
+            interval,
+            next_send: None,
+            last_release: None,
+            scheduled: VecDeque::new(),
+            routs: VecDeque::new(),
+            wouts: VecDeque::new(),
+        }
+    }
+
+    /// target_bitrate_bps returns the rate RTP is released at, 0 disables pacing
+    pub fn target_bitrate_bps(&self) -> u64 {
+        self.target_bitrate_bps
+    }
+
+    /// set_target_bitrate_bps updates the pacing rate, e.g. from a congestion
+    /// controller's estimate. It applies to packets written afterwards.
+    pub fn set_target_bitrate_bps(&mut self, target_bitrate_bps: u64) {
+        self.target_bitrate_bps = target_bitrate_bps;
+    }
+
+    /// interval returns the interval between two releases of paced packets
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// queued_packets returns the number of RTP packets waiting to be released
+    pub fn queued_packets(&self) -> usize {
+        self.scheduled.len()
+    }
+
+    fn send_duration(&self, size: usize) -> Duration {
+        Duration::from_nanos(
+            (size as u64 * 8).saturating_mul(1_000_000_000) / self.target_bitrate_bps,
+        )
+    }
+
+    fn release(&mut self, now: Instant) {
+        while let Some((send_at, _)) = self.scheduled.front() {
+            if *send_at > now {
+                break;
+            }
+            if let Some((_, mut msg)) = self.scheduled.pop_front() {
+                msg.now = now;
+                self.wouts.push_back(msg);
+            }
+        }
+    }
+}
+
+impl RTCHandler for RtpPacer {
+    type Ein = ();
+    type Eout = ();
+    type Rin = RTCMessage;
+    type Rout = RTCMessage;
+    type Win = RTCMessage;
+    type Wout = RTCMessage;
+
+    fn handle_read(&mut self, msg: Transmit<Self::Rin>) -> Result<()> {
+        self.routs.push_back(msg);
+        Ok(())
+    }
+
+    fn poll_read(&mut self) -> Option<Transmit<Self::Rout>> {
+        self.routs.pop_front()
+    }
+
+    fn handle_write(&mut self, msg: Transmit<Self::Win>) -> Result<()> {
+        let size = match &msg.message {
+            RTCMessage::Rtp(RTPMessage::Rtp(packet)) if self.target_bitrate_bps != 0 => {
+                packet.marshal_size()
+            }
+            _ => {
+                self.wouts.push_back(msg);
+                return Ok(());
+            }
+        };
+
+        let msg_now = msg.now;
+        let send_at = match self.next_send {
+            Some(next_send) if next_send > msg_now => next_send,
+            _ => msg_now,
+        };
+        self.next_send = Some(send_at + self.send_duration(size));
+        self.scheduled.push_back((send_at, msg));
+
+        // the pacing budget allows sending right away
+        if send_at == msg_now {
+            self.release(msg_now);
+        }
+
+        Ok(())
+    }
+
+    fn poll_write(&mut self) -> Option<Transmit<Self::Wout>> {
+        self.wouts.pop_front()
+    }
+
+    fn handle_timeout(&mut self, now: Instant) -> Result<()> {
+        if let Some(last_release) = self.last_release {
+            if now < last_release + self.interval {
+                return Ok(());
+            }
+        }
+        if self
+            .scheduled
+            .front()
+            .is_some_and(|(send_at, _)| *send_at <= now)
+        {
+            self.last_release = Some(now);
+            self.release(now);
+        }
+        Ok(())
+    }
+
+    fn poll_timeout(&mut self) -> Option<Instant> {
+        let (send_at, _) = self.scheduled.front()?;
+        match self.last_release {
+            Some(last_release) if last_release + self.interval > *send_at => {
+                Some(last_release + self.interval)
+            }
+            _ => Some(*send_at),
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use super::*;
+use bytes::Bytes;
+use rtcp::receiver_report::ReceiverReport;
+use shared::{Protocol, TransportContext};
+
+fn transport() -> TransportContext {
+    TransportContext {
+        local_addr: "127.0.0.1:5000".parse().unwrap(),
+        peer_addr: "127.0.0.1:6000".parse().unwrap(),
+        protocol: Protocol::UDP,
+        ecn: None,
+    }
+}
+
+fn write_rtp(pacer: &mut RtpPacer, now: Instant, sequence_number: u16) -> Result<()> {
+    // 12 bytes header + 1188 bytes payload
+    pacer.handle_write(Transmit {
+        now,
+        transport: transport(),
+        message: RTCMessage::Rtp(RTPMessage::Rtp(rtp::packet::Packet {
+            header: rtp::header::Header {
+                version: 2,
+                sequence_number,
+                ..Default::default()
+            },
+            payload: Bytes::from(vec![0u8; 1188]),
+        })),
+    })
+}
+
+fn poll_sequence_numbers(pacer: &mut RtpPacer) -> Vec<u16> {
+    let mut sequence_numbers = vec![];
+    while let Some(msg) = pacer.poll_write() {
+        match msg.message {
+            RTCMessage::Rtp(RTPMessage::Rtp(packet)) => {
+                sequence_numbers.push(packet.header.sequence_number)
+            }
+            other => panic!("expected RTP, got {other:?}"),
+        }
+    }
+    sequence_numbers
+}
+
+#[test]
+fn test_rtp_pacer_releases_at_target_bitrate() -> Result<()> {
+    // 960 kbps sends a 1200 bytes packet every 10 ms
+    let mut pacer = RtpPacer::new(960_000, Duration::from_millis(10));
+
+    let start = Instant::now();
+    for sequence_number in 0..10 {
+        write_rtp(&mut pacer, start, sequence_number)?;
+    }
+    assert_eq!(vec![0], poll_sequence_numbers(&mut pacer));
+    assert_eq!(9, pacer.queued_packets());
+
+    for step in 1..10u16 {
+        let now = start + Duration::from_millis(10) * step as u32;
+        assert_eq!(Some(now), pacer.poll_timeout());
+
+        pacer.handle_timeout(now - Duration::from_millis(1))?;
+        assert!(pacer.poll_write().is_none(), "released too early");
+
+        pacer.handle_timeout(now)?;
+        assert_eq!(vec![step], poll_sequence_numbers(&mut pacer));
+    }
+    assert_eq!(0, pacer.queued_packets());
+    assert_eq!(None, pacer.poll_timeout());
+
+    Ok(())
+}
+
+#[test]
+fn test_rtp_pacer_releases_bursts_per_interval() -> Result<()> {
+    let mut pacer = RtpPacer::new(960_000, Duration::from_millis(20));
+
+    let start = Instant::now();
+    for sequence_number in 0..5 {
+        write_rtp(&mut pacer, start, sequence_number)?;
+    }
+    assert_eq!(vec![0], poll_sequence_numbers(&mut pacer));
+
+    pacer.handle_timeout(start + Duration::from_millis(10))?;
+    assert_eq!(vec![1], poll_sequence_numbers(&mut pacer));
+
+    // the next release waits for the pacing interval and catches up
+    assert_eq!(
+        Some(start + Duration::from_millis(30)),
+        pacer.poll_timeout()
+    );
+    pacer.handle_timeout(start + Duration::from_millis(20))?;
+    assert!(pacer.poll_write().is_none());
+    pacer.handle_timeout(start + Duration::from_millis(30))?;
+    assert_eq!(vec![2, 3], poll_sequence_numbers(&mut pacer));
+
+    Ok(())
+}
+
+#[test]
+fn test_rtp_pacer_passes_through_rtcp() -> Result<()> {
+    let mut pacer = RtpPacer::new(960_000, DEFAULT_PACING_INTERVAL);
+
+    let now = Instant::now();
+    write_rtp(&mut pacer, now, 0)?;
+    write_rtp(&mut pacer, now, 1)?;
+    assert_eq!(vec![0], poll_sequence_numbers(&mut pacer));
+
+    pacer.handle_write(Transmit {
+        now,
+        transport: transport(),
+        message: RTCMessage::Rtp(RTPMessage::Rtcp(vec![Box::<ReceiverReport>::default()])),
+    })?;
+    assert!(matches!(
+        pacer.poll_write().map(|msg| msg.message),
+        Some(RTCMessage::Rtp(RTPMessage::Rtcp(_)))
+    ));
+    assert_eq!(1, pacer.queued_packets());
+
+    Ok(())
+}
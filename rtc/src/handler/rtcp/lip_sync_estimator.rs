@@ -0,0 +1,72 @@
+use rtcp::sender_report::SenderReport;
+use std::time::Duration;
+
+/// LipSyncEstimator maps the RTP timestamps of a stream to the sender's wall clock
+/// using the NTP/RTP timestamp pair of its latest Sender Report, as described in
+/// RFC 3550 Section 6.4.1. Comparing the estimates of an audio and a video stream
+/// from the same sender gives their playout offset for lip-sync.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LipSyncEstimator {
+    pub ssrc: u32,
+    pub last_sr_ntp: u64,
+    pub last_sr_rtp_ts: u32,
+    pub clock_rate: u32,
+}
+
+impl LipSyncEstimator {
+    pub fn new(ssrc: u32, clock_rate: u32) -> Self {
+        Self {
+            ssrc,
+            clock_rate,
+            ..Default::default()
+        }
+    }
+
+    /// update_from_sr records the reference point of a Sender Report about ssrc
+    pub fn update_from_sr(&mut self, sr: &SenderReport) {
+        if sr.ssrc == self.ssrc {
+            self.last_sr_ntp = sr.ntp_time;
+            self.last_sr_rtp_ts = sr.rtp_time;
+        }
+    }
+
+    /// estimate_wall_clock returns the NTP wall clock time, as a Duration since the
+    /// NTP epoch, at which the sender captured rtp_ts. It is None until a Sender
+    /// Report has been received.
+    pub fn estimate_wall_clock(&self, rtp_ts: u32) -> Option<Duration> {
+        if self.last_sr_ntp == 0 || self.clock_rate == 0 {
+            return None;
+        }
+
+        // the RTP timestamp may be before the SR reference point or wrap around
+        let ticks = rtp_ts.wrapping_sub(self.last_sr_rtp_ts) as i32;
+        let elapsed = Duration::from_secs_f64(ticks.unsigned_abs() as f64 / self.clock_rate as f64);
+        let reference = ntp_to_duration(self.last_sr_ntp);
+        if ticks >= 0 {
+            reference.checked_add(elapsed)
+        } else {
+            reference.checked_sub(elapsed)
+        }
+    }
+}
+
+/// av_offset_ms returns by how many milliseconds audio_ts was captured after
+/// video_ts, negative if it was captured before.
+pub fn av_offset_ms(
+    audio: &LipSyncEstimator,
+    audio_ts: u32,
+    video: &LipSyncEstimator,
+    video_ts: u32,
+) -> Option<i32> {
+    let audio_time = audio.estimate_wall_clock(audio_ts)?.as_secs_f64();
+    let video_time = video.estimate_wall_clock(video_ts)?.as_secs_f64();
+    Some(((audio_time - video_time) * 1000.0).round() as i32)
+}
+
+/// ntp_to_duration converts a 64-bit NTP timestamp, 32.32 fixed point seconds,
+/// to a Duration since the NTP epoch.
+fn ntp_to_duration(ntp_time: u64) -> Duration {
+    let secs = ntp_time >> 32;
+    let nanos = ((ntp_time & 0xFFFF_FFFF) * 1_000_000_000) >> 32;
+    Duration::new(secs, nanos as u32)
+}
@@ -0,0 +1,266 @@
+#[cfg(test)]
+mod rtcp_test;
+
+pub mod lip_sync_estimator;
+pub mod rtt_estimator;
+
+use crate::messages::{RTCMessage, RTPMessage};
+use lip_sync_estimator::LipSyncEstimator;
+use log::debug;
+use rtcp::header::PacketType;
+use rtcp::packet::Packet;
+use rtcp::receiver_report::ReceiverReport;
+use rtcp::sender_report::SenderReport;
+use rtt_estimator::RttEstimator;
+use shared::error::Result;
+use shared::handler::RTCHandler;
+use shared::marshal::MarshalSize;
+use shared::{Transmit, TransportContext};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Default cap of a compound RTCP packet, which keeps it below common path MTUs
+pub const DEFAULT_MAX_COMPOUND_SIZE: usize = 1200;
+
+struct PendingRtcp {
+    now: Instant,
+    transport: TransportContext,
+    packets: Vec<Box<dyn Packet>>,
+}
+
+/// RtcpCompoundSender aggregates the RTCP packets written within a single event loop
+/// iteration, e.g. NACK, TWCC and SR pushed by independent interceptors, and flushes
+/// them as compound packets as required by RFC 3550 Section 6.1.
+pub struct RtcpCompoundSender {
+    max_compound_size: usize,
+    reduced_size: bool,
+    local_ssrc: u32,
+    pending: Vec<PendingRtcp>,
+    rtt_estimator: RttEstimator,
+    rtt_measurements: VecDeque<(u32, Duration)>,
+    lip_sync_estimators: HashMap<u32, LipSyncEstimator>,
+    routs: VecDeque<Transmit<RTCMessage>>,
+    wouts: VecDeque<Transmit<RTCMessage>>,
+}
+
+impl Default for RtcpCompoundSender {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_COMPOUND_SIZE)
+    }
+}
+
+impl RtcpCompoundSender {
+    pub fn new(max_compound_size: usize) -> Self {
+        Self {
+            max_compound_size,
+            reduced_size: false,
+            local_ssrc: rand::random::<u32>(),
+            pending: vec![],
+            rtt_estimator: RttEstimator::new(),
+            rtt_measurements: VecDeque::new(),
+            lip_sync_estimators: HashMap::new(),
+            routs: VecDeque::new(),
+            wouts: VecDeque::new(),
+        }
+    }
+
+    /// max_compound_size returns the cap of a flushed compound packet in bytes
+    pub fn max_compound_size(&self) -> usize {
+        self.max_compound_size
+    }
+
+    /// set_reduced_size enables reduced-size RTCP (RFC 5506) once a=rtcp-rsize has been
+    /// negotiated, so compound packets are no longer prefixed with a SR/RR.
+    pub fn set_reduced_size(&mut self, reduced_size: bool) {
+        self.reduced_size = reduced_size;
+    }
+
+    /// reduced_size returns whether reduced-size RTCP is allowed
+    pub fn reduced_size(&self) -> bool {
+        self.reduced_size
+    }
+
+    /// set_local_ssrc sets the SSRC that reports the Receiver Report prefixed to
+    /// compound packets without a SR/RR, e.g. the SSRC of a local RTP sender.
+    pub fn set_local_ssrc(&mut self, local_ssrc: u32) {
+        self.local_ssrc = local_ssrc;
+    }
+
+    /// local_ssrc returns the SSRC of the prefixed Receiver Reports
+    pub fn local_ssrc(&self) -> u32 {
+        self.local_ssrc
+    }
+
+    /// rtt_estimator returns the RTT estimated from the Sender Reports sent and
+    /// the Receiver Reports received through this handler
+    pub fn rtt_estimator(&self) -> &RttEstimator {
+        &self.rtt_estimator
+    }
+
+    /// poll_rtt_measurement returns the next round trip time measured from a reception
+    /// report, along with the SSRC of the local sender the report is about
+    pub fn poll_rtt_measurement(&mut self) -> Option<(u32, Duration)> {
+        self.rtt_measurements.pop_front()
+    }
+
+    /// add_lip_sync_estimator starts tracking the Sender Reports received about the
+    /// remote stream ssrc, whose RTP clock runs at clock_rate
+    pub fn add_lip_sync_estimator(&mut self, ssrc: u32, clock_rate: u32) {
+        self.lip_sync_estimators
+            .insert(ssrc, LipSyncEstimator::new(ssrc, clock_rate));
+    }
+
+    /// lip_sync_estimator returns the estimator added for the remote stream ssrc
+    pub fn lip_sync_estimator(&self, ssrc: u32) -> Option<&LipSyncEstimator> {
+        self.lip_sync_estimators.get(&ssrc)
+    }
+
+    fn enqueue(
+        &mut self,
+        now: Instant,
+        transport: TransportContext,
+        packets: Vec<Box<dyn Packet>>,
+    ) {
+        let index = if let Some(index) = self
+            .pending
+            .iter()
+            .position(|pending| pending.transport == transport)
+        {
+            index
+        } else {
+            self.pending.push(PendingRtcp {
+                now,
+                transport,
+                packets: vec![],
+            });
+            self.pending.len() - 1
+        };
+
+        let pending = &mut self.pending[index];
+        for packet in packets {
+            // interceptors may report the same feedback independently, drop duplicates
+            if pending.packets.iter().any(|p| p.equal(&*packet)) {
+                debug!(
+                    "drop duplicated RTCP packet {}",
+                    packet.header().packet_type
+                );
+                continue;
+            }
+            pending.packets.push(packet);
+        }
+    }
+
+    fn flush(&mut self) {
+        for pending in std::mem::take(&mut self.pending) {
+            let (mut reports, mut others): (Vec<_>, Vec<_>) = pending
+                .packets
+                .into_iter()
+                .partition(|p| is_report(p.as_ref()));
+            // SR/RR must lead the compound packet
+            reports.append(&mut others);
+
+            let mut compound: Vec<Box<dyn Packet>> = vec![];
+            let mut size = 0;
+            for packet in reports {
+                let packet_size = packet.marshal_size();
+                if !compound.is_empty() && size + packet_size > self.max_compound_size {
+                    self.push_compound(pending.now, pending.transport, compound);
+                    compound = vec![];
+                    size = 0;
+                }
+                if compound.is_empty() && !self.reduced_size && !is_report(packet.as_ref()) {
+                    let rr = ReceiverReport {
+                        ssrc: self.local_ssrc,
+                        ..Default::default()
+                    };
+                    size += rr.marshal_size();
+                    compound.push(Box::new(rr));
+                }
+                size += packet_size;
+                compound.push(packet);
+            }
+            if !compound.is_empty() {
+                self.push_compound(pending.now, pending.transport, compound);
+            }
+        }
+    }
+
+    fn push_compound(
+        &mut self,
+        now: Instant,
+        transport: TransportContext,
+        packets: Vec<Box<dyn Packet>>,
+    ) {
+        self.wouts.push_back(Transmit {
+            now,
+            transport,
+            message: RTCMessage::Rtp(RTPMessage::Rtcp(packets)),
+        });
+    }
+}
+
+fn is_report(packet: &dyn Packet) -> bool {
+    matches!(
+        packet.header().packet_type,
+        PacketType::SenderReport | PacketType::ReceiverReport
+    )
+}
+
+impl RTCHandler for RtcpCompoundSender {
+    type Ein = ();
+    type Eout = ();
+    type Rin = RTCMessage;
+    type Rout = RTCMessage;
+    type Win = RTCMessage;
+    type Wout = RTCMessage;
+
+    fn handle_read(&mut self, msg: Transmit<Self::Rin>) -> Result<()> {
+        if let RTCMessage::Rtp(RTPMessage::Rtcp(packets)) = &msg.message {
+            for packet in packets {
+                let reports = if let Some(rr) = packet.as_any().downcast_ref::<ReceiverReport>() {
+                    &rr.reports
+                } else if let Some(sr) = packet.as_any().downcast_ref::<SenderReport>() {
+                    if let Some(estimator) = self.lip_sync_estimators.get_mut(&sr.ssrc) {
+                        estimator.update_from_sr(sr);
+                    }
+                    &sr.reports
+                } else {
+                    continue;
+                };
+                for report in reports {
+                    if let Some(rtt) = self.rtt_estimator.on_reception_report(report, msg.now) {
+                        self.rtt_measurements.push_back((report.ssrc, rtt));
+                    }
+                }
+            }
+        }
+        self.routs.push_back(msg);
+        Ok(())
+    }
+
+    fn poll_read(&mut self) -> Option<Transmit<Self::Rout>> {
+        self.routs.pop_front()
+    }
+
+    fn handle_write(&mut self, msg: Transmit<Self::Win>) -> Result<()> {
+        if let RTCMessage::Rtp(RTPMessage::Rtcp(packets)) = msg.message {
+            for packet in &packets {
+                if let Some(sr) = packet.as_any().downcast_ref::<SenderReport>() {
+                    self.rtt_estimator.on_sender_report(sr, msg.now);
+                }
+            }
+            self.enqueue(msg.now, msg.transport, packets);
+        } else {
+            self.wouts.push_back(msg);
+        }
+
+        Ok(())
+    }
+
+    fn poll_write(&mut self) -> Option<Transmit<Self::Wout>> {
+        if !self.pending.is_empty() {
+            self.flush();
+        }
+        self.wouts.pop_front()
+    }
+}
@@ -0,0 +1,286 @@
+use super::*;
+use bytes::BytesMut;
+use rtcp::reception_report::ReceptionReport;
+use rtcp::sender_report::SenderReport;
+use rtcp::transport_feedbacks::transport_layer_cc::TransportLayerCc;
+use rtcp::transport_feedbacks::transport_layer_nack::{NackPair, TransportLayerNack};
+use shared::Protocol;
+use std::time::Duration;
+
+fn transport() -> TransportContext {
+    TransportContext {
+        local_addr: "127.0.0.1:5000".parse().unwrap(),
+        peer_addr: "127.0.0.1:6000".parse().unwrap(),
+        protocol: Protocol::UDP,
+        ecn: None,
+    }
+}
+
+fn nack() -> Box<dyn Packet> {
+    Box::new(TransportLayerNack {
+        sender_ssrc: 1,
+        media_ssrc: 2,
+        nacks: vec![NackPair {
+            packet_id: 100,
+            lost_packets: 0,
+        }],
+    })
+}
+
+fn twcc() -> Box<dyn Packet> {
+    Box::new(TransportLayerCc {
+        sender_ssrc: 1,
+        media_ssrc: 2,
+        base_sequence_number: 10,
+        ..Default::default()
+    })
+}
+
+fn write_rtcp(sender: &mut RtcpCompoundSender, packets: Vec<Box<dyn Packet>>) -> Result<()> {
+    sender.handle_write(Transmit {
+        now: Instant::now(),
+        transport: transport(),
+        message: RTCMessage::Rtp(RTPMessage::Rtcp(packets)),
+    })
+}
+
+fn poll_rtcp(sender: &mut RtcpCompoundSender) -> Option<Vec<Box<dyn Packet>>> {
+    match sender.poll_write()?.message {
+        RTCMessage::Rtp(RTPMessage::Rtcp(packets)) => Some(packets),
+        other => panic!("expected RTCP, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_rtcp_compound_sender_coalesces_packets() -> Result<()> {
+    let mut sender = RtcpCompoundSender::default();
+    sender.set_reduced_size(true);
+
+    write_rtcp(&mut sender, vec![nack()])?;
+    write_rtcp(&mut sender, vec![twcc()])?;
+
+    let packets = poll_rtcp(&mut sender).expect("compound packet");
+    assert_eq!(2, packets.len());
+    assert!(packets[0].equal(&*nack()));
+    assert!(packets[1].equal(&*twcc()));
+    assert!(sender.poll_write().is_none(), "expected a single datagram");
+
+    Ok(())
+}
+
+#[test]
+fn test_rtcp_compound_sender_prefixes_report() -> Result<()> {
+    let mut sender = RtcpCompoundSender::default();
+    sender.set_local_ssrc(0x1234);
+
+    write_rtcp(&mut sender, vec![nack()])?;
+    write_rtcp(&mut sender, vec![twcc()])?;
+
+    let packets = poll_rtcp(&mut sender).expect("compound packet");
+    assert_eq!(3, packets.len());
+    let rr = packets[0]
+        .as_any()
+        .downcast_ref::<ReceiverReport>()
+        .expect("receiver report");
+    assert_eq!(0x1234, rr.ssrc);
+    assert!(sender.poll_write().is_none());
+
+    // an explicit SR is moved in front of the feedback instead
+    write_rtcp(&mut sender, vec![nack()])?;
+    write_rtcp(
+        &mut sender,
+        vec![Box::new(SenderReport {
+            ssrc: 1,
+            ..Default::default()
+        })],
+    )?;
+
+    let packets = poll_rtcp(&mut sender).expect("compound packet");
+    assert_eq!(2, packets.len());
+    assert_eq!(PacketType::SenderReport, packets[0].header().packet_type);
+
+    Ok(())
+}
+
+#[test]
+fn test_rtcp_compound_sender_drops_duplicates() -> Result<()> {
+    let mut sender = RtcpCompoundSender::default();
+    sender.set_reduced_size(true);
+
+    write_rtcp(&mut sender, vec![nack()])?;
+    write_rtcp(&mut sender, vec![nack(), twcc()])?;
+
+    let packets = poll_rtcp(&mut sender).expect("compound packet");
+    assert_eq!(2, packets.len());
+
+    Ok(())
+}
+
+#[test]
+fn test_rtcp_compound_sender_max_compound_size() -> Result<()> {
+    let nack_size = nack().marshal_size();
+    let mut sender = RtcpCompoundSender::new(nack_size + twcc().marshal_size() - 1);
+    sender.set_reduced_size(true);
+
+    write_rtcp(&mut sender, vec![nack(), twcc()])?;
+
+    let first = poll_rtcp(&mut sender).expect("first compound packet");
+    let second = poll_rtcp(&mut sender).expect("second compound packet");
+    assert_eq!(1, first.len());
+    assert_eq!(1, second.len());
+    assert!(sender.poll_write().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_rtcp_compound_sender_bypasses_non_rtcp() -> Result<()> {
+    let mut sender = RtcpCompoundSender::default();
+
+    sender.handle_write(Transmit {
+        now: Instant::now(),
+        transport: transport(),
+        message: RTCMessage::Raw(BytesMut::from(&[1u8, 2, 3][..])),
+    })?;
+
+    match sender.poll_write().map(|t| t.message) {
+        Some(RTCMessage::Raw(raw)) => assert_eq!(&[1u8, 2, 3][..], &raw[..]),
+        _ => panic!("expected raw message"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_rtcp_compound_sender_estimates_rtt() -> Result<()> {
+    let mut sender = RtcpCompoundSender::default();
+    let ntp_time = 0xDA8B_D1FC_DDDD_A05A;
+    let sent_at = Instant::now();
+
+    sender.handle_write(Transmit {
+        now: sent_at,
+        transport: transport(),
+        message: RTCMessage::Rtp(RTPMessage::Rtcp(vec![Box::new(SenderReport {
+            ssrc: 0x902F_9E2E,
+            ntp_time,
+            ..Default::default()
+        })])),
+    })?;
+    assert!(sender.rtt_estimator().current_rtt().is_none());
+
+    let receiver_report = |delay: u32| -> Vec<Box<dyn Packet>> {
+        vec![Box::new(ReceiverReport {
+            ssrc: 0x1234_5678,
+            reports: vec![ReceptionReport {
+                ssrc: 0x902F_9E2E,
+                last_sender_report: rtt_estimator::compact_ntp(ntp_time),
+                // DLSR in units of 1/65536 seconds
+                delay,
+                ..Default::default()
+            }],
+            ..Default::default()
+        })]
+    };
+
+    // 300ms after the SR with 125ms spent at the receiver
+    sender.handle_read(Transmit {
+        now: sent_at + Duration::from_millis(300),
+        transport: transport(),
+        message: RTCMessage::Rtp(RTPMessage::Rtcp(receiver_report(8192))),
+    })?;
+    assert_eq!(
+        Some(Duration::from_millis(175)),
+        sender.rtt_estimator().current_rtt()
+    );
+    assert_eq!(
+        Some(Duration::from_millis(175)),
+        sender.rtt_estimator().smoothed_rtt()
+    );
+    assert_eq!(
+        Some((0x902F_9E2E, Duration::from_millis(175))),
+        sender.poll_rtt_measurement()
+    );
+    assert!(sender.poll_rtt_measurement().is_none());
+
+    // 500ms after the SR with 250ms spent at the receiver
+    sender.handle_read(Transmit {
+        now: sent_at + Duration::from_millis(500),
+        transport: transport(),
+        message: RTCMessage::Rtp(RTPMessage::Rtcp(receiver_report(16384))),
+    })?;
+    assert_eq!(
+        Some(Duration::from_millis(250)),
+        sender.rtt_estimator().current_rtt()
+    );
+    let smoothed = sender.rtt_estimator().smoothed_rtt().unwrap();
+    assert!(smoothed > Duration::from_millis(175) && smoothed < Duration::from_millis(250));
+
+    // reports about unknown Sender Reports are ignored
+    let mut estimator = RttEstimator::new();
+    let rr = ReceiverReport {
+        reports: vec![ReceptionReport {
+            ssrc: 0x902F_9E2E,
+            last_sender_report: 1,
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    assert!(estimator.on_receiver_report(&rr, sent_at).is_none());
+    assert!(estimator.current_rtt().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_rtcp_compound_sender_estimates_lip_sync() -> Result<()> {
+    let (audio_ssrc, video_ssrc) = (0x1111_1111, 0x2222_2222);
+    let mut sender = RtcpCompoundSender::default();
+    sender.add_lip_sync_estimator(audio_ssrc, 48000);
+    sender.add_lip_sync_estimator(video_ssrc, 90000);
+    assert_eq!(
+        None,
+        sender
+            .lip_sync_estimator(audio_ssrc)
+            .and_then(|audio| audio.estimate_wall_clock(1000))
+    );
+
+    // the video SR is taken 500ms after the audio SR
+    let ntp_time = 0xDA8B_D1FC_0000_0000;
+    sender.handle_read(Transmit {
+        now: Instant::now(),
+        transport: transport(),
+        message: RTCMessage::Rtp(RTPMessage::Rtcp(vec![
+            Box::new(SenderReport {
+                ssrc: audio_ssrc,
+                ntp_time,
+                rtp_time: 1000,
+                ..Default::default()
+            }),
+            Box::new(SenderReport {
+                ssrc: video_ssrc,
+                ntp_time: ntp_time + (1 << 31),
+                rtp_time: 5000,
+                ..Default::default()
+            }),
+        ])),
+    })?;
+
+    let audio = sender.lip_sync_estimator(audio_ssrc).unwrap();
+    let video = sender.lip_sync_estimator(video_ssrc).unwrap();
+    // audio 2s after its SR, video 1.46s after its SR, i.e. 1.96s after the audio SR
+    let audio_ts = 1000 + 48000 * 2;
+    let video_ts = 5000 + 131_400;
+    let offset_ms = lip_sync_estimator::av_offset_ms(audio, audio_ts, video, video_ts).unwrap();
+    assert!((offset_ms - 40).abs() <= 1, "offset_ms {offset_ms}");
+
+    // a timestamp before the SR reference point maps before its wall clock time
+    assert_eq!(
+        Some(Duration::from_millis(500)),
+        audio
+            .estimate_wall_clock(1000)
+            .zip(audio.estimate_wall_clock(1000u32.wrapping_sub(24000)))
+            .map(|(sr, earlier)| sr - earlier)
+    );
+
+    Ok(())
+}
@@ -0,0 +1,97 @@
+use rtcp::receiver_report::ReceiverReport;
+use rtcp::reception_report::ReceptionReport;
+use rtcp::sender_report::SenderReport;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Number of Sender Reports remembered per SSRC to match LSR values against
+const MAX_SENDER_REPORTS: usize = 8;
+
+/// RttEstimator computes the round-trip time from the LSR and DLSR fields of
+/// incoming Receiver Reports as described in RFC 3550 Section 6.4.1.
+///
+/// Instead of comparing the middle 32 bits of NTP timestamps, the arrival time of
+/// a report is compared with the Instant its matching Sender Report was sent at,
+/// which keeps the computation independent of the wall clock.
+#[derive(Debug, Default)]
+pub struct RttEstimator {
+    sender_reports: HashMap<u32, VecDeque<(u32, Instant)>>,
+    current_rtt: Option<Duration>,
+    smoothed_rtt: Option<Duration>,
+}
+
+impl RttEstimator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// on_sender_report records a Sender Report sent at now
+    pub fn on_sender_report(&mut self, sr: &SenderReport, now: Instant) {
+        let reports = self.sender_reports.entry(sr.ssrc).or_default();
+        if reports.len() == MAX_SENDER_REPORTS {
+            reports.pop_front();
+        }
+        reports.push_back((compact_ntp(sr.ntp_time), now));
+    }
+
+    /// on_receiver_report updates the RTT from each reception report about a
+    /// Sender Report previously recorded, and returns the latest measurement.
+    pub fn on_receiver_report(&mut self, rr: &ReceiverReport, now: Instant) -> Option<Duration> {
+        let mut rtt = None;
+        for report in &rr.reports {
+            if let Some(measured) = self.on_reception_report(report, now) {
+                rtt = Some(measured);
+            }
+        }
+        rtt
+    }
+
+    /// on_reception_report updates the RTT from a single reception report,
+    /// which may also be carried by a Sender Report.
+    pub fn on_reception_report(
+        &mut self,
+        report: &ReceptionReport,
+        now: Instant,
+    ) -> Option<Duration> {
+        // LSR is zero if no Sender Report has been received yet
+        if report.last_sender_report == 0 {
+            return None;
+        }
+
+        let sent_at = self
+            .sender_reports
+            .get(&report.ssrc)?
+            .iter()
+            .find(|(lsr, _)| *lsr == report.last_sender_report)
+            .map(|(_, sent_at)| *sent_at)?;
+
+        // DLSR is expressed in units of 1/65536 seconds
+        let dlsr = Duration::from_secs_f64(report.delay as f64 / 65536.0);
+        let rtt = now.checked_duration_since(sent_at)?.saturating_sub(dlsr);
+
+        self.current_rtt = Some(rtt);
+        self.smoothed_rtt = Some(match self.smoothed_rtt {
+            // SRTT = 7/8 * SRTT + 1/8 * RTT, see RFC 6298 Section 2
+            Some(srtt) => (srtt * 7 + rtt) / 8,
+            None => rtt,
+        });
+
+        Some(rtt)
+    }
+
+    /// current_rtt returns the latest RTT measurement
+    pub fn current_rtt(&self) -> Option<Duration> {
+        self.current_rtt
+    }
+
+    /// smoothed_rtt returns the exponentially weighted moving average of the RTT
+    pub fn smoothed_rtt(&self) -> Option<Duration> {
+        self.smoothed_rtt
+    }
+}
+
+/// compact_ntp returns the middle 32 bits of a 64-bit NTP timestamp, which is
+/// how the LSR field refers to a Sender Report.
+pub(crate) fn compact_ntp(ntp_time: u64) -> u32 {
+    (ntp_time >> 16) as u32
+}
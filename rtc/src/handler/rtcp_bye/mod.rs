@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod rtcp_bye_test;
+
+use crate::messages::{RTCMessage, RTPMessage};
+use log::debug;
+use rtcp::goodbye::Goodbye;
+use shared::error::Result;
+use shared::handler::RTCHandler;
+use shared::Transmit;
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RtcpByeEvent {
+    /// The remote source with this SSRC sent an RTCP BYE, with its optional reason
+    SourceRemoved(u32, String),
+}
+
+/// RtcpByeHandler reports each remote source that leaves with an RTCP BYE
+/// (RFC 3550 Section 6.6) as an RtcpByeEvent::SourceRemoved event, so its state
+/// can be dropped and later RTP with the same SSRC starts a new stream.
+#[derive(Default)]
+pub struct RtcpByeHandler {
+    events: VecDeque<RtcpByeEvent>,
+    routs: VecDeque<Transmit<RTCMessage>>,
+    wouts: VecDeque<Transmit<RTCMessage>>,
+}
+
+impl RtcpByeHandler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn on_goodbye(&mut self, bye: &Goodbye) {
+        let reason = String::from_utf8_lossy(&bye.reason).into_owned();
+        for ssrc in &bye.sources {
+            debug!("remote source {ssrc} left: {reason}");
+            self.events
+                .push_back(RtcpByeEvent::SourceRemoved(*ssrc, reason.clone()));
+        }
+    }
+}
+
+impl RTCHandler for RtcpByeHandler {
+    type Ein = ();
+    type Eout = RtcpByeEvent;
+    type Rin = RTCMessage;
+    type Rout = RTCMessage;
+    type Win = RTCMessage;
+    type Wout = RTCMessage;
+
+    fn handle_read(&mut self, msg: Transmit<Self::Rin>) -> Result<()> {
+        if let RTCMessage::Rtp(RTPMessage::Rtcp(packets)) = &msg.message {
+            for packet in packets {
+                if let Some(bye) = packet.as_any().downcast_ref::<Goodbye>() {
+                    self.on_goodbye(bye);
+                }
+            }
+        }
+        self.routs.push_back(msg);
+        Ok(())
+    }
+
+    fn poll_read(&mut self) -> Option<Transmit<Self::Rout>> {
+        self.routs.pop_front()
+    }
+
+    fn handle_write(&mut self, msg: Transmit<Self::Win>) -> Result<()> {
+        self.wouts.push_back(msg);
+        Ok(())
+    }
+
+    fn poll_write(&mut self) -> Option<Transmit<Self::Wout>> {
+        self.wouts.pop_front()
+    }
+
+    fn poll_event(&mut self) -> Option<Self::Eout> {
+        self.events.pop_front()
+    }
+}
@@ -0,0 +1,62 @@
+use super::*;
+use bytes::Bytes;
+use shared::{Protocol, TransportContext};
+use std::time::Instant;
+
+fn transport() -> TransportContext {
+    TransportContext {
+        local_addr: "127.0.0.1:5000".parse().unwrap(),
+        peer_addr: "127.0.0.1:6000".parse().unwrap(),
+        protocol: Protocol::UDP,
+        ecn: None,
+    }
+}
+
+#[test]
+fn test_rtcp_bye_handler_removes_source() -> Result<()> {
+    let mut handler = RtcpByeHandler::new();
+
+    // RTP is passed on without events
+    handler.handle_read(Transmit {
+        now: Instant::now(),
+        transport: transport(),
+        message: RTCMessage::Rtp(RTPMessage::Rtp(rtp::packet::Packet {
+            header: rtp::header::Header {
+                version: 2,
+                ssrc: 0x902F_9E2E,
+                ..Default::default()
+            },
+            payload: Bytes::from_static(&[0u8; 10]),
+        })),
+    })?;
+    assert_eq!(None, handler.poll_event());
+    assert!(handler.poll_read().is_some());
+
+    let mut raw = Bytes::from_static(&[
+        0x81, 0xcb, 0x00, 0x02, // v=2, p=0, count=1, BYE, len=2
+        0x90, 0x2f, 0x9e, 0x2e, // ssrc=0x902f9e2e
+        0x03, 0x46, 0x4f, 0x4f, // len=3, text=FOO
+    ]);
+    handler.handle_read(Transmit {
+        now: Instant::now(),
+        transport: transport(),
+        message: RTCMessage::Rtp(RTPMessage::Rtcp(rtcp::packet::unmarshal(&mut raw)?)),
+    })?;
+
+    assert_eq!(
+        Some(RtcpByeEvent::SourceRemoved(0x902F_9E2E, "FOO".to_owned())),
+        handler.poll_event()
+    );
+    assert_eq!(None, handler.poll_event());
+
+    // the BYE is passed on
+    let msg = handler.poll_read().expect("BYE should be passed on");
+    if let RTCMessage::Rtp(RTPMessage::Rtcp(packets)) = msg.message {
+        assert!(packets[0].as_any().downcast_ref::<Goodbye>().is_some());
+    } else {
+        panic!("unexpected message {:?}", msg.message);
+    }
+    assert!(handler.poll_read().is_none());
+
+    Ok(())
+}
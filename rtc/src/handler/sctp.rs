@@ -2,8 +2,10 @@ use crate::messages::{
     DTLSMessage, DataChannelMessage, DataChannelMessageParams, DataChannelMessageType, RTCEvent,
     RTCMessage,
 };
-use crate::transport::sctp_transport::RTCSctpTransport;
-use bytes::BytesMut;
+use crate::transport::sctp_transport::sctp_transport_capabilities::SCTPTransportCapabilities;
+use crate::transport::sctp_transport::sctp_transport_state::RTCSctpTransportState;
+use crate::transport::sctp_transport::{RTCSctpTransport, SctpTransportEvent};
+use bytes::{Bytes, BytesMut};
 use log::{debug, error};
 use sctp::{
     AssociationEvent, AssociationHandle, DatagramEvent, EndpointEvent, Event, Payload,
@@ -17,6 +19,15 @@ use std::time::{Duration, Instant};
 
 enum SctpMessage {
     Inbound(DataChannelMessage),
+    Partial {
+        id: u16,
+        threshold: usize,
+        is_string: bool,
+        fragments: Vec<Bytes>,
+        is_message_end: bool,
+    },
+    Start,
+    StateChange(RTCSctpTransportState),
     Outbound(Transmit<sctp::Payload>),
 }
 
@@ -40,6 +51,7 @@ impl RTCHandler for RTCSctpTransport {
                     &mut self.sctp_associations,
                 );
 
+                let mut messages = vec![];
                 let mut sctp_events: HashMap<AssociationHandle, VecDeque<AssociationEvent>> =
                     HashMap::new();
                 if let Some((ch, event)) = sctp_endpoint.handle(
@@ -51,6 +63,7 @@ impl RTCHandler for RTCSctpTransport {
                     match event {
                         DatagramEvent::NewAssociation(conn) => {
                             sctp_associations.insert(ch, conn);
+                            messages.push(SctpMessage::Start);
                         }
                         DatagramEvent::AssociationEvent(event) => {
                             sctp_events.entry(ch).or_default().push_back(event);
@@ -58,7 +71,6 @@ impl RTCHandler for RTCSctpTransport {
                     }
                 }
 
-                let mut messages = vec![];
                 {
                     let mut endpoint_events: Vec<(AssociationHandle, EndpointEvent)> = vec![];
 
@@ -73,14 +85,57 @@ impl RTCHandler for RTCSctpTransport {
                         }
 
                         while let Some(event) = conn.poll() {
-                            if let Event::Stream(StreamEvent::Readable { id }) = event {
+                            if let Event::Connected = event {
+                                messages.push(SctpMessage::StateChange(
+                                    RTCSctpTransportState::Connected,
+                                ));
+                            } else if let Event::AssociationLost { reason } = event {
+                                debug!("association_handle {} lost: {}", ch.0, reason);
+                                messages
+                                    .push(SctpMessage::StateChange(RTCSctpTransportState::Closed));
+                            } else if let Event::Stream(StreamEvent::Finished { id }) = event {
+                                if self.pending_resets.remove(&id) {
+                                    self.events
+                                        .push_back(SctpTransportEvent::OnDataChannelClosed(id));
+                                }
+                            } else if let Event::Stream(StreamEvent::Readable { id }) = event {
+                                let threshold = self
+                                    .data_channels
+                                    .values()
+                                    .find(|dc| dc.id == id)
+                                    .and_then(|dc| dc.partial_delivery_threshold);
                                 let mut stream = conn.stream(id)?;
-                                while let Some(chunks) = stream.read_sctp()? {
+                                if threshold.is_some() {
+                                    stream.set_partial_delivery(true)?;
+                                }
+                                loop {
+                                    let chunks = if threshold.is_some() {
+                                        stream.read_partial()?
+                                    } else {
+                                        stream.read_sctp()?
+                                    };
+                                    let Some(chunks) = chunks else {
+                                        break;
+                                    };
+                                    let data_message_type = to_data_message_type(chunks.ppi);
+                                    if let Some(threshold) = threshold {
+                                        if data_message_type != DataChannelMessageType::Control {
+                                            messages.push(SctpMessage::Partial {
+                                                id,
+                                                threshold,
+                                                is_string: data_message_type
+                                                    == DataChannelMessageType::Text,
+                                                fragments: chunks.fragments(),
+                                                is_message_end: chunks.is_message_end(),
+                                            });
+                                            continue;
+                                        }
+                                    }
                                     let n = chunks.read(&mut self.internal_buffer)?;
                                     messages.push(SctpMessage::Inbound(DataChannelMessage {
                                         association_handle: ch.0,
                                         stream_id: id,
-                                        data_message_type: to_data_message_type(chunks.ppi),
+                                        data_message_type,
                                         params: None,
                                         payload: BytesMut::from(&self.internal_buffer[0..n]),
                                     }));
@@ -117,12 +172,38 @@ impl RTCHandler for RTCSctpTransport {
                                     "recv sctp data channel message {:?}",
                                     msg.transport.peer_addr
                                 );
+                                if message.data_message_type == DataChannelMessageType::Control {
+                                    self.check_data_channel_open(&message.payload);
+                                }
                                 self.routs.push_back(Transmit {
                                     now: msg.now,
                                     transport: msg.transport,
                                     message: RTCMessage::Dtls(DTLSMessage::Sctp(message)),
                                 })
                             }
+                            SctpMessage::Partial {
+                                id,
+                                threshold,
+                                is_string,
+                                fragments,
+                                is_message_end,
+                            } => {
+                                self.deliver_partial(
+                                    id,
+                                    threshold,
+                                    is_string,
+                                    &fragments,
+                                    is_message_end,
+                                );
+                            }
+                            SctpMessage::Start => {
+                                self.start(SCTPTransportCapabilities {
+                                    max_message_size: 0,
+                                })?;
+                            }
+                            SctpMessage::StateChange(state) => {
+                                self.state_change(state);
+                            }
                             SctpMessage::Outbound(transmit) => {
                                 if let Payload::RawEncode(raw_data) = transmit.message {
                                     for raw in raw_data {
@@ -7,6 +7,7 @@ use ring::rand::SystemRandom;
 use ring::rsa;
 use ring::signature::{EcdsaKeyPair, Ed25519KeyPair};
 use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
 
 use crate::peer_connection::math_rand_alpha;
 use crate::stats::stats_collector::StatsCollector;
@@ -14,6 +15,9 @@ use crate::stats::{CertificateStats, StatsReportType};
 use crate::transport::dtls_transport::dtls_fingerprint::RTCDtlsFingerprint;
 use shared::error::{Error, Result};
 
+const MIN_CERTIFICATE_EXPIRY: Duration = Duration::from_secs(60);
+const ARM_MAX_CERTIFICATE_EXPIRY: Duration = Duration::from_secs(172800); //60*60*48 or 2 days
+
 /// Certificate represents a X.509 certificate used to authenticate WebRTC communications.
 #[derive(Clone, Debug)]
 pub struct RTCCertificate {
@@ -81,7 +85,7 @@ impl RTCCertificate {
         let expires = if cfg!(target_arch = "arm") {
             // Workaround for issue overflow when adding duration to instant on armv7
             // https://github.com/webrtc-rs/examples/issues/5 https://github.com/chronotope/chrono/issues/343
-            SystemTime::now().add(Duration::from_secs(172800)) //60*60*48 or 2 days
+            SystemTime::now().add(ARM_MAX_CERTIFICATE_EXPIRY)
         } else {
             not_after.into()
         };
@@ -98,20 +102,31 @@ impl RTCCertificate {
 
     /// Generates a new certificate with default [`CertificateParams`] using the given keypair.
     pub fn from_key_pair(key_pair: KeyPair) -> Result<Self> {
-        let mut params = CertificateParams::new(vec![math_rand_alpha(16)]);
+        RTCCertificate::from_params(key_pair_params(key_pair)?)
+    }
 
-        if key_pair.is_compatible(&rcgen::PKCS_ED25519) {
-            params.alg = &rcgen::PKCS_ED25519;
-        } else if key_pair.is_compatible(&rcgen::PKCS_ECDSA_P256_SHA256) {
-            params.alg = &rcgen::PKCS_ECDSA_P256_SHA256;
-        } else if key_pair.is_compatible(&rcgen::PKCS_RSA_SHA256) {
-            params.alg = &rcgen::PKCS_RSA_SHA256;
-        } else {
-            return Err(Error::Other("Unsupported key_pair".to_owned()));
-        };
-        params.key_pair = Some(key_pair);
+    /// Generates a new certificate using the given keypair, which is valid from now on
+    /// for the given duration.
+    ///
+    /// The duration must be at least one minute so that the certificate does not expire
+    /// before the DTLS handshake completes.
+    pub fn from_key_pair_with_expiry(key_pair: KeyPair, duration: Duration) -> Result<Self> {
+        if duration < MIN_CERTIFICATE_EXPIRY {
+            return Err(Error::ErrCertificateExpiryTooShort);
+        }
+
+        let mut params = key_pair_params(key_pair)?;
+        let now = OffsetDateTime::now_utc();
+        params.not_before = now;
+        params.not_after = now + duration;
 
-        RTCCertificate::from_params(params)
+        let mut cert = RTCCertificate::from_params(params)?;
+        if cfg!(target_arch = "arm") && duration <= ARM_MAX_CERTIFICATE_EXPIRY {
+            // armv7 workaround in from_params only caps longer validity periods
+            cert.expires = SystemTime::now().add(duration);
+        }
+
+        Ok(cert)
     }
 
     /// Parses a certificate from the ASCII PEM format.
@@ -216,6 +231,23 @@ impl RTCCertificate {
     }
 }
 
+fn key_pair_params(key_pair: KeyPair) -> Result<CertificateParams> {
+    let mut params = CertificateParams::new(vec![math_rand_alpha(16)]);
+
+    if key_pair.is_compatible(&rcgen::PKCS_ED25519) {
+        params.alg = &rcgen::PKCS_ED25519;
+    } else if key_pair.is_compatible(&rcgen::PKCS_ECDSA_P256_SHA256) {
+        params.alg = &rcgen::PKCS_ECDSA_P256_SHA256;
+    } else if key_pair.is_compatible(&rcgen::PKCS_RSA_SHA256) {
+        params.alg = &rcgen::PKCS_RSA_SHA256;
+    } else {
+        return Err(Error::Other("Unsupported key_pair".to_owned()));
+    };
+    params.key_pair = Some(key_pair);
+
+    Ok(params)
+}
+
 fn gen_stats_id() -> String {
     format!(
         "certificate-{}",
@@ -279,6 +311,26 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_generate_certificate_with_expiry() -> Result<()> {
+        let kp = KeyPair::generate(&rcgen::PKCS_ECDSA_P256_SHA256)?;
+        let cert = RTCCertificate::from_key_pair_with_expiry(kp, Duration::from_secs(120))?;
+
+        assert!(
+            cert.expires
+                .duration_since(SystemTime::now())
+                .unwrap()
+                .as_secs()
+                < 130
+        );
+
+        let kp = KeyPair::generate(&rcgen::PKCS_ECDSA_P256_SHA256)?;
+        let result = RTCCertificate::from_key_pair_with_expiry(kp, Duration::from_secs(59));
+        assert_eq!(Some(Error::ErrCertificateExpiryTooShort), result.err());
+
+        Ok(())
+    }
+
     #[cfg(feature = "pem")]
     #[test]
     fn test_certificate_serialize_pem_and_from_pem() -> Result<()> {
@@ -1,9 +1,8 @@
-/*TODO:#[cfg(test)]
-pub(crate) mod peer_connection_test;
-*/
 pub mod certificate;
 pub mod configuration;
 pub mod offer_answer_options;
+#[cfg(test)]
+pub(crate) mod peer_connection_test;
 /*
 pub(crate) mod operation;
 mod peer_connection_internal;
@@ -13,12 +12,16 @@ pub mod policy;
 pub mod sdp;
 pub mod signaling_state;
 
-use ::sdp::description::session::{Origin, ATTR_KEY_ICELITE};
+use ::sdp::description::session::{Origin, SdpParseMode, ATTR_KEY_ICELITE};
+use bytes::BytesMut;
 use rcgen::KeyPair;
 use shared::error::{Error, Result};
+use shared::handler::RTCHandler;
+use shared::marshal::MarshalSize;
+use shared::{Transmit, TransportContext};
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 /*
 use ::ice::candidate::candidate_base::unmarshal_candidate;
 use ::ice::candidate::Candidate;
@@ -34,13 +37,27 @@ use ice::candidate::unmarshal_candidate;
 use rand::{thread_rng, Rng};
 //use srtp::stream::Stream;
 
+use crate::api::media_engine::extmap_negotiator::ExtmapNegotiator;
 use crate::api::media_engine::MediaEngine;
 use crate::api::setting_engine::SettingEngine;
 use crate::api::API;
 use crate::data_channel::data_channel_init::RTCDataChannelInit;
+use crate::data_channel::data_channel_message::RTCDataChannelMessage;
 use crate::data_channel::data_channel_parameters::DataChannelParameters;
-use crate::data_channel::RTCDataChannel;
+use crate::data_channel::data_channel_state::RTCDataChannelState;
+use crate::data_channel::{RTCDataChannel, RTCDataChannelId};
 use crate::handler::demuxer::Demuxer;
+use crate::handler::pacer::{RtpPacer, DEFAULT_PACING_INTERVAL};
+use crate::handler::rtcp::lip_sync_estimator::{av_offset_ms, LipSyncEstimator};
+use crate::handler::rtcp::RtcpCompoundSender;
+use crate::handler::rtcp_bye::{RtcpByeEvent, RtcpByeHandler};
+use crate::handler::{read_through, write_through};
+use crate::messages::{RTCEvent, RTCMessage, RTPMessage};
+use crate::stats::stats_accumulator::RTCStatsAccumulator;
+use crate::stats::stats_collector::StatsCollector;
+use crate::stats::{
+    OutboundRTPStats, RTCStatsType, RemoteInboundRTPStats, StatsReport, StatsReportType,
+};
 /*
 use crate::transports::data_channel::data_channel_init::RTCDataChannelInit;
 use crate::transports::data_channel::data_channel_parameters::DataChannelParameters;
@@ -55,6 +72,7 @@ use crate::transport::dtls_transport::dtls_role::{
 /*use crate::transports::dtls_transport::dtls_transport_state::RTCDtlsTransportState;
 use shared::error::{flatten_errs, Error, Result};
 //use crate::transports::ice_transport::ice_candidate::{RTCIceCandidate, RTCIceCandidateInit};*/
+use crate::transport::dtls_transport::dtls_transport_state::RTCDtlsTransportState;
 use crate::transport::ice_transport::ice_connection_state::RTCIceConnectionState;
 /*use crate::transports::ice_transport::ice_gatherer::{
     OnGatheringCompleteHdlrFn, OnICEGathererStateChangeHdlrFn, OnLocalCandidateHdlrFn,
@@ -68,7 +86,9 @@ use crate::transports::ice_transport::ice_transport_state::RTCIceTransportState;
 */
 use crate::peer_connection::certificate::RTCCertificate;
 use crate::peer_connection::configuration::RTCConfiguration;
-use crate::peer_connection::offer_answer_options::{RTCAnswerOptions, RTCOfferOptions};
+use crate::peer_connection::offer_answer_options::{
+    OfferedCodec, RTCAnswerOptions, RTCOfferOptions,
+};
 //use crate::peer_connection::offer_answer_options::{RTCAnswerOptions, RTCOfferOptions};
 //use crate::peer_connection::operation::{Operation, Operations};
 use crate::peer_connection::peer_connection_state::{
@@ -78,7 +98,9 @@ use crate::peer_connection::policy::ice_transport_policy::RTCIceTransportPolicy;
 use crate::peer_connection::sdp::sdp_type::RTCSdpType;
 use crate::peer_connection::sdp::session_description::RTCSessionDescription;
 use crate::peer_connection::sdp::{
-    extract_fingerprint, extract_ice_details, get_mid_value, get_peer_direction, get_rids,
+    check_answer_payload_types, codecs_from_media_description, extract_fingerprint,
+    extract_ice_details, get_mid_value, get_peer_direction, get_rids, get_ssrc, get_tias_bitrate,
+    have_rtcp_rsize, payload_type_overrides, rtp_extensions_from_media_description,
     update_sdp_origin, MediaSection, PopulateSdpParams,
 };
 use crate::peer_connection::sdp::{populate_local_candidates, populate_sdp};
@@ -87,21 +109,36 @@ use crate::peer_connection::signaling_state::{
     check_next_signaling_state, RTCSignalingState, StateChangeOp,
 };
 use crate::rtp_transceiver::rtp_codec::RTPCodecType;
+use crate::rtp_transceiver::rtp_receiver::RTCRtpReceiver;
+use crate::rtp_transceiver::rtp_sender::RTCRtpSender;
 use crate::rtp_transceiver::rtp_transceiver_direction::RTCRtpTransceiverDirection;
-use crate::rtp_transceiver::{find_by_mid, satisfy_type_and_direction, Mid, RTCRtpTransceiver};
+use crate::rtp_transceiver::{
+    find_by_mid, satisfy_type_and_direction, Mid, PayloadType, RTCRtpTransceiver,
+    RTCRtpTransceiverInit, SSRC, TYPE_RTCP_FB_NACK, TYPE_RTCP_FB_TRANSPORT_CC,
+};
+use crate::track::track_local::TrackLocal;
 //use crate::rtp_transceiver::rtp_codec::RTPCodecType;
 //use crate::rtp_transceiver::rtp_transceiver_direction::RTCRtpTransceiverDirection;
-use crate::transport::dtls_transport::RTCDtlsTransport;
+use crate::transport::dtls_transport::{DtlsTransportEvent, RTCDtlsTransport};
 use crate::transport::ice_transport::ice_candidate::{RTCIceCandidate, RTCIceCandidateInit};
 use crate::transport::ice_transport::ice_gatherer_state::RTCIceGathererState;
 use crate::transport::ice_transport::ice_gathering_state::RTCIceGatheringState;
 use crate::transport::ice_transport::ice_role::RTCIceRole;
+use crate::transport::ice_transport::ice_transport_state::RTCIceTransportState;
 use crate::transport::ice_transport::{
     ice_gatherer::{RTCIceGatherOptions, RTCIceGatherer},
-    RTCIceTransport,
+    IceTransportEvent, RTCIceTransport,
 };
 use crate::transport::sctp_transport::sctp_transport_state::RTCSctpTransportState;
-use crate::transport::sctp_transport::RTCSctpTransport;
+use crate::transport::sctp_transport::{RTCSctpTransport, SctpTransportEvent};
+use rtcp::extended_report::ExtendedReport;
+use rtcp::header::PacketType;
+use rtcp::payload_feedbacks::picture_loss_indication::PictureLossIndication;
+use rtcp::payload_feedbacks::receiver_estimated_maximum_bitrate::ReceiverEstimatedMaximumBitrate;
+use rtcp::sender_report::SenderReport;
+use rtcp::transport_feedbacks::temporary_maximum_media_bitrate::{TmmbEntry, TmmbrPacket};
+use rtcp::transport_feedbacks::transport_layer_cc::TransportLayerCc;
+use rtcp::transport_feedbacks::transport_layer_nack::TransportLayerNack;
 
 //use crate::transport::sctp_transport::RTCSctpTransport;
 /*use crate::rtp_transceiver::rtp_codec::{RTCRtpHeaderExtensionCapability, RTPCodecType};
@@ -130,6 +167,10 @@ pub(crate) const SIMULCAST_MAX_PROBE_ROUTINES: u64 = 25;
 
 pub(crate) const MEDIA_SECTION_APPLICATION: &str = "application";
 
+/// DATA_CHANNEL_PROTOCOL_MAX_LENGTH is the longest sub-protocol, in bytes, a
+/// DataChannel can be created with
+pub(crate) const DATA_CHANNEL_PROTOCOL_MAX_LENGTH: usize = 32767;
+
 const RUNES_ALPHA: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
 
 /// math_rand_alpha generates a mathematical random alphabet sequence of the requested length.
@@ -153,12 +194,37 @@ pub enum PeerConnectionEvent {
     OnIceCandidateError,
     OnSignalingStateChange(RTCSignalingState),
     OnIceConnectionStateChange(RTCIceConnectionState),
+    OnIceRestart {
+        new_ufrag: String,
+    },
     OnIceGatheringStateChane,
     OnPeerConnectionStateChange(RTCPeerConnectionState),
     // RTP Media API
     OnTrack,
+    /// A Sender Report updated the lip-sync estimate of the remote audio and video of
+    /// a MediaStream: the audio with RTP timestamp audio_ts was captured offset_ms
+    /// after the video with RTP timestamp video_ts, before if negative.
+    LipSyncEstimate {
+        audio_ts: u32,
+        video_ts: u32,
+        offset_ms: i32,
+    },
+    /// The remote source with this SSRC left with an RTCP BYE
+    SourceRemoved(SSRC),
     // Peer-to-peer Data API
     OnDataChannel,
+    OnDataChannelClosed(RTCDataChannelId),
+    DataChannelMessage(RTCDataChannelId, RTCDataChannelMessage),
+    DataChannelProtocolMismatch {
+        label: String,
+        local: String,
+        remote: String,
+    },
+    SctpStateChange(RTCSctpTransportState),
+    // Congestion control
+    BitrateEstimateUpdate(SSRC, u64),
+    // Statistics API
+    OnStats(StatsReport),
 }
 
 /*
@@ -203,6 +269,8 @@ pub struct RTCPeerConnection {
     pub(super) signaling_state: RTCSignalingState,
     pub(super) peer_connection_state: RTCPeerConnectionState,
     pub(super) ice_connection_state: RTCIceConnectionState,
+    // last ICE agent state mirrored into ice_connection_state
+    pub(super) ice_agent_state: RTCIceTransportState,
     pub(super) current_local_description: Option<RTCSessionDescription>,
     pub(super) current_remote_description: Option<RTCSessionDescription>,
     pub(super) pending_local_description: Option<RTCSessionDescription>,
@@ -212,6 +280,10 @@ pub struct RTCPeerConnection {
     pub(super) ice_transport: RTCIceTransport,
     pub(super) dtls_transport: RTCDtlsTransport,
     pub(super) sctp_transport: RTCSctpTransport,
+    pub(super) rtcp_bye_handler: RtcpByeHandler,
+    pub(super) rtcp_handler: RtcpCompoundSender,
+    pub(super) rtp_pacer: RtpPacer,
+    pub(super) stats_accumulator: RTCStatsAccumulator,
     /// ops is an operations queue which will ensure the enqueued actions are
     /// executed in order. It is used for asynchronously, but serially processing
     /// remote and local descriptions
@@ -233,6 +305,13 @@ pub struct RTCPeerConnection {
     pub(crate) media_engine: MediaEngine,
 
     pub(crate) events: VecDeque<PeerConnectionEvent>,
+    /// RTP and RTCP received by handle_read, pull by calling `poll_read`
+    pub(crate) routs: VecDeque<Transmit<RTCMessage>>,
+    /// datagrams to be written to the socket, pull by calling `poll_write`
+    pub(crate) wouts: VecDeque<Transmit<BytesMut>>,
+    /// interval of the OnStats events enabled with enable_periodic_stats
+    pub(crate) stats_interval: Option<Duration>,
+    pub(crate) next_stats_time: Option<Instant>,
 }
 
 impl std::fmt::Debug for RTCPeerConnection {
@@ -304,6 +383,7 @@ impl RTCPeerConnection {
             last_answer: "".to_string(),
             signaling_state: RTCSignalingState::Stable,
             ice_connection_state: RTCIceConnectionState::New,
+            ice_agent_state: RTCIceTransportState::New,
             current_local_description: None,
             current_remote_description: None,
             pending_local_description: None,
@@ -315,11 +395,19 @@ impl RTCPeerConnection {
             is_negotiation_needed: false,
 
             events: Default::default(),
+            routs: Default::default(),
+            wouts: Default::default(),
+            stats_interval: None,
+            next_stats_time: None,
 
             demuxer,
             ice_transport,
             dtls_transport,
             sctp_transport,
+            rtcp_bye_handler: RtcpByeHandler::new(),
+            rtcp_handler: RtcpCompoundSender::default(),
+            rtp_pacer: RtpPacer::new(0, DEFAULT_PACING_INTERVAL),
+            stats_accumulator: RTCStatsAccumulator::new(),
             rtp_transceivers: vec![],
         })
     }
@@ -646,6 +734,255 @@ impl RTCPeerConnection {
             .push_back(PeerConnectionEvent::OnIceConnectionStateChange(new_state));
     }
 
+    fn do_ice_connection_state_change(&mut self, ice_transport_state: RTCIceTransportState) {
+        self.ice_transport.set_state(ice_transport_state);
+
+        let new_state = RTCIceConnectionState::from(ice_transport_state);
+        if new_state == RTCIceConnectionState::Unspecified {
+            log::warn!(
+                "on_connection_state_change: unhandled ICE state: {}",
+                ice_transport_state
+            );
+            return;
+        }
+        if self.ice_connection_state != new_state {
+            self.update_ice_connection_state_change(new_state);
+            self.update_connection_state();
+        }
+    }
+
+    /// sync_ice_connection_state mirrors the connection state changes of the
+    /// underlying ICE agent into the ICE connection state of this PeerConnection.
+    fn sync_ice_connection_state(&mut self) {
+        while let Some(event) = RTCHandler::poll_event(&mut self.ice_transport) {
+            if let RTCEvent::IceTransportEvent(IceTransportEvent::OnConnectionStateChange(state)) =
+                event
+            {
+                self.ice_agent_state = state;
+                self.do_ice_connection_state_change(state);
+            }
+        }
+
+        // catch up with agent state changes that were not reported through its events
+        let agent_state = self.ice_transport.agent_state();
+        if agent_state != self.ice_agent_state {
+            self.ice_agent_state = agent_state;
+            self.do_ice_connection_state_change(agent_state);
+        }
+    }
+
+    /// poll_event returns the next PeerConnectionEvent.
+    pub fn poll_event(&mut self) -> Option<PeerConnectionEvent> {
+        self.sync_ice_connection_state();
+
+        while let Some(event) = RTCHandler::poll_event(&mut self.sctp_transport) {
+            match event {
+                RTCEvent::SctpTransportEvent(SctpTransportEvent::OnDataChannelClosed(id)) => {
+                    self.events
+                        .push_back(PeerConnectionEvent::OnDataChannelClosed(id));
+                }
+                RTCEvent::SctpTransportEvent(SctpTransportEvent::OnDataChannelMessage(
+                    id,
+                    message,
+                )) => {
+                    self.events
+                        .push_back(PeerConnectionEvent::DataChannelMessage(id, message));
+                }
+                RTCEvent::SctpTransportEvent(
+                    SctpTransportEvent::OnDataChannelProtocolMismatch {
+                        label,
+                        local,
+                        remote,
+                    },
+                ) => {
+                    self.events
+                        .push_back(PeerConnectionEvent::DataChannelProtocolMismatch {
+                            label,
+                            local,
+                            remote,
+                        });
+                }
+                RTCEvent::SctpTransportEvent(SctpTransportEvent::OnStateChange(state)) => {
+                    self.events
+                        .push_back(PeerConnectionEvent::SctpStateChange(state));
+                    self.update_connection_state();
+                }
+                _ => {}
+            }
+        }
+
+        while let Some(event) = RTCHandler::poll_event(&mut self.dtls_transport) {
+            if let RTCEvent::DtlsTransportEvent(DtlsTransportEvent::OnDtlsTransportStateChange(_)) =
+                event
+            {
+                self.update_connection_state();
+            }
+        }
+
+        self.events.pop_front()
+    }
+
+    /// enable_periodic_stats makes handle_timeout enqueue an OnStats event with a
+    /// get_stats snapshot every interval, starting one interval after now.
+    pub fn enable_periodic_stats(&mut self, now: Instant, interval: Duration) {
+        self.stats_interval = Some(interval);
+        self.next_stats_time = Some(now + interval);
+    }
+
+    /// disable_periodic_stats stops the OnStats events enabled with enable_periodic_stats.
+    pub fn disable_periodic_stats(&mut self) {
+        self.stats_interval = None;
+        self.next_stats_time = None;
+    }
+
+    /// handle_timeout runs the ICE connectivity checks and releases the paced RTP due
+    /// at now, and enqueues an OnStats event if a periodic stats snapshot is due. The
+    /// datagrams it sends are pulled with poll_write.
+    pub fn handle_timeout(&mut self, now: Instant) {
+        if let (Some(interval), Some(next_stats_time)) = (self.stats_interval, self.next_stats_time)
+        {
+            if now >= next_stats_time {
+                let report = self.get_stats();
+                self.events.push_back(PeerConnectionEvent::OnStats(report));
+                self.next_stats_time = Some(now + interval);
+            }
+        }
+
+        let mut try_timeout = || -> Result<()> {
+            RTCHandler::handle_timeout(&mut self.ice_transport, now)?;
+            self.rtp_pacer.handle_timeout(now)?;
+            self.flush_writes()
+        };
+        if let Err(err) = try_timeout() {
+            log::error!("handle_timeout with error {}", err);
+        }
+    }
+
+    /// poll_timeout returns when handle_timeout should be called next, if ever.
+    pub fn poll_timeout(&mut self) -> Option<Instant> {
+        self.sync_ice_connection_state();
+
+        [
+            self.next_stats_time,
+            RTCHandler::poll_timeout(&mut self.ice_transport),
+            self.rtp_pacer.poll_timeout(),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+
+    /// handle_read passes a datagram received from the socket through the demuxer,
+    /// ICE, DTLS, SCTP and RTCP handlers. The RTP and RTCP received are pulled
+    /// with poll_read; a remote source leaving with an RTCP BYE is reported as a
+    /// SourceRemoved event.
+    pub fn handle_read(&mut self, msg: Transmit<BytesMut>) -> Result<()> {
+        let msgs = vec![Transmit {
+            now: msg.now,
+            transport: msg.transport,
+            message: RTCMessage::Raw(msg.message),
+        }];
+        let msgs = read_through(&mut self.demuxer, msgs)?;
+        let msgs = read_through(&mut self.ice_transport, msgs)?;
+        let msgs = read_through(&mut self.dtls_transport, msgs)?;
+        let msgs = read_through(&mut self.sctp_transport, msgs)?;
+        let msgs = read_through(&mut self.rtcp_bye_handler, msgs)?;
+        let msgs = read_through(&mut self.rtcp_handler, msgs)?;
+
+        for msg in msgs {
+            match &msg.message {
+                RTCMessage::Rtp(RTPMessage::Rtp(packet)) => {
+                    self.stats_accumulator.on_rtp_received(
+                        packet.header.ssrc,
+                        packet.header.sequence_number,
+                        packet.header.timestamp,
+                        packet.payload.len(),
+                    );
+                    if let Some(receiver) = self
+                        .rtp_transceivers
+                        .iter_mut()
+                        .map(|t| t.receiver_mut())
+                        .find(|r| r.ssrc() == Some(packet.header.ssrc))
+                    {
+                        receiver.handle_rtp(
+                            msg.now,
+                            packet.header.payload_type,
+                            packet.marshal_size(),
+                        );
+                    }
+                }
+                RTCMessage::Rtp(RTPMessage::Rtcp(packets)) => {
+                    for sr in packets
+                        .iter()
+                        .filter_map(|p| p.as_any().downcast_ref::<SenderReport>())
+                    {
+                        self.update_lip_sync(sr.ssrc);
+                    }
+                    self.handle_bandwidth_feedback(packets);
+                    let notifications = self.handle_tmmbr(packets);
+                    if !notifications.is_empty() {
+                        self.rtcp_handler.handle_write(Transmit {
+                            now: msg.now,
+                            transport: msg.transport,
+                            message: RTCMessage::Rtp(RTPMessage::Rtcp(notifications)),
+                        })?;
+                    }
+                }
+                _ => {}
+            }
+            self.routs.push_back(msg);
+        }
+
+        while let Some((ssrc, rtt)) = self.rtcp_handler.poll_rtt_measurement() {
+            self.stats_accumulator.on_round_trip_time(ssrc, rtt);
+        }
+
+        while let Some(RtcpByeEvent::SourceRemoved(ssrc, _)) =
+            RTCHandler::poll_event(&mut self.rtcp_bye_handler)
+        {
+            let active_ssrcs: Vec<SSRC> = self
+                .stats_accumulator
+                .active_ssrcs()
+                .into_iter()
+                .filter(|active| *active != ssrc)
+                .collect();
+            self.stats_accumulator.cleanup_stale_streams(&active_ssrcs);
+            self.events
+                .push_back(PeerConnectionEvent::SourceRemoved(ssrc));
+        }
+
+        // handlers may answer what they read, e.g. STUN binding requests
+        self.flush_writes()
+    }
+
+    /// poll_read returns the next RTP or RTCP message received by handle_read.
+    pub fn poll_read(&mut self) -> Option<Transmit<RTCMessage>> {
+        self.sync_ice_connection_state();
+        self.routs.pop_front()
+    }
+
+    /// flush_writes passes what the handlers have queued down the pacer, RTCP, SCTP,
+    /// DTLS, ICE and demuxer handlers, into the datagrams pulled with poll_write.
+    fn flush_writes(&mut self) -> Result<()> {
+        let msgs = write_through(&mut self.rtp_pacer, vec![])?;
+        let msgs = write_through(&mut self.rtcp_handler, msgs)?;
+        let msgs = write_through(&mut self.sctp_transport, msgs)?;
+        let msgs = write_through(&mut self.dtls_transport, msgs)?;
+        let msgs = write_through(&mut self.ice_transport, msgs)?;
+        let msgs = write_through(&mut self.demuxer, msgs)?;
+
+        for msg in msgs {
+            if let RTCMessage::Raw(message) = msg.message {
+                self.wouts.push_back(Transmit {
+                    now: msg.now,
+                    transport: msg.transport,
+                    message,
+                });
+            }
+        }
+        Ok(())
+    }
+
     /*TODO: // set_configuration updates the configuration of this PeerConnection object.
     pub async fn set_configuration(&mut self, configuration: Configuration) -> Result<()> {
         //nolint:gocognit
@@ -750,6 +1087,9 @@ impl RTCPeerConnection {
             relay_acceptance_min_wait: setting_engine.timeout.ice_relay_acceptance_min_wait,
             local_ufrag: setting_engine.candidates.username_fragment.clone(),
             local_pwd: setting_engine.candidates.password.clone(),
+            enable_mdns_obfuscation: setting_engine.candidates.mdns_obfuscation,
+            multicast_dns_mode: setting_engine.candidates.multicast_dns_mode,
+            multicast_dns_host_name: setting_engine.candidates.multicast_dns_host_name.clone(),
             ..Default::default()
         };
 
@@ -791,6 +1131,34 @@ impl RTCPeerConnection {
         ))
     }
 
+    /// restart_ice_in_band restarts ICE with a new local ufrag/pwd, generated if not
+    /// provided, and immediately resumes connectivity checks with the current role and
+    /// remote candidates, without an offer/answer exchange. The remote peer has to learn
+    /// the new credentials through other means. It returns ErrIncorrectSignalingState
+    /// unless the PeerConnection is connected.
+    pub fn restart_ice_in_band(
+        &mut self,
+        new_ufrag: Option<String>,
+        new_pwd: Option<String>,
+    ) -> Result<()> {
+        if self.is_closed {
+            return Err(Error::ErrConnectionClosed);
+        }
+        if self.peer_connection_state != RTCPeerConnectionState::Connected {
+            return Err(Error::ErrIncorrectSignalingState);
+        }
+
+        self.ice_transport
+            .restart_in_band(new_ufrag.unwrap_or_default(), new_pwd.unwrap_or_default())
+    }
+
+    /// restart_ice restarts ICE in band with a generated local ufrag/pwd, see
+    /// restart_ice_in_band.
+    /// <https://w3c.github.io/webrtc-pc/#dom-rtcpeerconnection-restartice>
+    pub fn restart_ice(&mut self) -> Result<()> {
+        self.restart_ice_in_band(None, None)
+    }
+
     pub(crate) fn new_sctp_transport(
         setting_engine: &Arc<SettingEngine>,
     ) -> Result<RTCSctpTransport> {
@@ -807,10 +1175,9 @@ impl RTCPeerConnection {
             return Err(Error::ErrConnectionClosed);
         }
 
-        if let Some(options) = options {
-            if options.ice_restart {
-                self.ice_transport.restart()?;
-            }
+        let options = options.unwrap_or_default();
+        if options.ice_restart {
+            self.ice_transport.restart()?;
         }
 
         // include unmatched local transceivers
@@ -860,11 +1227,12 @@ impl RTCPeerConnection {
         let current_remote_description_is_none = self.current_remote_description.is_none();
 
         let mut d = if current_remote_description_is_none {
-            self.generate_unmatched_sdp()?
+            self.generate_unmatched_sdp(&options.offered_codecs)?
         } else {
             self.generate_matched_sdp(
                 true, /*includeUnmatched */
                 DEFAULT_DTLS_ROLE_OFFER.to_connection_role(),
+                &options.offered_codecs,
             )?
         };
 
@@ -886,8 +1254,10 @@ impl RTCPeerConnection {
     /// create_answer starts the PeerConnection and generates the localDescription
     pub fn create_answer(
         &mut self,
-        _options: Option<RTCAnswerOptions>,
+        options: Option<RTCAnswerOptions>,
     ) -> Result<RTCSessionDescription> {
+        let options = options.unwrap_or_default();
+
         let remote_description = if let Some(desc) = self.remote_description().cloned() {
             desc
         } else {
@@ -902,6 +1272,10 @@ impl RTCPeerConnection {
             return Err(Error::ErrIncorrectSignalingState);
         }
 
+        if let Some(parsed) = &remote_description.parsed {
+            check_answer_payload_types(parsed, &options.offered_codecs)?;
+        }
+
         let mut connection_role = self.setting_engine.answering_dtls_role.to_connection_role();
         if connection_role == ConnectionRole::Unspecified {
             connection_role = DEFAULT_DTLS_ROLE_ANSWER.to_connection_role();
@@ -912,7 +1286,9 @@ impl RTCPeerConnection {
             }
         }
 
-        let mut d = self.generate_matched_sdp(false /*includeUnmatched */, connection_role)?;
+        // the answer keeps the payload types of the offer
+        let mut d =
+            self.generate_matched_sdp(false /*includeUnmatched */, connection_role, &[])?;
 
         update_sdp_origin(&mut self.sdp_origin, &mut d);
 
@@ -929,55 +1305,62 @@ impl RTCPeerConnection {
         Ok(answer)
     }
 
-    /*
     /// Update the PeerConnectionState given the state of relevant transports
-    /// <https://www.w3.org/TR/webrtc/#rtcpeerconnectionstate-enum>
-    async fn update_connection_state(
-        on_peer_connection_state_change_handler: &Arc<
-            ArcSwapOption<Mutex<OnPeerConnectionStateChangeHdlrFn>>,
-        >,
-        is_closed: &Arc<AtomicBool>,
-        peer_connection_state: &Arc<AtomicU8>,
-        ice_connection_state: RTCIceConnectionState,
-        dtls_transport_state: RTCDtlsTransportState,
-    ) {
-        let connection_state =
-            // The RTCPeerConnection object's [[IsClosed]] slot is true.
-            if is_closed.load(Ordering::SeqCst) {
-                RTCPeerConnectionState::Closed
-            } else if ice_connection_state == RTCIceConnectionState::Failed || dtls_transport_state == RTCDtlsTransportState::Failed {
-                // Any of the RTCIceTransports or RTCDtlsTransports are in a "failed" state.
-                RTCPeerConnectionState::Failed
-            } else if ice_connection_state == RTCIceConnectionState::Disconnected {
-                // Any of the RTCIceTransports or RTCDtlsTransports are in the "disconnected"
-                // state and none of them are in the "failed" or "connecting" or "checking" state.
-                RTCPeerConnectionState::Disconnected
-            } else if ice_connection_state == RTCIceConnectionState::Connected && dtls_transport_state == RTCDtlsTransportState::Connected {
-                // All RTCIceTransports and RTCDtlsTransports are in the "connected", "completed" or "closed"
-                // state and at least one of them is in the "connected" or "completed" state.
-                RTCPeerConnectionState::Connected
-            } else if ice_connection_state == RTCIceConnectionState::Checking && dtls_transport_state == RTCDtlsTransportState::Connecting {
-                //  Any of the RTCIceTransports or RTCDtlsTransports are in the "connecting" or
-                // "checking" state and none of them is in the "failed" state.
-                RTCPeerConnectionState::Connecting
-            } else {
-                RTCPeerConnectionState::New
-            };
-
-        if peer_connection_state.load(Ordering::SeqCst) == connection_state as u8 {
+    fn update_connection_state(&mut self) {
+        let connection_state = self.derive_connection_state(self.ice_connection_state);
+        if self.peer_connection_state == connection_state {
             return;
         }
 
         log::info!("peer connection state changed: {}", connection_state);
-        peer_connection_state.store(connection_state as u8, Ordering::SeqCst);
+        self.update_peer_connection_state_change(connection_state);
+    }
 
-        RTCPeerConnection::update_peer_connection_state_change(
-            on_peer_connection_state_change_handler,
-            connection_state,
-        )
-        .await;
+    /// derive_connection_state returns the PeerConnectionState for ice_connection_state
+    /// and the current states of the DTLS and SCTP transports
+    /// <https://www.w3.org/TR/webrtc/#rtcpeerconnectionstate-enum>
+    fn derive_connection_state(
+        &self,
+        ice_connection_state: RTCIceConnectionState,
+    ) -> RTCPeerConnectionState {
+        let dtls_transport_state = self.dtls_transport.state();
+        // once data channels are configured, the SCTP association has to be up as well
+        let sctp_transport_connected = (self.sctp_transport.data_channels.is_empty()
+            && self.sctp_transport.data_channels_requested == 0)
+            || self.sctp_transport.state() == RTCSctpTransportState::Connected;
+
+        // The RTCPeerConnection object's [[IsClosed]] slot is true.
+        if self.is_closed {
+            RTCPeerConnectionState::Closed
+        } else if ice_connection_state == RTCIceConnectionState::Failed
+            || dtls_transport_state == RTCDtlsTransportState::Failed
+        {
+            // Any of the RTCIceTransports or RTCDtlsTransports are in a "failed" state.
+            RTCPeerConnectionState::Failed
+        } else if ice_connection_state == RTCIceConnectionState::Disconnected {
+            // Any of the RTCIceTransports or RTCDtlsTransports are in the "disconnected"
+            // state and none of them are in the "failed" or "connecting" or "checking" state.
+            RTCPeerConnectionState::Disconnected
+        } else if ice_connection_state == RTCIceConnectionState::Connected
+            && dtls_transport_state == RTCDtlsTransportState::Connected
+        {
+            // All RTCIceTransports and RTCDtlsTransports are in the "connected", "completed" or "closed"
+            // state and at least one of them is in the "connected" or "completed" state.
+            if sctp_transport_connected {
+                RTCPeerConnectionState::Connected
+            } else {
+                RTCPeerConnectionState::Connecting
+            }
+        } else if ice_connection_state == RTCIceConnectionState::Checking
+            && dtls_transport_state == RTCDtlsTransportState::Connecting
+        {
+            //  Any of the RTCIceTransports or RTCDtlsTransports are in the "connecting" or
+            // "checking" state and none of them is in the "failed" state.
+            RTCPeerConnectionState::Connecting
+        } else {
+            RTCPeerConnectionState::New
+        }
     }
-    */
 
     // Helper to trigger a negotiation needed.
     fn trigger_negotiation_needed(&self) {
@@ -1210,6 +1593,7 @@ impl RTCPeerConnection {
 
         desc.parsed = Some(desc.unmarshal()?);
         self.set_description(&desc, StateChangeOp::SetLocal)?;
+        self.update_reduced_size_rtcp();
 
         let we_answer = desc.sdp_type == RTCSdpType::Answer;
         let remote_description = self.remote_description().cloned();
@@ -1304,10 +1688,40 @@ impl RTCPeerConnection {
             return Err(Error::ErrConnectionClosed);
         }
 
+        desc.parsed = Some(desc.unmarshal_with_mode(self.setting_engine.sdp_parse_mode)?);
+        self.apply_remote_description(desc)
+    }
+
+    /// set_remote_description_with_fallback is like set_remote_description, but parses
+    /// the SDP strictly first and falls back to lenient parsing if that fails, which
+    /// tolerates remote peers sending subtly invalid SDP.
+    pub fn set_remote_description_with_fallback(
+        &mut self,
+        mut desc: RTCSessionDescription,
+    ) -> Result<()> {
+        if self.is_closed {
+            return Err(Error::ErrConnectionClosed);
+        }
+
+        let parsed = match desc.unmarshal_with_mode(SdpParseMode::Strict) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                log::warn!(
+                    "failed to parse remote description: {}, retrying leniently",
+                    err
+                );
+                desc.unmarshal_with_mode(SdpParseMode::Lenient)?
+            }
+        };
+        desc.parsed = Some(parsed);
+        self.apply_remote_description(desc)
+    }
+
+    fn apply_remote_description(&mut self, desc: RTCSessionDescription) -> Result<()> {
         let is_renegotiation = self.current_remote_description.is_some();
 
-        desc.parsed = Some(desc.unmarshal()?);
         self.set_description(&desc, StateChangeOp::SetRemote)?;
+        self.update_reduced_size_rtcp();
 
         if let Some(parsed) = &desc.parsed {
             self.media_engine.update_from_remote_description(parsed)?;
@@ -1358,52 +1772,27 @@ impl RTCPeerConnection {
                                 t.set_mid(mid_value.to_string())?;
                             }
                         } else {
-                            let _local_direction =
+                            let local_direction =
                                 if direction == RTCRtpTransceiverDirection::Recvonly {
                                     RTCRtpTransceiverDirection::Sendonly
                                 } else {
                                     RTCRtpTransceiverDirection::Recvonly
                                 };
 
-                            let _receive_mtu = self.setting_engine.get_receive_mtu();
+                            let receive_mtu = self.setting_engine.get_receive_mtu();
 
-                            /*TODO:
-                            let receiver = Arc::new(RTCRtpReceiver::new(
-                                receive_mtu,
-                                kind,
-                                Arc::clone(&self.internal.dtls_transport),
-                                Arc::clone(&self.internal.media_engine),
-                                Arc::clone(&self.interceptor),
-                            ));
-
-                            let sender = Arc::new(
-                                RTCRtpSender::new(
-                                    receive_mtu,
-                                    None,
-                                    Arc::clone(&self.internal.dtls_transport),
-                                    Arc::clone(&self.internal.media_engine),
-                                    Arc::clone(&self.interceptor),
-                                    false,
-                                )
-                                .await,
-                            );
-
-                            let t = RTCRtpTransceiver::new(
+                            let receiver = RTCRtpReceiver::new(receive_mtu, kind);
+                            let sender = RTCRtpSender::new(receive_mtu, None, false);
+                            let mut t = RTCRtpTransceiver::new(
                                 receiver,
                                 sender,
                                 local_direction,
                                 kind,
                                 vec![],
-                                Arc::clone(&self.internal.media_engine),
-                                Some(Box::new(self.internal.make_negotiation_needed_trigger())),
-                            )
-                            .await;
-
-                            self.internal.add_rtp_transceiver(Arc::clone(&t)).await;
+                            );
+                            t.set_mid(mid_value.to_string())?;
 
-                            if t.mid().is_none() {
-                                t.set_mid(mid_value.to_string())?;
-                            }*/
+                            self.add_rtp_transceiver(t);
                         }
                     }
                 }
@@ -1462,6 +1851,43 @@ impl RTCPeerConnection {
                 }
             }
 
+            // Honor the bitrate the remote is willing to receive on each media section
+            for media in &parsed.media_descriptions {
+                if let (Some(mid_value), Some(max_bitrate)) =
+                    (get_mid_value(media), get_tias_bitrate(media))
+                {
+                    if let Some((_, t)) = find_by_mid(mid_value, &mut self.rtp_transceivers) {
+                        t.sender_mut().set_max_bitrate(max_bitrate);
+                    }
+                }
+            }
+
+            // Remember the SSRC and stream ids the remote announced for each media section
+            for media in &parsed.media_descriptions {
+                if let Some(mid_value) = get_mid_value(media) {
+                    if let Some((_, t)) = find_by_mid(mid_value, &mut self.rtp_transceivers) {
+                        if let (RTPCodecType::Audio, Some(ssrc)) = (t.kind(), get_ssrc(media)) {
+                            // the receiver reports of audio carry the loss pattern in XR
+                            self.stats_accumulator.add_loss_rle(ssrc);
+                        }
+                        if let Some(ssrc) = get_ssrc(media) {
+                            let clock_rate = codecs_from_media_description(media)
+                                .ok()
+                                .and_then(|codecs| codecs.first().map(|c| c.capability.clock_rate));
+                            if let Some(clock_rate) = clock_rate {
+                                if self.rtcp_handler.lip_sync_estimator(ssrc).is_none() {
+                                    self.rtcp_handler.add_lip_sync_estimator(ssrc, clock_rate);
+                                }
+                            }
+                        }
+                        t.receiver_mut().set_ssrc(get_ssrc(media));
+                        t.receiver_mut().set_stream_ids(
+                            media.msids().into_iter().map(|m| m.stream_id).collect(),
+                        );
+                    }
+                }
+            }
+
             let (remote_ufrag, remote_pwd, candidates) = extract_ice_details(parsed)?;
 
             if is_renegotiation
@@ -1469,9 +1895,15 @@ impl RTCPeerConnection {
                     .ice_transport
                     .have_remote_credentials_change(&remote_ufrag, &remote_pwd)
             {
+                // RFC 8445 S9.3: a changed remote ufrag/pwd signals an ICE restart
+                self.events.push_back(PeerConnectionEvent::OnIceRestart {
+                    new_ufrag: remote_ufrag.clone(),
+                });
+
                 // An ICE Restart only happens implicitly for a set_remote_description of type offer
                 if !we_offer {
                     self.ice_transport.restart()?;
+                    self.update_ice_connection_state_change(RTCIceConnectionState::Checking);
                 }
 
                 self.ice_transport
@@ -1522,6 +1954,10 @@ impl RTCPeerConnection {
                 RTCIceRole::Controlled
             };
 
+            // Remember the remote credentials so that a later ICE restart can be detected
+            self.ice_transport
+                .set_remote_credentials(remote_ufrag.clone(), remote_pwd.clone())?;
+
             // Start the networking in a new routine since it will block until
             // the connection is actually established.
             if we_offer {
@@ -1604,10 +2040,349 @@ impl RTCPeerConnection {
         self.ice_transport.add_remote_candidate(ice_candidate)
     }
 
-    /// ice_connection_state returns the ICE connection state of the
-    /// PeerConnection instance.
+    /// ice_connection_state returns the ICE connection state of the underlying
+    /// ICE agent. It does not wait for a poll_* call to process the agent's
+    /// state changes.
     pub fn ice_connection_state(&self) -> RTCIceConnectionState {
-        self.ice_connection_state
+        RTCIceConnectionState::from(self.ice_transport.agent_state())
+    }
+
+    /// transceivers returns the RTPTransceivers that are currently attached to this PeerConnection
+    pub fn transceivers(&self) -> &[RTCRtpTransceiver] {
+        &self.rtp_transceivers
+    }
+
+    /// transceiver_by_mid returns the RTPTransceiver negotiated with the given mid, if any
+    pub fn transceiver_by_mid(&self, mid: &str) -> Option<&RTCRtpTransceiver> {
+        self.rtp_transceivers
+            .iter()
+            .find(|t| t.mid().is_some_and(|m| m == mid))
+    }
+
+    /// send_rtp passes an RTP packet created by the application for the sender of the
+    /// track with the given id down the pacer, which releases it to be protected and
+    /// queued for the selected ICE candidate pair.
+    /// The packet's SSRC must match the sender's SSRC. Packets for an inactive encoding
+    /// or over the sender's max bitrate are rejected; the latter count as packets_lost.
+    pub fn send_rtp(
+        &mut self,
+        now: Instant,
+        track_id: &str,
+        packet: rtp::packet::Packet,
+    ) -> Result<()> {
+        if self.is_closed {
+            return Err(Error::ErrConnectionClosed);
+        }
+
+        let transport = self.selected_transport();
+        let sender = self
+            .rtp_transceivers
+            .iter_mut()
+            .map(|t| t.sender_mut())
+            .find(|s| s.track().is_some_and(|track| track.id() == track_id))
+            .ok_or(Error::ErrUnknownTrack)?;
+        if packet.header.ssrc != sender.ssrc {
+            return Err(Error::ErrSsrcNotFound);
+        }
+        if !sender.active {
+            return Err(Error::ErrRTPSenderNotActive);
+        }
+
+        let transport = transport?;
+        if self.dtls_transport.local_srtp_context.is_none() {
+            return Err(Error::ErrDtlsTransportNotStarted);
+        }
+
+        if !sender.handle_write(now, packet.marshal_size()) {
+            return Err(Error::ErrRTPSenderMaxBitrateExceeded);
+        }
+        sender.payload_type = packet.header.payload_type;
+
+        self.stats_accumulator.on_rtp_sent(
+            packet.header.ssrc,
+            packet.header.marshal_size(),
+            packet.payload.len(),
+        );
+        self.rtp_pacer.handle_write(Transmit {
+            now,
+            transport,
+            message: RTCMessage::Rtp(RTPMessage::Rtp(packet)),
+        })?;
+        self.flush_writes()
+    }
+
+    /// send_rtcp passes RTCP packets created by the application to the RTCP handler,
+    /// which bundles them into compound packets to be protected and queued for the
+    /// selected ICE candidate pair. TWCC feedback whose estimate dropped below the
+    /// incoming bitrate is followed by a TMMBR, and a SR or RR by an XR with the Loss
+    /// RLE blocks of the audio received since the previous one.
+    pub fn send_rtcp(
+        &mut self,
+        now: Instant,
+        mut packets: Vec<Box<dyn rtcp::packet::Packet>>,
+    ) -> Result<()> {
+        if self.is_closed {
+            return Err(Error::ErrConnectionClosed);
+        }
+
+        let transport = self.selected_transport()?;
+        if self.dtls_transport.local_srtp_context.is_none() {
+            return Err(Error::ErrDtlsTransportNotStarted);
+        }
+
+        // report as the first local sender, a receive-only connection keeps its random SSRC
+        if let Some(sender) = self.rtp_transceivers.first().map(|t| t.sender()) {
+            self.rtcp_handler.set_local_ssrc(sender.ssrc);
+        }
+        // drop the feedback the remote sender's codec did not negotiate
+        packets.retain(|p| {
+            let any = p.as_any();
+            if let Some(nack) = any.downcast_ref::<TransportLayerNack>() {
+                self.supports_rtcp_feedback(nack.media_ssrc, TYPE_RTCP_FB_NACK, "")
+            } else if let Some(pli) = any.downcast_ref::<PictureLossIndication>() {
+                self.supports_rtcp_feedback(pli.media_ssrc, TYPE_RTCP_FB_NACK, "pli")
+            } else if let Some(twcc) = any.downcast_ref::<TransportLayerCc>() {
+                self.supports_rtcp_feedback(twcc.media_ssrc, TYPE_RTCP_FB_TRANSPORT_CC, "")
+            } else {
+                true
+            }
+        });
+        // ask the remote senders to slow down to the estimate of the TWCC feedback
+        let entries: Vec<TmmbEntry> = packets
+            .iter()
+            .filter_map(|p| p.as_any().downcast_ref::<TransportLayerCc>())
+            .flat_map(|twcc| {
+                self.rtp_transceivers
+                    .iter_mut()
+                    .map(|t| t.receiver_mut())
+                    .filter(|r| r.ssrc() == Some(twcc.media_ssrc))
+                    .filter_map(|r| r.handle_twcc(twcc))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        if !entries.is_empty() {
+            packets.push(Box::new(TmmbrPacket {
+                is_notification: false,
+                sender_ssrc: self.rtcp_handler.local_ssrc(),
+                media_ssrc: 0,
+                entries,
+            }));
+        }
+        if packets.iter().any(|p| {
+            matches!(
+                p.header().packet_type,
+                PacketType::SenderReport | PacketType::ReceiverReport
+            )
+        }) {
+            let reports = self.stats_accumulator.take_loss_rle_reports();
+            if !reports.is_empty() {
+                packets.push(Box::new(ExtendedReport {
+                    sender_ssrc: self.rtcp_handler.local_ssrc(),
+                    reports: reports
+                        .into_iter()
+                        .map(|r| Box::new(r) as Box<dyn rtcp::packet::Packet>)
+                        .collect(),
+                }));
+            }
+        }
+        self.rtcp_handler.handle_write(Transmit {
+            now,
+            transport,
+            message: RTCMessage::Rtp(RTPMessage::Rtcp(packets)),
+        })?;
+        self.flush_writes()
+    }
+
+    /// supports_rtcp_feedback returns true if the codec negotiated for the payload type
+    /// the receiver of media_ssrc receives includes the given RTCP feedback.
+    fn supports_rtcp_feedback(&self, media_ssrc: SSRC, typ: &str, parameter: &str) -> bool {
+        self.rtp_transceivers
+            .iter()
+            .map(|t| t.receiver())
+            .find(|r| r.ssrc() == Some(media_ssrc))
+            .and_then(|r| r.payload_type())
+            .is_some_and(|payload_type| {
+                self.codec_supports_rtcp_feedback(payload_type, typ, parameter)
+            })
+    }
+
+    /// codec_supports_rtcp_feedback returns true if the codec negotiated for
+    /// payload_type includes the given RTCP feedback.
+    fn codec_supports_rtcp_feedback(
+        &self,
+        payload_type: PayloadType,
+        typ: &str,
+        parameter: &str,
+    ) -> bool {
+        self.media_engine
+            .get_codec_by_payload(payload_type)
+            .is_ok_and(|(codec, _)| codec.capability.supports_rtcp_feedback(typ, parameter))
+    }
+
+    fn selected_transport(&self) -> Result<TransportContext> {
+        let (local, remote) = self
+            .ice_transport
+            .gatherer
+            .agent
+            .get_selected_candidate_pair()
+            .ok_or(Error::ErrICEConnectionNotStarted)?;
+        Ok(TransportContext {
+            local_addr: local.addr(),
+            peer_addr: remote.addr(),
+            protocol: local.network_type().to_protocol(),
+            ecn: None,
+        })
+    }
+
+    /// set_pacing_bitrate sets the rate RTP written with send_rtp is released at,
+    /// 0 disables pacing.
+    pub fn set_pacing_bitrate(&mut self, target_bitrate_bps: u64) {
+        self.rtp_pacer.set_target_bitrate_bps(target_bitrate_bps);
+    }
+
+    /// add_lip_sync_estimator starts mapping the RTP timestamps of the remote
+    /// source ssrc to wallclock time from its RTCP sender reports.
+    pub fn add_lip_sync_estimator(&mut self, ssrc: SSRC, clock_rate: u32) {
+        self.rtcp_handler.add_lip_sync_estimator(ssrc, clock_rate);
+    }
+
+    /// lip_sync_estimator returns the estimator added for the remote source ssrc
+    pub fn lip_sync_estimator(&self, ssrc: SSRC) -> Option<&LipSyncEstimator> {
+        self.rtcp_handler.lip_sync_estimator(ssrc)
+    }
+
+    /// stats_accumulator returns the state of the remote sources RTP is received from
+    pub fn stats_accumulator(&self) -> &RTCStatsAccumulator {
+        &self.stats_accumulator
+    }
+
+    /// update_lip_sync compares the wall clock estimates of the latest RTP received
+    /// from the remote source ssrc and from the source of the other kind in the same
+    /// MediaStream, records the playout delay of the later one and queues a
+    /// LipSyncEstimate event.
+    fn update_lip_sync(&mut self, ssrc: SSRC) {
+        let Some(transceiver) = self
+            .rtp_transceivers
+            .iter()
+            .find(|t| t.receiver().ssrc() == Some(ssrc))
+        else {
+            return;
+        };
+        let Some(other) = self.rtp_transceivers.iter().find(|t| {
+            t.kind() != transceiver.kind()
+                && t.receiver()
+                    .stream_ids()
+                    .iter()
+                    .any(|id| transceiver.receiver().stream_ids().contains(id))
+        }) else {
+            return;
+        };
+        let (audio_ssrc, video_ssrc) = match (transceiver.kind(), other.receiver().ssrc()) {
+            (RTPCodecType::Audio, Some(other_ssrc)) => (ssrc, other_ssrc),
+            (RTPCodecType::Video, Some(other_ssrc)) => (other_ssrc, ssrc),
+            _ => return,
+        };
+
+        let last_rtp_timestamp = |ssrc: SSRC| {
+            self.stats_accumulator
+                .inbound_stream(ssrc)
+                .and_then(|s| s.last_rtp_timestamp)
+        };
+        let (Some(audio_ts), Some(video_ts), Some(audio), Some(video)) = (
+            last_rtp_timestamp(audio_ssrc),
+            last_rtp_timestamp(video_ssrc),
+            self.rtcp_handler.lip_sync_estimator(audio_ssrc),
+            self.rtcp_handler.lip_sync_estimator(video_ssrc),
+        ) else {
+            return;
+        };
+        let Some(offset_ms) = av_offset_ms(audio, audio_ts, video, video_ts) else {
+            return;
+        };
+
+        // the media captured later is held back by the offset
+        let offset = Duration::from_millis(offset_ms.unsigned_abs() as u64);
+        let (audio_delay, video_delay) = if offset_ms >= 0 {
+            (offset, Duration::ZERO)
+        } else {
+            (Duration::ZERO, offset)
+        };
+        self.stats_accumulator
+            .set_estimated_playout_delay(audio_ssrc, audio_delay);
+        self.stats_accumulator
+            .set_estimated_playout_delay(video_ssrc, video_delay);
+        self.events.push_back(PeerConnectionEvent::LipSyncEstimate {
+            audio_ts,
+            video_ts,
+            offset_ms,
+        });
+    }
+
+    /// handle_tmmbr applies the TMMBR packets received to the senders they are about,
+    /// and returns the TMMBN packets acknowledging them.
+    fn handle_tmmbr(
+        &mut self,
+        packets: &[Box<dyn rtcp::packet::Packet>],
+    ) -> Vec<Box<dyn rtcp::packet::Packet>> {
+        let mut notifications: Vec<Box<dyn rtcp::packet::Packet>> = vec![];
+        for tmmbr in packets
+            .iter()
+            .filter_map(|p| p.as_any().downcast_ref::<TmmbrPacket>())
+            .filter(|p| !p.is_notification)
+        {
+            for sender in self.rtp_transceivers.iter_mut().map(|t| t.sender_mut()) {
+                if let Some(tmmbn) = sender.handle_tmmbr(tmmbr) {
+                    notifications.push(Box::new(tmmbn));
+                }
+            }
+        }
+        notifications
+    }
+
+    /// handle_bandwidth_feedback applies the TWCC and REMB packets of an incoming RTCP
+    /// compound packet to the bandwidth estimation of the senders they are about, and
+    /// queues a BitrateEstimateUpdate event for each sender whose target bitrate changed
+    /// by more than 10%. REMB is ignored for senders whose codec negotiated transport-cc.
+    fn handle_bandwidth_feedback(&mut self, packets: &[Box<dyn rtcp::packet::Packet>]) {
+        for packet in packets {
+            let packet = packet.as_any();
+            for i in 0..self.rtp_transceivers.len() {
+                let sender = self.rtp_transceivers[i].sender();
+                let bps = if let Some(twcc) = packet.downcast_ref::<TransportLayerCc>() {
+                    if twcc.media_ssrc != sender.ssrc {
+                        continue;
+                    }
+                    self.rtp_transceivers[i].sender_mut().handle_twcc(twcc)
+                } else if let Some(remb) = packet.downcast_ref::<ReceiverEstimatedMaximumBitrate>()
+                {
+                    if !remb.ssrcs.contains(&sender.ssrc)
+                        || self.codec_supports_rtcp_feedback(
+                            sender.payload_type,
+                            TYPE_RTCP_FB_TRANSPORT_CC,
+                            "",
+                        )
+                    {
+                        continue;
+                    }
+                    self.rtp_transceivers[i].sender_mut().handle_remb(remb)
+                } else {
+                    None
+                };
+                if let Some(bps) = bps {
+                    let ssrc = self.rtp_transceivers[i].sender().ssrc;
+                    self.events
+                        .push_back(PeerConnectionEvent::BitrateEstimateUpdate(ssrc, bps));
+                }
+            }
+        }
+    }
+
+    /// poll_write returns the next datagram queued by the handlers, e.g. the SRTP
+    /// packets written with send_rtp, to be written to the socket of its transport
+    /// context.
+    pub fn poll_write(&mut self) -> Option<Transmit<BytesMut>> {
+        self.sync_ice_connection_state();
+        self.wouts.pop_front()
     }
 
     /*
@@ -1757,6 +2532,67 @@ impl RTCPeerConnection {
         Ok(t)
     }
     */
+    /// add_transceiver_from_kind Create a new RtpTransceiver and adds it to the set of transceivers.
+    pub fn add_transceiver_from_kind(
+        &mut self,
+        kind: RTPCodecType,
+        init: Option<RTCRtpTransceiverInit>,
+    ) -> Result<&mut RTCRtpTransceiver> {
+        if self.is_closed {
+            return Err(Error::ErrConnectionClosed);
+        }
+
+        let direction = init
+            .map(|value| value.direction)
+            .unwrap_or(RTCRtpTransceiverDirection::Sendrecv);
+
+        if direction == RTCRtpTransceiverDirection::Unspecified {
+            return Err(Error::ErrPeerConnAddTransceiverFromKindSupport);
+        }
+
+        let receive_mtu = self.setting_engine.get_receive_mtu();
+        let receiver = RTCRtpReceiver::new(receive_mtu, kind);
+        let sender = RTCRtpSender::new(receive_mtu, None, false);
+        let t = RTCRtpTransceiver::new(receiver, sender, direction, kind, vec![]);
+
+        Ok(self.add_rtp_transceiver(t))
+    }
+
+    /// add_transceiver_from_track Create a new RtpTransceiver(SendRecv or SendOnly) and add it to the set of transceivers.
+    pub fn add_transceiver_from_track(
+        &mut self,
+        track: Box<dyn TrackLocal + Send + Sync>,
+        init: Option<RTCRtpTransceiverInit>,
+    ) -> Result<&mut RTCRtpTransceiver> {
+        if self.is_closed {
+            return Err(Error::ErrConnectionClosed);
+        }
+
+        let direction = init
+            .map(|init| init.direction)
+            .unwrap_or(RTCRtpTransceiverDirection::Sendrecv);
+
+        if direction == RTCRtpTransceiverDirection::Unspecified {
+            return Err(Error::ErrPeerConnAddTransceiverFromTrackSupport);
+        }
+
+        let receive_mtu = self.setting_engine.get_receive_mtu();
+        let kind = track.kind();
+        let receiver = RTCRtpReceiver::new(receive_mtu, kind);
+        let sender = RTCRtpSender::new(receive_mtu, Some(track), false);
+        let t = RTCRtpTransceiver::new(receiver, sender, direction, kind, vec![]);
+
+        Ok(self.add_rtp_transceiver(t))
+    }
+
+    /// add_rtp_transceiver appends t into rtp_transceivers
+    /// and fires onNegotiationNeeded
+    fn add_rtp_transceiver(&mut self, t: RTCRtpTransceiver) -> &mut RTCRtpTransceiver {
+        self.rtp_transceivers.push(t);
+        self.trigger_negotiation_needed();
+        self.rtp_transceivers.last_mut().unwrap()
+    }
+
     /// create_data_channel creates a new DataChannel object with the given label
     /// and optional DataChannelInit used to configure properties of the
     /// underlying channel such as data reliability.
@@ -1805,9 +2641,12 @@ impl RTCPeerConnection {
             }
 
             // https://w3c.github.io/webrtc-pc/#peer-to-peer-data-api (Step #11)
-            if params.protocol.len() > 65535 {
+            if params.protocol.len() > DATA_CHANNEL_PROTOCOL_MAX_LENGTH {
                 return Err(Error::ErrProtocolTooLarge);
             }
+            if params.protocol.contains('\0') {
+                return Err(Error::ErrProtocolInvalid);
+            }
 
             // https://w3c.github.io/webrtc-pc/#peer-to-peer-data-api (Step #12)
             params.negotiated = options.negotiated;
@@ -1834,6 +2673,72 @@ impl RTCPeerConnection {
 
         Ok(())
     }
+
+    /// data_channel returns the DataChannel with the given label
+    pub fn data_channel(&self, label: &str) -> Option<&RTCDataChannel> {
+        self.sctp_transport.data_channels.get(label)
+    }
+
+    /// data_channel_count returns the number of DataChannels of this PeerConnection
+    pub fn data_channel_count(&self) -> usize {
+        self.sctp_transport.data_channels.len()
+    }
+
+    /// has_data_channel returns true if a DataChannel with the given id exists
+    pub fn has_data_channel(&self, id: RTCDataChannelId) -> bool {
+        self.sctp_transport
+            .data_channels
+            .values()
+            .any(|d| d.id() == id)
+    }
+
+    /// data_channel_ids returns the ids of all DataChannels of this PeerConnection
+    pub fn data_channel_ids(&self) -> Vec<RTCDataChannelId> {
+        self.sctp_transport
+            .data_channels
+            .values()
+            .map(|d| d.id())
+            .collect()
+    }
+
+    /// close_data_channel resets the SCTP stream of the DataChannel with the given id
+    /// and removes it from this PeerConnection.
+    pub fn close_data_channel(&mut self, id: RTCDataChannelId) -> Result<()> {
+        let label = match self
+            .sctp_transport
+            .data_channels
+            .iter()
+            .find(|(_, d)| d.id() == id)
+        {
+            Some((label, _)) => label.clone(),
+            None => return Err(Error::ErrDataChannelNotExisted),
+        };
+
+        if let Some(mut d) = self.sctp_transport.data_channels.remove(&label) {
+            d.close(&mut self.sctp_transport)?;
+
+            // Opened channels are reported closed once the peer confirms the stream reset
+            if d.ready_state() == RTCDataChannelState::Closed {
+                self.events
+                    .push_back(PeerConnectionEvent::OnDataChannelClosed(id));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// enable_partial_delivery makes incoming messages of the DataChannel with the
+    /// given id that are larger than threshold_bytes be delivered as a sequence of
+    /// RTCDataChannelMessage::Partial pieces of at most threshold_bytes each,
+    /// instead of as a single Complete message.
+    pub fn enable_partial_delivery(
+        &mut self,
+        id: RTCDataChannelId,
+        threshold_bytes: usize,
+    ) -> Result<()> {
+        self.sctp_transport
+            .enable_partial_delivery(id, threshold_bytes)
+    }
     /*
     /// set_identity_provider is used to configure an identity provider to generate identity assertions
     pub fn set_identity_provider(&self, _provider: &str) -> Result<()> {
@@ -1990,6 +2895,85 @@ impl RTCPeerConnection {
         self.peer_connection_state
     }
 
+    /// peer_connection_state returns the connection state derived from the
+    /// current states of the ICE agent and the DTLS and SCTP transports. Unlike
+    /// connection_state, it does not wait for a poll_* call to process their changes.
+    pub fn peer_connection_state(&self) -> RTCPeerConnectionState {
+        self.derive_connection_state(self.ice_connection_state())
+    }
+
+    /// get_stats returns a snapshot of the statistics of the ICE, DTLS and SCTP
+    /// transports, the data channels and the RTP senders of this PeerConnection.
+    pub fn get_stats(&mut self) -> StatsReport {
+        let mut collector = StatsCollector::new();
+        self.ice_transport.gatherer.collect_stats(&mut collector);
+        self.ice_transport.collect_stats(&mut collector);
+        self.dtls_transport.collect_stats(&mut collector);
+        self.sctp_transport
+            .collect_stats(&mut collector, self.stats_id.clone());
+        self.collect_outbound_stats(&mut collector);
+        collector.into()
+    }
+
+    /// collect_outbound_stats reports the RTP sent by each sender with a track, and
+    /// the round trip times measured from the reception reports about it.
+    fn collect_outbound_stats(&self, collector: &mut StatsCollector) {
+        for transceiver in &self.rtp_transceivers {
+            let sender = transceiver.sender();
+            let Some(track) = sender.track() else {
+                continue;
+            };
+            let Some(stream) = self.stats_accumulator.outbound_stream(sender.ssrc) else {
+                continue;
+            };
+            let (kind, stream_kind) = match transceiver.kind() {
+                RTPCodecType::Unspecified => continue,
+                RTPCodecType::Audio => ("audio", "Audio"),
+                RTPCodecType::Video => ("video", "Video"),
+            };
+
+            let local_id = format!("RTCOutboundRTP{}Stream_{}", stream_kind, sender.ssrc);
+            collector.insert(
+                local_id.clone(),
+                StatsReportType::OutboundRTP(OutboundRTPStats {
+                    timestamp: Instant::now(),
+                    stats_type: RTCStatsType::OutboundRTP,
+                    id: local_id.clone(),
+                    ssrc: sender.ssrc,
+                    kind,
+                    packets_sent: stream.packets_sent,
+                    bytes_sent: stream.bytes_sent,
+                    track_identifier: track.id().to_owned(),
+                    mid: transceiver.mid().cloned().unwrap_or_default(),
+                    rid: None,
+                    header_bytes_sent: stream.header_bytes_sent,
+                    nack_count: 0,
+                    fir_count: None,
+                    pli_count: None,
+                }),
+            );
+
+            let id = format!("RTCRemoteInboundRTP{}Stream_{}", stream_kind, sender.ssrc);
+            collector.insert(
+                id.clone(),
+                StatsReportType::RemoteInboundRTP(RemoteInboundRTPStats {
+                    timestamp: Instant::now(),
+                    stats_type: RTCStatsType::RemoteInboundRTP,
+                    id,
+                    ssrc: sender.ssrc,
+                    kind,
+                    packets_received: 0,
+                    packets_lost: 0,
+                    local_id,
+                    round_trip_time: stream.round_trip_time,
+                    total_round_trip_time: stream.total_round_trip_time,
+                    fraction_lost: 0.0,
+                    round_trip_time_measurements: stream.round_trip_time_measurements,
+                }),
+            );
+        }
+    }
+
     /*
     pub async fn get_stats(&self) -> StatsReport {
         self.internal
@@ -2051,6 +3035,19 @@ impl RTCPeerConnection {
     /// otherwise it returns current_remote_description. This property is used to
     /// determine if setRemoteDescription has already been called.
     /// <https://www.w3.org/TR/webrtc/#dom-rtcpeerconnection-remotedescription>
+    /// update_reduced_size_rtcp enables reduced-size RTCP once both the local and the
+    /// remote description carry a=rtcp-rsize.
+    fn update_reduced_size_rtcp(&mut self) {
+        let local_description = self.local_description();
+        let reduced_size = [local_description.as_ref(), self.remote_description()]
+            .into_iter()
+            .all(|desc| {
+                desc.and_then(|desc| desc.parsed.as_ref())
+                    .is_some_and(have_rtcp_rsize)
+            });
+        self.rtcp_handler.set_reduced_size(reduced_size);
+    }
+
     fn remote_description(&self) -> Option<&RTCSessionDescription> {
         if self.pending_remote_description.is_some() {
             self.pending_remote_description.as_ref()
@@ -2071,7 +3068,10 @@ impl RTCPeerConnection {
 
     /// generate_unmatched_sdp generates an SDP that doesn't take remote state into account
     /// This is used for the initial call for CreateOffer
-    pub(super) fn generate_unmatched_sdp(&mut self) -> Result<SessionDescription> {
+    pub(super) fn generate_unmatched_sdp(
+        &mut self,
+        offered_codecs: &[OfferedCodec],
+    ) -> Result<SessionDescription> {
         let d = SessionDescription::new_jsep_session_description(false /*use_identity*/);
 
         let ice_params = self.ice_transport.gatherer.get_local_parameters()?;
@@ -2122,6 +3122,8 @@ impl RTCPeerConnection {
             is_icelite: self.setting_engine.candidates.ice_lite,
             connection_role: DEFAULT_DTLS_ROLE_OFFER.to_connection_role(),
             ice_gathering_state: self.ice_gathering_state(),
+            payload_type_overrides: payload_type_overrides(offered_codecs)?,
+            extension_ids: HashMap::new(),
         };
         populate_sdp(
             d,
@@ -2142,6 +3144,7 @@ impl RTCPeerConnection {
         &mut self,
         include_unmatched: bool,
         connection_role: ConnectionRole,
+        offered_codecs: &[OfferedCodec],
     ) -> Result<SessionDescription> {
         let d = SessionDescription::new_jsep_session_description(false /*use_identity*/);
 
@@ -2152,9 +3155,12 @@ impl RTCPeerConnection {
         let mut already_have_application_media_section = false;
         let mut matched: HashSet<Mid> = HashSet::new();
         let mut mid2index: HashMap<Mid, usize> = HashMap::new();
+        let mut remote_extensions = vec![];
         if let Some(remote_description) = self.remote_description().cloned() {
             if let Some(parsed) = &remote_description.parsed {
                 for media in &parsed.media_descriptions {
+                    remote_extensions.extend(rtp_extensions_from_media_description(media)?);
+
                     if let Some(mid_value) = get_mid_value(media) {
                         if mid_value.is_empty() {
                             return Err(Error::ErrPeerConnRemoteDescriptionWithoutMidValue);
@@ -2230,6 +3236,21 @@ impl RTCPeerConnection {
             }
         }
 
+        // The answer keeps the IDs of the offer for the extensions both sides support.
+        // The other local extensions keep their assigned ID unless the offer uses it.
+        let mut negotiator = ExtmapNegotiator::new();
+        let local_ids = negotiator.assign_ids(&self.media_engine.header_extension_uris());
+        let remote_extensions: Vec<(u8, &str)> = remote_extensions
+            .iter()
+            .filter_map(|(uri, id)| u8::try_from(*id).ok().map(|id| (id, uri.as_str())))
+            .collect();
+        let mut extension_ids = negotiator.reconcile(&remote_extensions);
+        for (uri, id) in local_ids {
+            if !extension_ids.values().any(|&v| v == id) {
+                extension_ids.entry(uri).or_insert(id);
+            }
+        }
+
         let dtls_fingerprints = if let Some(cert) = self.dtls_transport.certificates.first() {
             cert.get_fingerprints()
         } else {
@@ -2241,6 +3262,8 @@ impl RTCPeerConnection {
             is_icelite: self.setting_engine.candidates.ice_lite,
             connection_role,
             ice_gathering_state: self.ice_gathering_state(),
+            payload_type_overrides: payload_type_overrides(offered_codecs)?,
+            extension_ids,
         };
         populate_sdp(
             d,
@@ -1,15 +1,33 @@
+/// OfferedCodec pins a codec to a specific payload type in the generated
+/// description, overriding the payload type assigned by the MediaEngine.
+#[derive(Default, Debug, PartialEq, Eq, Clone)]
+pub struct OfferedCodec {
+    /// mime_type of the codec, e.g. "video/VP8". Matching is case-insensitive.
+    pub mime_type: String,
+    /// sdp_fmtp_line of the codec, which tells apart codecs sharing a mime type
+    /// such as the H264 profiles. Matching is exact.
+    pub sdp_fmtp_line: String,
+    /// preferred_payload_type is used in the a=rtpmap and a=rtcp-fb lines of the codec.
+    pub preferred_payload_type: u8,
+}
+
 /// AnswerOptions structure describes the options used to control the answer
 /// creation process.
-#[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
+#[derive(Default, Debug, PartialEq, Eq, Clone)]
 pub struct RTCAnswerOptions {
     /// voice_activity_detection allows the application to provide information
     /// about whether it wishes voice detection feature to be enabled or disabled.
     pub voice_activity_detection: bool,
+
+    /// offered_codecs lists the payload types expected for the listed codecs. An answer
+    /// reuses the payload types of the offer, so create_answer fails with
+    /// ErrPayloadTypeConflict if the offer assigned one of them another payload type.
+    pub offered_codecs: Vec<OfferedCodec>,
 }
 
 /// OfferOptions structure describes the options used to control the offer
 /// creation process
-#[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
+#[derive(Default, Debug, PartialEq, Eq, Clone)]
 pub struct RTCOfferOptions {
     /// voice_activity_detection allows the application to provide information
     /// about whether it wishes voice detection feature to be enabled or disabled.
@@ -19,4 +37,7 @@ pub struct RTCOfferOptions {
     /// When this value is true, the generated description will have ICE
     /// credentials that are different from the current credentials
     pub ice_restart: bool,
+
+    /// offered_codecs forces the listed codecs to use the given payload types.
+    pub offered_codecs: Vec<OfferedCodec>,
 }
@@ -1,4 +1,35 @@
-use std::sync::atomic::AtomicU32;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+
+use super::*;
+use crate::api::media_engine::{MediaEngine, MIME_TYPE_OPUS, MIME_TYPE_VP8};
+use crate::api::setting_engine::SettingEngine;
+use crate::api::APIBuilder;
+use crate::peer_connection::configuration::RTCConfiguration;
+use crate::rtp_transceiver::rtp_codec::{
+    RTCRtpCodecCapability, RTCRtpCodecParameters, RTCRtpHeaderExtensionCapability, RTPCodecType,
+};
+use crate::rtp_transceiver::rtp_transceiver_direction::RTCRtpTransceiverDirection;
+use crate::rtp_transceiver::{RTCPFeedback, RTCRtpTransceiverInit, TYPE_RTCP_FB_TRANSPORT_CC};
+use crate::track::track_local::track_local_static_rtp::TrackLocalStaticRTP;
+use ice::agent::agent_config::AgentConfig;
+use ice::candidate::candidate_host::CandidateHostConfig;
+use ice::candidate::CandidateConfig;
+use rtcp::payload_feedbacks::picture_loss_indication::PictureLossIndication;
+use rtcp::payload_feedbacks::receiver_estimated_maximum_bitrate::ReceiverEstimatedMaximumBitrate;
+use rtcp::transport_feedbacks::temporary_maximum_media_bitrate::{TmmbnPacket, TmmbrPacket};
+use rtcp::transport_feedbacks::transport_layer_cc::{
+    PacketStatusChunk, RunLengthChunk, StatusChunkTypeTcc, SymbolTypeTcc, TransportLayerCc,
+};
+use rtcp::transport_feedbacks::transport_layer_nack::TransportLayerNack;
+use shared::marshal::Marshal;
+use shared::{Protocol, Transmit};
+use srtp::context::Context;
+use srtp::protection_profile::ProtectionProfile;
+
+/*TODO:use std::sync::atomic::AtomicU32;
 use std::sync::Arc;
 
 use bytes::Bytes;
@@ -421,3 +452,1512 @@ async fn peer() -> Result<()> {
 
     Ok(())
 }
+*/
+
+#[test]
+fn test_peer_connection_ice_restart_event_on_remote_ufrag_change() -> Result<()> {
+    let api = APIBuilder::new().build();
+    let mut pc_offer = api.new_peer_connection(RTCConfiguration::default())?;
+    let mut pc_answer = api.new_peer_connection(RTCConfiguration::default())?;
+
+    pc_offer.create_data_channel("data", None)?;
+
+    let offer = pc_offer.create_offer(None)?;
+    pc_offer.set_local_description(offer.clone())?;
+    pc_answer.set_remote_description(offer.clone())?;
+    let answer = pc_answer.create_answer(None)?;
+    pc_answer.set_local_description(answer.clone())?;
+    pc_offer.set_remote_description(answer)?;
+
+    pc_answer.events.clear();
+
+    // Re-offer with different ICE credentials
+    let re_offer = pc_offer.create_offer(Some(RTCOfferOptions {
+        ice_restart: true,
+        ..Default::default()
+    }))?;
+    let new_ufrag = re_offer
+        .unmarshal()?
+        .media_descriptions
+        .iter()
+        .find_map(|m| m.attribute("ice-ufrag").flatten().map(|u| u.to_owned()))
+        .expect("re-offer should carry an ice-ufrag");
+    pc_answer.set_remote_description(re_offer)?;
+
+    let restart_index = pc_answer.events.iter().position(|event| {
+        matches!(event, PeerConnectionEvent::OnIceRestart { new_ufrag: u } if *u == new_ufrag)
+    });
+    let checking_index = pc_answer.events.iter().position(|event| {
+        matches!(
+            event,
+            PeerConnectionEvent::OnIceConnectionStateChange(RTCIceConnectionState::Checking)
+        )
+    });
+    assert!(
+        restart_index.is_some(),
+        "IceRestart event should be emitted"
+    );
+    assert!(checking_index.is_some(), "Checking state should be emitted");
+    assert!(restart_index < checking_index);
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_ice_connection_state_change_events() -> Result<()> {
+    let api = APIBuilder::new().build();
+    let mut pc = api.new_peer_connection(RTCConfiguration::default())?;
+    assert_eq!(RTCIceConnectionState::New, pc.ice_connection_state());
+
+    // ICE agent walks through checking to connected; repeated states are not re-emitted
+    pc.do_ice_connection_state_change(RTCIceTransportState::Checking);
+    pc.do_ice_connection_state_change(RTCIceTransportState::Connected);
+    pc.do_ice_connection_state_change(RTCIceTransportState::Connected);
+
+    let mut states = vec![];
+    while let Some(event) = pc.poll_event() {
+        if let PeerConnectionEvent::OnIceConnectionStateChange(state) = event {
+            states.push(state);
+        }
+    }
+
+    assert_eq!(
+        vec![
+            RTCIceConnectionState::Checking,
+            RTCIceConnectionState::Connected
+        ],
+        states
+    );
+    assert_eq!(RTCIceTransportState::Connected, pc.ice_transport.state());
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_ice_transport_close() -> Result<()> {
+    let api = APIBuilder::new().build();
+    let mut pc = api.new_peer_connection(RTCConfiguration::default())?;
+    while pc.poll_event().is_some() {}
+
+    pc.ice_transport.close()?;
+
+    let mut states = vec![];
+    while let Some(event) = pc.poll_event() {
+        if let PeerConnectionEvent::OnIceConnectionStateChange(state) = event {
+            states.push(state);
+        }
+    }
+    assert_eq!(vec![RTCIceConnectionState::Closed], states);
+    assert_eq!(RTCIceTransportState::Closed, pc.ice_transport.state());
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_transceivers() -> Result<()> {
+    let mut m = MediaEngine::default();
+    m.register_default_codecs()?;
+    let api = APIBuilder::new().with_media_engine(m).build();
+    let mut pc_offer = api.new_peer_connection(RTCConfiguration::default())?;
+    let mut pc_answer = api.new_peer_connection(RTCConfiguration::default())?;
+
+    pc_offer.add_transceiver_from_kind(
+        RTPCodecType::Audio,
+        Some(RTCRtpTransceiverInit {
+            direction: RTCRtpTransceiverDirection::Sendrecv,
+            send_encodings: vec![],
+        }),
+    )?;
+    pc_offer.add_transceiver_from_kind(
+        RTPCodecType::Video,
+        Some(RTCRtpTransceiverInit {
+            direction: RTCRtpTransceiverDirection::Recvonly,
+            send_encodings: vec![],
+        }),
+    )?;
+
+    let offer = pc_offer.create_offer(None)?;
+    pc_offer.set_local_description(offer.clone())?;
+    pc_answer.set_remote_description(offer)?;
+    let answer = pc_answer.create_answer(None)?;
+    pc_answer.set_local_description(answer)?;
+
+    let transceivers = pc_answer.transceivers();
+    assert_eq!(2, transceivers.len());
+    assert_eq!(Some(&"0".to_owned()), transceivers[0].mid());
+    assert_eq!(
+        RTCRtpTransceiverDirection::Recvonly,
+        transceivers[0].direction()
+    );
+    assert_eq!(Some(&"1".to_owned()), transceivers[1].mid());
+    assert_eq!(
+        RTCRtpTransceiverDirection::Sendonly,
+        transceivers[1].direction()
+    );
+
+    let video = pc_answer
+        .transceiver_by_mid("1")
+        .expect("transceiver for mid 1");
+    assert_eq!(RTPCodecType::Video, video.kind());
+    assert!(pc_answer.transceiver_by_mid("2").is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_remote_tias_bandwidth() -> Result<()> {
+    let (mut pc_offer, mut pc_answer) =
+        new_media_pair(default_media_engine()?, &[(MIME_TYPE_VP8, "video")])?;
+
+    let offer = pc_offer.create_offer(None)?;
+    pc_offer.set_local_description(offer.clone())?;
+    pc_answer.set_remote_description(offer)?;
+    let mut answer = pc_answer.create_answer(None)?;
+    pc_answer.set_local_description(answer.clone())?;
+
+    // the remote is willing to receive at most 500 kbps on the video section
+    let video = answer
+        .sdp
+        .find("m=video")
+        .expect("answer has a video section");
+    let c_line = video
+        + answer.sdp[video..]
+            .find("c=")
+            .expect("video section has c=");
+    let c_line_end = c_line + answer.sdp[c_line..].find("\r\n").unwrap() + 2;
+    answer.sdp.insert_str(c_line_end, "b=TIAS:500000\r\n");
+    pc_offer.set_remote_description(answer)?;
+
+    let sender = pc_offer.transceivers()[0].sender();
+    assert_eq!(sender.max_bitrate(), Some(500_000));
+    // the encoding parameters only carry the application's own cap
+    assert_eq!(sender.encoding_parameters()[0].max_bitrate_bps, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_answer_keeps_offer_extension_ids() -> Result<()> {
+    let new_pc = |extensions: &[&str]| -> Result<RTCPeerConnection> {
+        let mut m = MediaEngine::default();
+        m.register_default_codecs()?;
+        for extension in extensions {
+            m.register_header_extension(
+                RTCRtpHeaderExtensionCapability {
+                    uri: extension.to_string(),
+                },
+                RTPCodecType::Audio,
+                None,
+            )?;
+        }
+        APIBuilder::new()
+            .with_media_engine(m)
+            .build()
+            .new_peer_connection(RTCConfiguration::default())
+    };
+
+    let mut pc_offer = new_pc(&[
+        ::sdp::extmap::ABS_SEND_TIME_URI,
+        ::sdp::extmap::SDES_MID_URI,
+        ::sdp::extmap::AUDIO_LEVEL_URI,
+    ])?;
+    let mut pc_answer = new_pc(&[::sdp::extmap::AUDIO_LEVEL_URI, ::sdp::extmap::SDES_MID_URI])?;
+
+    pc_offer.add_transceiver_from_kind(RTPCodecType::Audio, None)?;
+    let offer = pc_offer.create_offer(None)?;
+    assert!(offer.sdp.contains(&format!(
+        "a=extmap:3 {}\r\n",
+        ::sdp::extmap::AUDIO_LEVEL_URI
+    )));
+    pc_offer.set_local_description(offer.clone())?;
+    pc_answer.set_remote_description(offer)?;
+    let answer = pc_answer.create_answer(None)?;
+
+    assert!(answer
+        .sdp
+        .contains(&format!("a=extmap:2 {}\r\n", ::sdp::extmap::SDES_MID_URI)));
+    assert!(answer.sdp.contains(&format!(
+        "a=extmap:3 {}\r\n",
+        ::sdp::extmap::AUDIO_LEVEL_URI
+    )));
+    assert!(!answer.sdp.contains(::sdp::extmap::ABS_SEND_TIME_URI));
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_data_channel_accessors() -> Result<()> {
+    let api = APIBuilder::new().build();
+    let mut pc = api.new_peer_connection(RTCConfiguration::default())?;
+
+    assert_eq!(0, pc.data_channel_count());
+    assert!(pc.data_channel_ids().is_empty());
+
+    pc.create_data_channel("data", None)?;
+    assert_eq!(1, pc.data_channel_count());
+    let ids = pc.data_channel_ids();
+    assert_eq!(1, ids.len());
+    assert!(pc.has_data_channel(ids[0]));
+    assert!(!pc.has_data_channel(ids[0] + 1));
+
+    pc.close_data_channel(ids[0])?;
+    assert_eq!(0, pc.data_channel_count());
+    assert!(!pc.has_data_channel(ids[0]));
+
+    let mut closed = false;
+    while let Some(event) = pc.events.pop_front() {
+        if let PeerConnectionEvent::OnDataChannelClosed(id) = event {
+            assert_eq!(ids[0], id);
+            closed = true;
+        }
+    }
+    assert!(closed, "expected OnDataChannelClosed event");
+
+    assert_eq!(
+        Err(Error::ErrDataChannelNotExisted),
+        pc.close_data_channel(ids[0])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_data_channel_protocol() -> Result<()> {
+    let api = APIBuilder::new().build();
+    let mut pc = api.new_peer_connection(RTCConfiguration::default())?;
+
+    pc.create_data_channel(
+        "data",
+        Some(RTCDataChannelInit {
+            protocol: Some("my-proto".to_owned()),
+            ..Default::default()
+        }),
+    )?;
+    assert_eq!("my-proto", pc.data_channel("data").unwrap().protocol());
+    assert!(pc.data_channel("missing").is_none());
+
+    assert_eq!(
+        Err(Error::ErrProtocolInvalid),
+        pc.create_data_channel(
+            "nul",
+            Some(RTCDataChannelInit {
+                protocol: Some("my\0proto".to_owned()),
+                ..Default::default()
+            }),
+        )
+    );
+    assert_eq!(
+        Err(Error::ErrProtocolTooLarge),
+        pc.create_data_channel(
+            "large",
+            Some(RTCDataChannelInit {
+                protocol: Some("a".repeat(DATA_CHANNEL_PROTOCOL_MAX_LENGTH + 1)),
+                ..Default::default()
+            }),
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_restart_ice_in_band_requires_connected() -> Result<()> {
+    let api = APIBuilder::new().build();
+    let mut pc = api.new_peer_connection(RTCConfiguration::default())?;
+
+    assert_eq!(
+        Err(Error::ErrIncorrectSignalingState),
+        pc.restart_ice_in_band(None, None)
+    );
+
+    pc.is_closed = true;
+    assert_eq!(
+        Err(Error::ErrConnectionClosed),
+        pc.restart_ice_in_band(None, None)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_set_remote_description_with_fallback() -> Result<()> {
+    let api = APIBuilder::new().build();
+    let mut pc_offer = api.new_peer_connection(RTCConfiguration::default())?;
+    pc_offer.create_data_channel("data", None)?;
+    let offer = pc_offer.create_offer(None)?;
+
+    // x= is not a known SDP type
+    let malformed = RTCSessionDescription {
+        sdp_type: RTCSdpType::Offer,
+        sdp: offer.sdp.replacen("t=", "x=unknown\r\nt=", 1),
+        ..Default::default()
+    };
+
+    let mut pc_strict = api.new_peer_connection(RTCConfiguration::default())?;
+    assert!(pc_strict.set_remote_description(malformed.clone()).is_err());
+
+    let mut pc_fallback = api.new_peer_connection(RTCConfiguration::default())?;
+    pc_fallback.set_remote_description_with_fallback(malformed.clone())?;
+    assert!(pc_fallback.remote_description().is_some());
+
+    let mut setting_engine = SettingEngine::default();
+    setting_engine.set_sdp_parse_mode(SdpParseMode::Lenient);
+    let api = APIBuilder::new()
+        .with_setting_engine(Arc::new(setting_engine))
+        .build();
+    let mut pc_lenient = api.new_peer_connection(RTCConfiguration::default())?;
+    pc_lenient.set_remote_description(malformed)?;
+
+    Ok(())
+}
+
+fn forward_transmits(from: &mut ice::Agent, to: &mut ice::Agent) {
+    while let Some(transmit) = from.poll_transmit() {
+        let _ = to.handle_read(Transmit {
+            now: transmit.now,
+            transport: TransportContext {
+                local_addr: transmit.transport.peer_addr,
+                peer_addr: transmit.transport.local_addr,
+                ..transmit.transport
+            },
+            message: transmit.message,
+        });
+    }
+}
+
+fn new_host_candidate(address: &str, port: u16) -> Result<ice::candidate::Candidate> {
+    CandidateHostConfig {
+        base_config: CandidateConfig {
+            network: "udp".to_owned(),
+            address: address.to_owned(),
+            port,
+            component: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+    .new_candidate_host()
+}
+
+/// connect_ice drives the ICE agent of pc to Connected against a remote agent on
+/// 192.168.0.2:5001 by exchanging their STUN messages in memory.
+fn connect_ice(pc: &mut RTCPeerConnection) -> Result<ice::Agent> {
+    let agent = &mut pc.ice_transport.gatherer.agent;
+    let mut remote = ice::Agent::new(Arc::new(AgentConfig::default()))?;
+
+    agent.add_local_candidate(new_host_candidate("192.168.0.1", 5000)?)?;
+    remote.add_remote_candidate(new_host_candidate("192.168.0.1", 5000)?)?;
+    remote.add_local_candidate(new_host_candidate("192.168.0.2", 5001)?)?;
+    agent.add_remote_candidate(new_host_candidate("192.168.0.2", 5001)?)?;
+
+    let local_credentials = agent.get_local_credentials().clone();
+    let remote_credentials = remote.get_local_credentials().clone();
+    agent.start_connectivity_checks(true, remote_credentials.ufrag, remote_credentials.pwd)?;
+    remote.start_connectivity_checks(false, local_credentials.ufrag, local_credentials.pwd)?;
+
+    for _ in 0..10 {
+        if agent.is_connected() && remote.is_connected() {
+            break;
+        }
+        forward_transmits(agent, &mut remote);
+        forward_transmits(&mut remote, agent);
+        agent.handle_timeout(Instant::now());
+        remote.handle_timeout(Instant::now());
+    }
+    assert!(agent.is_connected());
+
+    Ok(remote)
+}
+
+#[test]
+fn test_peer_connection_send_rtp() -> Result<()> {
+    let mut m = MediaEngine::default();
+    m.register_default_codecs()?;
+    let api = APIBuilder::new().with_media_engine(m).build();
+    let mut pc = api.new_peer_connection(RTCConfiguration::default())?;
+
+    let track = Box::new(TrackLocalStaticRTP::new(
+        RTCRtpCodecCapability {
+            mime_type: MIME_TYPE_VP8.to_owned(),
+            ..Default::default()
+        },
+        "video".to_owned(),
+        "webrtc-rs".to_owned(),
+    ));
+    pc.add_transceiver_from_track(track, None)?;
+    let ssrc = pc.transceivers()[0].sender().ssrc;
+
+    let packet = rtp::packet::Packet {
+        header: rtp::header::Header {
+            version: 2,
+            payload_type: 96,
+            sequence_number: 1,
+            ssrc,
+            ..Default::default()
+        },
+        payload: Bytes::from_static(&[0xde, 0xad, 0xbe, 0xef]),
+    };
+    let now = Instant::now();
+
+    assert_eq!(
+        pc.send_rtp(now, "audio", packet.clone()),
+        Err(Error::ErrUnknownTrack)
+    );
+    let mut wrong_ssrc = packet.clone();
+    wrong_ssrc.header.ssrc = ssrc.wrapping_add(1);
+    assert_eq!(
+        pc.send_rtp(now, "video", wrong_ssrc),
+        Err(Error::ErrSsrcNotFound)
+    );
+    assert_eq!(
+        pc.send_rtp(now, "video", packet.clone()),
+        Err(Error::ErrICEConnectionNotStarted)
+    );
+
+    let _remote = connect_ice(&mut pc)?;
+    assert_eq!(
+        pc.send_rtp(now, "video", packet.clone()),
+        Err(Error::ErrDtlsTransportNotStarted)
+    );
+    assert!(pc.poll_write().is_none());
+
+    let (key, salt) = ([0x01; 16], [0x02; 14]);
+    let profile = ProtectionProfile::Aes128CmHmacSha1_80;
+    pc.dtls_transport
+        .set_local_srtp_context(Context::new(&key, &salt, profile, None, None)?);
+    let mut remote_context = Context::new(&key, &salt, profile, None, None)?;
+
+    pc.send_rtp(now, "video", packet.clone())?;
+    let transmit = pc.poll_write().expect("packet should be queued");
+    assert_eq!(transmit.now, now);
+    assert_eq!(
+        transmit.transport.local_addr,
+        "192.168.0.1:5000".parse().unwrap()
+    );
+    assert_eq!(
+        transmit.transport.peer_addr,
+        "192.168.0.2:5001".parse().unwrap()
+    );
+    assert_ne!(&transmit.message[..], &packet.marshal()?[..]);
+    assert_eq!(
+        &remote_context.decrypt_rtp(&transmit.message)?[..],
+        &packet.marshal()?[..]
+    );
+    assert!(pc.poll_write().is_none());
+
+    // rejected packets are reported instead of being dropped silently
+    pc.rtp_transceivers[0].sender_mut().active = false;
+    assert_eq!(
+        pc.send_rtp(now, "video", packet.clone()),
+        Err(Error::ErrRTPSenderNotActive)
+    );
+    pc.rtp_transceivers[0].sender_mut().active = true;
+    pc.rtp_transceivers[0].sender_mut().set_max_bitrate(1);
+    assert_eq!(
+        pc.send_rtp(now, "video", packet.clone()),
+        Err(Error::ErrRTPSenderMaxBitrateExceeded)
+    );
+    assert_eq!(pc.transceivers()[0].sender().packets_lost(), 1);
+    assert!(pc.poll_write().is_none());
+
+    pc.is_closed = true;
+    assert_eq!(
+        pc.send_rtp(now, "video", packet),
+        Err(Error::ErrConnectionClosed)
+    );
+
+    Ok(())
+}
+
+/// forward_writes passes the datagrams written by from to the handle_read of to, as if
+/// they went through the network.
+fn forward_writes(from: &mut RTCPeerConnection, to: &mut RTCPeerConnection) -> Result<()> {
+    while let Some(transmit) = from.poll_write() {
+        to.handle_read(Transmit {
+            now: transmit.now,
+            transport: TransportContext {
+                local_addr: transmit.transport.peer_addr,
+                peer_addr: transmit.transport.local_addr,
+                ..transmit.transport
+            },
+            message: transmit.message,
+        })?;
+    }
+    Ok(())
+}
+
+/// connect_pipeline connects the ICE agents of pc_a and pc_b with host candidates
+/// through handle_timeout, poll_write and handle_read only, and keys SRTP from pc_a
+/// to pc_b. It returns the time the connection completed at.
+fn connect_pipeline(pc_a: &mut RTCPeerConnection, pc_b: &mut RTCPeerConnection) -> Result<Instant> {
+    let agent_a = &mut pc_a.ice_transport.gatherer.agent;
+    let agent_b = &mut pc_b.ice_transport.gatherer.agent;
+    agent_a.add_local_candidate(new_host_candidate("192.168.0.1", 5000)?)?;
+    agent_b.add_remote_candidate(new_host_candidate("192.168.0.1", 5000)?)?;
+    agent_b.add_local_candidate(new_host_candidate("192.168.0.2", 5001)?)?;
+    agent_a.add_remote_candidate(new_host_candidate("192.168.0.2", 5001)?)?;
+    let credentials_a = agent_a.get_local_credentials().clone();
+    let credentials_b = agent_b.get_local_credentials().clone();
+    agent_a.start_connectivity_checks(true, credentials_b.ufrag, credentials_b.pwd)?;
+    agent_b.start_connectivity_checks(false, credentials_a.ufrag, credentials_a.pwd)?;
+
+    // the connectivity checks only go through handle_timeout, poll_write and handle_read
+    let mut now = Instant::now();
+    for _ in 0..20 {
+        if pc_a.ice_connection_state() == RTCIceConnectionState::Connected
+            && pc_b.ice_connection_state() == RTCIceConnectionState::Connected
+        {
+            break;
+        }
+        let timeout = pc_a
+            .poll_timeout()
+            .expect("connectivity checks are scheduled");
+        now = now.max(timeout);
+        pc_a.handle_timeout(now);
+        pc_b.handle_timeout(now);
+        forward_writes(pc_a, pc_b)?;
+        forward_writes(pc_b, pc_a)?;
+        while pc_a.poll_event().is_some() {}
+        while pc_b.poll_event().is_some() {}
+    }
+    assert_eq!(
+        RTCIceConnectionState::Connected,
+        pc_a.ice_connection_state()
+    );
+    assert_eq!(
+        RTCIceConnectionState::Connected,
+        pc_b.ice_connection_state()
+    );
+
+    let (key, salt) = ([0x01; 16], [0x02; 14]);
+    let profile = ProtectionProfile::Aes128CmHmacSha1_80;
+    pc_a.dtls_transport
+        .set_local_srtp_context(Context::new(&key, &salt, profile, None, None)?);
+    pc_b.dtls_transport
+        .set_remote_srtp_context(Context::new(&key, &salt, profile, None, None)?);
+
+    Ok(now)
+}
+
+/// default_media_engine returns a MediaEngine with the default codecs registered.
+fn default_media_engine() -> Result<MediaEngine> {
+    let mut m = MediaEngine::default();
+    m.register_default_codecs()?;
+    Ok(m)
+}
+
+/// new_media_pair returns two PeerConnections built from m, pc_a sending a
+/// TrackLocalStaticRTP for each (mime type, track id) of tracks.
+fn new_media_pair(
+    m: MediaEngine,
+    tracks: &[(&str, &str)],
+) -> Result<(RTCPeerConnection, RTCPeerConnection)> {
+    let api = APIBuilder::new().with_media_engine(m).build();
+    let mut pc_a = api.new_peer_connection(RTCConfiguration::default())?;
+    let pc_b = api.new_peer_connection(RTCConfiguration::default())?;
+
+    for (mime_type, id) in tracks {
+        pc_a.add_transceiver_from_track(
+            Box::new(TrackLocalStaticRTP::new(
+                RTCRtpCodecCapability {
+                    mime_type: mime_type.to_string(),
+                    ..Default::default()
+                },
+                id.to_string(),
+                "webrtc-rs".to_owned(),
+            )),
+            None,
+        )?;
+    }
+
+    Ok((pc_a, pc_b))
+}
+
+/// reverse_srtp_context keys SRTP from pc_b to pc_a, the reverse of connect_pipeline,
+/// for the RTCP pc_b sends back.
+fn reverse_srtp_context(pc_a: &mut RTCPeerConnection, pc_b: &mut RTCPeerConnection) -> Result<()> {
+    let (key, salt) = ([0x03; 16], [0x04; 14]);
+    let profile = ProtectionProfile::Aes128CmHmacSha1_80;
+    pc_b.dtls_transport
+        .set_local_srtp_context(Context::new(&key, &salt, profile, None, None)?);
+    pc_a.dtls_transport
+        .set_remote_srtp_context(Context::new(&key, &salt, profile, None, None)?);
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_pipeline_end_to_end() -> Result<()> {
+    let (mut pc_a, mut pc_b) =
+        new_media_pair(default_media_engine()?, &[(MIME_TYPE_VP8, "video")])?;
+    assert!(pc_a.poll_timeout().is_none());
+    let ssrc = pc_a.transceivers()[0].sender().ssrc;
+    let now = connect_pipeline(&mut pc_a, &mut pc_b)?;
+
+    let packet = rtp::packet::Packet {
+        header: rtp::header::Header {
+            version: 2,
+            payload_type: 96,
+            sequence_number: 1,
+            ssrc,
+            ..Default::default()
+        },
+        payload: Bytes::from_static(&[0xde, 0xad, 0xbe, 0xef]),
+    };
+    pc_a.send_rtp(now, "video", packet.clone())?;
+    forward_writes(&mut pc_a, &mut pc_b)?;
+
+    let msg = pc_b.poll_read().expect("RTP should be received");
+    assert_eq!(msg.transport.peer_addr, "192.168.0.1:5000".parse().unwrap());
+    match msg.message {
+        RTCMessage::Rtp(RTPMessage::Rtp(received)) => assert_eq!(received, packet),
+        message => panic!("unexpected message {message:?}"),
+    }
+    assert!(pc_b.poll_read().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_reports_round_trip_time() -> Result<()> {
+    let (mut pc_a, mut pc_b) =
+        new_media_pair(default_media_engine()?, &[(MIME_TYPE_VP8, "video")])?;
+    let ssrc = pc_a.transceivers()[0].sender().ssrc;
+    let now = connect_pipeline(&mut pc_a, &mut pc_b)?;
+    reverse_srtp_context(&mut pc_a, &mut pc_b)?;
+
+    let packet = rtp::packet::Packet {
+        header: rtp::header::Header {
+            version: 2,
+            payload_type: 96,
+            sequence_number: 1,
+            ssrc,
+            ..Default::default()
+        },
+        payload: Bytes::from_static(&[0xde, 0xad, 0xbe, 0xef]),
+    };
+    pc_a.send_rtp(now, "video", packet)?;
+    let ntp_time = 0xDA8B_D1FC_DDDD_A05A;
+    pc_a.send_rtcp(
+        now,
+        vec![Box::new(rtcp::sender_report::SenderReport {
+            ssrc,
+            ntp_time,
+            ..Default::default()
+        })],
+    )?;
+    forward_writes(&mut pc_a, &mut pc_b)?;
+
+    // pc_b reports on the Sender Report 100ms later, right after receiving it
+    pc_b.send_rtcp(
+        now + Duration::from_millis(100),
+        vec![Box::new(rtcp::receiver_report::ReceiverReport {
+            ssrc: 0x1234_5678,
+            reports: vec![rtcp::reception_report::ReceptionReport {
+                ssrc,
+                last_sender_report: crate::handler::rtcp::rtt_estimator::compact_ntp(ntp_time),
+                ..Default::default()
+            }],
+            ..Default::default()
+        })],
+    )?;
+    forward_writes(&mut pc_b, &mut pc_a)?;
+
+    let stats = pc_a.get_stats();
+    match stats
+        .reports
+        .get(&format!("RTCOutboundRTPVideoStream_{ssrc}"))
+    {
+        Some(StatsReportType::OutboundRTP(outbound)) => {
+            assert_eq!(outbound.packets_sent, 1);
+            assert_eq!(outbound.bytes_sent, 4);
+            assert_eq!(outbound.track_identifier, "video");
+        }
+        report => panic!("unexpected outbound stats {report:?}"),
+    }
+    match stats
+        .reports
+        .get(&format!("RTCRemoteInboundRTPVideoStream_{ssrc}"))
+    {
+        Some(StatsReportType::RemoteInboundRTP(remote)) => {
+            let rtt = remote.round_trip_time.expect("round trip time");
+            assert!((rtt - 0.1).abs() < 1e-6, "{rtt}");
+            assert!((remote.total_round_trip_time - 0.1).abs() < 1e-6);
+            assert_eq!(remote.round_trip_time_measurements, 1);
+        }
+        report => panic!("unexpected remote inbound stats {report:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_restart_ice_in_band_sends_new_ufrag() -> Result<()> {
+    let api = APIBuilder::new().build();
+    let mut pc_a = api.new_peer_connection(RTCConfiguration::default())?;
+    let mut pc_b = api.new_peer_connection(RTCConfiguration::default())?;
+    let now = connect_pipeline(&mut pc_a, &mut pc_b)?;
+    // the pair is only connected over ICE, skip the DTLS handshake
+    pc_a.peer_connection_state = RTCPeerConnectionState::Connected;
+    let remote_ufrag = pc_b
+        .ice_transport
+        .gatherer
+        .agent
+        .get_local_credentials()
+        .ufrag
+        .clone();
+
+    pc_a.restart_ice_in_band(
+        Some("restartufrag".to_owned()),
+        Some("restartpasswordrestartpassword".to_owned()),
+    )?;
+
+    let mut usernames = vec![];
+    let mut now = now;
+    for _ in 0..5 {
+        now = now.max(
+            pc_a.poll_timeout()
+                .expect("connectivity checks are scheduled"),
+        );
+        pc_a.handle_timeout(now);
+        while let Some(transmit) = pc_a.poll_write() {
+            let mut msg = stun::message::Message::new();
+            msg.raw = transmit.message.to_vec();
+            msg.decode()?;
+            if msg.typ == stun::message::BINDING_REQUEST {
+                let username =
+                    stun::textattrs::Username::get_from_as(&msg, stun::attributes::ATTR_USERNAME)?;
+                usernames.push(username.to_string());
+            }
+        }
+        if !usernames.is_empty() {
+            break;
+        }
+    }
+    assert!(!usernames.is_empty(), "expected binding requests");
+    for username in usernames {
+        assert_eq!(format!("{remote_ufrag}:restartufrag"), username);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_restart_ice_generates_credentials() -> Result<()> {
+    let api = APIBuilder::new().build();
+    let mut pc_a = api.new_peer_connection(RTCConfiguration::default())?;
+    let mut pc_b = api.new_peer_connection(RTCConfiguration::default())?;
+
+    assert_eq!(Err(Error::ErrIncorrectSignalingState), pc_a.restart_ice());
+
+    connect_pipeline(&mut pc_a, &mut pc_b)?;
+    pc_a.peer_connection_state = RTCPeerConnectionState::Connected;
+    let credentials = pc_a
+        .ice_transport
+        .gatherer
+        .agent
+        .get_local_credentials()
+        .clone();
+
+    pc_a.restart_ice()?;
+
+    let restarted = pc_a.ice_transport.gatherer.agent.get_local_credentials();
+    assert!(!restarted.ufrag.is_empty());
+    assert!(!restarted.pwd.is_empty());
+    assert_ne!(credentials.ufrag, restarted.ufrag);
+    assert_ne!(credentials.pwd, restarted.pwd);
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_receiver_report_carries_loss_rle() -> Result<()> {
+    let (mut pc_a, mut pc_b) =
+        new_media_pair(default_media_engine()?, &[(MIME_TYPE_OPUS, "audio")])?;
+    let ssrc = pc_a.transceivers()[0].sender().ssrc;
+    let offer = pc_a.create_offer(None)?;
+    pc_a.set_local_description(offer.clone())?;
+    pc_b.set_remote_description(offer)?;
+
+    let now = connect_pipeline(&mut pc_a, &mut pc_b)?;
+    reverse_srtp_context(&mut pc_a, &mut pc_b)?;
+
+    // the packet with sequence number 3 is lost
+    for sequence_number in [1, 2, 4] {
+        pc_a.send_rtp(
+            now,
+            "audio",
+            rtp::packet::Packet {
+                header: rtp::header::Header {
+                    version: 2,
+                    payload_type: 111,
+                    sequence_number,
+                    ssrc,
+                    ..Default::default()
+                },
+                payload: Bytes::from_static(&[0xde, 0xad, 0xbe, 0xef]),
+            },
+        )?;
+    }
+    forward_writes(&mut pc_a, &mut pc_b)?;
+
+    pc_b.send_rtcp(
+        now,
+        vec![Box::new(rtcp::receiver_report::ReceiverReport {
+            ssrc: 0x1234_5678,
+            ..Default::default()
+        })],
+    )?;
+    forward_writes(&mut pc_b, &mut pc_a)?;
+
+    let msg = pc_a.poll_read().expect("RTCP should be received");
+    let packets = match msg.message {
+        RTCMessage::Rtp(RTPMessage::Rtcp(packets)) => packets,
+        message => panic!("unexpected message {message:?}"),
+    };
+    assert_eq!(2, packets.len());
+    let xr = packets[1]
+        .as_any()
+        .downcast_ref::<ExtendedReport>()
+        .expect("extended report");
+    assert_eq!(1, xr.reports.len());
+    let rle = xr.reports[0]
+        .as_any()
+        .downcast_ref::<rtcp::extended_report::LossRLEReportBlock>()
+        .expect("loss RLE report block");
+    assert_eq!(ssrc, rle.ssrc);
+    assert_eq!(1, rle.begin_seq);
+    assert_eq!(5, rle.end_seq);
+    // received, received, lost, received
+    assert_eq!(0xE800, rle.chunks[0].0 & 0xF800);
+
+    // the next report only covers what was received since
+    pc_b.send_rtcp(
+        now,
+        vec![Box::new(rtcp::receiver_report::ReceiverReport {
+            ssrc: 0x1234_5678,
+            ..Default::default()
+        })],
+    )?;
+    forward_writes(&mut pc_b, &mut pc_a)?;
+    match pc_a.poll_read().map(|msg| msg.message) {
+        Some(RTCMessage::Rtp(RTPMessage::Rtcp(packets))) => assert_eq!(1, packets.len()),
+        message => panic!("unexpected message {message:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_tmmbr_round_trip() -> Result<()> {
+    let mut m = default_media_engine()?;
+    m.register_feedback(
+        RTCPFeedback {
+            typ: TYPE_RTCP_FB_TRANSPORT_CC.to_owned(),
+            parameter: "".to_owned(),
+        },
+        RTPCodecType::Video,
+    );
+    let (mut pc_a, mut pc_b) = new_media_pair(m, &[(MIME_TYPE_VP8, "video")])?;
+    let ssrc = pc_a.transceivers()[0].sender().ssrc;
+    let offer = pc_a.create_offer(None)?;
+    pc_a.set_local_description(offer.clone())?;
+    pc_b.set_remote_description(offer)?;
+
+    let now = connect_pipeline(&mut pc_a, &mut pc_b)?;
+    reverse_srtp_context(&mut pc_a, &mut pc_b)?;
+
+    // 1000 bytes every 10ms, ~800 kbps
+    for sequence_number in 0..=100u16 {
+        pc_a.send_rtp(
+            now + Duration::from_millis(10 * sequence_number as u64),
+            "video",
+            rtp::packet::Packet {
+                header: rtp::header::Header {
+                    version: 2,
+                    payload_type: 96,
+                    sequence_number,
+                    ssrc,
+                    ..Default::default()
+                },
+                payload: Bytes::from(vec![0u8; 988]),
+            },
+        )?;
+        forward_writes(&mut pc_a, &mut pc_b)?;
+    }
+    while pc_b.poll_read().is_some() {}
+    let incoming_bitrate = pc_b.transceivers()[0].receiver().incoming_bitrate();
+    assert!(incoming_bitrate > 700_000, "{incoming_bitrate}");
+
+    // the TWCC estimate stays at the 300 kbps start bitrate, below the incoming bitrate
+    let later = now + Duration::from_secs(1);
+    pc_b.send_rtcp(
+        later,
+        vec![Box::new(TransportLayerCc {
+            sender_ssrc: 0x1234_5678,
+            media_ssrc: ssrc,
+            ..Default::default()
+        })],
+    )?;
+    forward_writes(&mut pc_b, &mut pc_a)?;
+
+    let packets = match pc_a.poll_read().map(|msg| msg.message) {
+        Some(RTCMessage::Rtp(RTPMessage::Rtcp(packets))) => packets,
+        message => panic!("unexpected message {message:?}"),
+    };
+    let tmmbr = packets
+        .iter()
+        .find_map(|p| p.as_any().downcast_ref::<TmmbrPacket>())
+        .expect("TMMBR should follow the TWCC feedback");
+    assert!(!tmmbr.is_notification);
+    assert_eq!(1, tmmbr.entries.len());
+    assert_eq!(ssrc, tmmbr.entries[0].ssrc);
+    let requested = tmmbr.entries[0].max_total_media_bitrate;
+    assert!(requested < incoming_bitrate, "{requested}");
+    assert_eq!(
+        pc_a.transceivers()[0].sender().tmmbr_bitrate(),
+        Some(requested)
+    );
+
+    // pc_a acknowledges the request with a TMMBN owned by pc_b
+    forward_writes(&mut pc_a, &mut pc_b)?;
+    let packets = match pc_b.poll_read().map(|msg| msg.message) {
+        Some(RTCMessage::Rtp(RTPMessage::Rtcp(packets))) => packets,
+        message => panic!("unexpected message {message:?}"),
+    };
+    let tmmbn = packets
+        .iter()
+        .find_map(|p| p.as_any().downcast_ref::<TmmbnPacket>())
+        .expect("TMMBN should answer the TMMBR");
+    assert!(tmmbn.is_notification);
+    assert_eq!(ssrc, tmmbn.sender_ssrc);
+    assert_eq!(tmmbr.sender_ssrc, tmmbn.entries[0].ssrc);
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_bitrate_estimate_from_feedback() -> Result<()> {
+    let mut m = default_media_engine()?;
+    m.register_feedback(
+        RTCPFeedback {
+            typ: TYPE_RTCP_FB_TRANSPORT_CC.to_owned(),
+            parameter: "".to_owned(),
+        },
+        RTPCodecType::Video,
+    );
+    let (mut pc_a, mut pc_b) = new_media_pair(m, &[(MIME_TYPE_VP8, "video")])?;
+    let ssrc = pc_a.transceivers()[0].sender().ssrc;
+    let offer = pc_a.create_offer(None)?;
+    pc_a.set_local_description(offer.clone())?;
+    pc_b.set_remote_description(offer)?;
+    let answer = pc_b.create_answer(None)?;
+    pc_b.set_local_description(answer.clone())?;
+    pc_a.set_remote_description(answer)?;
+
+    let now = connect_pipeline(&mut pc_a, &mut pc_b)?;
+    reverse_srtp_context(&mut pc_a, &mut pc_b)?;
+
+    pc_a.send_rtp(
+        now,
+        "video",
+        rtp::packet::Packet {
+            header: rtp::header::Header {
+                version: 2,
+                payload_type: 96,
+                ssrc,
+                ..Default::default()
+            },
+            payload: Bytes::from(vec![0u8; 988]),
+        },
+    )?;
+    forward_writes(&mut pc_a, &mut pc_b)?;
+    while pc_b.poll_read().is_some() {}
+    while pc_a.poll_event().is_some() {}
+
+    // VP8 negotiated transport-cc, REMB is ignored
+    pc_b.send_rtcp(
+        now,
+        vec![Box::new(ReceiverEstimatedMaximumBitrate {
+            sender_ssrc: 0x1234_5678,
+            bitrate: 1_000_000.0,
+            ssrcs: vec![ssrc],
+        })],
+    )?;
+    forward_writes(&mut pc_b, &mut pc_a)?;
+    while pc_a.poll_read().is_some() {}
+    assert_eq!(
+        300_000,
+        pc_a.transceivers()[0].sender().current_target_bitrate()
+    );
+
+    // TWCC feedback of another SSRC doesn't change the estimate of this sender
+    let twcc = TransportLayerCc {
+        sender_ssrc: 0x1234_5678,
+        media_ssrc: ssrc,
+        packet_status_count: 10,
+        packet_chunks: vec![PacketStatusChunk::RunLengthChunk(RunLengthChunk {
+            type_tcc: StatusChunkTypeTcc::RunLengthChunk,
+            packet_status_symbol: SymbolTypeTcc::PacketNotReceived,
+            run_length: 10,
+        })],
+        ..Default::default()
+    };
+    let other = TransportLayerCc {
+        media_ssrc: ssrc.wrapping_add(1),
+        ..twcc.clone()
+    };
+    pc_a.handle_bandwidth_feedback(&[Box::new(other)]);
+    assert_eq!(
+        300_000,
+        pc_a.transceivers()[0].sender().current_target_bitrate()
+    );
+
+    // every packet was lost, the estimate drops and is reported
+    pc_b.send_rtcp(now, vec![Box::new(twcc)])?;
+    forward_writes(&mut pc_b, &mut pc_a)?;
+    while pc_a.poll_read().is_some() {}
+    let mut updates = vec![];
+    while let Some(event) = pc_a.poll_event() {
+        if let PeerConnectionEvent::BitrateEstimateUpdate(ssrc, bps) = event {
+            updates.push((ssrc, bps));
+        }
+    }
+    assert_eq!(vec![(ssrc, 150_000)], updates);
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_negotiates_reduced_size_rtcp() -> Result<()> {
+    let (mut pc_a, mut pc_b) =
+        new_media_pair(default_media_engine()?, &[(MIME_TYPE_VP8, "video")])?;
+    let ssrc = pc_a.transceivers()[0].sender().ssrc;
+    let offer = pc_a.create_offer(None)?;
+    assert!(offer.sdp.contains("a=rtcp-rsize\r\n"));
+    pc_a.set_local_description(offer.clone())?;
+    pc_b.set_remote_description(offer)?;
+    assert!(!pc_b.rtcp_handler.reduced_size());
+    let answer = pc_b.create_answer(None)?;
+    pc_b.set_local_description(answer.clone())?;
+    assert!(pc_b.rtcp_handler.reduced_size());
+
+    // an answer without a=rtcp-rsize keeps the full compound packets
+    let mut full_size = answer.clone();
+    full_size.sdp = full_size.sdp.replace("a=rtcp-rsize\r\n", "");
+    full_size.parsed = None;
+    pc_a.set_remote_description(full_size)?;
+    assert!(!pc_a.rtcp_handler.reduced_size());
+
+    let now = connect_pipeline(&mut pc_a, &mut pc_b)?;
+    reverse_srtp_context(&mut pc_a, &mut pc_b)?;
+
+    pc_a.send_rtp(
+        now,
+        "video",
+        rtp::packet::Packet {
+            header: rtp::header::Header {
+                version: 2,
+                payload_type: 96,
+                ssrc,
+                ..Default::default()
+            },
+            payload: Bytes::from(vec![0u8; 100]),
+        },
+    )?;
+    forward_writes(&mut pc_a, &mut pc_b)?;
+    while pc_b.poll_read().is_some() {}
+
+    // the NACK of pc_b is sent alone, without a receiver report
+    pc_b.send_rtcp(
+        now,
+        vec![Box::new(TransportLayerNack {
+            sender_ssrc: 0x1234_5678,
+            media_ssrc: ssrc,
+            ..Default::default()
+        })],
+    )?;
+    forward_writes(&mut pc_b, &mut pc_a)?;
+    let packets = match pc_a.poll_read().map(|msg| msg.message) {
+        Some(RTCMessage::Rtp(RTPMessage::Rtcp(packets))) => packets,
+        message => panic!("unexpected message {message:?}"),
+    };
+    assert_eq!(1, packets.len());
+    assert!(packets[0]
+        .as_any()
+        .downcast_ref::<TransportLayerNack>()
+        .is_some());
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_sends_only_negotiated_feedback() -> Result<()> {
+    let mut m = default_media_engine()?;
+    // another audio codec negotiating nack doesn't enable it for the Opus stream
+    m.register_codec(
+        RTCRtpCodecParameters {
+            capability: RTCRtpCodecCapability {
+                mime_type: "audio/ISAC".to_owned(),
+                clock_rate: 16000,
+                rtcp_feedback: vec![RTCPFeedback {
+                    typ: "nack".to_owned(),
+                    parameter: "".to_owned(),
+                }],
+                ..Default::default()
+            },
+            payload_type: 103,
+            ..Default::default()
+        },
+        RTPCodecType::Audio,
+    )?;
+    let (mut pc_a, mut pc_b) =
+        new_media_pair(m, &[(MIME_TYPE_VP8, "video"), (MIME_TYPE_OPUS, "audio")])?;
+    let video_ssrc = pc_a.transceivers()[0].sender().ssrc;
+    let audio_ssrc = pc_a.transceivers()[1].sender().ssrc;
+    let offer = pc_a.create_offer(None)?;
+    pc_a.set_local_description(offer.clone())?;
+    pc_b.set_remote_description(offer)?;
+
+    let now = connect_pipeline(&mut pc_a, &mut pc_b)?;
+    reverse_srtp_context(&mut pc_a, &mut pc_b)?;
+
+    // the feedback is checked against the codec of the payload type received
+    for (track_id, ssrc, payload_type) in [("video", video_ssrc, 96), ("audio", audio_ssrc, 111)] {
+        pc_a.send_rtp(
+            now,
+            track_id,
+            rtp::packet::Packet {
+                header: rtp::header::Header {
+                    version: 2,
+                    payload_type,
+                    ssrc,
+                    ..Default::default()
+                },
+                payload: Bytes::from(vec![0u8; 100]),
+            },
+        )?;
+    }
+    forward_writes(&mut pc_a, &mut pc_b)?;
+    while pc_b.poll_read().is_some() {}
+
+    // VP8 negotiated nack and nack pli but not transport-cc, Opus negotiated no feedback
+    let mut packets: Vec<Box<dyn rtcp::packet::Packet>> = vec![];
+    for media_ssrc in [video_ssrc, audio_ssrc] {
+        packets.push(Box::new(TransportLayerNack {
+            sender_ssrc: 0x1234_5678,
+            media_ssrc,
+            ..Default::default()
+        }));
+        packets.push(Box::new(PictureLossIndication {
+            sender_ssrc: 0x1234_5678,
+            media_ssrc,
+        }));
+        packets.push(Box::new(TransportLayerCc {
+            sender_ssrc: 0x1234_5678,
+            media_ssrc,
+            ..Default::default()
+        }));
+    }
+    pc_b.send_rtcp(now, packets)?;
+    forward_writes(&mut pc_b, &mut pc_a)?;
+
+    let packets = match pc_a.poll_read().map(|msg| msg.message) {
+        Some(RTCMessage::Rtp(RTPMessage::Rtcp(packets))) => packets,
+        message => panic!("unexpected message {message:?}"),
+    };
+    // the compound packet starts with a receiver report
+    assert_eq!(3, packets.len());
+    let nack = packets[1]
+        .as_any()
+        .downcast_ref::<TransportLayerNack>()
+        .expect("NACK should be sent");
+    assert_eq!(video_ssrc, nack.media_ssrc);
+    let pli = packets[2]
+        .as_any()
+        .downcast_ref::<PictureLossIndication>()
+        .expect("PLI should be sent");
+    assert_eq!(video_ssrc, pli.media_ssrc);
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_lip_sync_estimate() -> Result<()> {
+    let (mut pc_a, mut pc_b) = new_media_pair(
+        default_media_engine()?,
+        &[(MIME_TYPE_OPUS, "audio"), (MIME_TYPE_VP8, "video")],
+    )?;
+    let audio_ssrc = pc_a.transceivers()[0].sender().ssrc;
+    let video_ssrc = pc_a.transceivers()[1].sender().ssrc;
+    let offer = pc_a.create_offer(None)?;
+    pc_a.set_local_description(offer.clone())?;
+    pc_b.set_remote_description(offer)?;
+
+    // the estimators are registered with the negotiated clock rates
+    assert_eq!(
+        Some(48000),
+        pc_b.lip_sync_estimator(audio_ssrc).map(|e| e.clock_rate)
+    );
+    assert_eq!(
+        Some(90000),
+        pc_b.lip_sync_estimator(video_ssrc).map(|e| e.clock_rate)
+    );
+
+    let now = connect_pipeline(&mut pc_a, &mut pc_b)?;
+    let ntp_time = 0xDA8B_D1FC_0000_0000;
+    let (audio_sr_ts, video_sr_ts) = (1_000, 50_000);
+    // captured 100ms and 60ms after the Sender Reports
+    let (audio_ts, video_ts) = (audio_sr_ts + 4_800, video_sr_ts + 5_400);
+    for (track_id, ssrc, payload_type, timestamp) in [
+        ("audio", audio_ssrc, 111, audio_ts),
+        ("video", video_ssrc, 96, video_ts),
+    ] {
+        pc_a.send_rtp(
+            now,
+            track_id,
+            rtp::packet::Packet {
+                header: rtp::header::Header {
+                    version: 2,
+                    payload_type,
+                    sequence_number: 1,
+                    timestamp,
+                    ssrc,
+                    ..Default::default()
+                },
+                payload: Bytes::from_static(&[0xde, 0xad, 0xbe, 0xef]),
+            },
+        )?;
+    }
+    for (ssrc, rtp_time) in [(audio_ssrc, audio_sr_ts), (video_ssrc, video_sr_ts)] {
+        pc_a.send_rtcp(
+            now,
+            vec![Box::new(rtcp::sender_report::SenderReport {
+                ssrc,
+                ntp_time,
+                rtp_time,
+                ..Default::default()
+            })],
+        )?;
+        forward_writes(&mut pc_a, &mut pc_b)?;
+    }
+
+    let mut estimates = vec![];
+    while let Some(event) = pc_b.poll_event() {
+        if let PeerConnectionEvent::LipSyncEstimate {
+            audio_ts,
+            video_ts,
+            offset_ms,
+        } = event
+        {
+            estimates.push((audio_ts, video_ts, offset_ms));
+        }
+    }
+    assert_eq!(vec![(audio_ts, video_ts, 40)], estimates);
+
+    let audio = pc_b.stats_accumulator().inbound_stream(audio_ssrc).copied();
+    let video = pc_b.stats_accumulator().inbound_stream(video_ssrc).copied();
+    assert_eq!(
+        Some(Duration::from_millis(40)),
+        audio.and_then(|s| s.estimated_playout_delay)
+    );
+    assert_eq!(
+        Some(Duration::ZERO),
+        video.and_then(|s| s.estimated_playout_delay)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_periodic_stats() -> Result<()> {
+    let api = APIBuilder::new().build();
+    let mut pc = api.new_peer_connection(RTCConfiguration::default())?;
+    let start = Instant::now();
+    assert!(pc.poll_timeout().is_none());
+
+    pc.enable_periodic_stats(start, Duration::from_millis(100));
+    assert!(pc.poll_timeout().is_some());
+    pc.handle_timeout(start + Duration::from_millis(150));
+    pc.handle_timeout(start + Duration::from_millis(250));
+    // the next snapshot is due 100ms after the last one
+    pc.handle_timeout(start + Duration::from_millis(300));
+
+    let mut stats_events = 0;
+    while let Some(event) = pc.poll_event() {
+        if let PeerConnectionEvent::OnStats(report) = event {
+            assert!(report.reports.contains_key(pc.get_stats_id()));
+            stats_events += 1;
+        }
+    }
+    assert_eq!(stats_events, 2);
+
+    pc.disable_periodic_stats();
+    assert!(pc.poll_timeout().is_none());
+    pc.handle_timeout(start + Duration::from_secs(10));
+    assert!(pc.poll_event().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_source_removed() -> Result<()> {
+    let api = APIBuilder::new().build();
+    let mut pc = api.new_peer_connection(RTCConfiguration::default())?;
+
+    let (key, salt) = ([0x01; 16], [0x02; 14]);
+    let profile = ProtectionProfile::Aes128CmHmacSha1_80;
+    pc.dtls_transport
+        .set_remote_srtp_context(Context::new(&key, &salt, profile, None, None)?);
+    let mut remote_context = Context::new(&key, &salt, profile, None, None)?;
+
+    let ssrc = 0x902F_9E2E;
+    let transport = TransportContext {
+        local_addr: "192.168.0.1:5000".parse().unwrap(),
+        peer_addr: "192.168.0.2:5001".parse().unwrap(),
+        protocol: Protocol::UDP,
+        ecn: None,
+    };
+    let now = Instant::now();
+    let mut send_rtp = |pc: &mut RTCPeerConnection, sequence_number: u16| -> Result<()> {
+        let packet = rtp::packet::Packet {
+            header: rtp::header::Header {
+                version: 2,
+                payload_type: 96,
+                sequence_number,
+                ssrc,
+                ..Default::default()
+            },
+            payload: Bytes::from_static(&[0xde, 0xad, 0xbe, 0xef]),
+        };
+        let message = remote_context.encrypt_rtp(&packet.marshal()?)?;
+        pc.handle_read(Transmit {
+            now,
+            transport,
+            message,
+        })
+    };
+
+    send_rtp(&mut pc, 1)?;
+    send_rtp(&mut pc, 2)?;
+    let received = pc.stats_accumulator().inbound_stream(ssrc).copied();
+    assert_eq!(received.map(|s| s.packets_received), Some(2));
+    assert_eq!(received.map(|s| s.bytes_received), Some(8));
+    while let Some(msg) = pc.poll_read() {
+        assert!(matches!(msg.message, RTCMessage::Rtp(RTPMessage::Rtp(_))));
+    }
+
+    let bye = rtcp::goodbye::Goodbye {
+        sources: vec![ssrc],
+        reason: Bytes::from_static(b"FOO"),
+    };
+    let mut bye_context = Context::new(&key, &salt, profile, None, None)?;
+    let message = bye_context.encrypt_rtcp(&bye.marshal()?)?;
+    pc.handle_read(Transmit {
+        now,
+        transport,
+        message,
+    })?;
+    assert!(matches!(
+        pc.poll_read().map(|msg| msg.message),
+        Some(RTCMessage::Rtp(RTPMessage::Rtcp(_)))
+    ));
+
+    let mut removed = vec![];
+    while let Some(event) = pc.poll_event() {
+        if let PeerConnectionEvent::SourceRemoved(ssrc) = event {
+            removed.push(ssrc);
+        }
+    }
+    assert_eq!(removed, vec![ssrc]);
+    assert!(pc.stats_accumulator().inbound_stream(ssrc).is_none());
+
+    // RTP received from the same SSRC afterwards starts a new stream
+    send_rtp(&mut pc, 3)?;
+    let received = pc.stats_accumulator().inbound_stream(ssrc).copied();
+    assert_eq!(received.map(|s| s.packets_received), Some(1));
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_sctp_state_change_events() -> Result<()> {
+    let api = APIBuilder::new().build();
+    let mut pc = api.new_peer_connection(RTCConfiguration::default())?;
+    pc.create_data_channel("data", None)?;
+
+    let offer = pc.create_offer(None)?;
+    pc.set_local_description(offer)?;
+
+    // ICE and DTLS are up, but the SCTP association is still being negotiated
+    let remote_caps = pc.sctp_transport.get_capabilities();
+    pc.sctp_transport.start(remote_caps)?;
+    pc.do_ice_connection_state_change(RTCIceTransportState::Connected);
+    pc.dtls_transport.state = RTCDtlsTransportState::Connected;
+
+    let mut sctp_states = vec![];
+    let mut poll_events = |pc: &mut RTCPeerConnection| {
+        while let Some(event) = pc.poll_event() {
+            if let PeerConnectionEvent::SctpStateChange(state) = event {
+                sctp_states.push(state);
+            }
+        }
+    };
+    poll_events(&mut pc);
+    assert_eq!(RTCPeerConnectionState::Connecting, pc.connection_state());
+
+    // INIT handshake completed
+    pc.sctp_transport
+        .state_change(RTCSctpTransportState::Connected);
+    poll_events(&mut pc);
+    assert_eq!(RTCPeerConnectionState::Connected, pc.connection_state());
+
+    pc.sctp_transport.stop()?;
+    poll_events(&mut pc);
+
+    assert_eq!(
+        vec![
+            RTCSctpTransportState::Connecting,
+            RTCSctpTransportState::Connected,
+            RTCSctpTransportState::Closed,
+        ],
+        sctp_states
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_peer_connection_state_accessors_follow_agent() -> Result<()> {
+    let api = APIBuilder::new().build();
+    let mut pc = api.new_peer_connection(RTCConfiguration::default())?;
+
+    pc.ice_transport.gatherer.agent.start_connectivity_checks(
+        true,
+        "remote_ufrag".to_owned(),
+        "remote_pwd_0123456789abcdef".to_owned(),
+    )?;
+    pc.dtls_transport.state = RTCDtlsTransportState::Connecting;
+
+    // the accessors reflect the agent before its events are processed
+    assert_eq!(RTCIceConnectionState::Checking, pc.ice_connection_state());
+    assert_eq!(
+        RTCPeerConnectionState::Connecting,
+        pc.peer_connection_state()
+    );
+    assert_eq!(RTCPeerConnectionState::New, pc.connection_state());
+    assert_eq!(RTCSignalingState::Stable, pc.signaling_state());
+
+    // any poll_* call queues the state change events, not only poll_event
+    assert!(pc.poll_write().is_none());
+    assert_eq!(RTCPeerConnectionState::Connecting, pc.connection_state());
+
+    let mut ice_states = vec![];
+    while let Some(event) = pc.poll_event() {
+        if let PeerConnectionEvent::OnIceConnectionStateChange(state) = event {
+            ice_states.push(state);
+        }
+    }
+    assert_eq!(vec![RTCIceConnectionState::Checking], ice_states);
+
+    Ok(())
+}
@@ -1,7 +1,7 @@
-/*TODO:
 #[cfg(test)]
 mod sdp_test;
 
+/*TODO:
 use crate::api::media_engine::MediaEngine;
 use crate::transports::dtls_transport::dtls_fingerprint::RTCDtlsFingerprint;
 use crate::transports::ice_transport::ice_candidate::RTCIceCandidate;
@@ -13,14 +13,14 @@ use crate::rtp_transceiver::rtp_codec::{
     RTCRtpCodecParameters, //, RTPCodecType,
 };
 use crate::rtp_transceiver::rtp_transceiver_direction::RTCRtpTransceiverDirection;
-use crate::rtp_transceiver::{Mid, PayloadType, RTCPFeedback, RTCRtpTransceiver};
+use crate::rtp_transceiver::{Mid, PayloadType, RTCPFeedback, RTCRtpTransceiver, SSRC};
 
 use shared::error::{Error, Result};
 
 pub mod sdp_type;
 pub mod session_description;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 //use std::convert::From;
 use ice::candidate::{unmarshal_candidate, Candidate};
 use sdp::description::common::{Address, ConnectionInformation};
@@ -35,11 +35,13 @@ use crate::constants::{SDP_ATTRIBUTE_RID, SDP_ATTRIBUTE_SIMULCAST};
 use sdp::description::media::{MediaDescription, MediaName, RangedPort};
 use sdp::description::session::*;
 use sdp::extmap::ExtMap;
+use sdp::msid::MsidAttribute;
 use sdp::util::ConnectionRole;
 /*use sdp::util::ConnectionRole;
 use smol_str::SmolStr;*/
 use url::Url;
 
+use crate::peer_connection::offer_answer_options::OfferedCodec;
 use crate::peer_connection::MEDIA_SECTION_APPLICATION;
 use crate::transport::dtls_transport::dtls_fingerprint::RTCDtlsFingerprint;
 use crate::transport::ice_transport::ice_candidate::RTCIceCandidate;
@@ -436,6 +438,61 @@ pub(crate) struct AddTransceiverSdpParams {
     dtls_role: ConnectionRole,
     ice_gathering_state: RTCIceGatheringState,
     offered_direction: Option<RTCRtpTransceiverDirection>,
+    payload_type_overrides: HashMap<(String, String), PayloadType>,
+    extension_ids: HashMap<String, u8>,
+}
+
+/// payload_type_overrides maps the lowercase mime type and fmtp line of each offered codec
+/// to its preferred payload type, and fails if two codecs are pinned to the same payload type.
+pub(crate) fn payload_type_overrides(
+    offered_codecs: &[OfferedCodec],
+) -> Result<HashMap<(String, String), PayloadType>> {
+    let mut overrides = HashMap::new();
+    let mut used = HashSet::new();
+    for offered_codec in offered_codecs {
+        if !used.insert(offered_codec.preferred_payload_type) {
+            return Err(Error::ErrPayloadTypeConflict);
+        }
+        overrides.insert(
+            (
+                offered_codec.mime_type.to_lowercase(),
+                offered_codec.sdp_fmtp_line.clone(),
+            ),
+            offered_codec.preferred_payload_type,
+        );
+    }
+    Ok(overrides)
+}
+
+/// check_answer_payload_types fails if one of the offered codecs is pinned to another
+/// payload type than the one the remote offer assigned to it, since an answer has to
+/// reuse the payload types of the offer (RFC 3264 section 6.1).
+pub(crate) fn check_answer_payload_types(
+    remote: &SessionDescription,
+    offered_codecs: &[OfferedCodec],
+) -> Result<()> {
+    let overrides = payload_type_overrides(offered_codecs)?;
+    if overrides.is_empty() {
+        return Ok(());
+    }
+
+    for media in &remote.media_descriptions {
+        // e.g. the application section has no codecs
+        let Ok(codecs) = codecs_from_media_description(media) else {
+            continue;
+        };
+        for codec in codecs {
+            let preferred_payload_type = overrides.get(&(
+                codec.capability.mime_type.to_lowercase(),
+                codec.capability.sdp_fmtp_line,
+            ));
+            if preferred_payload_type.is_some_and(|pt| *pt != codec.payload_type) {
+                return Err(Error::ErrPayloadTypeConflict);
+            }
+        }
+    }
+
+    Ok(())
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -469,7 +526,20 @@ pub(crate) fn add_transceiver_sdp(
             .with_property_attribute(ATTR_KEY_RTCPRSIZE.to_owned());
 
     let codecs = transceiver.get_codecs(media_engine);
+    let mut payload_types = HashSet::new();
     for codec in &codecs {
+        let payload_type = params
+            .payload_type_overrides
+            .get(&(
+                codec.capability.mime_type.to_lowercase(),
+                codec.capability.sdp_fmtp_line.clone(),
+            ))
+            .copied()
+            .unwrap_or(codec.payload_type);
+        if !payload_types.insert(payload_type) {
+            return Err(Error::ErrPayloadTypeConflict);
+        }
+
         let name = codec
             .capability
             .mime_type
@@ -477,7 +547,7 @@ pub(crate) fn add_transceiver_sdp(
             .trim_start_matches("video/")
             .to_owned();
         media = media.with_codec(
-            codec.payload_type,
+            payload_type,
             name,
             codec.capability.clock_rate,
             codec.capability.channels,
@@ -487,10 +557,7 @@ pub(crate) fn add_transceiver_sdp(
         for feedback in &codec.capability.rtcp_feedback {
             media = media.with_value_attribute(
                 "rtcp-fb".to_owned(),
-                format!(
-                    "{} {} {}",
-                    codec.payload_type, feedback.typ, feedback.parameter
-                ),
+                format!("{} {} {}", payload_type, feedback.typ, feedback.parameter),
             );
         }
     }
@@ -541,8 +608,12 @@ pub(crate) fn add_transceiver_sdp(
         media_engine.get_rtp_parameters_by_kind(transceiver.kind, transceiver.direction());
     for rtp_extension in &parameters.header_extensions {
         let ext_url = Url::parse(rtp_extension.uri.as_str())?;
+        let id = params
+            .extension_ids
+            .get(&rtp_extension.uri)
+            .map_or(rtp_extension.id, |&id| id as isize);
         media = media.with_extmap(sdp::extmap::ExtMap {
-            value: rtp_extension.id,
+            value: id,
             uri: Some(ext_url),
             ..Default::default()
         });
@@ -602,8 +673,10 @@ pub(crate) fn add_transceiver_sdp(
             // is handled below.
             if sender.initial_track_id().is_none() {
                 for stream_id in sender.associated_media_stream_ids() {
-                    media =
-                        media.with_property_attribute(format!("msid:{} {}", stream_id, track.id()));
+                    media = media.with_msid(MsidAttribute {
+                        stream_id: stream_id.to_owned(),
+                        track_id: Some(track.id().to_owned()),
+                    });
                 }
 
                 sender.set_initial_track_id(track.id().to_string())?;
@@ -623,7 +696,10 @@ pub(crate) fn add_transceiver_sdp(
             // description, "a=msid" line(s) MUST be generated according to the
             // same rules as for an initial offer.
             for stream_id in sender.associated_media_stream_ids() {
-                media = media.with_property_attribute(format!("msid:{stream_id} {track_id}"));
+                media = media.with_msid(MsidAttribute {
+                    stream_id: stream_id.to_owned(),
+                    track_id: Some(track_id.to_owned()),
+                });
             }
         }
     }
@@ -666,7 +742,7 @@ pub(crate) fn add_transceiver_sdp(
             transceiver.direction()
         }
     };
-    media = media.with_property_attribute(direction.to_string());
+    media = media.with_property_attribute(direction.to_sdp_attribute().to_owned());
 
     for fingerprint in dtls_fingerprints {
         media = media.with_fingerprint(
@@ -755,6 +831,8 @@ pub(crate) struct PopulateSdpParams {
     pub(crate) is_icelite: bool,
     pub(crate) connection_role: ConnectionRole,
     pub(crate) ice_gathering_state: RTCIceGatheringState,
+    pub(crate) payload_type_overrides: HashMap<(String, String), PayloadType>,
+    pub(crate) extension_ids: HashMap<String, u8>,
 }
 
 /// populate_sdp serializes a PeerConnections state into an SDP
@@ -807,6 +885,8 @@ pub(crate) fn populate_sdp(
                 dtls_role: params.connection_role,
                 ice_gathering_state: params.ice_gathering_state,
                 offered_direction: m.offered_direction,
+                payload_type_overrides: params.payload_type_overrides.clone(),
+                extension_ids: params.extension_ids.clone(),
             };
             let (d1, should_add_id) = add_transceiver_sdp(
                 d,
@@ -854,13 +934,29 @@ pub(crate) fn get_mid_value(media: &MediaDescription) -> Option<&String> {
 }
 
 pub(crate) fn get_peer_direction(media: &MediaDescription) -> RTCRtpTransceiverDirection {
-    for a in &media.attributes {
-        let direction = RTCRtpTransceiverDirection::from(a.key.as_str());
-        if direction != RTCRtpTransceiverDirection::Unspecified {
-            return direction;
-        }
-    }
-    RTCRtpTransceiverDirection::Unspecified
+    media
+        .attributes
+        .iter()
+        .find_map(|a| RTCRtpTransceiverDirection::from_sdp_attribute(a.key.as_str()))
+        .unwrap_or(RTCRtpTransceiverDirection::Unspecified)
+}
+
+/// get_tias_bitrate returns the b=TIAS bandwidth of a media section in bits per second.
+pub(crate) fn get_tias_bitrate(media: &MediaDescription) -> Option<u64> {
+    media
+        .bandwidth
+        .iter()
+        .find(|b| !b.experimental && b.bandwidth_type == "TIAS")
+        .map(|b| b.bandwidth)
+}
+
+/// get_ssrc returns the first a=ssrc announced by a media section.
+pub(crate) fn get_ssrc(media: &MediaDescription) -> Option<SSRC> {
+    media
+        .attributes
+        .iter()
+        .filter(|a| a.key == ATTR_KEY_SSRC)
+        .find_map(|a| a.value.as_ref()?.split(' ').next()?.parse::<SSRC>().ok())
 }
 
 pub(crate) fn extract_fingerprint(desc: &SessionDescription) -> Result<(String, String)> {
@@ -958,6 +1054,17 @@ pub(crate) fn have_application_media_section(desc: &SessionDescription) -> bool
     false
 }
 
+/// have_rtcp_rsize returns true if every RTP media section of desc carries a=rtcp-rsize,
+/// i.e. the peer accepts reduced-size RTCP (RFC 5506).
+pub(crate) fn have_rtcp_rsize(desc: &SessionDescription) -> bool {
+    let mut rtp_sections = desc
+        .media_descriptions
+        .iter()
+        .filter(|m| m.media_name.media != MEDIA_SECTION_APPLICATION)
+        .peekable();
+    rtp_sections.peek().is_some() && rtp_sections.all(|m| m.attribute(ATTR_KEY_RTCPRSIZE).is_some())
+}
+
 pub(crate) fn get_by_mid<'a>(
     search_mid: &str,
     desc: &'a session_description::RTCSessionDescription,
@@ -995,6 +1102,8 @@ pub(crate) fn codecs_from_media_description(
         ..Default::default()
     };
 
+    let rtcp_feedback = m.rtcp_feedback();
+
     let mut out = vec![];
     for payload_str in &m.media_name.formats {
         let payload_type: PayloadType = payload_str.parse::<u8>()?;
@@ -1010,24 +1119,14 @@ pub(crate) fn codecs_from_media_description(
 
         let channels = codec.encoding_parameters.parse::<u16>().unwrap_or(0);
 
-        let mut feedback = vec![];
-        for raw in &codec.rtcp_feedback {
-            let split: Vec<&str> = raw.split(' ').collect();
-
-            let entry = if split.len() == 2 {
-                RTCPFeedback {
-                    typ: split[0].to_string(),
-                    parameter: split[1].to_string(),
-                }
-            } else {
-                RTCPFeedback {
-                    typ: split[0].to_string(),
-                    parameter: String::new(),
-                }
-            };
-
-            feedback.push(entry);
-        }
+        let feedback = rtcp_feedback
+            .iter()
+            .filter(|fb| fb.pt == payload_type)
+            .map(|fb| RTCPFeedback {
+                typ: fb.mechanism.clone(),
+                parameter: fb.param.clone().unwrap_or_default(),
+            })
+            .collect();
 
         out.push(RTCRtpCodecParameters {
             capability: RTCRtpCodecCapability {
@@ -1045,10 +1144,12 @@ pub(crate) fn codecs_from_media_description(
     Ok(out)
 }
 
+/// rtp_extensions_from_media_description returns the a=extmap URIs and IDs of m,
+/// in SDP order
 pub(crate) fn rtp_extensions_from_media_description(
     m: &MediaDescription,
-) -> Result<HashMap<String, isize>> {
-    let mut out = HashMap::new();
+) -> Result<Vec<(String, isize)>> {
+    let mut out = vec![];
 
     for a in &m.attributes {
         if a.key == ATTR_KEY_EXT_MAP {
@@ -1057,7 +1158,7 @@ pub(crate) fn rtp_extensions_from_media_description(
             let e = ExtMap::unmarshal(&mut reader)?;
 
             if let Some(uri) = e.uri {
-                out.insert(uri.to_string(), e.value);
+                out.push((uri.to_string(), e.value));
             }
         }
     }
@@ -1,6 +1,6 @@
 use std::io::Cursor;
 
-use sdp::description::session::SessionDescription;
+use sdp::description::session::{SdpParseMode, SessionDescription};
 use serde::{Deserialize, Serialize};
 
 use super::sdp_type::RTCSdpType;
@@ -68,8 +68,14 @@ impl RTCSessionDescription {
 
     /// Unmarshal is a helper to deserialize the sdp
     pub fn unmarshal(&self) -> Result<SessionDescription> {
+        self.unmarshal_with_mode(SdpParseMode::Strict)
+    }
+
+    /// unmarshal_with_mode deserializes the sdp, skipping malformed optional
+    /// lines in SdpParseMode::Lenient
+    pub fn unmarshal_with_mode(&self, mode: SdpParseMode) -> Result<SessionDescription> {
         let mut reader = Cursor::new(self.sdp.as_bytes());
-        let parsed = SessionDescription::unmarshal(&mut reader)?;
+        let parsed = SessionDescription::unmarshal_with_mode(&mut reader, mode)?;
         Ok(parsed)
     }
 }
@@ -105,10 +105,39 @@ pub struct RTCRtpCodingParameters {
 /// <http://draft.ortc.org/#dom-rtcrtpdecodingparameters>
 pub type RTCRtpDecodingParameters = RTCRtpCodingParameters;
 
-/// RTPEncodingParameters provides information relating to both encoding and decoding.
+/// RTPEncodingParameters provides information relating to encoding.
 /// This is a subset of the RFC since Pion WebRTC doesn't implement encoding itself
 /// <http://draft.ortc.org/#dom-rtcrtpencodingparameters>
-pub type RTCRtpEncodingParameters = RTCRtpCodingParameters;
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RTCRtpEncodingParameters {
+    pub rid: String,
+    pub ssrc: SSRC,
+    pub payload_type: PayloadType,
+    pub rtx: RTCRtpRtxParameters,
+    /// max_bitrate_bps caps the bitrate of the encoding, in bits per second.
+    pub max_bitrate_bps: Option<u64>,
+    /// scale_resolution_down_by is the factor the video resolution is scaled down by.
+    pub scale_resolution_down_by: Option<f64>,
+    /// max_framerate caps the frame rate of the encoding, in frames per second.
+    pub max_framerate: Option<f64>,
+    /// active indicates whether the encoding is currently being sent.
+    pub active: bool,
+}
+
+impl Default for RTCRtpEncodingParameters {
+    fn default() -> Self {
+        Self {
+            rid: String::new(),
+            ssrc: 0,
+            payload_type: 0,
+            rtx: RTCRtpRtxParameters::default(),
+            max_bitrate_bps: None,
+            scale_resolution_down_by: None,
+            max_framerate: None,
+            active: true,
+        }
+    }
+}
 
 /// RTPReceiveParameters contains the RTP stack settings used by receivers
 #[derive(Debug)]
@@ -242,6 +271,28 @@ impl RTCRtpTransceiver {
             Ok(())
         }
     */
+    pub(crate) fn new(
+        receiver: RTCRtpReceiver,
+        mut sender: RTCRtpSender,
+        direction: RTCRtpTransceiverDirection,
+        kind: RTPCodecType,
+        codecs: Vec<RTCRtpCodecParameters>,
+    ) -> Self {
+        sender.set_paused(!direction.has_send());
+
+        RTCRtpTransceiver {
+            mid: None,
+            sender,
+            receiver,
+            direction,
+            current_direction: RTCRtpTransceiverDirection::Unspecified,
+            codecs,
+            stopped: false,
+            kind,
+            trigger_negotiation_needed: false,
+        }
+    }
+
     /// Codecs returns list of supported codecs
     pub(crate) fn get_codecs(&mut self, media_engine: &MediaEngine) -> Vec<RTCRtpCodecParameters> {
         RTCRtpReceiver::get_codecs(&mut self.codecs, self.kind, media_engine)
@@ -257,6 +308,31 @@ impl RTCRtpTransceiver {
         &mut self.sender
     }
 
+    /// receiver returns the RTPTransceiver's RTPReceiver
+    pub fn receiver(&self) -> &RTCRtpReceiver {
+        &self.receiver
+    }
+
+    pub(crate) fn receiver_mut(&mut self) -> &mut RTCRtpReceiver {
+        &mut self.receiver
+    }
+
+    /// sender_ssrc returns the SSRC used by the sender, or None if it has no track to send.
+    pub fn sender_ssrc(&self) -> Option<SSRC> {
+        self.sender.track().map(|_| self.sender.ssrc)
+    }
+
+    /// stream_ids returns the MediaStream ids the sender is associated with,
+    /// which are announced in the a=msid lines of the generated SDP.
+    pub fn stream_ids(&self) -> &[String] {
+        self.sender.associated_media_stream_ids()
+    }
+
+    /// receiver_ssrc returns the SSRC the remote peer announced for this transceiver, if any.
+    pub fn receiver_ssrc(&self) -> Option<SSRC> {
+        self.receiver.ssrc()
+    }
+
     pub fn trigger_negotiation_needed(&self) -> bool {
         self.trigger_negotiation_needed
     }
@@ -61,6 +61,14 @@ pub struct RTCRtpCodecCapability {
 }
 
 impl RTCRtpCodecCapability {
+    /// supports_rtcp_feedback returns true if the codec negotiated the given RTCP feedback,
+    /// e.g. ("nack", "") for generic NACK or ("nack", "pli") for Picture Loss Indication.
+    pub fn supports_rtcp_feedback(&self, typ: &str, parameter: &str) -> bool {
+        self.rtcp_feedback
+            .iter()
+            .any(|fb| fb.typ == typ && fb.parameter == parameter)
+    }
+
     /// Turn codec capability into a `packetizer::Payloader`
     pub fn payloader_for_codec(&self) -> Result<Box<dyn rtp::packetizer::Payloader>> {
         let mime_type = self.mime_type.to_lowercase();
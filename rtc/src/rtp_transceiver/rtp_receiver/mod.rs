@@ -28,7 +28,17 @@ use crate::api::media_engine::MediaEngine;
 use crate::rtp_transceiver::rtp_codec::{
     codec_parameters_fuzzy_search, CodecMatch, RTCRtpCodecParameters, RTPCodecType,
 };
+use crate::rtp_transceiver::rtp_sender::bandwidth_estimation::{
+    BandwidthEstimation, GoogCongestionControl,
+};
+use crate::rtp_transceiver::{PayloadType, SSRC};
+use rtcp::transport_feedbacks::temporary_maximum_media_bitrate::TmmbEntry;
+use rtcp::transport_feedbacks::transport_layer_cc::TransportLayerCc;
 use shared::error::Result;
+use std::time::{Duration, Instant};
+
+/// Interval the incoming bitrate of a receiver is measured over
+const INCOMING_BITRATE_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u8)]
@@ -297,6 +307,20 @@ pub struct RTCRtpReceiver {
 
     // State is stored within the channel
     state: State,
+    /// SSRC announced by the remote description for this receiver
+    ssrc: Option<SSRC>,
+    /// Stream ids announced by the remote description's a=msid lines
+    stream_ids: Vec<String>,
+    /// payload type of the last RTP received
+    payload_type: Option<PayloadType>,
+    /// estimates the bitrate the remote sender can use from the TWCC feedback about it
+    congestion_control: GoogCongestionControl,
+    /// start of the current incoming bitrate interval and the bytes received in it
+    incoming_interval: Option<(Instant, u64)>,
+    /// bitrate RTP was received at during the last complete interval
+    incoming_bitrate: u64,
+    /// bitrate last requested from the remote sender with TMMBR
+    requested_bitrate: Option<u64>,
     /*state_rx: watch::Receiver<State>,
 
     tracks: RwLock<Vec<TrackStreams>>,
@@ -718,6 +742,21 @@ impl RTCRtpReceiver {
         }
 
     */
+    pub(crate) fn new(receive_mtu: usize, kind: RTPCodecType) -> Self {
+        RTCRtpReceiver {
+            receive_mtu,
+            kind,
+            state: State::Unstarted,
+            ssrc: None,
+            stream_ids: vec![],
+            payload_type: None,
+            congestion_control: GoogCongestionControl::new(BandwidthEstimation::default()),
+            incoming_interval: None,
+            incoming_bitrate: 0,
+            requested_bitrate: None,
+        }
+    }
+
     // State
 
     pub(crate) fn current_state(&self) -> State {
@@ -787,4 +826,65 @@ impl RTCRtpReceiver {
     pub(crate) fn close(&mut self) {
         self.state = State::Stopped
     }
+
+    /// ssrc returns the SSRC the remote peer announced for this receiver, if any.
+    pub fn ssrc(&self) -> Option<SSRC> {
+        self.ssrc
+    }
+
+    pub(crate) fn set_ssrc(&mut self, ssrc: Option<SSRC>) {
+        self.ssrc = ssrc;
+    }
+
+    /// stream_ids returns the MediaStream ids the remote peer associated with this receiver.
+    pub fn stream_ids(&self) -> &[String] {
+        &self.stream_ids
+    }
+
+    pub(crate) fn set_stream_ids(&mut self, stream_ids: Vec<String>) {
+        self.stream_ids = stream_ids;
+    }
+
+    /// payload_type returns the payload type of the last RTP received, if any.
+    pub fn payload_type(&self) -> Option<PayloadType> {
+        self.payload_type
+    }
+
+    /// handle_rtp accounts an RTP packet of size bytes with payload_type received at
+    /// now for the incoming bitrate.
+    pub(crate) fn handle_rtp(&mut self, now: Instant, payload_type: PayloadType, size: usize) {
+        self.payload_type = Some(payload_type);
+        let (start, bytes) = self.incoming_interval.get_or_insert((now, 0));
+        *bytes += size as u64;
+
+        let elapsed = now.saturating_duration_since(*start);
+        if elapsed >= INCOMING_BITRATE_INTERVAL {
+            self.incoming_bitrate = (*bytes as f64 * 8.0 / elapsed.as_secs_f64()) as u64;
+            self.incoming_interval = Some((now, 0));
+        }
+    }
+
+    /// incoming_bitrate returns the bits per second RTP was received at during the
+    /// last complete measurement interval.
+    pub fn incoming_bitrate(&self) -> u64 {
+        self.incoming_bitrate
+    }
+
+    /// handle_twcc updates the estimate of the bitrate the remote sender can use with
+    /// the TWCC feedback sent about it. When the estimate drops below the incoming
+    /// bitrate, it returns the TMMBR entry asking the sender to slow down to it.
+    pub fn handle_twcc(&mut self, twcc: &TransportLayerCc) -> Option<TmmbEntry> {
+        let ssrc = self.ssrc?;
+        let estimate = self.congestion_control.update(twcc);
+        if estimate >= self.incoming_bitrate || self.requested_bitrate == Some(estimate) {
+            return None;
+        }
+
+        self.requested_bitrate = Some(estimate);
+        Some(TmmbEntry {
+            ssrc,
+            max_total_media_bitrate: estimate,
+            measured_overhead: 0,
+        })
+    }
 }
@@ -0,0 +1,90 @@
+use super::*;
+use rtcp::transport_feedbacks::transport_layer_cc::{
+    RunLengthChunk, StatusChunkTypeTcc, StatusVectorChunk, SymbolSizeTypeTcc,
+};
+
+fn twcc_run_length(symbol: SymbolTypeTcc, count: u16) -> TransportLayerCc {
+    TransportLayerCc {
+        sender_ssrc: 1,
+        media_ssrc: 2,
+        packet_status_count: count,
+        packet_chunks: vec![PacketStatusChunk::RunLengthChunk(RunLengthChunk {
+            type_tcc: StatusChunkTypeTcc::RunLengthChunk,
+            packet_status_symbol: symbol,
+            run_length: count,
+        })],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_goog_congestion_control_full_loss() {
+    let mut gcc = GoogCongestionControl::default();
+    let twcc = twcc_run_length(SymbolTypeTcc::PacketNotReceived, 20);
+
+    // halved on every feedback until it reaches the floor
+    let mut previous = gcc.estimation().current_estimate_bps;
+    for _ in 0..10 {
+        let bps = gcc.update(&twcc);
+        assert!(
+            bps < previous || bps == DEFAULT_MIN_BITRATE,
+            "estimate should drop, {bps} >= {previous}"
+        );
+        previous = bps;
+    }
+    assert_eq!(gcc.estimation().current_estimate_bps, DEFAULT_MIN_BITRATE);
+}
+
+#[test]
+fn test_goog_congestion_control_no_loss() {
+    let mut gcc = GoogCongestionControl::new(BandwidthEstimation {
+        current_estimate_bps: 100_000,
+        min_bps: 50_000,
+        max_bps: 110_000,
+    });
+    let twcc = twcc_run_length(SymbolTypeTcc::PacketReceivedSmallDelta, 20);
+
+    assert_eq!(gcc.update(&twcc), 105_000);
+    assert_eq!(gcc.update(&twcc), 110_000);
+    assert_eq!(gcc.update(&twcc), 110_000);
+
+    // feedback without any packet status leaves the estimate unchanged
+    assert_eq!(gcc.update(&TransportLayerCc::default()), 110_000);
+}
+
+#[test]
+fn test_goog_congestion_control_loss_fraction() {
+    // 7 packets reported by a vector padded to 14 symbols, 3 of them lost
+    let mut symbol_list = vec![SymbolTypeTcc::PacketReceivedSmallDelta; 14];
+    for i in [0, 3, 5] {
+        symbol_list[i] = SymbolTypeTcc::PacketNotReceived;
+    }
+    let twcc = TransportLayerCc {
+        packet_status_count: 7,
+        packet_chunks: vec![PacketStatusChunk::StatusVectorChunk(StatusVectorChunk {
+            type_tcc: StatusChunkTypeTcc::StatusVectorChunk,
+            symbol_size: SymbolSizeTypeTcc::OneBit,
+            symbol_list,
+        })],
+        ..Default::default()
+    };
+    assert_eq!(loss_fraction(&twcc), Some(3.0 / 7.0));
+    assert_eq!(loss_fraction(&TransportLayerCc::default()), None);
+}
+
+#[test]
+fn test_goog_congestion_control_remb() {
+    let mut gcc = GoogCongestionControl::default();
+    let remb = ReceiverEstimatedMaximumBitrate {
+        sender_ssrc: 1,
+        bitrate: 1_000_000.0,
+        ssrcs: vec![2],
+    };
+    assert_eq!(gcc.update_remb(&remb), 1_000_000);
+
+    let remb = ReceiverEstimatedMaximumBitrate {
+        bitrate: 10.0,
+        ..remb
+    };
+    assert_eq!(gcc.update_remb(&remb), DEFAULT_MIN_BITRATE);
+}
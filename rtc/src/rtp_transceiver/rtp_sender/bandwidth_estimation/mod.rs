@@ -0,0 +1,129 @@
+#[cfg(test)]
+mod bandwidth_estimation_test;
+
+use rtcp::payload_feedbacks::receiver_estimated_maximum_bitrate::ReceiverEstimatedMaximumBitrate;
+use rtcp::transport_feedbacks::transport_layer_cc::{
+    PacketStatusChunk, SymbolTypeTcc, TransportLayerCc,
+};
+
+/// Start bitrate of the estimate, before any feedback was received
+pub const DEFAULT_START_BITRATE: u64 = 300_000;
+/// Lowest bitrate the estimate drops to
+pub const DEFAULT_MIN_BITRATE: u64 = 30_000;
+/// Highest bitrate the estimate grows to
+pub const DEFAULT_MAX_BITRATE: u64 = 2_500_000;
+
+// loss fractions below which the estimate grows, and above which it drops,
+// from the loss-based controller of draft-ietf-rmcat-gcc-02 Section 6
+const LOW_LOSS_FRACTION: f64 = 0.02;
+const HIGH_LOSS_FRACTION: f64 = 0.10;
+const INCREASE_FACTOR: f64 = 1.05;
+
+/// BandwidthEstimation is the send bitrate estimated from the congestion
+/// feedback of the remote peer, bounded by min_bps and max_bps.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BandwidthEstimation {
+    pub current_estimate_bps: u64,
+    pub min_bps: u64,
+    pub max_bps: u64,
+}
+
+impl Default for BandwidthEstimation {
+    fn default() -> Self {
+        Self {
+            current_estimate_bps: DEFAULT_START_BITRATE,
+            min_bps: DEFAULT_MIN_BITRATE,
+            max_bps: DEFAULT_MAX_BITRATE,
+        }
+    }
+}
+
+impl BandwidthEstimation {
+    fn clamp(&self, bps: u64) -> u64 {
+        bps.clamp(self.min_bps, self.max_bps.max(self.min_bps))
+    }
+}
+
+/// GoogCongestionControl adapts a BandwidthEstimation to the packet loss reported
+/// by TWCC feedback, with the loss-based controller of Google Congestion Control.
+/// REMB feedback, used when TWCC was not negotiated, caps the estimate instead.
+#[derive(Default, Debug, Clone)]
+pub struct GoogCongestionControl {
+    pub(crate) estimation: BandwidthEstimation,
+}
+
+impl GoogCongestionControl {
+    pub fn new(estimation: BandwidthEstimation) -> Self {
+        Self {
+            estimation: BandwidthEstimation {
+                current_estimate_bps: estimation.clamp(estimation.current_estimate_bps),
+                ..estimation
+            },
+        }
+    }
+
+    /// estimation returns the current bandwidth estimation
+    pub fn estimation(&self) -> &BandwidthEstimation {
+        &self.estimation
+    }
+
+    /// update applies the loss reported by a TWCC feedback packet and returns the
+    /// new target bitrate
+    pub fn update(&mut self, twcc: &TransportLayerCc) -> u64 {
+        if let Some(loss) = loss_fraction(twcc) {
+            let estimate = self.estimation.current_estimate_bps as f64;
+            let target = if loss > HIGH_LOSS_FRACTION {
+                estimate * (1.0 - 0.5 * loss)
+            } else if loss < LOW_LOSS_FRACTION {
+                estimate * INCREASE_FACTOR
+            } else {
+                estimate
+            };
+            self.estimation.current_estimate_bps = self.estimation.clamp(target as u64);
+        }
+        self.estimation.current_estimate_bps
+    }
+
+    /// update_remb caps the estimate at the bitrate of a REMB packet and returns
+    /// the new target bitrate
+    pub fn update_remb(&mut self, remb: &ReceiverEstimatedMaximumBitrate) -> u64 {
+        self.estimation.current_estimate_bps = self.estimation.clamp(remb.bitrate as u64);
+        self.estimation.current_estimate_bps
+    }
+}
+
+/// loss_fraction returns the fraction of the packets reported by twcc that were
+/// not received, or None if it reports no packets.
+fn loss_fraction(twcc: &TransportLayerCc) -> Option<f64> {
+    let mut remaining = twcc.packet_status_count as usize;
+    let mut lost = 0;
+    for chunk in &twcc.packet_chunks {
+        if remaining == 0 {
+            break;
+        }
+        match chunk {
+            PacketStatusChunk::RunLengthChunk(c) => {
+                let count = (c.run_length as usize).min(remaining);
+                if c.packet_status_symbol == SymbolTypeTcc::PacketNotReceived {
+                    lost += count;
+                }
+                remaining -= count;
+            }
+            PacketStatusChunk::StatusVectorChunk(c) => {
+                for symbol in c.symbol_list.iter().take(remaining) {
+                    if *symbol == SymbolTypeTcc::PacketNotReceived {
+                        lost += 1;
+                    }
+                    remaining -= 1;
+                }
+            }
+        }
+    }
+
+    let reported = twcc.packet_status_count as usize - remaining;
+    if reported == 0 {
+        None
+    } else {
+        Some(lost as f64 / reported as f64)
+    }
+}
@@ -1,7 +1,7 @@
-/*#[cfg(test)]
+#[cfg(test)]
 mod rtp_sender_test;
 
-use std::sync::atomic::{AtomicBool, Ordering};
+/*use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Weak};
 
 use ice::rand::generate_crypto_random_string;
@@ -79,9 +79,85 @@ impl RTPSenderInternal {
 }
  */
 
-use crate::rtp_transceiver::{PayloadType, SSRC};
+pub mod bandwidth_estimation;
+
+use crate::rtp_transceiver::{PayloadType, RTCRtpEncodingParameters, SSRC};
 use crate::track::track_local::TrackLocal;
+use bandwidth_estimation::{BandwidthEstimation, GoogCongestionControl};
+use ice::rand::generate_crypto_random_string;
+use rtcp::payload_feedbacks::receiver_estimated_maximum_bitrate::ReceiverEstimatedMaximumBitrate;
+use rtcp::transport_feedbacks::temporary_maximum_media_bitrate::{
+    TmmbEntry, TmmbnPacket, TmmbrPacket,
+};
+use rtcp::transport_feedbacks::transport_layer_cc::TransportLayerCc;
 use shared::error::{Error, Result};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// The window over which the current send bitrate is measured.
+pub(crate) const BITRATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Relative change of the target bitrate above which it is reported again.
+pub(crate) const BITRATE_REPORT_THRESHOLD: f64 = 0.10;
+
+/// SendRateLimiter tracks bytes sent during the last BITRATE_WINDOW and rejects
+/// packets that would push the send rate above the lowest of the maximum set with
+/// set_max_bitrate, e.g. from the remote b=TIAS, the maximum of the application's
+/// encoding parameters and the maximum requested by the remote peer with TMMBR.
+#[derive(Default, Debug)]
+pub(crate) struct SendRateLimiter {
+    pub(crate) max_bitrate: Option<u64>,
+    pub(crate) encoding_max_bitrate: Option<u64>,
+    pub(crate) tmmbr_bitrate: Option<u64>,
+    history: VecDeque<(Instant, usize)>,
+    bytes_in_window: usize,
+}
+
+impl SendRateLimiter {
+    fn expire(&mut self, now: Instant) {
+        while let Some(&(sent_at, size)) = self.history.front() {
+            if now.saturating_duration_since(sent_at) < BITRATE_WINDOW {
+                break;
+            }
+            self.bytes_in_window -= size;
+            self.history.pop_front();
+        }
+    }
+
+    /// bitrate returns the bits per second sent during the window ending at now.
+    pub(crate) fn bitrate(&mut self, now: Instant) -> u64 {
+        self.expire(now);
+        self.bytes_in_window as u64 * 8 * 1000 / BITRATE_WINDOW.as_millis() as u64
+    }
+
+    /// limit returns the bitrate packets are currently capped at, if any.
+    pub(crate) fn limit(&self) -> Option<u64> {
+        [
+            self.max_bitrate,
+            self.encoding_max_bitrate,
+            self.tmmbr_bitrate,
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+
+    /// try_send records a packet of size bytes and returns true, or returns false
+    /// without recording it if the packet would exceed the limit.
+    pub(crate) fn try_send(&mut self, now: Instant, size: usize) -> bool {
+        self.expire(now);
+        if let Some(max_bitrate) = self.limit() {
+            let bits =
+                (self.bytes_in_window + size) as u64 * 8 * 1000 / BITRATE_WINDOW.as_millis() as u64;
+            if bits > max_bitrate {
+                return false;
+            }
+        }
+        self.bytes_in_window += size;
+        self.history.push_back((now, size));
+        true
+    }
+}
 
 /// RTPSender allows an application to control how a given Track is encoded and transmitted to a remote peer
 pub struct RTCRtpSender {
@@ -119,6 +195,17 @@ pub struct RTCRtpSender {
     stop_called_signal: Arc<AtomicBool>,*/
     pub(crate) paused: bool,
     //internal: Arc<RTPSenderInternal>,
+    pub(crate) rate_limiter: SendRateLimiter,
+    /// packets dropped because they would have exceeded the max bitrate
+    pub(crate) packets_lost: u64,
+    /// whether the encoding is sent; inactive encodings drop all outgoing RTP
+    pub(crate) active: bool,
+    pub(crate) scale_resolution_down_by: Option<f64>,
+    pub(crate) max_framerate: Option<f64>,
+    /// estimates the send bitrate from TWCC or REMB feedback
+    pub(crate) congestion_control: GoogCongestionControl,
+    /// target bitrate last returned by handle_twcc or handle_remb
+    pub(crate) reported_target_bitrate: u64,
 }
 
 impl std::fmt::Debug for RTCRtpSender {
@@ -213,6 +300,45 @@ impl RTCRtpSender {
         }
     }
     */
+    pub(crate) fn new(
+        receive_mtu: usize,
+        track: Option<Box<dyn TrackLocal + Send + Sync>>,
+        start_paused: bool,
+    ) -> Self {
+        let id = generate_crypto_random_string(
+            32,
+            b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ",
+        );
+        let ssrc = rand::random::<u32>();
+
+        let associated_media_stream_ids = track
+            .as_ref()
+            .map(|track| vec![track.stream_id().to_string()])
+            .unwrap_or_default();
+        Self {
+            track,
+
+            payload_type: 0,
+            ssrc,
+            receive_mtu,
+
+            negotiated: false,
+
+            id,
+            initial_track_id: None,
+            associated_media_stream_ids,
+
+            paused: start_paused,
+            rate_limiter: SendRateLimiter::default(),
+            packets_lost: 0,
+            active: true,
+            scale_resolution_down_by: None,
+            max_framerate: None,
+            congestion_control: GoogCongestionControl::default(),
+            reported_target_bitrate: bandwidth_estimation::DEFAULT_START_BITRATE,
+        }
+    }
+
     pub(crate) fn is_negotiated(&self) -> bool {
         self.negotiated
     }
@@ -234,6 +360,146 @@ impl RTCRtpSender {
         self.paused = paused;
     }
 
+    /// set_max_bitrate caps the send bitrate of this sender, in bits per second.
+    pub fn set_max_bitrate(&mut self, bps: u64) {
+        self.rate_limiter.max_bitrate = Some(bps);
+    }
+
+    /// max_bitrate returns the configured send bitrate cap, in bits per second.
+    pub fn max_bitrate(&self) -> Option<u64> {
+        self.rate_limiter.max_bitrate
+    }
+
+    /// tmmbr_bitrate returns the send bitrate cap last requested by the remote peer
+    /// with TMMBR, in bits per second.
+    pub fn tmmbr_bitrate(&self) -> Option<u64> {
+        self.rate_limiter.tmmbr_bitrate
+    }
+
+    /// current_bitrate returns the bits per second sent during the second ending at now.
+    pub fn current_bitrate(&mut self, now: Instant) -> u64 {
+        self.rate_limiter.bitrate(now)
+    }
+
+    /// packets_lost returns the number of packets dropped by the bitrate cap.
+    pub fn packets_lost(&self) -> u64 {
+        self.packets_lost
+    }
+
+    /// encoding_parameters returns the parameters of the encodings sent by this sender.
+    pub fn encoding_parameters(&self) -> Vec<RTCRtpEncodingParameters> {
+        vec![RTCRtpEncodingParameters {
+            ssrc: self.ssrc,
+            payload_type: self.payload_type,
+            max_bitrate_bps: self.rate_limiter.encoding_max_bitrate,
+            scale_resolution_down_by: self.scale_resolution_down_by,
+            max_framerate: self.max_framerate,
+            active: self.active,
+            ..Default::default()
+        }]
+    }
+
+    /// set_encoding_parameters updates the encodings sent by this sender. There must
+    /// be at least one encoding and every encoding must refer to an SSRC already used
+    /// by the sender. The max bitrate of an encoding applies on top of the one set with
+    /// set_max_bitrate, the lower of the two is enforced.
+    pub fn set_encoding_parameters(&mut self, params: Vec<RTCRtpEncodingParameters>) -> Result<()> {
+        if params.is_empty() {
+            return Err(Error::ErrRTPSenderNoEncodings);
+        }
+        if params.iter().any(|p| p.ssrc != self.ssrc) {
+            return Err(Error::ErrSsrcNotFound);
+        }
+
+        for p in params {
+            self.rate_limiter.encoding_max_bitrate = p.max_bitrate_bps;
+            self.scale_resolution_down_by = p.scale_resolution_down_by;
+            self.max_framerate = p.max_framerate;
+            self.active = p.active;
+        }
+
+        Ok(())
+    }
+
+    /// handle_write accounts for an outgoing RTP packet of size bytes and returns
+    /// false if the packet must be dropped because the encoding is inactive or
+    /// to stay within the max bitrate.
+    pub(crate) fn handle_write(&mut self, now: Instant, size: usize) -> bool {
+        if !self.active {
+            false
+        } else if self.rate_limiter.try_send(now, size) {
+            true
+        } else {
+            self.packets_lost += 1;
+            false
+        }
+    }
+
+    /// handle_tmmbr applies the TMMBR entry for this sender's SSRC, if any, on top of
+    /// the configured max bitrate and returns the TMMBN acknowledging it.
+    pub fn handle_tmmbr(&mut self, tmmbr: &TmmbrPacket) -> Option<TmmbnPacket> {
+        let entry = tmmbr.entries.iter().find(|e| e.ssrc == self.ssrc)?;
+        self.rate_limiter.tmmbr_bitrate = Some(entry.max_total_media_bitrate);
+
+        // the tuple of a TMMBN is owned by the sender of the TMMBR, RFC 5104 Section 4.2.2.1
+        Some(TmmbnPacket {
+            is_notification: true,
+            sender_ssrc: self.ssrc,
+            media_ssrc: 0,
+            entries: vec![TmmbEntry {
+                ssrc: tmmbr.sender_ssrc,
+                ..*entry
+            }],
+        })
+    }
+
+    /// bandwidth_estimation returns the send bitrate estimated from the congestion
+    /// feedback of the remote peer.
+    pub fn bandwidth_estimation(&self) -> &BandwidthEstimation {
+        self.congestion_control.estimation()
+    }
+
+    /// set_bandwidth_estimation restarts the estimation from estimation, e.g. to
+    /// change its bounds.
+    pub fn set_bandwidth_estimation(&mut self, estimation: BandwidthEstimation) {
+        self.congestion_control = GoogCongestionControl::new(estimation);
+        self.reported_target_bitrate = self.congestion_control.estimation().current_estimate_bps;
+    }
+
+    /// current_target_bitrate returns the bits per second the encoder of this
+    /// sender should produce to adapt to the network conditions.
+    pub fn current_target_bitrate(&self) -> u64 {
+        self.congestion_control.estimation().current_estimate_bps
+    }
+
+    /// handle_twcc updates the bandwidth estimation with TWCC feedback and returns
+    /// the new target bitrate if it changed by more than 10% since last returned.
+    pub fn handle_twcc(&mut self, twcc: &TransportLayerCc) -> Option<u64> {
+        let bps = self.congestion_control.update(twcc);
+        self.report_target_bitrate(bps)
+    }
+
+    /// handle_remb updates the bandwidth estimation with the REMB entry for this
+    /// sender's SSRC, if any, and returns the new target bitrate if it changed by
+    /// more than 10% since last returned.
+    pub fn handle_remb(&mut self, remb: &ReceiverEstimatedMaximumBitrate) -> Option<u64> {
+        if !remb.ssrcs.contains(&self.ssrc) {
+            return None;
+        }
+        let bps = self.congestion_control.update_remb(remb);
+        self.report_target_bitrate(bps)
+    }
+
+    fn report_target_bitrate(&mut self, bps: u64) -> Option<u64> {
+        let reported = self.reported_target_bitrate as f64;
+        if (bps as f64 - reported).abs() > reported * BITRATE_REPORT_THRESHOLD {
+            self.reported_target_bitrate = bps;
+            Some(bps)
+        } else {
+            None
+        }
+    }
+
     /*
     /// transport returns the currently-configured DTLSTransport
     /// if one has not yet been configured
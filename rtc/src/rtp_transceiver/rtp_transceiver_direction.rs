@@ -72,6 +72,28 @@ impl fmt::Display for RTCRtpTransceiverDirection {
 }
 
 impl RTCRtpTransceiverDirection {
+    /// to_sdp_attribute returns the SDP direction attribute of this direction.
+    /// Unspecified is written as "sendrecv", the default when no attribute is present.
+    pub fn to_sdp_attribute(&self) -> &'static str {
+        match *self {
+            RTCRtpTransceiverDirection::Sendonly => RTP_TRANSCEIVER_DIRECTION_SENDONLY_STR,
+            RTCRtpTransceiverDirection::Recvonly => RTP_TRANSCEIVER_DIRECTION_RECVONLY_STR,
+            RTCRtpTransceiverDirection::Inactive => RTP_TRANSCEIVER_DIRECTION_INACTIVE_STR,
+            RTCRtpTransceiverDirection::Sendrecv | RTCRtpTransceiverDirection::Unspecified => {
+                RTP_TRANSCEIVER_DIRECTION_SENDRECV_STR
+            }
+        }
+    }
+
+    /// from_sdp_attribute parses an SDP direction attribute, returning None for any
+    /// other attribute.
+    pub fn from_sdp_attribute(s: &str) -> Option<RTCRtpTransceiverDirection> {
+        match RTCRtpTransceiverDirection::from(s) {
+            RTCRtpTransceiverDirection::Unspecified => None,
+            direction => Some(direction),
+        }
+    }
+
     /// reverse indicate the opposite direction
     pub fn reverse(&self) -> RTCRtpTransceiverDirection {
         match *self {
@@ -140,6 +162,37 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_rtp_transceiver_direction_sdp_attribute() {
+        use RTCRtpTransceiverDirection::*;
+
+        let tests = vec![
+            (Sendrecv, "sendrecv", "sendrecv"),
+            (Sendonly, "sendonly", "recvonly"),
+            (Recvonly, "recvonly", "sendonly"),
+            (Inactive, "inactive", "inactive"),
+        ];
+
+        for (d, expected_attribute, expected_reversed_attribute) in tests {
+            assert_eq!(d.to_sdp_attribute(), expected_attribute);
+            assert_eq!(d.reverse().to_sdp_attribute(), expected_reversed_attribute);
+            assert_eq!(
+                RTCRtpTransceiverDirection::from_sdp_attribute(expected_attribute),
+                Some(d)
+            );
+            assert_eq!(
+                RTCRtpTransceiverDirection::from_sdp_attribute(expected_reversed_attribute),
+                Some(d.reverse())
+            );
+        }
+
+        assert_eq!(Unspecified.to_sdp_attribute(), "sendrecv");
+        assert_eq!(
+            RTCRtpTransceiverDirection::from_sdp_attribute("rtcp-mux"),
+            None
+        );
+    }
+
     #[test]
     fn test_rtp_transceiver_has_send() {
         let tests = vec![
@@ -17,6 +17,7 @@ use crate::transport::dtls_transport::dtls_fingerprint::RTCDtlsFingerprint;
 use crate::transport::sctp_transport::RTCSctpTransport;
 
 mod serialize;
+pub mod stats_accumulator;
 pub mod stats_collector;
 
 #[derive(Debug, Serialize)]
@@ -233,6 +234,11 @@ pub struct ICECandidateStats {
     pub priority: u32,
     pub relay_protocol: String,
     pub url: String,
+    // Non-canon
+    pub packets_sent: u64,
+    pub packets_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
 }
 
 impl ICECandidateStats {
@@ -249,6 +255,10 @@ impl ICECandidateStats {
             stats_type,
             timestamp: stats.timestamp,
             url: stats.url,
+            packets_sent: stats.packets_sent,
+            packets_received: stats.packets_received,
+            bytes_sent: stats.bytes_sent,
+            bytes_received: stats.bytes_received,
         }
     }
 }
@@ -263,14 +273,16 @@ pub struct ICETransportStats {
     pub stats_type: RTCStatsType,
     pub id: String,
     // Non-canon
+    pub pending_binding_requests_count: usize,
     //TODO: pub bytes_received: usize,
     //TODO: pub bytes_sent: usize,
 }
 
 impl ICETransportStats {
-    pub(crate) fn new(id: String, _agent: &Agent) -> Self {
+    pub(crate) fn new(id: String, agent: &Agent) -> Self {
         ICETransportStats {
             id,
+            pending_binding_requests_count: agent.pending_binding_requests_count(),
             //TODO: bytes_received: agent.get_bytes_received(),
             //TODO: bytes_sent: agent.get_bytes_sent(),
             stats_type: RTCStatsType::Transport,
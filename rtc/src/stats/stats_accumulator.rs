@@ -0,0 +1,137 @@
+use rtcp::extended_report::{LossRLEReportBlock, RLEReportBlockBuilder};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::rtp_transceiver::SSRC;
+
+/// InboundRtpStreamAccumulator counts the RTP received from a remote source
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InboundRtpStreamAccumulator {
+    pub packets_received: u64,
+    pub bytes_received: u64,
+    /// RTP timestamp of the latest packet received
+    pub last_rtp_timestamp: Option<u32>,
+    /// delay the playout of this stream needs to be synchronized with the other
+    /// media of its MediaStream, from the latest lip-sync estimate
+    pub estimated_playout_delay: Option<Duration>,
+}
+
+/// OutboundRtpStreamAccumulator counts the RTP sent by a local sender and the round
+/// trip times measured from the reports the remote peer sends about it
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct OutboundRtpStreamAccumulator {
+    pub packets_sent: u64,
+    pub bytes_sent: u64,
+    pub header_bytes_sent: u64,
+    /// latest round trip time in seconds
+    pub round_trip_time: Option<f64>,
+    /// sum of the round trip times in seconds
+    pub total_round_trip_time: f64,
+    pub round_trip_time_measurements: u64,
+}
+
+/// RTCStatsAccumulator keeps the state of the remote sources RTP is received from,
+/// keyed by SSRC, until they leave, and of the local senders RTP is sent by.
+#[derive(Debug, Default)]
+pub struct RTCStatsAccumulator {
+    inbound_streams: HashMap<SSRC, InboundRtpStreamAccumulator>,
+    outbound_streams: HashMap<SSRC, OutboundRtpStreamAccumulator>,
+    loss_rle_builders: HashMap<SSRC, RLEReportBlockBuilder>,
+}
+
+impl RTCStatsAccumulator {
+    pub(crate) fn new() -> Self {
+        RTCStatsAccumulator {
+            ..Default::default()
+        }
+    }
+
+    /// on_rtp_received accounts the RTP packet sequence_number with timestamp of
+    /// payload_len bytes received from ssrc
+    pub(crate) fn on_rtp_received(
+        &mut self,
+        ssrc: SSRC,
+        sequence_number: u16,
+        timestamp: u32,
+        payload_len: usize,
+    ) {
+        let stream = self.inbound_streams.entry(ssrc).or_default();
+        stream.packets_received += 1;
+        stream.bytes_received += payload_len as u64;
+        stream.last_rtp_timestamp = Some(timestamp);
+
+        if let Some(builder) = self.loss_rle_builders.get_mut(&ssrc) {
+            builder.add_packet(sequence_number, true);
+        }
+    }
+
+    /// set_estimated_playout_delay records the playout delay of the remote source ssrc
+    /// estimated for lip-sync
+    pub(crate) fn set_estimated_playout_delay(&mut self, ssrc: SSRC, delay: Duration) {
+        if let Some(stream) = self.inbound_streams.get_mut(&ssrc) {
+            stream.estimated_playout_delay = Some(delay);
+        }
+    }
+
+    /// add_loss_rle starts recording the packets received from the remote source ssrc
+    /// into Loss RLE report blocks (RFC 3611 Section 4.1)
+    pub(crate) fn add_loss_rle(&mut self, ssrc: SSRC) {
+        self.loss_rle_builders
+            .entry(ssrc)
+            .or_insert_with(|| RLEReportBlockBuilder::new(ssrc));
+    }
+
+    /// take_loss_rle_reports returns a Loss RLE report block for each remote source
+    /// RTP was received from since the last call, and starts the next intervals
+    pub(crate) fn take_loss_rle_reports(&mut self) -> Vec<LossRLEReportBlock> {
+        let mut reports = vec![];
+        for (ssrc, builder) in self.loss_rle_builders.iter_mut() {
+            let report = builder.build();
+            if report.begin_seq != report.end_seq {
+                reports.push(report);
+                *builder = RLEReportBlockBuilder::new(*ssrc);
+            }
+        }
+        reports
+    }
+
+    /// on_rtp_sent accounts an RTP packet with a header of header_len bytes and
+    /// payload_len bytes of payload sent by ssrc
+    pub(crate) fn on_rtp_sent(&mut self, ssrc: SSRC, header_len: usize, payload_len: usize) {
+        let stream = self.outbound_streams.entry(ssrc).or_default();
+        stream.packets_sent += 1;
+        stream.header_bytes_sent += header_len as u64;
+        stream.bytes_sent += payload_len as u64;
+    }
+
+    /// on_round_trip_time accounts the round trip time measured from a reception
+    /// report about the local sender ssrc
+    pub(crate) fn on_round_trip_time(&mut self, ssrc: SSRC, rtt: Duration) {
+        let stream = self.outbound_streams.entry(ssrc).or_default();
+        stream.round_trip_time = Some(rtt.as_secs_f64());
+        stream.total_round_trip_time += rtt.as_secs_f64();
+        stream.round_trip_time_measurements += 1;
+    }
+
+    /// outbound_stream returns the accumulated state of the local sender ssrc
+    pub fn outbound_stream(&self, ssrc: SSRC) -> Option<&OutboundRtpStreamAccumulator> {
+        self.outbound_streams.get(&ssrc)
+    }
+
+    /// inbound_stream returns the accumulated state of the remote source ssrc
+    pub fn inbound_stream(&self, ssrc: SSRC) -> Option<&InboundRtpStreamAccumulator> {
+        self.inbound_streams.get(&ssrc)
+    }
+
+    /// cleanup_stale_streams drops the state of every remote source not listed in
+    /// active_ssrcs, so RTP received from them later starts a new stream
+    pub fn cleanup_stale_streams(&mut self, active_ssrcs: &[SSRC]) {
+        self.inbound_streams
+            .retain(|ssrc, _| active_ssrcs.contains(ssrc));
+    }
+
+    /// active_ssrcs returns the SSRC of every remote source with state
+    pub fn active_ssrcs(&self) -> Vec<SSRC> {
+        self.inbound_streams.keys().copied().collect()
+    }
+}
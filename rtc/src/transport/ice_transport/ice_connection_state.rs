@@ -1,3 +1,5 @@
+use crate::transport::ice_transport::ice_transport_state::RTCIceTransportState;
+use ice::state::ConnectionState;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
@@ -88,6 +90,27 @@ impl From<u8> for RTCIceConnectionState {
     }
 }
 
+impl From<RTCIceTransportState> for RTCIceConnectionState {
+    fn from(raw: RTCIceTransportState) -> Self {
+        match raw {
+            RTCIceTransportState::New => RTCIceConnectionState::New,
+            RTCIceTransportState::Checking => RTCIceConnectionState::Checking,
+            RTCIceTransportState::Connected => RTCIceConnectionState::Connected,
+            RTCIceTransportState::Completed => RTCIceConnectionState::Completed,
+            RTCIceTransportState::Failed => RTCIceConnectionState::Failed,
+            RTCIceTransportState::Disconnected => RTCIceConnectionState::Disconnected,
+            RTCIceTransportState::Closed => RTCIceConnectionState::Closed,
+            _ => RTCIceConnectionState::Unspecified,
+        }
+    }
+}
+
+impl From<ConnectionState> for RTCIceConnectionState {
+    fn from(raw: ConnectionState) -> Self {
+        RTCIceTransportState::from(raw).into()
+    }
+}
+
 impl fmt::Display for RTCIceConnectionState {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let s = match *self {
@@ -153,4 +176,32 @@ mod test {
             assert_eq!(state.to_string(), expected_string)
         }
     }
+
+    #[test]
+    fn test_ice_connection_state_from_agent_state() {
+        let tests = vec![
+            (
+                ConnectionState::Unspecified,
+                RTCIceConnectionState::Unspecified,
+            ),
+            (ConnectionState::New, RTCIceConnectionState::New),
+            (ConnectionState::Checking, RTCIceConnectionState::Checking),
+            (ConnectionState::Connected, RTCIceConnectionState::Connected),
+            (ConnectionState::Completed, RTCIceConnectionState::Completed),
+            (ConnectionState::Failed, RTCIceConnectionState::Failed),
+            (
+                ConnectionState::Disconnected,
+                RTCIceConnectionState::Disconnected,
+            ),
+            (ConnectionState::Closed, RTCIceConnectionState::Closed),
+        ];
+
+        for (agent_state, expected_state) in tests {
+            assert_eq!(
+                RTCIceConnectionState::from(agent_state),
+                expected_state,
+                "testCase: {expected_state}",
+            );
+        }
+    }
 }
@@ -62,57 +62,14 @@ impl RTCIceGatherer {
         }
     }
 
-    pub fn gather(&self) -> Result<()> {
-        /*TODO:/// Gather ICE candidates.
-        self.create_agent().await?;
-        self.set_state(RTCIceGathererState::Gathering).await;
-
-        if let Some(agent) = self.get_agent().await {
-            let state = Arc::clone(&self.state);
-            let on_local_candidate_handler = Arc::clone(&self.on_local_candidate_handler);
-            let on_state_change_handler = Arc::clone(&self.on_state_change_handler);
-            let on_gathering_complete_handler = Arc::clone(&self.on_gathering_complete_handler);
-
-            agent.on_candidate(Box::new(
-                move |candidate: Option<Arc<dyn Candidate + Send + Sync>>| {
-                    let state_clone = Arc::clone(&state);
-                    let on_local_candidate_handler_clone = Arc::clone(&on_local_candidate_handler);
-                    let on_state_change_handler_clone = Arc::clone(&on_state_change_handler);
-                    let on_gathering_complete_handler_clone =
-                        Arc::clone(&on_gathering_complete_handler);
-
-                    Box::pin(async move {
-                        if let Some(cand) = candidate {
-                            if let Some(handler) = &*on_local_candidate_handler_clone.load() {
-                                let mut f = handler.lock().await;
-                                f(Some(RTCIceCandidate::from(&cand))).await;
-                            }
-                        } else {
-                            state_clone
-                                .store(RTCIceGathererState::Complete as u8, Ordering::SeqCst);
-
-                            if let Some(handler) = &*on_state_change_handler_clone.load() {
-                                let mut f = handler.lock().await;
-                                f(RTCIceGathererState::Complete).await;
-                            }
-
-                            if let Some(handler) = &*on_gathering_complete_handler_clone.load() {
-                                let mut f = handler.lock().await;
-                                f().await;
-                            }
-
-                            if let Some(handler) = &*on_local_candidate_handler_clone.load() {
-                                let mut f = handler.lock().await;
-                                f(None).await;
-                            }
-                        }
-                    })
-                },
-            ));
-
-            agent.gather_candidates()?;
-        }*/
-        Ok(())
+    /// gather starts gathering the relay candidates of the TURN servers, which are
+    /// announced through the agent's CandidateGathered events once allocated.
+    pub fn gather(&mut self) -> Result<()> {
+        if self.state != RTCIceGathererState::New {
+            return Ok(());
+        }
+        self.set_state(RTCIceGathererState::Gathering);
+        self.agent.gather_relay_candidates()
     }
 
     /// Close prunes all local candidates, and closes the ports.
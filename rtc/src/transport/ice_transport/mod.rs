@@ -1,11 +1,14 @@
 //use ice::candidate::Candidate;
 //use ice::state::ConnectionState;
+use ice::candidate::candidate_pair::CandidatePairState;
+use ice::url::Url;
 use ice::Credentials;
 use ice_candidate::RTCIceCandidate;
 use ice_candidate_pair::RTCIceCandidatePair;
 use ice_gatherer::RTCIceGatherer;
 use ice_role::RTCIceRole;
 use std::collections::VecDeque;
+use std::net::{IpAddr, SocketAddr};
 
 //use crate::transports::ice_transport::ice_parameters::RTCIceParameters;
 use crate::messages::RTCMessage;
@@ -37,6 +40,13 @@ pub mod ice_transport_state;
 pub enum IceTransportEvent {
     OnConnectionStateChange(RTCIceTransportState),
     OnSelectedCandidatePairChange(Box<RTCIceCandidatePair>),
+    OnRemoteCandidateNeedsResolution(String, Box<RTCIceCandidate>),
+    OnCandidatePairStateChange(Box<RTCIceCandidatePair>, CandidatePairState),
+    OnNominationChange(Box<RTCIceCandidatePair>),
+    OnIceRestartCompleted,
+    OnCandidateGathered(Box<RTCIceCandidate>),
+    OnGatheringFailed(Url),
+    OnUrlNeedsResolution(Url),
 }
 
 /// ICETransport allows an application access to information about the ICE
@@ -188,12 +198,25 @@ impl RTCIceTransport {
                 .clone(),
             self.gatherer.setting_engine.candidates.password.clone(),
         );
-        self.gatherer.agent.restart(ufrag, pwd, false)?;
+        self.gatherer.agent.restart(ufrag, pwd, false, false)?;
 
         //TODO: self.gatherer.gather()
         Ok(())
     }
 
+    /// restart_in_band restarts the ICE agent with new local credentials and resumes
+    /// connectivity checks with the current remote credentials and candidates.
+    pub(crate) fn restart_in_band(&mut self, ufrag: String, pwd: String) -> Result<()> {
+        self.gatherer.agent.restart_in_band(ufrag, pwd)
+    }
+
+    /// close closes the underlying ICE agent, which then refuses inbound traffic.
+    /// Its ConnectionStateChange(Closed) event is reported through poll_event as
+    /// OnConnectionStateChange(RTCIceTransportState::Closed).
+    pub fn close(&mut self) -> Result<()> {
+        self.gatherer.agent.close()
+    }
+
     /// Stop irreversibly stops the ICETransport.
     pub fn stop(&mut self) -> Result<()> {
         self.set_state(RTCIceTransportState::Closed);
@@ -242,15 +265,45 @@ impl RTCIceTransport {
         Ok(())
     }
 
+    /// set_remote_candidate_address updates the address of the remote candidate at
+    /// index once its mDNS name has been resolved.
+    pub fn set_remote_candidate_address(
+        &mut self,
+        index: usize,
+        resolved_addr: SocketAddr,
+    ) -> Result<()> {
+        self.gatherer
+            .agent
+            .set_remote_candidate_address(index, resolved_addr)
+    }
+
+    /// resolve_remote_candidate sets the address of the remote candidates announced
+    /// with the mDNS name once it has been resolved to ip.
+    pub fn resolve_remote_candidate(&mut self, name: &str, ip: IpAddr) -> Result<()> {
+        self.gatherer.agent.resolve_remote_candidate(name, ip)
+    }
+
     /// State returns the current ice transport state.
     pub fn state(&self) -> RTCIceTransportState {
         self.state
     }
 
+    /// agent_state returns the current state of the underlying ICE agent, which
+    /// state only follows once the agent's events have been processed.
+    pub fn agent_state(&self) -> RTCIceTransportState {
+        RTCIceTransportState::from(self.gatherer.agent.state())
+    }
+
     pub(crate) fn set_state(&mut self, s: RTCIceTransportState) {
         self.state = s;
     }
 
+    /// pending_binding_requests_count returns the number of outbound binding requests
+    /// that are still awaiting a response
+    pub fn pending_binding_requests_count(&self) -> usize {
+        self.gatherer.agent.pending_binding_requests_count()
+    }
+
     pub(crate) fn collect_stats(&self, collector: &mut StatsCollector) {
         let stats = ICETransportStats::new("ice_transport".to_string(), &self.gatherer.agent);
 
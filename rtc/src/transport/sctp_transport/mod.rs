@@ -1,11 +1,13 @@
-//TODO:#[cfg(test)]
-//TODO:mod sctp_transport_test;
+#[cfg(test)]
+mod sctp_transport_test;
 
 pub mod sctp_transport_capabilities;
 pub mod sctp_transport_state;
 
 //use datachannel::data_channel::DataChannel;
 //use datachannel::message::message_channel_open::ChannelType;
+use bytes::{Bytes, BytesMut};
+use datachannel::message::Message;
 use sctp::{Association, AssociationHandle};
 use sctp_transport_state::RTCSctpTransportState;
 use std::collections::{HashMap, HashSet, VecDeque};
@@ -13,8 +15,9 @@ use std::sync::Arc;
 
 use crate::api::setting_engine::SettingEngine;
 //use crate::transports::data_channel::data_channel_parameters::DataChannelParameters;
+use crate::data_channel::data_channel_message::{DataChannelMessage, RTCDataChannelMessage};
 use crate::data_channel::data_channel_state::RTCDataChannelState;
-use crate::data_channel::RTCDataChannel;
+use crate::data_channel::{RTCDataChannel, RTCDataChannelId};
 use crate::transport::dtls_transport::dtls_role::DTLSRole;
 //use crate::transports::dtls_transport::*;
 use crate::messages::RTCMessage;
@@ -23,6 +26,7 @@ use crate::stats::PeerConnectionStats;
 use crate::stats::StatsReportType::PeerConnection;
 use crate::transport::sctp_transport::sctp_transport_capabilities::SCTPTransportCapabilities;
 use shared::error::*;
+use shared::marshal::Unmarshal;
 use shared::Transmit;
 
 const SCTP_MAX_CHANNELS: u16 = u16::MAX;
@@ -32,6 +36,14 @@ pub enum SctpTransportEvent {
     OnError,
     OnDataChannel(Box<RTCDataChannel>),
     OnDataChannelOpened(Box<RTCDataChannel>),
+    OnDataChannelClosed(RTCDataChannelId),
+    OnDataChannelMessage(RTCDataChannelId, RTCDataChannelMessage),
+    OnStateChange(RTCSctpTransportState),
+    OnDataChannelProtocolMismatch {
+        label: String,
+        local: String,
+        remote: String,
+    },
 }
 
 /// SCTPTransport provides details about the SCTP transport.
@@ -60,6 +72,11 @@ pub struct RTCSctpTransport {
     pub(crate) data_channels_opened: u32,
     pub(crate) data_channels_requested: u32,
     data_channels_accepted: u32,
+    // Streams with an outgoing reset request awaiting the peer's response
+    pub(crate) pending_resets: HashSet<u16>,
+    // Undelivered bytes of messages being delivered in partial pieces, and whether
+    // a piece of the message was already delivered
+    partial_buffers: HashMap<RTCDataChannelId, (BytesMut, bool)>,
 
     setting_engine: Arc<SettingEngine>,
 
@@ -93,6 +110,20 @@ impl RTCSctpTransport {
     /// Start the SCTPTransport. Since both local and remote parties must mutually
     /// create an SCTPTransport, SCTP SO (Simultaneous Open) is used to establish
     /// a connection over SCTP.
+    pub fn start(&mut self, _remote_caps: SCTPTransportCapabilities) -> Result<()> {
+        if self.is_started {
+            return Ok(());
+        }
+        self.is_started = true;
+
+        // the transport is created in the connecting state, so it is announced here
+        // rather than through state_change
+        self.state = RTCSctpTransportState::Connecting;
+        self.events
+            .push_back(SctpTransportEvent::OnStateChange(self.state));
+        Ok(())
+    }
+
     /*TODO:pub async fn start(&self, _remote_caps: SCTPTransportCapabilities) -> Result<()> {
         if self.is_started.load(Ordering::SeqCst) {
             return Ok(());
@@ -152,10 +183,20 @@ impl RTCSctpTransport {
 
     /// Stop stops the SCTPTransport
     pub fn stop(&mut self) -> Result<()> {
-        self.state = RTCSctpTransportState::Closed;
+        self.state_change(RTCSctpTransportState::Closed);
         Ok(())
     }
 
+    /// state_change updates the state of the SCTPTransport and queues an
+    /// OnStateChange event if it changed
+    pub(crate) fn state_change(&mut self, state: RTCSctpTransportState) {
+        if self.state != state {
+            self.state = state;
+            self.events
+                .push_back(SctpTransportEvent::OnStateChange(state));
+        }
+    }
+
     /*todo: fn accept_data_channels(param: AcceptDataChannelParams) {
         let dcs = param.data_channels.lock().await;
         let mut existing_data_channels = Vec::new();
@@ -348,6 +389,20 @@ impl RTCSctpTransport {
         self.sctp_associations.get(association_handle)
     }
 
+    /// reset_stream queues an SCTP Outgoing SSN Reset Request for the given stream.
+    /// OnDataChannelClosed is emitted once the peer has performed the reset.
+    pub fn reset_stream(&mut self, stream_id: u16) -> Result<()> {
+        for association in self.sctp_associations.values_mut() {
+            if let Ok(mut stream) = association.stream(stream_id) {
+                stream.stop()?;
+                self.pending_resets.insert(stream_id);
+                return Ok(());
+            }
+        }
+
+        Err(Error::ErrStreamNotExisted)
+    }
+
     pub(crate) fn data_channels_accepted(&self) -> u32 {
         self.data_channels_accepted
     }
@@ -359,4 +414,101 @@ impl RTCSctpTransport {
     pub(crate) fn data_channels_requested(&self) -> u32 {
         self.data_channels_requested
     }
+
+    /// enable_partial_delivery makes incoming messages of the DataChannel with the
+    /// given id that are larger than threshold bytes be delivered as a sequence of
+    /// RTCDataChannelMessage::Partial pieces of at most threshold bytes each, as
+    /// their DATA chunks arrive, instead of as a single Complete message.
+    pub(crate) fn enable_partial_delivery(
+        &mut self,
+        id: RTCDataChannelId,
+        threshold: usize,
+    ) -> Result<()> {
+        let dc = self
+            .data_channels
+            .values_mut()
+            .find(|dc| dc.id == id)
+            .ok_or(Error::ErrDataChannelNotExisted)?;
+        dc.partial_delivery_threshold = Some(threshold.max(1));
+
+        for conn in self.sctp_associations.values_mut() {
+            if let Ok(mut stream) = conn.stream(id) {
+                stream.set_partial_delivery(true)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// deliver_partial appends fragments of an incoming message on stream id and
+    /// emits OnDataChannelMessage Partial events of threshold bytes each. The rest of
+    /// the message is emitted with is_last set once is_message_end, or as a Complete
+    /// message if it never exceeded the threshold.
+    pub(crate) fn deliver_partial(
+        &mut self,
+        id: RTCDataChannelId,
+        threshold: usize,
+        is_string: bool,
+        fragments: &[Bytes],
+        is_message_end: bool,
+    ) {
+        let (buffer, delivered) = self.partial_buffers.entry(id).or_default();
+        for fragment in fragments {
+            buffer.extend_from_slice(fragment);
+        }
+        while buffer.len() > threshold {
+            let data = buffer.split_to(threshold);
+            *delivered = true;
+            self.events
+                .push_back(SctpTransportEvent::OnDataChannelMessage(
+                    id,
+                    RTCDataChannelMessage::Partial {
+                        is_string,
+                        data,
+                        is_last: false,
+                    },
+                ));
+        }
+
+        if is_message_end {
+            if let Some((data, delivered)) = self.partial_buffers.remove(&id) {
+                let message = if delivered {
+                    RTCDataChannelMessage::Partial {
+                        is_string,
+                        data,
+                        is_last: true,
+                    }
+                } else {
+                    RTCDataChannelMessage::Complete(DataChannelMessage {
+                        is_string,
+                        data: data.freeze(),
+                    })
+                };
+                self.events
+                    .push_back(SctpTransportEvent::OnDataChannelMessage(id, message));
+            }
+        }
+    }
+
+    /// check_data_channel_open compares a DATA_CHANNEL_OPEN message received from the
+    /// peer with the local DataChannel of the same label, if any, and emits
+    /// OnDataChannelProtocolMismatch when they were created with different protocols.
+    pub(crate) fn check_data_channel_open(&mut self, payload: &[u8]) {
+        let mut buf = payload;
+        let Ok(Message::DataChannelOpen(open)) = Message::unmarshal(&mut buf) else {
+            return;
+        };
+
+        let label = String::from_utf8_lossy(&open.label);
+        let remote = String::from_utf8_lossy(&open.protocol);
+        if let Some(dc) = self.data_channels.get(label.as_ref()) {
+            if dc.protocol() != remote {
+                self.events
+                    .push_back(SctpTransportEvent::OnDataChannelProtocolMismatch {
+                        label: label.into_owned(),
+                        local: dc.protocol().to_owned(),
+                        remote: remote.into_owned(),
+                    });
+            }
+        }
+    }
 }
@@ -1,6 +1,11 @@
-use std::sync::atomic::AtomicU16;
-
 use super::*;
+use crate::messages::DTLSMessage;
+use shared::handler::RTCHandler;
+use shared::{Protocol, TransportContext};
+use std::net::SocketAddr;
+use std::time::Instant;
+
+/*TODO:use std::sync::atomic::AtomicU16;
 
 #[tokio::test]
 async fn test_generate_data_channel_id() -> Result<()> {
@@ -41,3 +46,188 @@ async fn test_generate_data_channel_id() -> Result<()> {
 
     Ok(())
 }
+*/
+
+#[test]
+fn test_partial_delivery() -> Result<()> {
+    let mut sctp_transport = RTCSctpTransport::new(Arc::new(SettingEngine::default()));
+    sctp_transport.data_channels.insert(
+        "dc".to_owned(),
+        RTCDataChannel {
+            id: 1,
+            ..Default::default()
+        },
+    );
+    assert!(sctp_transport.enable_partial_delivery(2, 100).is_err());
+    sctp_transport.enable_partial_delivery(1, 100)?;
+    assert_eq!(
+        sctp_transport.data_channels["dc"].partial_delivery_threshold,
+        Some(100)
+    );
+
+    // a 300-byte binary message arriving in two reads of DATA chunks
+    let message = Bytes::from(vec![0xAAu8; 300]);
+    sctp_transport.deliver_partial(1, 100, false, &[message.slice(..150)], false);
+    sctp_transport.deliver_partial(1, 100, false, &[message.slice(150..)], true);
+    // a text message within the threshold is delivered whole
+    sctp_transport.deliver_partial(1, 100, true, &[Bytes::from_static(b"hello")], true);
+
+    let mut partials = vec![];
+    let mut completes = vec![];
+    while let Some(event) = sctp_transport.events.pop_front() {
+        match event {
+            SctpTransportEvent::OnDataChannelMessage(
+                id,
+                RTCDataChannelMessage::Partial {
+                    is_string,
+                    data,
+                    is_last,
+                },
+            ) => {
+                assert_eq!(id, 1);
+                partials.push((is_string, data.len(), is_last));
+            }
+            SctpTransportEvent::OnDataChannelMessage(id, RTCDataChannelMessage::Complete(m)) => {
+                assert_eq!(id, 1);
+                completes.push((m.is_string, m.data));
+            }
+            _ => {}
+        }
+    }
+    assert_eq!(
+        partials,
+        vec![(false, 100, false), (false, 100, false), (false, 100, true)]
+    );
+    assert_eq!(completes, vec![(true, Bytes::from_static(b"hello"))]);
+
+    Ok(())
+}
+
+#[test]
+fn test_sctp_transport_data_channel_protocol_mismatch() -> Result<()> {
+    use datachannel::message::message_channel_open::{ChannelType, DataChannelOpen};
+    use shared::marshal::Marshal;
+
+    let mut sctp_transport = RTCSctpTransport::new(Arc::new(SettingEngine::default()));
+    sctp_transport.data_channels.insert(
+        "dc".to_owned(),
+        RTCDataChannel {
+            id: 1,
+            protocol: "local-proto".to_owned(),
+            ..Default::default()
+        },
+    );
+
+    let open = |label: &str, protocol: &str| -> Result<BytesMut> {
+        Message::DataChannelOpen(DataChannelOpen {
+            channel_type: ChannelType::Reliable,
+            priority: 0,
+            reliability_parameter: 0,
+            label: label.as_bytes().to_vec(),
+            protocol: protocol.as_bytes().to_vec(),
+        })
+        .marshal()
+    };
+
+    // matching protocol and unknown label are not reported
+    sctp_transport.check_data_channel_open(&open("dc", "local-proto")?);
+    sctp_transport.check_data_channel_open(&open("other", "remote-proto")?);
+    assert!(sctp_transport.events.is_empty());
+
+    sctp_transport.check_data_channel_open(&open("dc", "remote-proto")?);
+    match sctp_transport.events.pop_front() {
+        Some(SctpTransportEvent::OnDataChannelProtocolMismatch {
+            label,
+            local,
+            remote,
+        }) => {
+            assert_eq!(label, "dc");
+            assert_eq!(local, "local-proto");
+            assert_eq!(remote, "remote-proto");
+        }
+        event => panic!("expected OnDataChannelProtocolMismatch, got {event:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_sctp_transport_state_change_events() -> Result<()> {
+    let mut sctp_transport = RTCSctpTransport::new(Arc::new(SettingEngine::default()));
+
+    let remote_caps = sctp_transport.get_capabilities();
+    sctp_transport.start(remote_caps)?;
+    // starting twice does not announce the transport again
+    sctp_transport.start(remote_caps)?;
+    sctp_transport.state_change(RTCSctpTransportState::Connected);
+    sctp_transport.state_change(RTCSctpTransportState::Connected);
+    sctp_transport.stop()?;
+
+    let mut states = vec![];
+    while let Some(event) = sctp_transport.events.pop_front() {
+        if let SctpTransportEvent::OnStateChange(state) = event {
+            states.push(state);
+        }
+    }
+    assert_eq!(
+        states,
+        vec![
+            RTCSctpTransportState::Connecting,
+            RTCSctpTransportState::Connected,
+            RTCSctpTransportState::Closed,
+        ]
+    );
+    assert_eq!(sctp_transport.state(), RTCSctpTransportState::Closed);
+
+    Ok(())
+}
+
+#[test]
+fn test_sctp_transport_starts_on_new_association() -> Result<()> {
+    let local_addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+    let peer_addr: SocketAddr = "127.0.0.1:5001".parse().unwrap();
+    let endpoint_config = Arc::new(sctp::EndpointConfig::default());
+
+    let mut sctp_transport = RTCSctpTransport::new(Arc::new(SettingEngine::default()));
+    sctp_transport.sctp_endpoint = Some(sctp::Endpoint::new(
+        local_addr,
+        Protocol::UDP,
+        Arc::clone(&endpoint_config),
+        Some(Arc::new(sctp::ServerConfig::default())),
+    ));
+
+    let mut peer = sctp::Endpoint::new(peer_addr, Protocol::UDP, endpoint_config, None);
+    let (_, mut peer_association) = peer
+        .connect(sctp::ClientConfig::default(), local_addr)
+        .expect("connect should succeed");
+
+    // the peer's INIT creates the association
+    let now = Instant::now();
+    while let Some(transmit) = peer_association.poll_transmit(now) {
+        if let sctp::Payload::RawEncode(contents) = transmit.message {
+            for content in contents {
+                sctp_transport.handle_read(Transmit {
+                    now,
+                    transport: TransportContext {
+                        local_addr,
+                        peer_addr,
+                        protocol: Protocol::UDP,
+                        ecn: None,
+                    },
+                    message: RTCMessage::Dtls(DTLSMessage::Raw(BytesMut::from(&content[..]))),
+                })?;
+            }
+        }
+    }
+
+    let mut states = vec![];
+    while let Some(event) = sctp_transport.events.pop_front() {
+        if let SctpTransportEvent::OnStateChange(state) = event {
+            states.push(state);
+        }
+    }
+    assert_eq!(states, vec![RTCSctpTransportState::Connecting]);
+    assert_eq!(sctp_transport.sctp_associations.len(), 1);
+
+    Ok(())
+}